@@ -1,40 +1,74 @@
 use super::command::{Command, ValueEnum};
-use anyhow::Result;
+use anyhow::{ensure, Context, Result};
 use clap::{
     builder::{PossibleValuesParser, TypedValueParser as _},
     Parser,
 };
-use flume::unbounded;
+use flume::{unbounded, Receiver};
 use itertools::{self, Itertools};
-use log::info;
+use log::{info, warn};
 use noodles::{
     bam::Writer as BamWriter,
     bgzf,
     bgzf::writer::CompressionLevel,
-    sam::header::{
-        record::value::{
-            map::{Program, ReferenceSequence},
-            Map,
+    sam::{
+        alignment::Record as SamRecord,
+        header::{
+            record::value::{
+                map::{Program, ReadGroup as SamReadGroup, ReferenceSequence},
+                Map,
+            },
+            Header as SamHeader,
         },
-        Header as SamHeader,
+        record::data::field::Value,
     },
 };
 use proglog::{CountFormatterKind, ProgLogBuilder};
+use seq_io::fastq::{OwnedRecord as FastqOwnedRecord, Record as FastqRecord};
 use std::{
-    env, io, io::Write, num::NonZeroUsize, path::PathBuf, sync::Arc, thread::JoinHandle,
-    time::Duration,
+    collections::HashMap,
+    env,
+    ffi::OsString,
+    fs::File,
+    io,
+    io::{BufWriter, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 use stitch::{
     align::{
+        adapters::{AdapterSplitter, ADAPTER_TAG, SEGMENT_TAG},
+        config::AlignerConfig,
+        coverage::CoverageTrack,
+        dedup::AlignmentCache,
+        duplex::{DuplexPairer, DUPLEX_TAG},
+        filter::AlignmentFilter,
         io::{
-            FastqGroupingIterator, FastqThreadReader, OutputMessage, OutputResult,
+            FastqGroupingIterator, FastqThreadReader, OutputMessage, OutputResult, ReadInput,
             READER_CHANNEL_NUM_CHUNKS,
         },
-        AlignmentMode, Builder, PrimaryPickingStrategy,
+        jump_sites::JumpSites,
+        metrics::{MetricsFormat, MetricsSummary, MetricsWriter, ReadMetrics, Stage},
+        primers::{PrimerMode, PrimerTrimmer, PRIMER_TAG},
+        qc::{QcCollector, QcReport},
+        read_group::{ReadGroup, READ_GROUP_TAG},
+        shard::{merge_shards, Checkpoint, OutputOrder, Shard, ShardWriter},
+        splice::SpliceAnnotation,
+        strategy::AutoStrategy,
+        subsample::{Subsample, DEFAULT_SEED},
+        umi::{comment_tag, UmiSource, FAMILY_SIZE_TAG, UMI_TAG},
+        AlignmentLength, AlignmentMode, Builder, ClipPolicy, CsStyle, PrimaryPickingStrategy,
+        ProgressCallback, ScoringPreset, TracebackLayout, SKIP_TAG,
     },
     util::{
-        target_seq,
-        version::{built_info, built_info::VERSION},
+        contig_set::ContigSet,
+        genbank::{self, AnnotatedRecord, FeatureAnnotations},
+        run_info::RunInfo,
+        target_seq::{self, TargetHash, TargetSeq},
+        version::built_info,
     },
 };
 
@@ -49,6 +83,7 @@ impl ValueEnum for AlignmentMode {
             Self::QueryLocal,
             Self::TargetLocal,
             Self::Global,
+            Self::SemiGlobal,
         ]
     }
 }
@@ -59,6 +94,60 @@ impl ValueEnum for PrimaryPickingStrategy {
     }
 }
 
+impl ValueEnum for AlignmentLength {
+    fn variants<'a>() -> &'a [Self] {
+        &[Self::Columns, Self::Query, Self::Contig]
+    }
+}
+
+impl ValueEnum for ClipPolicy {
+    fn variants<'a>() -> &'a [Self] {
+        &[Self::Soft, Self::Hard, Self::Trimmed]
+    }
+}
+
+impl ValueEnum for CsStyle {
+    fn variants<'a>() -> &'a [Self] {
+        &[Self::Short, Self::Long]
+    }
+}
+
+impl ValueEnum for MetricsFormat {
+    fn variants<'a>() -> &'a [Self] {
+        &[Self::Json, Self::Tsv]
+    }
+}
+
+impl ValueEnum for OutputOrder {
+    fn variants<'a>() -> &'a [Self] {
+        &[Self::Input, Self::Completion]
+    }
+}
+
+impl ValueEnum for PrimerMode {
+    fn variants<'a>() -> &'a [Self] {
+        &[Self::Trim, Self::Mask]
+    }
+}
+
+impl ValueEnum for ScoringPreset {
+    fn variants<'a>() -> &'a [Self] {
+        &[
+            Self::Illumina,
+            Self::Ont,
+            Self::PacbioHifi,
+            Self::Amplicon,
+            Self::Spliced,
+        ]
+    }
+}
+
+impl ValueEnum for TracebackLayout {
+    fn variants<'a>() -> &'a [Self] {
+        &[Self::RowMajor, Self::ColumnMajor]
+    }
+}
+
 /// Perfoms alignment of a long reads against a reference/expected vector/plasmid/construct.
 ///
 /// The alignment extends the traditional alignment algorithms by introducing a "jump"
@@ -90,22 +179,167 @@ impl ValueEnum for PrimaryPickingStrategy {
 /// and the same strand (`--jump-score-same-contig-and-strand`), the same contig but opposite
 /// strand (`--jump-score-same-contig-opposite-strand`), and the across different contigs.
 /// (`--jump-score-inter-contig`).  If any of these options are not specified, then they will
-/// default to the the value specified by `--jump-score`.
+/// default to the the value specified by `--jump-score`.  Jumps within the same contig and strand
+/// may further be scored by their direction, with `--jump-score-forward` for jumps ahead in the
+/// contig (deletion-like) and `--jump-score-backward` for jumps back in the contig (tandem
+/// duplication-like), otherwise defaulting to `--jump-score-same-contig-and-strand`.
+///
+/// To align RNA (e.g. cDNA) to a genome, jumps forward within the same contig and strand over an
+/// intron, from a splice donor to a splice acceptor, may be scored with `--splice-jump-score`.
+/// Introns are found from their `GT`-`AG` motifs (or `CT`-`AC` for the genes on the opposite
+/// strand), and from the transcripts in a GTF or GFF file given with `--annotation`, so that the
+/// exons are joined by cheap jumps at their splice sites, while the other jumps show fusions or
+/// other rearrangements.
+///
+/// Rather than choosing each score, a named set of scores tuned for a sequencing platform or
+/// assay may be given with `--preset`, with any scores given explicitly overriding those of the
+/// preset.  The presets are `illumina` (`-A 1 -B -4 -O -6 -E -1 -J -10`), `ont`
+/// (`-A 2 -B -4 -O -4 -E -2 -J -30`), `pacbio-hifi` (`-A 1 -B -4 -O -6 -E -2 -J -15`), and
+/// `amplicon` (`-A 1 -B -3 -O -5 -E -1 -J -15`), and `spliced`
+/// (`-A 1 -B -4 -O -6 -E -1 -J -30 --splice-jump-score -8`).
+///
+/// ## Configuration files
+///
+/// The alignment parameters (the scoring, alignment, and filtering options) may be read from a
+/// TOML file with `--config`, with the names of the options using underscores rather than
+/// dashes, `default_jump_score` for `--jump-score`, `kmer_size` for `-k`, `band_width` for `-w`,
+/// and the filtering options in a `[filter]` table.  Parameters in the file override those given
+/// on the command line.  The parameters used are recorded in the `@CO` lines of the output
+/// header, prefixed with `stitch config: `, which may be stripped to recreate the file.
+///
+/// ## Run manifest
+///
+/// The MD5 checksum of each reference is given by the `M5` tag of its `@SQ` header line.  The
+/// version, command line, parameters, and the name, length, and MD5 checksum of each reference
+/// are recorded in the `@CO` lines of the output header, prefixed with `stitch version: `,
+/// `stitch command: `, `stitch config: `, and `stitch reference: ` respectively, and in the
+/// `run_info` field of the JSON QC report, so that the results may be traced to the run.
+///
+/// ## Read groups
+///
+/// Many inputs may be given with `-f`, for example one per sample, in which case their reads are
+/// aligned in turn.  With `--read-group` and/or `--sample`, given once for all the inputs or once
+/// per input, the reads of each input are assigned to a read group (whose ID is its sample if no
+/// read group is given), given by the `RG` tag of their records, with an `@RG` header line and an
+/// entry in the `read_groups` of the `run_info` of the JSON QC report for each read group.
+///
+/// ## Subsampling
+///
+/// For a quick preview of a large dataset, at most `--max-reads` reads of each input are aligned,
+/// and with `--subsample-fraction`, each read is aligned with that probability, before the limit
+/// on the number of reads applies.  The reads kept are chosen by a pseudo-random number generator
+/// seeded with `--seed`, so are the same for the same seed and inputs.  With `--umi`, the UMI
+/// families, rather than the reads, are subsampled.
+///
+/// ## Resuming runs
+///
+/// With `--shard-dir`, each shard has a checkpoint (`shard.<index>.checkpoint`), to which the
+/// position in the shard and the number of reads of each chunk of reads are appended once the
+/// chunk is written to the shard.  An interrupted run may then be resumed by running it again
+/// with the same inputs and options and `--resume`, whereby the reads in the chunks of the
+/// checkpoints are not aligned again, and their shards are merged with those of the resumed run
+/// to the output.  Without `--resume`, any shards of a previous run are removed.  The QC report
+/// and coverage (`--qc-report` and `--coverage`) are not supported when resuming.
+///
+/// ## Timing metrics
+///
+/// With `--metrics`, the time spent aligning each read is written to `--metrics-output` (or
+/// standard error), broken down by the stages of the jump-aware alignment: initializing the
+/// matrices (`matrix_init`), filling their columns (`column_fill`), computing the best jump into
+/// each contig for each column (`jump_info`), and tracing back the alignments (`traceback`).  The
+/// `total` also includes the stages before and after the jump-aware alignment, such as
+/// pre-aligning.  Reads aligned from the cache (see `--dedup-cache-size`) are not timed.  At the
+/// end of the run, the time spent in each stage over all the reads, and the slowest read, are
+/// logged.  When built with the `tracing` feature, the stages are also `tracing` spans, logged at
+/// the debug (or, for each column, trace) level.
 #[derive(Parser, Debug, Clone)]
 #[clap(version = built_info::VERSION.as_str(), term_width=0)]
 pub struct Align {
-    /// The path to the input FASTQ with sequenced vector/plasmid/construct long reads.
-    #[clap(long, short = 'f', display_order = 1)]
-    reads_fastq: PathBuf,
+    /// The path to the input FASTQ with sequenced vector/plasmid/construct long reads.  A BAM
+    /// (with the `.bam` extension) may be given instead, in which case the primary records are
+    /// aligned, with bases and qualities in the orientation in which they were sequenced.  May
+    /// be given many times, to align the reads of each input in turn (see "Read groups" above).
+    #[clap(long, short = 'f', required = true, num_args = 1.., display_order = 1)]
+    reads_fastq: Vec<PathBuf>,
+
+    /// The ID of the read group of the reads, once for all the inputs or once per input.
+    #[clap(long, display_order = 1)]
+    read_group: Vec<String>,
+
+    /// The sample of the reads, once for all the inputs or once per input.
+    #[clap(long, display_order = 1)]
+    sample: Vec<String>,
+
+    /// Align at most this many reads of each input (see "Subsampling" above).
+    #[clap(long, display_order = 1)]
+    max_reads: Option<usize>,
 
-    /// The path to the referece vector/plasmid/construct FASTA sequence.
+    /// Align each read with this probability, in (0, 1] (see "Subsampling" above).
+    #[clap(long, display_order = 1)]
+    subsample_fraction: Option<f64>,
+
+    /// The seed of the random number generator choosing the reads to align with
+    /// `--subsample-fraction`.
+    #[clap(long, default_value_t = DEFAULT_SEED, display_order = 1)]
+    seed: u64,
+
+    /// Read the alignment parameters from this TOML file, overriding those given on the command
+    /// line (see "Configuration files" above).
+    #[clap(long, display_order = 1)]
+    config: Option<PathBuf>,
+
+    /// When the input is a BAM, only align reads that are unmapped or have at least this many
+    /// soft-clipped bases, for example to rescue chimeric reads after a standard aligner.
+    #[clap(long, display_order = 1)]
+    bam_min_soft_clip: Option<usize>,
+
+    /// The path to the referece vector/plasmid/construct FASTA sequence, which may be GZIP or BGZF
+    /// compressed.  May instead be a GenBank or EMBL file (optionally GZIP compressed), whose
+    /// feature table is then used to describe jumps in terms of the named features of the
+    /// reference by the subcommands that support it.  A record declared circular is treated as
+    /// circular.
     #[clap(long, short = 'r', display_order = 2)]
     ref_fasta: PathBuf,
 
+    /// The records read from `--ref-fasta` if a GenBank or EMBL file.
+    #[clap(skip)]
+    annotated_records: Option<Arc<Vec<AnnotatedRecord>>>,
+
+    /// Only load the given contigs from the reference FASTA, which must then be indexed with
+    /// `samtools faidx`.  The FASTA may be BGZF compressed, but then must also have a `.gzi`
+    /// index.  May be specified multiple times.
+    #[clap(long, display_order = 2)]
+    contig: Vec<String>,
+
+    /// Read the references (and their k-mer indexes for pre-alignment) from this contig index
+    /// rather than the reference FASTA, or if the index does not exist, build it from the
+    /// reference FASTA (and `--contig`) and write it here, so that repeated runs against the same
+    /// reference skip preprocessing the reference.  The index is GZIP compressed if the path ends
+    /// with `.gz`.  The scoring the index was built with is the default scoring, overridden by
+    /// `--preset`, the scoring options, and `--config`.
+    #[clap(long, display_order = 2)]
+    index: Option<PathBuf>,
+
+    /// The contig index read (or built) from `--index`.
+    #[clap(skip)]
+    contig_set: Option<Arc<ContigSet>>,
+
     /// Align to both strands of the reference simulataneously.
     #[clap(long, short = 'd', default_value = "false", display_order = 3)]
     double_strand: bool,
 
+    /// When aligning to both strands, derive the reverse strand of each contig from the forward
+    /// strand during alignment, rather than storing both.  This halves the memory used for contigs
+    /// at a small cost in speed.
+    #[clap(long, default_value = "false", display_order = 3)]
+    lazy_reverse_complement: bool,
+
+    /// Evaluate the contigs in order of name, rather than the order in the reference FASTA, so
+    /// that ties between equally good alignments are broken the same way regardless of the order
+    /// of the reference sequences.
+    #[clap(long, default_value = "false", display_order = 3)]
+    canonical_contig_order: bool,
+
     /// The number of threads to use.
     #[clap(long, short = 't', default_value = "2", display_order = 4)]
     threads: usize,
@@ -126,6 +360,18 @@ pub struct Align {
     #[clap(long, short = 'w', default_value = "50", display_order = 8)]
     w: usize,
 
+    /// Only fill the matrix cells within this many target bases of the best scoring cell of the
+    /// previous read base, when aligning with jumps.  This is faster for long targets, but may
+    /// miss indels longer than the band, and jumps when the alignment is otherwise improving.
+    #[clap(long, display_order = 8)]
+    adaptive_band_width: Option<usize>,
+
+    /// Fill the matrix columns of targets longer than this many bases in blocks of this many
+    /// bases in parallel, so that aligning to a long target uses multiple threads.  These threads
+    /// are in addition to those set by `--threads`.
+    #[clap(long, display_order = 8)]
+    parallel_block_rows: Option<usize>,
+
     /// The minimum score required for pre-alignment.
     #[clap(
         long,
@@ -140,57 +386,76 @@ pub struct Align {
     #[clap(long, short = 'x', default_value = "true", display_order = 8)]
     pre_align_subset_contigs: bool,
 
-    /// Use soft-clipping for all alignments, otherwise secondary alignemnts will use hard-clipping
-    #[clap(long, short = 'S', default_value = "false", display_order = 10)]
-    soft_clip: bool,
+    /// Pre-filter contigs using a fast edit distance bound on the local alignment score.  The
+    /// bound is per contig and ignores jumps, so a read that only reaches the
+    /// `--pre-filter-min-score` by jumping between contigs (e.g. a chimeric read) is unmapped.
+    #[clap(long, default_value = "false", display_order = 9)]
+    pre_filter: bool,
 
-    /// Use =/X CIGAR operators, otherwise use M
-    #[clap(long, short = 'X', default_value = "false", display_order = 11)]
-    use_eq_and_x: bool,
-
-    /// Score for a sequence match (must be positive)
-    #[clap(long, short = 'A', default_value = "1", display_order = 12)]
-    match_score: i32,
-
-    /// Score for a sequence mismatch (must be negative)
+    /// The minimum score of the local alignment (without jumps) to a contig to pass the
+    /// `--pre-filter`.
     #[clap(
         long,
-        short = 'B',
-        default_value = "-4",
+        default_value = "100",
         allow_hyphen_values = true,
-        display_order = 13
+        display_order = 9
     )]
-    mismatch_score: i32,
+    pre_filter_min_score: i32,
 
-    /// Score for a gap open (must be negative)
-    #[clap(
-        long,
-        short = 'O',
-        default_value = "-6",
-        allow_hyphen_values = true,
-        display_order = 14
-    )]
-    gap_open: i32,
+    /// Use soft-clipping for all alignments, as with `--clip-policy soft`
+    #[clap(long, short = 'S', default_value = "false", display_order = 10)]
+    soft_clip: bool,
 
-    /// Score for a gap extend (must be negative); a gap of size k costs '{-O} + {-E}*k'
+    /// How the read bases outside of the aligned part of secondary records are represented, as
+    /// downstream tools differ on what they accept (the primary record is always soft-clipped):
+    /// - Soft: soft-clipped, with the whole read in the record
+    /// - Hard: hard-clipped, with only the aligned bases in the record
+    /// - Trimmed: omitted, with only the aligned bases in the record and no clip operators
     #[clap(
         long,
-        short = 'E',
-        default_value = "-2",
-        allow_hyphen_values = true,
-        display_order = 15
+        value_parser = PossibleValuesParser::new(ClipPolicy::possible_values())
+            .map(|s| s.parse::<ClipPolicy>().unwrap()),
+        default_value_t = ClipPolicy::Hard,
+        ignore_case = true,
+        display_order = 10,
+        verbatim_doc_comment
     )]
-    gap_extend: i32,
+    clip_policy: ClipPolicy,
+
+    /// Use =/X CIGAR operators, otherwise use M
+    #[clap(long, short = 'X', default_value = "false", display_order = 11)]
+    use_eq_and_x: bool,
+
+    /// Score for a sequence match (must be positive) [default: 1, or that of the preset]
+    #[clap(long, short = 'A', display_order = 12)]
+    match_score: Option<i32>,
+
+    /// Score for a sequence mismatch (must be negative) [default: -4, or that of the preset]
+    #[clap(long, short = 'B', allow_hyphen_values = true, display_order = 13)]
+    mismatch_score: Option<i32>,
 
-    /// Score for a target jump (must be negative).
+    /// Score for a gap open (must be negative) [default: -6, or that of the preset]
+    #[clap(long, short = 'O', allow_hyphen_values = true, display_order = 14)]
+    gap_open: Option<i32>,
+
+    /// Score for a gap extend (must be negative); a gap of size k costs '{-O} + {-E}*k' [default:
+    /// -2, or that of the preset]
+    #[clap(long, short = 'E', allow_hyphen_values = true, display_order = 15)]
+    gap_extend: Option<i32>,
+
+    /// Score for a target jump (must be negative) [default: -10, or that of the preset]
+    #[clap(long, short = 'J', allow_hyphen_values = true, display_order = 16)]
+    jump_score: Option<i32>,
+
+    /// The named set of match, mismatch, gap, and jump scores to start from (see above).
     #[clap(
         long,
-        short = 'J',
-        default_value = "-10",
-        allow_hyphen_values = true,
-        display_order = 16
+        value_parser = PossibleValuesParser::new(ScoringPreset::possible_values())
+            .map(|s| s.parse::<ScoringPreset>().unwrap()),
+        ignore_case = true,
+        display_order = 12
     )]
-    jump_score: i32,
+    preset: Option<ScoringPreset>,
 
     /// Score for a target jump within the same contig and strand (must be negative)
     #[clap(long, allow_hyphen_values = true, display_order = 16)]
@@ -204,11 +469,84 @@ pub struct Align {
     #[clap(long, allow_hyphen_values = true, display_order = 16)]
     jump_score_inter_contig: Option<i32>,
 
+    /// Score for a target jump forward within the same contig and strand (must be negative)
+    #[clap(long, allow_hyphen_values = true, display_order = 16)]
+    jump_score_forward: Option<i32>,
+
+    /// Score for a target jump backward within the same contig and strand (must be negative)
+    #[clap(long, allow_hyphen_values = true, display_order = 16)]
+    jump_score_backward: Option<i32>,
+
+    /// Score for a target jump forward within the same contig and strand over an intron, from a
+    /// splice donor to a splice acceptor (must be negative) [default: none, or that of the preset]
+    #[clap(long, allow_hyphen_values = true, display_order = 16)]
+    splice_jump_score: Option<i32>,
+
+    /// A GTF or GFF file of the transcripts of the references, whose introns (between the exons
+    /// of each transcript) are jumped over with `--splice-jump-score`, whatever their motifs.
+    #[clap(long, display_order = 16)]
+    annotation: Option<PathBuf>,
+
+    /// The introns read from `--annotation`.
+    #[clap(skip)]
+    splice_annotation: Option<Arc<SpliceAnnotation>>,
+
+    /// Score for a gap open next to (or inside) a homopolymer run in the target of at least
+    /// `--homopolymer-min-length` bases (must be negative), since long read errors are enriched
+    /// for insertions and deletions in homopolymers [default: `--gap-open`]
+    #[clap(long, allow_hyphen_values = true, display_order = 15)]
+    homopolymer_gap_open: Option<i32>,
+
+    /// The minimum length of a homopolymer run for `--homopolymer-gap-open`
+    #[clap(long, default_value = "3", display_order = 15)]
+    homopolymer_min_length: usize,
+
+    /// Remove segments after a jump with fewer than this many read bases aligned (matched or
+    /// mismatched), deleting their read bases, since such short segments are usually noise.
+    #[clap(long, display_order = 16)]
+    min_segment_length: Option<usize>,
+
+    /// Forbid jumps into or out of the clipped regions of the references, so that every jump is
+    /// from an aligned reference base to another, rather than jumping over the ends of a
+    /// reference in place of clipping it.
+    #[clap(long, default_value = "false", display_order = 16)]
+    forbid_clip_jumps: bool,
+
+    /// Treat runs of at least this many Ns in the references as scaffold gaps of unknown length,
+    /// and jump over them for free, so that a read spanning a gap is aligned to the bases on
+    /// either side of it however long the run of Ns.
+    #[clap(long, display_order = 16)]
+    scaffold_gap_min_len: Option<usize>,
+
+    /// Only jump from and to the boundaries of the features in this BED file (for example the
+    /// exons of a transcript, or the ITRs of a vector), so that jumps are placed at the exon
+    /// junctions or ITR boundaries.  Jumps from and to the references without features are not
+    /// restricted.
+    #[clap(long, display_order = 16)]
+    jump_sites: Option<PathBuf>,
+
+    /// With `--jump-sites`, score each end of a jump that is not at a feature boundary with this
+    /// score (must not be positive), so that such jumps are penalized rather than forbidden.
+    #[clap(long, allow_hyphen_values = true, display_order = 16)]
+    off_site_jump_score: Option<i32>,
+
+    /// Score the jumps from or to the boundaries of the features in this BED file (for example
+    /// known recombination or splice points) without the jump score, so that such jumps are
+    /// penalty-free.  With `--jump-sites`, jumps from and to these boundaries are also allowed.
+    #[clap(long, display_order = 16)]
+    free_jump_sites: Option<PathBuf>,
+
+    /// The jump sites read from `--jump-sites` and `--free-jump-sites`.
+    #[clap(skip)]
+    jump_site_features: Option<Arc<JumpSites>>,
+
     /// The alignment mode:
     /// - Local: aligns a sub-sequence of the read versus a sub-sequence of the reference.
     /// - QueryLocal: aligns a sub-sequence of the read versus the full reference.
     /// - TargetLocal: aligns the full read versus a sub-sequence of the reference.
     /// - Global: aligns the full read versus the full reference.
+    /// - SemiGlobal: aligns the full read versus sub-sequences of the reference, never clipping
+    ///   the read, for example for amplicons spanning an assembled construct.
     #[clap(
         long,
         short = 'm',
@@ -236,6 +574,35 @@ pub struct Align {
     )]
     pick_primary: PrimaryPickingStrategy,
 
+    /// The length used to order suboptimal alignments with the same score (see `--suboptimal`),
+    /// longest first:
+    /// - Columns: the number of alignment columns (matches, mismatches, insertions and deletions)
+    /// - Query: the number of aligned query bases
+    /// - Contig: the number of aligned reference bases
+    #[clap(
+        long,
+        value_parser = PossibleValuesParser::new(AlignmentLength::possible_values())
+            .map(|s| s.parse::<AlignmentLength>().unwrap()),
+        default_value_t = AlignmentLength::Columns,
+        ignore_case = true,
+        display_order = 25,
+        verbatim_doc_comment
+    )]
+    tie_break_length: AlignmentLength,
+
+    /// Add the minimap2-style `cs` difference string of each record to its `cs` tag:
+    /// - Short: runs of identical bases are written as their length (e.g. `:10`)
+    /// - Long: runs of identical bases are written as their bases (e.g. `=ACGTACGTAC`)
+    #[clap(
+        long,
+        value_parser = PossibleValuesParser::new(CsStyle::possible_values())
+            .map(|s| s.parse::<CsStyle>().unwrap()),
+        ignore_case = true,
+        display_order = 25,
+        verbatim_doc_comment
+    )]
+    cs: Option<CsStyle>,
+
     /// True to treat the input target as circular.  This allows the alignment to jump back to the
     /// start of the target/reference at no cost.
     #[clap(long, short = 'C', default_value = "false", display_order = 19)]
@@ -267,14 +634,655 @@ pub struct Align {
     /// The compression level of the output BAM
     #[clap(long, short = 'c', default_value = "0", display_order = 25)]
     compression: u8,
+
+    /// Filter out alignments with a score less than this.  A read with no alignments passing the
+    /// filters is written as unmapped.
+    #[clap(long, allow_hyphen_values = true, display_order = 25)]
+    min_score: Option<i32>,
+
+    /// Filter out alignments with an identity (the fraction of matches in the aligned columns)
+    /// less than this.
+    #[clap(long, display_order = 25)]
+    min_identity: Option<f64>,
+
+    /// Filter out alignments with fewer than this many read bases aligned.
+    #[clap(long, display_order = 25)]
+    min_aligned_length: Option<usize>,
+
+    /// Filter out alignments with more than this many jumps.
+    #[clap(long, display_order = 25)]
+    max_jumps: Option<usize>,
+
+    /// Do not align reads whose alignment matrices would have more than this many cells (the
+    /// read length times the length of the references to align to), to bound the memory used
+    /// by an unusually long read.  Such reads are written as unmapped, with the reason in the
+    /// `sk` tag (`matrix-size`).
+    #[clap(long, display_order = 25)]
+    max_matrix_cells: Option<usize>,
+
+    /// Rather than not aligning reads over `--max-matrix-cells`, align the longest prefix of the
+    /// read within the limit, with the rest of the read clipped.
+    #[clap(long, default_value = "false", display_order = 25)]
+    truncate_oversized_reads: bool,
+
+    /// Stop aligning a read after this many milliseconds, writing it as unmapped, with the reason
+    /// in the `sk` tag (`timeout`).
+    #[clap(long, display_order = 25)]
+    read_timeout_ms: Option<u64>,
+
+    /// Align reads longer than this many bases in overlapping chunks of this many bases, then
+    /// merge the alignments of the chunks, so that the memory used is bounded by the chunk length
+    /// for ultra-long reads.  Chunked reads are not re-aligned around the origin of circular
+    /// references, nor have sub-optimal alignments.  Only used with `--mode local`,
+    /// `--mode target-local`, or `--mode semi-global`.
+    #[clap(long, display_order = 25)]
+    chunk_length: Option<usize>,
+
+    /// The number of bases by which consecutive chunks overlap (see `--chunk-length`).
+    #[clap(long, default_value = "1000", display_order = 25)]
+    chunk_overlap: usize,
+
+    /// Keep only every this many read bases of the alignment matrices for tracing back the
+    /// alignment, and recompute the bases in between, so that aligning long reads uses about this
+    /// many times less memory and up to twice the time.  The alignments are unchanged.
+    #[clap(long, display_order = 25)]
+    traceback_checkpoint_interval: Option<usize>,
+
+    /// The order in which the cells of the traceback matrices are stored: `row-major` stores the
+    /// cells of each target base together, while `column-major` stores the cells of each read
+    /// base together, in the order in which they are filled.  The alignments are unchanged, but
+    /// either may be faster depending on the lengths of the reads and targets.
+    #[clap(
+        long,
+        value_parser = PossibleValuesParser::new(TracebackLayout::possible_values())
+            .map(|s| s.parse::<TracebackLayout>().unwrap()),
+        default_value_t = TracebackLayout::RowMajor,
+        ignore_case = true,
+        display_order = 25
+    )]
+    traceback_layout: TracebackLayout,
+
+    /// Checkpoint the alignment matrices of reads whose matrices would use more than this many
+    /// bytes, as with `--traceback-checkpoint-interval`, choosing the interval that uses the
+    /// least memory.  Reads whose matrices would still use more are not aligned, and are output
+    /// unmapped with the reason in the `sk` tag (`matrix-size`).
+    #[clap(long, display_order = 25)]
+    max_read_memory: Option<usize>,
+
+    /// Pick how to align each read from its length, the lengths of the contigs, and its identity
+    /// to the references estimated from their shared k-mers: in full, banded (as with
+    /// `--adaptive-band-width`), by first finding the contigs to align to (as with `--pre-align`),
+    /// or in chunks (as with `--chunk-length`), so that reads are aligned quickly without tuning.
+    #[clap(long, default_value = "false", display_order = 25)]
+    auto_strategy: bool,
+
+    /// The parameters for picking how to align each read with `--auto-strategy`, from `--config`.
+    #[clap(skip)]
+    auto_strategy_params: Option<AutoStrategy>,
+
+    /// Re-align the read bases around each jump, without a band, to place the jump at the best
+    /// read base, refining the breakpoint reported by the alignment.
+    #[clap(long, default_value = "false", display_order = 25)]
+    refine_jumps: bool,
+
+    /// The number of read bases on either side of each jump re-aligned with `--refine-jumps`.
+    #[clap(long, default_value = "20", display_order = 25)]
+    refine_jumps_window: usize,
+
+    /// Break down the score of each alignment into the contributions of its runs of aligned
+    /// bases, gaps, clips, and jumps, in the `sb` tag of its records (e.g. `40M:40,1J:-10,20M:20`).
+    #[clap(long, default_value = "false", display_order = 25)]
+    score_breakdown: bool,
+
+    /// Log the progress of aligning each read at least this long, every 10% of the read, since
+    /// long reads against large references may take minutes to align.
+    #[clap(long, display_order = 25)]
+    progress_min_read_length: Option<usize>,
+
+    /// Align each distinct read sequence once, reusing the alignments of up to this many of the
+    /// most recent distinct sequences (per thread) for reads with the same sequence, which is
+    /// much faster for amplicon data with many duplicate reads.  Adjacent reads with the same
+    /// sequence are always aligned once.
+    #[clap(long, default_value = "0", display_order = 25)]
+    dedup_cache_size: usize,
+
+    /// Group the reads by UMI and align one consensus read per UMI, where the UMI is the last
+    /// colon-delimited field of the read name (`name`) or the value of the given tag in the
+    /// comment of the read header (e.g. `RX`).  The consensus is named after the first read
+    /// with its UMI, and has its UMI and the number of reads with its UMI in the `RX` and `cD`
+    /// tags respectively.  All the reads are read into memory.
+    #[clap(long, display_order = 25)]
+    umi: Option<UmiSource>,
+
+    /// Trim (or mask, see `--primer-mode`) the primers in this FASTA from the ends of each read
+    /// before alignment, since primer-derived bases otherwise bias the placement of jumps near the
+    /// ends of amplicons.  Any primer is searched for at the start of the read, and the reverse
+    /// complement of any primer at its end.  The primers found are in the `pt` tag of the records
+    /// of the read, as `<primer>:<start>-<end>` (0-based, in the read before trimming), separated
+    /// by commas.
+    #[clap(long, display_order = 25)]
+    primers: Option<PathBuf>,
+
+    /// Whether to trim the primers found in the reads (see `--primers`), along with any bases
+    /// outside them, or to mask them with `N` keeping the read length.
+    #[clap(
+        long,
+        value_parser = PossibleValuesParser::new(PrimerMode::possible_values())
+            .map(|s| s.parse::<PrimerMode>().unwrap()),
+        default_value_t = PrimerMode::Trim,
+        ignore_case = true,
+        display_order = 25
+    )]
+    primer_mode: PrimerMode,
+
+    /// The maximum number of mismatches of a primer found in a read (see `--primers`).
+    #[clap(long, default_value = "2", display_order = 25)]
+    primer_max_mismatches: usize,
+
+    /// The number of bases at either end of a read in which the primers are searched for (see
+    /// `--primers`).
+    #[clap(long, default_value = "50", display_order = 25)]
+    primer_search_window: usize,
+
+    /// The primer trimmer built from `--primers`.
+    #[clap(skip)]
+    primer_trimmer: Option<Arc<PrimerTrimmer>>,
+
+    /// Split concatemeric reads at the adapter (or backbone) sequences in this FASTA before
+    /// alignment (and before trimming any primers), aligning each segment between the adapters as
+    /// its own read.  The adapters are found on either strand by local alignment.  The segments
+    /// of a read split into multiple segments are named `<read>_<index>`, and the records of each
+    /// segment have the segment in the `sg` tag, as `<index>/<count>:<start>-<end>`, and the
+    /// adapters found in the read in the `ad` tag, as `<adapter><strand>:<start>-<end>` separated
+    /// by commas (0-based, in the read before splitting).
+    #[clap(long, display_order = 25)]
+    adapters: Option<PathBuf>,
+
+    /// The minimum score of the local alignment of an adapter to a read (see `--adapters`), as a
+    /// fraction of the score of the adapter aligned to itself.
+    #[clap(long, default_value = "0.7", display_order = 25)]
+    adapter_min_score_fraction: f64,
+
+    /// The minimum length of a segment of a read split at adapters (see `--adapters`) to be
+    /// aligned, with shorter segments discarded.
+    #[clap(long, default_value = "100", display_order = 25)]
+    adapter_min_segment_len: usize,
+
+    /// The adapters read from `--adapters`.
+    #[clap(skip)]
+    adapter_seqs: Option<Arc<Vec<TargetSeq>>>,
+
+    /// Pair the reads of the two strands of the same molecule (e.g. the template and complement
+    /// reads of nanopore duplex sequencing), and align the consensus of each pair in place of its
+    /// reads.  A read is paired with a following read (see `--duplex-window`) whose reverse
+    /// complement aligns to it over most of both reads.  The consensus is named after the template
+    /// read, with the name of the complement read in the `dx` tag.  Reads are only paired within
+    /// the same chunk of reads sent to an aligner thread.
+    #[clap(long, display_order = 25)]
+    duplex: bool,
+
+    /// The number of reads following a read that are searched for its complement read (see
+    /// `--duplex`).
+    #[clap(long, default_value = "5", display_order = 25)]
+    duplex_window: usize,
+
+    /// The minimum identity of the alignment of the two reads of a pair (see `--duplex`).
+    #[clap(long, default_value = "0.8", display_order = 25)]
+    duplex_min_identity: f64,
+
+    /// Write a run-level QC report of the best alignment of each read (the alignment rate, the
+    /// identity and jump count distributions, the strand switch rate, and the coverage of each
+    /// reference) to `<qc-report>.json` and `<qc-report>.txt` at the end of the run.
+    #[clap(long, display_order = 25)]
+    qc_report: Option<PathBuf>,
+
+    /// Write the coverage of each strand of the references by all the alignments of the reads in
+    /// bedGraph format to `<coverage>.fwd.bedGraph` and `<coverage>.rev.bedGraph` at the end of
+    /// the run.  Use `bedGraphToBigWig` to convert them to BigWig.
+    #[clap(long, display_order = 25)]
+    coverage: Option<PathBuf>,
+
+    /// Write the time spent in each stage of aligning each read in the given format (see "Timing
+    /// metrics" above):
+    /// - Json: one JSON object per line
+    /// - Tsv: tab-delimited, with a header line
+    #[clap(
+        long,
+        value_parser = PossibleValuesParser::new(MetricsFormat::possible_values())
+            .map(|s| s.parse::<MetricsFormat>().unwrap()),
+        ignore_case = true,
+        display_order = 25,
+        verbatim_doc_comment
+    )]
+    metrics: Option<MetricsFormat>,
+
+    /// The path to which to write the timing metrics (see `--metrics`), or standard error if not
+    /// given.
+    #[clap(long, display_order = 25)]
+    metrics_output: Option<PathBuf>,
+
+    /// The order in which the records of the reads are output:
+    /// - Input: the order of the reads in the input
+    /// - Completion: the order in which the chunks of reads finish aligning, so a slow read does
+    ///   not hold back the output of the reads after it
+    #[clap(
+        long,
+        value_parser = PossibleValuesParser::new(OutputOrder::possible_values())
+            .map(|s| s.parse::<OutputOrder>().unwrap()),
+        default_value_t = OutputOrder::Input,
+        ignore_case = true,
+        display_order = 25,
+        verbatim_doc_comment
+    )]
+    output_order: OutputOrder,
+
+    /// Have each thread write the BAM records of the reads it aligns to its own shard in this
+    /// directory, rather than all threads sharing the output, then merge the shards to the output
+    /// in the order given by `--output-order` at the end of the run.  The shards are removed once
+    /// merged.  Only used by `align`.
+    #[clap(long, display_order = 25)]
+    shard_dir: Option<PathBuf>,
+
+    /// Resume an interrupted run from the shards it wrote to `--shard-dir`, aligning only the
+    /// reads not already written to a shard (see "Resuming runs" above).  Only used by `align`.
+    #[clap(long, default_value = "false", display_order = 25)]
+    resume: bool,
 }
 
+/// Encodes the records of a chunk of aligned reads, to be written to a shard.
+type ChunkEncoder = Arc<dyn Fn(&[OutputResult]) -> Result<Vec<u8>> + Send + Sync>;
+
 impl Align {
-    /// Executes the align command
-    pub fn execute(&self) -> anyhow::Result<()> {
-        info!("Starting alignment...");
+    /// Returns the manifest of this run, aligning to the given targets, with their MD5 checksums
+    /// from the contig index if any.
+    pub(crate) fn run_info(&self, target_seqs: &[TargetSeq]) -> Result<RunInfo> {
+        let command_line = env::args_os()
+            .map(|s| s.to_string_lossy().into_owned())
+            .join(" ");
+        let mut run_info = match &self.contig_set {
+            Some(contig_set) => {
+                RunInfo::with_md5s(&command_line, self.config(), target_seqs, contig_set.md5s())
+            }
+            None => RunInfo::new(&command_line, self.config(), target_seqs),
+        };
+        run_info.read_groups = ReadGroup::distinct(&self.read_groups()?);
+        Ok(run_info)
+    }
+
+    /// Returns the read group of each input, or none if not given.
+    pub(crate) fn read_groups(&self) -> Result<Vec<ReadGroup>> {
+        ReadGroup::for_inputs(self.reads_fastq.len(), &self.read_group, &self.sample)
+    }
+
+    /// Returns the limits on the reads to align of each input.
+    fn subsample(&self) -> Result<Subsample> {
+        Subsample::new(self.max_reads, self.subsample_fraction, self.seed)
+    }
+
+    /// Returns the inputs of reads, with their read groups.
+    fn read_inputs(&self) -> Result<Vec<ReadInput>> {
+        let mut read_groups = self.read_groups()?.into_iter();
+        let inputs = self
+            .reads_fastq
+            .iter()
+            .map(|path| ReadInput::new(path.clone(), read_groups.next()))
+            .collect();
+        info!(
+            "Reading reads FASTQ from {}",
+            self.reads_fastq
+                .iter()
+                .map(|path| path.display())
+                .join(", ")
+        );
+        Ok(inputs)
+    }
+
+    /// Returns the alignment parameters from the command line options.
+    pub(crate) fn config(&self) -> AlignerConfig {
+        let mut scores = AlignerConfig::default();
+        if let Some(contig_set) = &self.contig_set {
+            scores.set_scoring(contig_set.scoring());
+        }
+        if let Some(preset) = self.preset {
+            scores.set_scoring(&preset.scoring());
+        }
+        AlignerConfig {
+            mode: self.mode,
+            match_score: self.match_score.unwrap_or(scores.match_score),
+            mismatch_score: self.mismatch_score.unwrap_or(scores.mismatch_score),
+            gap_open: self.gap_open.unwrap_or(scores.gap_open),
+            gap_extend: self.gap_extend.unwrap_or(scores.gap_extend),
+            default_jump_score: self.jump_score.unwrap_or(scores.default_jump_score),
+            jump_score_same_contig_and_strand: self
+                .jump_score_same_contig_and_strand
+                .or(scores.jump_score_same_contig_and_strand),
+            jump_score_same_contig_opposite_strand: self
+                .jump_score_same_contig_opposite_strand
+                .or(scores.jump_score_same_contig_opposite_strand),
+            jump_score_inter_contig: self
+                .jump_score_inter_contig
+                .or(scores.jump_score_inter_contig),
+            jump_score_forward: self.jump_score_forward,
+            jump_score_backward: self.jump_score_backward,
+            splice_jump_score: self.splice_jump_score.or(scores.splice_jump_score),
+            min_segment_length: self.min_segment_length,
+            forbid_clip_jumps: self.forbid_clip_jumps,
+            scaffold_gap_min_len: self.scaffold_gap_min_len,
+            homopolymer_gap_open: self.homopolymer_gap_open.or(scores.homopolymer_gap_open),
+            homopolymer_min_length: self.homopolymer_min_length,
+            kmer_size: self.k,
+            band_width: self.w,
+            adaptive_band_width: self.adaptive_band_width,
+            parallel_block_rows: self.parallel_block_rows,
+            double_strand: self.double_strand,
+            circular: self.circular,
+            lazy_reverse_complement: self.lazy_reverse_complement,
+            canonical_contig_order: self.canonical_contig_order,
+            circular_slop: self.circular_slop,
+            pre_align: self.pre_align,
+            pre_align_min_score: self.pre_align_min_score,
+            pre_align_subset_contigs: self.pre_align_subset_contigs,
+            pre_filter: self.pre_filter,
+            pre_filter_min_score: self.pre_filter_min_score,
+            suboptimal: self.suboptimal,
+            suboptimal_pct: self.suboptimal_pct,
+            clip_policy: if self.soft_clip {
+                ClipPolicy::Soft
+            } else {
+                self.clip_policy
+            },
+            use_eq_and_x: self.use_eq_and_x,
+            pick_primary: self.pick_primary,
+            filter_secondary: self.filter_secondary,
+            filter_secondary_pct: self.filter_secondary_pct,
+            tie_break_length: self.tie_break_length,
+            cs_tag: self.cs,
+            max_matrix_cells: self.max_matrix_cells,
+            truncate_oversized_reads: self.truncate_oversized_reads,
+            read_timeout_ms: self.read_timeout_ms,
+            chunk_length: self.chunk_length,
+            chunk_overlap: self.chunk_overlap,
+            traceback_checkpoint_interval: self.traceback_checkpoint_interval,
+            traceback_layout: self.traceback_layout,
+            max_read_memory: self.max_read_memory,
+            auto_strategy: self
+                .auto_strategy
+                .then(|| self.auto_strategy_params.unwrap_or_default()),
+            refine_jumps: self.refine_jumps,
+            refine_jumps_window: self.refine_jumps_window,
+            score_breakdown: self.score_breakdown,
+            filter: AlignmentFilter {
+                min_score: self.min_score,
+                min_identity: self.min_identity,
+                min_aligned_length: self.min_aligned_length,
+                max_jumps: self.max_jumps,
+            },
+        }
+    }
+
+    /// Sets the command line options from the alignment parameters.
+    fn set_config(&mut self, config: &AlignerConfig) {
+        self.mode = config.mode;
+        self.preset = None;
+        self.match_score = Some(config.match_score);
+        self.mismatch_score = Some(config.mismatch_score);
+        self.gap_open = Some(config.gap_open);
+        self.gap_extend = Some(config.gap_extend);
+        self.jump_score = Some(config.default_jump_score);
+        self.jump_score_same_contig_and_strand = config.jump_score_same_contig_and_strand;
+        self.jump_score_same_contig_opposite_strand = config.jump_score_same_contig_opposite_strand;
+        self.jump_score_inter_contig = config.jump_score_inter_contig;
+        self.jump_score_forward = config.jump_score_forward;
+        self.jump_score_backward = config.jump_score_backward;
+        self.splice_jump_score = config.splice_jump_score;
+        self.min_segment_length = config.min_segment_length;
+        self.forbid_clip_jumps = config.forbid_clip_jumps;
+        self.scaffold_gap_min_len = config.scaffold_gap_min_len;
+        self.homopolymer_gap_open = config.homopolymer_gap_open;
+        self.homopolymer_min_length = config.homopolymer_min_length;
+        self.k = config.kmer_size;
+        self.w = config.band_width;
+        self.adaptive_band_width = config.adaptive_band_width;
+        self.parallel_block_rows = config.parallel_block_rows;
+        self.double_strand = config.double_strand;
+        self.circular = config.circular;
+        self.lazy_reverse_complement = config.lazy_reverse_complement;
+        self.canonical_contig_order = config.canonical_contig_order;
+        self.circular_slop = config.circular_slop;
+        self.pre_align = config.pre_align;
+        self.pre_align_min_score = config.pre_align_min_score;
+        self.pre_align_subset_contigs = config.pre_align_subset_contigs;
+        self.pre_filter = config.pre_filter;
+        self.pre_filter_min_score = config.pre_filter_min_score;
+        self.suboptimal = config.suboptimal;
+        self.suboptimal_pct = config.suboptimal_pct;
+        self.soft_clip = false;
+        self.clip_policy = config.clip_policy;
+        self.use_eq_and_x = config.use_eq_and_x;
+        self.pick_primary = config.pick_primary;
+        self.filter_secondary = config.filter_secondary;
+        self.filter_secondary_pct = config.filter_secondary_pct;
+        self.tie_break_length = config.tie_break_length;
+        self.cs = config.cs_tag;
+        self.max_matrix_cells = config.max_matrix_cells;
+        self.truncate_oversized_reads = config.truncate_oversized_reads;
+        self.read_timeout_ms = config.read_timeout_ms;
+        self.chunk_length = config.chunk_length;
+        self.chunk_overlap = config.chunk_overlap;
+        self.traceback_checkpoint_interval = config.traceback_checkpoint_interval;
+        self.traceback_layout = config.traceback_layout;
+        self.max_read_memory = config.max_read_memory;
+        self.auto_strategy = config.auto_strategy.is_some();
+        self.auto_strategy_params = config.auto_strategy;
+        self.refine_jumps = config.refine_jumps;
+        self.refine_jumps_window = config.refine_jumps_window;
+        self.score_breakdown = config.score_breakdown;
+        self.min_score = config.filter.min_score;
+        self.min_identity = config.filter.min_identity;
+        self.min_aligned_length = config.filter.min_aligned_length;
+        self.max_jumps = config.filter.max_jumps;
+    }
+
+    /// Returns these options with the alignment parameters overridden by those in the `--config`
+    /// file, if any.  This should be called before using the options.
+    pub(crate) fn with_config_file(&self) -> Result<Self> {
+        let mut align = self.clone();
+        if let Some(path) = self.index.as_ref().filter(|path| path.exists()) {
+            info!("Reading the contig index from {}", path.display());
+            align.contig_set = Some(Arc::new(ContigSet::load(path)?));
+        }
+        if let Some(path) = &self.jump_sites {
+            ensure!(
+                self.off_site_jump_score.is_none_or(|score| score <= 0),
+                "--off-site-jump-score must not be positive"
+            );
+            info!("Reading the jump sites from {}", path.display());
+            let sites = JumpSites::from_path(path, self.off_site_jump_score)?;
+            align.jump_site_features = Some(Arc::new(sites));
+        }
+        if let Some(path) = &self.free_jump_sites {
+            info!("Reading the free jump sites from {}", path.display());
+            let mut sites = align
+                .jump_site_features
+                .take()
+                .map_or_else(|| JumpSites::new(None), Arc::unwrap_or_clone);
+            sites.add_free_sites_from_path(path)?;
+            align.jump_site_features = Some(Arc::new(sites));
+        }
+        if let Some(path) = &self.annotation {
+            info!("Reading the annotated introns from {}", path.display());
+            align.splice_annotation = Some(Arc::new(SpliceAnnotation::from_path(path)?));
+        }
+        if let Some(path) = &self.primers {
+            info!("Reading the primers from {}", path.display());
+            let trimmer = PrimerTrimmer::new(
+                target_seq::from_fasta(path, false)?,
+                self.primer_mode,
+                self.primer_max_mismatches,
+                self.primer_search_window,
+            )?;
+            align.primer_trimmer = Some(Arc::new(trimmer));
+        }
+        if let Some(path) = &self.adapters {
+            ensure!(
+                self.adapter_min_score_fraction > 0.0 && self.adapter_min_score_fraction <= 1.0,
+                "--adapter-min-score-fraction must be in (0, 1]"
+            );
+            info!("Reading the adapters from {}", path.display());
+            let adapters = target_seq::from_fasta(path, false)?;
+            ensure!(!adapters.is_empty(), "No adapters in {}", path.display());
+            align.adapter_seqs = Some(Arc::new(adapters));
+        }
+        if let Some(path) = &self.config {
+            align = align.with_parameters_from(path)?;
+            align.config = None;
+        }
+        align.builder().validate()?;
+        // The references are read from the contig index if any, otherwise they may be annotated
+        if align.contig_set.is_none()
+            && self.ref_fasta.is_file()
+            && genbank::is_flat_file_path(&self.ref_fasta)?
+        {
+            info!(
+                "Reading the annotated reference from {}",
+                self.ref_fasta.display()
+            );
+            align.annotated_records = Some(Arc::new(genbank::read_records(&self.ref_fasta)?));
+        }
+        if let Some(path) = self.index.as_ref().filter(|_| align.contig_set.is_none()) {
+            let scoring = align.builder().build_scoring();
+            let contig_set = ContigSet::new(align.target_seqs()?, align.k, scoring);
+            info!("Writing the contig index to {}", path.display());
+            contig_set.write(path)?;
+            align.contig_set = Some(Arc::new(contig_set));
+        }
+        Ok(align)
+    }
+
+    /// Returns these options with the alignment parameters overridden by those in the given TOML
+    /// file (see "Configuration files" above), for example for a second set of parameters to
+    /// compare with the first.
+    pub(crate) fn with_parameters_from(&self, path: &Path) -> Result<Self> {
+        info!("Reading alignment parameters from {}", path.display());
+        let mut align = self.clone();
+        let config = align.config().merge_file(path)?;
+        align.set_config(&config);
+        align.builder().validate()?;
+        Ok(align)
+    }
+
+    /// Creates the builder from the command line options.  The builder is used when initializing
+    /// each thread to create the Aligners object, and to create the SamRecordFormatter for
+    /// converting alignments to SAM format for writing.
+    pub(crate) fn builder(&self) -> Builder {
+        self.config().builder()
+    }
+
+    /// The jump sites read from `--jump-sites` and `--free-jump-sites`, if any.
+    pub(crate) fn jump_site_features(&self) -> Option<Arc<JumpSites>> {
+        self.jump_site_features.clone()
+    }
+
+    /// The annotated introns read from `--annotation`, if any.
+    pub(crate) fn splice_annotation(&self) -> Option<Arc<SpliceAnnotation>> {
+        self.splice_annotation.clone()
+    }
+
+    /// Creates the filter applied to the alignments of each read from the command line options.
+    pub(crate) fn alignment_filter(&self) -> AlignmentFilter {
+        self.config().filter
+    }
+
+    /// Reads the reference/target FASTA records.
+    pub(crate) fn target_seqs(&self) -> Result<Vec<TargetSeq>> {
+        if let Some(contig_set) = &self.contig_set {
+            return Ok(contig_set.target_seqs().to_vec());
+        }
+        if let Some(records) = &self.annotated_records {
+            let (target_seqs, _) = genbank::to_target_seqs(records, &self.contig, self.circular)?;
+            return Ok(target_seqs);
+        }
         info!("Reading reference FASTA from {}", self.ref_fasta.display());
-        info!("Reading reads FASTQ from {}", self.reads_fastq.display());
+        if self.contig.is_empty() {
+            target_seq::from_fasta(&self.ref_fasta, self.circular)
+        } else {
+            target_seq::from_indexed_fasta(&self.ref_fasta, &self.contig, self.circular)
+        }
+    }
+
+    /// The features of the references if read from a GenBank or EMBL file, otherwise `None`.
+    pub(crate) fn annotations(&self) -> Result<Option<FeatureAnnotations>> {
+        match &self.annotated_records {
+            None => Ok(None),
+            Some(records) => {
+                let (_, annotations) =
+                    genbank::to_target_seqs(records, &self.contig, self.circular)?;
+                Ok(Some(annotations))
+            }
+        }
+    }
+
+    /// Opens the input FASTQ (or BAM) of reads.
+    pub(crate) fn read_records(&self) -> Result<Box<dyn Iterator<Item = FastqOwnedRecord>>> {
+        stitch::align::io::read_inputs(
+            &self.read_inputs()?,
+            self.decompress,
+            self.bam_min_soft_clip,
+            self.subsample()?,
+        )
+    }
+
+    /// True if aligning to both strands of the reference.
+    #[cfg(feature = "viz")]
+    pub(crate) fn double_strand(&self) -> bool {
+        self.double_strand
+    }
+
+    /// Builds the k-mer hashes of the targets used for banded pre-alignment.
+    pub(crate) fn target_hashes<'a>(&self, target_seqs: &'a [TargetSeq]) -> Vec<TargetHash<'a>> {
+        target_seqs
+            .iter()
+            .map(|target_seq| target_seq.build_target_hash(self.k))
+            .collect()
+    }
+
+    /// Aligns the reads in the input FASTQ across multiple threads, calling `consume` on the
+    /// result for each read in the order given by `--output-order`.
+    pub(crate) fn align_reads<F>(
+        &self,
+        builder: &Arc<Builder>,
+        target_seqs: &Arc<Vec<TargetSeq>>,
+        consume: F,
+    ) -> Result<()>
+    where
+        F: FnMut(OutputResult) -> Result<()>,
+    {
+        self.align_reads_with_shards(builder, target_seqs, None, consume)
+            .map(|_| ())
+    }
+
+    /// Aligns the reads as with [`Self::align_reads`].  When `shards` is given, each aligner
+    /// thread also writes each chunk of results it aligns, encoded with the given encoder, to its
+    /// own shard in the given directory, returning the shards to be merged, and `consume` is
+    /// called in the order in which the chunks finish aligning.  The chunks in the given
+    /// checkpoint of an interrupted run are not aligned again, and the shards are numbered after
+    /// those of the interrupted run.
+    fn align_reads_with_shards<F>(
+        &self,
+        builder: &Arc<Builder>,
+        target_seqs: &Arc<Vec<TargetSeq>>,
+        shards: Option<(&Path, ChunkEncoder, &Checkpoint)>,
+        mut consume: F,
+    ) -> Result<Vec<Shard>>
+    where
+        F: FnMut(OutputResult) -> Result<()>,
+    {
+        let inputs = self.read_inputs()?;
+        let subsample = self.subsample()?;
+        let (completed_chunks, first_shard_index) = match &shards {
+            Some((_, _, checkpoint)) => (checkpoint.chunks().clone(), checkpoint.next_index()),
+            None => (HashMap::new(), 0),
+        };
         // ensure!(self. > 1, "Must specify at least two threads");
         let progress_logger = ProgLogBuilder::new()
             .name("stitch-progress")
@@ -288,82 +1296,196 @@ impl Align {
             .count_formatter(CountFormatterKind::Comma)
             .build();
 
-        // Create the Builder - we will use this when initializing each thread to create the
-        // Aligners object, and to create the SamRecordFormatter for converting alignments to
-        // SAM format for writing.
-        let mut builder = Builder::default();
-        builder
-            .mode(self.mode)
-            .match_score(self.match_score)
-            .mismatch_score(self.mismatch_score)
-            .gap_open(self.gap_open)
-            .gap_extend(self.gap_extend)
-            .default_jump_score(self.jump_score)
-            .jump_score_same_contig_and_strand(self.jump_score_same_contig_and_strand)
-            .jump_score_same_contig_opposite_strand(self.jump_score_same_contig_opposite_strand)
-            .jump_score_inter_contig(self.jump_score_inter_contig)
-            .kmer_size(self.k)
-            .band_width(self.w)
-            .double_strand(self.double_strand)
-            .circular(self.circular)
-            .circular_slop(self.circular_slop)
-            .pre_align(self.pre_align)
-            .pre_align_min_score(self.pre_align_min_score)
-            .pre_align_subset_contigs(self.pre_align_subset_contigs)
-            .suboptimal(self.suboptimal)
-            .suboptimal_pct(self.suboptimal_pct)
-            .soft_clip(self.soft_clip)
-            .use_eq_and_x(self.use_eq_and_x)
-            .pick_primary(self.pick_primary)
-            .filter_secondary(self.filter_secondary)
-            .filter_secondary_pct(self.filter_secondary_pct);
-        // share the builder across threads
-        let builder = Arc::new(builder);
-
-        // Read in the refefence/target FASTA records - these are shared across threads
-        let target_seqs = Arc::new(target_seq::from_fasta(&self.ref_fasta, self.circular)?);
+        // Collect the QC report of the alignments as they are consumed
+        let mut qc = self
+            .qc_report
+            .as_ref()
+            .map(|_| QcCollector::new(target_seqs));
+        let mut coverage = self
+            .coverage
+            .as_ref()
+            .map(|_| CoverageTrack::new(target_seqs));
+        // Write the time spent aligning each read as it is aligned, shared by the aligner threads
+        let metrics_writer = self
+            .metrics_writer()?
+            .map(|writer| Arc::new(Mutex::new(writer)));
 
         // Create the thread to read in the FASTQ records
-        let reader =
-            FastqThreadReader::new(self.reads_fastq.clone(), self.decompress, self.threads);
+        let reader = match &self.umi {
+            Some(umi_source) => FastqThreadReader::with_umi_consensus(
+                inputs,
+                self.decompress,
+                self.threads,
+                self.bam_min_soft_clip,
+                umi_source.clone(),
+                subsample,
+                completed_chunks,
+            ),
+            None => FastqThreadReader::new(
+                inputs,
+                self.decompress,
+                self.threads,
+                self.bam_min_soft_clip,
+                subsample,
+                completed_chunks,
+            ),
+        };
 
         // Create the channel to gracefully signal a shutdown of the aligner threads
         let (shutdown_tx, shutdown_rx) = unbounded::<()>();
 
+        // Pack the contigs once, shared by the aligners of all the threads
+        let shared_contigs = Arc::new(builder.build_shared_contigs(target_seqs));
+
         // Create and start the aligner threads
         let sleep_delay = Duration::from_millis(100);
-        let thread_handles: Vec<JoinHandle<Result<()>>> = (0..self.threads)
-            .map(|_| {
+        let thread_handles: Vec<JoinHandle<Result<Option<Shard>>>> = (0..self.threads)
+            .map(|index| {
                 let to_align_rx = reader.to_align_rx.clone();
                 let shutdown_rx = shutdown_rx.clone();
-                let target_seqs = Arc::clone(&target_seqs);
-                let builder = Arc::clone(&builder);
+                let target_seqs = Arc::clone(target_seqs);
+                let builder = Arc::clone(builder);
+                let shared_contigs = Arc::clone(&shared_contigs);
                 let k = self.k;
+                let progress_min_read_length = self.progress_min_read_length;
+                let dedup_cache_size = self.dedup_cache_size;
+                let alignment_filter = self.alignment_filter();
+                let contig_set = self.contig_set.clone();
+                let jump_sites = self.jump_site_features.clone();
+                let splice_annotation = self.splice_annotation.clone();
+                let primer_trimmer = self.primer_trimmer.clone();
+                let adapter_seqs = self.adapter_seqs.clone();
+                let adapter_min_score_fraction = self.adapter_min_score_fraction;
+                let adapter_min_segment_len = self.adapter_min_segment_len;
+                let duplex = self.duplex;
+                let duplex_window = self.duplex_window;
+                let duplex_min_identity = self.duplex_min_identity;
+                let shard = shards
+                    .as_ref()
+                    .map(|(dir, encoder, _)| (dir.to_path_buf(), Arc::clone(encoder)));
+                let metrics_writer = metrics_writer.clone();
 
                 std::thread::spawn(move || {
-                    // Build target hashes to use during alignment
+                    let mut shard = match shard {
+                        Some((dir, encoder)) => {
+                            let writer = ShardWriter::create(&dir, first_shard_index + index)?;
+                            Some((writer, encoder))
+                        }
+                        None => None,
+                    };
+                    // Build target hashes to use during alignment, from the contig index if any
                     // TODO: there should be a way to do this once and share it across threads
-                    let target_hashes = target_seqs
-                        .iter()
-                        .map(|target_seq| target_seq.build_target_hash(k))
-                        .collect::<Vec<_>>();
-                    let mut aligners = builder.build_aligners(&target_seqs);
+                    let target_hashes = match &contig_set {
+                        Some(contig_set) => contig_set.target_hashes(k),
+                        None => target_seqs
+                            .iter()
+                            .map(|target_seq| target_seq.build_target_hash(k))
+                            .collect::<Vec<_>>(),
+                    };
+                    let mut aligners = builder.build_aligners_with(&shared_contigs);
+                    aligners.set_jump_sites(jump_sites);
+                    aligners.set_splice_annotation(splice_annotation);
+                    aligners.set_record_timings(metrics_writer.is_some());
+                    let mut adapter_splitter = adapter_seqs.map(|adapters| {
+                        AdapterSplitter::new(
+                            &adapters,
+                            builder.build_scoring(),
+                            adapter_min_score_fraction,
+                            adapter_min_segment_len,
+                        )
+                    });
+                    let duplex_pairer = duplex.then(|| {
+                        DuplexPairer::new(
+                            builder.build_scoring(),
+                            duplex_window,
+                            duplex_min_identity,
+                        )
+                    });
+                    let mut cache = AlignmentCache::new(dedup_cache_size);
                     loop {
                         // Try to process one chunk of alignments
                         if let Ok(msg) = to_align_rx.try_recv() {
-                            let iter = FastqGroupingIterator::new(msg.records.into_iter());
+                            let num_reads = msg.records.len();
+                            let mut records = msg.records;
+                            if let Some(splitter) = &mut adapter_splitter {
+                                records = records
+                                    .into_iter()
+                                    .flat_map(|record| splitter.split(record))
+                                    .collect();
+                            }
+                            if let Some(duplex_pairer) = &duplex_pairer {
+                                records = duplex_pairer.pair_reads(records);
+                            }
+                            if let Some(primer_trimmer) = &primer_trimmer {
+                                for record in &mut records {
+                                    primer_trimmer.trim(record);
+                                }
+                            }
+                            let iter = FastqGroupingIterator::new(records.into_iter());
                             let mut results: Vec<OutputResult> = Vec::new();
-                            for group in iter {
+                            for mut group in iter {
                                 let first = group.first().unwrap();
+                                let mut skip_reason = None;
+                                let mut read_metrics = None;
                                 let (alignments, maybe_score) =
-                                    aligners.align(first, &target_seqs, &target_hashes);
+                                    cache.get_or_align(&first.seq, || {
+                                        if let Some(min_length) = progress_min_read_length {
+                                            let callback = (first.seq.len() >= min_length)
+                                                .then(|| Self::progress_callback(&first.head));
+                                            aligners.set_progress_callback(callback);
+                                        }
+                                        let started = Instant::now();
+                                        let (mut alignments, maybe_score) =
+                                            aligners.align(first, &target_seqs, &target_hashes);
+                                        skip_reason = aligners.skip_reason();
+                                        read_metrics =
+                                            aligners.timings().map(|timings| ReadMetrics {
+                                                read_name: String::from_utf8_lossy(
+                                                    first.id_bytes(),
+                                                )
+                                                .into_owned(),
+                                                read_length: first.seq.len(),
+                                                timings: *timings,
+                                                total: started.elapsed(),
+                                            });
+                                        if !alignment_filter.is_empty() {
+                                            let query = first.seq.to_ascii_uppercase();
+                                            alignment_filter.retain(
+                                                &mut alignments,
+                                                &target_seqs,
+                                                &query,
+                                            );
+                                        }
+                                        (alignments, maybe_score)
+                                    });
+                                if let (Some(writer), Some(read_metrics)) =
+                                    (&metrics_writer, read_metrics)
+                                {
+                                    writer.lock().unwrap().write(&read_metrics)?;
+                                }
 
+                                // Mark reads that were not aligned, and attempt them again if seen
+                                if let Some(reason) = skip_reason {
+                                    warn!(
+                                        "Skipped read {} ({reason})",
+                                        String::from_utf8_lossy(first.id_bytes())
+                                    );
+                                    cache.remove(&first.seq);
+                                    for record in &mut group {
+                                        record
+                                            .head
+                                            .extend(format!("\t{SKIP_TAG}:Z:{reason}").bytes());
+                                    }
+                                }
                                 for record in group {
                                     let alignments = alignments.clone();
                                     results.push((record, alignments, maybe_score));
                                 }
                             }
 
+                            if let Some((writer, encoder)) = &mut shard {
+                                writer.write_chunk(msg.ordinal, num_reads, &encoder(&results)?)?;
+                            }
                             msg.oneshot
                                 .send(OutputMessage { results })
                                 .expect("Send failed");
@@ -374,55 +1496,55 @@ impl Align {
                             std::thread::sleep(sleep_delay);
                         }
                     }
-                    Ok(())
+                    shard.map(|(writer, _)| writer.finish()).transpose()
                 })
             })
             .collect();
 
-        // Setup and write the SAM header
-        let command_line = env::args_os().map(|s| s.into_string().unwrap()).join(" ");
-        let stdout = io::stdout().lock();
-        let encoder = bgzf::writer::Builder::default()
-            .set_compression_level(CompressionLevel::try_from(self.compression)?)
-            .build_with_writer(stdout);
-        let mut writer = BamWriter::from(encoder);
-        let header = {
-            let mut builder = SamHeader::builder().set_header(Map::default()).add_program(
-                "stitch",
-                Map::<Program>::builder()
-                    .set_name("stitch")
-                    .set_version(VERSION.clone())
-                    .set_command_line(command_line)
-                    .build()?,
-            );
-            for target_seq in target_seqs.iter() {
-                builder = builder.add_reference_sequence(
-                    target_seq.name.parse()?,
-                    Map::<ReferenceSequence>::new(NonZeroUsize::try_from(target_seq.len())?),
-                );
-            }
-            builder.build()
+        // The shards are merged in order at the end, so consume the chunks as they complete
+        let output_order = match shards {
+            Some(_) => OutputOrder::Completion,
+            None => self.output_order,
         };
-        writer.write_header(&header)?;
-
-        // Convert the alignments to SAM records
-        let record_formatter = builder.build_sam_record_formatter(&target_seqs);
+        let mut pending: Vec<Receiver<OutputMessage>> = Vec::new();
         loop {
-            // Get a receiver for the alignment of a record
-            if let Ok(receiver) = reader.to_output_rx.try_recv() {
-                let msg = receiver.recv()?;
-                for (fastq, alignments, alt_score) in msg.results {
+            // Get the alignments of the next chunk of records to output, if aligned
+            let maybe_msg = match output_order {
+                OutputOrder::Input => match reader.to_output_rx.try_recv() {
+                    Ok(receiver) => Some(receiver.recv()?),
+                    Err(_) => None,
+                },
+                OutputOrder::Completion => {
+                    pending.extend(reader.to_output_rx.try_iter());
+                    match pending
+                        .iter()
+                        .position(|rx| !rx.is_empty() || rx.is_disconnected())
+                    {
+                        Some(index) => Some(pending.remove(index).recv()?),
+                        None => None,
+                    }
+                }
+            };
+            if let Some(msg) = maybe_msg {
+                for result in msg.results {
                     progress_logger.record();
-                    let records = record_formatter.format(&fastq, &alignments, alt_score)?;
-                    for record in records {
-                        writer.write_record(&header, &record)?;
+                    if let Some(qc) = &mut qc {
+                        let (fastq, alignments, _) = &result;
+                        let query = fastq.seq.to_ascii_uppercase();
+                        qc.add(alignments.first(), target_seqs, &query);
+                    }
+                    if let Some(coverage) = &mut coverage {
+                        for alignment in &result.1 {
+                            coverage.add(alignment, target_seqs);
+                        }
                     }
-                    io::stdout().flush()?;
+                    consume(result)?;
                 }
             } else {
                 if reader.handle.is_finished()
                     && reader.to_align_rx.is_empty()
                     && reader.to_output_rx.is_empty()
+                    && pending.is_empty()
                 {
                     break;
                 }
@@ -432,21 +1554,379 @@ impl Align {
 
         // All done, shut down the reader and alignment threads
         match reader.handle.join() {
-            Ok(_) => (),
+            Ok(result) => result?,
             Err(e) => std::panic::resume_unwind(e),
         };
         drop(shutdown_tx); // to signal the alignment threads
-        thread_handles
+        let shards: Vec<Shard> = thread_handles
             .into_iter()
-            .try_for_each(|handle| match handle.join() {
+            .map(|handle| match handle.join() {
                 Ok(result) => result,
                 Err(e) => std::panic::resume_unwind(e),
-            })?;
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        if let Some(writer) = metrics_writer {
+            let writer = Arc::into_inner(writer).expect("The aligner threads are done");
+            Self::log_metrics(&writer.into_inner().unwrap().finish()?);
+        }
+        if let (Some(prefix), Some(qc)) = (&self.qc_report, qc) {
+            let mut report = qc.report(target_seqs);
+            report.run_info = Some(self.run_info(target_seqs)?);
+            Self::write_qc_report(prefix, &report)?;
+        }
+        if let (Some(prefix), Some(coverage)) = (&self.coverage, coverage) {
+            Self::write_coverage(prefix, &coverage, target_seqs)?;
+        }
+
+        Ok(shards)
+    }
+
+    /// Returns the writer of the timing metrics of each read to `--metrics-output` (or standard
+    /// error), if writing them.
+    fn metrics_writer(&self) -> Result<Option<MetricsWriter>> {
+        let Some(format) = self.metrics else {
+            return Ok(None);
+        };
+        let writer: Box<dyn Write + Send> = match &self.metrics_output {
+            Some(path) => {
+                Box::new(BufWriter::new(File::create(path).with_context(|| {
+                    format!("Error creating: {}", path.display())
+                })?))
+            }
+            None => Box::new(io::stderr()),
+        };
+        MetricsWriter::new(writer, format).map(Some)
+    }
+
+    /// Logs the time spent in each stage of aligning the reads, and the slowest read.
+    fn log_metrics(summary: &MetricsSummary) {
+        info!(
+            "Spent {:.3}s aligning {} reads",
+            summary.total.as_secs_f64(),
+            summary.reads
+        );
+        for stage in Stage::ALL {
+            info!(
+                "  {stage}: {:.3}s ({:.1}%)",
+                summary.timings.get(stage).as_secs_f64(),
+                100.0 * summary.fraction(stage)
+            );
+        }
+        if let Some((read_name, total)) = &summary.slowest {
+            info!("Slowest read: {read_name} ({:.3}s)", total.as_secs_f64());
+        }
+    }
+
+    /// Writes the coverage of each strand in bedGraph format to the outputs with the given prefix.
+    fn write_coverage(
+        prefix: &Path,
+        coverage: &CoverageTrack,
+        target_seqs: &[TargetSeq],
+    ) -> Result<()> {
+        for (suffix, is_forward) in [(".fwd.bedGraph", true), (".rev.bedGraph", false)] {
+            let path = output_path(prefix, suffix);
+            info!("Writing the coverage to {}", path.display());
+            let mut writer = BufWriter::new(
+                File::create(&path)
+                    .with_context(|| format!("Error creating: {}", path.display()))?,
+            );
+            coverage.write_bedgraph(&mut writer, target_seqs, is_forward)?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the QC report as JSON and text to the outputs with the given prefix.
+    fn write_qc_report(prefix: &Path, report: &QcReport) -> Result<()> {
+        let json_path = output_path(prefix, ".json");
+        info!("Writing the QC report to {}", json_path.display());
+        std::fs::write(&json_path, report.to_json()?)
+            .with_context(|| format!("Error writing: {}", json_path.display()))?;
+        let text_path = output_path(prefix, ".txt");
+        let mut writer = BufWriter::new(
+            File::create(&text_path)
+                .with_context(|| format!("Error creating: {}", text_path.display()))?,
+        );
+        report.write_text(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns a callback that logs the progress of aligning the read with the given name, every
+    /// 10% of the read.
+    fn progress_callback(read_name: &[u8]) -> ProgressCallback {
+        let read_name = String::from_utf8_lossy(read_name).into_owned();
+        Arc::new(move |column, num_columns| {
+            let step = num_columns.div_ceil(10);
+            if column % step == 0 || column == num_columns {
+                info!(
+                    "Aligned {column}/{num_columns} bases ({}%) of read {read_name}",
+                    100 * column / num_columns
+                );
+            }
+        })
+    }
+
+    /// Executes the align command
+    pub fn execute(&self) -> anyhow::Result<()> {
+        ensure!(
+            !self.resume || self.shard_dir.is_some(),
+            "--resume requires --shard-dir"
+        );
+        ensure!(
+            self.metrics.is_some() || self.metrics_output.is_none(),
+            "--metrics-output requires --metrics"
+        );
+        let align = self.with_config_file()?;
+        info!("Starting alignment...");
+        // share the builder and the targets across threads
+        let builder = Arc::new(align.builder());
+        let target_seqs = Arc::new(align.target_seqs()?);
+
+        // Setup and write the SAM header
+        let run_info = align.run_info(&target_seqs)?;
+        let stdout = io::stdout().lock();
+        let encoder = bgzf::writer::Builder::default()
+            .set_compression_level(CompressionLevel::try_from(align.compression)?)
+            .build_with_writer(stdout);
+        let mut writer = BamWriter::from(encoder);
+        let header = {
+            let mut builder = SamHeader::builder().set_header(Map::default()).add_program(
+                "stitch",
+                Map::<Program>::builder()
+                    .set_name("stitch")
+                    .set_version(run_info.version.clone())
+                    .set_command_line(run_info.command_line.clone())
+                    .build()?,
+            );
+            for comment in run_info.header_comments()? {
+                builder = builder.add_comment(comment);
+            }
+            for reference in &run_info.references {
+                let mut reference_sequence =
+                    Map::<ReferenceSequence>::new(NonZeroUsize::try_from(reference.length)?);
+                *reference_sequence.md5_checksum_mut() = Some(reference.md5.parse()?);
+                builder =
+                    builder.add_reference_sequence(reference.name.parse()?, reference_sequence);
+            }
+            for read_group in &run_info.read_groups {
+                let mut map = Map::<SamReadGroup>::builder();
+                if let Some(sample) = &read_group.sample {
+                    map = map.set_sample(sample.clone());
+                }
+                builder = builder.add_read_group(read_group.id.clone(), map.build()?);
+            }
+            builder.build()
+        };
+        writer.write_header(&header)?;
+
+        if let Some(shard_dir) = &align.shard_dir {
+            return align.execute_sharded(&builder, &target_seqs, header, writer, shard_dir);
+        }
+
+        // Convert the alignments to SAM records
+        let record_formatter = builder.build_sam_record_formatter(&target_seqs);
+        align.align_reads(&builder, &target_seqs, |(fastq, alignments, alt_score)| {
+            let mut records = record_formatter.format(&fastq, &alignments, alt_score)?;
+            if align.umi.is_some() {
+                add_umi_tags(&fastq, &mut records)?;
+            }
+            add_skip_tag(&fastq, &mut records)?;
+            add_read_group_tag(&fastq, &mut records)?;
+            add_primer_tag(&fastq, &mut records)?;
+            add_adapter_tags(&fastq, &mut records)?;
+            add_duplex_tag(&fastq, &mut records)?;
+            for record in records {
+                writer.write_record(&header, &record)?;
+            }
+            io::stdout().flush()?;
+            Ok(())
+        })
+    }
 
+    /// Aligns the reads with each thread writing the BGZF-compressed BAM records of the chunks of
+    /// reads it aligns to its own shard in the given directory, then merges the shards after the
+    /// header written to the output, followed by the BGZF end-of-file marker.  With `--resume`,
+    /// the chunks in the shards of an interrupted run are merged rather than aligned again,
+    /// otherwise any such shards are removed.
+    fn execute_sharded<W: Write>(
+        &self,
+        builder: &Arc<Builder>,
+        target_seqs: &Arc<Vec<TargetSeq>>,
+        header: SamHeader,
+        mut writer: BamWriter<bgzf::Writer<W>>,
+        shard_dir: &Path,
+    ) -> Result<()> {
+        std::fs::create_dir_all(shard_dir)
+            .with_context(|| format!("Error creating: {}", shard_dir.display()))?;
+        let mut checkpoint = Checkpoint::load(shard_dir)?;
+        if self.resume {
+            ensure!(
+                self.qc_report.is_none() && self.coverage.is_none(),
+                "--resume is not supported with --qc-report or --coverage"
+            );
+            info!(
+                "Resuming after {} reads in {} chunks from {}",
+                checkpoint.num_reads(),
+                checkpoint.num_chunks(),
+                shard_dir.display()
+            );
+        } else {
+            if checkpoint.num_chunks() > 0 {
+                warn!(
+                    "Removing the shards of a previous run from {}",
+                    shard_dir.display()
+                );
+            }
+            checkpoint.remove()?;
+            checkpoint = Checkpoint::default();
+        }
+        let encoder: ChunkEncoder = {
+            let builder = Arc::clone(builder);
+            let target_seqs = Arc::clone(target_seqs);
+            let compression = CompressionLevel::try_from(self.compression)?;
+            let umi = self.umi.is_some();
+            Arc::new(move |results: &[OutputResult]| {
+                let record_formatter = builder.build_sam_record_formatter(&target_seqs);
+                let encoder = bgzf::writer::Builder::default()
+                    .set_compression_level(compression)
+                    .build_with_writer(Vec::new());
+                let mut chunk_writer = BamWriter::from(encoder);
+                for (fastq, alignments, alt_score) in results {
+                    let mut records = record_formatter.format(fastq, alignments, *alt_score)?;
+                    if umi {
+                        add_umi_tags(fastq, &mut records)?;
+                    }
+                    add_skip_tag(fastq, &mut records)?;
+                    add_read_group_tag(fastq, &mut records)?;
+                    add_primer_tag(fastq, &mut records)?;
+                    add_adapter_tags(fastq, &mut records)?;
+                    add_duplex_tag(fastq, &mut records)?;
+                    for record in records {
+                        chunk_writer.write_record(&header, &record)?;
+                    }
+                }
+                // Compress the last block, but do not add the end-of-file marker
+                chunk_writer.get_mut().flush()?;
+                Ok(chunk_writer.into_inner().into_inner())
+            })
+        };
+        let shards = self.align_reads_with_shards(
+            builder,
+            target_seqs,
+            Some((shard_dir, encoder, &checkpoint)),
+            |_| Ok(()),
+        )?;
+        let shards = checkpoint
+            .into_shards()
+            .into_iter()
+            .chain(shards)
+            .collect::<Vec<_>>();
+
+        info!("Merging {} shards to the output", shards.len());
+        writer.get_mut().flush()?;
+        let mut output = writer.into_inner().into_inner();
+        merge_shards(shards, self.output_order, &mut output)?;
+        bgzf::Writer::new(output).finish()?;
         Ok(())
     }
 }
 
+/// Returns the path of the output with the given prefix and suffix.
+pub(crate) fn output_path(prefix: &Path, suffix: &str) -> PathBuf {
+    let mut path: OsString = prefix.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Adds the UMI and family size of a consensus read, from the comment of its header (see
+/// [`stitch::align::umi::UmiFamily::consensus`]), to each of its records.
+fn add_umi_tags(fastq: &FastqOwnedRecord, records: &mut [SamRecord]) -> Result<()> {
+    let head = fastq.head();
+    let umi = comment_tag(head, UMI_TAG).context("consensus read is missing its UMI")?;
+    let umi = String::from_utf8(umi.to_vec())?;
+    let family_size: i32 = comment_tag(head, FAMILY_SIZE_TAG)
+        .context("consensus read is missing its family size")
+        .and_then(|size| Ok(std::str::from_utf8(size)?.parse()?))?;
+    for record in records {
+        let data = record.data_mut();
+        data.insert(UMI_TAG.parse()?, Value::String(umi.clone()));
+        data.insert(FAMILY_SIZE_TAG.parse()?, Value::from(family_size));
+    }
+    Ok(())
+}
+
+/// Adds the reason a read was not aligned, from the comment of its header, to its records (see
+/// [`SKIP_TAG`]).
+fn add_skip_tag(fastq: &FastqOwnedRecord, records: &mut [SamRecord]) -> Result<()> {
+    if let Some(reason) = comment_tag(fastq.head(), SKIP_TAG) {
+        let reason = String::from_utf8(reason.to_vec())?;
+        for record in records {
+            let data = record.data_mut();
+            data.insert(SKIP_TAG.parse()?, Value::String(reason.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Adds the ID of the read group of a read, from the comment of its header, to its records (see
+/// [`READ_GROUP_TAG`]).
+fn add_read_group_tag(fastq: &FastqOwnedRecord, records: &mut [SamRecord]) -> Result<()> {
+    if let Some(id) = ReadGroup::id_of(fastq.head()) {
+        let id = String::from_utf8(id.to_vec())?;
+        for record in records {
+            let data = record.data_mut();
+            data.insert(READ_GROUP_TAG.parse()?, Value::String(id.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Adds the primers trimmed (or masked) from a read, from the comment of its header, to its
+/// records (see [`PRIMER_TAG`]).
+fn add_primer_tag(fastq: &FastqOwnedRecord, records: &mut [SamRecord]) -> Result<()> {
+    if let Some(primers) = comment_tag(fastq.head(), PRIMER_TAG) {
+        let primers = String::from_utf8(primers.to_vec())?;
+        for record in records {
+            let data = record.data_mut();
+            data.insert(PRIMER_TAG.parse()?, Value::String(primers.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Adds the segment of a read split at adapters, and the adapters found in the read, from the
+/// comment of its header, to its records (see [`SEGMENT_TAG`] and [`ADAPTER_TAG`]).
+fn add_adapter_tags(fastq: &FastqOwnedRecord, records: &mut [SamRecord]) -> Result<()> {
+    for tag in [SEGMENT_TAG, ADAPTER_TAG] {
+        if let Some(value) = comment_tag(fastq.head(), tag) {
+            let value = String::from_utf8(value.to_vec())?;
+            for record in records.iter_mut() {
+                let data = record.data_mut();
+                data.insert(tag.parse()?, Value::String(value.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds the name of the complement read of a duplex consensus read, from the comment of its
+/// header, to its records (see [`DUPLEX_TAG`]).
+fn add_duplex_tag(fastq: &FastqOwnedRecord, records: &mut [SamRecord]) -> Result<()> {
+    if let Some(complement) = comment_tag(fastq.head(), DUPLEX_TAG) {
+        let complement = String::from_utf8(complement.to_vec())?;
+        for record in records {
+            let data = record.data_mut();
+            data.insert(DUPLEX_TAG.parse()?, Value::String(complement.clone()));
+        }
+    }
+    Ok(())
+}
+
 impl Command for Align {
     fn execute(&self) -> anyhow::Result<()> {
         Align::execute(self)
@@ -455,13 +1935,427 @@ impl Command for Align {
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
+
     use clap::Parser;
+    use noodles::sam::{
+        alignment::Record as SamRecord,
+        record::data::field::{Tag, Value},
+    };
+    use seq_io::fastq::OwnedRecord as FastqOwnedRecord;
+    use stitch::align::{
+        adapters::{ADAPTER_TAG, SEGMENT_TAG},
+        config::AlignerConfig,
+        duplex::DUPLEX_TAG,
+        metrics::MetricsFormat,
+        primers::PRIMER_TAG,
+        read_group::{ReadGroup, READ_GROUP_TAG},
+        strategy::AutoStrategy,
+        subsample::Subsample,
+        umi::{UmiSource, FAMILY_SIZE_TAG, UMI_TAG},
+        AlignmentMode, ClipPolicy, PrimaryPickingStrategy, Scoring, TracebackLayout, SKIP_TAG,
+    };
 
-    use super::Align;
+    use super::{
+        add_adapter_tags, add_duplex_tag, add_primer_tag, add_read_group_tag, add_skip_tag,
+        add_umi_tags, Align,
+    };
 
     /// Check that the argument parser works
     #[test]
     fn test_parse() {
         Align::parse_from(["align", "-f", ".", "-r", "."]);
     }
+
+    /// Check that resuming a run requires its shards
+    #[test]
+    fn test_resume_requires_shard_dir() {
+        let align = Align::parse_from(["align", "-f", ".", "-r", ".", "--resume"]);
+        let error = align.execute().unwrap_err();
+        assert_eq!(error.to_string(), "--resume requires --shard-dir");
+    }
+
+    /// Check that writing the timing metrics to a file requires their format
+    #[test]
+    fn test_metrics() {
+        let args = ["align", "-f", ".", "-r", ".", "--metrics", "TSV"];
+        assert_eq!(Align::parse_from(args).metrics, Some(MetricsFormat::Tsv));
+        let args = ["align", "-f", ".", "-r", ".", "--metrics-output", "m.tsv"];
+        let error = Align::parse_from(args).execute().unwrap_err();
+        assert_eq!(error.to_string(), "--metrics-output requires --metrics");
+    }
+
+    /// Check that the subsampling options are validated
+    #[test]
+    fn test_subsample() {
+        let align = Align::parse_from(["align", "-f", ".", "-r", "."]);
+        assert!(align.subsample().unwrap().is_none());
+        let args = [
+            "align",
+            "-f",
+            ".",
+            "-r",
+            ".",
+            "--max-reads",
+            "100",
+            "--subsample-fraction",
+            "0.1",
+            "--seed",
+            "7",
+        ];
+        assert_eq!(
+            Align::parse_from(args).subsample().unwrap(),
+            Subsample::new(Some(100), Some(0.1), 7).unwrap()
+        );
+        let args = ["align", "-f", ".", "-r", ".", "--subsample-fraction", "2"];
+        assert!(Align::parse_from(args).subsample().is_err());
+    }
+
+    /// Check that the UMI and family size of a consensus read are added to its records
+    #[test]
+    fn test_add_umi_tags() {
+        let align = Align::parse_from(["align", "-f", ".", "-r", ".", "--umi", "name"]);
+        assert_eq!(align.umi, Some(UmiSource::ReadName));
+
+        let fastq = FastqOwnedRecord {
+            head: b"r1:ACGT RX:Z:ACGT cD:i:3".to_vec(),
+            seq: b"ACGT".to_vec(),
+            qual: b"IIII".to_vec(),
+        };
+        let mut records = vec![SamRecord::default(), SamRecord::default()];
+        add_umi_tags(&fastq, &mut records).unwrap();
+        let umi_tag: Tag = UMI_TAG.parse().unwrap();
+        let family_size_tag: Tag = FAMILY_SIZE_TAG.parse().unwrap();
+        for record in records {
+            assert_eq!(
+                record.data().get(&umi_tag),
+                Some(&Value::String("ACGT".to_string()))
+            );
+            assert_eq!(record.data().get(&family_size_tag), Some(&Value::from(3)));
+        }
+    }
+
+    /// Check that the reason a read was not aligned is added to its record
+    #[test]
+    fn test_add_skip_tag() {
+        let skip_tag: Tag = SKIP_TAG.parse().unwrap();
+        let mut fastq = FastqOwnedRecord {
+            head: b"r1".to_vec(),
+            seq: b"ACGT".to_vec(),
+            qual: b"IIII".to_vec(),
+        };
+        let mut records = vec![SamRecord::default()];
+        add_skip_tag(&fastq, &mut records).unwrap();
+        assert_eq!(records[0].data().get(&skip_tag), None);
+
+        fastq.head = b"r1\tsk:Z:timeout".to_vec();
+        add_skip_tag(&fastq, &mut records).unwrap();
+        assert_eq!(
+            records[0].data().get(&skip_tag),
+            Some(&Value::String("timeout".to_string()))
+        );
+    }
+
+    /// Check that the read group of a read is added to its records, and that the inputs are
+    /// assigned their read groups
+    #[test]
+    fn test_add_read_group_tag() {
+        let read_group_tag: Tag = READ_GROUP_TAG.parse().unwrap();
+        let fastq = FastqOwnedRecord {
+            head: b"r1 comment\tRG:Z:rg1".to_vec(),
+            seq: b"ACGT".to_vec(),
+            qual: b"IIII".to_vec(),
+        };
+        let mut records = vec![SamRecord::default(), SamRecord::default()];
+        add_read_group_tag(&fastq, &mut records).unwrap();
+        for record in records {
+            assert_eq!(
+                record.data().get(&read_group_tag),
+                Some(&Value::String("rg1".to_string()))
+            );
+        }
+
+        let args = [
+            "align",
+            "-f",
+            "a.fq",
+            "b.fq",
+            "-r",
+            ".",
+            "--read-group",
+            "rg1",
+            "--read-group",
+            "rg2",
+            "--sample",
+            "s1",
+        ];
+        let inputs = Align::parse_from(args).read_inputs().unwrap();
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(
+            inputs[1].read_group,
+            Some(ReadGroup::new("rg2", Some("s1")))
+        );
+        let args = [
+            "align", "-f", "a.fq", "b.fq", "-r", ".", "--sample", "s1", "--sample", "s2",
+            "--sample", "s3",
+        ];
+        assert!(Align::parse_from(args).read_inputs().is_err());
+    }
+
+    /// Check that the primers trimmed from a read are added to its records
+    #[test]
+    fn test_add_primer_tag() {
+        let primer_tag: Tag = PRIMER_TAG.parse().unwrap();
+        let mut fastq = FastqOwnedRecord {
+            head: b"r1".to_vec(),
+            seq: b"ACGT".to_vec(),
+            qual: b"IIII".to_vec(),
+        };
+        let mut records = vec![SamRecord::default(), SamRecord::default()];
+        add_primer_tag(&fastq, &mut records).unwrap();
+        assert_eq!(records[0].data().get(&primer_tag), None);
+
+        fastq.head = b"r1\tpt:Z:fwd:0-20,rev:480-500".to_vec();
+        add_primer_tag(&fastq, &mut records).unwrap();
+        for record in &records {
+            assert_eq!(
+                record.data().get(&primer_tag),
+                Some(&Value::String("fwd:0-20,rev:480-500".to_string()))
+            );
+        }
+    }
+
+    /// Check that the segment and adapters of a read split at adapters are added to its records
+    #[test]
+    fn test_add_adapter_tags() {
+        let segment_tag: Tag = SEGMENT_TAG.parse().unwrap();
+        let adapter_tag: Tag = ADAPTER_TAG.parse().unwrap();
+        let mut fastq = FastqOwnedRecord {
+            head: b"r1".to_vec(),
+            seq: b"ACGT".to_vec(),
+            qual: b"IIII".to_vec(),
+        };
+        let mut records = vec![SamRecord::default(), SamRecord::default()];
+        add_adapter_tags(&fastq, &mut records).unwrap();
+        assert_eq!(records[0].data().get(&segment_tag), None);
+        assert_eq!(records[0].data().get(&adapter_tag), None);
+
+        fastq.head = b"r1_2\tsg:Z:2/2:528-1000\tad:Z:adapter+:500-528".to_vec();
+        add_adapter_tags(&fastq, &mut records).unwrap();
+        for record in &records {
+            assert_eq!(
+                record.data().get(&segment_tag),
+                Some(&Value::String("2/2:528-1000".to_string()))
+            );
+            assert_eq!(
+                record.data().get(&adapter_tag),
+                Some(&Value::String("adapter+:500-528".to_string()))
+            );
+        }
+    }
+
+    /// Check that the complement read of a duplex consensus read is added to its records
+    #[test]
+    fn test_add_duplex_tag() {
+        let duplex_tag: Tag = DUPLEX_TAG.parse().unwrap();
+        let mut fastq = FastqOwnedRecord {
+            head: b"r1".to_vec(),
+            seq: b"ACGT".to_vec(),
+            qual: b"IIII".to_vec(),
+        };
+        let mut records = vec![SamRecord::default()];
+        add_duplex_tag(&fastq, &mut records).unwrap();
+        assert_eq!(records[0].data().get(&duplex_tag), None);
+
+        fastq.head = b"r1\tdx:Z:r2".to_vec();
+        add_duplex_tag(&fastq, &mut records).unwrap();
+        assert_eq!(
+            records[0].data().get(&duplex_tag),
+            Some(&Value::String("r2".to_string()))
+        );
+    }
+
+    /// Check that the defaults of the command line are the defaults of the configuration
+    #[test]
+    fn test_config_defaults() {
+        let align = Align::parse_from(["align", "-f", ".", "-r", "."]);
+        assert_eq!(align.config(), AlignerConfig::default());
+    }
+
+    /// Check that every parameter is set from the configuration
+    #[test]
+    fn test_set_config() {
+        let mut config = AlignerConfig {
+            mode: AlignmentMode::Global,
+            match_score: 2,
+            mismatch_score: -3,
+            gap_open: -5,
+            gap_extend: -1,
+            default_jump_score: -11,
+            jump_score_same_contig_and_strand: Some(-12),
+            jump_score_same_contig_opposite_strand: Some(-13),
+            jump_score_inter_contig: Some(-14),
+            jump_score_forward: Some(-15),
+            jump_score_backward: Some(-16),
+            splice_jump_score: Some(-7),
+            min_segment_length: Some(5),
+            forbid_clip_jumps: true,
+            scaffold_gap_min_len: Some(100),
+            homopolymer_gap_open: Some(-2),
+            homopolymer_min_length: 4,
+            kmer_size: 10,
+            band_width: 40,
+            adaptive_band_width: Some(30),
+            parallel_block_rows: Some(1000),
+            double_strand: true,
+            circular: true,
+            lazy_reverse_complement: true,
+            canonical_contig_order: true,
+            circular_slop: 30,
+            pre_align: true,
+            pre_align_min_score: 50,
+            pre_align_subset_contigs: false,
+            pre_filter: true,
+            pre_filter_min_score: 60,
+            suboptimal: true,
+            suboptimal_pct: 5.0,
+            clip_policy: ClipPolicy::Trimmed,
+            use_eq_and_x: true,
+            pick_primary: PrimaryPickingStrategy::Score,
+            filter_secondary: true,
+            filter_secondary_pct: 15.0,
+            max_matrix_cells: Some(1_000_000),
+            truncate_oversized_reads: true,
+            read_timeout_ms: Some(500),
+            chunk_length: Some(10_000),
+            chunk_overlap: 500,
+            traceback_checkpoint_interval: Some(64),
+            traceback_layout: TracebackLayout::ColumnMajor,
+            max_read_memory: Some(1 << 30),
+            auto_strategy: Some(AutoStrategy {
+                band_width: 20,
+                ..AutoStrategy::default()
+            }),
+            refine_jumps: true,
+            refine_jumps_window: 10,
+            score_breakdown: true,
+            ..AlignerConfig::default()
+        };
+        config.filter.min_score = Some(20);
+        config.filter.min_identity = Some(0.8);
+        config.filter.min_aligned_length = Some(100);
+        config.filter.max_jumps = Some(3);
+        let mut align = Align::parse_from(["align", "-f", ".", "-r", "."]);
+        align.set_config(&config);
+        assert_eq!(align.config(), config);
+    }
+
+    /// Check that `--soft-clip` overrides the clip policy
+    #[test]
+    fn test_clip_policy() {
+        let args = ["align", "-f", ".", "-r", ".", "--clip-policy", "trimmed"];
+        assert_eq!(
+            Align::parse_from(args).config().clip_policy,
+            ClipPolicy::Trimmed
+        );
+        let args = [
+            "align",
+            "-f",
+            ".",
+            "-r",
+            ".",
+            "--clip-policy",
+            "trimmed",
+            "-S",
+        ];
+        assert_eq!(
+            Align::parse_from(args).config().clip_policy,
+            ClipPolicy::Soft
+        );
+        let args = ["align", "-f", ".", "-r", "."];
+        assert_eq!(
+            Align::parse_from(args).config().clip_policy,
+            ClipPolicy::Hard
+        );
+    }
+
+    /// Check that explicit scores override those of the preset
+    #[test]
+    fn test_preset() {
+        let args = ["align", "-f", ".", "-r", ".", "--preset", "ont", "-B", "-3"];
+        let config = Align::parse_from(args).config();
+        let mut expected = AlignerConfig::default();
+        expected.set_scoring(&Scoring::preset_ont());
+        assert_eq!(config.match_score, 2);
+        assert_eq!(config.default_jump_score, -30);
+        assert_eq!(config.mismatch_score, -3);
+        assert_eq!(
+            config,
+            AlignerConfig {
+                mismatch_score: -3,
+                ..expected
+            }
+        );
+    }
+
+    /// Check that the parameters in the config file override those on the command line
+    #[test]
+    fn test_with_config_file() {
+        let dir = std::env::temp_dir().join(format!("stitch-test-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let mut file = std::fs::File::create(&path).unwrap();
+        writeln!(file, "match_score = 2\n\n[filter]\nmax_jumps = 1").unwrap();
+
+        let path = path.to_str().unwrap();
+        let args = [
+            "align", "-f", ".", "-r", ".", "-A", "3", "-B", "-2", "--config", path,
+        ];
+        let align = Align::parse_from(args).with_config_file().unwrap();
+        let config = align.config();
+        assert_eq!(config.match_score, 2);
+        assert_eq!(config.mismatch_score, -2);
+        assert_eq!(config.filter.max_jumps, Some(1));
+        assert!(align.config.is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Check that pathological scores are rejected rather than giving nonsensical alignments
+    #[test]
+    fn test_with_config_file_validates_scoring() {
+        let args = ["align", "-f", ".", "-r", ".", "-A", "3", "--jump-score=-2"];
+        let message = Align::parse_from(args)
+            .with_config_file()
+            .unwrap_err()
+            .to_string();
+        assert!(message.starts_with("The jump_score_same_contig_and_strand score must be"));
+    }
+
+    /// Check that the contig index is built on first use, then read instead of the reference
+    #[test]
+    fn test_with_index() {
+        let dir = std::env::temp_dir().join(format!("stitch-test-index-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fasta = dir.join("ref.fa");
+        std::fs::write(&fasta, ">chr1\nACGTACGTTTGACA\n>chr2\nTTTTGGGGAACC\n").unwrap();
+        let index = dir.join("ref.stitch");
+
+        let (fasta, index) = (fasta.to_str().unwrap(), index.to_str().unwrap());
+        let args = ["align", "-f", ".", "-r", fasta, "--index", index, "-A", "2"];
+        let align = Align::parse_from(args).with_config_file().unwrap();
+        let target_seqs = align.target_seqs().unwrap();
+        assert_eq!(target_seqs.len(), 2);
+
+        // The reference is no longer read, and the scoring of the index is the default
+        std::fs::remove_file(fasta).unwrap();
+        let args = [
+            "align", "-f", ".", "-r", fasta, "--index", index, "-B", "-3",
+        ];
+        let align = Align::parse_from(args).with_config_file().unwrap();
+        assert_eq!(align.target_seqs().unwrap(), target_seqs);
+        let config = align.config();
+        assert_eq!(config.match_score, 2);
+        assert_eq!(config.mismatch_score, -3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,118 @@
+use super::{
+    align::{output_path, Align},
+    command::Command,
+};
+use anyhow::{Context, Result};
+use clap::Parser;
+use log::info;
+use seq_io::fastq::Record;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use stitch::{
+    align::all_vs_all::{ModelComparison, ModelComparisonSummary},
+    util::version::built_info,
+};
+
+/// Compares the jump-aware alignment of each read with its alignment to each reference
+/// individually, without jumps.
+///
+/// The reads are aligned as with `align` (see `stitch align --help` for the alignment options),
+/// and each read is also aligned to every reference (and strand, with `--double-strand`) on its
+/// own with jumps disabled, as a conventional alignment would.  The difference between the score
+/// of the jump-aware alignment and the best per-reference alignment quantifies how much the
+/// chimeric model improves the alignment of the read.  The output options of `align` are
+/// ignored.
+///
+/// The score of each read against each reference and strand is written to
+/// `<output-prefix>.all_vs_all.txt`, and the best per-reference alignment, the jump-aware
+/// alignment and the improvement of each read are written to `<output-prefix>.comparison.txt`.
+/// The number of reads improved by the jump-aware alignment, and the mean improvement, are
+/// written to `<output-prefix>.summary.txt`.
+#[derive(Parser, Debug, Clone)]
+#[clap(version = built_info::VERSION.as_str(), term_width=0)]
+pub struct AllVsAll {
+    #[clap(flatten)]
+    align: Align,
+
+    /// The prefix of the output files.
+    #[clap(long, short = 'o', display_order = 26)]
+    output_prefix: PathBuf,
+}
+
+impl AllVsAll {
+    /// Executes the all-vs-all command
+    pub fn execute(&self) -> Result<()> {
+        info!("Starting all-vs-all alignment...");
+        let align = self.align.with_config_file()?;
+        let builder = Arc::new(align.builder());
+        let target_seqs = Arc::new(align.target_seqs()?);
+        let mut per_contig_aligner = builder.build_per_contig_aligner(&target_seqs);
+
+        let create = |suffix: &str| -> Result<BufWriter<File>> {
+            let path = output_path(&self.output_prefix, suffix);
+            let file = File::create(&path)
+                .with_context(|| format!("Error creating output: {}", path.display()))?;
+            Ok(BufWriter::new(file))
+        };
+        let mut scores_writer = create(".all_vs_all.txt")?;
+        writeln!(scores_writer, "read\tcontig\tstrand\tscore")?;
+        let mut comparison_writer = create(".comparison.txt")?;
+        ModelComparison::write_header(&mut comparison_writer)?;
+
+        let mut summary = ModelComparisonSummary::default();
+        align.align_reads(&builder, &target_seqs, |(fastq, alignments, _)| {
+            let read_name = read_name(fastq.head());
+            let query = fastq.seq().to_ascii_uppercase();
+            let per_contig = per_contig_aligner.align(&query);
+            let comparison = ModelComparison::new(&per_contig, alignments.first());
+            comparison.write_contig_scores(&mut scores_writer, &read_name, &target_seqs)?;
+            comparison.write_row(&mut comparison_writer, &read_name, &target_seqs)?;
+            summary.add(&comparison);
+            Ok(())
+        })?;
+        scores_writer.flush()?;
+        comparison_writer.flush()?;
+
+        info!(
+            "The jump-aware alignment improved {} of {} compared reads",
+            summary.improved(),
+            summary.compared(),
+        );
+        let mut writer = create(".summary.txt")?;
+        summary.write_table(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Returns the read name, being the first whitespace-delimited field of the FASTQ header.
+pub(crate) fn read_name(head: &[u8]) -> String {
+    String::from_utf8_lossy(head)
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+impl Command for AllVsAll {
+    fn execute(&self) -> Result<()> {
+        AllVsAll::execute(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::AllVsAll;
+
+    /// Check that the argument parser works
+    #[test]
+    fn test_parse() {
+        AllVsAll::parse_from(["all-vs-all", "-f", ".", "-r", ".", "-o", "."]);
+    }
+}
@@ -0,0 +1,169 @@
+use super::{align::Align, all_vs_all::read_name, command::Command};
+use anyhow::{ensure, Context, Result};
+use clap::Parser;
+use log::info;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use stitch::{
+    align::{
+        breakpoints::{BreakpointCounter, SampleBreakpointCounter},
+        read_group::SampleIndex,
+        Builder,
+    },
+    util::{genbank::FeatureAnnotations, target_seq::TargetSeq, version::built_info},
+};
+
+/// Reports the breakpoints given by the jumps in the alignments of long reads against a
+/// reference/expected vector/plasmid/construct.
+///
+/// The reads are aligned as with `align` (see `stitch align --help` for the alignment options),
+/// but instead of writing the alignments, each jump is converted into a breakpoint, namely the
+/// last reference position aligned before the jump and the first reference position aligned
+/// after the jump, along with the strand of each.  A breakpoint observed on the opposite strand
+/// of a read is counted as the same breakpoint.  The output options of `align` are ignored.
+///
+/// The output is in BEDPE format, with one line per breakpoint, and the number of reads supporting
+/// the breakpoint in the score column.  Breakpoints are sorted by the first reference and
+/// position, then the second reference and position.  When the reference is a GenBank or EMBL
+/// file, two more columns have the names of the features containing the first and second position
+/// respectively (e.g. a junction inside the CMV promoter), or `.` if none.
+///
+/// With `--per-sample`, the reads of many samples (e.g. one FASTQ per sample, see "Read groups" in
+/// `stitch align --help`) are aligned in a single run against the same reference, and the output
+/// is a matrix of the breakpoints across the samples: the BEDPE has a header line (starting with
+/// `#`) followed by one more column per sample, named by the sample, with the number of reads of
+/// the sample supporting the breakpoint.  The score column has the number of reads across all the
+/// samples, to which `--min-reads` applies.
+#[derive(Parser, Debug, Clone)]
+#[clap(version = built_info::VERSION.as_str(), term_width=0)]
+pub struct Breakpoints {
+    #[clap(flatten)]
+    align: Align,
+
+    /// The path to the output BEDPE.
+    #[clap(long, short = 'o', display_order = 26)]
+    output: PathBuf,
+
+    /// The minimum number of reads supporting a breakpoint for it to be output.
+    #[clap(long, default_value = "1", display_order = 27)]
+    min_reads: usize,
+
+    /// Count the reads supporting each breakpoint in each sample (requires `--sample` or
+    /// `--read-group`).
+    #[clap(long, default_value = "false", display_order = 28)]
+    per_sample: bool,
+}
+
+impl Breakpoints {
+    /// Executes the breakpoints command
+    pub fn execute(&self) -> Result<()> {
+        info!("Starting breakpoint detection...");
+        let align = self.align.with_config_file()?;
+        let builder = Arc::new(align.builder());
+        let target_seqs = Arc::new(align.target_seqs()?);
+        let annotations = align.annotations()?;
+
+        if self.per_sample {
+            return self.execute_per_sample(&align, &builder, &target_seqs, annotations);
+        }
+
+        let mut counter = BreakpointCounter::new();
+        align.align_reads(&builder, &target_seqs, |(_, alignments, _)| {
+            counter.add(&alignments, &target_seqs);
+            Ok(())
+        })?;
+
+        info!(
+            "Writing {} breakpoints to {}",
+            counter.len(),
+            self.output.display()
+        );
+        let mut writer = BufWriter::new(File::create(&self.output)?);
+        counter.write_bedpe(
+            &mut writer,
+            &target_seqs,
+            self.min_reads,
+            annotations.as_ref(),
+        )?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Counts the reads supporting each breakpoint in each sample, and writes the matrix of the
+    /// breakpoints across the samples.
+    fn execute_per_sample(
+        &self,
+        align: &Align,
+        builder: &Arc<Builder>,
+        target_seqs: &Arc<Vec<TargetSeq>>,
+        annotations: Option<FeatureAnnotations>,
+    ) -> Result<()> {
+        let samples = SampleIndex::new(&align.read_groups()?);
+        ensure!(
+            !samples.is_empty(),
+            "--per-sample requires --sample or --read-group"
+        );
+        let mut counter = SampleBreakpointCounter::new(samples.samples());
+        align.align_reads(builder, target_seqs, |(fastq, alignments, _)| {
+            let sample_idx = samples
+                .index_of(&fastq.head)
+                .with_context(|| format!("No sample for read: {}", read_name(&fastq.head)))?;
+            counter.add(sample_idx, &alignments, target_seqs);
+            Ok(())
+        })?;
+
+        info!(
+            "Writing {} breakpoints across {} samples to {}",
+            counter.len(),
+            samples.len(),
+            self.output.display()
+        );
+        let mut writer = BufWriter::new(File::create(&self.output)?);
+        counter.write_bedpe(
+            &mut writer,
+            target_seqs,
+            self.min_reads,
+            annotations.as_ref(),
+        )?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Command for Breakpoints {
+    fn execute(&self) -> Result<()> {
+        Breakpoints::execute(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::Breakpoints;
+
+    /// Check that the argument parser works
+    #[test]
+    fn test_parse() {
+        Breakpoints::parse_from(["breakpoints", "-f", ".", "-r", ".", "-o", "."]);
+        Breakpoints::parse_from([
+            "breakpoints",
+            "-f",
+            "a.fq",
+            "b.fq",
+            "--sample",
+            "s1",
+            "--sample",
+            "s2",
+            "-r",
+            ".",
+            "-o",
+            ".",
+            "--per-sample",
+        ]);
+    }
+}
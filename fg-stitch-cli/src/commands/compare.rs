@@ -0,0 +1,135 @@
+use super::{
+    align::{output_path, Align},
+    all_vs_all::read_name,
+    command::Command,
+};
+use anyhow::{Context, Result};
+use clap::Parser;
+use log::info;
+use seq_io::fastq::Record;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use stitch::{
+    align::compare::{AlignmentComparison, AlignmentComparisonSummary},
+    util::version::built_info,
+};
+
+/// Compares the best alignment of each read under two sets of alignment parameters, and reports
+/// the reads whose best alignment changes, for tuning the parameters.
+///
+/// The reads are aligned as with `align` (see `stitch align --help` for the alignment options)
+/// with the first set of parameters, and again with the second set, being the first set
+/// overridden by the parameters in the `--other-config` TOML file (see "Configuration files" in
+/// `stitch align --help`).  Only the alignment parameters differ between the two, so the
+/// references, reads, and other inputs are the same.  The output options of `align` are ignored.
+///
+/// For each read whose best alignment changes (or every read with `--all-reads`), the score of the
+/// best alignment under each set, the change of the score (the second less the first), the jump
+/// structure of the best alignment under each set (as for `msa`), and whether the jump structure
+/// changed are written to `<output-prefix>.changes.txt`, with `.` for a read that did not align.
+/// The number of reads whose best alignment changed, and the mean change of the score, are written
+/// to `<output-prefix>.summary.txt`.
+#[derive(Parser, Debug, Clone)]
+#[clap(version = built_info::VERSION.as_str(), term_width=0)]
+pub struct Compare {
+    #[clap(flatten)]
+    align: Align,
+
+    /// The prefix of the output files.
+    #[clap(long, short = 'o', display_order = 26)]
+    output_prefix: PathBuf,
+
+    /// The TOML file with the second set of alignment parameters, which override the first.
+    #[clap(long, display_order = 27)]
+    other_config: PathBuf,
+
+    /// Write every read, not only those whose best alignment changes.
+    #[clap(long, default_value = "false", display_order = 28)]
+    all_reads: bool,
+}
+
+impl Compare {
+    /// Executes the compare command
+    pub fn execute(&self) -> Result<()> {
+        info!("Starting comparison of alignment parameters...");
+        let align = self.align.with_config_file()?;
+        let other = align.with_parameters_from(&self.other_config)?;
+        let builder = Arc::new(align.builder());
+        let target_seqs = Arc::new(align.target_seqs()?);
+        let other_hashes = other.target_hashes(&target_seqs);
+        let other_filter = other.alignment_filter();
+        let mut other_aligners = other.builder().build_aligners(&target_seqs);
+        other_aligners.set_jump_sites(align.jump_site_features());
+        other_aligners.set_splice_annotation(align.splice_annotation());
+
+        let create = |suffix: &str| -> Result<BufWriter<File>> {
+            let path = output_path(&self.output_prefix, suffix);
+            let file = File::create(&path)
+                .with_context(|| format!("Error creating output: {}", path.display()))?;
+            Ok(BufWriter::new(file))
+        };
+        let mut changes_writer = create(".changes.txt")?;
+        AlignmentComparison::write_header(&mut changes_writer)?;
+
+        let mut summary = AlignmentComparisonSummary::default();
+        align.align_reads(&builder, &target_seqs, |(record, alignments, _)| {
+            let (mut other_alignments, _) =
+                other_aligners.align(&record, &target_seqs, &other_hashes);
+            if !other_filter.is_empty() {
+                let query = record.seq().to_ascii_uppercase();
+                other_filter.retain(&mut other_alignments, &target_seqs, &query);
+            }
+            let comparison = AlignmentComparison::new(alignments.first(), other_alignments.first());
+            summary.add(&comparison);
+            if self.all_reads || comparison.changed {
+                let read_name = read_name(record.head());
+                comparison.write_row(&mut changes_writer, &read_name, &target_seqs)?;
+            }
+            Ok(())
+        })?;
+        changes_writer.flush()?;
+
+        info!(
+            "The best alignment changed for {} of {} reads",
+            summary.changed(),
+            summary.reads(),
+        );
+        let mut writer = create(".summary.txt")?;
+        summary.write_table(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Command for Compare {
+    fn execute(&self) -> Result<()> {
+        Compare::execute(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::Compare;
+
+    /// Check that the argument parser works
+    #[test]
+    fn test_parse() {
+        Compare::parse_from([
+            "compare",
+            "-f",
+            ".",
+            "-r",
+            ".",
+            "-o",
+            ".",
+            "--other-config",
+            ".",
+        ]);
+    }
+}
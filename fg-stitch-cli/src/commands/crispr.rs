@@ -0,0 +1,204 @@
+use super::{align::Align, all_vs_all::read_name, command::Command};
+use anyhow::{ensure, Context, Result};
+use clap::Parser;
+use log::info;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use stitch::{
+    align::{
+        crispr::{EditClassifier, EditSummary, SampleEditSummary},
+        read_group::SampleIndex,
+    },
+    util::{target_seq, version::built_info},
+};
+
+/// Classifies long reads from a CRISPR-edited amplicon by the outcome of the edit.
+///
+/// The reads are aligned as with `align` (see `stitch align --help` for the alignment options),
+/// and the best alignment of each read is classified, in order of precedence, as:
+/// - unaligned: the read did not align.
+/// - translocation: the read aligned to a reference other than the amplicon.
+/// - complex: the read has a jump within the amplicon that is not a large deletion, for example
+///   a duplication or an inversion.
+/// - large-deletion: the read has a deletion of at least `--min-large-deletion` bases.
+/// - hdr: the read has all the variants of the `--hdr-template` relative to the amplicon.
+/// - nhej: the read has an insertion or deletion overlapping the window around the cut site.
+/// - wild-type: otherwise.
+///
+/// The output is a tab-delimited table with the number and fraction of reads in each class.  The
+/// output options of `align` are ignored, and `--circular` is not supported.
+///
+/// With `--per-sample`, the reads of many samples (e.g. one FASTQ per sample, see "Read groups" in
+/// `stitch align --help`) are aligned in a single run against the same amplicon, and the output is
+/// a matrix of the classes across the samples, with one row per class and, for each sample, the
+/// number and fraction of its reads in the `<sample>.count` and `<sample>.fraction` columns.
+#[derive(Parser, Debug, Clone)]
+#[clap(version = built_info::VERSION.as_str(), term_width=0, verbatim_doc_comment)]
+pub struct Crispr {
+    #[clap(flatten)]
+    align: Align,
+
+    /// The path to the output summary table.
+    #[clap(long, short = 'o', display_order = 26)]
+    output: PathBuf,
+
+    /// The name of the amplicon in the reference FASTA, otherwise the first sequence.
+    #[clap(long, display_order = 27)]
+    amplicon: Option<String>,
+
+    /// The path to a FASTA with the expected sequence of the amplicon after HDR, as its first
+    /// sequence.
+    #[clap(long, display_order = 28)]
+    hdr_template: Option<PathBuf>,
+
+    /// The 1-based position in the amplicon of the base preceding the cut site, otherwise indels
+    /// anywhere in the amplicon are considered NHEJ outcomes.
+    #[clap(long, display_order = 29)]
+    cut_site: Option<usize>,
+
+    /// The number of bases on either side of the `--cut-site` in which indels are considered NHEJ
+    /// outcomes.
+    #[clap(long, default_value = "10", display_order = 30)]
+    window_size: usize,
+
+    /// The minimum length of a deletion (as a jump or a gap) to be considered a large deletion.
+    #[clap(long, default_value = "50", display_order = 31)]
+    min_large_deletion: usize,
+
+    /// Classify the reads of each sample separately (requires `--sample` or `--read-group`).
+    #[clap(long, default_value = "false", display_order = 32)]
+    per_sample: bool,
+}
+
+impl Crispr {
+    /// Executes the crispr command
+    pub fn execute(&self) -> Result<()> {
+        info!("Starting edit classification...");
+        let align = self.align.with_config_file()?;
+        let builder = Arc::new(align.builder());
+        let target_seqs = Arc::new(align.target_seqs()?);
+
+        let amplicon_idx = match &self.amplicon {
+            None => 0,
+            Some(name) => target_seqs
+                .iter()
+                .position(|target_seq| &target_seq.name == name)
+                .with_context(|| format!("Amplicon not found in the reference: {name}"))?,
+        };
+        let hdr_template = match &self.hdr_template {
+            None => None,
+            Some(path) => {
+                let template = target_seq::from_fasta(path, false)?
+                    .into_iter()
+                    .next()
+                    .with_context(|| format!("No sequences in: {}", path.display()))?;
+                Some(template.fwd.to_ascii_uppercase())
+            }
+        };
+        let window = match self.cut_site {
+            None => None,
+            Some(cut_site) => {
+                let amplicon_len = target_seqs[amplicon_idx].len();
+                ensure!(
+                    cut_site > 0 && cut_site < amplicon_len,
+                    "--cut-site must be within the amplicon"
+                );
+                let start = cut_site.saturating_sub(self.window_size);
+                let end = (cut_site + self.window_size).min(amplicon_len);
+                Some(start..end)
+            }
+        };
+        let classifier = EditClassifier::new(
+            &target_seqs,
+            amplicon_idx,
+            hdr_template.as_deref(),
+            window,
+            self.min_large_deletion,
+            builder.build_scoring(),
+        )?;
+
+        if self.per_sample {
+            let samples = SampleIndex::new(&align.read_groups()?);
+            ensure!(
+                !samples.is_empty(),
+                "--per-sample requires --sample or --read-group"
+            );
+            let mut summary = SampleEditSummary::new(samples.samples());
+            align.align_reads(&builder, &target_seqs, |(fastq, alignments, _)| {
+                let sample_idx = samples
+                    .index_of(&fastq.head)
+                    .with_context(|| format!("No sample for read: {}", read_name(&fastq.head)))?;
+                let query = fastq.seq.to_ascii_uppercase();
+                let class = classifier.classify(alignments.first(), &target_seqs, &query);
+                summary.add(sample_idx, class);
+                Ok(())
+            })?;
+
+            info!(
+                "Writing the summary for {} reads across {} samples to {}",
+                summary.total(),
+                samples.len(),
+                self.output.display()
+            );
+            let mut writer = BufWriter::new(File::create(&self.output)?);
+            summary.write_table(&mut writer)?;
+            writer.flush()?;
+            return Ok(());
+        }
+
+        let mut summary = EditSummary::new();
+        align.align_reads(&builder, &target_seqs, |(fastq, alignments, _)| {
+            let query = fastq.seq.to_ascii_uppercase();
+            summary.add(classifier.classify(alignments.first(), &target_seqs, &query));
+            Ok(())
+        })?;
+
+        info!(
+            "Writing the summary for {} reads to {}",
+            summary.total(),
+            self.output.display()
+        );
+        let mut writer = BufWriter::new(File::create(&self.output)?);
+        summary.write_table(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Command for Crispr {
+    fn execute(&self) -> Result<()> {
+        Crispr::execute(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::Crispr;
+
+    /// Check that the argument parser works
+    #[test]
+    fn test_parse() {
+        Crispr::parse_from(["crispr", "-f", ".", "-r", ".", "-o", "."]);
+        Crispr::parse_from([
+            "crispr",
+            "-f",
+            "a.fq",
+            "b.fq",
+            "--sample",
+            "s1",
+            "--sample",
+            "s2",
+            "-r",
+            ".",
+            "-o",
+            ".",
+            "--per-sample",
+        ]);
+    }
+}
@@ -0,0 +1,128 @@
+use super::{
+    align::{output_path, Align},
+    command::Command,
+};
+use anyhow::{Context, Result};
+use clap::Parser;
+use log::info;
+use seq_io::fastq::Record;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use stitch::{
+    align::demux::{read_target_groups, Demultiplexer, DemuxSummary, UNASSIGNED},
+    util::version::built_info,
+};
+
+/// Demultiplexes reads by the reference (or group of references) to which most of their bases
+/// align.
+///
+/// The reads are aligned as with `align` (see `stitch align --help` for the alignment options),
+/// and each read is assigned to the group with more than `--min-fraction` of the aligned bases
+/// (matches and mismatches) of its best alignment.  The aligned bases are summed across the jumps
+/// of the alignment, so a chimeric read is assigned to the group with most of its bases rather
+/// than the group where its alignment starts.  By default each reference is its own group, and
+/// `--groups` may assign multiple references to the same group.  The output options of `align`
+/// are ignored.
+///
+/// The reads of each group are written to `<output-prefix>.<group>.fastq`, with reads not
+/// aligned or not assigned to any group written to `<output-prefix>.unassigned.fastq`.  The
+/// number and fraction of reads in each group are written to `<output-prefix>.summary.txt`.
+#[derive(Parser, Debug, Clone)]
+#[clap(version = built_info::VERSION.as_str(), term_width=0)]
+pub struct Demux {
+    #[clap(flatten)]
+    align: Align,
+
+    /// The prefix of the output files.
+    #[clap(long, short = 'o', display_order = 26)]
+    output_prefix: PathBuf,
+
+    /// The path to a headerless tab-delimited file with the name of a reference in the first
+    /// column and the name of its group in the second column.  References not in the file are
+    /// each in their own group, named after the reference.
+    #[clap(long, display_order = 27)]
+    groups: Option<PathBuf>,
+
+    /// The fraction of the aligned bases of a read that must be aligned to a group (exclusive)
+    /// for the read to be assigned to the group.
+    #[clap(long, default_value = "0.5", display_order = 28)]
+    min_fraction: f64,
+}
+
+impl Demux {
+    /// Executes the demux command
+    pub fn execute(&self) -> Result<()> {
+        info!("Starting demultiplexing...");
+        let align = self.align.with_config_file()?;
+        let builder = Arc::new(align.builder());
+        let target_seqs = Arc::new(align.target_seqs()?);
+
+        let groups = match &self.groups {
+            None => HashMap::new(),
+            Some(path) => read_target_groups(path)?,
+        };
+        let demux = Demultiplexer::new(&target_seqs, &groups, self.min_fraction)?;
+
+        // Open one output per group, then the output for the unassigned reads
+        let mut writers = demux
+            .group_names()
+            .iter()
+            .map(String::as_str)
+            .chain([UNASSIGNED])
+            .map(|group| {
+                let path = output_path(&self.output_prefix, &format!(".{group}.fastq"));
+                let file = File::create(&path)
+                    .with_context(|| format!("Error creating output: {}", path.display()))?;
+                Ok(BufWriter::new(file))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut summary = DemuxSummary::new(demux.group_names().len());
+        align.align_reads(&builder, &target_seqs, |(fastq, alignments, _)| {
+            let group = demux.assign(alignments.first());
+            summary.add(group);
+            let index = group.unwrap_or(writers.len() - 1);
+            fastq.write(&mut writers[index])?;
+            Ok(())
+        })?;
+        for writer in &mut writers {
+            writer.flush()?;
+        }
+
+        let path = output_path(&self.output_prefix, ".summary.txt");
+        info!(
+            "Assigned {} of {} reads, writing the summary to {}",
+            summary.total() - summary.unassigned(),
+            summary.total(),
+            path.display()
+        );
+        let mut writer = BufWriter::new(File::create(&path)?);
+        summary.write_table(&mut writer, demux.group_names())?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Command for Demux {
+    fn execute(&self) -> Result<()> {
+        Demux::execute(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::Demux;
+
+    /// Check that the argument parser works
+    #[test]
+    fn test_parse() {
+        Demux::parse_from(["demux", "-f", ".", "-r", ".", "-o", "."]);
+    }
+}
@@ -0,0 +1,197 @@
+use super::{align::Align, command::Command};
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::io::{self, IsTerminal, Write};
+#[cfg(feature = "viz")]
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+use stitch::{align::describe::AlignmentDescriber, util::version::built_info};
+#[cfg(feature = "viz")]
+use stitch::{
+    align::viz::{DotPlot, ScoreSurface},
+    util::target_seq::TargetSeq,
+};
+
+/// The maximum width and height in pixels of the visualizations written as PNG.
+#[cfg(feature = "viz")]
+const MAX_IMAGE_SIZE: usize = 2000;
+
+/// Describes the alignment of a single read, for debugging the alignment of individual reads.
+///
+/// The read is aligned as with `align` (see `stitch align --help` for the alignment options), and
+/// the best alignment is printed in a human-readable form: a summary of the alignment, then for
+/// each segment between jumps, the read and reference coordinates followed by the pairwise
+/// alignment, with mismatches, gaps, and jumps colored when writing to a terminal.  Up to
+/// `--top-k` alternate alignments are printed after the best alignment, which overrides the
+/// `--suboptimal` options of `align`.  The output options of `align` are ignored.
+///
+/// Reference coordinates are 1-based inclusive on the forward strand of the reference, and read
+/// coordinates are 1-based inclusive.
+///
+/// When built with the `viz` feature, `--viz-prefix` writes visualizations for understanding why
+/// a jump was or wasn't chosen, as both TSV and grayscale PNG (query along the x-axis, target
+/// along the y-axis):
+/// - `<prefix>.dotplot.<target>.{tsv,png}` - the k-mers of length `--viz-kmer-size` shared by the
+///   read and each target, with reverse strand k-mers in gray when aligning to both strands.
+/// - `<prefix>.scores.<target>.<fwd|rev>.{tsv,png}` - the best score of an alignment ending at each
+///   reference and read position, with darker pixels for higher scores.
+#[derive(Parser, Debug, Clone)]
+#[clap(version = built_info::VERSION.as_str(), term_width=0)]
+pub struct Describe {
+    #[clap(flatten)]
+    align: Align,
+
+    /// The name of the read to describe, otherwise the first read.
+    #[clap(long, short = 'n', display_order = 26)]
+    read_name: Option<String>,
+
+    /// The maximum number of alternate alignments to describe.
+    #[clap(long, short = 'K', default_value = "3", display_order = 27)]
+    top_k: usize,
+
+    /// The number of alignment columns per line of the pairwise alignment.
+    #[clap(long, default_value = "80", display_order = 28)]
+    width: usize,
+
+    /// Do not color the output, otherwise the output is colored when writing to a terminal.
+    #[clap(long, default_value = "false", display_order = 29)]
+    no_color: bool,
+
+    /// The prefix of the dot-plot and score surface visualizations to write.
+    #[cfg(feature = "viz")]
+    #[clap(long, display_order = 30)]
+    viz_prefix: Option<PathBuf>,
+
+    /// The k-mer size used to build the dot-plot visualization.
+    #[cfg(feature = "viz")]
+    #[clap(long, default_value = "11", display_order = 31)]
+    viz_kmer_size: usize,
+}
+
+impl Describe {
+    /// Executes the describe command
+    pub fn execute(&self) -> Result<()> {
+        let align = self.align.with_config_file()?;
+        let mut builder = align.builder();
+        if self.top_k > 0 {
+            builder.suboptimal(true).suboptimal_pct(0.0);
+        }
+        let target_seqs = align.target_seqs()?;
+        let target_hashes = align.target_hashes(&target_seqs);
+        let mut aligners = builder.build_aligners(&target_seqs);
+        #[cfg(feature = "viz")]
+        aligners.set_record_score_surfaces(self.viz_prefix.is_some());
+
+        let is_read = |head: &[u8]| match &self.read_name {
+            None => true,
+            Some(read_name) => {
+                let head = String::from_utf8_lossy(head);
+                head.split_whitespace().next() == Some(read_name.as_str())
+            }
+        };
+        let record = align
+            .read_records()?
+            .find(|record| is_read(&record.head))
+            .with_context(|| match &self.read_name {
+                None => "No reads found".to_string(),
+                Some(read_name) => format!("Read not found: {read_name}"),
+            })?;
+        let query = record.seq.to_ascii_uppercase();
+        let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        #[cfg(feature = "viz")]
+        if let Some(prefix) = &self.viz_prefix {
+            let double_strand = align.double_strand();
+            self.write_viz(
+                prefix,
+                &target_seqs,
+                &query,
+                double_strand,
+                aligners.score_surfaces(),
+            )?;
+        }
+
+        let color = !self.no_color && io::stdout().is_terminal();
+        let describer = AlignmentDescriber::new(&target_seqs, color, self.width);
+        let mut stdout = io::stdout().lock();
+        writeln!(
+            stdout,
+            "read: {} (length: {})",
+            String::from_utf8_lossy(&record.head),
+            query.len()
+        )?;
+        match alignments.first() {
+            None => writeln!(stdout, "\nbest alignment: unaligned")?,
+            Some(best) => {
+                writeln!(stdout, "\nbest alignment:")?;
+                write!(stdout, "{}", describer.describe(best, &query))?;
+            }
+        }
+        for (index, alignment) in alignments.iter().skip(1).take(self.top_k).enumerate() {
+            writeln!(stdout, "\nalternate alignment {}:", index + 1)?;
+            write!(stdout, "{}", describer.describe(alignment, &query))?;
+        }
+        stdout.flush()?;
+        Ok(())
+    }
+
+    /// Writes the dot-plot of the read versus each target, and the score surface of each contig,
+    /// as TSV and PNG files with the given prefix.
+    #[cfg(feature = "viz")]
+    fn write_viz(
+        &self,
+        prefix: &Path,
+        target_seqs: &[TargetSeq],
+        query: &[u8],
+        double_strand: bool,
+        score_surfaces: &[ScoreSurface],
+    ) -> Result<()> {
+        let path = |suffix: String| PathBuf::from(format!("{}.{suffix}", prefix.display()));
+        let create = |path: PathBuf| -> Result<BufWriter<File>> {
+            let file = File::create(&path)
+                .with_context(|| format!("Could not create: {}", path.display()))?;
+            Ok(BufWriter::new(file))
+        };
+
+        for dot_plot in DotPlot::build(target_seqs, query, self.viz_kmer_size, double_strand) {
+            let name = &dot_plot.name;
+            dot_plot.write_tsv(&mut create(path(format!("dotplot.{name}.tsv")))?)?;
+            let mut writer = create(path(format!("dotplot.{name}.png")))?;
+            dot_plot.write_png(&mut writer, MAX_IMAGE_SIZE)?;
+        }
+        if score_surfaces.is_empty() {
+            log::warn!(
+                "No score surfaces recorded, as no contig passed pre-filtering or pre-alignment"
+            );
+        }
+        for surface in score_surfaces {
+            let strand = if surface.is_forward { "fwd" } else { "rev" };
+            let name = format!("scores.{}.{strand}", surface.name);
+            surface.write_tsv(&mut create(path(format!("{name}.tsv")))?)?;
+            let mut writer = create(path(format!("{name}.png")))?;
+            surface.write_png(&mut writer, MAX_IMAGE_SIZE)?;
+        }
+        Ok(())
+    }
+}
+
+impl Command for Describe {
+    fn execute(&self) -> Result<()> {
+        Describe::execute(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::Describe;
+
+    /// Check that the argument parser works
+    #[test]
+    fn test_parse() {
+        Describe::parse_from(["describe", "-f", ".", "-r", ".", "-n", "read1"]);
+    }
+}
@@ -0,0 +1,100 @@
+use super::{align::Align, command::Command};
+use anyhow::{ensure, Result};
+use clap::Parser;
+use log::info;
+use seq_io::fastq::Record;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use stitch::{align::fusions::FusionCounter, util::version::built_info};
+
+/// Reports candidate gene fusions given by the jumps between references in the alignments of RNA
+/// reads against two or more gene or transcript references.
+///
+/// The reads are aligned as with `align` (see `stitch align --help` for the alignment options),
+/// but instead of writing the alignments, each jump from one reference to another is converted
+/// into a candidate fusion, namely the last reference position aligned before the jump and the
+/// first reference position aligned after the jump, along with the strand of each.  The
+/// references should be given in their sense orientation, and the reads aligned with
+/// `--double-strand` (and usually `--preset spliced`), so that a fusion observed on the opposite
+/// strand of a read is counted as the same fusion, with the reference before the junction the 5'
+/// partner and the one after it the 3' partner.  Jumps within a reference are ignored.  The output
+/// options of `align` are ignored.
+///
+/// The output is a tab-delimited table with a header, with one line per fusion: the 5' reference,
+/// its last position before the junction (1-based) and strand, the 3' reference, its first
+/// position after the junction (1-based) and strand, the number of reads supporting the fusion,
+/// and the read bases on either side of the junction (separated by `|`) from the first supporting
+/// read.  A fusion between opposite strands of the references (e.g. `+` and `-`) joins a gene to
+/// the antisense of another.  Fusions are sorted by decreasing number of supporting reads.
+#[derive(Parser, Debug, Clone)]
+#[clap(version = built_info::VERSION.as_str(), term_width=0)]
+pub struct Fusions {
+    #[clap(flatten)]
+    align: Align,
+
+    /// The path to the output table.
+    #[clap(long, short = 'o', display_order = 26)]
+    output: PathBuf,
+
+    /// The minimum number of reads supporting a fusion for it to be output.
+    #[clap(long, default_value = "2", display_order = 27)]
+    min_reads: usize,
+
+    /// The number of read bases on either side of the junction to output.
+    #[clap(long, default_value = "20", display_order = 27)]
+    junction_flank: usize,
+}
+
+impl Fusions {
+    /// Executes the fusions command
+    pub fn execute(&self) -> Result<()> {
+        info!("Starting fusion detection...");
+        let align = self.align.with_config_file()?;
+        let builder = Arc::new(align.builder());
+        let target_seqs = Arc::new(align.target_seqs()?);
+        ensure!(
+            target_seqs.len() >= 2,
+            "At least two references are required to detect fusions, found {}",
+            target_seqs.len()
+        );
+
+        let mut counter = FusionCounter::new(self.junction_flank);
+        align.align_reads(&builder, &target_seqs, |(record, alignments, _)| {
+            counter.add(record.seq(), &alignments, &target_seqs);
+            Ok(())
+        })?;
+
+        info!(
+            "Writing {} candidate fusions to {}",
+            counter.len(),
+            self.output.display()
+        );
+        let mut writer = BufWriter::new(File::create(&self.output)?);
+        counter.write_tsv(&mut writer, &target_seqs, self.min_reads)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Command for Fusions {
+    fn execute(&self) -> Result<()> {
+        Fusions::execute(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::Fusions;
+
+    /// Check that the argument parser works
+    #[test]
+    fn test_parse() {
+        Fusions::parse_from(["fusions", "-f", ".", "-r", ".", "-o", "."]);
+    }
+}
@@ -0,0 +1,125 @@
+use super::{align::Align, command::Command};
+use anyhow::{ensure, Result};
+use clap::Parser;
+use log::info;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use stitch::{align::integration::IntegrationSiteCounter, util::version::built_info};
+
+/// Reports the integration sites of a vector (e.g. AAV or lentiviral) in a host genome, given by
+/// the jumps between the vector and host references in the alignments of reads.
+///
+/// The reference FASTA contains both the vector references, named with `--vector`, and the host
+/// references (e.g. the host genome, or regions of it).  The reads are aligned as with `align`
+/// (see `stitch align --help` for the alignment options, where `--double-strand` should usually
+/// be given), but instead of writing the alignments, each jump between a vector and a host
+/// reference is converted into an integration site, namely the host position adjacent to the
+/// junction.  Junctions are oriented so that the vector is on its forward strand: a
+/// `host-vector` junction joins the host to the start of the vector, and a `vector-host` junction
+/// the end of the vector to the host, with the host strand giving the orientation of the vector
+/// in the host.  A junction observed on the opposite strand of a read is counted as the same
+/// site.  The output options of `align` are ignored.
+///
+/// The output is a tab-delimited table with a header, with one line per site: the host reference,
+/// the host position adjacent to the junction (1-based) and strand, the junction, the vector
+/// reference and the vector position adjacent to the junction (1-based) from the first
+/// supporting read, the number of reads supporting the site, and the relative abundance of the
+/// site among all sites.  Sites are sorted by decreasing number of supporting reads.  The
+/// clonality of the integrations, from 0 (every site equally abundant) to 1 (a single site), is
+/// logged.
+#[derive(Parser, Debug, Clone)]
+#[clap(version = built_info::VERSION.as_str(), term_width=0)]
+pub struct Integration {
+    #[clap(flatten)]
+    align: Align,
+
+    /// The path to the output table.
+    #[clap(long, short = 'o', display_order = 26)]
+    output: PathBuf,
+
+    /// The name of a vector reference, the other references being the host.  May be specified
+    /// multiple times.
+    #[clap(long, required = true, display_order = 26)]
+    vector: Vec<String>,
+
+    /// The minimum number of reads supporting a site for it to be output.
+    #[clap(long, default_value = "1", display_order = 27)]
+    min_reads: usize,
+}
+
+impl Integration {
+    /// Executes the integration command
+    pub fn execute(&self) -> Result<()> {
+        info!("Starting integration site detection...");
+        let align = self.align.with_config_file()?;
+        let builder = Arc::new(align.builder());
+        let target_seqs = Arc::new(align.target_seqs()?);
+
+        for name in &self.vector {
+            ensure!(
+                target_seqs
+                    .iter()
+                    .any(|target_seq| &target_seq.name == name),
+                "Vector reference not found: {name}"
+            );
+        }
+        let is_vector = target_seqs
+            .iter()
+            .map(|target_seq| self.vector.contains(&target_seq.name))
+            .collect::<Vec<_>>();
+        ensure!(
+            is_vector.iter().any(|is_vector| !is_vector),
+            "At least one host reference is required, found only vector references"
+        );
+
+        let mut counter = IntegrationSiteCounter::new(is_vector);
+        align.align_reads(&builder, &target_seqs, |(_, alignments, _)| {
+            counter.add(&alignments, &target_seqs);
+            Ok(())
+        })?;
+
+        info!(
+            "Writing {} integration sites to {}, with a clonality of {:.4}",
+            counter.len(),
+            self.output.display(),
+            counter.clonality()
+        );
+        let mut writer = BufWriter::new(File::create(&self.output)?);
+        counter.write_tsv(&mut writer, &target_seqs, self.min_reads)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Command for Integration {
+    fn execute(&self) -> Result<()> {
+        Integration::execute(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::Integration;
+
+    /// Check that the argument parser works
+    #[test]
+    fn test_parse() {
+        Integration::parse_from([
+            "integration",
+            "-f",
+            ".",
+            "-r",
+            ".",
+            "-o",
+            ".",
+            "--vector",
+            "aav",
+        ]);
+    }
+}
@@ -1,2 +1,12 @@
 pub mod align;
+pub mod all_vs_all;
+pub mod breakpoints;
 pub mod command;
+pub mod compare;
+pub mod crispr;
+pub mod demux;
+pub mod describe;
+pub mod fusions;
+pub mod integration;
+pub mod msa;
+pub mod verify;
@@ -0,0 +1,94 @@
+use super::{align::Align, command::Command};
+use anyhow::Result;
+use clap::Parser;
+use log::info;
+use seq_io::fastq::Record;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use stitch::{align::poa::JumpAwareMsa, util::version::built_info};
+
+/// Collapses the reads with the same jump structure into a consensus and alleles, for example to
+/// report the alleles of an edited locus.
+///
+/// The reads are aligned as with `align` (see `stitch align --help` for the alignment options),
+/// but instead of writing the alignments, the reads are grouped by the jump structure of their
+/// best alignment (the contig on which it starts and its jumps), and the reads of each group are
+/// collapsed into a partial-order alignment graph, by threading each read through the graph along
+/// its alignment.  The consensus of each group is the heaviest path through its graph, and the
+/// alleles of each group are the distinct paths of its reads.  Reads from opposite strands of the
+/// same molecule have different jump structures, so are in different groups.  Unaligned reads are
+/// ignored.  The output options of `align` are ignored.
+///
+/// The output is a tab-delimited table with a header, with one line for the consensus and one line
+/// per allele of each group: the jump structure (the target and strand if there are no jumps, and
+/// otherwise each jump as the target, 1-based position and strand of the last base before the
+/// jump, then of the first base after the jump, e.g. `chr1:100:+>chr2:500:-`), the number of reads
+/// of the group, the allele (`consensus`, or the 1-based rank of the allele), the number of reads
+/// with exactly the allele and their fraction of the reads of the group, the allele bases, and the
+/// number of reads supporting each allele base (separated by commas).  Groups are sorted by
+/// decreasing number of reads, and alleles by decreasing number of reads.
+#[derive(Parser, Debug, Clone)]
+#[clap(version = built_info::VERSION.as_str(), term_width=0)]
+pub struct Msa {
+    #[clap(flatten)]
+    align: Align,
+
+    /// The path to the output table.
+    #[clap(long, short = 'o', display_order = 26)]
+    output: PathBuf,
+
+    /// The minimum number of reads of a group, and of an allele, for it to be output.
+    #[clap(long, default_value = "2", display_order = 27)]
+    min_reads: usize,
+}
+
+impl Msa {
+    /// Executes the msa command
+    pub fn execute(&self) -> Result<()> {
+        info!("Starting jump-aware multiple sequence alignment...");
+        let align = self.align.with_config_file()?;
+        let builder = Arc::new(align.builder());
+        let target_seqs = Arc::new(align.target_seqs()?);
+
+        let mut msa = JumpAwareMsa::new();
+        align.align_reads(&builder, &target_seqs, |(record, alignments, _)| {
+            if let Some(alignment) = alignments.first() {
+                msa.add(alignment, record.seq());
+            }
+            Ok(())
+        })?;
+
+        info!(
+            "Writing the alleles of {} jump structures to {}",
+            msa.len(),
+            self.output.display()
+        );
+        let mut writer = BufWriter::new(File::create(&self.output)?);
+        msa.write_tsv(&mut writer, &target_seqs, self.min_reads)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Command for Msa {
+    fn execute(&self) -> Result<()> {
+        Msa::execute(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::Msa;
+
+    /// Check that the argument parser works
+    #[test]
+    fn test_parse() {
+        Msa::parse_from(["msa", "-f", ".", "-r", ".", "-o", "."]);
+    }
+}
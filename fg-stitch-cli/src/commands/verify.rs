@@ -0,0 +1,130 @@
+use super::{align::Align, command::Command};
+use anyhow::{Context, Result};
+use clap::Parser;
+use log::info;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use stitch::{
+    align::verify::{PlasmidFeature, PlasmidVerifier},
+    util::version::built_info,
+};
+
+/// Verifies a plasmid (or other construct) against its expected map using long reads.
+///
+/// The reads are aligned as with `align` (see `stitch align --help` for the alignment options,
+/// where `--double-strand` should usually be given, along with `--circular` for a circular
+/// plasmid), and the best alignment of each read is compared to the expected plasmid:
+/// - feature: each expected feature (from `--features`, or the feature table of a GenBank or EMBL
+///   reference) passes if all its bases are covered by at least `--min-depth` reads.
+/// - deletion: each deletion of at least `--min-deletion` bases, as a gap or a jump forward on the
+///   same strand, fails if supported by at least `--min-event-fraction` of the aligned reads, and
+///   otherwise warns.
+/// - junction: each other jump, such as a duplication, an inversion, or a junction with another
+///   reference (e.g. from recombination with the host), fails or warns as for deletions.
+///
+/// The plasmid passes if it has aligned reads and passes every check.  The output is a
+/// tab-delimited table with a header, with one line per check (the kind, name, location, reads,
+/// fraction, and status), and a final summary line with the number and fraction of aligned reads
+/// and the overall status.  Locations are 1-based.  The output options of `align` are ignored.
+#[derive(Parser, Debug, Clone)]
+#[clap(version = built_info::VERSION.as_str(), term_width=0, verbatim_doc_comment)]
+pub struct Verify {
+    #[clap(flatten)]
+    align: Align,
+
+    /// The path to the output report.
+    #[clap(long, short = 'o', display_order = 26)]
+    output: PathBuf,
+
+    /// The name of the plasmid in the reference FASTA, otherwise the first sequence.
+    #[clap(long, display_order = 27)]
+    plasmid: Option<String>,
+
+    /// The path to a BED with the expected features of the plasmid, named by its fourth column.
+    /// Otherwise, when the reference is a GenBank or EMBL file, the features of its feature table.
+    #[clap(long, display_order = 28)]
+    features: Option<PathBuf>,
+
+    /// The minimum depth of every base of a feature for it to pass.
+    #[clap(long, default_value = "5", display_order = 29)]
+    min_depth: u32,
+
+    /// The minimum length of a deletion (as a gap or a jump) to be reported.
+    #[clap(long, default_value = "50", display_order = 30)]
+    min_deletion: usize,
+
+    /// The minimum fraction of the aligned reads supporting a deletion or junction for it to fail.
+    #[clap(long, default_value = "0.1", display_order = 31)]
+    min_event_fraction: f64,
+}
+
+impl Verify {
+    /// Executes the verify command
+    pub fn execute(&self) -> Result<()> {
+        info!("Starting plasmid verification...");
+        let align = self.align.with_config_file()?;
+        let builder = Arc::new(align.builder());
+        let target_seqs = Arc::new(align.target_seqs()?);
+
+        let plasmid_idx = match &self.plasmid {
+            None => 0,
+            Some(name) => target_seqs
+                .iter()
+                .position(|target_seq| &target_seq.name == name)
+                .with_context(|| format!("Plasmid not found in the reference: {name}"))?,
+        };
+        let plasmid = &target_seqs[plasmid_idx].name;
+        let features = match (&self.features, align.annotations()?) {
+            (Some(path), _) => PlasmidFeature::from_path(path, plasmid)?,
+            (None, Some(annotations)) => PlasmidFeature::from_annotations(&annotations, plasmid),
+            (None, None) => Vec::new(),
+        };
+        info!("Verifying {} features", features.len());
+        let mut verifier =
+            PlasmidVerifier::new(&target_seqs, plasmid_idx, features, self.min_deletion)?;
+
+        align.align_reads(&builder, &target_seqs, |(_, alignments, _)| {
+            verifier.add(alignments.first(), &target_seqs);
+            Ok(())
+        })?;
+
+        let status = verifier.status(self.min_depth, self.min_event_fraction);
+        info!(
+            "Writing the report for {} reads to {}, with status {status}",
+            verifier.reads().0,
+            self.output.display()
+        );
+        let mut writer = BufWriter::new(File::create(&self.output)?);
+        verifier.write_report(
+            &mut writer,
+            &target_seqs,
+            self.min_depth,
+            self.min_event_fraction,
+        )?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Command for Verify {
+    fn execute(&self) -> Result<()> {
+        Verify::execute(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::Verify;
+
+    /// Check that the argument parser works
+    #[test]
+    fn test_parse() {
+        Verify::parse_from(["verify", "-f", ".", "-r", ".", "-o", "."]);
+    }
+}
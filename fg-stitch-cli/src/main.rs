@@ -2,7 +2,11 @@ mod commands;
 
 use anyhow::Result;
 use clap::Parser;
-use commands::{align::Align, command::Command};
+use commands::{
+    align::Align, all_vs_all::AllVsAll, breakpoints::Breakpoints, command::Command,
+    compare::Compare, crispr::Crispr, demux::Demux, describe::Describe, fusions::Fusions,
+    integration::Integration, msa::Msa, verify::Verify,
+};
 use enum_dispatch::enum_dispatch;
 use env_logger::Env;
 use stitch::util::version::built_info;
@@ -21,6 +25,16 @@ struct Args {
 #[command(version = built_info::VERSION.as_str())]
 enum Subcommand {
     Align(Align),
+    AllVsAll(AllVsAll),
+    Breakpoints(Breakpoints),
+    Compare(Compare),
+    Crispr(Crispr),
+    Demux(Demux),
+    Describe(Describe),
+    Fusions(Fusions),
+    Integration(Integration),
+    Msa(Msa),
+    Verify(Verify),
 }
 
 fn main() -> Result<()> {
@@ -0,0 +1,139 @@
+#ifndef STITCH_H
+#define STITCH_H
+
+/* Warning: this file is generated by cbindgen from fg-stitch-ffi/src/lib.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The alignment mode, namely which of the query and contigs may be clipped.
+typedef enum StitchAlignmentMode {
+  // Aligns a sub-sequence of the query versus a sub-sequence of the contigs.
+  STITCH_ALIGNMENT_MODE_LOCAL = 0,
+  // Aligns a sub-sequence of the query versus the full contigs.
+  STITCH_ALIGNMENT_MODE_QUERY_LOCAL = 1,
+  // Aligns the full query versus a sub-sequence of the contigs.
+  STITCH_ALIGNMENT_MODE_TARGET_LOCAL = 2,
+  // Aligns the full query versus the full contigs.
+  STITCH_ALIGNMENT_MODE_GLOBAL = 3,
+} StitchAlignmentMode;
+
+// A jump-aware aligner of queries to one or more contigs, created with [`stitch_aligner_new`]
+// and freed with [`stitch_aligner_free`].
+typedef struct StitchAligner StitchAligner;
+
+// The options for the aligner, with the same meaning and defaults as `stitch align`.
+typedef struct StitchOptions {
+  // The alignment mode.
+  enum StitchAlignmentMode mode;
+  // The score for a matching base (should be positive).
+  int32_t match_score;
+  // The score for a mismatching base (should not be positive).
+  int32_t mismatch_score;
+  // The score for opening a gap (should not be positive).
+  int32_t gap_open;
+  // The score for extending a gap (should not be positive).
+  int32_t gap_extend;
+  // The score for jumping to the same contig and strand (should not be positive).
+  int32_t jump_score_same_contig_and_strand;
+  // The score for jumping to the same contig and opposite strand (should not be positive).
+  int32_t jump_score_same_contig_opposite_strand;
+  // The score for jumping to a different contig (should not be positive).
+  int32_t jump_score_inter_contig;
+} StitchOptions;
+
+// The alignment of a query, created by [`stitch_align`] and freed with
+// [`stitch_alignment_free`].
+//
+// Contig coordinates are 0-based on the strand of the contig, with the start on the first
+// aligned contig and the end on the last aligned contig (these differ if the alignment jumps
+// between contigs).  If the query did not align, the CIGAR is empty.
+typedef struct StitchAlignment {
+  // The alignment score.
+  int32_t score;
+  // The 0-based start of the alignment in the query.
+  size_t query_start;
+  // The 0-based exclusive end of the alignment in the query.
+  size_t query_end;
+  // The index of the first aligned contig, as returned by [`stitch_add_contig`].
+  size_t start_contig_idx;
+  // The 0-based start of the alignment in the first aligned contig.
+  size_t contig_start;
+  // The index of the last aligned contig, as returned by [`stitch_add_contig`].
+  size_t end_contig_idx;
+  // The 0-based exclusive end of the alignment in the last aligned contig.
+  size_t contig_end;
+  // The NUL-terminated CIGAR of the alignment, including clipping and jumps.
+  char *cigar;
+} StitchAlignment;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns the default options, the same as the defaults of `stitch align`.
+struct StitchOptions stitch_options_default(void);
+
+// Returns the message of the most recent failure on the calling thread, or `NULL` if none.  The
+// message is owned by the library and valid until the next failure on the same thread.
+const char *stitch_last_error(void);
+
+// Creates a new aligner with the given options, or the default options if `options` is
+// `NULL`.  Returns `NULL` if the options are invalid.
+//
+// # Safety
+//
+// `options` must be `NULL` or point to a valid [`StitchOptions`].
+struct StitchAligner *stitch_aligner_new(const struct StitchOptions *options);
+
+// Frees an aligner created with [`stitch_aligner_new`].  Does nothing if `aligner` is `NULL`.
+//
+// # Safety
+//
+// `aligner` must be `NULL` or a pointer returned by [`stitch_aligner_new`] that has not
+// already been freed.
+void stitch_aligner_free(struct StitchAligner *aligner);
+
+// Adds a strand of a contig to the aligner, returning the index of the contig in the aligner, or
+// -1 on failure (e.g. if the same strand of the contig was already added).
+//
+// The sequence is always given for the forward strand of the contig; if `is_forward` is false,
+// the aligner aligns to its reverse complement.  To align to both strands, add the contig twice.
+//
+// # Safety
+//
+// `aligner` must be a valid aligner, `name` a NUL-terminated string, and `seq` must point to
+// `len` readable bytes.
+int64_t stitch_add_contig(struct StitchAligner *aligner,
+                          const char *name,
+                          const uint8_t *seq,
+                          size_t len,
+                          bool is_forward,
+                          bool circular);
+
+// Aligns the query to the contigs added to the aligner, returning the best alignment, or `NULL`
+// on failure (e.g. if no contigs were added).
+//
+// # Safety
+//
+// `aligner` must be a valid aligner, and `query` must point to `len` readable bytes.
+struct StitchAlignment *stitch_align(struct StitchAligner *aligner,
+                                     const uint8_t *query,
+                                     size_t len);
+
+// Frees an alignment returned by [`stitch_align`].  Does nothing if `alignment` is `NULL`.
+//
+// # Safety
+//
+// `alignment` must be `NULL` or a pointer returned by [`stitch_align`] that has not already
+// been freed.
+void stitch_alignment_free(struct StitchAlignment *alignment);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* STITCH_H */
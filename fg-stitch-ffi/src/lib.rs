@@ -0,0 +1,507 @@
+//! A C API for the Stitch jump-aware aligner, for integration into existing C/C++ pipelines.
+//!
+//! The header for this API is `include/stitch.h`, generated with `cbindgen` (see
+//! `cbindgen.toml`).  A typical use is:
+//!
+//! ```c
+//! StitchOptions options = stitch_options_default();
+//! StitchAligner *aligner = stitch_aligner_new(&options);
+//! stitch_add_contig(aligner, "chr1", seq, seq_len, true, false);
+//! StitchAlignment *alignment = stitch_align(aligner, query, query_len);
+//! if (alignment == NULL) fprintf(stderr, "%s\n", stitch_last_error());
+//! ...
+//! stitch_alignment_free(alignment);
+//! stitch_aligner_free(aligner);
+//! ```
+//!
+//! Functions that may fail return `NULL` (or a negative value) on failure, with the reason
+//! retrieved by [`stitch_last_error`].  Failures never unwind across the C boundary.
+
+use std::{
+    any::Any,
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use bio::alignment::pairwise::MatchParams;
+use stitch::{
+    align::{AlignmentMode, Builder, MultiContigAligner, Scoring},
+    util::dna::reverse_complement,
+};
+
+thread_local! {
+    /// The message of the most recent failure on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Records the message of a failure, for retrieval by [`stitch_last_error`].
+fn set_last_error(message: impl Into<String>) {
+    let message = message.into().replace('\0', " ");
+    let message = CString::new(message).expect("NUL bytes were removed");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Returns the message of a panic.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown error".to_string()
+    }
+}
+
+/// Runs the function, recording the failure and returning `on_error` if it fails or panics.
+fn guard<T>(on_error: T, f: impl FnOnce() -> Result<T, String>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(message)) => {
+            set_last_error(message);
+            on_error
+        }
+        Err(payload) => {
+            set_last_error(panic_message(payload.as_ref()));
+            on_error
+        }
+    }
+}
+
+/// Returns the bytes at the given pointer, or an error naming the argument if it is `NULL`.
+///
+/// # Safety
+///
+/// `data` must be `NULL` or point to `len` readable bytes.
+unsafe fn bytes<'a>(data: *const u8, len: usize, arg: &str) -> Result<&'a [u8], String> {
+    if data.is_null() {
+        Err(format!("{arg} must not be NULL"))
+    } else {
+        Ok(slice::from_raw_parts(data, len))
+    }
+}
+
+/// The alignment mode, namely which of the query and contigs may be clipped.
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum StitchAlignmentMode {
+    /// Aligns a sub-sequence of the query versus a sub-sequence of the contigs.
+    Local = 0,
+    /// Aligns a sub-sequence of the query versus the full contigs.
+    QueryLocal = 1,
+    /// Aligns the full query versus a sub-sequence of the contigs.
+    TargetLocal = 2,
+    /// Aligns the full query versus the full contigs.
+    Global = 3,
+}
+
+impl From<StitchAlignmentMode> for AlignmentMode {
+    fn from(mode: StitchAlignmentMode) -> Self {
+        match mode {
+            StitchAlignmentMode::Local => AlignmentMode::Local,
+            StitchAlignmentMode::QueryLocal => AlignmentMode::QueryLocal,
+            StitchAlignmentMode::TargetLocal => AlignmentMode::TargetLocal,
+            StitchAlignmentMode::Global => AlignmentMode::Global,
+        }
+    }
+}
+
+/// The options for the aligner, with the same meaning and defaults as `stitch align`.
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct StitchOptions {
+    /// The alignment mode.
+    pub mode: StitchAlignmentMode,
+    /// The score for a matching base (should be positive).
+    pub match_score: i32,
+    /// The score for a mismatching base (should not be positive).
+    pub mismatch_score: i32,
+    /// The score for opening a gap (should not be positive).
+    pub gap_open: i32,
+    /// The score for extending a gap (should not be positive).
+    pub gap_extend: i32,
+    /// The score for jumping to the same contig and strand (should not be positive).
+    pub jump_score_same_contig_and_strand: i32,
+    /// The score for jumping to the same contig and opposite strand (should not be positive).
+    pub jump_score_same_contig_opposite_strand: i32,
+    /// The score for jumping to a different contig (should not be positive).
+    pub jump_score_inter_contig: i32,
+}
+
+impl StitchOptions {
+    /// Returns the builder of the jump-aware aligner with these options.
+    fn builder(&self) -> Builder {
+        Builder::default()
+            .mode(self.mode.into())
+            .match_score(self.match_score)
+            .mismatch_score(self.mismatch_score)
+            .gap_open(self.gap_open)
+            .gap_extend(self.gap_extend)
+            .jump_score_same_contig_and_strand(Some(self.jump_score_same_contig_and_strand))
+            .jump_score_same_contig_opposite_strand(Some(
+                self.jump_score_same_contig_opposite_strand,
+            ))
+            .jump_score_inter_contig(Some(self.jump_score_inter_contig))
+            .clone()
+    }
+}
+
+impl Default for StitchOptions {
+    fn default() -> Self {
+        Self {
+            mode: StitchAlignmentMode::Local,
+            match_score: 1,
+            mismatch_score: -4,
+            gap_open: -6,
+            gap_extend: -2,
+            jump_score_same_contig_and_strand: -10,
+            jump_score_same_contig_opposite_strand: -10,
+            jump_score_inter_contig: -10,
+        }
+    }
+}
+
+/// A jump-aware aligner of queries to one or more contigs, created with [`stitch_aligner_new`]
+/// and freed with [`stitch_aligner_free`].
+pub struct StitchAligner {
+    aligner: MultiContigAligner<MatchParams>,
+    scoring: Scoring<MatchParams>,
+}
+
+/// The alignment of a query, created by [`stitch_align`] and freed with
+/// [`stitch_alignment_free`].
+///
+/// Contig coordinates are 0-based on the strand of the contig, with the start on the first
+/// aligned contig and the end on the last aligned contig (these differ if the alignment jumps
+/// between contigs).  If the query did not align, the CIGAR is empty.
+#[repr(C)]
+#[derive(Debug)]
+pub struct StitchAlignment {
+    /// The alignment score.
+    pub score: i32,
+    /// The 0-based start of the alignment in the query.
+    pub query_start: usize,
+    /// The 0-based exclusive end of the alignment in the query.
+    pub query_end: usize,
+    /// The index of the first aligned contig, as returned by [`stitch_add_contig`].
+    pub start_contig_idx: usize,
+    /// The 0-based start of the alignment in the first aligned contig.
+    pub contig_start: usize,
+    /// The index of the last aligned contig, as returned by [`stitch_add_contig`].
+    pub end_contig_idx: usize,
+    /// The 0-based exclusive end of the alignment in the last aligned contig.
+    pub contig_end: usize,
+    /// The NUL-terminated CIGAR of the alignment, including clipping and jumps.
+    pub cigar: *mut c_char,
+}
+
+/// Returns the default options, the same as the defaults of `stitch align`.
+#[no_mangle]
+pub extern "C" fn stitch_options_default() -> StitchOptions {
+    StitchOptions::default()
+}
+
+/// Returns the message of the most recent failure on the calling thread, or `NULL` if none.  The
+/// message is owned by the library and valid until the next failure on the same thread.
+#[no_mangle]
+pub extern "C" fn stitch_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+/// Creates a new aligner with the given options, or the default options if `options` is
+/// `NULL`.  Returns `NULL` if the options are invalid.
+///
+/// # Safety
+///
+/// `options` must be `NULL` or point to a valid [`StitchOptions`].
+#[no_mangle]
+pub unsafe extern "C" fn stitch_aligner_new(options: *const StitchOptions) -> *mut StitchAligner {
+    let options = options.as_ref().copied().unwrap_or_default();
+    guard(ptr::null_mut(), || {
+        let builder = options.builder();
+        builder.validate().map_err(|error| error.to_string())?;
+        let aligner = StitchAligner {
+            aligner: MultiContigAligner::new(),
+            scoring: builder.build_scoring(),
+        };
+        Ok(Box::into_raw(Box::new(aligner)))
+    })
+}
+
+/// Frees an aligner created with [`stitch_aligner_new`].  Does nothing if `aligner` is `NULL`.
+///
+/// # Safety
+///
+/// `aligner` must be `NULL` or a pointer returned by [`stitch_aligner_new`] that has not
+/// already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn stitch_aligner_free(aligner: *mut StitchAligner) {
+    if !aligner.is_null() {
+        drop(Box::from_raw(aligner));
+    }
+}
+
+/// Adds a strand of a contig to the aligner, returning the index of the contig in the aligner, or
+/// -1 on failure (e.g. if the same strand of the contig was already added).
+///
+/// The sequence is always given for the forward strand of the contig; if `is_forward` is false,
+/// the aligner aligns to its reverse complement.  To align to both strands, add the contig twice.
+///
+/// # Safety
+///
+/// `aligner` must be a valid aligner, `name` a NUL-terminated string, and `seq` must point to
+/// `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn stitch_add_contig(
+    aligner: *mut StitchAligner,
+    name: *const c_char,
+    seq: *const u8,
+    len: usize,
+    is_forward: bool,
+    circular: bool,
+) -> i64 {
+    guard(-1, || {
+        let aligner = aligner.as_mut().ok_or("aligner must not be NULL")?;
+        if name.is_null() {
+            return Err("name must not be NULL".to_string());
+        }
+        let name = CStr::from_ptr(name)
+            .to_str()
+            .map_err(|e| format!("name is not valid UTF-8: {e}"))?;
+        let seq = bytes(seq, len, "seq")?.to_ascii_uppercase();
+        let seq = if is_forward {
+            seq
+        } else {
+            reverse_complement(&seq)
+        };
+        let contig_idx = aligner.aligner.len();
+        let scoring = aligner.scoring;
+        aligner
+            .aligner
+            .add_contig(name, is_forward, &seq, circular, scoring);
+        Ok(contig_idx as i64)
+    })
+}
+
+/// Aligns the query to the contigs added to the aligner, returning the best alignment, or `NULL`
+/// on failure (e.g. if no contigs were added).
+///
+/// # Safety
+///
+/// `aligner` must be a valid aligner, and `query` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn stitch_align(
+    aligner: *mut StitchAligner,
+    query: *const u8,
+    len: usize,
+) -> *mut StitchAlignment {
+    guard(ptr::null_mut(), || {
+        let aligner = aligner.as_mut().ok_or("aligner must not be NULL")?;
+        if aligner.aligner.is_empty() {
+            return Err("no contigs were added to the aligner".to_string());
+        }
+        let query = bytes(query, len, "query")?.to_ascii_uppercase();
+        let alignment = aligner.aligner.custom(&query);
+        let cigar = CString::new(alignment.cigar()).map_err(|e| e.to_string())?;
+        let alignment = StitchAlignment {
+            score: alignment.score,
+            query_start: alignment.ystart,
+            query_end: alignment.yend,
+            start_contig_idx: alignment.start_contig_idx,
+            contig_start: alignment.xstart,
+            end_contig_idx: alignment.end_contig_idx,
+            contig_end: alignment.xend,
+            cigar: cigar.into_raw(),
+        };
+        Ok(Box::into_raw(Box::new(alignment)))
+    })
+}
+
+/// Frees an alignment returned by [`stitch_align`].  Does nothing if `alignment` is `NULL`.
+///
+/// # Safety
+///
+/// `alignment` must be `NULL` or a pointer returned by [`stitch_align`] that has not already
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn stitch_alignment_free(alignment: *mut StitchAlignment) {
+    if !alignment.is_null() {
+        let alignment = Box::from_raw(alignment);
+        if !alignment.cigar.is_null() {
+            drop(CString::from_raw(alignment.cigar));
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use std::{
+        ffi::{CStr, CString},
+        ptr,
+    };
+
+    use rstest::rstest;
+    use stitch::util::dna::reverse_complement;
+
+    use super::{
+        stitch_add_contig, stitch_align, stitch_aligner_free, stitch_aligner_new,
+        stitch_alignment_free, stitch_last_error, stitch_options_default, StitchAlignment,
+        StitchAlignmentMode,
+    };
+
+    const CHR1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const CHR2: &str = "TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(stitch_last_error()) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    fn cigar(alignment: &StitchAlignment) -> String {
+        unsafe { CStr::from_ptr(alignment.cigar) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[rstest]
+    fn test_align() {
+        unsafe {
+            let aligner = stitch_aligner_new(ptr::null());
+            assert!(!aligner.is_null());
+            for (name, seq) in [("chr1", CHR1), ("chr2", CHR2)] {
+                let name = CString::new(name).unwrap();
+                for is_forward in [true, false] {
+                    let idx = stitch_add_contig(
+                        aligner,
+                        name.as_ptr(),
+                        seq.as_ptr(),
+                        seq.len(),
+                        is_forward,
+                        false,
+                    );
+                    assert!(idx >= 0);
+                }
+            }
+
+            // chr1:0-20 then the reverse strand of chr2:20-40
+            let query = [
+                &CHR1.as_bytes()[..20],
+                &reverse_complement(&CHR2.as_bytes()[20..]),
+            ]
+            .concat();
+            let alignment = stitch_align(aligner, query.as_ptr(), query.len());
+            assert!(!alignment.is_null());
+            {
+                let alignment = &*alignment;
+                assert_eq!(alignment.score, 30);
+                assert_eq!((alignment.query_start, alignment.query_end), (0, 40));
+                assert_eq!((alignment.start_contig_idx, alignment.contig_start), (0, 0));
+                assert_eq!((alignment.end_contig_idx, alignment.contig_end), (3, 20));
+                assert_eq!(cigar(alignment), "20=3C20j20=20A");
+            }
+            stitch_alignment_free(alignment);
+            stitch_aligner_free(aligner);
+        }
+    }
+
+    #[rstest]
+    fn test_options() {
+        let mut options = stitch_options_default();
+        assert_eq!(options.mode, StitchAlignmentMode::Local);
+        unsafe {
+            options.jump_score_inter_contig = 1;
+            assert!(stitch_aligner_new(&options).is_null());
+            assert_eq!(
+                last_error(),
+                "The jump_score_inter_contig score must be at most minus the match score (-1), \
+                 found 1: alignments would jump to align each matching base"
+            );
+
+            options.jump_score_inter_contig = -10;
+            options.mode = StitchAlignmentMode::Global;
+            let aligner = stitch_aligner_new(&options);
+            assert!(!aligner.is_null());
+            let name = CString::new("chr1").unwrap();
+            stitch_add_contig(
+                aligner,
+                name.as_ptr(),
+                CHR1.as_ptr(),
+                CHR1.len(),
+                true,
+                false,
+            );
+            let query = &CHR1.as_bytes()[10..30];
+            let alignment = stitch_align(aligner, query.as_ptr(), query.len());
+            // the full contig is aligned, with the contig bases missing from the query deleted
+            assert_eq!(cigar(&*alignment), "10J20=10J");
+            stitch_alignment_free(alignment);
+            stitch_aligner_free(aligner);
+        }
+    }
+
+    #[rstest]
+    fn test_errors() {
+        unsafe {
+            let aligner = stitch_aligner_new(ptr::null());
+            let query = b"ACGT";
+            assert!(stitch_align(aligner, query.as_ptr(), query.len()).is_null());
+            assert_eq!(last_error(), "no contigs were added to the aligner");
+
+            let name = CString::new("chr1").unwrap();
+            let idx =
+                stitch_add_contig(aligner, ptr::null(), CHR1.as_ptr(), CHR1.len(), true, false);
+            assert_eq!(idx, -1);
+            assert_eq!(last_error(), "name must not be NULL");
+            let idx = stitch_add_contig(
+                aligner,
+                name.as_ptr(),
+                CHR1.as_ptr(),
+                CHR1.len(),
+                true,
+                false,
+            );
+            assert_eq!(idx, 0);
+            let idx = stitch_add_contig(
+                aligner,
+                name.as_ptr(),
+                CHR1.as_ptr(),
+                CHR1.len(),
+                true,
+                false,
+            );
+            assert_eq!(idx, -1);
+            assert!(last_error().starts_with("Contig already added!"));
+
+            assert!(stitch_align(aligner, ptr::null(), 0).is_null());
+            assert_eq!(last_error(), "query must not be NULL");
+            assert!(stitch_align(ptr::null_mut(), query.as_ptr(), query.len()).is_null());
+            assert_eq!(last_error(), "aligner must not be NULL");
+
+            stitch_aligner_free(aligner);
+            stitch_aligner_free(ptr::null_mut());
+            stitch_alignment_free(ptr::null_mut());
+        }
+    }
+
+    /// Check that every exported function is declared in the header.
+    #[rstest]
+    fn test_header() {
+        let header = include_str!("../include/stitch.h");
+        let source = include_str!("lib.rs");
+        let names = source
+            .lines()
+            .filter_map(|line| line.split_once("extern \"C\" fn "))
+            .filter(|(prefix, _)| prefix.starts_with("pub"))
+            .map(|(_, rest)| rest.split('(').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names.len(), 7);
+        for name in names {
+            assert!(header.contains(&format!("{name}(")), "missing {name}");
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Benchmarks of the aligner on the synthetic datasets of [`fg_stitch_lib::bench_util`], run with
+//! `cargo bench -p fg-stitch-lib --features test-util`.
+//!
+//! The `align` group fills the alignment matrices of every query of a dataset and traces back its
+//! best alignment, with the throughput in cells of the matrices, so it mostly measures the filling
+//! of the columns.  The `traceback` group traces back all the alignments of the first query of a
+//! dataset from matrices already filled, so it measures the traceback alone.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use fg_stitch_lib::bench_util::{Dataset, DatasetKind};
+
+/// The seed of the datasets, so that the numbers of different runs are comparable.
+const SEED: u64 = 42;
+
+fn bench_align(c: &mut Criterion) {
+    let mut group = c.benchmark_group("align");
+    for kind in DatasetKind::ALL {
+        let dataset = Dataset::new(kind, SEED);
+        let mut aligner = dataset.aligner();
+        group.throughput(Throughput::Elements(dataset.num_cells()));
+        group.bench_function(kind.to_string(), |b| {
+            b.iter(|| {
+                for query in &dataset.queries {
+                    black_box(aligner.custom(black_box(query)));
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_traceback(c: &mut Criterion) {
+    let mut group = c.benchmark_group("traceback");
+    for kind in DatasetKind::ALL {
+        let dataset = Dataset::new(kind, SEED);
+        let mut aligner = dataset.aligner();
+        let query = &dataset.queries[0];
+        aligner.custom(query);
+        group.bench_function(kind.to_string(), |b| {
+            b.iter(|| black_box(aligner.traceback_all(query.len(), None)));
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    // The long reads take a while to align
+    config = Criterion::default().sample_size(10);
+    targets = bench_align, bench_traceback
+}
+criterion_main!(benches);
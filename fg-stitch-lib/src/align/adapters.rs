@@ -0,0 +1,319 @@
+//! Detection of adapter (or backbone) sequences within long reads, and the splitting of
+//! concatemeric reads at the adapters before alignment, so that each insert is aligned as its own
+//! read rather than as a chain of jumps through the adapters.
+
+use bio::alignment::pairwise::MatchFunc;
+use seq_io::fastq::OwnedRecord as FastqOwnedRecord;
+
+use super::{
+    aligners::{constants::MIN_PENALTY, multi_contig_aligner::MultiContigAligner},
+    scoring::Scoring,
+};
+use crate::util::target_seq::TargetSeq;
+
+/// The tag of the records of a read split at adapters, with the segment of the read, as
+/// `<index>/<count>:<start>-<end>`, with the 1-based index of the segment, the number of segments,
+/// and the 0-based half-open range of the segment in the read before splitting.
+pub const SEGMENT_TAG: &str = "sg";
+
+/// The tag of the records of a read with the adapters found in the read, as a comma-separated
+/// list of `<adapter><strand>:<start>-<end>`, with the 0-based half-open range of the adapter in
+/// the read before splitting.
+pub const ADAPTER_TAG: &str = "ad";
+
+/// An adapter found in a read.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AdapterHit {
+    /// The index of the adapter.
+    pub adapter_idx: usize,
+    /// True if the forward strand of the adapter was found.
+    pub is_forward: bool,
+    /// The 0-based half-open range of the adapter in the read.
+    pub start: usize,
+    pub end: usize,
+    pub score: i32,
+}
+
+/// Finds the adapters within reads by local alignment, and splits the reads at the adapters.
+///
+/// Each strand of each adapter is a contig of an aligner with jumps disabled and free clipping of
+/// both the adapter and the read, so that the best local alignment of any adapter in the read is
+/// found.  An alignment is an adapter hit if its score is at least the minimum fraction of the
+/// score of the adapter aligned to itself, in which case the read is searched again on either side
+/// of the hit, until no more adapters are found.
+pub struct AdapterSplitter<F: MatchFunc> {
+    aligner: MultiContigAligner<F>,
+    /// The names of the adapters, in the order of their contig indexes.
+    names: Vec<String>,
+    /// The minimum score of a hit of each adapter.
+    min_scores: Vec<i32>,
+    /// The minimum length of a segment of a split read to be kept.
+    min_segment_len: usize,
+}
+
+impl<F: MatchFunc + Sync + Copy> AdapterSplitter<F> {
+    /// Creates the splitter of the given adapters.  The match and gap scores of `scoring` are
+    /// used to align the adapters, while its jump and clipping scores are ignored.
+    ///
+    /// Arguments:
+    /// - `adapters` - The adapter (or backbone) sequences.
+    /// - `scoring` - The scoring of the alignment of the adapters to the reads.
+    /// - `min_score_fraction` - The minimum score of a hit, as a fraction of the score of the
+    ///   adapter aligned to itself.
+    /// - `min_segment_len` - The minimum length of a segment of a split read to be kept.
+    pub fn new(
+        adapters: &[TargetSeq],
+        scoring: Scoring<F>,
+        min_score_fraction: f64,
+        min_segment_len: usize,
+    ) -> Self {
+        let scoring = scoring
+            .set_jump_score(MIN_PENALTY)
+            .set_xclip(0)
+            .set_yclip(0);
+        let mut aligner = MultiContigAligner::new();
+        for adapter in adapters {
+            aligner.add_contig(&adapter.name, true, &adapter.fwd, false, scoring);
+        }
+        for adapter in adapters {
+            aligner.add_contig(&adapter.name, false, &adapter.revcomp, false, scoring);
+        }
+        let min_scores = adapters
+            .iter()
+            .map(|adapter| {
+                let max_score: i32 = adapter
+                    .fwd
+                    .iter()
+                    .map(|base| scoring.match_fn.score(*base, *base))
+                    .sum();
+                (f64::from(max_score) * min_score_fraction).ceil() as i32
+            })
+            .collect();
+        Self {
+            aligner,
+            names: adapters
+                .iter()
+                .map(|adapter| adapter.name.clone())
+                .collect(),
+            min_scores,
+            min_segment_len,
+        }
+    }
+
+    /// Returns the adapters found in the read bases, sorted by their position in the read.
+    pub fn find_adapters(&mut self, seq: &[u8]) -> Vec<AdapterHit> {
+        let num_adapters = self.names.len();
+        let mut hits = Vec::new();
+        // The regions of the read left to search
+        let mut regions = Vec::new();
+        regions.push(0..seq.len());
+        while let Some(region) = regions.pop() {
+            if region.is_empty() {
+                continue;
+            }
+            let alignment = self.aligner.custom(&seq[region.clone()]);
+            if alignment.length == 0 {
+                continue;
+            }
+            let adapter_idx = alignment.start_contig_idx % num_adapters;
+            if alignment.score < self.min_scores[adapter_idx] {
+                continue;
+            }
+            let (start, end) = (
+                region.start + alignment.ystart,
+                region.start + alignment.yend,
+            );
+            hits.push(AdapterHit {
+                adapter_idx,
+                is_forward: alignment.start_contig_idx < num_adapters,
+                start,
+                end,
+                score: alignment.score,
+            });
+            regions.push(region.start..start);
+            regions.push(end..region.end);
+        }
+        hits.sort_by_key(|hit| hit.start);
+        hits
+    }
+
+    /// Splits the read at the adapters found in it, returning the segments between the adapters
+    /// (and the ends of the read) of at least the minimum length.
+    ///
+    /// A read without adapters is returned unchanged.  Otherwise, the adapters found are added to
+    /// the comment of the header of each segment (see [`ADAPTER_TAG`]), along with the segment
+    /// (see [`SEGMENT_TAG`]), and if there are multiple segments, the index of the segment is
+    /// appended to its name (e.g. `read1_2`).  If no segment is long enough, the read is
+    /// returned with only the adapters found.
+    pub fn split(&mut self, record: FastqOwnedRecord) -> Vec<FastqOwnedRecord> {
+        let hits = self.find_adapters(&record.seq);
+        if hits.is_empty() {
+            return vec![record];
+        }
+        let adapters = hits
+            .iter()
+            .map(|hit| {
+                let strand = if hit.is_forward { '+' } else { '-' };
+                let name = &self.names[hit.adapter_idx];
+                format!("{name}{strand}:{}-{}", hit.start, hit.end)
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let adapter_tag = format!("\t{ADAPTER_TAG}:Z:{adapters}");
+
+        let bounds = std::iter::once(0)
+            .chain(hits.iter().flat_map(|hit| [hit.start, hit.end]))
+            .chain(std::iter::once(record.seq.len()))
+            .collect::<Vec<_>>();
+        let segments = bounds
+            .chunks_exact(2)
+            .map(|bounds| bounds[0]..bounds[1])
+            .filter(|segment| segment.len() >= self.min_segment_len.max(1))
+            .collect::<Vec<_>>();
+        if segments.is_empty() {
+            let mut record = record;
+            record.head.extend(adapter_tag.bytes());
+            return vec![record];
+        }
+
+        let (name, comment) = match record.head.iter().position(u8::is_ascii_whitespace) {
+            Some(index) => record.head.split_at(index),
+            None => (record.head.as_slice(), [].as_slice()),
+        };
+        let count = segments.len();
+        segments
+            .into_iter()
+            .enumerate()
+            .map(|(index, segment)| {
+                let mut head = name.to_vec();
+                if count > 1 {
+                    head.extend(format!("_{}", index + 1).bytes());
+                }
+                head.extend_from_slice(comment);
+                head.extend(
+                    format!(
+                        "\t{SEGMENT_TAG}:Z:{}/{count}:{}-{}",
+                        index + 1,
+                        segment.start,
+                        segment.end
+                    )
+                    .bytes(),
+                );
+                head.extend(adapter_tag.bytes());
+                FastqOwnedRecord {
+                    head,
+                    seq: record.seq[segment.clone()].to_vec(),
+                    qual: record.qual[segment].to_vec(),
+                }
+            })
+            .collect()
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+    use seq_io::fastq::OwnedRecord as FastqOwnedRecord;
+
+    use crate::align::{scoring::Scoring, umi::comment_tag};
+    use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+    use super::{AdapterHit, AdapterSplitter, ADAPTER_TAG, SEGMENT_TAG};
+
+    const ADAPTER: &str = "AATGTACTTCGTTCAGTTACGTATTGCT";
+    const INSERT1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const INSERT2: &str = "TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    fn splitter() -> AdapterSplitter<MatchParams> {
+        let adapters = vec![TargetSeq::new(
+            "adapter",
+            &ADAPTER.as_bytes().to_vec(),
+            false,
+        )];
+        let scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4));
+        AdapterSplitter::new(&adapters, scoring, 0.8, 10)
+    }
+
+    fn record(seq: &[u8]) -> FastqOwnedRecord {
+        FastqOwnedRecord {
+            head: b"read1 comment".to_vec(),
+            seq: seq.to_vec(),
+            qual: vec![b'I'; seq.len()],
+        }
+    }
+
+    #[rstest]
+    fn test_find_adapters() {
+        let mut splitter = splitter();
+        // insert1, adapter, insert2, then the reverse complement of the adapter
+        let seq = [
+            INSERT1.as_bytes(),
+            ADAPTER.as_bytes(),
+            INSERT2.as_bytes(),
+            &reverse_complement(ADAPTER.as_bytes()),
+        ]
+        .concat();
+        let hits = splitter.find_adapters(&seq);
+        assert_eq!(
+            hits,
+            vec![
+                AdapterHit {
+                    adapter_idx: 0,
+                    is_forward: true,
+                    start: 40,
+                    end: 68,
+                    score: 28,
+                },
+                AdapterHit {
+                    adapter_idx: 0,
+                    is_forward: false,
+                    start: 108,
+                    end: 136,
+                    score: 28,
+                },
+            ]
+        );
+        assert!(splitter.find_adapters(INSERT1.as_bytes()).is_empty());
+    }
+
+    #[rstest]
+    fn test_split() {
+        let mut splitter = splitter();
+        let seq = [
+            INSERT1.as_bytes(),
+            ADAPTER.as_bytes(),
+            INSERT2.as_bytes(),
+            &reverse_complement(ADAPTER.as_bytes()),
+        ]
+        .concat();
+        let segments = splitter.split(record(&seq));
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].seq, INSERT1.as_bytes());
+        assert_eq!(segments[1].seq, INSERT2.as_bytes());
+        assert!(segments[0].head.starts_with(b"read1_1 comment\t"));
+        assert!(segments[1].head.starts_with(b"read1_2 comment\t"));
+        assert_eq!(
+            comment_tag(&segments[1].head, SEGMENT_TAG),
+            Some(b"2/2:68-108".as_slice())
+        );
+        for segment in &segments {
+            assert_eq!(
+                comment_tag(&segment.head, ADAPTER_TAG),
+                Some(b"adapter+:40-68,adapter-:108-136".as_slice())
+            );
+        }
+
+        // a read without adapters is unchanged
+        let segments = splitter.split(record(INSERT1.as_bytes()));
+        assert_eq!(segments, vec![record(INSERT1.as_bytes())]);
+
+        // a read with a single segment keeps its name
+        let seq = [INSERT1.as_bytes(), ADAPTER.as_bytes()].concat();
+        let segments = splitter.split(record(&seq));
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].seq, INSERT1.as_bytes());
+        assert!(segments[0].head.starts_with(b"read1 comment\t"));
+    }
+}
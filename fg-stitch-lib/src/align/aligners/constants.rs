@@ -6,6 +6,26 @@ use std::{fmt::Display, str::FromStr};
 /// adding two negative infinities. Use ~ `0.4 * i32::MIN`
 pub const MIN_SCORE: i32 = -858_993_459;
 
+/// The most negative value allowed for the gap, jump, and substitution scores, such that adding
+/// any two of them to a score no less than [`MIN_SCORE`] cannot underflow.  Use ~ `0.2 * i32::MIN`.
+pub const MIN_PENALTY: i32 = MIN_SCORE / 2;
+
+/// Returns the given score, or [`MIN_SCORE`] if less.  Scores in the alignment matrices are
+/// floored so that unreachable cells stay at [`MIN_SCORE`], rather than accumulating penalties
+/// towards underflow across long sequences.
+#[inline]
+pub fn floor_score(score: i32) -> i32 {
+    score.max(MIN_SCORE)
+}
+
+/// Returns the score of a gap of the given length, floored at [`MIN_SCORE`] (see
+/// [`floor_score`]) rather than underflowing for long gaps with large penalties.
+#[inline]
+pub fn gap_score(gap_open: i32, gap_extend: i32, len: usize) -> i32 {
+    let len = i32::try_from(len).unwrap_or(i32::MAX);
+    floor_score(gap_open.saturating_add(gap_extend.saturating_mul(len)))
+}
+
 pub const DEFAULT_ALIGNER_CAPACITY: usize = 200;
 
 /// Alignment operations supported are match, substitution, insertion, deletion
@@ -16,7 +36,7 @@ pub const DEFAULT_ALIGNER_CAPACITY: usize = 200;
 /// value associated with the clipping operations are the lengths clipped. In case
 /// of standard modes like Global, Semi-Global and Local alignment, the clip operations
 /// are filtered out.
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Eq, PartialEq, Debug, Copy, Clone, Hash)]
 pub enum AlignmentOperation {
     Match,               // Consumes one x and one y base
@@ -93,7 +113,7 @@ impl AlignmentOperation {
 /// appropriately set.
 ///
 /// The default alignment mode is Global.
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
 pub enum AlignmentMode {
     /// Aligns a sub-sequence of the read versus a sub-sequence of the reference
@@ -105,6 +125,11 @@ pub enum AlignmentMode {
     TargetLocal,
     /// Aligns the full read versus the full reference.
     Global,
+    /// Aligns the full read versus sub-sequences of the reference, for example a read spanning an
+    /// assembled construct, where the read is never clipped (see
+    /// [`crate::align::MultiContigAligner::set_span_query`]) rather than clipped with a score too
+    /// low to be chosen as in the target-local mode.
+    SemiGlobal,
     Custom,
 }
 
@@ -115,6 +140,7 @@ impl Display for AlignmentMode {
             Self::QueryLocal => write!(f, "query-local"),
             Self::TargetLocal => write!(f, "target-local"),
             Self::Global => write!(f, "global"),
+            Self::SemiGlobal => write!(f, "semi-global"),
             Self::Custom => write!(f, "custom"),
         }
     }
@@ -131,8 +157,47 @@ impl FromStr for AlignmentMode {
                 Ok(AlignmentMode::TargetLocal)
             }
             "global" => Ok(AlignmentMode::Global),
+            "semi-global" | "semi_global" | "semiglobal" | "span" => Ok(AlignmentMode::SemiGlobal),
             "custom" => Ok(AlignmentMode::Custom),
             _ => Err(anyhow!("Invalid alignment mode: {}", s)),
         }
     }
 }
+
+/// The algorithm used to compute an alignment.  The wavefront algorithm is only supported when
+/// computing the global alignment of a single contig without jumps; all other alignments fall back
+/// to dynamic programming.
+///
+/// The default backend is dynamic programming.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum AlignerBackend {
+    /// The full dynamic programming, supporting jumps and clipping.
+    #[default]
+    DynamicProgramming,
+    /// The gap-affine wavefront alignment (WFA) algorithm, which is fast for similar sequences.
+    Wavefront,
+}
+
+impl Display for AlignerBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DynamicProgramming => write!(f, "dp"),
+            Self::Wavefront => write!(f, "wavefront"),
+        }
+    }
+}
+
+impl FromStr for AlignerBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dp" | "dynamic-programming" | "dynamic_programming" => {
+                Ok(AlignerBackend::DynamicProgramming)
+            }
+            "wavefront" | "wfa" => Ok(AlignerBackend::Wavefront),
+            _ => Err(anyhow!("Invalid aligner backend: {}", s)),
+        }
+    }
+}
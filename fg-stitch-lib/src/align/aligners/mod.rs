@@ -6,7 +6,10 @@
 // except according to those terms.
 pub(crate) mod constants;
 pub(crate) mod multi_contig_aligner;
+pub(crate) mod pool;
+pub(crate) mod shared_contigs;
 pub(crate) mod single_contig_aligner;
+pub(crate) mod wavefront;
 
 pub use constants::AlignmentMode;
 
@@ -19,14 +22,31 @@ use crate::{
                 AlignmentOperation::{Del, Ins, Match, Subst, Xjump},
                 MIN_SCORE,
             },
-            multi_contig_aligner::MultiContigAligner,
+            multi_contig_aligner::{MultiContigAligner, ProgressCallback, StreamingAligner},
+            shared_contigs::SharedContigs,
+            single_contig_aligner::SingleContigAligner,
         },
-        alignment::Alignment,
-        scoring::Scoring,
+        alignment::{target_seq_for, Alignment},
+        all_vs_all::PerContigAligner,
+        bisulfite::{BisulfiteLibrary, BisulfiteMatchFunc},
+        filter::EditDistanceFilter,
+        jump_sites::JumpSites,
+        metrics::{stage_span, StageTimings},
+        overlap::OverlapAligner,
+        position::{ContigIdx, ContigPos},
+        read_support::ReadSetAligner,
+        scoring::{Scoring, DEFAULT_HOMOPOLYMER_MIN_LENGTH},
+        self_align::SelfAligner,
+        splice::SpliceAnnotation,
+        strategy::{estimate_identity, AlignStrategy, AutoStrategy},
         sub_alignment::SubAlignmentBuilder,
-        PrimaryPickingStrategy,
+        substitution_matrix::MatrixMatchFunc,
+        traceback::TracebackLayout,
+        AlignmentLength, ClipPolicy, CsStyle, PrimaryPickingStrategy, SkipReason,
+        SCORE_BREAKDOWN_TAG,
     },
     util::{
+        alphabet::{Alphabet, Dna, Protein},
         dna::reverse_complement,
         index_map::IndexMap,
         target_seq::{TargetHash, TargetSeq},
@@ -52,13 +72,17 @@ use noodles::{
     },
 };
 use seq_io::fastq::{OwnedRecord as FastqOwnedRecord, Record as FastqRecord};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 #[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
 pub struct JumpInfo {
     score: i32,
     len: u32,
-    idx: u32,
-    from: u32,
+    idx: ContigIdx,
+    from: ContigPos,
 }
 
 // TODO: impl Default with same values from CLI
@@ -83,14 +107,40 @@ pub struct Options {
     jump_score_same_contig_opposite_strand: Option<i32>,
     #[builder(default)]
     jump_score_inter_contig: Option<i32>,
+    #[builder(default)]
+    jump_score_forward: Option<i32>,
+    #[builder(default)]
+    jump_score_backward: Option<i32>,
+    #[builder(default)]
+    splice_jump_score: Option<i32>,
+    #[builder(default)]
+    min_segment_length: Option<usize>,
+    #[builder(default = "false")]
+    forbid_clip_jumps: bool,
+    /// Jump over runs of at least this many Ns in the contigs for free, as scaffold gaps of unknown
+    /// length (see [`MultiContigAligner::set_scaffold_gap_min_len`]).
+    #[builder(default)]
+    scaffold_gap_min_len: Option<usize>,
+    #[builder(default)]
+    homopolymer_gap_open: Option<i32>,
+    #[builder(default = "DEFAULT_HOMOPOLYMER_MIN_LENGTH")]
+    homopolymer_min_length: usize,
     #[builder(default = "12")]
     kmer_size: usize,
     #[builder(default = "50")]
     band_width: usize,
+    #[builder(default)]
+    adaptive_band_width: Option<usize>,
+    #[builder(default)]
+    parallel_block_rows: Option<usize>,
     #[builder(default = "false")]
     double_strand: bool,
     #[builder(default = "false")]
     circular: bool,
+    #[builder(default = "false")]
+    lazy_reverse_complement: bool,
+    #[builder(default = "false")]
+    canonical_contig_order: bool,
     #[builder(default = "20")]
     circular_slop: usize,
     #[builder(default = "false")]
@@ -100,11 +150,16 @@ pub struct Options {
     #[builder(default = "true")]
     pre_align_subset_contigs: bool,
     #[builder(default = "false")]
+    pre_filter: bool,
+    #[builder(default = "100")]
+    pre_filter_min_score: i32,
+    #[builder(default = "false")]
     suboptimal: bool,
     #[builder(default = "20.0")]
     suboptimal_pct: f32,
-    #[builder(default = "false")]
-    soft_clip: bool,
+    /// How the query bases outside of the aligned part of each secondary record are represented.
+    #[builder(default)]
+    clip_policy: ClipPolicy,
     #[builder(default = "false")]
     use_eq_and_x: bool,
     #[builder(default = "PrimaryPickingStrategy::default()")]
@@ -113,6 +168,68 @@ pub struct Options {
     filter_secondary: bool,
     #[builder(default = "10.0")]
     filter_secondary_pct: f32,
+    #[builder(default)]
+    tie_break_length: AlignmentLength,
+    #[builder(default)]
+    cs_tag: Option<CsStyle>,
+    /// Do not align reads whose matrices would have more than this many cells across the contigs
+    /// to align, to bound the memory used by a single read (see [`SkipReason::MatrixSize`]).
+    #[builder(default)]
+    max_matrix_cells: Option<usize>,
+    /// Align the longest prefix of a read within `max_matrix_cells`, leaving the rest of the read
+    /// unaligned (clipped), rather than not aligning the read at all.
+    #[builder(default = "false")]
+    truncate_oversized_reads: bool,
+    /// Stop aligning a read after this many milliseconds of filling its matrices, leaving the
+    /// read unaligned (see [`SkipReason::Timeout`]).
+    #[builder(default)]
+    read_timeout_ms: Option<u64>,
+    /// Align reads longer than this many bases in overlapping chunks of this many bases, merging
+    /// the alignments of the chunks (see [`Alignment::merge_chunks`]), so that the memory used
+    /// is bounded by the chunk length rather than the read length.  Only used in the local,
+    /// target-local, and semi-global modes, as each chunk is aligned to a sub-sequence of the
+    /// contigs.
+    #[builder(default)]
+    chunk_length: Option<usize>,
+    /// The number of bases by which consecutive chunks overlap (see `chunk_length`), at most
+    /// one less than the chunk length.
+    #[builder(default = "1000")]
+    chunk_overlap: usize,
+    /// Keep only every this many columns (query bases) of the traceback of each contig, and
+    /// recompute the columns in between when tracing back, trading up to twice the time for this
+    /// many times less traceback memory (see
+    /// [`MultiContigAligner::set_traceback_checkpoint_interval`]).
+    #[builder(default)]
+    traceback_checkpoint_interval: Option<usize>,
+    /// The order in which the cells of the traceback of each contig are stored (see
+    /// [`MultiContigAligner::set_traceback_layout`]), to be chosen by benchmarking the reads and
+    /// contigs at hand.
+    #[builder(default)]
+    traceback_layout: TracebackLayout,
+    /// Checkpoint the traceback of reads whose matrices would use more than this many bytes (see
+    /// [`MultiContigAligner::estimate_memory`]), with the interval that uses the least memory, and
+    /// do not align reads whose matrices would use more even then (see
+    /// [`SkipReason::MatrixSize`]).
+    #[builder(default)]
+    max_read_memory: Option<usize>,
+    /// Pick the strategy for aligning each read from its length, the lengths of the contigs, and
+    /// its identity to the targets estimated from their shared k-mers (see [`AutoStrategy`]),
+    /// overriding the pre-alignment, band, and chunk length options for reads it does not align in
+    /// full.
+    #[builder(default)]
+    auto_strategy: Option<AutoStrategy>,
+    /// Re-align a window of `refine_jumps_window` columns on either side of each jump, without a
+    /// band, to place the jump at the best query base (see [`Alignment::refine_jumps`]).
+    #[builder(default = "false")]
+    refine_jumps: bool,
+    /// The number of columns on either side of each jump re-aligned when refining jumps.
+    #[builder(default = "20")]
+    refine_jumps_window: usize,
+    /// Break down the score of each alignment into the contributions of its runs of operations
+    /// (see [`Alignment::rescore_breakdown`]), in [`Alignment::score_breakdown`] and in the
+    /// [`SCORE_BREAKDOWN_TAG`] tag of its records.
+    #[builder(default = "false")]
+    score_breakdown: bool,
 }
 
 impl Options {
@@ -124,7 +241,7 @@ impl Options {
         match self.mode {
             AlignmentMode::Local => (0, 0, 0, 0),
             AlignmentMode::QueryLocal => (MIN_SCORE, MIN_SCORE, 0, 0),
-            AlignmentMode::TargetLocal => (0, 0, MIN_SCORE, MIN_SCORE),
+            AlignmentMode::TargetLocal | AlignmentMode::SemiGlobal => (0, 0, MIN_SCORE, MIN_SCORE),
             AlignmentMode::Global => (MIN_SCORE, MIN_SCORE, MIN_SCORE, MIN_SCORE),
             AlignmentMode::Custom => panic!("Custom alignment mode not supported"), // TODO: move to main run method
         }
@@ -140,35 +257,84 @@ impl Options {
             .yclip_suffix(yclip_suffix)
     }
 
+    /// Builds the scoring field by field, rather than with the constructor and setters of
+    /// [`Scoring`], which panic on positive scores, so that pathological scores are reported by
+    /// [`Scoring::validate`] instead.
     fn contig_scoring(&self) -> Scoring<MatchParams> {
-        let jump_score_same_contig_and_strand = self
-            .jump_score_same_contig_and_strand
-            .unwrap_or(self.default_jump_score);
-        let jump_score_same_contig_opposite_strand = self
-            .jump_score_same_contig_opposite_strand
-            .unwrap_or(self.default_jump_score);
-        let jump_score_inter_contig = self
-            .jump_score_inter_contig
-            .unwrap_or(self.default_jump_score);
-        let match_params = self.match_params();
+        let jump_score = |score: Option<i32>| score.unwrap_or(self.default_jump_score);
         let (xclip_prefix, xclip_suffix, yclip_prefix, yclip_suffix) = self.clipping();
-        Scoring::with_jump_scores(
-            self.gap_open,
-            self.gap_extend,
-            jump_score_same_contig_and_strand,
-            jump_score_same_contig_opposite_strand,
-            jump_score_inter_contig,
-            match_params,
-        )
-        .set_xclip_prefix(xclip_prefix)
-        .set_xclip_suffix(xclip_suffix)
-        .set_yclip_prefix(yclip_prefix)
-        .set_yclip_suffix(yclip_suffix)
+        Scoring {
+            gap_open: self.gap_open,
+            gap_extend: self.gap_extend,
+            jump_score_same_contig_and_strand: jump_score(self.jump_score_same_contig_and_strand),
+            jump_score_same_contig_opposite_strand: jump_score(
+                self.jump_score_same_contig_opposite_strand,
+            ),
+            jump_score_inter_contig: jump_score(self.jump_score_inter_contig),
+            jump_score_forward: self.jump_score_forward,
+            jump_score_backward: self.jump_score_backward,
+            splice_jump_score: self.splice_jump_score,
+            homopolymer_gap_open: self.homopolymer_gap_open,
+            homopolymer_min_length: self.homopolymer_min_length,
+            match_fn: self.match_params(),
+            match_scores: None,
+            xclip_prefix,
+            xclip_suffix,
+            yclip_prefix,
+            yclip_suffix,
+        }
     }
 }
 
 impl Builder {
-    pub fn build_aligners<'a>(&self, target_seqs: &'a [TargetSeq]) -> Aligners<'a, MatchParams> {
+    /// Builds the scoring used by the jump-aware aligner.
+    pub fn build_scoring(&self) -> Scoring<MatchParams> {
+        self.build_options().unwrap().contig_scoring()
+    }
+
+    /// Checks that the scores are not pathological (see [`Scoring::validate`]), returning an
+    /// error describing the first problem found otherwise.
+    pub fn validate(&self) -> Result<()> {
+        let opts = self.build_options()?;
+        ensure!(
+            opts.match_score > 0,
+            "The match score must be positive, found {}: the empty alignment would be optimal",
+            opts.match_score
+        );
+        ensure!(
+            opts.mismatch_score <= 0,
+            "The mismatch score must not be positive, found {}",
+            opts.mismatch_score
+        );
+        ensure!(
+            opts.mode != AlignmentMode::Custom,
+            "The custom alignment mode is not supported by the aligners, which take the clip \
+             scores from the mode"
+        );
+        opts.contig_scoring().validate()
+    }
+
+    pub fn build_aligners(&self, target_seqs: &[TargetSeq]) -> Aligners<MatchParams> {
+        self.build_aligners_with(&self.build_shared_contigs(target_seqs))
+    }
+
+    /// Packs the given contigs once, on the strands to align to, so that the aligners of many
+    /// threads may be built from them without each copying the contigs (see
+    /// [`Self::build_aligners_with`]).
+    pub fn build_shared_contigs(&self, target_seqs: &[TargetSeq]) -> SharedContigs {
+        let opts = self.build_options().unwrap();
+        SharedContigs::new(
+            target_seqs,
+            opts.circular,
+            opts.double_strand,
+            opts.lazy_reverse_complement,
+        )
+    }
+
+    /// Builds the aligners as [`Self::build_aligners`], sharing the sequences of the given
+    /// contigs rather than copying them.  Only the alignment matrices are specific to the
+    /// aligners, so one set of contigs, e.g. in an [`Arc`], serves the aligners of every thread.
+    pub fn build_aligners_with(&self, contigs: &SharedContigs) -> Aligners<MatchParams> {
         let opts = self.build_options().unwrap();
         // Banded alignment is always local since the goal is to find at least some minimal scoring
         // local alignment.
@@ -179,35 +345,138 @@ impl Builder {
             opts.kmer_size,
             opts.band_width,
         );
-        let capacity = target_seqs.len() * (if opts.double_strand { 2 } else { 1 });
-        let mut multi_contig: MultiContigAligner<'a, MatchParams> =
+        let capacity = contigs.len() * (if contigs.double_strand() { 2 } else { 1 });
+        let mut multi_contig: MultiContigAligner<MatchParams> =
             MultiContigAligner::with_capacity(capacity);
+        multi_contig.set_lazy_reverse_complement(opts.lazy_reverse_complement);
+        multi_contig.set_canonical_contig_order(opts.canonical_contig_order);
+        multi_contig.set_adaptive_band_width(opts.adaptive_band_width);
+        multi_contig.set_parallel_block_rows(opts.parallel_block_rows);
+        multi_contig.set_span_query(opts.mode == AlignmentMode::SemiGlobal);
+        multi_contig.set_forbid_clip_jumps(opts.forbid_clip_jumps);
+        multi_contig.set_scaffold_gap_min_len(opts.scaffold_gap_min_len);
+        multi_contig.set_traceback_checkpoint_interval(opts.traceback_checkpoint_interval);
+        multi_contig.set_traceback_layout(opts.traceback_layout);
         let multi_contig_scoring = opts.contig_scoring();
+        multi_contig.add_shared_contigs(contigs, |_| multi_contig_scoring);
+        Aligners {
+            banded,
+            multi_contig,
+            opts,
+            skip_reason: None,
+            strategy: None,
+        }
+    }
+
+    /// Builds the aligner of queries to each contig individually without jumps, with the same
+    /// contigs and strands as [`Self::build_aligners`] (see [`PerContigAligner`]).
+    pub fn build_per_contig_aligner(
+        &self,
+        target_seqs: &[TargetSeq],
+    ) -> PerContigAligner<MatchParams> {
+        let opts = self.build_options().unwrap();
+        PerContigAligner::new(target_seqs, opts.double_strand, opts.contig_scoring())
+    }
+
+    /// Builds the jump-aware aligner of bisulfite-converted queries, with the contigs and strands
+    /// of [`Self::build_aligners`].  The conversions of the given library are scored as matches,
+    /// with a match function specific to the strand of each contig (see [`BisulfiteMatchFunc`]).
+    pub fn build_bisulfite_aligner(
+        &self,
+        target_seqs: &[TargetSeq],
+        library: BisulfiteLibrary,
+    ) -> MultiContigAligner<BisulfiteMatchFunc> {
+        let opts = self.build_options().unwrap();
+        self.build_multi_contig_aligner::<Dna, _>(target_seqs, |is_forward| {
+            opts.contig_scoring()
+                .set_match_fn(BisulfiteMatchFunc::for_strand(
+                    opts.match_score,
+                    opts.mismatch_score,
+                    is_forward,
+                    library,
+                ))
+        })
+    }
+
+    /// Builds the jump-aware aligner of amino acid queries to amino acid contigs, scored with the
+    /// given substitution matrix (e.g. [`MatrixMatchFunc::blosum62`]) and the gap, jump, and
+    /// clipping scores of [`Self::build_aligners`].  The contigs are aligned on their forward
+    /// strand only, whatever the double strand option.
+    pub fn build_protein_aligner(
+        &self,
+        target_seqs: &[TargetSeq],
+        matrix: MatrixMatchFunc,
+    ) -> MultiContigAligner<MatrixMatchFunc> {
+        let opts = self.build_options().unwrap();
+        self.build_multi_contig_aligner::<Protein, _>(target_seqs, |_| {
+            opts.contig_scoring().set_match_fn(matrix)
+        })
+    }
+
+    /// Builds the jump-aware aligner of the contigs of the given alphabet, with the scoring of
+    /// each strand given by `scoring_for_strand` (true for the forward strand).  The reverse
+    /// strands are added only if the alphabet has strands and the double strand option is set.
+    fn build_multi_contig_aligner<A: Alphabet, F: MatchFunc + Sync>(
+        &self,
+        target_seqs: &[TargetSeq],
+        scoring_for_strand: impl Fn(bool) -> Scoring<F>,
+    ) -> MultiContigAligner<F> {
+        let opts = self.build_options().unwrap();
+        let double_strand = A::HAS_STRANDS && opts.double_strand;
+        let capacity = target_seqs.len() * (if double_strand { 2 } else { 1 });
+        let mut multi_contig: MultiContigAligner<F> = MultiContigAligner::with_capacity(capacity);
+        multi_contig.set_lazy_reverse_complement(A::HAS_STRANDS && opts.lazy_reverse_complement);
+        multi_contig.set_canonical_contig_order(opts.canonical_contig_order);
+        multi_contig.set_span_query(opts.mode == AlignmentMode::SemiGlobal);
+        multi_contig.set_forbid_clip_jumps(opts.forbid_clip_jumps);
+        // Only nucleotide sequences have Ns for unknown bases
+        if A::HAS_STRANDS {
+            multi_contig.set_scaffold_gap_min_len(opts.scaffold_gap_min_len);
+        }
         for target_seq in target_seqs {
             multi_contig.add_contig(
                 &target_seq.name,
                 true,
                 &target_seq.fwd,
                 opts.circular,
-                multi_contig_scoring,
+                scoring_for_strand(true),
             );
         }
-        if opts.double_strand {
+        if double_strand {
             for target_seq in target_seqs {
                 multi_contig.add_contig(
                     &target_seq.name,
                     false,
                     &target_seq.revcomp,
                     opts.circular,
-                    multi_contig_scoring,
+                    scoring_for_strand(false),
                 );
             }
         }
-        Aligners {
-            banded,
-            multi_contig,
-            opts,
-        }
+        multi_contig
+    }
+
+    /// Builds the aligner of a suffix of one contig to a prefix of another, with the match,
+    /// mismatch, and gap scores of [`Self::build_aligners`] (see [`OverlapAligner`]).
+    pub fn build_overlap_aligner(&self) -> OverlapAligner<MatchParams> {
+        let opts = self.build_options().unwrap();
+        OverlapAligner::new(opts.contig_scoring())
+    }
+
+    /// Builds the aligner of a sequence to itself, to find its duplications and inverted repeats
+    /// of at least `min_len` bases, with the match, mismatch, gap, and jump scores of
+    /// [`Self::build_aligners`] (see [`SelfAligner`]).
+    pub fn build_self_aligner(&self, min_len: usize) -> SelfAligner<MatchParams> {
+        let opts = self.build_options().unwrap();
+        SelfAligner::new(opts.contig_scoring(), min_len)
+    }
+
+    /// Builds the aligner of a read to the given reads as contigs, with the scoring of
+    /// [`Self::build_aligners`] (see [`ReadSetAligner`]).  The local mode suits the reads, which
+    /// overlap each other only in part.
+    pub fn build_read_set_aligner(&self, reads: &[TargetSeq]) -> ReadSetAligner<MatchParams> {
+        let opts = self.build_options().unwrap();
+        ReadSetAligner::new(reads, opts.contig_scoring())
     }
 
     pub fn build_sam_record_formatter<'a>(
@@ -224,30 +493,146 @@ impl Builder {
     }
 }
 
-pub struct Aligners<'a, F: MatchFunc> {
+pub struct Aligners<F: MatchFunc> {
     // Aligner used to quickly determine if there are ANY high-quality local alignments.
     banded: BandedAligner<MatchParams>,
     // Aligner used when there are more than one contig (or double strand, or both)
-    multi_contig: MultiContigAligner<'a, F>,
+    multi_contig: MultiContigAligner<F>,
     // The alignment mode
     opts: Options,
+    // The reason the most recent query was not aligned, if any
+    skip_reason: Option<SkipReason>,
+    // The strategy picked for the most recent query, if picking automatically
+    strategy: Option<AlignStrategy>,
 }
 
-impl Aligners<'_, MatchParams> {
+impl Aligners<MatchParams> {
+    /// Sets the callback invoked after each column of the jump-aware alignment of a query is
+    /// filled (see [`MultiContigAligner::set_progress_callback`]).
+    pub fn set_progress_callback(&mut self, progress_callback: Option<ProgressCallback>) {
+        self.multi_contig.set_progress_callback(progress_callback);
+    }
+
+    /// Sets the positions of the contigs that the jump-aware alignment of a query may jump from or
+    /// to (see [`MultiContigAligner::set_jump_sites`]).
+    pub fn set_jump_sites(&mut self, jump_sites: Option<Arc<JumpSites>>) {
+        self.multi_contig.set_jump_sites(jump_sites);
+    }
+
+    /// Sets the annotated introns of the contigs, over which the jump-aware alignment of a query
+    /// jumps with the splice jump score (see [`MultiContigAligner::set_splice_annotation`]).
+    pub fn set_splice_annotation(&mut self, splice_annotation: Option<Arc<SpliceAnnotation>>) {
+        self.multi_contig.set_splice_annotation(splice_annotation);
+    }
+
+    /// Sets whether to record the score surface of each contig in the jump-aware alignment of a
+    /// query (see [`MultiContigAligner::set_record_score_surfaces`]).
+    #[cfg(feature = "viz")]
+    pub fn set_record_score_surfaces(&mut self, record: bool) {
+        self.multi_contig.set_record_score_surfaces(record);
+    }
+
+    /// Returns the score surface of each contig from the most recent jump-aware alignment (see
+    /// [`MultiContigAligner::score_surfaces`]).
+    #[cfg(feature = "viz")]
+    pub fn score_surfaces(&self) -> &[crate::align::viz::ScoreSurface] {
+        self.multi_contig.score_surfaces()
+    }
+
+    /// Sets whether to record the time spent in each stage of the jump-aware alignment of a query
+    /// (see [`MultiContigAligner::set_record_timings`]).
+    pub fn set_record_timings(&mut self, record: bool) {
+        self.multi_contig.set_record_timings(record);
+    }
+
+    /// Returns the time spent in each stage of the jump-aware alignment of the query most recently
+    /// given to [`Self::align`], if recording (see [`Self::set_record_timings`]).
+    pub fn timings(&self) -> Option<&StageTimings> {
+        self.multi_contig.timings()
+    }
+
+    /// Starts the jump-aware alignment of a query whose bases arrive over time (see
+    /// [`StreamingAligner`]).  Unlike [`Self::align`], the query is not pre-filtered, pre-aligned,
+    /// or re-aligned around the origin, and its bases should be upper case.
+    pub fn streaming(&mut self) -> StreamingAligner<'_, MatchParams> {
+        self.multi_contig.streaming()
+    }
+
+    /// Returns the reason the query most recently given to [`Self::align`] was not aligned, if it
+    /// was stopped by the matrix size or time guards.
+    pub fn skip_reason(&self) -> Option<SkipReason> {
+        self.skip_reason
+    }
+
+    /// Returns the strategy picked for aligning the query most recently given to [`Self::align`],
+    /// if picking automatically (see [`Builder::auto_strategy`]).
+    pub fn strategy(&self) -> Option<AlignStrategy> {
+        self.strategy
+    }
+
     pub fn align(
         &mut self,
         record: &FastqOwnedRecord,
         target_seqs: &[TargetSeq],
         target_hashes: &[TargetHash],
     ) -> (Vec<Alignment>, Option<i32>) {
+        self.skip_reason = None;
+        self.strategy = None;
+        self.multi_contig.reset_timings();
+        stage_span!(DEBUG, "align_read", read_length = record.seq().len());
         let query = record
             .seq()
             .iter()
             .map(u8::to_ascii_uppercase)
             .collect_vec();
+        // A read without bases is unmapped
+        if query.is_empty() {
+            return (Vec::new(), None);
+        }
+        // Only align to contigs where the best local alignment (without jumps) could reach the
+        // minimum score, based on the edit distance of the query to the contig.  The bound is per
+        // contig, so a query whose alignment only reaches the minimum score by jumping between
+        // contigs is unmapped.
+        let prefiltered_contigs: Option<BitSet<u32>> = if self.opts.pre_filter {
+            let indexes = self.prefilter_contigs(&query, target_seqs);
+            // If there was no contig that could have a good enough alignment, return None now.
+            if indexes.is_empty() {
+                return (Vec::new(), None);
+            }
+            Some(indexes)
+        } else {
+            None
+        };
+
+        // Pick the strategy for the query, if picking automatically
+        if let Some(auto) = self.opts.auto_strategy {
+            let identity = estimate_identity(&query, target_hashes, self.opts.kmer_size);
+            let num_cells = self
+                .multi_contig
+                .num_cells(query.len(), prefiltered_contigs.as_ref());
+            let strategy = auto.choose(
+                query.len(),
+                num_cells,
+                target_seqs.len(),
+                self.is_local(),
+                identity,
+            );
+            let band_width = match strategy {
+                AlignStrategy::Banded => Some(auto.band_width),
+                _ => self.opts.adaptive_band_width,
+            };
+            self.multi_contig.set_adaptive_band_width(band_width);
+            self.strategy = Some(strategy);
+        }
+        // Seeding and extending finds the contigs to align to by pre-aligning, but aligns to all
+        // the contigs if none pre-aligned
+        let seed_and_extend = self.strategy == Some(AlignStrategy::SeedAndExtend);
+        let pre_align = self.opts.pre_align || seed_and_extend;
+        let pre_align_subset_contigs = self.opts.pre_align_subset_contigs || seed_and_extend;
+
         let mut contig_idx_to_prealign_score: IndexMap<i32> =
             IndexMap::new(self.multi_contig.len());
-        if self.opts.pre_align {
+        if pre_align {
             // Align the record to all the targets in a local banded alignment. If there is at least one
             // alignment with minimum score, then align uses the full aligner.
             for (index, target_seq) in target_seqs.iter().enumerate() {
@@ -277,34 +662,93 @@ impl Aligners<'_, MatchParams> {
                     );
                 }
                 // If we are going to align to all the contigs anyhow, then we can stop here.
-                if !self.opts.pre_align_subset_contigs && !contig_idx_to_prealign_score.is_empty() {
+                if !pre_align_subset_contigs && !contig_idx_to_prealign_score.is_empty() {
                     break;
                 }
             }
             // If there was no contig with a good enough alignment, return None now.
-            if contig_idx_to_prealign_score.is_empty() {
+            if contig_idx_to_prealign_score.is_empty() && !seed_and_extend {
                 return (Vec::new(), None);
             }
         }
 
         // Get the contigs to align based on if we pre-aligned or not
         let contigs_to_align: Option<BitSet<u32>> =
-            if self.opts.pre_align && self.opts.pre_align_subset_contigs {
-                let indexes = contig_idx_to_prealign_score.keys().collect::<BitSet<u32>>();
-                assert!(!indexes.is_empty(), "Bug: should have returned above");
+            if pre_align && pre_align_subset_contigs && !contig_idx_to_prealign_score.is_empty() {
+                let mut indexes = contig_idx_to_prealign_score.keys().collect::<BitSet<u32>>();
+                if let Some(prefiltered) = &prefiltered_contigs {
+                    indexes.intersect_with(prefiltered);
+                    if indexes.is_empty() {
+                        return (Vec::new(), None);
+                    }
+                }
                 Some(indexes)
             } else {
-                // Use all the contigs!
-                None
+                // Use all the contigs! (or those that passed the pre-filter)
+                prefiltered_contigs
             };
 
+        // Do not align (or only align a prefix of) a read whose matrices would be too large, where
+        // a read aligned in chunks has the matrices of a chunk
+        let query_len = query.len();
+        let chunk_length = match (self.strategy, self.opts.auto_strategy) {
+            (Some(AlignStrategy::Chunked), Some(auto)) => Some(auto.chunk_length),
+            _ => self.chunk_length_for(query_len),
+        };
+        let matrix_len = chunk_length.unwrap_or(query_len);
+        let query = match self.max_query_len(matrix_len, contigs_to_align.as_ref()) {
+            Some(max_len) if max_len < matrix_len => {
+                if !self.opts.truncate_oversized_reads || max_len == 0 {
+                    self.skip_reason = Some(SkipReason::MatrixSize);
+                    return (Vec::new(), None);
+                }
+                &query[..max_len]
+            }
+            _ => &query[..],
+        };
+        // A truncated query fits in the matrices of a single chunk
+        let chunk_length = chunk_length.filter(|_| query.len() == query_len);
+
+        // Checkpoint the traceback of a read whose matrices would use too much memory
+        let Some(interval) = self.checkpoint_interval_for(
+            chunk_length.unwrap_or(query.len()),
+            contigs_to_align.as_ref(),
+        ) else {
+            self.skip_reason = Some(SkipReason::MatrixSize);
+            return (Vec::new(), None);
+        };
+        self.multi_contig
+            .set_traceback_checkpoint_interval(interval);
+
         // Align to all the contigs! (or those that had a "good enough" pre-align score)
         // This populates the traceback matrices too for suboptimal alignments.
-        let original_alignment = self.multi_contig_align(&query, contigs_to_align.as_ref());
+        self.multi_contig.set_deadline(
+            self.opts
+                .read_timeout_ms
+                .map(|ms| Instant::now() + Duration::from_millis(ms)),
+        );
+        let original_alignment = match chunk_length {
+            Some(chunk_length) => {
+                self.chunked_align(query, chunk_length, target_seqs, contigs_to_align.as_ref())
+            }
+            None => Some(self.multi_contig_align(query, contigs_to_align.as_ref())),
+        };
+        self.multi_contig.set_deadline(None);
+        if self.multi_contig.timed_out() {
+            self.skip_reason = Some(SkipReason::Timeout);
+            return (Vec::new(), None);
+        }
+        let Some(original_alignment) = original_alignment else {
+            return (Vec::new(), None);
+        };
 
         // Get all alignments if we want to keep sub-optimal alignments, or just process this one
         let mut alignments = Vec::new();
-        if self.opts.suboptimal {
+        if chunk_length.is_some() {
+            // There is no traceback of the full query to re-align around the origin or to get
+            // sub-optimal alignments from
+            alignments.push(self.remove_short_segments(query, target_seqs, original_alignment));
+        } else if self.opts.suboptimal {
             // Use the traceback from the original alignment to get sub-optimal alignments
             let new_alignments = self
                 .multi_contig
@@ -315,13 +759,20 @@ impl Aligners<'_, MatchParams> {
                 // remove leading/trailing clipping, needed to for origin re-alignment
                 let alignment = self.remove_clipping(alignment);
                 let alignment =
-                    self.realign_origin(&query, alignment, self.opts.circular_slop, false);
-                alignments.push(alignment);
+                    self.realign_origin(query, alignment, self.opts.circular_slop, false);
+                alignments.push(self.remove_short_segments(query, target_seqs, alignment));
             }
 
             // Filter out sub-optimal alignments
             if alignments.len() > 1 {
-                alignments.sort_by_key(|a| -a.score); // descending
+                // descending by score, then by length
+                let tie_break_length = self.opts.tie_break_length;
+                alignments.sort_by_key(|a| {
+                    (
+                        std::cmp::Reverse(a.score),
+                        std::cmp::Reverse(a.length_of(tie_break_length)),
+                    )
+                });
                 let min_score = alignments[0].score as f32 * self.opts.suboptimal_pct / 100.0;
                 let mut new_alignments = Vec::new();
                 for alignment in alignments {
@@ -334,8 +785,33 @@ impl Aligners<'_, MatchParams> {
         } else {
             // Re-align around the origin if the contig is circular or we force circular
             let alignment =
-                self.realign_origin(&query, original_alignment, self.opts.circular_slop, false);
-            alignments.push(alignment);
+                self.realign_origin(query, original_alignment, self.opts.circular_slop, false);
+            alignments.push(self.remove_short_segments(query, target_seqs, alignment));
+        }
+
+        // The bases after a truncated query are unaligned, and the alignments are flagged if
+        // ambiguous, with the range over which each jump may be placed once refined
+        let scoring = self.opts.contig_scoring();
+        for alignment in &mut alignments {
+            if self.opts.refine_jumps {
+                *alignment = alignment.refine_jumps(
+                    self.opts.refine_jumps_window,
+                    target_seqs,
+                    query,
+                    &scoring,
+                );
+            }
+            alignment.ambiguity = alignment.count_co_optimal(target_seqs, query);
+            alignment.jump_intervals = alignment.find_jump_intervals(target_seqs);
+            alignment.segments = alignment.find_segments(|contig_idx| {
+                let (target_seq, is_forward) = target_seq_for(target_seqs, contig_idx);
+                (target_seq.name.clone(), is_forward)
+            });
+            if self.opts.score_breakdown {
+                alignment.score_breakdown =
+                    alignment.rescore_breakdown(target_seqs, query, &scoring);
+            }
+            alignment.ylen = query_len;
         }
 
         // Get the maximum pre-align score to return
@@ -343,10 +819,145 @@ impl Aligners<'_, MatchParams> {
         (alignments, prealign_score)
     }
 
+    /// Returns the length of the chunks in which to align a query of the given length, if the
+    /// query is aligned in chunks (see [`Builder::chunk_length`]).
+    fn chunk_length_for(&self, query_len: usize) -> Option<usize> {
+        let chunk_length = self.opts.chunk_length?;
+        (self.is_local() && query_len > chunk_length).then_some(chunk_length)
+    }
+
+    /// Returns true if the alignment mode is local, target-local, or semi-global, so that the
+    /// query may be aligned to a sub-sequence of the contigs.
+    fn is_local(&self) -> bool {
+        matches!(
+            self.opts.mode,
+            AlignmentMode::Local | AlignmentMode::TargetLocal | AlignmentMode::SemiGlobal
+        )
+    }
+
+    /// Aligns the query in overlapping chunks of the given length, then merges the alignments of
+    /// the chunks (see [`Alignment::merge_chunks`]).  Returns `None` if no chunk aligned, or if
+    /// the alignment timed out (see [`MultiContigAligner::timed_out`]).
+    fn chunked_align(
+        &mut self,
+        query: &[u8],
+        chunk_length: usize,
+        target_seqs: &[TargetSeq],
+        contig_indexes: Option<&BitSet<u32>>,
+    ) -> Option<Alignment> {
+        let step = chunk_length.saturating_sub(self.opts.chunk_overlap).max(1);
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        loop {
+            let end = (start + chunk_length).min(query.len());
+            let alignment = self.multi_contig_align(&query[start..end], contig_indexes);
+            if self.multi_contig.timed_out() {
+                return None;
+            }
+            chunks.push((start, alignment));
+            if end == query.len() {
+                break;
+            }
+            start += step;
+        }
+        Alignment::merge_chunks(&chunks, target_seqs, query, &self.opts.contig_scoring())
+    }
+
+    /// Returns the length of the longest query whose matrices have at most
+    /// `max_matrix_cells` cells across the given contigs, if a maximum is set.
+    fn max_query_len(
+        &self,
+        query_len: usize,
+        contig_indexes: Option<&BitSet<u32>>,
+    ) -> Option<usize> {
+        let max_cells = self.opts.max_matrix_cells?;
+        if self.multi_contig.num_cells(query_len, contig_indexes) <= max_cells {
+            return Some(query_len);
+        }
+        // the cells of a query of length n are the cells of a query of length zero times n + 1
+        let cells_per_column = self.multi_contig.num_cells(0, contig_indexes);
+        Some((max_cells / cells_per_column).saturating_sub(1))
+    }
+
+    /// Returns the traceback checkpoint interval for aligning a query of the given length to the
+    /// given contigs: the configured interval if the matrices fit in the maximum memory, otherwise
+    /// the interval using the least memory if the matrices then fit, or `None` if they do not.
+    fn checkpoint_interval_for(
+        &self,
+        query_len: usize,
+        contig_indexes: Option<&BitSet<u32>>,
+    ) -> Option<Option<usize>> {
+        let interval = self.opts.traceback_checkpoint_interval;
+        let Some(max_memory) = self.opts.max_read_memory else {
+            return Some(interval);
+        };
+        let fits = |interval| {
+            self.multi_contig
+                .estimate_memory_with(query_len, contig_indexes, interval)
+                <= max_memory
+        };
+        if fits(interval) {
+            return Some(interval);
+        }
+        let interval =
+            Some(SingleContigAligner::<MatchParams>::min_memory_checkpoint_interval(query_len));
+        fits(interval).then_some(interval)
+    }
+
+    /// Returns the indexes of the contigs for which the upper bound on the local alignment score
+    /// (without jumps) of the query is at least the minimum pre-filter score.
+    fn prefilter_contigs(&self, query: &[u8], target_seqs: &[TargetSeq]) -> BitSet<u32> {
+        let filter = EditDistanceFilter::new(query);
+        let scoring = self.opts.contig_scoring();
+        let min_score = self.opts.pre_filter_min_score;
+        let mut indexes = BitSet::default();
+        for target_seq in target_seqs {
+            if filter.passes(&target_seq.fwd, self.opts.circular, &scoring, min_score) {
+                indexes.insert(
+                    self.multi_contig
+                        .contig_index_for_strand(true, &target_seq.name)
+                        .unwrap(),
+                );
+            }
+            if self.opts.double_strand
+                && filter.passes(&target_seq.revcomp, self.opts.circular, &scoring, min_score)
+            {
+                indexes.insert(
+                    self.multi_contig
+                        .contig_index_for_strand(false, &target_seq.name)
+                        .unwrap(),
+                );
+            }
+        }
+        indexes
+    }
+
+    /// Removes segments after a jump that are shorter than the minimum segment length, if any
+    /// (see [`Alignment::remove_short_segments`]).
+    fn remove_short_segments(
+        &self,
+        query: &[u8],
+        target_seqs: &[TargetSeq],
+        alignment: Alignment,
+    ) -> Alignment {
+        match self.opts.min_segment_length {
+            Some(min_segment_length) => alignment.remove_short_segments(
+                min_segment_length,
+                target_seqs,
+                query,
+                &self.opts.contig_scoring(),
+            ),
+            None => alignment,
+        }
+    }
+
     /// Removes leading and trailing clipping
     fn remove_clipping(&self, mut aln: Alignment) -> Alignment {
         match self.opts.mode {
-            AlignmentMode::Local | AlignmentMode::QueryLocal | AlignmentMode::TargetLocal => {
+            AlignmentMode::Local
+            | AlignmentMode::QueryLocal
+            | AlignmentMode::TargetLocal
+            | AlignmentMode::SemiGlobal => {
                 aln.operations
                     .retain(|x| matches!(*x, Match | Subst | Ins | Del | Xjump(_, _)));
             }
@@ -671,10 +1282,15 @@ impl<'a, F: MatchFunc> SamRecordFormatter<'a, F> {
         let mut is_first = true;
         let mut primary_score = MIN_SCORE;
         for alignment in alignments {
-            let hard_clip = !self.opts.soft_clip;
             let mut builder: SubAlignmentBuilder = SubAlignmentBuilder::new(self.opts.use_eq_and_x);
             let mut subs = builder.build(alignment, true, &self.scoring);
             ensure!(!subs.is_empty());
+            let segments = match self.opts.cs_tag {
+                Some(_) => alignment.aligned_segments(self.target_seqs, bases),
+                None => Vec::new(),
+            };
+            let score_breakdown = (!alignment.score_breakdown.is_empty())
+                .then(|| alignment.score_breakdown.iter().join(","));
 
             let mut primary_index = if is_first {
                 let idx = match self.opts.pick_primary {
@@ -737,30 +1353,29 @@ impl<'a, F: MatchFunc> SamRecordFormatter<'a, F> {
                 }
                 *record.flags_mut() = new_flags;
 
-                let (bases_vec, quals_vec, cigar) = match (is_forward, hard_clip && is_secondary) {
-                    (true, false) => (bases.to_vec(), quals.to_vec(), sub.cigar.clone()),
-                    (true, true) => (
-                        bases[sub.query_start..sub.query_end].to_vec(),
-                        quals[sub.query_start..sub.query_end].to_vec(),
-                        Cigar::try_from(sub.cigar.iter().rev().copied().collect::<Vec<Op>>())
-                            .unwrap(),
-                    ),
-                    (false, false) => (
-                        reverse_complement(bases),
-                        quals.iter().copied().rev().collect(),
-                        Cigar::try_from(sub.cigar.iter().rev().copied().collect::<Vec<Op>>())
-                            .unwrap(),
-                    ),
-                    (false, true) => (
-                        reverse_complement(bases[sub.query_start..sub.query_end].to_vec()),
-                        quals[sub.query_start..sub.query_end]
-                            .iter()
-                            .copied()
-                            .rev()
-                            .collect(),
+                // The primary record holds the whole query, so is always soft-clipped
+                let clip_policy = if is_secondary {
+                    self.opts.clip_policy
+                } else {
+                    ClipPolicy::Soft
+                };
+                let query_range = match clip_policy {
+                    ClipPolicy::Soft => 0..bases.len(),
+                    ClipPolicy::Hard | ClipPolicy::Trimmed => sub.query_start..sub.query_end,
+                };
+                let (bases_vec, quals_vec, cigar) = if is_forward {
+                    (
+                        bases[query_range.clone()].to_vec(),
+                        quals[query_range].to_vec(),
+                        sub.cigar.clone(),
+                    )
+                } else {
+                    (
+                        reverse_complement(&bases[query_range.clone()]),
+                        quals[query_range].iter().copied().rev().collect(),
                         Cigar::try_from(sub.cigar.iter().rev().copied().collect::<Vec<Op>>())
                             .unwrap(),
-                    ),
+                    )
                 };
 
                 // bases
@@ -770,10 +1385,10 @@ impl<'a, F: MatchFunc> SamRecordFormatter<'a, F> {
                 *record.quality_scores_mut() = QualityScores::try_from(quals_vec).unwrap();
 
                 // cigar
-                let clip_op = if hard_clip && is_secondary {
-                    Kind::HardClip
-                } else {
-                    Kind::SoftClip
+                let clip_op = match clip_policy {
+                    ClipPolicy::Soft => Some(Kind::SoftClip),
+                    ClipPolicy::Hard => Some(Kind::HardClip),
+                    ClipPolicy::Trimmed => None,
                 };
                 let mut cigar_ops = Vec::new();
                 // Clip the start of the alignment
@@ -782,7 +1397,7 @@ impl<'a, F: MatchFunc> SamRecordFormatter<'a, F> {
                 } else {
                     bases.len() - sub.query_end
                 };
-                if clip_prefix_len > 0 {
+                if let Some(clip_op) = clip_op.filter(|_| clip_prefix_len > 0) {
                     cigar_ops.push(Op::new(clip_op, clip_prefix_len));
                 }
                 // Add the CIGAR from the alignment
@@ -793,7 +1408,7 @@ impl<'a, F: MatchFunc> SamRecordFormatter<'a, F> {
                 } else {
                     sub.query_start
                 };
-                if clip_suffix_len > 0 {
+                if let Some(clip_op) = clip_op.filter(|_| clip_suffix_len > 0) {
                     cigar_ops.push(Op::new(clip_op, clip_suffix_len));
                 }
                 *record.cigar_mut() = Cigar::try_from(cigar_ops).unwrap();
@@ -862,6 +1477,25 @@ impl<'a, F: MatchFunc> SamRecordFormatter<'a, F> {
                     TOTAL_HIT_COUNT,
                     noodles::sam::record::data::field::Value::from(subs.len() as i32),
                 );
+                if let Some(style) = self.opts.cs_tag {
+                    let segment = segments.iter().find(|segment| {
+                        segment.contig_idx == sub.contig_idx
+                            && segment.y_start == sub.query_start
+                            && segment.y_end == sub.query_end
+                    });
+                    if let Some(segment) = segment {
+                        data.insert(
+                            "cs".parse().unwrap(),
+                            noodles::sam::record::data::field::Value::String(segment.cs(style)),
+                        );
+                    }
+                }
+                if let Some(score_breakdown) = &score_breakdown {
+                    data.insert(
+                        SCORE_BREAKDOWN_TAG.parse().unwrap(),
+                        noodles::sam::record::data::field::Value::String(score_breakdown.clone()),
+                    );
+                }
                 *record.data_mut() = data;
 
                 records.push(record);
@@ -876,28 +1510,461 @@ impl<'a, F: MatchFunc> SamRecordFormatter<'a, F> {
 
 #[cfg(test)]
 pub mod tests {
-    use super::Builder;
-    use crate::util::target_seq::{self, TargetHash};
+    use super::{AlignmentMode, Builder, SingleContigAligner};
+    use crate::align::{
+        strategy::{AlignStrategy, AutoStrategy},
+        ClipPolicy, CsStyle, MatrixMatchFunc, SkipReason, SCORE_BREAKDOWN_TAG,
+    };
+    use crate::test_util::{random_dna, Rng, SEED};
+    use crate::util::alphabet::{Protein, AMINO_ACIDS};
+    use crate::util::target_seq::{TargetHash, TargetSeq};
+    use bio::alignment::pairwise::MatchParams;
+    use noodles::sam::record::{
+        cigar::op::Kind,
+        data::field::{Tag, Value},
+        Flags,
+    };
     use seq_io::fastq::OwnedRecord as FastqOwnedRecord;
 
+    /// Returns `n` targets named `chr1`, `chr2`, etc., each of `len` bases of random DNA.
+    fn random_targets(n: usize, len: usize) -> Vec<TargetSeq> {
+        let mut rng = Rng::new(SEED);
+        (1..=n)
+            .map(|i| TargetSeq::new(&format!("chr{i}"), &random_dna(&mut rng, len), false))
+            .collect()
+    }
+
+    /// Returns the hashes of the k-mers of the targets.
+    fn hashes(target_seqs: &[TargetSeq], k: usize) -> Vec<TargetHash<'_>> {
+        target_seqs
+            .iter()
+            .map(|target_seq| target_seq.build_target_hash(k))
+            .collect()
+    }
+
+    /// Returns the FASTQ record of the query.
+    fn fastq_record(query: &[u8]) -> FastqOwnedRecord {
+        FastqOwnedRecord {
+            head: b"test-record".to_vec(),
+            seq: query.to_vec(),
+            qual: vec![b'#'; query.len()],
+        }
+    }
+
     #[test]
     fn test_case_insensitive() {
         let seq = b"ACGGACAGATCGAATACGACAGGAC".to_vec();
-        let target_seqs = [target_seq::TargetSeq::new("test-contig", &seq, false)];
+        let target_seqs = [TargetSeq::new("test-contig", &seq, false)];
         let mut aligners = Builder::default().build_aligners(&target_seqs);
-        let record = FastqOwnedRecord {
-            head: b"test-record".to_vec(),
-            seq: seq.clone(),
-            qual: vec![b'#'; seq.len()],
-        };
+        let record = fastq_record(&seq);
         let k = 7;
-        let target_hashes: Vec<TargetHash> = target_seqs
-            .iter()
-            .map(|target_seq| target_seq.build_target_hash(k))
-            .collect();
+        let target_hashes = hashes(&target_seqs, k);
         let (alignment, _) = aligners.align(&record, &target_seqs, &target_hashes);
         assert_eq!(alignment.len(), 1);
         assert_eq!(alignment[0].length, seq.len());
         assert_eq!(alignment[0].cigar(), format!("{}=", seq.len()));
     }
+
+    #[test]
+    fn test_pre_filter() {
+        let seq = b"ACGGACAGATCGAATACGACAGGAC".to_vec();
+        let other = b"TTTTTTTTTTTTTTTTTTTTTTTTT".to_vec();
+        let target_seqs = [
+            TargetSeq::new("test-contig", &seq, false),
+            TargetSeq::new("other-contig", &other, false),
+        ];
+        let target_hashes = hashes(&target_seqs, 7);
+        let record = fastq_record(&seq);
+
+        // the query aligns perfectly to the first contig
+        let mut aligners = Builder::default()
+            .pre_filter(true)
+            .pre_filter_min_score(seq.len() as i32)
+            .build_aligners(&target_seqs);
+        let (alignment, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        assert_eq!(alignment.len(), 1);
+        assert_eq!(alignment[0].start_contig_idx, 0);
+        assert_eq!(alignment[0].cigar(), format!("{}=", seq.len()));
+
+        // no contig can reach the minimum score
+        let mut aligners = Builder::default()
+            .pre_filter(true)
+            .pre_filter_min_score(seq.len() as i32 + 1)
+            .build_aligners(&target_seqs);
+        let (alignment, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        assert!(alignment.is_empty());
+    }
+
+    #[test]
+    fn test_cs_tag() {
+        let seq = b"ACGGACAGATCGAATACGACAGGAC".to_vec();
+        let target_seqs = [TargetSeq::new("test-contig", &seq, false)];
+        let target_hashes = hashes(&target_seqs, 7);
+        // a mismatch in the middle of the query
+        let mut query = seq.clone();
+        query[12] = b'T';
+        let record = fastq_record(&query);
+
+        let cs_tag: Tag = "cs".parse().unwrap();
+        for (cs_style, expected) in [
+            (None, None),
+            (Some(CsStyle::Short), Some(":12*at:12")),
+            (Some(CsStyle::Long), Some("=ACGGACAGATCG*at=ATACGACAGGAC")),
+        ] {
+            let mut builder = Builder::default();
+            builder.mismatch_score(-1).cs_tag(cs_style);
+            let mut aligners = builder.build_aligners(&target_seqs);
+            let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+            let records = builder
+                .build_sam_record_formatter(&target_seqs)
+                .format(&record, &alignments, None)
+                .unwrap();
+            assert_eq!(records.len(), 1);
+            assert_eq!(
+                records[0].data().get(&cs_tag),
+                expected.map(|cs| Value::String(cs.to_string())).as_ref()
+            );
+        }
+    }
+
+    #[test]
+    fn test_score_breakdown() {
+        let seq = b"ACGGACAGATCGAATACGACAGGAC".to_vec();
+        let target_seqs = [TargetSeq::new("test-contig", &seq, false)];
+        let target_hashes = hashes(&target_seqs, 7);
+        // a mismatch in the middle of the query
+        let mut query = seq.clone();
+        query[12] = b'T';
+        let record = fastq_record(&query);
+
+        let tag: Tag = SCORE_BREAKDOWN_TAG.parse().unwrap();
+        for (score_breakdown, expected) in [(false, None), (true, Some("25M:20"))] {
+            let mut builder = Builder::default();
+            builder.mismatch_score(-4).score_breakdown(score_breakdown);
+            let mut aligners = builder.build_aligners(&target_seqs);
+            let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+            assert_eq!(alignments[0].score_breakdown.is_empty(), !score_breakdown);
+            let records = builder
+                .build_sam_record_formatter(&target_seqs)
+                .format(&record, &alignments, None)
+                .unwrap();
+            assert_eq!(
+                records[0].data().get(&tag),
+                expected
+                    .map(|breakdown| Value::String(breakdown.to_string()))
+                    .as_ref()
+            );
+        }
+    }
+
+    #[test]
+    fn test_clip_policy() {
+        let target_seqs = random_targets(2, 40);
+        let target_hashes = hashes(&target_seqs, 7);
+        // the first 35 bases of chr1 then the last 25 bases of chr2
+        let query = [&target_seqs[0].fwd[..35], &target_seqs[1].fwd[15..]].concat();
+        let record = fastq_record(&query);
+
+        for clip_policy in [ClipPolicy::Soft, ClipPolicy::Hard, ClipPolicy::Trimmed] {
+            let mut builder = Builder::default();
+            builder.clip_policy(clip_policy);
+            let mut aligners = builder.build_aligners(&target_seqs);
+            let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+            let records = builder
+                .build_sam_record_formatter(&target_seqs)
+                .format(&record, &alignments, None)
+                .unwrap();
+            assert_eq!(records.len(), 2);
+            let (primary, secondary) = (&records[0], &records[1]);
+            assert!(!primary.flags().contains(Flags::SECONDARY));
+            assert!(secondary.flags().contains(Flags::SECONDARY));
+
+            // the primary record is always soft-clipped
+            assert_eq!(primary.sequence().len(), query.len());
+            assert!(primary.cigar().iter().all(|op| op.kind() != Kind::HardClip));
+
+            let clips: Vec<Kind> = secondary
+                .cigar()
+                .iter()
+                .map(|op| op.kind())
+                .filter(|kind| matches!(kind, Kind::SoftClip | Kind::HardClip))
+                .collect();
+            let aligned: usize = secondary
+                .cigar()
+                .iter()
+                .filter(|op| matches!(op.kind(), Kind::Match | Kind::Insertion))
+                .map(|op| op.len())
+                .sum();
+            let (expected_clips, expected_len) = match clip_policy {
+                ClipPolicy::Soft => (vec![Kind::SoftClip], query.len()),
+                ClipPolicy::Hard => (vec![Kind::HardClip], aligned),
+                ClipPolicy::Trimmed => (Vec::new(), aligned),
+            };
+            assert_eq!(clips, expected_clips, "{clip_policy}");
+            assert_eq!(secondary.sequence().len(), expected_len, "{clip_policy}");
+            assert_eq!(
+                secondary.quality_scores().len(),
+                expected_len,
+                "{clip_policy}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Builder::default().validate().is_ok());
+        let mut builder = Builder::default();
+        builder.match_score(-1);
+        assert!(builder.validate().is_err());
+        let mut builder = Builder::default();
+        builder.gap_open(2);
+        assert!(builder.validate().is_err());
+        // a jump cheaper than a match
+        let mut builder = Builder::default();
+        builder.match_score(2).jump_score_inter_contig(Some(-1));
+        let message = builder.validate().unwrap_err().to_string();
+        assert!(message.starts_with("The jump_score_inter_contig score must be at most minus"));
+        // the custom mode, whose clip scores the aligners cannot take from the mode
+        let mut builder = Builder::default();
+        builder.mode(AlignmentMode::Custom);
+        let message = builder.validate().unwrap_err().to_string();
+        assert!(message.starts_with("The custom alignment mode is not supported"));
+    }
+
+    #[test]
+    fn test_matrix_size_guard() {
+        let seq = b"ACGGACAGATCGAATACGACAGGAC".to_vec();
+        let target_seqs = [TargetSeq::new("test-contig", &seq, false)];
+        let target_hashes = hashes(&target_seqs, 7);
+        let record = fastq_record(&seq);
+        // the matrices of the query have 26 x 26 cells
+        let cells = (seq.len() + 1) * (seq.len() + 1);
+
+        // the matrices fit
+        let mut aligners = Builder::default()
+            .max_matrix_cells(Some(cells))
+            .build_aligners(&target_seqs);
+        let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        assert_eq!(alignments.len(), 1);
+        assert_eq!(aligners.skip_reason(), None);
+
+        // the matrices do not fit, so the query is skipped
+        let mut builder = Builder::default();
+        builder.max_matrix_cells(Some(cells - 1));
+        let mut aligners = builder.build_aligners(&target_seqs);
+        let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        assert!(alignments.is_empty());
+        assert_eq!(aligners.skip_reason(), Some(SkipReason::MatrixSize));
+
+        // only the first 20 bases are aligned, with the rest clipped
+        builder
+            .max_matrix_cells(Some(21 * (seq.len() + 1)))
+            .truncate_oversized_reads(true);
+        let mut aligners = builder.build_aligners(&target_seqs);
+        let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        assert_eq!(aligners.skip_reason(), None);
+        assert_eq!(alignments.len(), 1);
+        assert_eq!(alignments[0].cigar(), "20=");
+        assert_eq!((alignments[0].yend, alignments[0].ylen), (20, seq.len()));
+        let records = builder
+            .build_sam_record_formatter(&target_seqs)
+            .format(&record, &alignments, None)
+            .unwrap();
+        assert_eq!(records[0].cigar().to_string(), "20M5S");
+    }
+
+    #[test]
+    fn test_chunked_alignment() {
+        let target_seqs = random_targets(1, 300);
+        let target_hashes = hashes(&target_seqs, 7);
+        let seq = &target_seqs[0].fwd;
+        // a deletion of 40bp, and a mismatch
+        let mut query = [&seq[10..120], &seq[160..290]].concat();
+        query[200] = if query[200] == b'A' { b'C' } else { b'A' };
+        let record = fastq_record(&query);
+
+        let mut builder = Builder::default();
+        builder.default_jump_score(-5);
+        let (whole, _) =
+            builder
+                .build_aligners(&target_seqs)
+                .align(&record, &target_seqs, &target_hashes);
+        assert_eq!(whole[0].cigar(), "110=40J90=1X39=");
+
+        // the chunks are joined in their overlaps, with the same alignment as the whole query
+        for (chunk_length, chunk_overlap) in [(60, 20), (100, 10), (240, 239)] {
+            builder
+                .chunk_length(Some(chunk_length))
+                .chunk_overlap(chunk_overlap);
+            let (chunked, _) =
+                builder
+                    .build_aligners(&target_seqs)
+                    .align(&record, &target_seqs, &target_hashes);
+            assert_eq!(
+                chunked, whole,
+                "chunks of {chunk_length} overlapping by {chunk_overlap}"
+            );
+        }
+
+        // reads no longer than the chunk length are aligned whole
+        builder.chunk_length(Some(query.len()));
+        let (alignments, _) =
+            builder
+                .build_aligners(&target_seqs)
+                .align(&record, &target_seqs, &target_hashes);
+        assert_eq!(alignments, whole);
+    }
+
+    #[test]
+    fn test_max_read_memory() {
+        let target_seqs = random_targets(1, 300);
+        let target_hashes = hashes(&target_seqs, 7);
+        let seq = &target_seqs[0].fwd;
+        let query = [&seq[10..120], &seq[160..290]].concat();
+        let record = fastq_record(&query);
+
+        let mut builder = Builder::default();
+        builder.default_jump_score(-5);
+        let mut aligners = builder.build_aligners(&target_seqs);
+        let memory = aligners.multi_contig.estimate_memory(query.len());
+        let interval =
+            SingleContigAligner::<MatchParams>::min_memory_checkpoint_interval(query.len());
+        let min_memory =
+            aligners
+                .multi_contig
+                .estimate_memory_with(query.len(), None, Some(interval));
+        assert!(min_memory * 4 < memory, "{min_memory} vs {memory}");
+        let (whole, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        assert_eq!(whole[0].cigar(), "110=40J130=");
+
+        // the traceback is checkpointed, with the same alignment
+        builder.max_read_memory(Some(min_memory));
+        let mut aligners = builder.build_aligners(&target_seqs);
+        let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        assert_eq!(aligners.skip_reason(), None);
+        assert_eq!(alignments, whole);
+
+        // the matrices do not fit even when checkpointing, so the query is skipped
+        builder.max_read_memory(Some(min_memory - 1));
+        let mut aligners = builder.build_aligners(&target_seqs);
+        let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        assert!(alignments.is_empty());
+        assert_eq!(aligners.skip_reason(), Some(SkipReason::MatrixSize));
+    }
+
+    #[test]
+    fn test_semi_global() {
+        let target_seqs = random_targets(2, 300);
+        let target_hashes = hashes(&target_seqs, 12);
+        let query = [
+            b"NNNNN",
+            &target_seqs[0].fwd[10..120],
+            &target_seqs[1].fwd[160..290],
+            b"NNNNN",
+        ]
+        .concat();
+        let record = fastq_record(&query);
+
+        // the flanks of the query are clipped in the local mode, but not the semi-global mode
+        let mut builder = Builder::default();
+        builder.default_jump_score(-5);
+        for (mode, ystart, yend) in [
+            (AlignmentMode::Local, 5, query.len() - 5),
+            (AlignmentMode::SemiGlobal, 0, query.len()),
+        ] {
+            builder.mode(mode);
+            let mut aligners = builder.build_aligners(&target_seqs);
+            let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+            assert_eq!(alignments.len(), 1);
+            let alignment = &alignments[0];
+            assert_eq!((alignment.ystart, alignment.yend), (ystart, yend), "{mode}");
+        }
+    }
+
+    #[test]
+    fn test_auto_strategy() {
+        let target_seqs = random_targets(3, 300);
+        let target_hashes = hashes(&target_seqs, 12);
+        let query = [&target_seqs[0].fwd[10..120], &target_seqs[1].fwd[160..290]].concat();
+        let record = fastq_record(&query);
+
+        let mut builder = Builder::default();
+        builder.default_jump_score(-5);
+        let mut aligners = builder.build_aligners(&target_seqs);
+        let (full, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        assert_eq!(aligners.strategy(), None);
+        // the query jumps from chr1 to chr2 at the junction of its two parts, or before it by the
+        // number of bases ending the chr1 part that also precede the chr2 part in chr2
+        let (chr1, chr2) = (&target_seqs[0].fwd, &target_seqs[1].fwd);
+        let shared = (1..=110)
+            .take_while(|i| chr1[120 - i] == chr2[160 - i])
+            .count();
+        assert_eq!(
+            full[0].cigar(),
+            format!("{}=1C40J{}=", 110 - shared, 130 + shared)
+        );
+
+        // small matrices are filled in full
+        builder.auto_strategy(Some(AutoStrategy::default()));
+        let mut aligners = builder.build_aligners(&target_seqs);
+        let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        assert_eq!(aligners.strategy(), Some(AlignStrategy::Full));
+        assert_eq!(alignments, full);
+
+        // larger matrices of a query similar to the targets are seeded and extended
+        let auto = AutoStrategy {
+            max_full_cells: 0,
+            ..AutoStrategy::default()
+        };
+        builder.auto_strategy(Some(auto));
+        let mut aligners = builder.build_aligners(&target_seqs);
+        let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        assert_eq!(aligners.strategy(), Some(AlignStrategy::SeedAndExtend));
+        assert_eq!(alignments, full);
+
+        // or banded, with a single target
+        let mut aligners = builder.build_aligners(&target_seqs[1..2]);
+        let record = fastq_record(&target_seqs[1].fwd[20..280]);
+        let (alignments, _) = aligners.align(&record, &target_seqs[1..2], &target_hashes[1..2]);
+        assert_eq!(aligners.strategy(), Some(AlignStrategy::Banded));
+        assert_eq!(alignments[0].cigar(), "260=");
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        let seq = b"ACGGACAGATCGAATACGACAGGAC".to_vec();
+        let target_seqs = [TargetSeq::new("test-contig", &seq, false)];
+        let target_hashes = hashes(&target_seqs, 7);
+        let record = fastq_record(&seq);
+        let mut aligners = Builder::default()
+            .read_timeout_ms(Some(0))
+            .build_aligners(&target_seqs);
+        let (alignments, _) = aligners.align(&record, &target_seqs, &target_hashes);
+        assert!(alignments.is_empty());
+        assert_eq!(aligners.skip_reason(), Some(SkipReason::Timeout));
+    }
+
+    #[test]
+    fn test_protein_aligner() {
+        let mut rng = Rng::new(SEED);
+        let seqs: Vec<Vec<u8>> = (0..2)
+            .map(|_| (0..100).map(|_| AMINO_ACIDS[rng.below(20)]).collect())
+            .collect();
+        let target_seqs = [
+            TargetSeq::with_alphabet::<Protein>("a", &seqs[0], false),
+            TargetSeq::with_alphabet::<Protein>("b", &seqs[1], false),
+        ];
+        assert!(target_seqs[0].revcomp.is_empty());
+        // proteins have no reverse strand, whatever the double strand option
+        let mut aligner = Builder::default()
+            .double_strand(true)
+            .build_protein_aligner(&target_seqs, MatrixMatchFunc::blosum62());
+        assert_eq!(aligner.len(), 2);
+
+        // the query jumps from the first half of one protein to the second half of the other
+        let query = [&seqs[0][..50], &seqs[1][50..]].concat();
+        let alignment = aligner.custom(&query);
+        assert_eq!(alignment.start_contig_idx, 0);
+        assert_eq!(alignment.end_contig_idx, 1);
+    }
 }
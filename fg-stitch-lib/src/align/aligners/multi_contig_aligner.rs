@@ -1,36 +1,84 @@
 use crate::{
     align::{
         aligners::{
-            constants::DEFAULT_ALIGNER_CAPACITY, single_contig_aligner::SingleContigAligner,
+            constants::{AlignmentOperation, DEFAULT_ALIGNER_CAPACITY, MIN_SCORE},
+            pool::MatrixPool,
+            shared_contigs::SharedContigs,
+            single_contig_aligner::SingleContigAligner,
         },
         alignment::Alignment,
-        scoring::Scoring,
-        traceback::{traceback, traceback_all, traceback_from},
+        jump_sites::JumpSites,
+        metrics::{lap, stage_span, Stage, StageTimings},
+        position::ContigIdx,
+        profile::ScoreProfile,
+        scoring::{GlobalScoring, Scoring},
+        splice::SpliceAnnotation,
+        traceback::{
+            alignment_ends, best_alignment_end, traceback, traceback_all_from, traceback_from,
+            EndTieBreak, TracebackCursor, TracebackLayout, TracebackStep,
+        },
     },
-    util::index_map::IndexMap,
+    util::{dna::PackedSeq, index_map::IndexMap},
 };
 use bio::{alignment::pairwise::MatchFunc, utils::TextSlice};
 use bit_set::BitSet;
 use itertools::Itertools;
+use std::{ops::Range, sync::Arc, time::Instant};
 
 use super::JumpInfo;
+#[cfg(feature = "serde")]
+use crate::align::traceback::dump::{ContigTraceback, TracebackDump};
+#[cfg(feature = "viz")]
+use crate::align::viz::ScoreSurface;
+
+/// The sequence of a contig, either stored packed, derived on access from the packed sequence
+/// of the forward strand of the contig, or a score profile whose consensus is the sequence.
+enum ContigSeq {
+    Packed(Arc<PackedSeq>),
+    ReverseComplementOf(Arc<PackedSeq>),
+    Profile(Arc<ScoreProfile>),
+}
+
+impl ContigSeq {
+    fn len(&self) -> usize {
+        match self {
+            Self::Packed(seq) | Self::ReverseComplementOf(seq) => seq.len(),
+            Self::Profile(profile) => profile.len(),
+        }
+    }
+
+    /// The (unpacked) bases of the sequence, the consensus for a profile.
+    fn to_vec(&self) -> Vec<u8> {
+        match self {
+            Self::Packed(seq) => seq.to_vec(),
+            Self::ReverseComplementOf(seq) => seq.reverse_complement().to_vec(),
+            Self::Profile(profile) => profile.consensus().to_vec(),
+        }
+    }
+}
 
-struct ContigAligner<'a, F: MatchFunc> {
+struct ContigAligner<F: MatchFunc> {
     pub name: String,
     pub is_forward: bool,
     pub aligner: SingleContigAligner<F>,
-    pub seq: &'a [u8],
+    pub seq: ContigSeq,
+    /// The offset of the aligned region in the full contig.
+    pub offset: usize,
+    /// The length of the full contig.
+    pub contig_len: usize,
 }
 
-impl<'a, F: MatchFunc> ContigAligner<'a, F> {
+impl<F: MatchFunc + Sync> ContigAligner<F> {
     pub fn new(
         name: String,
         is_forward: bool,
         scoring: Scoring<F>,
-        seq: TextSlice<'a>,
+        seq: ContigSeq,
+        region: Range<usize>,
+        contig_len: usize,
         contig_idx: usize,
         circular: bool,
-    ) -> ContigAligner<'a, F> {
+    ) -> ContigAligner<F> {
         let mut aligner = SingleContigAligner::with_capacity_and_scoring(
             DEFAULT_ALIGNER_CAPACITY,
             DEFAULT_ALIGNER_CAPACITY,
@@ -38,37 +86,542 @@ impl<'a, F: MatchFunc> ContigAligner<'a, F> {
         );
         aligner.set_contig_idx(contig_idx);
         aligner.set_circular(circular);
+        if let ContigSeq::Profile(profile) = &seq {
+            aligner.set_profile(Some(profile.clone()));
+        }
         Self {
             name,
             is_forward,
             aligner,
             seq,
+            offset: region.start,
+            contig_len,
         }
     }
 
+    /// The length of the aligned region of the contig.
     pub fn len(&self) -> usize {
         self.seq.len()
     }
+
+    /// Sets the score of jumps from or to each position of the aligned region of the contig, and
+    /// whether such jumps are free, from the given jump sites (see [`JumpSites::site_scores`] and
+    /// [`JumpSites::free_sites`]), or so that jumps may be from or to any position, and none is
+    /// free, if `None`.
+    pub fn set_jump_sites(&mut self, jump_sites: Option<&JumpSites>) {
+        let region = self.offset..self.offset + self.len();
+        let (scores, free) = jump_sites.map_or_else(Default::default, |sites| {
+            (
+                sites.site_scores(&self.name, self.is_forward, self.contig_len, region.clone()),
+                sites.free_sites(&self.name, self.is_forward, self.contig_len, region),
+            )
+        });
+        self.aligner.set_jump_site_scores(scores);
+        self.aligner.set_free_jump_sites(free);
+    }
+
+    /// Sets the annotated splice sites of each position of the aligned region of the contig from
+    /// the given annotation (see [`SpliceAnnotation::splice_sites`]), or so that no position is an
+    /// annotated splice site if `None`.
+    pub fn set_splice_annotation(&mut self, annotation: Option<&SpliceAnnotation>) {
+        let region = self.offset..self.offset + self.len();
+        let sites = annotation.map_or_else(Vec::new, |annotation| {
+            annotation.splice_sites(&self.name, self.is_forward, self.contig_len, region)
+        });
+        self.aligner.set_annotated_splice_sites(sites);
+    }
+}
+
+/// Converts the alignment from the coordinates of the aligned region of each contig to the
+/// coordinates of the full contig, given the offset of the region in the contig, the length of the
+/// region, and the length of the contig, by contig index.
+pub(crate) fn region_to_contig_coordinates(
+    mut alignment: Alignment,
+    region_of: impl Fn(usize) -> (usize, usize, usize),
+) -> Alignment {
+    let (prefix_len, _, _) = region_of(alignment.start_contig_idx);
+    let (end_offset, end_len, end_contig_len) = region_of(alignment.end_contig_idx);
+    let suffix_len = end_contig_len - end_offset - end_len;
+
+    // NB: an alignment with no aligned bases has all coordinates set to zero
+    if alignment.xend > 0 || alignment.yend > 0 {
+        alignment.xstart += prefix_len;
+        alignment.xend += end_offset;
+    }
+    alignment.xlen = end_contig_len;
+
+    // Update the jump positions, and the leading and trailing clipping of x
+    let is_clip = |op: &AlignmentOperation| {
+        matches!(
+            op,
+            AlignmentOperation::Xclip(_) | AlignmentOperation::Yclip(_)
+        )
+    };
+    let first_aligned = alignment.operations.iter().position(|op| !is_clip(op));
+    let last_aligned = alignment.operations.iter().rposition(|op| !is_clip(op));
+    for (index, op) in alignment.operations.iter_mut().enumerate() {
+        match op {
+            AlignmentOperation::Xjump(contig_idx, x) => *x += region_of(*contig_idx).0,
+            AlignmentOperation::Xclip(len) if first_aligned.is_none_or(|i| index < i) => {
+                *len += prefix_len;
+            }
+            AlignmentOperation::Xclip(len) if last_aligned.is_none_or(|i| index > i) => {
+                *len += suffix_len;
+            }
+            _ => (),
+        }
+    }
+
+    // The alignment may start (or end) at the start (or end) of the region, in which case x
+    // is not clipped in the region, but is clipped in the full contig.
+    if let (Some(first), Some(last)) = (first_aligned, last_aligned) {
+        let is_xclip = |op: &AlignmentOperation| matches!(op, AlignmentOperation::Xclip(_));
+        let ops = &mut alignment.operations;
+        if suffix_len > 0 && !ops[last + 1..].iter().any(is_xclip) {
+            ops.push(AlignmentOperation::Xclip(suffix_len));
+        }
+        if prefix_len > 0 && !ops[..first].iter().any(is_xclip) {
+            ops.insert(0, AlignmentOperation::Xclip(prefix_len));
+        }
+    }
+    alignment
+}
+
+/// The query of the most recent alignment when checkpointing the traceback, from which to
+/// recompute the columns of the traceback that are not stored (see
+/// [`MultiContigAligner::set_traceback_checkpoint_interval`]).
+struct CheckpointedQuery {
+    y: Vec<u8>,
+    start: Option<(usize, usize)>,
+    interval: usize,
+    /// The indexes of the contigs aligned to.
+    contig_indexes: BitSet<u32>,
 }
 
-pub struct MultiContigAligner<'a, F: MatchFunc> {
-    contigs: Vec<ContigAligner<'a, F>>,
+/// A contig and strand added to a [`MultiContigAligner`], as returned by
+/// [`MultiContigAligner::iter_contigs`], to convert the contig indexes of an alignment (see
+/// [`Alignment::start_contig_idx`] and [`AlignmentOperation::Xjump`]) to names and strands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContigInfo<'a> {
+    /// The index of the contig, as in the alignments.
+    pub contig_idx: usize,
+    /// The name of the contig.
+    pub name: &'a str,
+    /// True if the contig is the forward strand.
+    pub is_forward: bool,
+    /// The length of the full contig.
+    pub len: usize,
+    /// The aligned region of the contig, on its strand (see
+    /// [`MultiContigAligner::add_contig_region`]).
+    pub region: Range<usize>,
+    /// True if the contig is circular.
+    pub circular: bool,
+}
+
+/// A callback invoked after each column (query base) of the alignment matrices is filled, with the
+/// number of columns filled so far and the total number of columns (the query length).
+pub type ProgressCallback = Arc<dyn Fn(usize, usize) + Send + Sync>;
+
+pub struct MultiContigAligner<F: MatchFunc> {
+    contigs: Vec<ContigAligner<F>>,
+    /// The indexes of the contigs with at least one base, the only contigs aligned to (see
+    /// [`Self::custom`]).
+    non_empty_contigs: BitSet<u32>,
     to_opposite_strand: IndexMap<usize>,
+    lazy_reverse_complement: bool,
+    canonical_contig_order: bool,
+    adaptive_band_width: Option<usize>,
+    parallel_block_rows: Option<usize>,
+    span_query: bool,
+    forbid_clip_jumps: bool,
+    /// The positions of the contigs that jumps may be from or to, if restricted (see
+    /// [`Self::set_jump_sites`]).
+    jump_sites: Option<Arc<JumpSites>>,
+    /// The annotated introns of the contigs, if any (see [`Self::set_splice_annotation`]).
+    splice_annotation: Option<Arc<SpliceAnnotation>>,
+    /// The minimum length of a run of Ns to jump over for free, if any (see
+    /// [`Self::set_scaffold_gap_min_len`]).
+    scaffold_gap_min_len: Option<usize>,
+    progress_callback: Option<ProgressCallback>,
+    /// The time by which to stop aligning, if any (see [`Self::set_deadline`]).
+    deadline: Option<Instant>,
+    /// True if the most recent alignment was stopped at the deadline.
+    timed_out: bool,
+    /// The number of columns between the checkpoints of the traceback, if checkpointing (see
+    /// [`Self::set_traceback_checkpoint_interval`]).
+    traceback_checkpoint_interval: Option<usize>,
+    checkpointed_query: Option<CheckpointedQuery>,
+    /// The order in which the cells of the traceback of each contig are stored (see
+    /// [`Self::set_traceback_layout`]).
+    traceback_layout: TracebackLayout,
+    /// The buffers of the matrices of the contigs left out of the most recent alignment, for
+    /// reuse by the contigs aligned to (see [`Self::custom_with_subset`]).
+    pool: MatrixPool,
+    /// The part of the scoring shared by all the contigs, from the first contig added.
+    global_scoring: Option<GlobalScoring>,
+    /// The time spent in each stage of aligning, if recording (see
+    /// [`Self::set_record_timings`]).
+    timings: Option<StageTimings>,
+    /// The score surface of each aligned contig from the most recent alignment, if recording.
+    #[cfg(feature = "viz")]
+    score_surfaces: Option<Vec<ScoreSurface>>,
 }
 
-impl<'a, F: MatchFunc> MultiContigAligner<'a, F> {
+impl<F: MatchFunc + Sync> Default for MultiContigAligner<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: MatchFunc + Sync> MultiContigAligner<F> {
     #[allow(dead_code)]
     pub fn new() -> Self {
         MultiContigAligner {
             contigs: Vec::new(),
+            non_empty_contigs: BitSet::new(),
             to_opposite_strand: IndexMap::new(128),
+            lazy_reverse_complement: false,
+            canonical_contig_order: false,
+            adaptive_band_width: None,
+            parallel_block_rows: None,
+            span_query: false,
+            forbid_clip_jumps: false,
+            jump_sites: None,
+            splice_annotation: None,
+            scaffold_gap_min_len: None,
+            progress_callback: None,
+            deadline: None,
+            timed_out: false,
+            traceback_checkpoint_interval: None,
+            checkpointed_query: None,
+            traceback_layout: TracebackLayout::default(),
+            pool: MatrixPool::default(),
+            global_scoring: None,
+            timings: None,
+            #[cfg(feature = "viz")]
+            score_surfaces: None,
         }
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
         MultiContigAligner {
             contigs: Vec::with_capacity(capacity),
+            non_empty_contigs: BitSet::with_capacity(capacity),
             to_opposite_strand: IndexMap::new(capacity),
+            lazy_reverse_complement: false,
+            canonical_contig_order: false,
+            adaptive_band_width: None,
+            parallel_block_rows: None,
+            span_query: false,
+            forbid_clip_jumps: false,
+            jump_sites: None,
+            splice_annotation: None,
+            scaffold_gap_min_len: None,
+            progress_callback: None,
+            deadline: None,
+            timed_out: false,
+            traceback_checkpoint_interval: None,
+            checkpointed_query: None,
+            traceback_layout: TracebackLayout::default(),
+            pool: MatrixPool::default(),
+            global_scoring: None,
+            timings: None,
+            #[cfg(feature = "viz")]
+            score_surfaces: None,
+        }
+    }
+
+    /// Sets the time by which to stop aligning a query, or `None` to align queries to completion.
+    /// A query not aligned by the deadline has no alignment (see [`Self::timed_out`]).  The
+    /// deadline is checked after each column of the matrices is filled.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// True if the most recent alignment was stopped at the deadline (see [`Self::set_deadline`]),
+    /// in which case the alignment returned has no aligned bases.
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    /// Sets the number of columns (query bases) between the checkpoints of the traceback, or `None`
+    /// to keep the whole traceback.  When checkpointing every `K` columns, the traceback of each
+    /// contig keeps only the state of each `K`th column and a window of `K + 1` columns, so uses
+    /// about `K` times less memory for long queries, and the columns of the window are recomputed
+    /// from the checkpoint before them when tracing back, so aligning takes up to twice as long.
+    /// The alignments are the same either way.  Streaming alignments are never checkpointed.
+    pub fn set_traceback_checkpoint_interval(&mut self, interval: Option<usize>) {
+        assert!(
+            interval.is_none_or(|interval| interval > 0),
+            "The traceback checkpoint interval must be positive"
+        );
+        self.traceback_checkpoint_interval = interval;
+    }
+
+    /// The number of cells in the matrices filled when aligning a query of the given length to
+    /// the contigs, or to the given subset of the contigs.
+    pub fn num_cells(&self, n: usize, contig_indexes: Option<&BitSet<u32>>) -> usize {
+        self.contigs
+            .iter()
+            .filter(|contig| {
+                contig_indexes
+                    .is_none_or(|indexes| indexes.contains(contig.aligner.contig_idx.get()))
+            })
+            .map(|contig| (contig.len() + 1) * (n + 1))
+            .sum()
+    }
+
+    /// The estimated number of bytes of the matrices for aligning a query of the given length to
+    /// all the contigs, with the current traceback checkpoint interval (see
+    /// [`Self::set_traceback_checkpoint_interval`]), for checking whether a query may be aligned
+    /// before aligning it.  This depends on the size of the traceback cells, and so on whether
+    /// the crate is built with `low_mem`.
+    pub fn estimate_memory(&self, query_len: usize) -> usize {
+        self.estimate_memory_with(query_len, None, self.traceback_checkpoint_interval)
+    }
+
+    /// The estimated number of bytes of the matrices for aligning a query of the given length to
+    /// the contigs, or to the given subset of the contigs, when checkpointing the traceback with
+    /// the given interval (see [`Self::estimate_memory`]).
+    pub fn estimate_memory_with(
+        &self,
+        query_len: usize,
+        contig_indexes: Option<&BitSet<u32>>,
+        interval: Option<usize>,
+    ) -> usize {
+        self.contigs
+            .iter()
+            .filter(|contig| {
+                contig_indexes
+                    .is_none_or(|indexes| indexes.contains(contig.aligner.contig_idx.get()))
+            })
+            .map(|contig| {
+                SingleContigAligner::<F>::estimate_memory(contig.len(), query_len, interval)
+            })
+            .sum()
+    }
+
+    /// Sets the value for deriving the sequence of a contig on the reverse strand from the
+    /// sequence on the forward strand, rather than storing both.  This only applies to contigs
+    /// added (see [`Self::add_contig`]) after the same contig on the forward strand.
+    pub fn set_lazy_reverse_complement(&mut self, lazy_reverse_complement: bool) {
+        self.lazy_reverse_complement = lazy_reverse_complement;
+    }
+
+    /// Sets the value for evaluating the contigs in a canonical order, namely by name then with the
+    /// forward strand first, rather than the order in which they were added.  The order in which
+    /// the contigs are evaluated breaks ties between equally good alignments, so this makes the
+    /// alignment independent of the order in which the contigs were added, except for the contig
+    /// indexes themselves.
+    pub fn set_canonical_contig_order(&mut self, canonical_contig_order: bool) {
+        self.canonical_contig_order = canonical_contig_order;
+    }
+
+    /// Sets the adaptive band width of the aligner for each contig (see
+    /// [`SingleContigAligner::set_adaptive_band_width`]), including those added later.
+    pub fn set_adaptive_band_width(&mut self, adaptive_band_width: Option<usize>) {
+        self.adaptive_band_width = adaptive_band_width;
+        for contig in &mut self.contigs {
+            contig.aligner.set_adaptive_band_width(adaptive_band_width);
+        }
+    }
+
+    /// Sets the number of rows in each block of rows to fill in parallel of the aligner for each
+    /// contig (see [`SingleContigAligner::set_parallel_block_rows`]), including those added later.
+    pub fn set_parallel_block_rows(&mut self, parallel_block_rows: Option<usize>) {
+        self.parallel_block_rows = parallel_block_rows;
+        for contig in &mut self.contigs {
+            contig.aligner.set_parallel_block_rows(parallel_block_rows);
+        }
+    }
+
+    /// Sets the order in which the cells of the traceback of each contig are stored (see
+    /// [`SingleContigAligner::set_traceback_layout`]), including those added later.
+    pub fn set_traceback_layout(&mut self, layout: TracebackLayout) {
+        self.traceback_layout = layout;
+        for contig in &mut self.contigs {
+            contig.aligner.set_traceback_layout(layout);
+        }
+    }
+
+    /// Sets whether the alignment must span the whole query for each contig (see
+    /// [`SingleContigAligner::set_span_query`]), including those added later, while the contigs
+    /// may still be clipped and jumped between.
+    pub fn set_span_query(&mut self, span_query: bool) {
+        self.span_query = span_query;
+        for contig in &mut self.contigs {
+            contig.aligner.set_span_query(span_query);
+        }
+    }
+
+    /// Sets whether jumps into or out of a clipped region of each contig are forbidden (see
+    /// [`SingleContigAligner::set_forbid_clip_jumps`]), including those added later, so that every
+    /// jump, within or between contigs, is from an aligned base to another.
+    pub fn set_forbid_clip_jumps(&mut self, forbid_clip_jumps: bool) {
+        self.forbid_clip_jumps = forbid_clip_jumps;
+        for contig in &mut self.contigs {
+            contig.aligner.set_forbid_clip_jumps(forbid_clip_jumps);
+        }
+    }
+
+    /// Sets whether each base of the aligned region of the contig with the given index may not be
+    /// aligned to the query base at the same position (see
+    /// [`SingleContigAligner::set_mask_diagonal`]), for aligning a query to itself as the contig.
+    /// Panics if there is no such contig.
+    pub fn set_mask_diagonal(&mut self, contig_idx: usize, mask_diagonal: bool) {
+        self.contigs[contig_idx]
+            .aligner
+            .set_mask_diagonal(mask_diagonal);
+    }
+
+    /// Sets the positions of the contigs that jumps may be from or to, or from which jumps are
+    /// preferred (see [`JumpSites`]), including the contigs added later, or `None` for jumps to be
+    /// from or to any position.
+    pub fn set_jump_sites(&mut self, jump_sites: Option<Arc<JumpSites>>) {
+        for contig in &mut self.contigs {
+            contig.set_jump_sites(jump_sites.as_deref());
+        }
+        self.jump_sites = jump_sites;
+    }
+
+    /// Sets the annotated introns of the contigs (see [`SpliceAnnotation`]), including the
+    /// contigs added later, so that the jumps over them are scored with the splice jump score
+    /// (see [`Scoring::splice_jump_score`]) whatever their motifs, or `None` for only the jumps
+    /// over introns with splice motifs to be.
+    pub fn set_splice_annotation(&mut self, splice_annotation: Option<Arc<SpliceAnnotation>>) {
+        for contig in &mut self.contigs {
+            contig.set_splice_annotation(splice_annotation.as_deref());
+        }
+        self.splice_annotation = splice_annotation;
+    }
+
+    /// Sets the minimum length of a run of Ns in the contigs, including those added later, to be a
+    /// scaffold gap of unknown length, over which a jump forward is free (see
+    /// [`SingleContigAligner::set_scaffold_gaps`]), or `None` to align Ns as any other base.
+    pub fn set_scaffold_gap_min_len(&mut self, scaffold_gap_min_len: Option<usize>) {
+        self.scaffold_gap_min_len = scaffold_gap_min_len;
+        for contig in &mut self.contigs {
+            let bases = contig.seq.to_vec();
+            contig
+                .aligner
+                .set_scaffold_gaps(&bases, scaffold_gap_min_len);
+        }
+    }
+
+    /// Sets the jump scores of all the contigs added so far (see [`Scoring::set_jump_scores`]),
+    /// which also removes any direction-specific scores for jumps within the same contig and
+    /// strand.  Jumps are compared across contigs, so are scored the same for all contigs.
+    pub fn set_jump_scores(
+        &mut self,
+        jump_score_same_contig_and_strand: i32,
+        jump_score_same_contig_opposite_strand: i32,
+        jump_score_inter_contig: i32,
+    ) {
+        self.update_scoring(None, |scoring| {
+            scoring.jump_score_same_contig_and_strand = jump_score_same_contig_and_strand;
+            scoring.jump_score_same_contig_opposite_strand = jump_score_same_contig_opposite_strand;
+            scoring.jump_score_inter_contig = jump_score_inter_contig;
+            scoring.jump_score_forward = None;
+            scoring.jump_score_backward = None;
+        });
+    }
+
+    /// Sets the clipping penalties of all the contigs added so far.  Clips are compared across
+    /// contigs, so are scored the same for all contigs.
+    pub fn set_clip_penalties(
+        &mut self,
+        xclip_prefix: i32,
+        xclip_suffix: i32,
+        yclip_prefix: i32,
+        yclip_suffix: i32,
+    ) {
+        self.update_scoring(None, |scoring| {
+            scoring.xclip_prefix = xclip_prefix;
+            scoring.xclip_suffix = xclip_suffix;
+            scoring.yclip_prefix = yclip_prefix;
+            scoring.yclip_suffix = yclip_suffix;
+        });
+    }
+
+    /// Sets the gap open and extend scores of both strands of the contig with the given name, or
+    /// of all the contigs added so far if `None`.
+    pub fn set_gap_penalties(&mut self, name: Option<&str>, gap_open: i32, gap_extend: i32) {
+        if let Some(name) = name {
+            assert!(
+                self.contigs.iter().any(|contig| contig.name == name),
+                "No contig with name: {name}"
+            );
+        }
+        self.update_scoring(name, |scoring| {
+            scoring.gap_open = gap_open;
+            scoring.gap_extend = gap_extend;
+        });
+    }
+
+    /// Updates the scoring of the contigs with the given name, or of all the contigs if `None`,
+    /// then the global scoring shared by the contigs.
+    fn update_scoring(&mut self, name: Option<&str>, update: impl Fn(&mut Scoring<F>)) {
+        for contig in &mut self.contigs {
+            if name.is_none_or(|name| contig.name == name) {
+                contig.aligner.update_scoring(&update);
+            }
+        }
+        self.global_scoring = self
+            .contigs
+            .first()
+            .map(|contig| contig.aligner.scoring.global_scoring());
+    }
+
+    /// Sets the callback invoked after each column of the alignment is filled, for reporting the
+    /// progress of aligning long queries (see [`ProgressCallback`]), or `None` to remove it.
+    pub fn set_progress_callback(&mut self, progress_callback: Option<ProgressCallback>) {
+        self.progress_callback = progress_callback;
+    }
+
+    /// Sets whether to record the time spent in each stage of aligning (see [`Stage`]), summed over
+    /// the alignments until reset with [`Self::reset_timings`], and retrieved with
+    /// [`Self::timings`].  Recording reads the clock a few times per column of the matrices.
+    pub fn set_record_timings(&mut self, record: bool) {
+        self.timings = record.then(StageTimings::default);
+    }
+
+    /// Returns the time spent in each stage of aligning since the timings were last reset, if
+    /// recording (see [`Self::set_record_timings`]).
+    pub fn timings(&self) -> Option<&StageTimings> {
+        self.timings.as_ref()
+    }
+
+    /// Resets the time spent in each stage of aligning to zero, if recording.
+    pub fn reset_timings(&mut self) {
+        if let Some(timings) = &mut self.timings {
+            *timings = StageTimings::default();
+        }
+    }
+
+    /// Sets whether to record the score surface of each contig (the best score of an alignment
+    /// ending at each contig and query position) when aligning, retrieved with
+    /// [`Self::score_surfaces`].  Recording uses memory proportional to the product of the query
+    /// length and the total length of the contigs.
+    #[cfg(feature = "viz")]
+    pub fn set_record_score_surfaces(&mut self, record: bool) {
+        self.score_surfaces = record.then(Vec::new);
+    }
+
+    /// Returns the score surface of each contig from the most recent alignment, in the order of
+    /// the contig index, or an empty slice if not recording (see
+    /// [`Self::set_record_score_surfaces`]).
+    #[cfg(feature = "viz")]
+    pub fn score_surfaces(&self) -> &[ScoreSurface] {
+        self.score_surfaces.as_deref().unwrap_or_default()
+    }
+
+    /// Records the given column of the score surface of each contig, if recording.
+    #[cfg(feature = "viz")]
+    fn record_score_surfaces(&mut self, j: usize, curr: usize) {
+        if let Some(surfaces) = &mut self.score_surfaces {
+            for (contig, surface) in self.contigs.iter().zip(surfaces.iter_mut()) {
+                surface.set_column(j, &contig.aligner.S[curr]);
+            }
         }
     }
 
@@ -76,25 +629,150 @@ impl<'a, F: MatchFunc> MultiContigAligner<'a, F> {
         self.contigs.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.contigs.is_empty()
+    }
+
+    /// The part of the scoring shared by all the contigs (see [`GlobalScoring`]), or `None` if
+    /// no contig has been added.
+    pub fn global_scoring(&self) -> Option<&GlobalScoring> {
+        self.global_scoring.as_ref()
+    }
+
     pub fn is_circular(&self, contig_idx: usize) -> bool {
         self.contigs[contig_idx].aligner.circular
     }
 
+    /// The name of the contig with the given index.  Panics if there is no such contig.
+    pub fn contig_name(&self, contig_idx: usize) -> &str {
+        &self.contigs[contig_idx].name
+    }
+
+    /// True if the contig with the given index is the forward strand.  Panics if there is no such
+    /// contig.
+    pub fn contig_is_forward(&self, contig_idx: usize) -> bool {
+        self.contigs[contig_idx].is_forward
+    }
+
+    /// The length of the full contig with the given index, not only of its aligned region (see
+    /// [`Self::add_contig_region`]).  Panics if there is no such contig.
+    pub fn contig_len(&self, contig_idx: usize) -> usize {
+        self.contigs[contig_idx].contig_len
+    }
+
+    /// Returns an iterator over the contigs, in the order of their indexes.
+    pub fn iter_contigs(&self) -> impl ExactSizeIterator<Item = ContigInfo<'_>> + '_ {
+        self.contigs.iter().map(|contig| ContigInfo {
+            contig_idx: contig.aligner.contig_idx.get(),
+            name: &contig.name,
+            is_forward: contig.is_forward,
+            len: contig.contig_len,
+            region: contig.offset..contig.offset + contig.len(),
+            circular: contig.aligner.circular,
+        })
+    }
+
     pub fn contig_index_for_strand(&self, is_forward: bool, name: &str) -> Option<usize> {
         for contig in &self.contigs {
             if contig.is_forward == is_forward && contig.name == name {
-                return Some(contig.aligner.contig_idx as usize);
+                return Some(contig.aligner.contig_idx.get());
             }
         }
         None
     }
 
     /// Adds a new aligner for the given contig and strand.
+    ///
+    /// If the reverse complement is derived lazily (see [`Self::set_lazy_reverse_complement`]),
+    /// and the contig on the forward strand has already been added, then the given sequence for
+    /// the reverse strand is not stored.
+    ///
+    /// The match function and gap scores of the scoring may differ between contigs (see
+    /// [`crate::align::ContigScoring`]), but the jump scores and clipping penalties must be the
+    /// same as those of the contigs already added (see [`GlobalScoring`]), otherwise this panics.
     pub fn add_contig(
         &mut self,
         name: &str,
         is_forward: bool,
-        seq: TextSlice<'a>,
+        seq: TextSlice<'_>,
+        circular: bool,
+        scoring: Scoring<F>,
+    ) {
+        self.add_contig_region(name, is_forward, seq, 0..seq.len(), circular, scoring);
+    }
+
+    /// Adds a new aligner for the given region of the contig and strand.  Only the bases in the
+    /// region are aligned, but the alignments are reported in the coordinates of the full contig.
+    /// The region is given in the coordinates of the given sequence, so for the reverse strand it
+    /// is a region of the reverse complement of the contig.  A circular contig must be added in
+    /// full.
+    ///
+    /// If the reverse complement is derived lazily (see [`Self::set_lazy_reverse_complement`]),
+    /// and the same region of the contig on the forward strand has already been added, then the
+    /// given sequence for the reverse strand is not stored.  As for [`Self::add_contig`], the
+    /// jump scores and clipping penalties must be the same for all contigs.
+    pub fn add_contig_region(
+        &mut self,
+        name: &str,
+        is_forward: bool,
+        seq: TextSlice<'_>,
+        region: Range<usize>,
+        circular: bool,
+        scoring: Scoring<F>,
+    ) {
+        assert!(
+            self.contig_index_for_strand(is_forward, name).is_none(),
+            "Contig already added! name: {name} is_forward: {is_forward}"
+        );
+        assert!(
+            region.start <= region.end && region.end <= seq.len(),
+            "Region {region:?} out of bounds for contig {name} with length {}",
+            seq.len()
+        );
+        assert!(
+            !circular || region == (0..seq.len()),
+            "A circular contig must be added in full: {name}"
+        );
+
+        let contig_len = seq.len();
+        let bases = &seq[region.clone()];
+        let fwd_contig = self
+            .contig_index_for_strand(true, name)
+            .map(|idx| &self.contigs[idx]);
+        let seq = match (self.lazy_reverse_complement, is_forward, fwd_contig) {
+            (true, false, Some(fwd_contig))
+                if fwd_contig.contig_len == seq.len()
+                    && fwd_contig.offset + fwd_contig.len() == seq.len() - region.start
+                    && fwd_contig.len() == region.len() =>
+            {
+                match &fwd_contig.seq {
+                    ContigSeq::Packed(fwd) => ContigSeq::ReverseComplementOf(fwd.clone()),
+                    ContigSeq::ReverseComplementOf(_) => unreachable!(),
+                    ContigSeq::Profile(_) => ContigSeq::Packed(Arc::new(PackedSeq::new(bases))),
+                }
+            }
+            _ => ContigSeq::Packed(Arc::new(PackedSeq::new(bases))),
+        };
+        self.push_contig(
+            name, is_forward, seq, bases, region, contig_len, circular, scoring,
+        );
+    }
+
+    /// Adds a new aligner for the given contig and strand, scoring the alignment of each query
+    /// base to each contig position with the given profile, rather than the match function of the
+    /// scoring.  The contig sequence is the consensus of the profile (see
+    /// [`ScoreProfile::consensus`]), and so the profile for the reverse strand is the reverse
+    /// complement of that of the forward strand (see [`ScoreProfile::reverse_complement`]).
+    ///
+    /// Re-scoring an alignment (see [`Alignment::rescore`]) uses the match function of the
+    /// scoring on the consensus, and so differs from the score of an alignment to a profile.  As
+    /// for [`Self::add_contig`], the jump scores and clipping penalties must be the same for all
+    /// contigs.
+    pub fn add_contig_profile(
+        &mut self,
+        name: &str,
+        is_forward: bool,
+        profile: &ScoreProfile,
         circular: bool,
         scoring: Scoring<F>,
     ) {
@@ -102,16 +780,117 @@ impl<'a, F: MatchFunc> MultiContigAligner<'a, F> {
             self.contig_index_for_strand(is_forward, name).is_none(),
             "Contig already added! name: {name} is_forward: {is_forward}"
         );
+        let seq = ContigSeq::Profile(Arc::new(profile.clone()));
+        self.push_contig(
+            name,
+            is_forward,
+            seq,
+            profile.consensus(),
+            0..profile.len(),
+            profile.len(),
+            circular,
+            scoring,
+        );
+    }
+
+    /// Adds a new aligner for each contig of the shared set, first on the forward strand of every
+    /// contig, then on the reverse strand if the set is double stranded, with the scoring of each
+    /// strand given by `scoring_for_strand` (true for the forward strand).  The aligners share the
+    /// sequences of the set rather than copying them, so the aligners of many threads may be
+    /// built from the same set.  As for [`Self::add_contig`], the jump scores and clipping
+    /// penalties must be the same for all contigs.
+    pub fn add_shared_contigs(
+        &mut self,
+        contigs: &SharedContigs,
+        scoring_for_strand: impl Fn(bool) -> Scoring<F>,
+    ) {
+        let strands: &[bool] = if contigs.double_strand() {
+            &[true, false]
+        } else {
+            &[true]
+        };
+        for &is_forward in strands {
+            for contig in contigs.contigs() {
+                let name = &contig.name;
+                assert!(
+                    self.contig_index_for_strand(is_forward, name).is_none(),
+                    "Contig already added! name: {name} is_forward: {is_forward}"
+                );
+                let seq = match (is_forward, &contig.revcomp) {
+                    (true, _) => ContigSeq::Packed(contig.fwd.clone()),
+                    (false, Some(revcomp)) => ContigSeq::Packed(revcomp.clone()),
+                    (false, None) => ContigSeq::ReverseComplementOf(contig.fwd.clone()),
+                };
+                let bases = seq.to_vec();
+                let len = bases.len();
+                self.push_contig(
+                    name,
+                    is_forward,
+                    seq,
+                    &bases,
+                    0..len,
+                    len,
+                    contig.circular,
+                    scoring_for_strand(is_forward),
+                );
+            }
+        }
+    }
 
+    /// Adds a new aligner for the given contig sequence, with `bases` the bases of the aligned
+    /// region of the contig.
+    #[allow(clippy::too_many_arguments)]
+    fn push_contig(
+        &mut self,
+        name: &str,
+        is_forward: bool,
+        seq: ContigSeq,
+        bases: &[u8],
+        region: Range<usize>,
+        contig_len: usize,
+        circular: bool,
+        scoring: Scoring<F>,
+    ) {
+        // Jumps and clips are compared across contigs, so must be scored the same for all contigs
+        let global_scoring = scoring.global_scoring();
+        match &self.global_scoring {
+            None => self.global_scoring = Some(global_scoring),
+            Some(expected) => {
+                if let Err(e) = expected.ensure_compatible(&global_scoring) {
+                    panic!("Scoring of contig {name} differs from the other contigs: {e}");
+                }
+            }
+        }
         let contig_idx: usize = self.contigs.len();
-        let contig = ContigAligner::new(
+        let mut contig = ContigAligner::new(
             name.to_string(),
             is_forward,
             scoring,
             seq,
+            region,
+            contig_len,
             contig_idx,
             circular,
         );
+        contig.aligner.set_homopolymer_runs(bases);
+        contig.aligner.set_splice_motifs(bases);
+        contig
+            .aligner
+            .set_scaffold_gaps(bases, self.scaffold_gap_min_len);
+        contig
+            .aligner
+            .set_adaptive_band_width(self.adaptive_band_width);
+        contig
+            .aligner
+            .set_parallel_block_rows(self.parallel_block_rows);
+        contig.aligner.set_traceback_layout(self.traceback_layout);
+        contig.aligner.set_span_query(self.span_query);
+        contig.aligner.set_forbid_clip_jumps(self.forbid_clip_jumps);
+        contig.set_jump_sites(self.jump_sites.as_deref());
+        contig.set_splice_annotation(self.splice_annotation.as_deref());
+        if contig.len() > 0 {
+            self.non_empty_contigs.insert(contig_idx);
+        }
         self.contigs.push(contig);
         if contig_idx >= self.to_opposite_strand.capacity() {
             self.to_opposite_strand.reserve(contig_idx);
@@ -121,27 +900,25 @@ impl<'a, F: MatchFunc> MultiContigAligner<'a, F> {
             if contig.name == name && contig.is_forward != is_forward {
                 assert!(self
                     .to_opposite_strand
-                    .get_u32(contig.aligner.contig_idx)
+                    .get(contig.aligner.contig_idx.get())
                     .is_none());
                 self.to_opposite_strand
-                    .put(contig_idx, contig.aligner.contig_idx as usize);
+                    .put(contig_idx, contig.aligner.contig_idx.get());
                 self.to_opposite_strand
-                    .put_u32(contig.aligner.contig_idx, contig_idx);
+                    .put(contig.aligner.contig_idx.get(), contig_idx);
                 break;
             }
         }
     }
 
-    fn jump_info_for_contig(contig: &ContigAligner<'a, F>, j: usize) -> JumpInfo {
-        contig.aligner.get_jump_info(
-            contig.len(),
-            j - 1,
-            contig.aligner.scoring.jump_score_same_contig_and_strand,
-        )
+    fn jump_info_for_contig(contig: &ContigAligner<F>, j: usize) -> JumpInfo {
+        contig
+            .aligner
+            .get_same_strand_jump_info(contig.len(), j - 1)
     }
 
     fn jump_info_for_opposite_strand(
-        opp_contig: Option<&ContigAligner<'a, F>>,
+        opp_contig: Option<&ContigAligner<F>>,
         j: usize,
     ) -> Option<JumpInfo> {
         opp_contig.map(|opp| {
@@ -156,11 +933,11 @@ impl<'a, F: MatchFunc> MultiContigAligner<'a, F> {
     }
 
     fn jump_info_for_inter_contig(
-        contig: &ContigAligner<'a, F>,
+        contig: &ContigAligner<F>,
         inter_contig_jump_infos: &[JumpInfo],
         opp_contig_idx: Option<usize>,
     ) -> Option<JumpInfo> {
-        let opp_contig_idx = opp_contig_idx.map_or(contig.aligner.contig_idx, |idx| idx as u32);
+        let opp_contig_idx = opp_contig_idx.map_or(contig.aligner.contig_idx, ContigIdx::new);
         inter_contig_jump_infos
             .iter()
             .filter(|info| info.idx != contig.aligner.contig_idx && info.idx != opp_contig_idx)
@@ -190,7 +967,7 @@ impl<'a, F: MatchFunc> MultiContigAligner<'a, F> {
                 let mut excluded = Vec::with_capacity(self.len() - indexes.len());
                 while !self.contigs.is_empty() {
                     let contig = self.contigs.remove(0);
-                    if indexes.contains(contig.aligner.contig_idx as usize) {
+                    if indexes.contains(contig.aligner.contig_idx.get()) {
                         included.push(contig);
                     } else {
                         excluded.push(contig);
@@ -198,8 +975,12 @@ impl<'a, F: MatchFunc> MultiContigAligner<'a, F> {
                 }
                 assert!(!included.is_empty());
 
-                // overwrite this aligners contigs with just the included subset
+                // overwrite this aligners contigs with just the included subset, and pool the
+                // buffers of the excluded contigs for the included contigs to reuse
                 self.contigs = included;
+                for contig in &mut excluded {
+                    contig.aligner.release_buffers(&mut self.pool);
+                }
 
                 // align!
                 let aln = self.custom(y);
@@ -224,117 +1005,328 @@ impl<'a, F: MatchFunc> MultiContigAligner<'a, F> {
 
     /// The core function to compute the alignment
     ///
+    /// An empty query, or a query to align to no contigs or only to empty contigs, has an empty
+    /// alignment (see [`Alignment::empty`]), after which there is no traceback to trace back
+    /// alignments from (see [`Self::traceback_all`]).  Empty contigs are otherwise left out of the
+    /// alignment, since no query base may be aligned to them.
+    ///
     /// # Arguments
     ///
     /// * `x` - Textslice
     /// * `y` - Textslice
     pub fn custom(&mut self, y: TextSlice<'_>) -> Alignment {
-        let n = y.len();
+        // The contigs may be a subset of those added (see `custom_with_subset`)
+        let num_non_empty = self
+            .contigs
+            .iter()
+            .filter(|contig| self.is_non_empty(contig))
+            .count();
+        if y.is_empty() || num_non_empty == 0 {
+            self.checkpointed_query = None;
+            for contig in &mut self.contigs {
+                contig.aligner.release_buffers(&mut self.pool);
+            }
+            return Alignment::empty(y.len());
+        }
+        if num_non_empty < self.len() {
+            let non_empty = self.non_empty_contigs.clone();
+            return self.custom_with_subset(y, Some(&non_empty));
+        }
+        self.align(y, None, None)
+    }
+
+    /// True if the contig has at least one base, and so may be aligned to.
+    fn is_non_empty(&self, contig: &ContigAligner<F>) -> bool {
+        self.non_empty_contigs
+            .contains(contig.aligner.contig_idx.get())
+    }
+
+    /// Starts aligning a query whose bases arrive over time (see [`StreamingAligner`]).  The
+    /// aligner may not be used otherwise until the returned streaming aligner is dropped.
+    pub fn streaming(&mut self) -> StreamingAligner<'_, F> {
+        // Leave out the empty contigs as when aligning the full query, and restore them once done
+        // (see drop)
+        let (contigs, empty_contigs) = std::mem::take(&mut self.contigs)
+            .into_iter()
+            .partition(|contig| self.is_non_empty(contig));
+        self.contigs = contigs;
+        // Evaluate the contigs in canonical order, and restore the order once done (see drop)
+        if self.canonical_contig_order {
+            self.contigs
+                .sort_by(|left, right| Self::canonical_order(left, right));
+        }
+        let (to_opposite_strand, max_contig_index) = self.opposite_strands();
+        self.checkpointed_query = None;
+        for contig in &mut self.contigs {
+            contig.aligner.set_traceback_checkpoint_interval(None);
+            contig
+                .aligner
+                .acquire_buffers(&mut self.pool, contig.len(), 0);
+            contig.aligner.init_matrices(contig.len(), 0);
+        }
+        StreamingAligner {
+            aligner: self,
+            y: Vec::new(),
+            to_opposite_strand,
+            max_contig_index,
+            empty_contigs,
+        }
+    }
+
+    /// Computes the alignment, forcing it to start and/or end at the given contig index and
+    /// position in the contig.
+    ///
+    /// An anchored alignment consumes the full query at the anchored end(s), so the clipping
+    /// penalties for the anchored end(s) are ignored.  An alignment anchored to start after the
+    /// start of the contig begins with a prefix clip of the contig, and similarly an alignment
+    /// anchored to end before the end of the contig ends with a suffix clip of the contig.  The
+    /// alignment may not jump before aligning the first query base.
+    ///
+    /// # Arguments
+    ///
+    /// * `y` - Textslice
+    /// * `start` - the contig index and (zero-based) position in the contig at which the alignment
+    ///   must start
+    /// * `end` - the contig index and (zero-based exclusive) position in the contig at which the
+    ///   alignment must end
+    pub fn custom_anchored(
+        &mut self,
+        y: TextSlice<'_>,
+        start: Option<(usize, usize)>,
+        end: Option<(usize, usize)>,
+    ) -> Alignment {
+        // Convert the anchors to the coordinates of the aligned region of the contig
+        let to_region = |(contig_idx, pos): (usize, usize)| {
+            let contig = self
+                .contigs
+                .iter()
+                .find(|c| c.aligner.contig_idx.get() == contig_idx)
+                .unwrap_or_else(|| panic!("Anchored to an unknown contig index: {contig_idx}"));
+            assert!(
+                contig.offset <= pos && pos <= contig.offset + contig.len(),
+                "Anchored position {pos} outside the aligned region of contig {}",
+                contig.name
+            );
+            (contig_idx, pos - contig.offset)
+        };
+        let start = start.map(to_region);
+        let end = end.map(to_region);
+
+        // Disable the clipping at the anchored end(s), and restore them after
+        let clip_penalties = self
+            .contigs
+            .iter()
+            .map(|c| {
+                let scoring = &c.aligner.scoring;
+                [
+                    scoring.xclip_prefix,
+                    scoring.xclip_suffix,
+                    scoring.yclip_prefix,
+                    scoring.yclip_suffix,
+                ]
+            })
+            .collect_vec();
+        for contig in &mut self.contigs {
+            let scoring = &mut contig.aligner.scoring;
+            if start.is_some() {
+                scoring.xclip_prefix = MIN_SCORE;
+                scoring.yclip_prefix = MIN_SCORE;
+            }
+            if end.is_some() {
+                scoring.xclip_suffix = MIN_SCORE;
+                scoring.yclip_suffix = MIN_SCORE;
+            }
+        }
+        let alignment = self.align(y, start, end);
+        // The columns of the traceback cannot be recomputed once the clipping is restored
+        self.checkpointed_query = None;
+        for (contig, clip_penalties) in self.contigs.iter_mut().zip(clip_penalties) {
+            let scoring = &mut contig.aligner.scoring;
+            scoring.xclip_prefix = clip_penalties[0];
+            scoring.xclip_suffix = clip_penalties[1];
+            scoring.yclip_prefix = clip_penalties[2];
+            scoring.yclip_suffix = clip_penalties[3];
+        }
+        alignment
+    }
+
+    /// Returns the position in the contig with the given index for the anchor, if the anchor is
+    /// on that contig.
+    fn anchor_pos(anchor: Option<(usize, usize)>, contig_idx: ContigIdx) -> Option<usize> {
+        anchor.and_then(|(idx, pos)| (idx == contig_idx.get()).then_some(pos))
+    }
+
+    /// Orders the contigs by name, then with the forward strand first.
+    fn canonical_order(left: &ContigAligner<F>, right: &ContigAligner<F>) -> std::cmp::Ordering {
+        (&left.name, !left.is_forward).cmp(&(&right.name, !right.is_forward))
+    }
+
+    /// Returns the aligners for all contigs in the order in which they are evaluated (see
+    /// [`Self::set_canonical_contig_order`]).
+    fn aligners_in_evaluation_order(&self) -> Vec<&SingleContigAligner<F>> {
+        let mut contigs = self.contigs.iter().collect_vec();
+        if self.canonical_contig_order {
+            contigs.sort_by(|left, right| Self::canonical_order(left, right));
+        }
+        contigs
+            .into_iter()
+            .map(|contig| &contig.aligner)
+            .collect_vec()
+    }
 
+    /// Returns the index (in the order of evaluation) of the contig on the opposite strand of
+    /// each contig, by contig index, and the maximum contig index.
+    fn opposite_strands(&self) -> (IndexMap<usize>, usize) {
         let max_contig_index = self
             .contigs
             .iter()
-            .map(|c| c.aligner.contig_idx)
+            .map(|c| c.aligner.contig_idx.get())
             .max()
-            .unwrap() as usize;
+            .unwrap_or_default();
 
         let mut to_opposite_strand: IndexMap<usize> = IndexMap::new(max_contig_index);
         // find the contig index for the opposite strand
         for i in 0..self.contigs.len() {
             let left_contig = &self.contigs[i];
-            let left_contig_idx = left_contig.aligner.contig_idx as usize;
+            let left_contig_idx = left_contig.aligner.contig_idx.get();
             if to_opposite_strand.contains(left_contig_idx) {
                 continue;
             }
             for j in (i + 1)..self.contigs.len() {
                 let right_contig = &self.contigs[j];
-                let right_contig_idx = right_contig.aligner.contig_idx as usize;
+                let right_contig_idx = right_contig.aligner.contig_idx.get();
                 if left_contig.name == right_contig.name
                     && left_contig.is_forward != right_contig.is_forward
                 {
                     assert!(to_opposite_strand
-                        .get_u32(left_contig.aligner.contig_idx)
+                        .get(left_contig.aligner.contig_idx.get())
                         .is_none());
                     to_opposite_strand.put(left_contig_idx, j);
                     to_opposite_strand.put(right_contig_idx, i);
                 }
             }
         }
+        (to_opposite_strand, max_contig_index)
+    }
 
-        // Set the initial conditions
-        // We are repeating some work, but that's okay!
+    /// Fills column `j` of the matrices of every contig, for the query `y` of length `n`, given the
+    /// contig on the opposite strand of each contig (see [`Self::opposite_strands`]).
+    fn fill_query_column(
+        &mut self,
+        y: TextSlice<'_>,
+        n: usize,
+        j: usize,
+        start: Option<(usize, usize)>,
+        to_opposite_strand: &IndexMap<usize>,
+        max_contig_index: usize,
+    ) {
+        let curr = j % 2;
+        let prev = 1 - curr;
+        stage_span!(TRACE, "fill_column", j);
+        let started = self.timings.map(|_| Instant::now());
+
+        // Initialize the column
         for contig in &mut self.contigs {
-            contig.aligner.init_matrices(contig.len(), n);
+            if start.is_some() {
+                let pos = Self::anchor_pos(start, contig.aligner.contig_idx);
+                contig
+                    .aligner
+                    .init_anchored_column(j, curr, contig.len(), n, pos);
+            } else {
+                contig.aligner.init_column(j, curr, contig.len(), n);
+            }
         }
 
-        for j in 1..=n {
-            let curr = j % 2;
-            let prev = 1 - curr;
+        let started = lap(&mut self.timings, Stage::ColumnFill, started);
 
-            // Initialize the column
-            for contig in &mut self.contigs {
-                contig.aligner.init_column(j, curr, contig.len(), n);
-            }
+        // pre-compute the inter-contig jump scores for each contig
+        let mut inter_contig_jump_infos = Vec::with_capacity(self.contigs.len());
+        for contig in &self.contigs {
+            let mut info = contig.aligner.get_jump_info(
+                contig.len(),
+                j - 1,
+                contig.aligner.scoring.jump_score_inter_contig,
+            );
+            info.idx = contig.aligner.contig_idx;
+            inter_contig_jump_infos.push(info);
+        }
 
-            // pre-compute the inter-contig jump scores for each contig
-            let mut inter_contig_jump_infos = Vec::with_capacity(self.contigs.len());
+        // the best jump from any contig without the jump score, for the jumps to free positions
+        let mut free_jump_into = JumpInfo {
+            score: MIN_SCORE,
+            ..JumpInfo::default()
+        };
+        if self.contigs.iter().any(|c| c.aligner.has_free_jump_sites()) {
             for contig in &self.contigs {
-                let mut info = contig.aligner.get_jump_info(
-                    contig.len(),
-                    j - 1,
-                    contig.aligner.scoring.jump_score_inter_contig,
-                );
+                let mut info = contig.aligner.get_jump_info(contig.len(), j - 1, 0);
                 info.idx = contig.aligner.contig_idx;
-                inter_contig_jump_infos.push(info);
+                if info.score > free_jump_into.score {
+                    free_jump_into = info;
+                }
             }
+        }
+        // An anchored alignment must align the first query base at the anchor
+        if start.is_some() && j == 1 {
+            free_jump_into.score = MIN_SCORE;
+        }
 
-            // Get the best jump for each contig
-            let mut best_jump_infos: IndexMap<JumpInfo> = IndexMap::new(max_contig_index);
-            for contig in &self.contigs {
-                // let opp_contig = self
-                //     .to_opposite_strand
-                //     .get_u32(contig.aligner.contig_idx)
-                //     // TODO: does not work when subsetting the contigs
-                //     .map(|idx| &self.contigs[idx]);
-                let opp_contig = to_opposite_strand
-                    .get_u32(contig.aligner.contig_idx)
-                    // TODO: does not work when subsetting the contigs
-                    .map(|idx| &self.contigs[idx]);
-
-                // Evaluate three jumps
-                // 1. jump to the same contig and strand
-                // 2. jump to the same contig and opposite strand
-                // 3. jump to a different contig and any strand
-                let same: JumpInfo = Self::jump_info_for_contig(contig, j);
-                let flip_strand: Option<JumpInfo> =
-                    Self::jump_info_for_opposite_strand(opp_contig, j);
-                let inter_contig = Self::jump_info_for_inter_contig(
-                    contig,
-                    &inter_contig_jump_infos,
-                    opp_contig.map(|c| c.aligner.contig_idx as usize),
-                );
+        // Get the best jump for each contig
+        let mut best_jump_infos: IndexMap<JumpInfo> = IndexMap::new(max_contig_index);
+        for contig in &self.contigs {
+            // let opp_contig = self
+            //     .to_opposite_strand
+            //     .get(contig.aligner.contig_idx.get())
+            //     // TODO: does not work when subsetting the contigs
+            //     .map(|idx| &self.contigs[idx]);
+            let opp_contig = to_opposite_strand
+                .get(contig.aligner.contig_idx.get())
+                // TODO: does not work when subsetting the contigs
+                .map(|idx| &self.contigs[idx]);
 
-                // NB: in case of ties, prefer a jump to the same contig and strand, then same
-                // contig, then inter-contig
-                let mut best_jump_info = same;
-                if let Some(jump_info) = flip_strand {
-                    if jump_info.score > best_jump_info.score {
-                        best_jump_info = jump_info;
-                    }
+            // Evaluate three jumps
+            // 1. jump to the same contig and strand
+            // 2. jump to the same contig and opposite strand
+            // 3. jump to a different contig and any strand
+            let same: JumpInfo = Self::jump_info_for_contig(contig, j);
+            let flip_strand: Option<JumpInfo> = Self::jump_info_for_opposite_strand(opp_contig, j);
+            let inter_contig = Self::jump_info_for_inter_contig(
+                contig,
+                &inter_contig_jump_infos,
+                opp_contig.map(|c| c.aligner.contig_idx.get()),
+            );
+
+            // NB: in case of ties, prefer a jump to the same contig and strand, then same
+            // contig, then inter-contig
+            let mut best_jump_info = same;
+            if let Some(jump_info) = flip_strand {
+                if jump_info.score > best_jump_info.score {
+                    best_jump_info = jump_info;
                 }
-                if let Some(jump_info) = inter_contig {
-                    if jump_info.score > best_jump_info.score {
-                        best_jump_info = jump_info;
-                    }
+            }
+            if let Some(jump_info) = inter_contig {
+                if jump_info.score > best_jump_info.score {
+                    best_jump_info = jump_info;
                 }
-                best_jump_infos.put_u32(contig.aligner.contig_idx, best_jump_info);
             }
+            // An anchored alignment must align the first query base at the anchor
+            if start.is_some() && j == 1 {
+                best_jump_info.score = MIN_SCORE;
+            }
+            best_jump_infos.put(contig.aligner.contig_idx.get(), best_jump_info);
+        }
+        let started = lap(&mut self.timings, Stage::JumpInfo, started);
 
-            // Fill in the column
-            for contig in &mut self.contigs {
-                let jump_info = best_jump_infos.get_u32(contig.aligner.contig_idx).unwrap();
-                contig.aligner.fill_column(
-                    contig.seq,
+        // Fill in the column.  An anchored alignment must not jump from the anchor (see
+        // above), including within the same contig and strand.
+        let same_strand_jumps = start.is_none() || j > 1;
+        for contig in &mut self.contigs {
+            let jump_info = best_jump_infos
+                .get(contig.aligner.contig_idx.get())
+                .unwrap();
+            contig.aligner.set_free_jump_into(free_jump_into);
+            match &contig.seq {
+                ContigSeq::Packed(seq) => contig.aligner.fill_column(
+                    seq.as_ref(),
                     y,
                     contig.len(),
                     n,
@@ -342,22 +1334,197 @@ impl<'a, F: MatchFunc> MultiContigAligner<'a, F> {
                     prev,
                     curr,
                     jump_info,
-                );
+                    same_strand_jumps,
+                ),
+                ContigSeq::ReverseComplementOf(seq) => contig.aligner.fill_column(
+                    &seq.reverse_complement_view(),
+                    y,
+                    contig.len(),
+                    n,
+                    j,
+                    prev,
+                    curr,
+                    jump_info,
+                    same_strand_jumps,
+                ),
+                ContigSeq::Profile(profile) => contig.aligner.fill_column(
+                    profile.as_ref(),
+                    y,
+                    contig.len(),
+                    n,
+                    j,
+                    prev,
+                    curr,
+                    jump_info,
+                    same_strand_jumps,
+                ),
+            }
+        }
+        lap(&mut self.timings, Stage::ColumnFill, started);
+    }
+
+    fn align(
+        &mut self,
+        y: TextSlice<'_>,
+        start: Option<(usize, usize)>,
+        end: Option<(usize, usize)>,
+    ) -> Alignment {
+        let n = y.len();
+        self.timed_out = false;
+        stage_span!(DEBUG, "align", query_len = n, contigs = self.contigs.len());
+
+        // Evaluate the contigs in canonical order, and restore the order by contig index after
+        if self.canonical_contig_order {
+            self.contigs
+                .sort_by(|left, right| Self::canonical_order(left, right));
+        }
+
+        let (to_opposite_strand, max_contig_index) = self.opposite_strands();
+
+        let interval = self.traceback_checkpoint_interval;
+        self.checkpointed_query = interval.map(|interval| CheckpointedQuery {
+            y: y.to_vec(),
+            start,
+            interval,
+            contig_indexes: self
+                .contigs
+                .iter()
+                .map(|contig| contig.aligner.contig_idx.get())
+                .collect(),
+        });
+
+        // Set the initial conditions
+        // We are repeating some work, but that's okay!
+        let started = self.timings.map(|_| Instant::now());
+        {
+            stage_span!(DEBUG, "matrix_init");
+            for contig in &mut self.contigs {
+                contig.aligner.set_traceback_checkpoint_interval(interval);
+                contig
+                    .aligner
+                    .acquire_buffers(&mut self.pool, contig.len(), n);
+                contig.aligner.init_matrices(contig.len(), n);
+                if start.is_some() {
+                    let pos = Self::anchor_pos(start, contig.aligner.contig_idx);
+                    contig.aligner.init_anchored_start(contig.len(), pos);
+                }
+                if interval.is_some() {
+                    contig.aligner.checkpoint_column(contig.len(), 0);
+                }
             }
         }
+        lap(&mut self.timings, Stage::MatrixInit, started);
+        #[cfg(feature = "viz")]
+        if let Some(surfaces) = &mut self.score_surfaces {
+            *surfaces = self
+                .contigs
+                .iter()
+                .map(|contig| {
+                    ScoreSurface::new(
+                        contig.aligner.contig_idx.get(),
+                        &contig.name,
+                        contig.is_forward,
+                        contig.offset,
+                        contig.len() + 1,
+                        n + 1,
+                    )
+                })
+                .collect();
+            self.record_score_surfaces(0, 0);
+        }
+
+        for j in 1..=n {
+            self.fill_query_column(y, n, j, start, &to_opposite_strand, max_contig_index);
+            if interval.is_some_and(|interval| j.is_multiple_of(interval) && j < n) {
+                for contig in &mut self.contigs {
+                    contig.aligner.checkpoint_column(contig.len(), j);
+                }
+            }
+
+            #[cfg(feature = "viz")]
+            self.record_score_surfaces(j, j % 2);
 
+            if let Some(progress_callback) = &self.progress_callback {
+                progress_callback(j, n);
+            }
+
+            if self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                self.timed_out = true;
+                break;
+            }
+        }
+        if self.timed_out {
+            if self.canonical_contig_order {
+                self.contigs.sort_by_key(|contig| contig.aligner.contig_idx);
+            }
+            return Alignment::default();
+        }
+
+        let started = self.timings.map(|_| Instant::now());
         for contig in &mut self.contigs {
-            contig
-                .aligner
-                .fill_last_column_and_end_clipping(contig.len(), n);
+            if end.is_some() {
+                let pos = Self::anchor_pos(end, contig.aligner.contig_idx);
+                contig.aligner.anchor_end(contig.len(), n, pos);
+            } else {
+                contig
+                    .aligner
+                    .fill_last_column_and_end_clipping(contig.len(), n);
+            }
+            if interval.is_some() {
+                contig.aligner.save_final_column(contig.len(), n);
+            }
         }
+        lap(&mut self.timings, Stage::ColumnFill, started);
+        #[cfg(feature = "viz")]
+        self.record_score_surfaces(n, n % 2);
 
         let aligners = self
             .contigs
             .iter()
             .map(|contig| &contig.aligner)
             .collect_vec();
-        traceback(&aligners, n)
+        let contig_idx = best_alignment_end(&aligners, n);
+        let alignment = self.traceback_from_contig(n, contig_idx).unwrap();
+        if self.canonical_contig_order {
+            self.contigs.sort_by_key(|contig| contig.aligner.contig_idx);
+        }
+        #[cfg(feature = "viz")]
+        if let Some(surfaces) = &mut self.score_surfaces {
+            surfaces.sort_by_key(|surface| surface.contig_idx);
+        }
+        self.finish_alignment(alignment)
+    }
+
+    /// Converts the alignment from the coordinates of the aligned region of each contig to the
+    /// coordinates of the full contig (see [`Self::add_contig_region`]), and finds its segments
+    /// (see [`Alignment::find_segments`]).
+    fn finish_alignment(&self, alignment: Alignment) -> Alignment {
+        let contig_of = |contig_idx: usize| {
+            self.contigs
+                .iter()
+                .find(|c| c.aligner.contig_idx.get() == contig_idx)
+                .unwrap()
+        };
+        let mut alignment = if self
+            .contigs
+            .iter()
+            .all(|c| c.offset == 0 && c.len() == c.contig_len)
+        {
+            alignment
+        } else {
+            region_to_contig_coordinates(alignment, |contig_idx| {
+                let contig = contig_of(contig_idx);
+                (contig.offset, contig.len(), contig.contig_len)
+            })
+        };
+        alignment.segments = alignment.find_segments(|contig_idx| {
+            let contig = contig_of(contig_idx);
+            (contig.name.clone(), contig.is_forward)
+        });
+        alignment
     }
 
     pub fn traceback_all(
@@ -370,36 +1537,379 @@ impl<'a, F: MatchFunc> MultiContigAligner<'a, F> {
             _ => self
                 .contigs
                 .iter()
-                .map(|contig| contig.aligner.contig_idx as usize)
+                .map(|contig| contig.aligner.contig_idx.get())
                 .collect::<BitSet<_>>(),
         };
-        let aligners = self.contigs.iter().map(|c| &c.aligner).collect_vec();
-        traceback_all(&aligners, n, &contig_indexes_to_consider)
+        let ends = alignment_ends(&self.aligners_in_evaluation_order(), n);
+        traceback_all_from(
+            &ends,
+            &contig_indexes_to_consider,
+            EndTieBreak::default(),
+            |contig_idx| self.traceback_from_contig(n, contig_idx),
+        )
+        .into_iter()
+        .map(|alignment| self.finish_alignment(alignment))
+        .collect_vec()
     }
 
     pub fn traceback_from(&mut self, n: usize, contig_index: usize) -> Option<Alignment> {
+        self.traceback_from_contig(n, ContigIdx::new(contig_index))
+            .map(|alignment| self.finish_alignment(alignment))
+    }
+
+    /// Returns the traceback matrices of the most recent alignment, of a query of length `n`, to
+    /// the given contigs (or all contigs), with the metadata of the contigs needed to trace back
+    /// alignments from them later (see [`TracebackDump`]).  Fails if the traceback is
+    /// checkpointed (see [`Self::set_traceback_checkpoint_interval`]), since not every column of
+    /// the traceback is stored.
+    #[cfg(feature = "serde")]
+    pub fn dump_traceback(
+        &self,
+        n: usize,
+        contig_indexes: Option<&BitSet<u32>>,
+    ) -> anyhow::Result<TracebackDump> {
+        anyhow::ensure!(
+            self.checkpointed_query.is_none(),
+            "Cannot dump a checkpointed traceback"
+        );
+        let mut contigs = self
+            .contigs
+            .iter()
+            .filter(|contig| {
+                contig_indexes
+                    .is_none_or(|indexes| indexes.contains(contig.aligner.contig_idx.get()))
+            })
+            .collect_vec();
+        if self.canonical_contig_order {
+            contigs.sort_by(|left, right| Self::canonical_order(left, right));
+        }
+        let contigs = contigs
+            .into_iter()
+            .map(|contig| {
+                ContigTraceback::new(
+                    &contig.aligner,
+                    n,
+                    &contig.name,
+                    contig.is_forward,
+                    contig.offset..contig.offset + contig.len(),
+                    contig.contig_len,
+                )
+            })
+            .collect_vec();
+        Ok(TracebackDump::new(n, contigs))
+    }
+
+    /// Traces back the best alignment ending in the contig with the given index, in the
+    /// coordinates of the aligned regions, recomputing the columns of the traceback that are not
+    /// stored when checkpointing (see [`Self::set_traceback_checkpoint_interval`]).  The columns
+    /// recomputed are timed as filling columns, not tracing back.
+    fn traceback_from_contig(&mut self, n: usize, contig_idx: ContigIdx) -> Option<Alignment> {
+        stage_span!(DEBUG, "traceback", contig_idx = contig_idx.get());
+        let mut started = self.timings.map(|_| Instant::now());
+        let aligners = self
+            .contigs
+            .iter()
+            .map(|contig| &contig.aligner)
+            .collect_vec();
+        if self.checkpointed_query.is_none() {
+            let alignment = traceback_from(&aligners, n, contig_idx);
+            lap(&mut self.timings, Stage::Traceback, started);
+            return alignment;
+        }
+        let Some(mut cursor) = TracebackCursor::new(&aligners, n, contig_idx) else {
+            lap(&mut self.timings, Stage::Traceback, started);
+            return None;
+        };
+        loop {
+            let aligners = self
+                .contigs
+                .iter()
+                .map(|contig| &contig.aligner)
+                .collect_vec();
+            let step = cursor.resume(&aligners);
+            lap(&mut self.timings, Stage::Traceback, started);
+            match step {
+                TracebackStep::Done(alignment) => return alignment,
+                TracebackStep::NeedsColumn(j) => self.recompute_columns(j),
+            }
+            started = self.timings.map(|_| Instant::now());
+        }
+    }
+
+    /// Recomputes the window of columns of the traceback containing column `j`, from the
+    /// checkpoint before it, when checkpointing (see [`Self::set_traceback_checkpoint_interval`]).
+    /// The columns are filled as when aligning the query, for the same contigs in the same order,
+    /// then the state of the last column is restored.
+    fn recompute_columns(&mut self, j: usize) {
+        let query = self
+            .checkpointed_query
+            .take()
+            .expect("The traceback is not checkpointed");
+        let n = query.y.len();
+        let checkpoint = j.saturating_sub(1) / query.interval;
+        let window_start = checkpoint * query.interval;
+
+        // Fill the columns of only the contigs aligned to, in the order of evaluation, and restore
+        // the contigs and their order after.
+        let order = self
+            .contigs
+            .iter()
+            .map(|contig| contig.aligner.contig_idx)
+            .collect_vec();
+        let (aligned, excluded): (Vec<_>, Vec<_>) = std::mem::take(&mut self.contigs)
+            .into_iter()
+            .partition(|contig| {
+                query
+                    .contig_indexes
+                    .contains(contig.aligner.contig_idx.get())
+            });
+        self.contigs = aligned;
+        if self.canonical_contig_order {
+            self.contigs
+                .sort_by(|left, right| Self::canonical_order(left, right));
+        }
+        let (to_opposite_strand, max_contig_index) = self.opposite_strands();
+
+        for contig in &mut self.contigs {
+            contig.aligner.restore_checkpoint(checkpoint, window_start);
+        }
+        for column in window_start + 1..(window_start + query.interval + 1).min(n) {
+            self.fill_query_column(
+                &query.y,
+                n,
+                column,
+                query.start,
+                &to_opposite_strand,
+                max_contig_index,
+            );
+        }
+        for contig in &mut self.contigs {
+            contig.aligner.restore_final_column(n);
+        }
+
+        self.contigs.extend(excluded);
+        let max_contig_idx = order.iter().max().map_or(0, |idx| idx.get());
+        let mut positions: IndexMap<usize> = IndexMap::new(max_contig_idx);
+        for (position, contig_idx) in order.iter().enumerate() {
+            positions.put(contig_idx.get(), position);
+        }
+        self.contigs
+            .sort_by_key(|contig| positions.get(contig.aligner.contig_idx.get()));
+        self.checkpointed_query = Some(query);
+    }
+}
+
+/// Aligns a query that grows as its bases arrive, such as a read that is still being sequenced
+/// when deciding whether to keep sequencing it (e.g. "Read Until").  Adding bases fills the
+/// columns of the alignment matrices for just those bases, without recomputing the columns for
+/// the earlier bases, and the alignment of the query so far may be retrieved at any point.
+///
+/// The alignment of the query so far is the same as from [`MultiContigAligner::custom`], since
+/// only the last column of the matrices depends on the query ending there.  That column is filled
+/// when retrieving the alignment, and restored after, so that the matrices may be extended by
+/// more bases.  The progress callback is not called, and score surfaces are not recorded.
+pub struct StreamingAligner<'a, F: MatchFunc> {
+    aligner: &'a mut MultiContigAligner<F>,
+    y: Vec<u8>,
+    to_opposite_strand: IndexMap<usize>,
+    max_contig_index: usize,
+    /// The empty contigs, left out until the streaming aligner is dropped.
+    empty_contigs: Vec<ContigAligner<F>>,
+}
+
+impl<F: MatchFunc + Sync> StreamingAligner<'_, F> {
+    /// Adds the given bases to the end of the query.
+    pub fn extend(&mut self, bases: TextSlice<'_>) {
+        // The last column is only filled when retrieving the alignment
+        let start = self.y.len().max(1);
+        self.y.extend_from_slice(bases);
+        let n = self.y.len();
+        for contig in &mut self.aligner.contigs {
+            contig.aligner.extend_matrices(n);
+        }
+        // None of these columns is the last, so fill them as if the query were never ending
+        for j in start..n {
+            self.aligner.fill_query_column(
+                &self.y,
+                usize::MAX,
+                j,
+                None,
+                &self.to_opposite_strand,
+                self.max_contig_index,
+            );
+        }
+    }
+
+    /// Returns the alignment of the query so far, which is empty for an empty query or when
+    /// there are no contigs to align to (see [`MultiContigAligner::custom`]).
+    pub fn alignment(&mut self) -> Alignment {
+        let n = self.y.len();
+        if n == 0 || self.aligner.contigs.is_empty() {
+            return Alignment::empty(n);
+        }
+        let states = self
+            .aligner
+            .contigs
+            .iter()
+            .map(|contig| contig.aligner.save_last_column(contig.len(), n))
+            .collect_vec();
+        self.aligner.fill_query_column(
+            &self.y,
+            n,
+            n,
+            None,
+            &self.to_opposite_strand,
+            self.max_contig_index,
+        );
+        for contig in &mut self.aligner.contigs {
+            contig
+                .aligner
+                .fill_last_column_and_end_clipping(contig.len(), n);
+        }
         let aligners = self
+            .aligner
             .contigs
             .iter()
             .map(|contig| &contig.aligner)
             .collect_vec();
-        traceback_from(&aligners, n, contig_index as u32)
+        let alignment = traceback(&aligners, n);
+        for (contig, state) in self.aligner.contigs.iter_mut().zip(states) {
+            contig.aligner.restore_last_column(n, state);
+        }
+        self.aligner.finish_alignment(alignment)
+    }
+
+    /// The query so far.
+    pub fn query(&self) -> &[u8] {
+        &self.y
+    }
+
+    /// The length of the query so far.
+    pub fn len(&self) -> usize {
+        self.y.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.y.is_empty()
+    }
+}
+
+/// Whether the query of a [`StreamingAligner`] is known to align with at least a minimum score,
+/// whatever its remaining bases (see [`StreamingAligner::verdict`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The query aligns with at least the minimum score, whatever its remaining bases.
+    Accept,
+    /// The query can't align with the minimum score, even if all its remaining bases match.
+    Reject,
+    /// The query may or may not align with the minimum score, depending on its remaining bases.
+    Undecided,
+}
+
+impl<F: MatchFunc + Sync> StreamingAligner<'_, F> {
+    /// Returns whether the query is known to align with at least `min_score` once all its bases
+    /// have arrived, given that it has at most `max_len` bases in total, from the matrices filled
+    /// so far (namely, up to but excluding the last base so far).
+    ///
+    /// The query is accepted once an alignment of the bases so far that suffix clips the
+    /// remaining bases reaches the minimum score, so only when y suffix clipping is allowed.  The
+    /// query is rejected once the best score so far, plus the highest match score for each
+    /// remaining base, can't reach the minimum score.  This assumes the gap, jump, and clipping
+    /// scores are not positive.
+    pub fn verdict(&self, min_score: i32, max_len: usize) -> Verdict {
+        let j = self.y.len().saturating_sub(1);
+        let curr = j % 2;
+        let remaining = max_len.saturating_sub(j) as i64;
+        let mut lower = i64::from(MIN_SCORE);
+        let mut upper = i64::from(MIN_SCORE);
+        for contig in &self.aligner.contigs {
+            let aligner = &contig.aligner;
+            let m = contig.len();
+            let xclip_suffix = i64::from(aligner.scoring.xclip_suffix);
+            // An alignment that suffix clips y, then x unless it ends at the end of x
+            let suffix_clipped = aligner
+                .Sn
+                .iter()
+                .enumerate()
+                .map(|(i, score)| i64::from(*score) + if i == m { 0 } else { xclip_suffix });
+            lower = lower.max(suffix_clipped.max().unwrap());
+            let best = aligner.S[curr].iter().copied().max().unwrap();
+            let gain = i64::from(aligner.max_match_score().max(0));
+            let best_suffix_clipped = aligner.Sn.iter().copied().max().unwrap();
+            upper = upper
+                .max(i64::from(best) + remaining * gain)
+                .max(i64::from(best_suffix_clipped));
+        }
+        if lower >= i64::from(min_score) {
+            Verdict::Accept
+        } else if upper < i64::from(min_score) {
+            Verdict::Reject
+        } else {
+            Verdict::Undecided
+        }
+    }
+
+    /// Adds the given bases to the end of the query one at a time, stopping as soon as the query
+    /// is accepted or rejected (see [`Self::verdict`]), so that the bases after are neither added
+    /// nor aligned.  Returns the verdict after the last base added.
+    pub fn extend_until_decided(
+        &mut self,
+        bases: TextSlice<'_>,
+        min_score: i32,
+        max_len: usize,
+    ) -> Verdict {
+        let mut verdict = self.verdict(min_score, max_len);
+        for base in bases {
+            if verdict != Verdict::Undecided {
+                break;
+            }
+            self.extend(std::slice::from_ref(base));
+            verdict = self.verdict(min_score, max_len);
+        }
+        verdict
+    }
+}
+
+impl<F: MatchFunc> Drop for StreamingAligner<'_, F> {
+    fn drop(&mut self) {
+        let restore_empty_contigs = !self.empty_contigs.is_empty();
+        self.aligner.contigs.append(&mut self.empty_contigs);
+        if self.aligner.canonical_contig_order || restore_empty_contigs {
+            self.aligner
+                .contigs
+                .sort_by_key(|contig| contig.aligner.contig_idx);
+        }
     }
 }
 
 // Tests
 #[cfg(test)]
 pub mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
     use bio::alignment::pairwise::MatchParams;
+    use bit_set::BitSet;
     use itertools::Itertools;
     use rstest::rstest;
 
     use crate::{
-        align::{aligners::constants::MIN_SCORE, scoring::Scoring},
+        align::{
+            aligners::{constants::MIN_SCORE, shared_contigs::SharedContigs},
+            metrics::StageTimings,
+            profile::ScoreProfile,
+            scoring::{ContigScoring, Scoring},
+            TracebackLayout,
+        },
+        test_util::{
+            multi_contig_aligner, random_dna, random_scoring, random_target_seqs, Rng, SEED,
+        },
         util::dna::reverse_complement,
     };
 
-    use super::{Alignment, MultiContigAligner};
+    use super::{Alignment, ContigInfo, MultiContigAligner, Verdict};
 
     /// Upper-cases and remove display-related characters from a string.
     fn s(bases: &str) -> Vec<u8> {
@@ -446,46 +1956,433 @@ pub mod tests {
             .set_yclip(MIN_SCORE)
     }
 
-    fn scoring_global() -> Scoring<MatchParams> {
-        scoring_global_custom(-1, -5, -1, -10)
+    fn scoring_global() -> Scoring<MatchParams> {
+        scoring_global_custom(-1, -5, -1, -10)
+    }
+
+    fn scoring_local_custom(
+        mismatch_score: i32,
+        gap_open: i32,
+        gap_extend: i32,
+        jump_score: i32,
+    ) -> Scoring<MatchParams> {
+        let match_fn = MatchParams::new(1, mismatch_score);
+        Scoring::with_jump_score(gap_open, gap_extend, jump_score, match_fn)
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    /// Identical sequences, all matches
+    #[rstest]
+    fn test_identical() {
+        let x = s("ACGTAACC");
+        let x_revcomp = reverse_complement(&x);
+        let y = s("ACGTAACC");
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("fwd", true, &x, false, scoring_global());
+        aligner.add_contig("revcomp", false, &x_revcomp, false, scoring_global());
+        let alignment = aligner.custom(&y);
+        assert_alignment(&alignment, 0, 8, 0, 8, 8, 0, "8=", 8);
+    }
+
+    /// Identical sequences, all matches, reverse complemented
+    #[rstest]
+    fn test_identical_revcomp() {
+        let x = s("ACGTAACC");
+        let x_revcomp = reverse_complement(&x);
+        let y = reverse_complement(s("ACGTAACC"));
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("fwd", true, &x, false, scoring_global());
+        aligner.add_contig("revcomp", false, &x_revcomp, false, scoring_global());
+        let alignment = aligner.custom(&y);
+        assert_alignment(&alignment, 0, 8, 0, 8, 8, 1, "8=", 8);
+    }
+
+    /// Empty and single-base queries and contigs
+    #[rstest]
+    fn test_degenerate_inputs() {
+        let x = s("ACGTAACC");
+        // no contigs
+        let mut aligner = MultiContigAligner::<MatchParams>::new();
+        let alignment = aligner.custom(&x);
+        assert_eq!(alignment, Alignment::empty(8));
+        assert!(alignment.is_empty());
+        assert!(aligner.traceback_all(x.len(), None).is_empty());
+
+        // an empty query
+        aligner.add_contig("fwd", true, &x, false, scoring_global());
+        aligner.add_contig("empty", true, &[], false, scoring_global());
+        assert_eq!(aligner.custom(&[]), Alignment::empty(0));
+        assert!(aligner.traceback_all(0, None).is_empty());
+        assert!(aligner.traceback_from(0, 0).is_none());
+
+        // the empty contig is left out
+        let alignment = aligner.custom(&x);
+        assert_alignment(&alignment, 0, 8, 0, 8, 8, 0, "8=", 8);
+        assert!(!alignment.is_empty());
+        assert_eq!(
+            aligner.traceback_all(x.len(), None),
+            vec![alignment.clone()]
+        );
+
+        // the same when streaming, after which the empty contig is restored
+        let mut streaming = aligner.streaming();
+        assert_eq!(streaming.alignment(), Alignment::empty(0));
+        streaming.extend(&x);
+        assert_eq!(streaming.alignment(), alignment);
+        drop(streaming);
+        assert_eq!(aligner.len(), 2);
+        assert_eq!(aligner.contigs[1].name, "empty");
+
+        // only empty contigs
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("empty", true, &[], false, scoring_global());
+        assert_eq!(aligner.custom(&x), Alignment::empty(8));
+        let mut streaming = aligner.streaming();
+        streaming.extend(&x);
+        assert_eq!(streaming.alignment(), Alignment::empty(8));
+
+        // single bases
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("single", true, b"A", false, scoring_global());
+        assert_alignment(&aligner.custom(b"A"), 0, 1, 0, 1, 1, 0, "1=", 1);
+        assert_alignment(&aligner.custom(b"C"), 0, 1, 0, 1, -1, 0, "1X", 1);
+    }
+
+    /// Jumps over a scaffold gap of Ns in the contig are free
+    #[rstest]
+    #[case(None, 10)]
+    #[case(Some(5), 20)]
+    #[case(Some(10), 20)]
+    #[case(Some(11), 10)] // the run of Ns is too short to be a scaffold gap
+    fn test_scaffold_gaps(#[case] min_len: Option<usize>, #[case] score: i32) {
+        let x = s("ACGTAACCGT NNNNNNNNNN TTGCATGCAA");
+        let y = s("ACGTAACCGT TTGCATGCAA");
+        let mut aligner = MultiContigAligner::new();
+        aligner.set_scaffold_gap_min_len(min_len);
+        aligner.add_contig("fwd", true, &x, false, scoring_global());
+        let alignment = aligner.custom(&y);
+        assert_alignment(&alignment, 0, 30, 0, 20, score, 0, "10=10J10=", 20);
+
+        // also applies to the contigs added before
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("fwd", true, &x, false, scoring_global());
+        aligner.set_scaffold_gap_min_len(min_len);
+        assert_eq!(aligner.custom(&y).score, score);
+    }
+
+    #[rstest]
+    #[case(false, &["b", "a"], "b")] // ties are broken by the order the contigs were added
+    #[case(false, &["a", "b"], "a")]
+    #[case(true, &["b", "a"], "a")] // ties are broken by the names of the contigs
+    #[case(true, &["a", "b"], "a")]
+    fn test_canonical_contig_order(
+        #[case] canonical_contig_order: bool,
+        #[case] names: &[&str],
+        #[case] expected: &str,
+    ) {
+        // the query aligns equally well to either contig
+        let x = s("ACGTAACC");
+        let y = s("ACGTAACC");
+        let mut aligner = MultiContigAligner::new();
+        aligner.set_canonical_contig_order(canonical_contig_order);
+        for name in names {
+            aligner.add_contig(name, true, &x, false, scoring_global());
+        }
+        let alignment = aligner.custom(&y);
+        assert_alignment(
+            &alignment,
+            0,
+            8,
+            0,
+            8,
+            8,
+            alignment.start_contig_idx,
+            "8=",
+            8,
+        );
+        assert_eq!(names[alignment.start_contig_idx], expected);
+        // the contigs are restored to the order in which they were added
+        for (contig_idx, name) in names.iter().enumerate() {
+            assert_eq!(
+                aligner.contig_index_for_strand(true, name),
+                Some(contig_idx)
+            );
+        }
+
+        let alignments = aligner.traceback_all(y.len(), None);
+        assert_eq!(alignments.len(), 2);
+        assert_eq!(names[alignments[0].start_contig_idx], expected);
     }
 
-    fn scoring_local_custom(
-        mismatch_score: i32,
-        gap_open: i32,
-        gap_extend: i32,
-        jump_score: i32,
-    ) -> Scoring<MatchParams> {
-        let match_fn = MatchParams::new(1, mismatch_score);
-        Scoring::with_jump_score(gap_open, gap_extend, jump_score, match_fn)
-            .set_xclip(0)
-            .set_yclip(0)
+    #[rstest]
+    fn test_profile() {
+        let variants = [s("AACCGGTTACGT"), s("AACCGGTTACGT"), s("AACCGATTACGT")];
+        let variants = variants.iter().map(Vec::as_slice).collect_vec();
+        let profile = ScoreProfile::from_sequences(&variants, 1, -3).unwrap();
+        let scoring = scoring_global_custom(-3, -5, -1, -10);
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig_profile("chr1", true, &profile, false, scoring);
+        aligner.add_contig_profile("chr1", false, &profile.reverse_complement(), false, scoring);
+        aligner.add_contig("chr2", true, profile.consensus(), false, scoring);
+
+        // the minor variant is penalized less against the profile than against the consensus
+        let x = variants[2];
+        let alignment = aligner.custom_with_subset(x, Some(&BitSet::from_iter([0, 2])));
+        assert_alignment(&alignment, 0, 12, 0, 12, 11 - 2, 0, "5=1X6=", 12);
+        let alignment = aligner.custom_with_subset(x, Some(&BitSet::from_iter([2])));
+        assert_alignment(&alignment, 0, 12, 0, 12, 11 - 3, 2, "5=1X6=", 12);
+
+        // the major variant is the consensus
+        let alignment = aligner.custom_with_subset(variants[0], Some(&BitSet::from_iter([0])));
+        assert_alignment(&alignment, 0, 12, 0, 12, 11, 0, "12=", 12);
+
+        // the reverse strand is scored with the reverse complemented profile
+        let alignment =
+            aligner.custom_with_subset(&reverse_complement(x), Some(&BitSet::from_iter([1, 2])));
+        assert_alignment(&alignment, 0, 12, 0, 12, 11 - 2, 1, "6=1X5=", 12);
     }
 
-    /// Identical sequences, all matches
     #[rstest]
-    fn test_identical() {
+    fn test_progress_callback() {
         let x = s("ACGTAACC");
-        let x_revcomp = reverse_complement(&x);
         let y = s("ACGTAACC");
+        let columns = Arc::new(Mutex::new(Vec::new()));
         let mut aligner = MultiContigAligner::new();
         aligner.add_contig("fwd", true, &x, false, scoring_global());
-        aligner.add_contig("revcomp", false, &x_revcomp, false, scoring_global());
+        let callback_columns = Arc::clone(&columns);
+        aligner.set_progress_callback(Some(Arc::new(move |j, n| {
+            callback_columns.lock().unwrap().push((j, n));
+        })));
         let alignment = aligner.custom(&y);
         assert_alignment(&alignment, 0, 8, 0, 8, 8, 0, "8=", 8);
+        assert_eq!(
+            *columns.lock().unwrap(),
+            (1..=8).map(|j| (j, 8)).collect_vec()
+        );
+
+        // the callback is no longer invoked once removed
+        aligner.set_progress_callback(None);
+        aligner.custom(&y);
+        assert_eq!(columns.lock().unwrap().len(), 8);
     }
 
-    /// Identical sequences, all matches, reverse complemented
     #[rstest]
-    fn test_identical_revcomp() {
+    fn test_deadline() {
         let x = s("ACGTAACC");
-        let x_revcomp = reverse_complement(&x);
-        let y = reverse_complement(s("ACGTAACC"));
+        let y = s("ACGTAACC");
         let mut aligner = MultiContigAligner::new();
         aligner.add_contig("fwd", true, &x, false, scoring_global());
-        aligner.add_contig("revcomp", false, &x_revcomp, false, scoring_global());
+        aligner.add_contig("rev", false, &x, false, scoring_global());
+        assert_eq!(aligner.num_cells(y.len(), None), 2 * 9 * 9);
+        let subset: BitSet<u32> = [1].into_iter().collect();
+        assert_eq!(aligner.num_cells(y.len(), Some(&subset)), 9 * 9);
+
+        // the deadline has passed, so the query is not aligned
+        aligner.set_deadline(Some(Instant::now()));
         let alignment = aligner.custom(&y);
-        assert_alignment(&alignment, 0, 8, 0, 8, 8, 1, "8=", 8);
+        assert!(aligner.timed_out());
+        assert_eq!(alignment, Alignment::default());
+
+        // the query is aligned without a deadline
+        aligner.set_deadline(None);
+        let alignment = aligner.custom(&y);
+        assert!(!aligner.timed_out());
+        assert_alignment(&alignment, 0, 8, 0, 8, 8, 0, "8=", 8);
+    }
+
+    #[rstest]
+    fn test_record_timings() {
+        let x = s(&"ACGTAACC".repeat(20));
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("fwd", true, &x, false, scoring_global());
+        aligner.add_contig("rev", false, &x, false, scoring_global());
+        aligner.custom(&x);
+        assert_eq!(aligner.timings(), None);
+
+        // the timings are summed over the alignments until reset
+        aligner.set_record_timings(true);
+        aligner.custom(&x);
+        let timings = *aligner.timings().unwrap();
+        assert!(timings.column_fill > Duration::ZERO);
+        assert!(timings.jump_info > Duration::ZERO);
+        aligner.custom(&x);
+        assert!(aligner.timings().unwrap().total() > timings.total());
+        aligner.reset_timings();
+        assert_eq!(aligner.timings(), Some(&StageTimings::default()));
+
+        // the same when checkpointing the traceback
+        aligner.set_traceback_checkpoint_interval(Some(16));
+        let alignment = aligner.custom(&x);
+        assert_alignment(&alignment, 0, 160, 0, 160, 160, 0, "160=", 160);
+        assert!(aligner.timings().unwrap().traceback > Duration::ZERO);
+    }
+
+    #[rstest]
+    fn test_traceback_checkpoint_interval(#[values(1, 2, 3, 7, 64)] interval: usize) {
+        let mut rng = Rng::new(SEED);
+        for _ in 0..50 {
+            let target_seqs = random_target_seqs(&mut rng, 3, 40);
+            // A query of pieces of the targets, so that the alignments jump between them
+            let mut y = Vec::new();
+            for _ in 0..=rng.below(3) {
+                let fwd = &target_seqs[rng.below(target_seqs.len())].fwd;
+                let start = rng.below(fwd.len());
+                let end = start + rng.below(fwd.len() - start) + 1;
+                y.extend_from_slice(&fwd[start..end]);
+                let len = rng.below(4);
+                y.extend(random_dna(&mut rng, len));
+            }
+            let mut aligner = multi_contig_aligner(&target_seqs, random_scoring(&mut rng), true);
+            aligner.set_canonical_contig_order(rng.below(2) == 0);
+            if rng.below(2) == 0 {
+                aligner.set_adaptive_band_width(Some(rng.below(8) + 1));
+            }
+            let subset: BitSet<u32> = [0].into_iter().collect();
+
+            let expected = aligner.custom(&y);
+            let expected_all = aligner.traceback_all(y.len(), None);
+            let expected_subset = aligner.custom_with_subset(&y, Some(&subset));
+            let expected_anchored = aligner.custom_anchored(&y, Some((0, 0)), None);
+            aligner.set_traceback_checkpoint_interval(Some(interval));
+            assert_eq!(aligner.custom(&y), expected);
+            assert_eq!(aligner.traceback_all(y.len(), None), expected_all);
+            assert_eq!(
+                aligner.custom_with_subset(&y, Some(&subset)),
+                expected_subset
+            );
+            assert_eq!(
+                aligner.custom_anchored(&y, Some((0, 0)), None),
+                expected_anchored
+            );
+        }
+    }
+
+    #[rstest]
+    fn test_traceback_layout(#[values(None, Some(1), Some(7))] interval: Option<usize>) {
+        let mut rng = Rng::new(SEED);
+        for _ in 0..50 {
+            let target_seqs = random_target_seqs(&mut rng, 3, 40);
+            // A query of pieces of the targets, so that the alignments jump between them
+            let mut y = Vec::new();
+            for _ in 0..=rng.below(3) {
+                let fwd = &target_seqs[rng.below(target_seqs.len())].fwd;
+                let start = rng.below(fwd.len());
+                let end = start + rng.below(fwd.len() - start) + 1;
+                y.extend_from_slice(&fwd[start..end]);
+            }
+            let mut aligner = multi_contig_aligner(&target_seqs, random_scoring(&mut rng), true);
+            let expected = aligner.custom(&y);
+            let expected_all = aligner.traceback_all(y.len(), None);
+            let expected_streaming = {
+                let mut streaming = aligner.streaming();
+                for bases in y.chunks(3) {
+                    streaming.extend(bases);
+                }
+                streaming.alignment()
+            };
+
+            aligner.set_traceback_layout(TracebackLayout::ColumnMajor);
+            aligner.set_traceback_checkpoint_interval(interval);
+            assert_eq!(aligner.custom(&y), expected);
+            assert_eq!(aligner.traceback_all(y.len(), None), expected_all);
+            let mut streaming = aligner.streaming();
+            for bases in y.chunks(3) {
+                streaming.extend(bases);
+            }
+            assert_eq!(streaming.alignment(), expected_streaming);
+        }
+    }
+
+    #[rstest]
+    fn test_matrix_pool() {
+        let mut rng = Rng::new(SEED);
+        for _ in 0..50 {
+            let target_seqs = random_target_seqs(&mut rng, 3, 40);
+            let fwd = &target_seqs[rng.below(target_seqs.len())].fwd;
+            let mut y = fwd.clone();
+            y.extend(random_dna(&mut rng, 5));
+            let scoring = random_scoring(&mut rng);
+            let subset: BitSet<u32> = [0].into_iter().collect();
+            let expected = multi_contig_aligner(&target_seqs, scoring, true).custom(&y);
+            let expected_subset = multi_contig_aligner(&target_seqs, scoring, true)
+                .custom_with_subset(&y, Some(&subset));
+
+            let mut aligner = multi_contig_aligner(&target_seqs, scoring, true);
+            assert_eq!(aligner.custom(&y), expected);
+            // the buffers of the contigs left out are pooled, and those contigs have no traceback
+            assert_eq!(
+                aligner.custom_with_subset(&y, Some(&subset)),
+                expected_subset
+            );
+            assert!(!aligner.pool.is_empty());
+            assert!(aligner.traceback_from(y.len(), 1).is_none());
+            assert!(aligner
+                .traceback_all(y.len(), None)
+                .iter()
+                .all(|alignment| alignment.end_contig_idx == 0));
+            // then reused
+            assert_eq!(aligner.custom(&y), expected);
+            assert!(aligner.pool.is_empty());
+        }
+    }
+
+    #[rstest]
+    fn test_adaptive_band_with_jumps() {
+        let a = s("ACGTTGCAAGTCCGATAGCTTACGGATCCAGTCATGGACT");
+        let b = s("TTGACCATGGCAATCGGTACCTAGGCATTCGAGTCAAGCT");
+        // jumps to the middle of the other contig, then back
+        let y = [&a[0..16], &b[20..36], &a[24..40]].concat();
+        let mut aligner = MultiContigAligner::new();
+        for (name, seq) in [("a", &a), ("b", &b)] {
+            aligner.add_contig(
+                name,
+                true,
+                seq,
+                false,
+                scoring_local_custom(-4, -6, -2, -10),
+            );
+        }
+        let expected = aligner.custom(&y);
+        assert_eq!(expected.cigar(), "16=1C4J16=1c12j16=");
+        aligner.set_adaptive_band_width(Some(2));
+        assert_eq!(aligner.custom(&y), expected);
+    }
+
+    #[rstest]
+    fn test_parallel_block_rows_with_jumps() {
+        let a = s("ACGTTGCAAGTCCGATAGCTTACGGATCCAGTCATGGACT");
+        let b = s("TTGACCATGGCAATCGGTACCTAGGCATTCGAGTCAAGCT");
+        // jumps to the reverse complement of the middle of the other contig, with an insertion,
+        // then back
+        let y = [
+            &a[0..16],
+            &reverse_complement(&b[20..36]),
+            b"GATTACA",
+            &a[24..40],
+        ]
+        .concat();
+        let mut aligner = MultiContigAligner::new();
+        for (name, seq) in [("a", &a), ("b", &b)] {
+            for is_forward in [true, false] {
+                let seq = if is_forward {
+                    seq.clone()
+                } else {
+                    reverse_complement(seq)
+                };
+                aligner.add_contig(
+                    name,
+                    is_forward,
+                    &seq,
+                    false,
+                    scoring_local_custom(-4, -6, -2, -10),
+                );
+            }
+        }
+        let expected = aligner.custom(&y);
+        assert_eq!(expected.cigar(), "15=3C12j17=3c3j1=1X4=1X16=");
+        aligner.set_parallel_block_rows(Some(3));
+        assert_eq!(aligner.custom(&y), expected);
     }
 
     #[rstest]
@@ -641,7 +2538,7 @@ pub mod tests {
         // [1C5j] jumps to contig x4, moves 5 bases backwards (offset 0)
         // [16=] on x4 (bases 0-15), ends at offset 16
         let mut aligner = MultiContigAligner::new();
-        let xs = vec![x1, x2, x3, x4];
+        let xs = [x1, x2, x3, x4];
         for (i, x) in xs.iter().enumerate() {
             aligner.add_contig(
                 &format!("contig-{i}").to_string(),
@@ -698,41 +2595,559 @@ pub mod tests {
         // make these into test cases?
 
         // jump to the same contig and strand is prioritized
-        for contig in &mut aligner.contigs {
-            contig.aligner.scoring = contig.aligner.scoring.set_jump_scores(-1, -2, -2);
-        }
+        aligner.set_jump_scores(-1, -2, -2);
         let alignment = aligner.custom(&y1);
         assert_alignment(&alignment, 0, 15, 0, 10, 10 - 1, 0, "5=5J5=", 10);
 
         // jump to the same contig and opposite strand is prioritized
         // starts in the middle of x2, then jumps back to the start of x1
-        for contig in &mut aligner.contigs {
-            contig.aligner.scoring = contig.aligner.scoring.set_jump_scores(-2, -1, -2);
-        }
+        aligner.set_jump_scores(-2, -1, -2);
         let alignment = aligner.custom(&y1);
         assert_alignment(&alignment, 5, 15, 0, 10, 10 - 1, 1, "5A5=1c5j5=", 10);
 
         // jump to a different contig is prioritized
         // starts by aligning to x3 fully, then jumping to x1 and alinging to the last 5bp of x1
-        for contig in &mut aligner.contigs {
-            contig.aligner.scoring = contig.aligner.scoring.set_jump_scores(-2, -2, -1);
-        }
+        aligner.set_jump_scores(-2, -2, -1);
         let alignment = aligner.custom(&y1);
         assert_alignment(&alignment, 0, 15, 0, 10, 10 - 1, 2, "5=2c5J5=", 10);
 
         // jump to the same contig and strand is prioritized when the scores are the same
-        for contig in &mut aligner.contigs {
-            contig.aligner.scoring = contig.aligner.scoring.set_jump_scores(-1, -1, -1);
-        }
+        aligner.set_jump_scores(-1, -1, -1);
         let alignment = aligner.custom(&y1);
         assert_alignment(&alignment, 0, 15, 0, 10, 10 - 1, 0, "5=5J5=", 10);
 
         // jump to the same contig and opposite is prioritized when the scores are the same
         // starts in the middle of x2, then jumps back to the start of x1
-        for contig in &mut aligner.contigs {
-            contig.aligner.scoring = contig.aligner.scoring.set_jump_scores(-2, -1, -1);
-        }
+        aligner.set_jump_scores(-2, -1, -1);
         let alignment = aligner.custom(&y1);
         assert_alignment(&alignment, 5, 15, 0, 10, 10 - 1, 1, "5A5=1c5j5=", 10);
     }
+
+    #[rstest]
+    #[case("AAGGCCTT", "AACCGGTT")]
+    #[case("AAGGCCTTACGT", "ACGTAAGG")]
+    #[case("AAGGNCCTTACGT", "AGGTTCC")]
+    #[case("AAGGCCTTACGTTTGACA", "TGTCAAAGGCC")]
+    fn test_lazy_reverse_complement(#[case] x: &str, #[case] y: &str) {
+        let x = s(x);
+        let x_revcomp = reverse_complement(&x);
+        let y = s(y);
+        let scoring = scoring_local_custom(-1, -3, -1, -2);
+
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("chr1", true, &x, false, scoring);
+        aligner.add_contig("chr1", false, &x_revcomp, false, scoring);
+        let expected = aligner.custom(&y);
+
+        let mut aligner = MultiContigAligner::new();
+        aligner.set_lazy_reverse_complement(true);
+        aligner.add_contig("chr1", true, &x, false, scoring);
+        aligner.add_contig("chr1", false, &x_revcomp, false, scoring);
+        assert!(matches!(
+            aligner.contigs[1].seq,
+            super::ContigSeq::ReverseComplementOf(_)
+        ));
+        let actual = aligner.custom(&y);
+
+        assert_eq!(actual, expected);
+    }
+
+    /// Aligners built from the same shared contigs share their sequences, and align as if the
+    /// contigs were added one at a time
+    #[rstest]
+    fn test_add_shared_contigs(#[values(false, true)] lazy_reverse_complement: bool) {
+        let mut rng = Rng::new(SEED);
+        let target_seqs = random_target_seqs(&mut rng, 4, 50);
+        let scoring = scoring_local_custom(-1, -3, -1, -2);
+        let contigs = Arc::new(SharedContigs::new(
+            &target_seqs,
+            false,
+            true,
+            lazy_reverse_complement,
+        ));
+        assert_eq!(contigs.len(), target_seqs.len());
+        assert!(contigs
+            .names()
+            .eq(target_seqs.iter().map(|t| t.name.as_str())));
+
+        let mut expected_aligner = multi_contig_aligner(&target_seqs, scoring, true);
+        let build = || {
+            let contigs = contigs.clone();
+            std::thread::spawn(move || {
+                let mut aligner = MultiContigAligner::new();
+                aligner.add_shared_contigs(&contigs, |_| scoring);
+                aligner
+            })
+            .join()
+            .unwrap()
+        };
+        let (mut first, second) = (build(), build());
+        assert_eq!(first.len(), 2 * target_seqs.len());
+        for (a, b) in first.contigs.iter().zip(&second.contigs) {
+            assert_eq!(a.seq.to_vec(), b.seq.to_vec());
+            let shared = match (&a.seq, &b.seq) {
+                (super::ContigSeq::Packed(a), super::ContigSeq::Packed(b))
+                | (
+                    super::ContigSeq::ReverseComplementOf(a),
+                    super::ContigSeq::ReverseComplementOf(b),
+                ) => Arc::ptr_eq(a, b),
+                _ => false,
+            };
+            assert!(shared, "contig {} not shared", a.name);
+            assert_eq!(
+                !a.is_forward && lazy_reverse_complement,
+                matches!(a.seq, super::ContigSeq::ReverseComplementOf(_))
+            );
+        }
+        for _ in 0..10 {
+            let y = random_dna(&mut rng, 30);
+            assert_eq!(first.custom(&y), expected_aligner.custom(&y));
+        }
+    }
+
+    #[rstest]
+    #[case::global(scoring_global(), false)]
+    #[case::local(scoring_local_custom(-1, -3, -1, -2), false)]
+    #[case::local_canonical_order(scoring_local_custom(-1, -3, -1, -2), true)]
+    fn test_streaming(#[case] scoring: Scoring<MatchParams>, #[case] canonical_contig_order: bool) {
+        let x1 = s("AAGGCCTTACGTTTGACA");
+        let x2 = s("TTTTGGGGAACC");
+        let mut aligner = MultiContigAligner::new();
+        aligner.set_canonical_contig_order(canonical_contig_order);
+        aligner.add_contig("chr2", true, &x2, false, scoring);
+        aligner.add_contig("chr1", true, &x1, false, scoring);
+        aligner.add_contig("chr1", false, &reverse_complement(&x1), false, scoring);
+
+        // with jumps between contigs and strands, and with clipping when local
+        for y in ["TGTCAAAGGCC", "AAGGCCTTTTGGGGTGTCA", "GGCCTTACG", "A"] {
+            let y = s(y);
+            let expected = (0..=y.len())
+                .map(|len| aligner.custom(&y[..len]))
+                .collect_vec();
+
+            // add the bases one, two, then three at a time
+            let mut streaming = aligner.streaming();
+            assert_eq!(streaming.alignment(), expected[0]);
+            for (chunk, size) in (1..=3).cycle().scan(0, |start, size| {
+                let chunk = *start..(*start + size).min(y.len());
+                *start = chunk.end;
+                Some((chunk, size))
+            }) {
+                if chunk.is_empty() {
+                    break;
+                }
+                streaming.extend(&y[chunk.clone()]);
+                assert_eq!(streaming.len(), chunk.end, "chunk size {size}");
+                assert_eq!(streaming.alignment(), expected[chunk.end]);
+                // retrieving the alignment does not change the matrices
+                assert_eq!(streaming.alignment(), expected[chunk.end]);
+            }
+            assert_eq!(streaming.query(), y);
+            drop(streaming);
+
+            // the aligner may be used as before
+            assert_eq!(aligner.custom(&y), expected[y.len()]);
+            if canonical_contig_order {
+                assert_eq!(aligner.contigs[0].name, "chr2");
+            }
+        }
+    }
+
+    #[rstest]
+    fn test_streaming_verdict() {
+        let x = s("AAGGCCTTACGTTTGACA");
+        let scoring = scoring_local_custom(-1, -3, -1, -2);
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("chr1", true, &x, false, scoring);
+        aligner.add_contig("chr1", false, &reverse_complement(&x), false, scoring);
+
+        // Accepted once ten bases are aligned before the last base so far
+        let mut streaming = aligner.streaming();
+        assert_eq!(
+            streaming.extend_until_decided(&x, 10, x.len()),
+            Verdict::Accept
+        );
+        assert_eq!(streaming.len(), 11);
+        assert_eq!(streaming.alignment().score, 11);
+        drop(streaming);
+
+        // Rejected once the remaining bases can't make up for the mismatches
+        let y = s("GGGGGGGGGGGGGGGGGG");
+        let mut streaming = aligner.streaming();
+        assert_eq!(
+            streaming.extend_until_decided(&y, 10, y.len()),
+            Verdict::Reject
+        );
+        assert!(streaming.len() < y.len());
+        drop(streaming);
+
+        // The verdict for every prefix agrees with the alignment of the full query
+        for y in [
+            "AAGGCCTTACGTTTGACA",
+            "TGTCAAAGGCC",
+            "AAGGCCTTGGGGGGGGCC",
+            "GGCCTTACG",
+        ] {
+            let y = s(y);
+            let score = aligner.custom(&y).score;
+            for min_score in [0, 5, 9, 15] {
+                let mut streaming = aligner.streaming();
+                for len in 0..=y.len() {
+                    if len > 0 {
+                        streaming.extend(&y[len - 1..len]);
+                    }
+                    match streaming.verdict(min_score, y.len()) {
+                        Verdict::Accept => assert!(score >= min_score),
+                        Verdict::Reject => assert!(score < min_score),
+                        Verdict::Undecided => (),
+                    }
+                }
+            }
+        }
+
+        // Never accepted early without suffix clipping of the query
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("chr1", true, &x, false, scoring_global());
+        let mut streaming = aligner.streaming();
+        assert_eq!(
+            streaming.extend_until_decided(&x, 10, x.len()),
+            Verdict::Undecided
+        );
+        assert_eq!(streaming.len(), x.len());
+    }
+
+    #[rstest]
+    #[case(None, None, 5, 0, 10, "5A10=5A")]
+    #[case(Some((0, 5)), Some((0, 15)), 5, 0, 10, "5A10=5A")]
+    #[case(Some((0, 4)), None, 4, 0, 10 - 4, "4A1I10=5A")]
+    #[case(None, Some((0, 16)), 6, 0, 8 - 2, "6A4=1X4=1X4A")]
+    #[case(Some((1, 0)), None, 0, 1, 9 - 1 - 2, "1X1c5J9=5A")]
+    #[case(Some((0, 0)), Some((0, 20)), 0, 0, 8 - 2 - 4, "1X5J8=5J1X")]
+    fn test_custom_anchored(
+        #[case] start: Option<(usize, usize)>,
+        #[case] end: Option<(usize, usize)>,
+        #[case] xstart: usize,
+        #[case] start_contig_idx: usize,
+        #[case] score: i32,
+        #[case] cigar: &str,
+    ) {
+        let x1 = s("AAAAACCCCCGGGGGTTTTT");
+        let x2 = s("TGCATGCATGACGTAGCTAC");
+        let y = s("CCCCCGGGGG");
+        let scoring = scoring_local_custom(-1, -3, -1, -2);
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("x1", true, &x1, false, scoring);
+        aligner.add_contig("x2", true, &x2, false, scoring);
+        let alignment = aligner.custom_anchored(&y, start, end);
+        assert_eq!(alignment.cigar(), cigar, "{alignment}");
+        assert_eq!(alignment.score, score, "{alignment}");
+        assert_eq!(alignment.xstart, xstart, "{alignment}");
+        assert_eq!(alignment.start_contig_idx, start_contig_idx, "{alignment}");
+        if let Some((contig_idx, pos)) = end {
+            assert_eq!(alignment.end_contig_idx, contig_idx, "{alignment}");
+            assert_eq!(alignment.xend, pos, "{alignment}");
+        }
+
+        // the clipping penalties are restored
+        let alignment = aligner.custom(&y);
+        assert_eq!(alignment.cigar(), "5A10=5A");
+    }
+
+    #[rstest]
+    fn test_custom_anchored_across_contigs() {
+        let x1 = s("AAAAACCCCCGGGGGTTTTT");
+        let x2 = s("TGCATGCATGACGTAGCTAC");
+        let y = s("CCCCCGGGGGATGACGTAGC");
+        let scoring = scoring_local_custom(-1, -3, -1, -2);
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("x1", true, &x1, false, scoring);
+        aligner.add_contig("x2", true, &x2, false, scoring);
+
+        let alignment = aligner.custom_anchored(&y, Some((0, 5)), Some((1, 18)));
+        assert_alignment(
+            &alignment,
+            5,
+            18,
+            0,
+            20,
+            20 - 2 - 4,
+            0,
+            "5A10=1C13j10=1I2A",
+            21,
+        );
+        assert_eq!(alignment.end_contig_idx, 1);
+    }
+
+    #[rstest]
+    #[should_panic(expected = "Anchored to an unknown contig index: 2")]
+    fn test_custom_anchored_unknown_contig() {
+        let x = s("AAAAACCCCCGGGGGTTTTT");
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("x", true, &x, false, scoring_global());
+        aligner.custom_anchored(&x, Some((2, 0)), None);
+    }
+
+    #[rstest]
+    #[case(12, 28, 12..28)] // the region is exactly the aligned bases
+    #[case(12, 28, 10..30)] // within the region
+    #[case(12, 28, 12..40)] // region extends to the end of the contig
+    #[case(12, 28, 0..28)] // region starts at the start of the contig
+    fn test_add_contig_region(
+        #[case] ystart: usize,
+        #[case] yend: usize,
+        #[case] region: std::ops::Range<usize>,
+    ) {
+        let x = s("TGCATGCATGACGTAGCTACAAAAACCCCCGGGGGTTTTT");
+        let y = x[ystart..yend].to_vec();
+        let scoring = scoring_local_custom(-1, -3, -1, -2);
+
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("chr1", true, &x, false, scoring);
+        let expected = aligner.custom(&y);
+
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig_region("chr1", true, &x, region, false, scoring);
+        let actual = aligner.custom(&y);
+        assert_eq!(actual, expected);
+        assert_alignment(
+            &actual,
+            ystart,
+            yend,
+            0,
+            yend - ystart,
+            16,
+            0,
+            "12A16=12A",
+            16,
+        );
+        assert_eq!(actual.xlen, x.len());
+    }
+
+    #[rstest]
+    fn test_add_contig_region_with_jumps() {
+        let x1 = s("GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT");
+        let x2 = s("TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT");
+        let y = [&x1[10..20], &x2[25..35], &x1[5..15]].concat();
+        let scoring = scoring_local_custom(-1, -3, -1, -2);
+
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("chr1", true, &x1, false, scoring);
+        aligner.add_contig("chr2", true, &x2, false, scoring);
+        let expected = aligner.custom(&y);
+        assert_eq!(expected.cigar(), "10A10=1C5j9=1c30j11=25A");
+
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig_region("chr1", true, &x1, 3..22, false, scoring);
+        aligner.add_contig_region("chr2", true, &x2, 20..38, false, scoring);
+        let actual = aligner.custom(&y);
+        assert_eq!(actual, expected);
+
+        assert_eq!(aligner.traceback_from(y.len(), 0).unwrap(), expected);
+        assert_eq!(aligner.traceback_all(y.len(), None)[0], expected);
+
+        // anchored in the coordinates of the full contig
+        let actual = aligner.custom_anchored(&y, Some((0, 10)), Some((0, 15)));
+        assert_eq!(actual, expected);
+    }
+
+    #[rstest]
+    fn test_contig_lookup() {
+        let x = s("TGCATGCATGACGTAGCTACAAAAACCCCCGGGGGTTTTT");
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("chr1", true, &x, true, scoring_global());
+        aligner.add_contig_region("chr2", false, &x, 10..30, false, scoring_global());
+        assert_eq!(aligner.contig_name(1), "chr2");
+        assert!(aligner.contig_is_forward(0));
+        assert!(!aligner.contig_is_forward(1));
+        assert_eq!(aligner.contig_len(1), 40);
+
+        let contigs = aligner.iter_contigs().collect_vec();
+        assert_eq!(contigs.len(), 2);
+        assert_eq!(
+            contigs[1],
+            ContigInfo {
+                contig_idx: 1,
+                name: "chr2",
+                is_forward: false,
+                len: 40,
+                region: 10..30,
+                circular: false,
+            }
+        );
+        assert!(contigs[0].circular);
+        assert_eq!(contigs[0].region, 0..40);
+    }
+
+    /// The segments of an alignment between jumps, in the coordinates of the full contigs
+    #[rstest]
+    fn test_segments() {
+        let x1 = s("GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT");
+        let x2 = s("TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT");
+        let x2_revcomp = reverse_complement(&x2);
+        let y = [&x1[10..20], &x2_revcomp[5..15]].concat();
+        let scoring = scoring_local_custom(-1, -3, -1, -2);
+
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig_region("chr1", true, &x1, 3..22, false, scoring);
+        aligner.add_contig("chr2", true, &x2, false, scoring);
+        aligner.add_contig_region("chr2", false, &x2_revcomp, 2..30, false, scoring);
+        let alignment = aligner.custom(&y);
+        let segments = &alignment.segments;
+        assert_eq!(segments.len(), 2, "{alignment}");
+        let (first, second) = (&segments[0], &segments[1]);
+        assert_eq!(
+            (
+                first.contig_idx,
+                first.contig_name.as_str(),
+                first.is_forward
+            ),
+            (0, "chr1", true)
+        );
+        assert_eq!(
+            (
+                second.contig_idx,
+                second.contig_name.as_str(),
+                second.is_forward
+            ),
+            (2, "chr2", false)
+        );
+        // the jump may be placed anywhere in a micro-homology, but the segments are contiguous
+        assert_eq!((first.contig_start, first.query_start), (10, 0));
+        assert_eq!(first.query_end, second.query_start);
+        assert_eq!(first.contig_end - first.contig_start, first.query_end);
+        assert_eq!((second.contig_end, second.query_end), (15, 20));
+        assert_eq!(
+            second.contig_end - second.contig_start,
+            20 - second.query_start
+        );
+
+        assert_eq!(aligner.traceback_from(y.len(), 2).unwrap(), alignment);
+    }
+
+    #[rstest]
+    fn test_add_contig_region_lazy_reverse_complement() {
+        let x = s("TGCATGCATGACGTAGCTACAAAAACCCCCGGGGGTTTTT");
+        let x_revcomp = reverse_complement(&x);
+        let y = reverse_complement(&x[12..28]);
+        let scoring = scoring_local_custom(-1, -3, -1, -2);
+
+        let mut aligner = MultiContigAligner::new();
+        aligner.set_lazy_reverse_complement(true);
+        aligner.add_contig_region("chr1", true, &x, 10..30, false, scoring);
+        aligner.add_contig_region("chr1", false, &x_revcomp, 10..30, false, scoring);
+        assert!(matches!(
+            aligner.contigs[1].seq,
+            super::ContigSeq::ReverseComplementOf(_)
+        ));
+        let alignment = aligner.custom(&y);
+        assert_alignment(&alignment, 12, 28, 0, 16, 16, 1, "12A16=12A", 16);
+
+        // the region on the reverse strand differs from the forward strand
+        let mut aligner = MultiContigAligner::new();
+        aligner.set_lazy_reverse_complement(true);
+        aligner.add_contig_region("chr1", true, &x, 10..30, false, scoring);
+        aligner.add_contig_region("chr1", false, &x_revcomp, 5..35, false, scoring);
+        assert!(matches!(
+            aligner.contigs[1].seq,
+            super::ContigSeq::Packed(_)
+        ));
+        let alignment = aligner.custom(&y);
+        assert_alignment(&alignment, 12, 28, 0, 16, 16, 1, "12A16=12A", 16);
+    }
+
+    #[rstest]
+    #[should_panic(expected = "Region 10..41 out of bounds for contig x with length 40")]
+    fn test_add_contig_region_out_of_bounds() {
+        let x = s("GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT");
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig_region("x", true, &x, 10..41, false, scoring_global());
+    }
+
+    #[rstest]
+    #[should_panic(expected = "A circular contig must be added in full: x")]
+    fn test_add_contig_region_circular() {
+        let x = s("GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT");
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig_region("x", true, &x, 10..30, true, scoring_global());
+    }
+
+    #[rstest]
+    fn test_add_contig_with_contig_scoring() {
+        let x1 = s("GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT");
+        let x2 = s("TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT");
+        let global = scoring_global().global_scoring();
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("x1", true, &x1, false, scoring_global());
+        // a different match function and gap scores for the second contig
+        let contig = ContigScoring::new(-2, -2, MatchParams::new(2, -3));
+        aligner.add_contig("x2", true, &x2, false, Scoring::from_parts(contig, global));
+        assert_eq!(aligner.global_scoring(), Some(&global));
+        let alignment = aligner.custom(&x2);
+        assert_eq!(alignment.score, 80);
+    }
+
+    #[rstest]
+    #[should_panic(
+        expected = "Scoring of contig x2 differs from the other contigs: Incompatible global \
+                    scoring: jump_score_same_contig_and_strand (-10 vs -5)"
+    )]
+    fn test_add_contig_incompatible_scoring() {
+        let x = s("GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT");
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("x1", true, &x, false, scoring_global());
+        let scoring = scoring_global().set_jump_scores(-5, -10, -10);
+        aligner.add_contig("x2", true, &x, false, scoring);
+    }
+
+    #[rstest]
+    fn test_set_scoring() {
+        let x1 = s("AAAAAAAAAACCCCCCCCCC");
+        let x2 = s("GGGGGGGGGGTTTTTTTTTT");
+        let y = s("AAAAAAAAAATTTTTTTTTT");
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig(
+            "x1",
+            true,
+            &x1,
+            false,
+            scoring_local_custom(-4, -6, -2, -10),
+        );
+        aligner.add_contig(
+            "x2",
+            true,
+            &x2,
+            false,
+            scoring_local_custom(-4, -6, -2, -10),
+        );
+        assert_eq!(aligner.custom(&y).score, 10);
+
+        // a cheaper jump makes the chimeric alignment best
+        aligner.set_jump_scores(-2, -2, -2);
+        assert_eq!(
+            aligner.global_scoring().unwrap().jump_score_inter_contig,
+            -2
+        );
+        let alignment = aligner.custom(&y);
+        assert_eq!(
+            (
+                alignment.score,
+                alignment.start_contig_idx,
+                alignment.end_contig_idx
+            ),
+            (18, 0, 1)
+        );
+
+        // without clipping nor jumps, the second half of the query is mismatched
+        aligner.set_clip_penalties(MIN_SCORE, MIN_SCORE, MIN_SCORE, MIN_SCORE);
+        aligner.set_jump_scores(-100, -100, -100);
+        assert_eq!(aligner.custom(&y).score, -30);
+
+        // cheaper gaps for all contigs, so the second half is inserted and deleted instead
+        aligner.set_gap_penalties(None, -1, -1);
+        assert_eq!(aligner.custom(&y).score, -12);
+
+        // free gaps for just one contig
+        aligner.set_gap_penalties(Some("x1"), 0, 0);
+        assert_eq!(aligner.custom(&y).score, 10);
+    }
 }
@@ -0,0 +1,109 @@
+//! A pool of the buffers of the alignment matrices, shared by the aligners of the contigs of a
+//! [`super::multi_contig_aligner::MultiContigAligner`], so that the buffers of the contigs left
+//! out of an alignment are reused by the contigs aligned to, rather than each contig keeping
+//! buffers for the largest query it was ever aligned to.
+
+use crate::align::traceback::Cell;
+
+/// The buffers of the traceback cells and the columns of scores released by the aligners of the
+/// contigs (see [`super::single_contig_aligner::SingleContigAligner::release_buffers`]).
+#[derive(Default, Debug, Clone)]
+pub struct MatrixPool {
+    cells: Vec<Vec<Cell>>,
+    scores: Vec<Vec<i32>>,
+}
+
+/// Removes and returns the smallest of the buffers with at least the given capacity, otherwise
+/// the largest buffer, or an empty buffer if there are none.
+fn take_fitting<T>(buffers: &mut Vec<Vec<T>>, len: usize) -> Vec<T> {
+    let fitting = buffers
+        .iter()
+        .enumerate()
+        .filter(|(_, buffer)| buffer.capacity() >= len)
+        .min_by_key(|(_, buffer)| buffer.capacity())
+        .map(|(index, _)| index);
+    let index = fitting.or_else(|| {
+        buffers
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, buffer)| buffer.capacity())
+            .map(|(index, _)| index)
+    });
+    index.map_or_else(Vec::new, |index| buffers.swap_remove(index))
+}
+
+impl MatrixPool {
+    /// Adds the buffer of traceback cells to the pool, unless it has no storage.
+    pub fn put_cells(&mut self, mut cells: Vec<Cell>) {
+        if cells.capacity() > 0 {
+            cells.clear();
+            self.cells.push(cells);
+        }
+    }
+
+    /// Adds the buffer of scores to the pool, unless it has no storage.
+    pub fn put_scores(&mut self, mut scores: Vec<i32>) {
+        if scores.capacity() > 0 {
+            scores.clear();
+            self.scores.push(scores);
+        }
+    }
+
+    /// Takes the buffer of traceback cells best fitting `len` cells from the pool, which is empty
+    /// if the pool has none.
+    pub fn take_cells(&mut self, len: usize) -> Vec<Cell> {
+        take_fitting(&mut self.cells, len)
+    }
+
+    /// Takes the buffer of scores best fitting `len` scores from the pool, which is empty if the
+    /// pool has none.
+    pub fn take_scores(&mut self, len: usize) -> Vec<i32> {
+        take_fitting(&mut self.scores, len)
+    }
+
+    /// The number of buffers in the pool.
+    pub fn len(&self) -> usize {
+        self.cells.len() + self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of bytes of storage of the buffers in the pool.
+    pub fn capacity_bytes(&self) -> usize {
+        let cells: usize = self.cells.iter().map(Vec::capacity).sum();
+        let scores: usize = self.scores.iter().map(Vec::capacity).sum();
+        cells * std::mem::size_of::<Cell>() + scores * std::mem::size_of::<i32>()
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::MatrixPool;
+
+    #[rstest]
+    fn test_take_fitting() {
+        let mut pool = MatrixPool::default();
+        pool.put_scores(Vec::new());
+        assert!(pool.is_empty());
+        for capacity in [10, 100, 50] {
+            pool.put_scores(vec![0; capacity]);
+        }
+        assert_eq!(pool.len(), 3);
+        assert_eq!(pool.capacity_bytes(), 160 * std::mem::size_of::<i32>());
+
+        // the smallest that fits, and cleared
+        let scores = pool.take_scores(20);
+        assert_eq!(scores.capacity(), 50);
+        assert!(scores.is_empty());
+        // the largest if none fits
+        assert_eq!(pool.take_scores(1000).capacity(), 100);
+        assert_eq!(pool.take_scores(1).capacity(), 10);
+        assert_eq!(pool.take_scores(1).capacity(), 0);
+        assert!(pool.take_cells(1).is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+//! The packed sequences of the contigs to align to, built once and shared read-only, e.g. in an
+//! [`Arc`], by the aligners of many worker threads (see
+//! [`super::multi_contig_aligner::MultiContigAligner::add_shared_contigs`]), so that each thread
+//! keeps only its own alignment matrices rather than its own copy of the contigs.
+
+use std::sync::Arc;
+
+use crate::util::{dna::PackedSeq, target_seq::TargetSeq};
+
+/// One contig of a [`SharedContigs`].
+#[derive(Debug, Clone)]
+pub(crate) struct SharedContig {
+    pub name: String,
+    pub circular: bool,
+    pub fwd: Arc<PackedSeq>,
+    /// The reverse complement of the contig, or `None` if the reverse strand is not aligned to or
+    /// is derived lazily from the forward strand.
+    pub revcomp: Option<Arc<PackedSeq>>,
+}
+
+/// The contigs to align to, on the forward strand and optionally the reverse strand, stored
+/// packed.  Cloning shares the sequences rather than copying them.
+#[derive(Debug, Clone)]
+pub struct SharedContigs {
+    contigs: Vec<SharedContig>,
+    double_strand: bool,
+}
+
+impl SharedContigs {
+    /// Packs the given contigs, and their reverse complement if `double_strand` is true, unless
+    /// `lazy_reverse_complement` is true, in which case the reverse strand is derived on access
+    /// from the forward strand (see
+    /// [`super::multi_contig_aligner::MultiContigAligner::set_lazy_reverse_complement`]).
+    pub fn new(
+        target_seqs: &[TargetSeq],
+        circular: bool,
+        double_strand: bool,
+        lazy_reverse_complement: bool,
+    ) -> Self {
+        let contigs = target_seqs
+            .iter()
+            .map(|target_seq| {
+                let fwd = Arc::new(PackedSeq::new(&target_seq.fwd));
+                let revcomp = (double_strand && !lazy_reverse_complement)
+                    .then(|| Arc::new(PackedSeq::new(&target_seq.revcomp)));
+                SharedContig {
+                    name: target_seq.name.clone(),
+                    circular,
+                    fwd,
+                    revcomp,
+                }
+            })
+            .collect();
+        Self {
+            contigs,
+            double_strand,
+        }
+    }
+
+    /// The number of contigs, not counting strands.
+    pub fn len(&self) -> usize {
+        self.contigs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contigs.is_empty()
+    }
+
+    /// True if the reverse strand of the contigs is aligned to.
+    pub fn double_strand(&self) -> bool {
+        self.double_strand
+    }
+
+    /// The names of the contigs, in the order in which they were given.
+    pub fn names(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.contigs.iter().map(|contig| contig.name.as_str())
+    }
+
+    pub(crate) fn contigs(&self) -> &[SharedContig] {
+        &self.contigs
+    }
+}
@@ -5,23 +5,43 @@
 // This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{cmp::max, i32, iter::repeat};
+use std::{
+    cmp::max,
+    iter::repeat_n,
+    ops::{Index, Range},
+    sync::Arc,
+};
+
+use itertools::Itertools;
 
-use crate::align::{aligners::constants::AlignmentMode, scoring::Scoring, traceback::TB_XJUMP};
+use crate::align::{
+    aligners::constants::AlignmentMode,
+    position::{ContigIdx, ContigPos},
+    profile::ScoreProfile,
+    scoring::Scoring,
+    splice::{acceptor, donor, motif_splice_sites, MIN_INTRON_LEN, SPLICE_KINDS},
+    traceback::TB_XJUMP,
+};
+use crate::util::dna::{homopolymer_run_lengths, n_run_lengths};
 use bio::{
     alignment::pairwise::{MatchFunc, MatchParams},
     utils::TextSlice,
 };
 
 use crate::align::{
-    aligners::constants::{AlignmentOperation, DEFAULT_ALIGNER_CAPACITY},
+    aligners::constants::{AlignerBackend, AlignmentOperation, DEFAULT_ALIGNER_CAPACITY},
     alignment::Alignment,
 };
 
-use super::{constants::MIN_SCORE, JumpInfo};
+use super::{
+    constants::{floor_score, gap_score, MIN_PENALTY, MIN_SCORE},
+    pool::MatrixPool,
+    wavefront::wavefront_global,
+    JumpInfo,
+};
 use crate::align::traceback::{
-    traceback, Cell, Traceback, TracebackCell, TB_DEL, TB_INS, TB_MATCH, TB_START, TB_SUBST,
-    TB_XCLIP_PREFIX, TB_XCLIP_SUFFIX, TB_YCLIP_PREFIX, TB_YCLIP_SUFFIX,
+    traceback, Cell, SValue, Traceback, TracebackCell, TracebackLayout, TB_DEL, TB_INS, TB_MATCH,
+    TB_START, TB_SUBST, TB_XCLIP_PREFIX, TB_XCLIP_SUFFIX, TB_YCLIP_PREFIX, TB_YCLIP_SUFFIX,
 };
 
 /// A generalized Smith-Waterman aligner, allowing for the alignment to jump forward
@@ -59,14 +79,55 @@ use crate::align::traceback::{
 /// `Lx` is the optimal x suffix clipping lengths from each position of the
 /// sequence y
 ///
-/// `Ly` is the optimal y suffix clipping lengths from each position of the
-/// sequence x
+/// `Ly` is the position in y (column) at which the optimal y suffix clipping
+/// starts from each position of the sequence x
 ///
 /// `Sn` is the last column of the matrix. This is needed to keep track of
 /// suffix clipping scores
 ///
+/// `homopolymer_runs` is the length of the homopolymer run containing each base of x,
+/// when gaps next to homopolymers are scored differently (see [`Scoring`])
+///
+/// `profile` is the position-specific scores for aligning to each base of x, used in place of the
+/// match function of the scoring when set (see [`ScoreProfile`])
+///
+/// `adaptive_band_width` is the number of rows on either side of the best scoring row of the
+/// previous column to fill in each column, when banding (see
+/// [`SingleContigAligner::set_adaptive_band_width`]), with `band` tracking that row
+///
+/// `parallel_block_rows` is the number of rows in each block of rows of a column to fill in
+/// parallel, for long contigs (see [`SingleContigAligner::set_parallel_block_rows`])
+///
+/// `forbid_clip_jumps` is whether every jump must be between aligned bases of x, rather than into
+/// or out of a clipped region of x (see [`SingleContigAligner::set_forbid_clip_jumps`])
+///
+/// `mask_diagonal` is whether each base of x may not be aligned to the base of y at the same
+/// position, when aligning a sequence to itself (see [`SingleContigAligner::set_mask_diagonal`])
+///
+/// `jump_site_scores` is the score added to a jump from or to each position of x, or empty if
+/// jumps may be from or to any position (see [`SingleContigAligner::set_jump_site_scores`])
+///
+/// `free_jump_sites` is whether jumps from or to each position of x are free, or empty if there
+/// are no such positions, with `free_jump_into` the best jump into the current column from any
+/// contig, scored without the jump score (see [`SingleContigAligner::set_free_jump_sites`])
+///
+/// `splice_motifs` and `annotated_splice_sites` are the splice sites at each position of x, from
+/// the intron motifs and the annotated introns respectively, when jumps over introns are scored
+/// differently (see [`SingleContigAligner::set_splice_motifs`])
+///
+/// `scaffold_gap_lens` is the length of the scaffold gap (a run of Ns of unknown length) ending
+/// before each position of x, or empty if jumps over scaffold gaps are not free (see
+/// [`SingleContigAligner::set_scaffold_gaps`])
+///
+/// `Jb` is the best backward jump (score and x-index) into each position of the
+/// current column, when jumps are scored by direction (see [`Scoring`])
+///
 /// `traceback` - see [`bio::alignment::pairwise::TracebackCell`](struct.TracebackCell.html)
 ///
+/// `checkpoints` is the state of each checkpoint column when the traceback is checkpointed, and
+/// `final_column` the state of the last column, from which the columns that are not stored are
+/// recomputed (see [`SingleContigAligner::set_traceback_checkpoint_interval`])
+///
 /// `scoring` - see [`bio::alignment::pairwise::Scoring`](struct.Scoring.html)
 #[allow(non_snake_case)]
 pub struct SingleContigAligner<F: MatchFunc> {
@@ -76,10 +137,126 @@ pub struct SingleContigAligner<F: MatchFunc> {
     pub Lx: Vec<usize>,
     pub Ly: Vec<usize>,
     pub Sn: Vec<i32>,
+    pub Jb: Vec<(i32, ContigPos)>,
+    pub homopolymer_runs: Vec<u8>,
+    pub profile: Option<Arc<ScoreProfile>>,
+    pub adaptive_band_width: Option<usize>,
+    pub band: AdaptiveBand,
+    pub parallel_block_rows: Option<usize>,
+    pub span_query: bool,
+    pub forbid_clip_jumps: bool,
+    pub mask_diagonal: bool,
+    pub jump_site_scores: Vec<i32>,
+    pub free_jump_sites: Vec<bool>,
+    pub free_jump_into: JumpInfo,
+    pub splice_motifs: Vec<u8>,
+    pub annotated_splice_sites: Vec<u8>,
+    pub scaffold_gap_lens: Vec<u32>,
     pub traceback: Traceback,
+    pub checkpoints: Vec<LastColumnState>,
+    pub final_column: Option<LastColumnState>,
     pub scoring: Scoring<F>,
-    pub contig_idx: u32,
+    pub contig_idx: ContigIdx,
     pub circular: bool,
+    pub backend: AlignerBackend,
+}
+
+/// The best forward jumps within the same contig and strand into a row of a column, from the
+/// rows before it: any forward jump, the spliced jumps from a donor of each kind of splice
+/// sites (see [`Scoring::splice_jump_score`]), and the free jump over a scaffold gap ending before
+/// the row (see [`SingleContigAligner::set_scaffold_gaps`]), with the score and x-index of each.
+#[derive(Copy, Clone, Debug)]
+struct ForwardJumps {
+    any: (i32, ContigPos),
+    spliced: [(i32, ContigPos); SPLICE_KINDS],
+    scaffold_gap: (i32, ContigPos),
+}
+
+impl Default for ForwardJumps {
+    fn default() -> Self {
+        let none = (MIN_SCORE, ContigPos::new(0));
+        Self {
+            any: none,
+            spliced: [none; SPLICE_KINDS],
+            scaffold_gap: none,
+        }
+    }
+}
+
+/// The state of the adaptive band (see [`SingleContigAligner::set_adaptive_band_width`]) after
+/// filling a column: the row with the best score in the column, the best score, the highest best
+/// score of any column so far, and whether to fill the next column in full.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AdaptiveBand {
+    pub center: usize,
+    pub best_score: i32,
+    pub peak_score: i32,
+    pub full_width: bool,
+}
+
+impl Default for AdaptiveBand {
+    fn default() -> Self {
+        Self {
+            center: 0,
+            best_score: MIN_SCORE,
+            peak_score: MIN_SCORE,
+            full_width: true,
+        }
+    }
+}
+
+/// The state changed by filling the last column of the matrices and applying the end clipping to
+/// it, saved so that the column may be restored, and the matrices extended by more columns after
+/// (see [`SingleContigAligner::save_last_column`]).
+#[derive(Clone, Debug)]
+pub struct LastColumnState {
+    s: Vec<i32>,
+    i: Vec<i32>,
+    d: Vec<i32>,
+    sn: Vec<i32>,
+    ly: Vec<usize>,
+    lx: usize,
+    band: AdaptiveBand,
+    cells: Vec<Cell>,
+}
+
+/// The moves into a cell other than an insertion, with the traceback of the best of the moves that
+/// are neither jumps nor clips.
+#[derive(Copy, Clone, Debug)]
+struct CellMoves {
+    tb: Cell,
+    score: i32,
+    d_score: i32,
+    diag_score: i32,
+    diag_len: u32,
+    jump: JumpInfo,
+    is_match: bool,
+}
+
+/// A move into a gap, with its score, traceback, and alignment length.
+#[derive(Copy, Clone, Debug)]
+struct GapMove {
+    score: i32,
+    tb: u16,
+    len: u32,
+}
+
+/// Whether suffix clipping from a cell with the given score and alignment length should replace
+/// the best suffix clip so far, namely if it scores better, or as well with a longer alignment.
+fn replaces_suffix_clip(score: i32, len: u32, best_score: i32, best_len: u32) -> bool {
+    score > best_score || (score == best_score && len > best_len)
+}
+
+/// Maps each of the given blocks of rows, in parallel when the `threads` feature is enabled.
+fn map_blocks<T: Send, R: Send>(blocks: Vec<T>, f: impl Fn(T) -> R + Send + Sync) -> Vec<R> {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "threads")] {
+            use rayon::prelude::*;
+            blocks.into_par_iter().map(f).collect()
+        } else {
+            blocks.into_iter().map(f).collect()
+        }
+    }
 }
 
 impl Default for SingleContigAligner<MatchParams> {
@@ -89,12 +266,22 @@ impl Default for SingleContigAligner<MatchParams> {
     }
 }
 
-impl<F: MatchFunc> SingleContigAligner<F> {
+impl<F: MatchFunc + Sync> SingleContigAligner<F> {
     pub fn set_contig_idx(&mut self, contig_idx: usize) {
-        self.contig_idx = contig_idx as u32;
+        self.contig_idx = ContigIdx::new(contig_idx);
     }
 
     pub fn init_matrices(&mut self, m: usize, n: usize) {
+        self.band = AdaptiveBand::default();
+        // The best score is at most the match score for each base of y
+        debug_assert!(
+            i32::try_from(n)
+                .ok()
+                .and_then(|n| n.checked_mul(self.scoring.match_fn.score(b'A', b'A').max(0)))
+                .is_some_and(|score| score < i32::MAX / 2),
+            "The score of aligning y (length {n}) could overflow"
+        );
+
         // initialize the traceback
         self.traceback.init(m, n);
 
@@ -105,38 +292,38 @@ impl<F: MatchFunc> SingleContigAligner<F> {
             self.D[k].clear();
             self.S[k].clear();
 
-            self.D[k].extend(repeat(MIN_SCORE).take(m + 1));
-            self.I[k].extend(repeat(MIN_SCORE).take(m + 1));
-            self.S[k].extend(repeat(MIN_SCORE).take(m + 1));
+            self.D[k].extend(repeat_n(MIN_SCORE, m + 1));
+            self.I[k].extend(repeat_n(MIN_SCORE, m + 1));
+            self.S[k].extend(repeat_n(MIN_SCORE, m + 1));
 
             self.S[k][0] = 0;
 
             if k == 0 {
                 let mut tb = Cell::default();
                 tb.set_all(TB_START, 0);
-                tb.set_s_all(TB_START, 0, self.contig_idx, 0);
+                tb.set_s_all(TB_START, 0, self.contig_idx, ContigPos::new(0));
                 self.traceback.set(0, 0, tb);
                 self.Lx.clear();
-                self.Lx.extend(repeat(0usize).take(n + 1));
+                self.Lx.extend(repeat_n(0usize, n + 1));
                 self.Ly.clear();
-                self.Ly.extend(repeat(0usize).take(m + 1));
+                self.Ly.extend(repeat_n(0usize, m + 1));
                 self.Sn.clear();
-                self.Sn.extend(repeat(MIN_SCORE).take(m + 1));
-                self.Sn[0] = self.scoring.yclip_suffix;
-                self.Ly[0] = n;
+                self.Sn.extend(repeat_n(MIN_SCORE, m + 1));
+                self.Sn[0] = self.yclip_suffix();
+                self.Ly[0] = 0;
             }
 
             for i in 1..=m {
                 let mut tb = Cell::default();
                 tb.set_all(TB_START, 0);
-                tb.set_s_all(TB_START, 0, self.contig_idx, 0);
+                tb.set_s_all(TB_START, 0, self.contig_idx, ContigPos::new(0));
                 if i == 1 {
                     self.I[k][i] = self.scoring.gap_open + self.scoring.gap_extend;
                     tb.set_i(TB_START, 1);
                 } else {
                     // Insert all i characters
                     // Could either be a single long-insertion, or x-clipping then an insertion start
-                    let i_score = self.scoring.gap_open + self.scoring.gap_extend * (i as i32);
+                    let i_score = gap_score(self.scoring.gap_open, self.scoring.gap_extend, i);
                     let c_score =
                         self.scoring.xclip_prefix + self.scoring.gap_open + self.scoring.gap_extend; // Clip then insert
                     if i_score > c_score {
@@ -177,9 +364,9 @@ impl<F: MatchFunc> SingleContigAligner<F> {
                 }
 
                 // Track the score if we do suffix clip (y) from here
-                if self.S[k][i] + self.scoring.yclip_suffix > self.Sn[i] {
+                if !self.span_query && self.S[k][i] + self.scoring.yclip_suffix > self.Sn[i] {
                     self.Sn[i] = self.S[k][i] + self.scoring.yclip_suffix;
-                    self.Ly[i] = n;
+                    self.Ly[i] = 0;
                 }
             }
         }
@@ -188,7 +375,7 @@ impl<F: MatchFunc> SingleContigAligner<F> {
     pub fn init_column(&mut self, j: usize, curr: usize, m: usize, n: usize) {
         // Handle i = 0 case
         let mut tb = Cell::default();
-        tb.set_s_all(TB_START, 0, self.contig_idx, 0);
+        tb.set_s_all(TB_START, 0, self.contig_idx, ContigPos::new(0));
         self.I[curr][0] = MIN_SCORE;
 
         // deletion
@@ -199,10 +386,9 @@ impl<F: MatchFunc> SingleContigAligner<F> {
         } else {
             // Delete all j characters
             // Could either be a single long-deletion, or y-clipping then an insertion start
-            let d_score = self.scoring.gap_open + self.scoring.gap_extend * (j as i32);
-            let c_score =
-                self.scoring.yclip_prefix + self.scoring.gap_open + self.scoring.gap_extend;
-            if d_score > c_score {
+            let d_score = gap_score(self.scoring.gap_open, self.scoring.gap_extend, j);
+            let c_score = self.yclip_prefix() + self.scoring.gap_open + self.scoring.gap_extend;
+            if self.span_query || d_score > c_score {
                 self.D[curr][0] = d_score;
                 tb.set_d(TB_DEL, j as u32);
             } else {
@@ -211,7 +397,7 @@ impl<F: MatchFunc> SingleContigAligner<F> {
                 tb.set_d(TB_YCLIP_PREFIX, 0);
             }
         }
-        if self.D[curr][0] > self.scoring.yclip_prefix {
+        if self.span_query || self.D[curr][0] > self.scoring.yclip_prefix {
             self.S[curr][0] = self.D[curr][0];
             tb.set_s(TB_DEL, j as u32);
         } else {
@@ -221,13 +407,15 @@ impl<F: MatchFunc> SingleContigAligner<F> {
         }
 
         // Track the score if we do suffix clip (y) from here
-        if j == n && self.Sn[0] > self.S[curr][0] {
+        if self.span_query {
+        } else if j == n && self.Sn[0] > self.S[curr][0] {
             self.S[curr][0] = self.Sn[0];
             // tb.set_s(TB_YCLIP_SUFFIX, (n + m) as u32);
             tb.set_s(TB_YCLIP_SUFFIX, 0);
         } else if self.S[curr][0] + self.scoring.yclip_suffix > self.Sn[0] {
             self.Sn[0] = self.S[curr][0] + self.scoring.yclip_suffix;
-            self.Ly[0] = n - j;
+            self.Ly[0] = j;
+            self.traceback.keep_y_suffix_clip_cell(0, tb);
         }
 
         self.traceback.set(0, j, tb);
@@ -238,6 +426,107 @@ impl<F: MatchFunc> SingleContigAligner<F> {
         }
     }
 
+    /// Restricts the alignment to start at the given position in x, or to not start in x at all
+    /// if `None`.  Must be called after [`Self::init_matrices`], and with the prefix clipping
+    /// penalties disabled.  An alignment that starts after the start of x will begin with a prefix
+    /// clip of x.
+    pub fn init_anchored_start(&mut self, m: usize, start: Option<usize>) {
+        for k in 0..2 {
+            self.S[k].fill(MIN_SCORE);
+            self.I[k].fill(MIN_SCORE);
+        }
+        self.Sn.fill(MIN_SCORE);
+        let Some(start) = start else {
+            return;
+        };
+        assert!(
+            start <= m,
+            "Anchored start past the end of x: {start} > {m}"
+        );
+
+        // The alignment starts at x[start]
+        let start_tb = if start == 0 {
+            TB_START
+        } else {
+            TB_XCLIP_PREFIX
+        };
+        let mut tb = Cell::default();
+        tb.set_all(TB_START, 0);
+        tb.set_s_all(start_tb, 0, self.contig_idx, ContigPos::new(0));
+        self.traceback.set(start, 0, tb);
+        for k in 0..2 {
+            self.S[k][start] = 0;
+        }
+        self.Sn[start] = self.yclip_suffix();
+        self.Ly[start] = 0;
+
+        // Insert the remaining characters in x
+        for i in (start + 1)..=m {
+            let len = (i - start) as u32;
+            let mut tb = Cell::default();
+            tb.set_all(TB_START, 0);
+            tb.set_s_all(TB_INS, len, self.contig_idx, ContigPos::new(0));
+            if i == start + 1 {
+                tb.set_i(start_tb, len);
+            } else {
+                tb.set_i(TB_INS, len);
+            }
+            self.traceback.set(i, 0, tb);
+            for k in 0..2 {
+                self.I[k][i] =
+                    gap_score(self.scoring.gap_open, self.scoring.gap_extend, len as usize);
+                self.S[k][i] = self.I[k][i];
+            }
+            self.Sn[i] = floor_score(self.S[0][i] + self.yclip_suffix());
+            self.Ly[i] = 0;
+        }
+    }
+
+    /// Initializes the column when the alignment is restricted to start at the given position in
+    /// x (see [`Self::init_anchored_start`]).  Only an alignment starting at the start of x may
+    /// begin with deletions in the first row.
+    pub fn init_anchored_column(
+        &mut self,
+        j: usize,
+        curr: usize,
+        m: usize,
+        n: usize,
+        start: Option<usize>,
+    ) {
+        if start == Some(0) {
+            self.init_column(j, curr, m, n);
+        } else {
+            self.I[curr][0] = MIN_SCORE;
+            self.D[curr][0] = MIN_SCORE;
+            self.S[curr].fill(MIN_SCORE);
+        }
+    }
+
+    /// Restricts the alignment to end at the given position in x, or to not end in x at all if
+    /// `None`.  Must be called instead of [`Self::fill_last_column_and_end_clipping`], and with the
+    /// suffix clipping penalties disabled.  An alignment that ends before the end of x will end
+    /// with a suffix clip of x.
+    pub fn anchor_end(&mut self, m: usize, n: usize, end: Option<usize>) {
+        let curr = n % 2;
+        match end {
+            None => self.S[curr][m] = MIN_SCORE,
+            Some(end) => {
+                assert!(end <= m, "Anchored end past the end of x: {end} > {m}");
+                if end < m {
+                    self.S[curr][m] = self.S[curr][end];
+                    self.Lx[n] = m - end;
+                    let prev_s = self.traceback.get(end, n).get_s();
+                    self.traceback.get_mut(m, n).set_s_all(
+                        TB_XCLIP_SUFFIX,
+                        prev_s.len,
+                        prev_s.idx,
+                        ContigPos::new(end),
+                    );
+                }
+            }
+        }
+    }
+
     /// Gets the jump score for a given cell in the matrix.
     fn get_jump_score_and_len(
         &self,
@@ -248,10 +537,18 @@ impl<F: MatchFunc> SingleContigAligner<F> {
         addend: i32,
         jump_info: JumpInfo,
     ) -> JumpInfo {
-        // add the specific addend!
+        // add the specific addend, and the score of jumping to x[i - 1]!
         let jump_info = {
-            let mut info = jump_info;
-            info.score += addend;
+            let mut info =
+                if self.is_free_jump_site(i - 1) && self.free_jump_into.score > jump_info.score {
+                    self.free_jump_into
+                } else {
+                    jump_info
+                };
+            info.score = match self.jump_site_score(i - 1) {
+                Some(site_score) => info.score + addend + site_score,
+                None => MIN_SCORE,
+            };
             info
         };
 
@@ -285,13 +582,402 @@ impl<F: MatchFunc> SingleContigAligner<F> {
             score: jump_from_end_score,
             len: jump_from_end_len,
             idx: self.contig_idx,
-            from: m as u32,
+            from: ContigPos::new(m),
+        }
+    }
+
+    /// Precomputes the length of the homopolymer run containing each base of x, if gaps next to
+    /// homopolymers are scored differently (see [`Scoring::homopolymer_gap_open`]).  Must be
+    /// called with the x to be aligned for such gaps to be scored.
+    pub fn set_homopolymer_runs(&mut self, x: &[u8]) {
+        self.homopolymer_runs = if self.scoring.homopolymer_gap_open.is_some() {
+            homopolymer_run_lengths(x)
+        } else {
+            Vec::new()
+        };
+    }
+
+    /// Precomputes the splice sites at each position of x from the intron motifs, if jumps over
+    /// introns are scored differently (see [`Scoring::splice_jump_score`]).  Must be called with
+    /// the x to be aligned for such jumps to be scored.
+    pub fn set_splice_motifs(&mut self, x: &[u8]) {
+        self.splice_motifs = if self.scoring.splice_jump_score.is_some() {
+            motif_splice_sites(x)
+        } else {
+            Vec::new()
+        };
+    }
+
+    /// Sets the splice sites at each position `0..=m` of x from the annotated introns (see
+    /// [`crate::align::splice::SpliceAnnotation::splice_sites`]), so that the jumps over the
+    /// annotated introns are scored as spliced whatever their motifs.  If empty, no position is
+    /// an annotated splice site.
+    pub fn set_annotated_splice_sites(&mut self, annotated_splice_sites: Vec<u8>) {
+        self.annotated_splice_sites = annotated_splice_sites;
+    }
+
+    /// Sets the scaffold gaps of x, the runs of at least `min_len` Ns standing for a gap of unknown
+    /// length in a scaffold, over which a jump forward, from the base before the gap to the base
+    /// after it, is free, so that the length of the gap does not count against the alignment.
+    /// With `None`, Ns are aligned as any other base.
+    ///
+    /// Re-scoring an alignment (see [`crate::align::Alignment::rescore`]) does not know of the
+    /// scaffold gaps, so scores such jumps as any other forward jump.
+    pub fn set_scaffold_gaps(&mut self, x: &[u8], min_len: Option<usize>) {
+        self.scaffold_gap_lens = match min_len {
+            Some(min_len) if x.iter().any(|base| base.eq_ignore_ascii_case(&b'N')) => {
+                n_run_lengths(x, min_len)
+            }
+            _ => Vec::new(),
+        };
+    }
+
+    /// True if the best jump within the same contig and strand depends on the position jumped to,
+    /// since jumps are scored by direction (see [`Scoring::has_directional_jump_scores`]), or
+    /// jumps over scaffold gaps are free (see [`Self::set_scaffold_gaps`]).
+    fn has_directional_jumps(&self) -> bool {
+        self.scoring.has_directional_jump_scores() || !self.scaffold_gap_lens.is_empty()
+    }
+
+    /// The splice sites at position `p` of x, from the intron motifs or the annotation.
+    fn splice_sites(&self, p: usize) -> u8 {
+        self.splice_motifs.get(p).copied().unwrap_or(0)
+            | self.annotated_splice_sites.get(p).copied().unwrap_or(0)
+    }
+
+    /// Adds the forward jumps from row `i - 1` of column `j - 1` into row `i` and the rows after
+    /// it, and the spliced jumps from the row [`MIN_INTRON_LEN`] rows before, so that each intron
+    /// is at least that long.  Also sets the free jump into row `i` over the scaffold gap ending
+    /// before `x[i - 1]`, if any.  Must be called for each row in order, from the first row.
+    fn add_forward_jumps(&self, jumps: &mut ForwardJumps, i: usize, j: usize) {
+        if let Some(score) = self.jump_from(i - 1, j - 1, self.scoring.forward_jump_score()) {
+            if score > jumps.any.0 {
+                jumps.any = (score, ContigPos::new(i - 1));
+            }
         }
+        jumps.scaffold_gap = (MIN_SCORE, ContigPos::new(0));
+        let gap_len = self.scaffold_gap_lens.get(i - 1).copied().unwrap_or(0) as usize;
+        if gap_len > 0 {
+            let k = i - 1 - gap_len;
+            if let Some(score) = self.jump_from(k, j - 1, 0) {
+                jumps.scaffold_gap = (score, ContigPos::new(k));
+            }
+        }
+        let Some(splice_jump_score) = self.scoring.splice_jump_score else {
+            return;
+        };
+        let Some(k) = (i - 1).checked_sub(MIN_INTRON_LEN) else {
+            return;
+        };
+        let sites = self.splice_sites(k);
+        if sites == 0 {
+            return;
+        }
+        if let Some(score) = self.jump_from(k, j - 1, splice_jump_score) {
+            for (kind, spliced) in jumps.spliced.iter_mut().enumerate() {
+                if sites & donor(kind) != 0 && score > spliced.0 {
+                    *spliced = (score, ContigPos::new(k));
+                }
+            }
+        }
+    }
+
+    /// The best forward jump into row `i`: any forward jump, a spliced jump from a donor if
+    /// `x[i - 1]` follows an acceptor of the same kind of splice sites, or the free jump over the
+    /// scaffold gap before `x[i - 1]`, when that scores higher.
+    fn forward_jump_into(&self, jumps: &ForwardJumps, i: usize) -> (i32, ContigPos) {
+        let sites = self.splice_sites(i - 1);
+        let mut best = jumps.any;
+        if jumps.scaffold_gap.0 > best.0 {
+            best = jumps.scaffold_gap;
+        }
+        for (kind, spliced) in jumps.spliced.iter().enumerate() {
+            if sites & acceptor(kind) != 0 && spliced.0 > best.0 {
+                best = *spliced;
+            }
+        }
+        best
+    }
+
+    /// The highest score for aligning any base of y to any base of x, from the profile if set, or
+    /// otherwise from the match function for the bases `ACGTN`.
+    pub fn max_match_score(&self) -> i32 {
+        match &self.profile {
+            Some(profile) => profile.max_score(),
+            None => b"ACGTN"
+                .iter()
+                .cartesian_product(b"ACGTN")
+                .map(|(p, q)| self.scoring.match_fn.score(*p, *q))
+                .max()
+                .unwrap(),
+        }
+    }
+
+    /// Sets the position-specific scores for aligning to each base of x, or `None` to score with
+    /// the match function of the scoring.  The x to be aligned should be the consensus of the
+    /// profile (see [`ScoreProfile::consensus`]).
+    pub fn set_profile(&mut self, profile: Option<Arc<ScoreProfile>>) {
+        self.profile = profile;
     }
 
-    pub fn fill_column(
+    /// Sets the number of rows on either side of the best scoring row of the previous column to
+    /// fill in each column, or `None` to fill every row.  This adaptive band follows the best
+    /// scoring cell, rather than a fixed diagonal, so it tolerates indels shorter than the band
+    /// and drift between the sequence lengths, in time proportional to the band width.  Rows
+    /// outside the band are unreachable, so the alignment is not guaranteed to be optimal.
+    ///
+    /// Since a jump may land on any row, a column is filled in full when the best jump into it
+    /// scores at least the best score of the previous column, or after the best scoring cell was
+    /// reached by a jump.  A column is also filled in full when multiple cells of the previous
+    /// column had the best score, as the band would be centered on just one of them, and while the
+    /// best score is no more than the highest best score of the previous columns (as after a
+    /// mismatch or gap), so that an alignment elsewhere (for example after a jump) may overtake
+    /// it.  The first column is always filled in full.  When aligning to multiple contigs, jumps
+    /// from the contig with the best alignment usually score at least as well as the alignments
+    /// to the other contigs, and so only the former is usually banded.
+    pub fn set_adaptive_band_width(&mut self, adaptive_band_width: Option<usize>) {
+        self.adaptive_band_width = adaptive_band_width;
+    }
+
+    /// Sets the number of rows in each block of rows to fill in parallel (when the `threads`
+    /// feature is enabled), or `None` to fill the rows in order, so that aligning to a long contig
+    /// scales with the number of threads.  Each column is filled in blocks when x is longer than a
+    /// block, unless banding (see [`Self::set_adaptive_band_width`]) or opening or extending gaps
+    /// scores positively, with the same result as filling the rows in order.
+    pub fn set_parallel_block_rows(&mut self, parallel_block_rows: Option<usize>) {
+        assert!(
+            parallel_block_rows != Some(0),
+            "parallel_block_rows must be greater than zero"
+        );
+        self.parallel_block_rows = parallel_block_rows;
+    }
+
+    /// Sets whether the alignment must span all of y, so that y is never clipped whatever the y
+    /// clipping penalties.  Rather than relying on the penalties being too low to be chosen (see
+    /// [`MIN_SCORE`]), the y clipping moves are not considered at all, so that y is not clipped
+    /// even when every alignment spanning y scores at the floor of the scores.
+    pub fn set_span_query(&mut self, span_query: bool) {
+        self.span_query = span_query;
+    }
+
+    /// Sets whether jumps into or out of a clipped region of x are forbidden, so that every jump is
+    /// from an aligned base of x to another.  Otherwise, the alignment may jump from the start of
+    /// x before aligning any base of it, from a prefix or suffix clip of x (which are not reported,
+    /// as the alignment continues after the jump), and over the remaining bases of x after the
+    /// last base of y, all in place of clipping x.
+    pub fn set_forbid_clip_jumps(&mut self, forbid_clip_jumps: bool) {
+        self.forbid_clip_jumps = forbid_clip_jumps;
+    }
+
+    /// Sets whether each base `x[i]` may not be aligned to the base `y[i]` at the same position,
+    /// whether through a diagonal move or a jump, so that a sequence aligned to itself (as both x
+    /// and y) is not trivially aligned along the main diagonal, and is instead aligned to its
+    /// repeats (see [`crate::align::self_align::SelfAligner`]).  Gaps may still cross the diagonal.
+    pub fn set_mask_diagonal(&mut self, mask_diagonal: bool) {
+        self.mask_diagonal = mask_diagonal;
+    }
+
+    /// Sets the score added to a jump from or to each position `0..=m` of x, where position `p`
+    /// is the boundary between `x[p - 1]` and `x[p]`, or [`MIN_SCORE`] to forbid jumps from or to
+    /// the position, for example to restrict the jumps to the boundaries of annotated features
+    /// (see [`crate::align::jump_sites::JumpSites`]).  If empty, jumps may be from or to any
+    /// position.  The circular jump from the end of x to its start is not scored.
+    pub fn set_jump_site_scores(&mut self, jump_site_scores: Vec<i32>) {
+        self.jump_site_scores = jump_site_scores;
+    }
+
+    /// Sets whether jumps from or to each position `0..=m` of x are free, with position `p` as in
+    /// [`Self::set_jump_site_scores`], for example at known recombination or splice points (see
+    /// [`crate::align::jump_sites::JumpSites::add_free_site`]).  A jump from a free position is
+    /// scored without the jump score, as is a jump to a free position from any contig, given
+    /// the best such jump into each column (see [`Self::set_free_jump_into`]).  The score added to
+    /// a jump from or to a position still applies.  If empty, no position is free.
+    ///
+    /// Re-scoring an alignment (see [`crate::align::Alignment::rescore`]) does not know of the
+    /// free positions, so scores such jumps as any other jump.
+    pub fn set_free_jump_sites(&mut self, free_jump_sites: Vec<bool>) {
+        self.free_jump_sites = free_jump_sites;
+    }
+
+    /// True if any position of x is free to jump from or to (see [`Self::set_free_jump_sites`]).
+    pub fn has_free_jump_sites(&self) -> bool {
+        self.free_jump_sites.iter().any(|free| *free)
+    }
+
+    /// True if jumps from or to position `p` of x are free (see [`Self::set_free_jump_sites`]).
+    fn is_free_jump_site(&self, p: usize) -> bool {
+        self.free_jump_sites.get(p).copied().unwrap_or(false)
+    }
+
+    /// Sets the best jump into the column to be filled from any contig, scored without the jump
+    /// score (see [`Self::get_jump_info`]), for the jumps to the free positions of x (see
+    /// [`Self::set_free_jump_sites`]).  Must be set before filling each column.
+    pub fn set_free_jump_into(&mut self, free_jump_into: JumpInfo) {
+        self.free_jump_into = free_jump_into;
+    }
+
+    /// The score added to a jump from or to position `p` of x, or `None` if forbidden (see
+    /// [`Self::set_jump_site_scores`]).
+    fn jump_site_score(&self, p: usize) -> Option<i32> {
+        match self.jump_site_scores.get(p) {
+            None => Some(0),
+            Some(&MIN_SCORE) => None,
+            Some(&score) => Some(score),
+        }
+    }
+
+    /// The score of jumping from cell `(k, j)` with the given jump score, or without it if `k` is
+    /// free to jump from (see [`Self::set_free_jump_sites`]), or `None` if jumps from the cell are
+    /// forbidden, as no base of x is aligned there or it is reached by clipping x (see
+    /// [`Self::set_forbid_clip_jumps`]), or as `k` is not a jump site (see
+    /// [`Self::set_jump_site_scores`]).
+    fn jump_from(&self, k: usize, j: usize, jump_score: i32) -> Option<i32> {
+        let jump_score = if self.is_free_jump_site(k) {
+            0
+        } else {
+            jump_score
+        };
+        let from_clip = self.forbid_clip_jumps
+            && (k == 0
+                || matches!(
+                    self.traceback.get(k, j).get_s().tb,
+                    TB_XCLIP_PREFIX | TB_XCLIP_SUFFIX
+                ));
+        if from_clip {
+            return None;
+        }
+        self.jump_site_score(k)
+            .map(|site_score| self.S[j % 2][k] + jump_score + site_score)
+    }
+
+    /// The score of prefix clipping y, or [`MIN_SCORE`] if y must be spanned (see
+    /// [`Self::set_span_query`]).
+    fn yclip_prefix(&self) -> i32 {
+        if self.span_query {
+            MIN_SCORE
+        } else {
+            self.scoring.yclip_prefix
+        }
+    }
+
+    /// The score of suffix clipping y, or [`MIN_SCORE`] if y must be spanned (see
+    /// [`Self::set_span_query`]).
+    fn yclip_suffix(&self) -> i32 {
+        if self.span_query {
+            MIN_SCORE
+        } else {
+            self.scoring.yclip_suffix
+        }
+    }
+
+    /// The first and last rows to fill in the next column, given the score of the best jump into
+    /// the column (see [`Self::set_adaptive_band_width`]).
+    fn band_rows(&self, m: usize, jump_score: i32) -> (usize, usize) {
+        match self.adaptive_band_width {
+            Some(width) if !self.band.full_width && jump_score < self.band.best_score => (
+                self.band.center.saturating_sub(width).max(1),
+                (self.band.center + width).min(m),
+            ),
+            _ => (1, m),
+        }
+    }
+
+    /// Updates the adaptive band after filling column `j`, with the best score and its (first)
+    /// row, and whether another row has the best score.
+    fn update_band(&mut self, j: usize, best_score: i32, best_row: usize, tied: bool) {
+        let s_value = self.traceback.get(best_row, j).get_s();
+        let jumped = (s_value.tb == TB_MATCH || s_value.tb == TB_SUBST)
+            && (s_value.idx != self.contig_idx || s_value.from.get() + 1 != best_row);
+        self.band = AdaptiveBand {
+            center: best_row,
+            best_score,
+            peak_score: self.band.peak_score.max(best_score),
+            full_width: jumped || tied || best_score <= self.band.peak_score,
+        };
+    }
+
+    /// The score for aligning `y` base `q` to `x[i - 1] = p`.
+    fn match_score(&self, i: usize, p: u8, q: u8) -> i32 {
+        match &self.profile {
+            Some(profile) => profile.score(i - 1, q),
+            None => self.scoring.match_fn.score(p, q),
+        }
+    }
+
+    /// The scores for opening an insertion (of `x[i - 1]`) and a deletion (after `x[i - 1]`) in
+    /// row `i > 0`, where a deletion is next to a homopolymer if either adjacent base is in one.
+    fn gap_opens(&self, i: usize) -> (i32, i32) {
+        if self.homopolymer_runs.is_empty() {
+            return (self.scoring.gap_open, self.scoring.gap_open);
+        }
+        let before = self.homopolymer_runs[i - 1];
+        let after = self.homopolymer_runs.get(i).copied().unwrap_or(0);
+        (
+            self.scoring.gap_open_for_run_length(before as usize),
+            self.scoring
+                .gap_open_for_run_length(before.max(after) as usize),
+        )
+    }
+
+    /// Fills the best backward jump into each position of column `j` from the previous column
+    /// (see `Jb`), preferring the jump from the lowest x-index on ties.  The last row does not
+    /// jump if it tracks an x-suffix clip, as the alignment cannot continue after the clip.
+    fn fill_backward_jumps(&mut self, m: usize, j: usize) {
+        let jump_score = self.scoring.backward_jump_score();
+        self.Jb.clear();
+        self.Jb.resize(m + 1, (MIN_SCORE, ContigPos::new(m)));
+        let mut best = (MIN_SCORE, ContigPos::new(m));
+        let from_xclip = self.traceback.get(m, j - 1).get_s().tb == TB_XCLIP_SUFFIX;
+        for k in (1..=m).rev() {
+            if k == m && from_xclip {
+                self.Jb[k] = best;
+                continue;
+            }
+            if let Some(score) = self.jump_from(k, j - 1, jump_score) {
+                if score >= best.0 {
+                    best = (score, ContigPos::new(k));
+                }
+            }
+            self.Jb[k] = best;
+        }
+    }
+
+    /// Returns the best jump into position `i` of column `j`, from the best jump forward or
+    /// backward within the same contig and strand, or the given jump to another contig or strand.
+    /// On ties, a forward jump is preferred, then a jump within the same contig and strand.
+    fn get_directional_jump_info(
+        &self,
+        i: usize,
+        j: usize,
+        forward: (i32, ContigPos),
+        jump_info: JumpInfo,
+    ) -> JumpInfo {
+        let backward = self.Jb[i];
+        let (score, from) = if backward.0 > forward.0 {
+            backward
+        } else {
+            forward
+        };
+        if jump_info.score > score {
+            return jump_info;
+        }
+        JumpInfo {
+            score,
+            len: self.traceback.get(from.get(), j - 1).get_s_len() + 1,
+            idx: self.contig_idx,
+            from,
+        }
+    }
+
+    /// Fills in the given column.  The jump into each position is the given jump, except when
+    /// jumps within the same contig and strand are scored by direction (see
+    /// [`Scoring::has_directional_jump_scores`]), where the best such jump depends on the
+    /// position, and so is computed here instead if `same_strand_jumps` is true.  In that case,
+    /// the given jump should not include jumps within the same contig and strand (see
+    /// [`Self::get_same_strand_jump_info`]).
+    pub fn fill_column<X: Index<usize, Output = u8> + Sync + ?Sized>(
         &mut self,
-        x: TextSlice<'_>,
+        x: &X,
         y: TextSlice<'_>,
         m: usize,
         n: usize,
@@ -299,155 +985,530 @@ impl<F: MatchFunc> SingleContigAligner<F> {
         prev: usize,
         curr: usize,
         jump_info: JumpInfo,
+        same_strand_jumps: bool,
     ) {
+        let directional = same_strand_jumps && self.has_directional_jumps();
+        if directional {
+            self.fill_backward_jumps(m, j);
+        }
         let q = y[j - 1];
         let xclip_score = self.scoring.xclip_prefix
             + max(
-                self.scoring.yclip_prefix,
-                self.scoring.gap_open + self.scoring.gap_extend * (j as i32),
+                self.yclip_prefix(),
+                gap_score(self.scoring.gap_open, self.scoring.gap_extend, j),
             );
 
-        for i in 1..=m {
-            let p: u8 = x[i - 1];
-            let mut tb = Cell::default();
-
-            // Insertion
-            // It does not make sense to _start_ an insertion right after a jump, since you might
-            // as well just jumped over the insertion!
-            let i_score = self.I[curr][i - 1] + self.scoring.gap_extend;
-            let s_score: i32 =
-                self.S[curr][i - 1] + self.scoring.gap_open + self.scoring.gap_extend;
-            let best_i_score = max(i_score, s_score);
-            if i_score == best_i_score {
-                tb.set_i(TB_INS, self.traceback.get(i - 1, j).get_i_len() + 1);
-            } else {
-                let s_value = self.traceback.get(i - 1, j).get_s();
-                tb.set_i(s_value.tb, s_value.len + 1);
+        // Fill long columns in blocks of rows, unless banding
+        if let Some(block_rows) = self.parallel_block_rows {
+            if m > block_rows
+                && self.adaptive_band_width.is_none()
+                && self.scoring.gap_open <= 0
+                && self.scoring.gap_extend <= 0
+            {
+                self.fill_column_blocks(
+                    x,
+                    m,
+                    n,
+                    j,
+                    prev,
+                    curr,
+                    q,
+                    xclip_score,
+                    jump_info,
+                    directional,
+                    block_rows,
+                );
+                return;
             }
+        }
 
-            // Deletion
-            let d_score = self.D[prev][i] + self.scoring.gap_extend;
-            let s_score = self.S[prev][i] + self.scoring.gap_open + self.scoring.gap_extend;
-            let best_d_score = max(d_score, s_score);
-            if d_score == best_d_score {
-                let prev_len = self.traceback.get(i, j - 1).get_d_len();
-                tb.set_d(TB_DEL, prev_len + 1);
-            } else {
-                let s_value = self.traceback.get(i, j - 1).get_s();
-                tb.set_d(s_value.tb, s_value.len + 1);
-            }
+        let mut forward_jumps = ForwardJumps::default();
 
-            // Set the optimal score for all moves
-            // Preferences if two or more moves have
-            // 1. diagonal over all other moves except jump, and over jump when the alignment length
-            // for the diagonal is greater than the jump's alignment length.
-            // 2. X-suffix clip (for implementation convenience)
-            // 3. deletion
-            // 4. insertion
-            // 5. jump *(exception see rule 1)
-            // 6. X-prefix clip
-            // 7. Y-prefix clip
-            tb.set_s(TB_XCLIP_SUFFIX, self.traceback.get(i, j).get_s_len());
-            let mut best_s_score = self.S[curr][i];
-            // Score for aligning just [x-1] with y[j-1] alone
-            let addend = self.scoring.match_fn.score(p, q);
-            // Align the x[i-1] with y[j-1] through a diagonal move.
-            let diag_score = self.S[prev][i - 1] + addend;
-            let diag_len = self.traceback.get(i - 1, j - 1).get_s_len() + 1;
-            if diag_score >= best_s_score {
-                best_s_score = diag_score;
-                let s_tb = if p == q { TB_MATCH } else { TB_SUBST };
-                tb.set_s_all(s_tb, diag_len, self.contig_idx, (i - 1) as u32);
+        // Only fill the rows in the band, so the other rows are unreachable
+        let (lo, hi) = self.band_rows(m, jump_info.score);
+        if lo > 1 || hi < m {
+            for k in (1..lo).chain(hi + 1..=m) {
+                self.I[curr][k] = MIN_SCORE;
+                self.D[curr][k] = MIN_SCORE;
             }
-            // Deletion
-            if best_d_score > best_s_score {
-                best_s_score = best_d_score;
-                tb.set_s_all(TB_DEL, tb.get_d_len(), self.contig_idx, i as u32);
-            }
-            // Insertion
-            if best_i_score > best_s_score {
-                best_s_score = best_i_score;
-                tb.set_s_all(TB_INS, tb.get_i_len(), self.contig_idx, (i - 1) as u32);
+        }
+        let (mut best_score, mut best_row, mut tied) = (MIN_SCORE, 0, false);
+        // The forward jumps into the band include those from the rows before it
+        if directional {
+            for i in 1..lo {
+                self.add_forward_jumps(&mut forward_jumps, i, j);
             }
+        }
+
+        for i in lo..=hi {
             // Align the x[i-1] with y[j-1] through a jump move.
-            let x_jump_info = self.get_jump_score_and_len(m, i, j, prev, addend, jump_info);
-            let do_jump = x_jump_info.score > best_s_score
-                || (x_jump_info.score == best_s_score
-                    && best_s_score == diag_score
-                    && x_jump_info.len > diag_len);
-            if do_jump {
-                best_s_score = x_jump_info.score;
-                let s_tb = if p == q { TB_MATCH } else { TB_SUBST };
-                tb.set_s_all(s_tb, x_jump_info.len, x_jump_info.idx, x_jump_info.from);
+            let jump_info = if directional {
+                self.add_forward_jumps(&mut forward_jumps, i, j);
+                let forward_jump = self.forward_jump_into(&forward_jumps, i);
+                self.get_directional_jump_info(i, j, forward_jump, jump_info)
+            } else {
+                jump_info
+            };
+            self.fill_cell(m, n, i, j, prev, curr, x[i - 1], q, xclip_score, jump_info);
+
+            // NB: the last row also tracks the x-suffix clip from the other rows
+            if i == m {
+            } else if self.S[curr][i] > best_score {
+                (best_score, best_row, tied) = (self.S[curr][i], i, false);
+            } else if self.S[curr][i] == best_score {
+                tied = true;
             }
-            // X-prefix clip
-            if xclip_score > best_s_score {
-                best_s_score = xclip_score;
-                let prev_len = self.traceback.get(0, j).get_s_len();
-                // tb.set_s_all(TB_XCLIP_PREFIX, prev_len + i as u32, 0, false);
-                tb.set_s_all(TB_XCLIP_PREFIX, prev_len, self.contig_idx, 0);
+        }
+
+        if self.adaptive_band_width.is_some() && best_row > 0 {
+            self.update_band(j, best_score, best_row, tied);
+        }
+    }
+
+    /// Fills cell `(i, j)` from the cells above it and in the previous column, given the best jump
+    /// into it, and tracks the score of suffix clipping from it.
+    #[allow(clippy::too_many_arguments)]
+    fn fill_cell(
+        &mut self,
+        m: usize,
+        n: usize,
+        i: usize,
+        j: usize,
+        prev: usize,
+        curr: usize,
+        p: u8,
+        q: u8,
+        xclip_score: i32,
+        jump_info: JumpInfo,
+    ) {
+        let (ins_gap_open, del_gap_open) = self.gap_opens(i);
+
+        // Insertion
+        // It does not make sense to _start_ an insertion right after a jump, since you might
+        // as well just jumped over the insertion!
+        let above = *self.traceback.get(i - 1, j);
+        let insertion = self.insertion_move(
+            (self.I[curr][i - 1], above.get_i_len()),
+            (self.S[curr][i - 1], above.get_s()),
+            ins_gap_open,
+        );
+        let moves = self.cell_moves(m, i, j, prev, curr, p, q, del_gap_open, jump_info);
+        let (best_s_score, tb) = self.best_move(&moves, insertion, xclip_score, i, j);
+
+        // Set the values in the matrices
+        self.S[curr][i] = floor_score(best_s_score);
+        self.I[curr][i] = floor_score(insertion.score);
+        self.D[curr][i] = floor_score(moves.d_score);
+
+        // Track the score if we do suffix clip (x) from here
+        let do_x_suffix_clip = replaces_suffix_clip(
+            self.S[curr][i] + self.scoring.xclip_suffix,
+            tb.get_s_len(),
+            self.S[curr][m],
+            self.traceback.get(m, j).get_s_len(),
+        );
+        if do_x_suffix_clip {
+            self.S[curr][m] = self.S[curr][i] + self.scoring.xclip_suffix;
+            let prev_s = tb.get_s();
+            self.traceback.get_mut(m, j).set_s_all(
+                TB_XCLIP_SUFFIX,
+                // prev_len + (m - i) as u32,
+                prev_s.len,
+                prev_s.idx,
+                ContigPos::new(i),
+            );
+            self.Lx[j] = m - i;
+        }
+
+        // Track the score if we do suffix clip (y) from here
+        let do_y_suffix_clip = !self.span_query
+            && replaces_suffix_clip(
+                self.S[curr][i] + self.scoring.yclip_suffix,
+                tb.get_s_len(),
+                self.Sn[i],
+                self.y_suffix_clip_len(i, j, n),
+            );
+        if do_y_suffix_clip {
+            self.Sn[i] = self.S[curr][i] + self.scoring.yclip_suffix;
+            self.Ly[i] = j;
+            self.traceback.keep_y_suffix_clip_cell(i, tb);
+        }
+
+        self.traceback.set(i, j, tb);
+    }
+
+    /// The length of the alignment suffix clipping y from row `i`, for breaking ties with suffix
+    /// clipping from column `j`, which is only set once the last column (`n`) is filled.
+    fn y_suffix_clip_len(&self, i: usize, j: usize, n: usize) -> u32 {
+        if j == n {
+            self.traceback.get(i, n).get_s_len()
+        } else {
+            0
+        }
+    }
+
+    /// The best insertion into a row, from extending the insertion into the row above (with the
+    /// given score and length), or opening one after the best move into the row above (with the
+    /// given score and traceback).
+    fn insertion_move(
+        &self,
+        above_i: (i32, u32),
+        above_s: (i32, SValue),
+        gap_open: i32,
+    ) -> GapMove {
+        let i_score = above_i.0 + self.scoring.gap_extend;
+        let s_score = above_s.0 + gap_open + self.scoring.gap_extend;
+        if i_score >= s_score {
+            GapMove {
+                score: i_score,
+                tb: TB_INS,
+                len: above_i.1 + 1,
             }
-            // Y-prefix clip
-            let yclip_score = self.scoring.yclip_prefix
-                + self.scoring.gap_open
-                + self.scoring.gap_extend * (i as i32);
-            if yclip_score > best_s_score {
-                let prev_len = self.traceback.get(i, 0).get_s_len();
-                best_s_score = yclip_score;
-                // tb.set_s_all(TB_YCLIP_PREFIX, prev_len + j as u32, i as u32, false);
-                tb.set_s_all(TB_YCLIP_PREFIX, prev_len, self.contig_idx, i as u32);
+        } else {
+            GapMove {
+                score: s_score,
+                tb: above_s.1.tb,
+                len: above_s.1.len + 1,
             }
+        }
+    }
 
-            // Set the values in the matrices
-            self.S[curr][i] = best_s_score;
-            self.I[curr][i] = best_i_score;
-            self.D[curr][i] = best_d_score;
+    /// The moves into cell `(i, j)` other than an insertion, given the best jump into it.
+    #[allow(clippy::too_many_arguments)]
+    fn cell_moves(
+        &self,
+        m: usize,
+        i: usize,
+        j: usize,
+        prev: usize,
+        curr: usize,
+        p: u8,
+        q: u8,
+        del_gap_open: i32,
+        jump_info: JumpInfo,
+    ) -> CellMoves {
+        let mut tb = Cell::default();
 
-            // Track the score if we do suffix clip (x) from here
-            let do_x_suffix_clip =
-                match (self.S[curr][i] + self.scoring.xclip_suffix).cmp(&self.S[curr][m]) {
-                    std::cmp::Ordering::Less => false,
-                    std::cmp::Ordering::Greater => true,
-                    std::cmp::Ordering::Equal => {
-                        // let left_len = tb.get_s_len() + (m - i) as u32;
-                        let left_len = tb.get_s_len();
-                        let right_len = self.traceback.get(m, j).get_s_len();
-                        left_len > right_len
+        // Deletion
+        let d_score = self.D[prev][i] + self.scoring.gap_extend;
+        let s_score = self.S[prev][i] + del_gap_open + self.scoring.gap_extend;
+        let best_d_score = max(d_score, s_score);
+        if d_score == best_d_score {
+            let prev_len = self.traceback.get(i, j - 1).get_d_len();
+            tb.set_d(TB_DEL, prev_len + 1);
+        } else {
+            let s_value = self.traceback.get(i, j - 1).get_s();
+            tb.set_d(s_value.tb, s_value.len + 1);
+        }
+
+        // Set the optimal score for all moves
+        // Preferences if two or more moves have
+        // 1. diagonal over all other moves except jump, and over jump when the alignment length
+        // for the diagonal is greater than the jump's alignment length.
+        // 2. X-suffix clip (for implementation convenience)
+        // 3. deletion
+        // 4. insertion
+        // 5. jump *(exception see rule 1)
+        // 6. X-prefix clip
+        // 7. Y-prefix clip
+        tb.set_s(TB_XCLIP_SUFFIX, self.traceback.get(i, j).get_s_len());
+        let mut best_s_score = self.S[curr][i];
+        // Score for aligning just [x-1] with y[j-1] alone, unless masked
+        let addend = if self.mask_diagonal && i == j {
+            MIN_PENALTY
+        } else {
+            self.match_score(i, p, q)
+        };
+        // Align the x[i-1] with y[j-1] through a diagonal move.
+        let diag_score = self.S[prev][i - 1] + addend;
+        let diag_len = self.traceback.get(i - 1, j - 1).get_s_len() + 1;
+        if diag_score >= best_s_score {
+            best_s_score = diag_score;
+            let s_tb = if p == q { TB_MATCH } else { TB_SUBST };
+            tb.set_s_all(s_tb, diag_len, self.contig_idx, ContigPos::new(i - 1));
+        }
+        // Deletion
+        if best_d_score > best_s_score {
+            best_s_score = best_d_score;
+            tb.set_s_all(TB_DEL, tb.get_d_len(), self.contig_idx, ContigPos::new(i));
+        }
+
+        CellMoves {
+            tb,
+            score: best_s_score,
+            d_score: best_d_score,
+            diag_score,
+            diag_len,
+            jump: self.get_jump_score_and_len(m, i, j, prev, addend, jump_info),
+            is_match: p == q,
+        }
+    }
+
+    /// The best move into cell `(i, j)` and its traceback, given the other moves into the cell and
+    /// the best insertion into it.
+    fn best_move(
+        &self,
+        moves: &CellMoves,
+        insertion: GapMove,
+        xclip_score: i32,
+        i: usize,
+        j: usize,
+    ) -> (i32, Cell) {
+        let mut tb = moves.tb;
+        tb.set_i(insertion.tb, insertion.len);
+        let mut best_s_score = moves.score;
+        // Insertion
+        if insertion.score > best_s_score {
+            best_s_score = insertion.score;
+            tb.set_s_all(
+                TB_INS,
+                tb.get_i_len(),
+                self.contig_idx,
+                ContigPos::new(i - 1),
+            );
+        }
+        // Align the x[i-1] with y[j-1] through a jump move.
+        let x_jump_info = moves.jump;
+        let do_jump = x_jump_info.score > best_s_score
+            || (x_jump_info.score == best_s_score
+                && best_s_score == moves.diag_score
+                && x_jump_info.len > moves.diag_len);
+        if do_jump {
+            best_s_score = x_jump_info.score;
+            let s_tb = if moves.is_match { TB_MATCH } else { TB_SUBST };
+            tb.set_s_all(s_tb, x_jump_info.len, x_jump_info.idx, x_jump_info.from);
+        }
+        // X-prefix clip
+        if xclip_score > best_s_score {
+            best_s_score = xclip_score;
+            let prev_len = self.traceback.get(0, j).get_s_len();
+            // tb.set_s_all(TB_XCLIP_PREFIX, prev_len + i as u32, 0, false);
+            tb.set_s_all(
+                TB_XCLIP_PREFIX,
+                prev_len,
+                self.contig_idx,
+                ContigPos::new(0),
+            );
+        }
+        // Y-prefix clip
+        let yclip_score = self.scoring.yclip_prefix
+            + gap_score(self.scoring.gap_open, self.scoring.gap_extend, i);
+        if !self.span_query && yclip_score > best_s_score {
+            let prev_len = self.traceback.get(i, 0).get_s_len();
+            best_s_score = yclip_score;
+            // tb.set_s_all(TB_YCLIP_PREFIX, prev_len + j as u32, i as u32, false);
+            tb.set_s_all(
+                TB_YCLIP_PREFIX,
+                prev_len,
+                self.contig_idx,
+                ContigPos::new(i),
+            );
+        }
+        (best_s_score, tb)
+    }
+
+    /// The insertion into the `k`th row of a block of rows from extending the insertion into the
+    /// row above the block (with the given score and length).
+    fn extended_insertion(&self, above_i: (i32, u32), k: usize) -> GapMove {
+        let extend = self.scoring.gap_extend;
+        GapMove {
+            score: floor_score(
+                above_i
+                    .0
+                    .saturating_add(extend.saturating_mul(k as i32 - 1)),
+            ) + extend,
+            tb: TB_INS,
+            len: above_i.1 + k as u32,
+        }
+    }
+
+    /// Fills the given column as [`Self::fill_column`] does, but in blocks of rows that are filled
+    /// in parallel (when the `threads` feature is enabled), except for the last row, which tracks
+    /// the x-suffix clip from the other rows.  The moves into each cell other than an insertion
+    /// depend only on the previous column, so are computed first for each block, along with the
+    /// insertions starting in the block.  Then the insertion into the row above each block is
+    /// carried from block to block, and extended into each block.  Since gaps are not opened with
+    /// a positive score, an insertion extended from the row above a cell scores at least as well
+    /// as an insertion opened after an insertion into the row above, so the result is the same as
+    /// filling the rows in order.
+    #[allow(clippy::too_many_arguments)]
+    fn fill_column_blocks<X: Index<usize, Output = u8> + Sync + ?Sized>(
+        &mut self,
+        x: &X,
+        m: usize,
+        n: usize,
+        j: usize,
+        prev: usize,
+        curr: usize,
+        q: u8,
+        xclip_score: i32,
+        jump_info: JumpInfo,
+        directional: bool,
+        block_rows: usize,
+    ) {
+        // The best forward jump into each row, from the rows before it
+        let forward_jumps: Vec<(i32, ContigPos)> = if directional {
+            let mut jumps = ForwardJumps::default();
+            (0..=m)
+                .map(|i| {
+                    if i == 0 {
+                        return jumps.any;
                     }
+                    self.add_forward_jumps(&mut jumps, i, j);
+                    self.forward_jump_into(&jumps, i)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let blocks: Vec<Range<usize>> = (1..m)
+            .step_by(block_rows)
+            .map(|start| start..(start + block_rows).min(m))
+            .collect();
+        let no_insertion = GapMove {
+            score: MIN_SCORE,
+            tb: TB_START,
+            len: 0,
+        };
+
+        let this = &*self;
+        let jump_into = |i: usize| {
+            if directional {
+                this.get_directional_jump_info(i, j, forward_jumps[i], jump_info)
+            } else {
+                jump_info
+            }
+        };
+        let moves_into = |i: usize| {
+            let (_, del_gap_open) = this.gap_opens(i);
+            this.cell_moves(m, i, j, prev, curr, x[i - 1], q, del_gap_open, jump_into(i))
+        };
+
+        // The moves into each cell other than an insertion, and the insertions starting in the
+        // block, which follow the best move into the row above the block other than an insertion
+        let blocks_moves = map_blocks(blocks.clone(), |rows| {
+            let mut above_s = if rows.start == 1 {
+                (this.S[curr][0], this.traceback.get(0, j).get_s())
+            } else {
+                let i = rows.start - 1;
+                let (score, tb) = this.best_move(&moves_into(i), no_insertion, xclip_score, i, j);
+                (floor_score(score), tb.get_s())
+            };
+            let mut above_i = (MIN_SCORE, 0);
+            rows.map(|i| {
+                let (ins_gap_open, _) = this.gap_opens(i);
+                let insertion = this.insertion_move(above_i, above_s, ins_gap_open);
+                let moves = moves_into(i);
+                let (score, tb) = this.best_move(&moves, no_insertion, xclip_score, i, j);
+                above_i = (floor_score(insertion.score), insertion.len);
+                above_s = (floor_score(score), tb.get_s());
+                (moves, insertion)
+            })
+            .collect::<Vec<_>>()
+        });
+
+        // The insertion into the row above each block
+        let mut above_i = (this.I[curr][0], this.traceback.get(0, j).get_i_len());
+        let carried: Vec<(i32, u32)> = blocks_moves
+            .iter()
+            .map(|block_moves| {
+                let carry = above_i;
+                let (_, last) = block_moves[block_moves.len() - 1];
+                let extended = this.extended_insertion(carry, block_moves.len());
+                let insertion = if extended.score >= last.score {
+                    extended
+                } else {
+                    last
                 };
-            if do_x_suffix_clip {
-                self.S[curr][m] = self.S[curr][i] + self.scoring.xclip_suffix;
-                let prev_s: crate::align::traceback::SValue = tb.get_s();
+                above_i = (floor_score(insertion.score), insertion.len);
+                carry
+            })
+            .collect();
+
+        // The best move into each cell, whether to suffix clip y from it, and the best cell of the
+        // block to suffix clip x from
+        let blocks_cells = map_blocks(
+            blocks
+                .iter()
+                .cloned()
+                .zip(blocks_moves)
+                .zip(carried)
+                .collect(),
+            |((rows, block_moves), carry)| {
+                let mut xclip_suffix: Option<(i32, u32, usize)> = None;
+                let cells = rows
+                    .zip(block_moves)
+                    .enumerate()
+                    .map(|(k, (i, (moves, insertion)))| {
+                        let extended = this.extended_insertion(carry, k + 1);
+                        let insertion = if extended.score >= insertion.score {
+                            extended
+                        } else {
+                            insertion
+                        };
+                        let (score, tb) = this.best_move(&moves, insertion, xclip_score, i, j);
+                        let score = floor_score(score);
+                        let clip_score = score + this.scoring.xclip_suffix;
+                        if xclip_suffix.is_none_or(|(best_score, best_len, _)| {
+                            replaces_suffix_clip(clip_score, tb.get_s_len(), best_score, best_len)
+                        }) {
+                            xclip_suffix = Some((clip_score, tb.get_s_len(), i));
+                        }
+                        let do_y_suffix_clip = !this.span_query
+                            && replaces_suffix_clip(
+                                score + this.scoring.yclip_suffix,
+                                tb.get_s_len(),
+                                this.Sn[i],
+                                this.y_suffix_clip_len(i, j, n),
+                            );
+                        let scores = (
+                            score,
+                            floor_score(insertion.score),
+                            floor_score(moves.d_score),
+                        );
+                        (scores, tb, do_y_suffix_clip)
+                    })
+                    .collect::<Vec<_>>();
+                (cells, xclip_suffix)
+            },
+        );
+
+        // Set the values in the matrices, in order
+        for (rows, (cells, xclip_suffix)) in blocks.into_iter().zip(blocks_cells) {
+            for (i, ((s_score, i_score, d_score), tb, do_y_suffix_clip)) in rows.zip(cells) {
+                self.S[curr][i] = s_score;
+                self.I[curr][i] = i_score;
+                self.D[curr][i] = d_score;
+                self.traceback.set(i, j, tb);
+                if do_y_suffix_clip {
+                    self.Sn[i] = s_score + self.scoring.yclip_suffix;
+                    self.Ly[i] = j;
+                    self.traceback.keep_y_suffix_clip_cell(i, tb);
+                }
+            }
+            let Some((score, len, i)) = xclip_suffix else {
+                continue;
+            };
+            let best_len = self.traceback.get(m, j).get_s_len();
+            if replaces_suffix_clip(score, len, self.S[curr][m], best_len) {
+                self.S[curr][m] = score;
+                let prev_s = self.traceback.get(i, j).get_s();
                 self.traceback.get_mut(m, j).set_s_all(
                     TB_XCLIP_SUFFIX,
-                    // prev_len + (m - i) as u32,
                     prev_s.len,
                     prev_s.idx,
-                    i as u32,
+                    ContigPos::new(i),
                 );
                 self.Lx[j] = m - i;
             }
-
-            // Track the score if we do suffix clip (y) from here
-            let do_y_suffix_clip =
-                match (self.S[curr][i] + self.scoring.yclip_suffix).cmp(&self.Sn[i]) {
-                    std::cmp::Ordering::Less => false,
-                    std::cmp::Ordering::Greater => true,
-                    std::cmp::Ordering::Equal => {
-                        // let left_len = tb.get_s_len() + (n - j) as u32;
-                        let left_len = tb.get_s_len();
-                        let right_len = self.traceback.get(i, n).get_s_len();
-                        left_len > right_len
-                    }
-                };
-
-            if do_y_suffix_clip {
-                self.Sn[i] = self.S[curr][i] + self.scoring.yclip_suffix;
-                self.Ly[i] = n - j;
-            }
-
-            self.traceback.set(i, j, tb);
         }
+
+        let jump_info = if directional {
+            self.get_directional_jump_info(m, j, forward_jumps[m], jump_info)
+        } else {
+            jump_info
+        };
+        self.fill_cell(m, n, m, j, prev, curr, x[m - 1], q, xclip_score, jump_info);
     }
 
     pub fn fill_last_column_and_end_clipping(&mut self, m: usize, n: usize) {
@@ -456,17 +1517,23 @@ impl<F: MatchFunc> SingleContigAligner<F> {
             let j: usize = n; // end of y
             let curr: usize = j % 2;
 
-            // jump over the remaining i bases in x
-            if self.S[curr][i] + self.scoring.jump_score_same_contig_and_strand > self.S[curr][m] {
-                self.S[curr][m] = self.S[curr][i] + self.scoring.jump_score_same_contig_and_strand;
-                let prev_s = self.traceback.get(i, j).get_s();
-                self.traceback
-                    .get_mut(m, j)
-                    .set_s_all(TB_XJUMP, prev_s.len, prev_s.idx, i as u32);
+            // jump over the remaining i bases in x, unless jumps into a clipped region are forbidden
+            let jump_score = self.scoring.forward_jump_score();
+            if !self.forbid_clip_jumps && self.S[curr][i] + jump_score > self.S[curr][m] {
+                self.S[curr][m] = self.S[curr][i] + jump_score;
+                // NB: the jump stays within this contig, whichever contig the cell came from
+                let prev_len = self.traceback.get(i, j).get_s_len();
+                self.traceback.get_mut(m, j).set_s_all(
+                    TB_XJUMP,
+                    prev_len,
+                    self.contig_idx,
+                    ContigPos::new(i),
+                );
             }
 
             // y-clip
             let do_y_suffix_clip = match (self.Sn[i]).cmp(&self.S[curr][i]) {
+                _ if self.span_query => false,
                 std::cmp::Ordering::Less => false,
                 std::cmp::Ordering::Greater => true,
                 std::cmp::Ordering::Equal => {
@@ -479,14 +1546,14 @@ impl<F: MatchFunc> SingleContigAligner<F> {
             if do_y_suffix_clip {
                 self.S[curr][i] = self.Sn[i];
                 // no need to set Ly[i] since it's already set in fill_last_column
-                let s_value = self.traceback.get(i, j - self.Ly[i]).get_s();
+                let s_value = self.traceback.y_suffix_clip_cell(i, self.Ly[i]).get_s();
                 let tb = self.traceback.get_mut(i, j);
                 tb.set_s_all(
                     TB_YCLIP_SUFFIX,
                     // s_value.len + self.Ly[i] as u32,
                     s_value.len,
                     s_value.idx,
-                    i as u32,
+                    ContigPos::new(i),
                 );
             }
 
@@ -511,7 +1578,7 @@ impl<F: MatchFunc> SingleContigAligner<F> {
                     // prev_len + (m - i) as u32,
                     prev_s.len,
                     prev_s.idx,
-                    i as u32,
+                    ContigPos::new(i),
                 );
             }
         }
@@ -521,7 +1588,7 @@ impl<F: MatchFunc> SingleContigAligner<F> {
         for i in 1..=m {
             let j = n;
             let curr = j % 2;
-            let i_score = self.S[curr][i - 1] + self.scoring.gap_open + self.scoring.gap_extend;
+            let i_score = self.S[curr][i - 1] + self.gap_opens(i).0 + self.scoring.gap_extend;
             if i_score > self.I[curr][i] {
                 self.I[curr][i] = i_score;
                 let s_value = self.traceback.get(i - 1, j).get_s();
@@ -537,7 +1604,7 @@ impl<F: MatchFunc> SingleContigAligner<F> {
                     TB_INS,
                     prev_len,
                     self.contig_idx,
-                    (i - 1) as u32,
+                    ContigPos::new(i - 1),
                 );
                 if self.S[curr][i] + self.scoring.xclip_suffix > self.S[curr][m] {
                     self.S[curr][m] = self.S[curr][i] + self.scoring.xclip_suffix;
@@ -547,13 +1614,170 @@ impl<F: MatchFunc> SingleContigAligner<F> {
                         // prev_len + ((m - i) as u32),
                         prev_len,
                         self.contig_idx,
-                        i as u32,
+                        ContigPos::new(i),
                     );
                 }
             }
         }
     }
 
+    /// Adds columns to the matrices so that they may hold the alignment of a query of length `n`,
+    /// keeping the columns filled so far (see [`Traceback::extend_cols`]).
+    pub fn extend_matrices(&mut self, n: usize) {
+        self.traceback.extend_cols(n);
+        self.Lx.resize(n + 1, 0);
+    }
+
+    /// Saves the state of column `n` before filling it as the last column and applying the end
+    /// clipping (see [`Self::fill_last_column_and_end_clipping`]), so that it may be restored
+    /// with [`Self::restore_last_column`] and the matrices extended to a longer query.
+    pub fn save_last_column(&self, m: usize, n: usize) -> LastColumnState {
+        let curr = n % 2;
+        LastColumnState {
+            s: self.S[curr].clone(),
+            i: self.I[curr].clone(),
+            d: self.D[curr].clone(),
+            sn: self.Sn.clone(),
+            ly: self.Ly.clone(),
+            lx: self.Lx[n],
+            band: self.band,
+            cells: (0..=m).map(|i| *self.traceback.get(i, n)).collect(),
+        }
+    }
+
+    /// Restores the state of column `n` saved with [`Self::save_last_column`].
+    pub fn restore_last_column(&mut self, n: usize, state: LastColumnState) {
+        let curr = n % 2;
+        self.S[curr] = state.s;
+        self.I[curr] = state.i;
+        self.D[curr] = state.d;
+        self.Sn = state.sn;
+        self.Ly = state.ly;
+        self.Lx[n] = state.lx;
+        self.band = state.band;
+        for (i, cell) in state.cells.into_iter().enumerate() {
+            self.traceback.set(i, n, cell);
+        }
+    }
+
+    /// Sets the number of columns between the checkpoints of the traceback, or `None` to keep the
+    /// whole traceback, taking effect from the next [`Self::init_matrices`] (see
+    /// [`Traceback::set_checkpoint_interval`]).  When checkpointing, the state of each checkpoint
+    /// column must be saved with [`Self::checkpoint_column`] as the columns are filled, and that
+    /// of the last column with [`Self::save_final_column`] once filled.
+    /// Moves the buffers of the traceback and of the columns of scores to the pool, for reuse by
+    /// the aligners of other contigs (see [`Self::acquire_buffers`]).  The aligner has no
+    /// traceback until its matrices are next initialized.
+    pub fn release_buffers(&mut self, pool: &mut MatrixPool) {
+        pool.put_cells(self.traceback.take_cells());
+        for scores in self
+            .I
+            .iter_mut()
+            .chain(self.D.iter_mut())
+            .chain(self.S.iter_mut())
+            .chain([&mut self.Sn])
+        {
+            pool.put_scores(std::mem::take(scores));
+        }
+    }
+
+    /// Takes the buffers released by [`Self::release_buffers`] back from the pool, fitting the
+    /// matrices for aligning a query of length `n` to a contig of length `m`, before
+    /// [`Self::init_matrices`].  The buffers that were not released are kept.
+    pub fn acquire_buffers(&mut self, pool: &mut MatrixPool, m: usize, n: usize) {
+        if self.traceback.capacity() == 0 {
+            self.traceback.set_cells(pool.take_cells((m + 1) * (n + 1)));
+        }
+        for scores in self
+            .I
+            .iter_mut()
+            .chain(self.D.iter_mut())
+            .chain(self.S.iter_mut())
+            .chain([&mut self.Sn])
+        {
+            if scores.capacity() == 0 {
+                *scores = pool.take_scores(m + 1);
+            }
+        }
+    }
+
+    /// Sets the order in which the cells of the traceback are stored, taking effect from the next
+    /// [`Self::init_matrices`] (see [`Traceback::set_layout`]).
+    pub fn set_traceback_layout(&mut self, layout: TracebackLayout) {
+        self.traceback.set_layout(layout);
+    }
+
+    pub fn set_traceback_checkpoint_interval(&mut self, interval: Option<usize>) {
+        self.traceback.set_checkpoint_interval(interval);
+        self.checkpoints.clear();
+        self.final_column = None;
+    }
+
+    /// The estimated number of bytes of the matrices for aligning a query of length `n` to a
+    /// contig of length `m`, when checkpointing the traceback with the given interval (see
+    /// [`Self::set_traceback_checkpoint_interval`]): the cells of the traceback, the two columns
+    /// of scores of each of `I`, `D`, and `S`, and the state kept for each row and column.
+    pub fn estimate_memory(m: usize, n: usize, interval: Option<usize>) -> usize {
+        let rows = m + 1;
+        let cell = size_of::<Cell>();
+        let row_state = 7 * size_of::<i32>() + size_of::<usize>() + size_of::<(i32, ContigPos)>();
+        let traceback = match interval {
+            None => rows * (n + 1) * cell,
+            Some(interval) => {
+                // The window, first, and last columns, the cells kept for suffix clipping, and
+                // the state of each checkpoint column and of the last column
+                let checkpoints = n.div_ceil(interval).max(1) + 1;
+                let column_state = cell + 4 * size_of::<i32>() + size_of::<usize>();
+                rows * ((interval + 4) * cell + checkpoints * column_state)
+            }
+        };
+        traceback + rows * row_state + (n + 1) * size_of::<usize>()
+    }
+
+    /// The traceback checkpoint interval with the least estimated memory for aligning a query of
+    /// length `n` (see [`Self::estimate_memory`]), which is the same for every contig length.
+    pub fn min_memory_checkpoint_interval(n: usize) -> usize {
+        let cell = size_of::<Cell>();
+        let column_state = cell + 4 * size_of::<i32>() + size_of::<usize>();
+        // The memory is about `interval * cell + (n / interval) * column_state` per row
+        let interval = ((n * column_state) as f64 / cell as f64).sqrt().round() as usize;
+        (interval.saturating_sub(1).max(1)..=interval.max(1) + 1)
+            .min_by_key(|&interval| Self::estimate_memory(0, n, Some(interval)))
+            .unwrap()
+    }
+
+    /// Saves the state of the checkpoint column `j` once filled, and moves the window of the
+    /// traceback to start at the column, so that the following columns may be filled.
+    pub fn checkpoint_column(&mut self, m: usize, j: usize) {
+        if j == 0 {
+            self.checkpoints.clear();
+        } else {
+            self.traceback.set_window_start(j, true);
+        }
+        self.checkpoints.push(self.save_last_column(m, j));
+    }
+
+    /// Saves the state of the last column (`n`) once filled, including the end clipping.
+    pub fn save_final_column(&mut self, m: usize, n: usize) {
+        self.final_column = Some(self.save_last_column(m, n));
+    }
+
+    /// Restores the state of the given checkpoint (see [`Self::checkpoint_column`]), at column
+    /// `j`, so that the columns after it may be filled again.
+    pub fn restore_checkpoint(&mut self, checkpoint: usize, j: usize) {
+        self.traceback.set_window_start(j, false);
+        self.restore_last_column(j, self.checkpoints[checkpoint].clone());
+    }
+
+    /// Restores the state of the last column (`n`) saved with [`Self::save_final_column`].
+    pub fn restore_final_column(&mut self, n: usize) {
+        let state = self
+            .final_column
+            .clone()
+            .expect("The last column was not saved");
+        self.restore_last_column(n, state);
+    }
+
     /// Create new aligner instance with given gap open and gap extend penalties
     /// and the score function.
     ///
@@ -563,7 +1787,7 @@ impl<F: MatchFunc> SingleContigAligner<F> {
     /// * `gap_extend` - the score for extending a gap (should be negative)
     /// * `jump_score` - the score for jumping back in the query (should not be positive)
     /// * `match_fn` - function that returns the score for substitutions
-    ///    (see also [`bio::alignment::pairwise::Scoring`](struct.Scoring.html))
+    ///   (see also [`bio::alignment::pairwise::Scoring`](struct.Scoring.html))
     pub fn new(gap_open: i32, gap_extend: i32, jump_score: i32, match_fn: F) -> Self {
         SingleContigAligner::with_capacity(
             DEFAULT_ALIGNER_CAPACITY,
@@ -586,7 +1810,7 @@ impl<F: MatchFunc> SingleContigAligner<F> {
     /// * `gap_extend` - the score for extending a gap (should be negative)
     /// * `jump_score` - the score for jumping back in the query (should not be positive)
     /// * `match_fn` - function that returns the score for substitutions
-    ///    (see also [`bio::alignment::pairwise::Scoring`](struct.Scoring.html))
+    ///   (see also [`bio::alignment::pairwise::Scoring`](struct.Scoring.html))
     pub fn with_capacity(
         m: usize,
         n: usize,
@@ -595,21 +1819,8 @@ impl<F: MatchFunc> SingleContigAligner<F> {
         jump_score: i32,
         match_fn: F,
     ) -> Self {
-        assert!(gap_open <= 0, "gap_open can't be positive");
-        assert!(gap_extend <= 0, "gap_extend can't be positive");
-
-        SingleContigAligner {
-            I: [Vec::with_capacity(m + 1), Vec::with_capacity(m + 1)],
-            D: [Vec::with_capacity(m + 1), Vec::with_capacity(m + 1)],
-            S: [Vec::with_capacity(m + 1), Vec::with_capacity(m + 1)],
-            Lx: Vec::with_capacity(n + 1),
-            Ly: Vec::with_capacity(m + 1),
-            Sn: Vec::with_capacity(m + 1),
-            traceback: Traceback::with_capacity(m, n),
-            scoring: Scoring::with_jump_score(gap_open, gap_extend, jump_score, match_fn),
-            contig_idx: 0,
-            circular: false,
-        }
+        let scoring = Scoring::with_jump_score(gap_open, gap_extend, jump_score, match_fn);
+        SingleContigAligner::with_capacity_and_scoring(m, n, scoring)
     }
 
     /// Create new aligner instance with given the scoring struct
@@ -635,8 +1846,91 @@ impl<F: MatchFunc> SingleContigAligner<F> {
     /// * `n` - the expected size of y
     /// * `scoring` - the scoring struct
     pub fn with_capacity_and_scoring(m: usize, n: usize, scoring: Scoring<F>) -> Self {
+        Self::validate_scoring(&scoring);
+        SingleContigAligner {
+            I: [Vec::with_capacity(m + 1), Vec::with_capacity(m + 1)],
+            D: [Vec::with_capacity(m + 1), Vec::with_capacity(m + 1)],
+            S: [Vec::with_capacity(m + 1), Vec::with_capacity(m + 1)],
+            Lx: Vec::with_capacity(n + 1),
+            Ly: Vec::with_capacity(m + 1),
+            Sn: Vec::with_capacity(m + 1),
+            Jb: Vec::new(),
+            homopolymer_runs: Vec::new(),
+            profile: None,
+            adaptive_band_width: None,
+            band: AdaptiveBand::default(),
+            parallel_block_rows: None,
+            span_query: false,
+            forbid_clip_jumps: false,
+            mask_diagonal: false,
+            jump_site_scores: Vec::new(),
+            free_jump_sites: Vec::new(),
+            free_jump_into: JumpInfo {
+                score: MIN_SCORE,
+                ..JumpInfo::default()
+            },
+            splice_motifs: Vec::new(),
+            annotated_splice_sites: Vec::new(),
+            scaffold_gap_lens: Vec::new(),
+            traceback: Traceback::with_capacity(m, n),
+            checkpoints: Vec::new(),
+            final_column: None,
+            scoring,
+            contig_idx: ContigIdx::new(0),
+            circular: false,
+            backend: AlignerBackend::default(),
+        }
+    }
+
+    /// Panics if the scores are positive where they must not be, or so negative that the scores
+    /// in the alignment matrices could underflow.
+    fn validate_scoring(scoring: &Scoring<F>) {
         assert!(scoring.gap_open <= 0, "gap_open can't be positive");
         assert!(scoring.gap_extend <= 0, "gap_extend can't be positive");
+        // Bound the penalties so that the scores in the alignment matrices can't underflow
+        let penalties = [
+            ("gap_open", scoring.gap_open),
+            ("gap_extend", scoring.gap_extend),
+            (
+                "jump_score_same_contig_and_strand",
+                scoring.jump_score_same_contig_and_strand,
+            ),
+            (
+                "jump_score_same_contig_opposite_strand",
+                scoring.jump_score_same_contig_opposite_strand,
+            ),
+            ("jump_score_inter_contig", scoring.jump_score_inter_contig),
+            ("jump_score_forward", scoring.forward_jump_score()),
+            ("jump_score_backward", scoring.backward_jump_score()),
+            ("splice_jump_score", scoring.splice_jump_score.unwrap_or(0)),
+            (
+                "homopolymer_gap_open",
+                scoring.homopolymer_gap_open.unwrap_or(0),
+            ),
+        ];
+        for (name, penalty) in penalties {
+            assert!(
+                penalty >= MIN_PENALTY,
+                "{name} can't be less than {MIN_PENALTY}: {penalty}"
+            );
+        }
+        for (p, q) in b"ACGT".iter().cartesian_product(b"ACGT") {
+            assert!(
+                scoring.match_fn.score(*p, *q) >= MIN_PENALTY,
+                "Substitution scores can't be less than {MIN_PENALTY}"
+            );
+        }
+        for clip in [
+            scoring.xclip_prefix,
+            scoring.xclip_suffix,
+            scoring.yclip_prefix,
+            scoring.yclip_suffix,
+        ] {
+            assert!(
+                clip >= MIN_SCORE,
+                "Clipping penalties can't be less than {MIN_SCORE}: {clip}"
+            );
+        }
         assert!(
             scoring.xclip_prefix <= 0,
             "Clipping penalty (x prefix) can't be positive"
@@ -653,19 +1947,13 @@ impl<F: MatchFunc> SingleContigAligner<F> {
             scoring.yclip_suffix <= 0,
             "Clipping penalty (y suffix) can't be positive"
         );
+    }
 
-        SingleContigAligner {
-            I: [Vec::with_capacity(m + 1), Vec::with_capacity(m + 1)],
-            D: [Vec::with_capacity(m + 1), Vec::with_capacity(m + 1)],
-            S: [Vec::with_capacity(m + 1), Vec::with_capacity(m + 1)],
-            Lx: Vec::with_capacity(n + 1),
-            Ly: Vec::with_capacity(m + 1),
-            Sn: Vec::with_capacity(m + 1),
-            traceback: Traceback::with_capacity(m, n),
-            scoring,
-            contig_idx: 0,
-            circular: false,
-        }
+    /// Updates the scoring of the aligner in place, for example to sweep over parameters without
+    /// rebuilding the aligner, then validates it as when creating the aligner.
+    pub fn update_scoring(&mut self, update: impl FnOnce(&mut Scoring<F>)) {
+        update(&mut self.scoring);
+        Self::validate_scoring(&self.scoring);
     }
 
     /// Sets the value for treating x as circular, allowing for a zero-cost jump to the start of x.
@@ -673,16 +1961,22 @@ impl<F: MatchFunc> SingleContigAligner<F> {
         self.circular = circular;
     }
 
-    /// Gets the best jump score and x-index for the jump
-    pub fn get_jump_info(&self, m: usize, j: usize, jump_score: i32) -> JumpInfo {
-        let cur = j % 2;
+    /// Sets the backend used to compute global alignments.
+    pub fn set_backend(&mut self, backend: AlignerBackend) {
+        self.backend = backend;
+    }
 
-        let mut best_jump_score = self.S[cur][0] + jump_score;
+    /// Gets the best jump score and x-index for the jump, where the jumps from the free positions
+    /// of x are scored without the jump score (see [`Self::set_free_jump_sites`]).
+    pub fn get_jump_info(&self, m: usize, j: usize, jump_score: i32) -> JumpInfo {
+        let mut best_jump_score = self.jump_from(0, j, jump_score).unwrap_or(MIN_SCORE);
         let mut best_jump_from = 0;
         for k in 1..=m {
-            if best_jump_score < self.S[cur][k] + jump_score {
-                best_jump_score = self.S[cur][k] + jump_score;
-                best_jump_from = k;
+            if let Some(score) = self.jump_from(k, j, jump_score) {
+                if best_jump_score < score {
+                    best_jump_score = score;
+                    best_jump_from = k;
+                }
             }
         }
 
@@ -690,12 +1984,27 @@ impl<F: MatchFunc> SingleContigAligner<F> {
 
         JumpInfo {
             score: best_jump_score,
-            from: best_jump_from as u32,
+            from: ContigPos::new(best_jump_from),
             idx: self.contig_idx,
             len: best_jump_len,
         }
     }
 
+    /// Gets the best jump score and x-index for a jump within the same contig and strand, for
+    /// [`Self::fill_column`].  When jumps are scored by direction, the best jump depends on the
+    /// position jumped to (see [`Self::has_directional_jumps`]), and so no jump is returned here.
+    pub fn get_same_strand_jump_info(&self, m: usize, j: usize) -> JumpInfo {
+        if self.has_directional_jumps() {
+            JumpInfo {
+                score: MIN_SCORE,
+                idx: self.contig_idx,
+                ..JumpInfo::default()
+            }
+        } else {
+            self.get_jump_info(m, j, self.scoring.jump_score_same_contig_and_strand)
+        }
+    }
+
     /// The core function to compute the alignment
     ///
     /// # Arguments
@@ -704,8 +2013,16 @@ impl<F: MatchFunc> SingleContigAligner<F> {
     /// * `y` - Textslice
     pub fn custom(&mut self, x: TextSlice<'_>, y: TextSlice<'_>) -> Alignment {
         let (m, n) = (x.len(), y.len());
+        assert!(
+            self.profile
+                .as_ref()
+                .is_none_or(|profile| profile.len() == m),
+            "The profile length does not match the length of x"
+        );
 
         self.init_matrices(m, n);
+        self.set_homopolymer_runs(x);
+        self.set_splice_motifs(x);
 
         for j in 1..=n {
             let curr = j % 2;
@@ -715,11 +2032,13 @@ impl<F: MatchFunc> SingleContigAligner<F> {
             self.init_column(j, curr, m, n);
 
             // Get the best jump score and x-index for the jump
-            let jump_info =
-                self.get_jump_info(m, j - 1, self.scoring.jump_score_same_contig_and_strand);
+            let jump_info = self.get_same_strand_jump_info(m, j - 1);
+            if self.has_free_jump_sites() {
+                self.free_jump_into = self.get_jump_info(m, j - 1, 0);
+            }
 
             // Fill the column
-            self.fill_column(x, y, m, n, j, prev, curr, jump_info);
+            self.fill_column(x, y, m, n, j, prev, curr, jump_info, true);
         }
 
         self.fill_last_column_and_end_clipping(m, n);
@@ -729,8 +2048,20 @@ impl<F: MatchFunc> SingleContigAligner<F> {
     }
 
     /// Calculate global alignment of x against y.
+    ///
+    /// When the backend is [`AlignerBackend::Wavefront`], the alignment is computed with the
+    /// wavefront algorithm, and so jumps are not considered, unless aligning to a profile (see
+    /// [`Self::set_profile`]) or masking the diagonal (see [`Self::set_mask_diagonal`]), which
+    /// the wavefront algorithm does not support.
     #[allow(dead_code)]
     pub fn global(&mut self, x: TextSlice<'_>, y: TextSlice<'_>) -> Alignment {
+        if self.backend == AlignerBackend::Wavefront
+            && self.profile.is_none()
+            && !self.mask_diagonal
+        {
+            return wavefront_global(x, y, &self.scoring, self.contig_idx.get());
+        }
+
         // Store the current clip penalties
         let clip_penalties = [
             self.scoring.xclip_prefix,
@@ -879,9 +2210,12 @@ pub mod tests {
     use itertools::Itertools;
     use rstest::rstest;
 
-    use crate::align::alignment::Alignment;
+    use crate::align::{
+        aligners::constants::{AlignmentMode, MIN_PENALTY},
+        alignment::Alignment,
+    };
 
-    use super::SingleContigAligner;
+    use super::{AdaptiveBand, SingleContigAligner};
 
     /// Upper-cases and remove display-related characters from a string.
     fn s(bases: &str) -> Vec<u8> {
@@ -1270,6 +2604,7 @@ pub mod tests {
     }
 
     #[rstest]
+    #[allow(clippy::identity_op)]
     fn test_global_leading_and_trailing_deletions() {
         let x = s("-------------------GGTTTTAGAGCTAGAAATAGCAAGTTAAAATAAGGCTAGTCCGTTATCAACTTG---------------------------");
         let y = s("AGGGCTATAGACTGCTAGAGGTTTTAGAGCTAGAAATAGCAAGTTAAAATAAGGCTAGTCCGTTATCAACTTGAAATGAGCTATTAGTCATGACGCTTTT");
@@ -1750,6 +3085,251 @@ pub mod tests {
         assert_alignment(&alignment, 0, 8, 0, 8, 8 - 1 - 1 - 1, "2=2J2=4j2=2J2=", 8);
     }
 
+    #[rstest]
+    #[case::same(None, None, 8 - 1 - 1 - 1)]
+    #[case::forward(Some(-2), None, 8 - 2 - 1 - 2)]
+    #[case::backward(None, Some(-3), 8 - 1 - 3 - 1)]
+    #[case::both(Some(-2), Some(-3), 8 - 2 - 3 - 2)]
+    fn test_global_short_jumps_by_direction(
+        #[case] forward: Option<i32>,
+        #[case] backward: Option<i32>,
+        #[case] score: i32,
+    ) {
+        let x = s("AAGGCCTT");
+        let y = s("AACCGGTT");
+        let match_fn = MatchParams::new(1, -100_000);
+        let mut aligner = SingleContigAligner::new(-100_000, -100_000, -1, match_fn);
+        aligner.scoring = aligner.scoring.set_jump_score_directions(forward, backward);
+        let alignment = aligner.global(&x, &y);
+        assert_alignment(&alignment, 0, 8, 0, 8, score, "2=2J2=4j2=2J2=", 8);
+    }
+
+    #[rstest]
+    fn test_global_penalize_jump_back() {
+        // Jumping back to the start of x would give 16 matches and a jump (-10), but with a backward
+        // jump score of -30, is worse than deleting the second half of y (-5 - 8)
+        let x = s("GATCGATC________");
+        let y = s("GATCGATCGATCGATC");
+        let mut aligner = SingleContigAligner::default();
+        aligner.scoring = aligner.scoring.set_jump_score_directions(None, Some(-30));
+        let alignment = aligner.global(&x, &y);
+        assert_alignment(&alignment, 0, 8, 0, 16, 16 - 8 - 5 - 8, "8D8=", 16);
+    }
+
+    #[rstest]
+    #[case::none(None, 3, 22 - 5 - 1)]
+    #[case::homopolymer(Some(-1), 3, 22 - 1 - 1)]
+    #[case::homopolymer_too_short(Some(-1), 7, 22 - 5 - 1)]
+    fn test_homopolymer_gap_open(
+        #[case] homopolymer_gap_open: Option<i32>,
+        #[case] homopolymer_min_length: usize,
+        #[case] score: i32,
+    ) {
+        // One of the six bases of the homopolymer in x is missing in y, then an extra base is in y
+        let x = s("ACGTACGTAAAAAACGTACGTAC");
+        let y = s("ACGTACGTAAAAA-CGTACGTAC");
+        let mut aligner = SingleContigAligner::default();
+        aligner.scoring = aligner
+            .scoring
+            .set_homopolymer_gap_open(homopolymer_gap_open, homopolymer_min_length);
+        let alignment = aligner.global(&x, &y);
+        assert_alignment(&alignment, 0, 23, 0, 22, score, "8=1I14=", 23);
+
+        let x = s("ACGTACGTAAAAAA-CGTACGTAC");
+        let y = s("ACGTACGTAAAAAAACGTACGTAC");
+        let alignment = aligner.global(&x, &y);
+        assert_alignment(&alignment, 0, 23, 0, 24, score + 1, "8=1D15=", 24);
+    }
+
+    #[rstest]
+    #[case::local(AlignmentMode::Local)]
+    #[case::targetlocal(AlignmentMode::TargetLocal)]
+    fn test_extreme_penalties(#[case] mode: AlignmentMode) {
+        // long gaps with the most negative penalties would underflow if not floored
+        let x = s("CCCCCCCCCCCCCCCCCCCC ACGTACGT CCCCCCCCCCCCCCCCCCCC");
+        let y = s("ACGTACGT");
+        let match_fn = MatchParams::new(1, MIN_PENALTY);
+        let mut aligner = SingleContigAligner::new(MIN_PENALTY, MIN_PENALTY, MIN_PENALTY, match_fn);
+        let alignment = match mode {
+            AlignmentMode::Local => aligner.local(&x, &y),
+            AlignmentMode::TargetLocal => aligner.targetlocal(&x, &y),
+            _ => unreachable!(),
+        };
+        assert_alignment(&alignment, 20, 28, 0, 8, 8, "8=", 8);
+    }
+
+    #[rstest]
+    fn test_span_query() {
+        // the flanks of y are clipped, unless y must be spanned
+        let x = s("CCCCCCCCCC ACGTACGTAC CCCCCCCCCC");
+        let y = s("TTTT ACGTACGTAC TTTT");
+        let match_fn = MatchParams::new(1, -100);
+        let mut aligner = SingleContigAligner::new(-100, -100, -1000, match_fn);
+        let alignment = aligner.local(&x, &y);
+        assert_alignment(&alignment, 10, 20, 4, 14, 10, "10=", 10);
+
+        aligner.set_span_query(true);
+        let alignment = aligner.local(&x, &y);
+        assert_alignment(&alignment, 6, 24, 0, 18, 10 - 800, "4X10=4X", 18);
+    }
+
+    #[rstest]
+    #[case::prefix("TTTTTTTTTT ACGTACGTAC", false, 0, "10J10=")]
+    #[case::prefix_forbidden("TTTTTTTTTT ACGTACGTAC", true, -1 - 10 + 9, "1X10J9=")]
+    #[case::suffix("ACGTACGTAC TTTTTTTTTT", false, 0, "10=10J")]
+    #[case::suffix_forbidden("ACGTACGTAC TTTTTTTTTT", true, 9 - 10 - 1, "9=10J1X")]
+    fn test_forbid_clip_jumps(
+        #[case] x: &str,
+        #[case] forbid_clip_jumps: bool,
+        #[case] score: i32,
+        #[case] cigar: &str,
+    ) {
+        // jumping over the flank of x scores better than inserting it, but when jumps into or out
+        // of clipped regions are forbidden, the alignment must align a base before jumping over it
+        let x = s(x);
+        let y = s("ACGTACGTAC");
+        let mut aligner = SingleContigAligner::default();
+        aligner.set_forbid_clip_jumps(forbid_clip_jumps);
+        let alignment = aligner.global(&x, &y);
+        assert_alignment(&alignment, 0, 20, 0, 10, score, cigar, 10);
+    }
+
+    #[rstest]
+    fn test_extreme_penalties_global() {
+        // the long gaps are floored, and so scored below jumping over the flanks
+        let x = s("CCCCCCCCCCCCCCCCCCCC ACGTACGT CCCCCCCCCCCCCCCCCCCC");
+        let y = s("ACGTACGT");
+        let match_fn = MatchParams::new(1, MIN_PENALTY);
+        let mut aligner = SingleContigAligner::new(MIN_PENALTY, MIN_PENALTY, MIN_PENALTY, match_fn);
+        let alignment = aligner.global(&x, &y);
+        assert_alignment(&alignment, 0, 48, 0, 8, 2 * MIN_PENALTY + 8, "20J8=20J", 8);
+    }
+
+    #[rstest]
+    #[should_panic(expected = "gap_extend can't be less than -429496729: -429496730")]
+    fn test_penalty_too_negative() {
+        let match_fn = MatchParams::new(1, -1);
+        SingleContigAligner::new(-1, MIN_PENALTY - 1, -1, match_fn);
+    }
+
+    #[rstest]
+    #[case::identical("ACGTTGCAAGTCCGATAGCTTACGGATCCAGT", "ACGTTGCAAGTCCGATAGCTTACGGATCCAGT")]
+    #[case::mismatch("ACGTTGCAAGTCCGATAGCTTACGGATCCAGT", "ACGTTGCAAGTCCGTTAGCTTACGGATCCAGT")]
+    #[case::insertion("ACGTTGCAAGTCCGATAGCTTACGGATCCAGT", "ACGTTGCAAGTC--ATAGCTTACGGATCCAGT")]
+    #[case::deletion(
+        "ACGTTGCAAGTCC--GATAGCTTACGGATCCAGT",
+        "ACGTTGCAAGTCCTTGATAGCTTACGGATCCAGT"
+    )]
+    #[case::flanked(
+        "GGGGGGGGGG ACGTTGCAAGTCCGATAGCTTACG GGGGGGGGGG",
+        "ACGTTGCAAGTCCGATAGCTTACG"
+    )]
+    fn test_adaptive_band(#[case] x: &str, #[case] y: &str) {
+        let (x, y) = (s(x), s(y));
+        let mut aligner = SingleContigAligner::default();
+        let expected = aligner.local(&x, &y);
+        aligner.set_adaptive_band_width(Some(3));
+        let alignment = aligner.local(&x, &y);
+        assert_eq!(alignment, expected);
+    }
+
+    #[rstest]
+    fn test_adaptive_band_delays_jump() {
+        let x = s("ACGTTGCAAGTCCGATAGCTTACGGATCCAGTCATG");
+        let y = s("ACGTTGCAAGTCCG------TACGGATCCAGTCATG");
+        let mut aligner = SingleContigAligner::default();
+        let alignment = aligner.local(&x, &y);
+        assert_alignment(&alignment, 0, 36, 0, 30, 30 - 10, "14=6J16=", 30);
+        // the column after the jump is banded, since the best score was increasing, so the jump
+        // is only found from the next column, after a mismatch
+        aligner.set_adaptive_band_width(Some(3));
+        let alignment = aligner.local(&x, &y);
+        assert_alignment(&alignment, 0, 36, 0, 30, 18, "14=1X6J15=", 30);
+    }
+
+    #[rstest]
+    fn test_adaptive_band_follows_best_cell() {
+        let x = s("GGGGGGGGGG ACGTTGCAAGTCCGATAGCTTACG GGGGGGGGGG");
+        let y = s("ACGTTGCAAGTCCGATAGCTTACG");
+        let mut aligner = SingleContigAligner::default();
+        aligner.set_adaptive_band_width(Some(3));
+        aligner.local(&x, &y);
+        // the last column is centered on the end of the alignment
+        assert_eq!(
+            aligner.band,
+            AdaptiveBand {
+                center: 34,
+                best_score: 24,
+                peak_score: 24,
+                full_width: false
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::default(None, None, None, false)]
+    #[case::by_direction(Some(-2), Some(-30), None, false)]
+    #[case::homopolymer(None, None, Some(-1), false)]
+    #[case::circular(None, None, None, true)]
+    fn test_parallel_block_rows(
+        #[case] forward: Option<i32>,
+        #[case] backward: Option<i32>,
+        #[case] homopolymer_gap_open: Option<i32>,
+        #[case] circular: bool,
+    ) {
+        let pairs = [
+            (
+                "ACGTTGCAAGTCCGATAGCTTACGGATCCAGTCATG",
+                "ACGTTGCAAGTCCG------TACGGATCCAGTCATG",
+            ),
+            (
+                "ACGTTGCAAGTC--ATAGCTTACGGATCC",
+                "ACGTTGCAAGTCCGATAGCTTACGGATCC",
+            ),
+            ("TTTTTGATCGAT________CTTTTT", "TTTTTGATCGATCGATCGATCTTTTT"),
+            ("GATCGATC________", "GATCGATCGATCGATC"),
+            ("AAGGCCTTAAAAAAACGT", "AACCGGTTAAAACGT"),
+            ("GGTTAACC", "AACCGGTT"),
+            ("ACGTACGTAAAAAACGTACGTAC", "ACGTACGTAAAAA-CGTACGTAC"),
+        ];
+        let modes = [
+            AlignmentMode::Local,
+            AlignmentMode::QueryLocal,
+            AlignmentMode::TargetLocal,
+            AlignmentMode::Global,
+            AlignmentMode::SemiGlobal,
+        ];
+        for ((x, y), mode, block_rows) in pairs
+            .iter()
+            .cartesian_product(modes)
+            .cartesian_product([1, 2, 5])
+            .map(|((p, m), b)| (p, m, b))
+        {
+            let (x, y) = (s(x), s(y));
+            let mut aligner = SingleContigAligner::default();
+            aligner.scoring = aligner
+                .scoring
+                .set_jump_score_directions(forward, backward)
+                .set_homopolymer_gap_open(homopolymer_gap_open, 3);
+            aligner.set_circular(circular);
+            aligner.set_span_query(mode == AlignmentMode::SemiGlobal);
+            let align = |aligner: &mut SingleContigAligner<MatchParams>| match mode {
+                AlignmentMode::Local | AlignmentMode::SemiGlobal => aligner.local(&x, &y),
+                AlignmentMode::QueryLocal => aligner.querylocal(&x, &y),
+                AlignmentMode::TargetLocal => aligner.targetlocal(&x, &y),
+                _ => aligner.global(&x, &y),
+            };
+            let expected = align(&mut aligner);
+            let (expected_s, expected_sn) = (aligner.S.clone(), aligner.Sn.clone());
+            aligner.set_parallel_block_rows(Some(block_rows));
+            let alignment = align(&mut aligner);
+            let message = format!("{mode:?} in blocks of {block_rows} rows: {alignment}");
+            assert_eq!(alignment, expected, "{message}");
+            assert_eq!(aligner.S, expected_s, "{message}");
+            assert_eq!(aligner.Sn, expected_sn, "{message}");
+        }
+    }
+
     #[rstest]
     fn test_local_circular_jump() {
         let x = s("AACCGGTT");
@@ -0,0 +1,385 @@
+//! Gap-affine wavefront alignment (WFA), as described in Marco-Sola et al. (2021), "Fast gap-affine
+//! pairwise alignment using the wavefront algorithm".
+//!
+//! The wavefront algorithm computes the global alignment in `O(ns)` time, where `n` is the
+//! sequence length and `s` is the alignment penalty, and so is much faster than the full dynamic
+//! programming for high-identity sequences.  It does not support jumps or clipping.
+//!
+//! WFA minimizes a penalty (with a zero-cost match), whereas the aligners maximize a score.  The
+//! scores are converted to penalties following Eizenga & Paten (2022): for a match score `a`,
+//! mismatch score `b`, gap open `o`, and gap extend `e`, the penalties are `2(a - b)` for a
+//! mismatch, `-2o` for a gap open, and `a - 2e` for a gap extend.  The score of the global
+//! alignment of sequences of length `m` and `n` with penalty `p` is then `(a(m + n) - p) / 2`.
+
+use crate::align::{
+    aligners::constants::{AlignmentMode, AlignmentOperation},
+    alignment::Alignment,
+    scoring::Scoring,
+};
+use bio::alignment::pairwise::MatchFunc;
+
+/// Value to use for an offset that cannot be reached.
+const NULL_OFFSET: i32 = i32::MIN / 2;
+
+/// The wavefront penalties derived from a [`Scoring`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Penalties {
+    match_score: i32,
+    mismatch: usize,
+    gap_open: usize,
+    gap_extend: usize,
+}
+
+impl Penalties {
+    fn from_scoring<F: MatchFunc>(scoring: &Scoring<F>) -> Self {
+        let match_score = scoring.match_fn.score(b'A', b'A');
+        let mismatch_score = scoring.match_fn.score(b'A', b'C');
+        assert!(
+            match_score > 0 && mismatch_score < match_score,
+            "The wavefront alignment requires a positive match score greater than the mismatch score"
+        );
+        assert!(
+            scoring.gap_open <= 0 && scoring.gap_extend <= 0,
+            "The wavefront alignment requires gap open and extend scores that are not positive"
+        );
+        Self {
+            match_score,
+            mismatch: (2 * (match_score - mismatch_score)) as usize,
+            gap_open: (-2 * scoring.gap_open) as usize,
+            gap_extend: (match_score - 2 * scoring.gap_extend) as usize,
+        }
+    }
+}
+
+/// The match (M), insertion (I), and deletion (D) wavefronts for a single penalty, storing the
+/// furthest reaching offset in `y` for each diagonal `k = j - i` in `[lo, hi]`.
+struct Wavefront {
+    lo: i32,
+    hi: i32,
+    m: Vec<i32>,
+    i: Vec<i32>,
+    d: Vec<i32>,
+}
+
+impl Wavefront {
+    fn new(lo: i32, hi: i32) -> Self {
+        let len = (hi - lo + 1) as usize;
+        Self {
+            lo,
+            hi,
+            m: vec![NULL_OFFSET; len],
+            i: vec![NULL_OFFSET; len],
+            d: vec![NULL_OFFSET; len],
+        }
+    }
+
+    #[inline]
+    fn get(values: &[i32], lo: i32, hi: i32, k: i32) -> i32 {
+        if k < lo || k > hi {
+            NULL_OFFSET
+        } else {
+            values[(k - lo) as usize]
+        }
+    }
+
+    fn m(&self, k: i32) -> i32 {
+        Self::get(&self.m, self.lo, self.hi, k)
+    }
+
+    fn i(&self, k: i32) -> i32 {
+        Self::get(&self.i, self.lo, self.hi, k)
+    }
+
+    fn d(&self, k: i32) -> i32 {
+        Self::get(&self.d, self.lo, self.hi, k)
+    }
+}
+
+/// Returns the wavefront for the given penalty, if it exists.
+fn wavefront_at(wavefronts: &[Option<Wavefront>], penalty: isize) -> Option<&Wavefront> {
+    if penalty < 0 {
+        None
+    } else {
+        wavefronts.get(penalty as usize).and_then(Option::as_ref)
+    }
+}
+
+/// Returns the offset if it is within the bounds of the two sequences, otherwise the null offset.
+#[inline]
+fn bounded(offset: i32, k: i32, m: i32, n: i32) -> i32 {
+    if offset < 0 || offset > n || offset - k < 0 || offset - k > m {
+        NULL_OFFSET
+    } else {
+        offset
+    }
+}
+
+/// Extends the offset along the diagonal `k` while the bases in `x` and `y` match.
+#[inline]
+fn extend(x: &[u8], y: &[u8], k: i32, offset: i32) -> i32 {
+    let mut offset = offset;
+    while offset < y.len() as i32
+        && offset - k < x.len() as i32
+        && x[(offset - k) as usize] == y[offset as usize]
+    {
+        offset += 1;
+    }
+    offset
+}
+
+/// Computes the (non-extended) wavefront for the given penalty from the previous wavefronts.
+fn next_wavefront(
+    wavefronts: &[Option<Wavefront>],
+    penalty: usize,
+    penalties: &Penalties,
+    m: i32,
+    n: i32,
+) -> Option<Wavefront> {
+    let s = penalty as isize;
+    let mismatch = wavefront_at(wavefronts, s - penalties.mismatch as isize);
+    let open = wavefront_at(
+        wavefronts,
+        s - (penalties.gap_open + penalties.gap_extend) as isize,
+    );
+    let extend = wavefront_at(wavefronts, s - penalties.gap_extend as isize);
+    if mismatch.is_none() && open.is_none() && extend.is_none() {
+        return None;
+    }
+
+    let sources = [mismatch, open, extend];
+    let lo = sources.iter().flatten().map(|wf| wf.lo).min().unwrap() - 1;
+    let hi = sources.iter().flatten().map(|wf| wf.hi).max().unwrap() + 1;
+    let mut wavefront = Wavefront::new(lo, hi);
+    for k in lo..=hi {
+        let idx = (k - lo) as usize;
+        // consumes a base in y
+        let i_offset = open
+            .map_or(NULL_OFFSET, |wf| wf.m(k - 1))
+            .max(extend.map_or(NULL_OFFSET, |wf| wf.i(k - 1)))
+            + 1;
+        // consumes a base in x
+        let d_offset = open
+            .map_or(NULL_OFFSET, |wf| wf.m(k + 1))
+            .max(extend.map_or(NULL_OFFSET, |wf| wf.d(k + 1)));
+        let i_offset = bounded(i_offset, k, m, n);
+        let d_offset = bounded(d_offset, k, m, n);
+        let x_offset = bounded(mismatch.map_or(NULL_OFFSET, |wf| wf.m(k)) + 1, k, m, n);
+        wavefront.i[idx] = i_offset;
+        wavefront.d[idx] = d_offset;
+        wavefront.m[idx] = x_offset.max(i_offset).max(d_offset);
+    }
+    Some(wavefront)
+}
+
+/// The matrix (component) of the wavefront being traced back.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Component {
+    M,
+    I,
+    D,
+}
+
+/// Traces back the alignment operations from the final wavefront.
+fn backtrace(
+    wavefronts: &[Option<Wavefront>],
+    penalties: &Penalties,
+    final_penalty: usize,
+    k_end: i32,
+    n: i32,
+) -> Vec<AlignmentOperation> {
+    let mut operations = Vec::new();
+    let mut s = final_penalty as isize;
+    let mut k = k_end;
+    let mut offset = n;
+    let mut component = Component::M;
+    let gap_open = (penalties.gap_open + penalties.gap_extend) as isize;
+    let gap_extend = penalties.gap_extend as isize;
+    loop {
+        let wavefront = wavefront_at(wavefronts, s).unwrap();
+        match component {
+            Component::M => {
+                if s == 0 {
+                    // the initial extension along the main diagonal
+                    assert_eq!(k, 0);
+                    operations.extend(std::iter::repeat_n(
+                        AlignmentOperation::Match,
+                        offset as usize,
+                    ));
+                    break;
+                }
+                let x_offset = wavefront_at(wavefronts, s - penalties.mismatch as isize)
+                    .map_or(NULL_OFFSET, |wf| wf.m(k))
+                    + 1;
+                let i_offset = wavefront.i(k);
+                let d_offset = wavefront.d(k);
+                let source = x_offset.max(i_offset).max(d_offset);
+                operations.extend(std::iter::repeat_n(
+                    AlignmentOperation::Match,
+                    (offset - source) as usize,
+                ));
+                offset = source;
+                if source == x_offset {
+                    operations.push(AlignmentOperation::Subst);
+                    offset -= 1;
+                    s -= penalties.mismatch as isize;
+                } else if source == d_offset {
+                    component = Component::D;
+                } else {
+                    component = Component::I;
+                }
+            }
+            Component::I => {
+                // consumes a base in y
+                operations.push(AlignmentOperation::Del);
+                let from_open =
+                    wavefront_at(wavefronts, s - gap_open).map_or(NULL_OFFSET, |wf| wf.m(k - 1));
+                if from_open + 1 == offset {
+                    component = Component::M;
+                    s -= gap_open;
+                } else {
+                    s -= gap_extend;
+                }
+                k -= 1;
+                offset -= 1;
+            }
+            Component::D => {
+                // consumes a base in x
+                operations.push(AlignmentOperation::Ins);
+                let from_open =
+                    wavefront_at(wavefronts, s - gap_open).map_or(NULL_OFFSET, |wf| wf.m(k + 1));
+                if from_open == offset {
+                    component = Component::M;
+                    s -= gap_open;
+                } else {
+                    s -= gap_extend;
+                }
+                k += 1;
+            }
+        }
+    }
+    operations.reverse();
+    operations
+}
+
+/// Computes the global alignment of `x` against `y` using the gap-affine wavefront algorithm.
+///
+/// The mismatch score is taken to be the score of aligning `A` to `C`, and the match score the
+/// score of aligning `A` to `A`, with bases compared case-sensitively.  Jumps and clipping are not
+/// considered.
+pub fn wavefront_global<F: MatchFunc>(
+    x: &[u8],
+    y: &[u8],
+    scoring: &Scoring<F>,
+    contig_idx: usize,
+) -> Alignment {
+    let penalties = Penalties::from_scoring(scoring);
+    let (m, n) = (x.len() as i32, y.len() as i32);
+    let k_end = n - m;
+
+    let mut initial = Wavefront::new(0, 0);
+    initial.m[0] = extend(x, y, 0, 0);
+    let mut wavefronts: Vec<Option<Wavefront>> = vec![Some(initial)];
+    let mut penalty = 0;
+    while wavefront_at(&wavefronts, penalty as isize).is_none_or(|wf| wf.m(k_end) < n) {
+        penalty += 1;
+        let mut wavefront = next_wavefront(&wavefronts, penalty, &penalties, m, n);
+        if let Some(wf) = wavefront.as_mut() {
+            for k in wf.lo..=wf.hi {
+                let idx = (k - wf.lo) as usize;
+                if wf.m[idx] >= 0 {
+                    wf.m[idx] = extend(x, y, k, wf.m[idx]);
+                }
+            }
+        }
+        wavefronts.push(wavefront);
+    }
+
+    let operations = backtrace(&wavefronts, &penalties, penalty, k_end, n);
+    let score = (penalties.match_score * (m + n) - penalty as i32) / 2;
+    Alignment {
+        score,
+        ystart: 0,
+        xstart: 0,
+        yend: y.len(),
+        xend: x.len(),
+        ylen: y.len(),
+        xlen: x.len(),
+        start_contig_idx: contig_idx,
+        end_contig_idx: contig_idx,
+        length: operations.len(),
+        ambiguity: 0,
+        jump_intervals: Vec::new(),
+        score_breakdown: Vec::new(),
+        segments: Vec::new(),
+        operations,
+        mode: AlignmentMode::Global,
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{
+        aligners::{constants::AlignerBackend, single_contig_aligner::SingleContigAligner},
+        scoring::Scoring,
+    };
+
+    use super::wavefront_global;
+
+    #[rstest]
+    #[case("ACGTAACC", "ACGTAACC", "8=")]
+    #[case("AACCGGTT", "AACCGTTT", "5=1X2=")]
+    #[case("AACCGTT", "AACCGGTT", "5=1D2=")]
+    #[case("AACCGGTT", "AACCGTT", "5=1I2=")]
+    #[case("", "ACG", "3D")]
+    #[case("ACG", "", "3I")]
+    #[case("", "", "")]
+    fn test_wavefront_global(#[case] x: &str, #[case] y: &str, #[case] cigar: &str) {
+        let scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -1));
+        let alignment = wavefront_global(x.as_bytes(), y.as_bytes(), &scoring, 0);
+        assert_eq!(alignment.cigar(), cigar);
+        alignment.validate();
+    }
+
+    #[rstest]
+    #[should_panic(
+        expected = "The wavefront alignment requires gap open and extend scores that are not positive"
+    )]
+    fn test_wavefront_positive_gap_score() {
+        // a gap extend score above half the match score would wrap to a huge penalty
+        let mut scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -1));
+        scoring.gap_extend = 1;
+        wavefront_global(b"ACGT", b"ACGT", &scoring, 0);
+    }
+
+    #[rstest]
+    #[case("ACGTAACC", "ACGTAACC")]
+    #[case("AACCGGTT", "AACCGTTT")]
+    #[case("AAACGCGCGCGCGTT", "AACGCGCGCGCGTTT")]
+    #[case("TTTGACGACGACGACGA", "TTTGACGACGACGA")]
+    #[case(
+        "GGTTTTAGAGCTAGAAATAGCAAGTTAAAATAAGGCT",
+        "AGGGCTATAGACTGCTAGAGGTTTTAGAGCTAGAAATAGCAAGTTAAAAT"
+    )]
+    #[case("ACGTACGTACGTTTTTGGGGAAAA", "ACGTTCGTACGAAAAGGGGAAAAA")]
+    fn test_wavefront_score_matches_dynamic_programming(#[case] x: &str, #[case] y: &str) {
+        for (match_score, mismatch_score, gap_open, gap_extend) in [
+            (1, -1, -5, -1),
+            (1, -4, -6, -2),
+            (2, -3, -3, -1),
+            (1, -1, 0, -1),
+        ] {
+            let match_fn = MatchParams::new(match_score, mismatch_score);
+            // use a jump score that will never be used
+            let mut aligner = SingleContigAligner::new(gap_open, gap_extend, -100_000, match_fn);
+            let expected = aligner.global(x.as_bytes(), y.as_bytes());
+            aligner.set_backend(AlignerBackend::Wavefront);
+            let actual = aligner.global(x.as_bytes(), y.as_bytes());
+            assert_eq!(actual.score, expected.score, "{actual} vs {expected}");
+            actual.validate();
+        }
+    }
+}
@@ -1,10 +1,42 @@
-use std::fmt;
+use std::{
+    fmt,
+    ops::{Range, RangeInclusive},
+};
+
+use bio::alignment::pairwise::MatchFunc;
+use itertools::Itertools;
 
-use super::aligners::constants::{AlignmentMode, AlignmentOperation};
+use super::aligners::constants::{AlignmentMode, AlignmentOperation, MIN_SCORE};
+use super::position::{ContigIdx, ContigPos, QueryPos};
+use super::scoring::Scoring;
+use super::{AlignmentLength, CigarFormat, ClipPolicy, CsStyle};
 use crate::align::aligners::constants::{
     AlignmentMode::{Global, QueryLocal, TargetLocal},
     AlignmentOperation::{Del, Ins, Match, Subst, Xclip, Xjump, Yclip, Yjump},
 };
+use crate::util::{
+    dna::{complement, homopolymer_run_length_at},
+    target_seq::TargetSeq,
+};
+
+/// Returns the target sequence and the strand for the given contig index.
+///
+/// Contig indexes follow the convention used by [`crate::align::Builder::build_aligners`]: the
+/// first `target_seqs.len()` indexes are the forward strand of each target, and the next
+/// `target_seqs.len()` are the reverse complement of each target.
+pub(crate) fn target_seq_for(target_seqs: &[TargetSeq], contig_idx: usize) -> (&TargetSeq, bool) {
+    let target_seq = &target_seqs[contig_idx % target_seqs.len()];
+    (target_seq, contig_idx < target_seqs.len())
+}
+
+/// Returns the bases for the given contig index, reverse complemented if the contig index
+/// refers to the reverse strand.  See [`target_seq_for`].
+pub(crate) fn contig_bases_for(target_seqs: &[TargetSeq], contig_idx: usize) -> &[u8] {
+    match target_seq_for(target_seqs, contig_idx) {
+        (target_seq, true) => &target_seq.fwd,
+        (target_seq, false) => &target_seq.revcomp,
+    }
+}
 
 /// We consider alignment between two sequences x and  y. x is the query or read sequence
 /// and y is the reference or template sequence. An alignment, consisting of a score,
@@ -12,7 +44,7 @@ use crate::align::aligners::constants::{
 /// lengths of sequences x and y, and the alignment edit operations. The start position
 /// and end position of the alignment does not include the clipped regions. The length
 /// of clipped regions are already encapsulated in the Alignment Operation.
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Eq, PartialEq, Clone, Default)]
 pub struct Alignment {
     // FIXME: rename to Alignment
@@ -47,8 +79,152 @@ pub struct Alignment {
     pub operations: Vec<AlignmentOperation>,
     pub mode: AlignmentMode,
 
-    /// Alignment length, excluding any clipped (x or y) bases and jumps.
+    /// Alignment length, excluding any clipped (x or y) bases and jumps, as tracked in the
+    /// traceback.  This is the number of alignment columns (see [`Self::column_count`]); see
+    /// [`Self::length_of`] for other definitions of the length.
     pub length: usize,
+
+    /// The number of other placements of the gaps and jumps of the alignment with the same
+    /// score, as counted by [`Self::count_co_optimal`], or zero if not counted.  Breakpoints of an
+    /// ambiguous alignment (see [`Self::is_ambiguous`]) are uncertain.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ambiguity: usize,
+
+    /// The range over which each jump of the alignment may be placed with the same score, as
+    /// found by [`Self::find_jump_intervals`], or empty if not found.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub jump_intervals: Vec<JumpInterval>,
+
+    /// The contribution of each run of operations to the score of the alignment, as found by
+    /// [`Self::rescore_breakdown`], or empty if not found.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub score_breakdown: Vec<ScoreContribution>,
+
+    /// The contig, strand, and coordinates of each segment of the alignment between jumps, as
+    /// found by [`Self::find_segments`], or empty if not found.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub segments: Vec<SegmentInfo>,
+}
+
+/// The range of query positions over which a jump may be placed without changing the score of
+/// the alignment, because the bases around the jump are identical in the contigs on either side
+/// of it (a micro-homology).  Breakpoints given by the jump are only known within this range.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default)]
+pub struct JumpInterval {
+    /// The index of the jump in the operations of the alignment.
+    pub op_index: usize,
+    /// The 0-based position in y (the query) at which the jump is placed.
+    pub y_pos: usize,
+    /// The number of columns by which the jump may be moved earlier in the query.
+    pub left: usize,
+    /// The number of columns by which the jump may be moved later in the query.
+    pub right: usize,
+}
+
+impl JumpInterval {
+    /// The positions in y (the query) at which the jump may be placed.
+    pub fn query_range(&self) -> RangeInclusive<usize> {
+        self.y_pos - self.left..=self.y_pos + self.right
+    }
+
+    /// True if the jump may only be placed where it is.
+    pub fn is_exact(&self) -> bool {
+        self.left == 0 && self.right == 0
+    }
+}
+
+/// A segment of an alignment between jumps, with the contig to which it is aligned, as found by
+/// [`Alignment::find_segments`].  The coordinates are 0-based and exclusive of the end, and those
+/// in the contig are on the strand to which the segment is aligned, as for the alignment.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct SegmentInfo {
+    /// The index of the contig to which the segment is aligned.
+    pub contig_idx: usize,
+    /// The name of the contig to which the segment is aligned.
+    pub contig_name: String,
+    /// True if the segment is aligned to the forward strand of the contig.
+    pub is_forward: bool,
+    /// The start of the segment in x (the contig).
+    pub contig_start: usize,
+    /// The end of the segment in x (the contig).
+    pub contig_end: usize,
+    /// The start of the segment in y (the query).
+    pub query_start: usize,
+    /// The end of the segment in y (the query).
+    pub query_end: usize,
+}
+
+/// The kinds of runs of alignment operations in a score breakdown (see
+/// [`Alignment::rescore_breakdown`]).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ScoreComponent {
+    /// A run of aligned columns (matches and mismatches).
+    Aligned,
+    /// A run of `Ins` operations (consuming only the contig).
+    Ins,
+    /// A run of `Del` operations (consuming only the query).
+    Del,
+    /// A clip of the contig.
+    Xclip,
+    /// A clip of the query.
+    Yclip,
+    /// A jump, within or between contigs.
+    Jump,
+    /// Query bases skipped by a `Yjump`, which are not scored.
+    Skip,
+}
+
+impl ScoreComponent {
+    /// The component of the given operation.
+    fn of(op: &AlignmentOperation) -> Self {
+        match op {
+            Match | Subst => Self::Aligned,
+            Ins => Self::Ins,
+            Del => Self::Del,
+            Xclip(_) => Self::Xclip,
+            Yclip(_) => Self::Yclip,
+            Xjump(_, _) => Self::Jump,
+            Yjump(_) => Self::Skip,
+        }
+    }
+
+    /// The code of the component, as in the extended CIGAR (see [`Alignment::cigar`]), with `M`
+    /// for aligned columns.
+    pub fn code(&self) -> char {
+        match self {
+            Self::Aligned => 'M',
+            Self::Ins => 'I',
+            Self::Del => 'D',
+            Self::Xclip => 'A',
+            Self::Yclip => 'B',
+            Self::Jump => 'J',
+            Self::Skip => 'S',
+        }
+    }
+}
+
+/// The contribution of a run of alignment operations to the score of an alignment (see
+/// [`Alignment::rescore_breakdown`]).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct ScoreContribution {
+    /// The index of the first operation of the run in the operations of the alignment.
+    pub op_index: usize,
+    /// The number of columns of a run of aligned columns or gaps, the number of bases clipped or
+    /// skipped, or one for a jump.
+    pub len: usize,
+    pub component: ScoreComponent,
+    pub score: i32,
+}
+
+impl fmt::Display for ScoreContribution {
+    /// Formats the contribution as `<len><code>:<score>` (e.g. `50M:42` or `1J:-10`).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}:{}", self.len, self.component.code(), self.score)
+    }
 }
 
 #[allow(dead_code)]
@@ -62,7 +238,7 @@ impl Alignment {
                 assert_eq!(self.ystart, 0);
                 assert_eq!(self.yend, self.ylen);
             }
-            AlignmentMode::TargetLocal => {
+            AlignmentMode::TargetLocal | AlignmentMode::SemiGlobal => {
                 assert!(self.xend <= self.xlen);
                 assert_eq!(self.ystart, 0);
                 assert_eq!(self.yend, self.ylen);
@@ -103,6 +279,84 @@ impl Alignment {
         assert_eq!(self.length, length, "length");
     }
 
+    /// Returns the empty alignment of a query of the given length, with a score of zero and no
+    /// operations, for when there is nothing to align (see
+    /// [`crate::align::MultiContigAligner::custom`]).
+    pub fn empty(ylen: usize) -> Self {
+        Self {
+            ylen,
+            mode: AlignmentMode::Custom,
+            ..Self::default()
+        }
+    }
+
+    /// True if no base of the query is aligned to a contig, as for an empty alignment (see
+    /// [`Self::empty`]) or an alignment clipping the whole query.
+    pub fn is_empty(&self) -> bool {
+        self.column_count() == 0
+    }
+
+    /// The index of the contig in which the alignment starts.
+    pub fn start_contig(&self) -> ContigIdx {
+        ContigIdx::new(self.start_contig_idx)
+    }
+
+    /// The index of the contig in which the alignment ends.
+    pub fn end_contig(&self) -> ContigIdx {
+        ContigIdx::new(self.end_contig_idx)
+    }
+
+    /// The offset of the first aligned base in the start contig (see [`Self::start_contig`]).
+    pub fn contig_start(&self) -> ContigPos {
+        ContigPos::new(self.xstart)
+    }
+
+    /// The offset after the last aligned base in the end contig (see [`Self::end_contig`]).
+    pub fn contig_end(&self) -> ContigPos {
+        ContigPos::new(self.xend)
+    }
+
+    /// The offsets of the first aligned base and after the last aligned base of the query.
+    pub fn query_span(&self) -> Range<QueryPos> {
+        QueryPos::new(self.ystart)..QueryPos::new(self.yend)
+    }
+
+    /// The number of query bases aligned to a contig (matches, mismatches, and deletions),
+    /// excluding clipped and skipped query bases.
+    pub fn query_aligned_length(&self) -> usize {
+        self.operations
+            .iter()
+            .filter(|op| matches!(op, Match | Subst | Del))
+            .count()
+    }
+
+    /// The number of contig bases aligned to the query (matches, mismatches, and insertions),
+    /// summed across the contigs the alignment jumps between, and excluding clipped contig bases.
+    pub fn contig_aligned_length(&self) -> usize {
+        self.operations
+            .iter()
+            .filter(|op| matches!(op, Match | Subst | Ins))
+            .count()
+    }
+
+    /// The number of alignment columns (matches, mismatches, insertions, and deletions),
+    /// excluding clips and jumps.
+    pub fn column_count(&self) -> usize {
+        self.operations
+            .iter()
+            .filter(|op| matches!(op, Match | Subst | Ins | Del))
+            .count()
+    }
+
+    /// The length of the alignment with the given definition, computed from the operations.
+    pub fn length_of(&self, length: AlignmentLength) -> usize {
+        match length {
+            AlignmentLength::Columns => self.column_count(),
+            AlignmentLength::Query => self.query_aligned_length(),
+            AlignmentLength::Contig => self.contig_aligned_length(),
+        }
+    }
+
     pub fn cigar(&self) -> String {
         let mut cigar: String = String::new();
         if self.operations.is_empty() {
@@ -149,6 +403,335 @@ impl Alignment {
         cigar
     }
 
+    /// Returns the operations of the alignment in the given format (see [`CigarFormat`]).
+    ///
+    /// The extended format is returned as a single string across the whole alignment, while the
+    /// other formats are returned as one string per segment of the alignment between jumps, in
+    /// the order of the segments in the alignment.
+    ///
+    /// Arguments:
+    /// - `format` - The format in which to write the operations.
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `y` - The query sequence that was aligned.
+    pub fn cigar_with_format(
+        &self,
+        format: CigarFormat,
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+    ) -> Vec<String> {
+        match format {
+            CigarFormat::Extended => vec![self.cigar()],
+            CigarFormat::SamCompat => self.sam_cigars(target_seqs, y, ClipPolicy::Soft),
+            CigarFormat::Cs => self.cs(target_seqs, y, CsStyle::Short),
+        }
+    }
+
+    /// Returns the standard SAM CIGAR of each segment of the alignment between jumps, on the
+    /// forward strand of the target, in the order of the segments in the alignment, with the
+    /// query bases outside of each segment represented per the clip policy (see
+    /// [`ClipPolicy`]).  The CIGAR of a primary record should be soft-clipped.
+    ///
+    /// Arguments:
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `y` - The query sequence that was aligned.
+    /// - `clip_policy` - How the query bases outside of each segment are represented.
+    pub fn sam_cigars(
+        &self,
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+        clip_policy: ClipPolicy,
+    ) -> Vec<String> {
+        self.aligned_segments(target_seqs, y)
+            .iter()
+            .map(|segment| segment.sam_cigar(self.ylen, clip_policy))
+            .collect()
+    }
+
+    /// Returns the minimap2-style `cs` difference string of each segment of the alignment
+    /// between jumps, in the order of the segments in the alignment.  Each string is on the
+    /// forward strand of the target to which its segment is aligned, as for a SAM record, so
+    /// segments aligned to the reverse strand are reversed and complemented.
+    ///
+    /// Arguments:
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `y` - The query sequence that was aligned.
+    /// - `style` - The form of the `cs` strings (see [`CsStyle`]).
+    pub fn cs(&self, target_seqs: &[TargetSeq], y: &[u8], style: CsStyle) -> Vec<String> {
+        self.aligned_segments(target_seqs, y)
+            .iter()
+            .map(|segment| segment.cs(style))
+            .collect()
+    }
+
+    /// Returns the segments of the alignment between jumps (see [`SegmentInfo`]), given the name
+    /// and strand of the contig with each index.  Segments without any alignment column are
+    /// omitted, as for the segments of [`Self::cigar_with_format`].
+    pub fn find_segments(&self, contig_of: impl Fn(usize) -> (String, bool)) -> Vec<SegmentInfo> {
+        let mut segments = Vec::new();
+        let mut current: Option<SegmentInfo> = None;
+        for step in self.iter_steps() {
+            match step.op {
+                Match | Subst | Ins | Del => {
+                    let segment = current.get_or_insert_with(|| {
+                        let (contig_name, is_forward) = contig_of(step.contig_idx);
+                        SegmentInfo {
+                            contig_idx: step.contig_idx,
+                            contig_name,
+                            is_forward,
+                            contig_start: step.x_pos,
+                            contig_end: step.x_pos,
+                            query_start: step.y_pos,
+                            query_end: step.y_pos,
+                        }
+                    });
+                    segment.contig_end = step.x_pos + step.op.length_on_x(step.x_pos) as usize;
+                    segment.query_end = step.y_pos + step.op.length_on_y();
+                }
+                Xjump(_, _) | Yjump(_) => segments.extend(current.take()),
+                Xclip(_) | Yclip(_) => (),
+            }
+        }
+        segments.extend(current);
+        segments
+    }
+
+    /// Splits the alignment into the segments between jumps, with the contig and query bases of
+    /// each alignment column (see [`AlignedSegment`]).  Segments without any alignment column
+    /// are omitted.
+    pub(crate) fn aligned_segments(
+        &self,
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+    ) -> Vec<AlignedSegment> {
+        let mut segments = Vec::new();
+        let mut current: Option<AlignedSegment> = None;
+        for step in self.iter_steps() {
+            match step.op {
+                Match | Subst | Ins | Del => {
+                    let segment = current.get_or_insert_with(|| AlignedSegment {
+                        contig_idx: step.contig_idx,
+                        y_start: step.y_pos,
+                        y_end: step.y_pos,
+                        is_forward: target_seq_for(target_seqs, step.contig_idx).1,
+                        columns: Vec::new(),
+                    });
+                    let x_base = match step.op {
+                        Del => 0,
+                        _ => contig_bases_for(target_seqs, step.contig_idx)[step.x_pos],
+                    };
+                    let y_base = match step.op {
+                        Ins => 0,
+                        _ => y[step.y_pos],
+                    };
+                    segment.y_end = step.y_pos + step.op.length_on_y();
+                    segment.columns.push((step.op, x_base, y_base));
+                }
+                Xjump(_, _) | Yjump(_) => segments.extend(current.take()),
+                Xclip(_) | Yclip(_) => (),
+            }
+        }
+        segments.extend(current);
+
+        // Put the segments on the reverse strand back onto the forward strand of the target
+        for segment in segments.iter_mut().filter(|segment| !segment.is_forward) {
+            segment.columns.reverse();
+            for (_, x_base, y_base) in &mut segment.columns {
+                for base in [x_base, y_base] {
+                    if *base != 0 {
+                        *base = complement(*base);
+                    }
+                }
+            }
+        }
+        segments
+    }
+
+    /// Re-scores the alignment under the given scoring by replaying the alignment operations
+    /// against the contig and query sequences, without re-running the dynamic programming.
+    ///
+    /// Substitutions are scored with the scoring's match function on the actual bases, gaps with
+    /// the affine gap model (with gaps opened next to homopolymers scored as in the aligner), and
+    /// jumps by their type (same contig and strand, forward or backward, same contig and opposite
+    /// strand, or inter-contig), with a jump forward over an intron with splice motifs scored with
+    /// the splice jump score if set (see [`Scoring::same_strand_jump_score_in`]).  A jump from the
+    /// end to the start of the same circular contig costs nothing, as in the aligner.  Leading
+    /// clips are assumed to already be accounted for in `xstart` and `ystart`, and `Yjump`s are
+    /// not scored.
+    ///
+    /// Arguments:
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `y` - The query sequence that was aligned.
+    /// - `scoring` - The scoring with which to re-score the alignment.
+    pub fn rescore<F: MatchFunc>(
+        &self,
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+        scoring: &Scoring<F>,
+    ) -> i32 {
+        // Accumulate in a wider type, as the penalties of a long alignment may underflow
+        let score: i64 = self.operation_scores(target_seqs, y, scoring).iter().sum();
+        // Floored as in the aligner (see `floor_score`)
+        score.clamp(i64::from(MIN_SCORE), i64::from(i32::MAX)) as i32
+    }
+
+    /// Breaks down the score of the alignment under the given scoring into the contributions of
+    /// its runs of operations: each run of aligned columns (matches and mismatches, so typically
+    /// the segments between gaps and jumps), each gap, each clip, each jump, and each run of
+    /// skipped query bases, in the order of the operations.  The operations are scored as in
+    /// [`Self::rescore`], so the contributions sum to the re-scored alignment score unless it is
+    /// floored.  This shows how much each jump, gap, and segment costs, for example to tune the
+    /// scores.
+    pub fn rescore_breakdown<F: MatchFunc>(
+        &self,
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+        scoring: &Scoring<F>,
+    ) -> Vec<ScoreContribution> {
+        let mut breakdown: Vec<(ScoreContribution, i64)> = Vec::new();
+        let scores = self.operation_scores(target_seqs, y, scoring);
+        for (op_index, (op, score)) in self.operations.iter().zip(scores).enumerate() {
+            let component = ScoreComponent::of(op);
+            let len = match *op {
+                Xclip(len) | Yclip(len) | Yjump(len) => len,
+                _ => 1,
+            };
+            // Consecutive aligned columns, or gap columns of the same kind, are a single run
+            let is_run = matches!(
+                component,
+                ScoreComponent::Aligned | ScoreComponent::Ins | ScoreComponent::Del
+            );
+            match breakdown.last_mut() {
+                Some((last, total)) if is_run && last.component == component => {
+                    last.len += 1;
+                    *total += score;
+                }
+                _ => breakdown.push((
+                    ScoreContribution {
+                        op_index,
+                        len,
+                        component,
+                        score: 0,
+                    },
+                    score,
+                )),
+            }
+        }
+        breakdown
+            .into_iter()
+            .map(|(contribution, total)| ScoreContribution {
+                score: total.clamp(i64::from(MIN_SCORE), i64::from(i32::MAX)) as i32,
+                ..contribution
+            })
+            .collect()
+    }
+
+    /// Returns the score of each operation of the alignment under the given scoring, by replaying
+    /// the operations against the contig and query sequences (see [`Self::rescore`]).
+    fn operation_scores<F: MatchFunc>(
+        &self,
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+        scoring: &Scoring<F>,
+    ) -> Vec<i64> {
+        let mut scores = Vec::with_capacity(self.operations.len());
+        let mut x_index = self.xstart;
+        let mut y_index = self.ystart;
+        let mut contig_idx = self.start_contig_idx;
+        let mut x = contig_bases_for(target_seqs, contig_idx);
+        let mut seen_aligned = false;
+        let mut last_op: Option<AlignmentOperation> = None;
+        for op in &self.operations {
+            let score = match *op {
+                Match | Subst => {
+                    let score = scoring.match_fn.score(x[x_index], y[y_index]);
+                    x_index += 1;
+                    y_index += 1;
+                    seen_aligned = true;
+                    i64::from(score)
+                }
+                Ins | Del => {
+                    let mut score = i64::from(scoring.gap_extend);
+                    if last_op != Some(*op) {
+                        score += i64::from(Self::gap_open_at(scoring, x, x_index, y_index, *op));
+                    }
+                    x_index += op.length_on_x(x_index) as usize;
+                    y_index += op.length_on_y();
+                    seen_aligned = true;
+                    score
+                }
+                Xclip(_) => i64::from(if seen_aligned {
+                    scoring.xclip_suffix
+                } else {
+                    scoring.xclip_prefix
+                }),
+                Yclip(_) => i64::from(if seen_aligned {
+                    scoring.yclip_suffix
+                } else {
+                    scoring.yclip_prefix
+                }),
+                Xjump(new_contig_idx, new_x_index) => {
+                    let (from_target, _) = target_seq_for(target_seqs, contig_idx);
+                    let (to_target, _) = target_seq_for(target_seqs, new_contig_idx);
+                    let score = i64::from(if new_contig_idx == contig_idx {
+                        if from_target.circular && x_index == x.len() && new_x_index == 0 {
+                            0
+                        } else {
+                            scoring.same_strand_jump_score_in(x, x_index, new_x_index)
+                        }
+                    } else if std::ptr::eq(from_target, to_target) {
+                        scoring.jump_score_same_contig_opposite_strand
+                    } else {
+                        scoring.jump_score_inter_contig
+                    });
+                    contig_idx = new_contig_idx;
+                    x = contig_bases_for(target_seqs, contig_idx);
+                    x_index = new_x_index;
+                    score
+                }
+                Yjump(len) => {
+                    y_index += len;
+                    0
+                }
+            };
+            scores.push(score);
+            last_op = Some(*op);
+        }
+        scores
+    }
+
+    /// Returns the score for opening the given gap at the given indexes in x and y, as in the
+    /// aligner: next to a homopolymer in x if set (see [`Scoring::homopolymer_gap_open`]), except
+    /// for gaps at the start of x or y.
+    fn gap_open_at<F: MatchFunc>(
+        scoring: &Scoring<F>,
+        x: &[u8],
+        x_index: usize,
+        y_index: usize,
+        op: AlignmentOperation,
+    ) -> i32 {
+        if scoring.homopolymer_gap_open.is_none() {
+            return scoring.gap_open;
+        }
+        let run_length = match op {
+            Ins if y_index > 0 => homopolymer_run_length_at(x, x_index),
+            Del if x_index > 0 => {
+                let before = homopolymer_run_length_at(x, x_index - 1);
+                let after = if x_index < x.len() {
+                    homopolymer_run_length_at(x, x_index)
+                } else {
+                    0
+                };
+                before.max(after)
+            }
+            _ => return scoring.gap_open,
+        };
+        scoring.gap_open_for_run_length(run_length as usize)
+    }
+
     /// Returns the 0-based index in x of the earliest base in y that is aligned to the contig with
     /// the given index.
     pub fn earliest_x_base_for(&self, contig_idx: usize) -> Option<usize> {
@@ -200,6 +783,80 @@ impl Alignment {
         latest_x_base
     }
 
+    /// Returns a lazy iterator over the steps of the alignment path, one per alignment operation,
+    /// with the position in x and y, and the contig index, at which each operation starts.  See
+    /// [`AlignmentStep`].
+    pub fn iter_steps(&self) -> AlignmentSteps<'_> {
+        AlignmentSteps::new(self)
+    }
+
+    /// Returns the contig index and the 0-based position in x (the contig) to which the given
+    /// 0-based position in y (the query) is aligned, or `None` if the query base is not aligned
+    /// to a contig base (i.e. it is clipped, skipped by a `Yjump`, or consumed by a `Del`).
+    pub fn project_query_to_contig(&self, y_pos: usize) -> Option<(usize, usize)> {
+        self.iter_steps()
+            .find(|step| step.op.length_on_y() > 0 && y_pos < step.y_pos + step.op.length_on_y())
+            .filter(|step| matches!(step.op, Match | Subst))
+            .map(|step| (step.contig_idx, step.x_pos))
+    }
+
+    /// Returns the 0-based position in y (the query) aligned to the given 0-based position in x
+    /// on the contig with the given index, or `None` if the contig base is not aligned to a query
+    /// base (i.e. it is clipped, jumped over, or consumed by an `Ins`).  If jumps cause the
+    /// contig base to be aligned more than once, the earliest position in y is returned.
+    pub fn project_contig_to_query(&self, contig_idx: usize, x_pos: usize) -> Option<usize> {
+        self.iter_steps()
+            .find(|step| {
+                matches!(step.op, Match | Subst)
+                    && step.contig_idx == contig_idx
+                    && step.x_pos == x_pos
+            })
+            .map(|step| step.y_pos)
+    }
+
+    /// Computes summary statistics of the alignment by replaying the alignment operations against
+    /// the contig and query sequences.  See [`AlignmentStats`].
+    ///
+    /// Arguments:
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `y` - The query sequence that was aligned.
+    pub fn stats(&self, target_seqs: &[TargetSeq], y: &[u8]) -> AlignmentStats {
+        let mut stats = AlignmentStats::default();
+        for step in self.iter_steps() {
+            match step.op {
+                Match | Subst => {
+                    let x = contig_bases_for(target_seqs, step.contig_idx);
+                    if x[step.x_pos] == y[step.y_pos] {
+                        stats.matches += 1;
+                    } else {
+                        stats.mismatches += 1;
+                    }
+                }
+                Ins => stats.insertions += 1,
+                Del => stats.deletions += 1,
+                Xjump(new_contig_idx, _) => {
+                    stats.jumps += 1;
+                    let (_, from_forward) = target_seq_for(target_seqs, step.contig_idx);
+                    let (_, to_forward) = target_seq_for(target_seqs, new_contig_idx);
+                    if from_forward != to_forward {
+                        stats.strand_switches += 1;
+                    }
+                }
+                Xclip(_) | Yclip(_) | Yjump(_) => (),
+            }
+        }
+        let aligned_query_bases = stats.matches + stats.mismatches + stats.deletions;
+        if self.ylen > 0 {
+            stats.aligned_query_fraction = aligned_query_bases as f64 / self.ylen as f64;
+        }
+        let columns = aligned_query_bases + stats.insertions;
+        if columns > 0 {
+            stats.identity = stats.matches as f64 / columns as f64;
+        }
+        stats
+    }
+
     /// Splits the alignment into two halves, one half aligned up to `y_pivot` point, and the other
     /// half after and including the `y_pivot` point, then swaps their order, and joins them.
     ///
@@ -262,6 +919,10 @@ impl Alignment {
             mode: self.mode,
             score: 0,
             length: 0,
+            ambiguity: 0,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
         };
         assert!(y_pivot >= pre_pivot_aln.yend);
 
@@ -295,6 +956,10 @@ impl Alignment {
             mode: self.mode,
             score: 0,
             length: 0,
+            ambiguity: 0,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
         };
 
         // join the two alignments
@@ -311,6 +976,10 @@ impl Alignment {
             operations: Vec::new(),
             mode: self.mode,
             length: self.length,
+            ambiguity: self.ambiguity,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
         };
 
         // True if we are to add prefix/suffix clipping to x/y respectively.
@@ -359,6 +1028,477 @@ impl Alignment {
 
         aln
     }
+
+    /// Returns the (contig index, x index) before each operation that consumes a query (y) base,
+    /// along with the index of that operation and the y index consumed.
+    fn y_states(&self) -> Vec<(usize, usize, usize, usize)> {
+        let mut states = Vec::with_capacity(self.yend - self.ystart);
+        let mut x_index = self.xstart;
+        let mut y_index = self.ystart;
+        let mut contig_idx = self.start_contig_idx;
+        for (op_index, op) in self.operations.iter().enumerate() {
+            match *op {
+                Match | Subst | Del => states.push((y_index, contig_idx, x_index, op_index)),
+                Xjump(new_contig_idx, _) => contig_idx = new_contig_idx,
+                _ => (),
+            }
+            x_index = (x_index as i32 + op.length_on_x(x_index)) as usize;
+            y_index += op.length_on_y();
+        }
+        states
+    }
+
+    /// Merges the alignments of overlapping chunks (windows) of a query into a single alignment
+    /// of the full query.
+    ///
+    /// Each chunk is given as the offset of the chunk in the query along with the alignment of
+    /// the chunk.  Any clipping is removed.  Consecutive chunks are joined at the first query base
+    /// in their overlap where both alignments agree on the contig and contig position, otherwise
+    /// the earlier chunk is truncated where the later chunk starts and the two are joined with a
+    /// jump.  Query bases not covered by either chunk are added as deletions.  Chunks that are
+    /// entirely contained in the preceding chunks are ignored.  The score is re-computed with
+    /// [`Alignment::rescore`].
+    ///
+    /// Returns `None` if there are no chunks with a non-empty alignment.
+    ///
+    /// Arguments:
+    /// - `chunks` - The offset of each chunk in the query, and the alignment of the chunk.
+    /// - `target_seqs` - The targets to which the chunks were aligned.
+    /// - `y` - The full query sequence.
+    /// - `scoring` - The scoring used to re-score the merged alignment.
+    pub fn merge_chunks<F: MatchFunc>(
+        chunks: &[(usize, Alignment)],
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+        scoring: &Scoring<F>,
+    ) -> Option<Alignment> {
+        // Shift each alignment to be relative to the full query, and remove all clipping
+        let mut chunks = chunks
+            .iter()
+            .filter(|(_, aln)| aln.operations.iter().any(|op| !op.is_special()))
+            .map(|(offset, aln)| {
+                let mut aln = aln.clone();
+                aln.operations
+                    .retain(|op| !matches!(op, Xclip(_) | Yclip(_)));
+                aln.ystart += offset;
+                aln.yend += offset;
+                aln.ylen = y.len();
+                aln
+            })
+            .collect::<Vec<_>>();
+        chunks.sort_by_key(|aln| (aln.ystart, aln.yend));
+
+        let mut chunks = chunks.into_iter();
+        let mut merged = chunks.next()?;
+        for chunk in chunks {
+            if chunk.yend <= merged.yend {
+                continue;
+            }
+
+            // Find the first query base in the overlap where both alignments agree
+            let merged_states = merged.y_states();
+            let chunk_states = chunk.y_states();
+            let join =
+                merged_states
+                    .iter()
+                    .find_map(|&(y_index, contig_idx, x_index, op_index)| {
+                        chunk_states
+                            .iter()
+                            .find(|state| state.0 == y_index)
+                            .filter(|state| state.1 == contig_idx && state.2 == x_index)
+                            .map(|state| (op_index, state.3))
+                    });
+
+            let mut operations =
+                Vec::with_capacity(merged.operations.len() + chunk.operations.len());
+            match join {
+                Some((merged_op_index, chunk_op_index)) => {
+                    operations.extend_from_slice(&merged.operations[..merged_op_index]);
+                    operations.extend_from_slice(&chunk.operations[chunk_op_index..]);
+                }
+                None => {
+                    // Truncate the merged alignment to just before the chunk starts
+                    let (merged_op_index, contig_idx, x_index) =
+                        match merged_states.iter().find(|state| state.0 >= chunk.ystart) {
+                            Some(&(_, contig_idx, x_index, op_index)) => {
+                                (op_index, contig_idx, x_index)
+                            }
+                            None => (merged.operations.len(), merged.end_contig_idx, merged.xend),
+                        };
+                    operations.extend_from_slice(&merged.operations[..merged_op_index]);
+                    // Query bases between the two chunks are deleted
+                    let y_index = merged.yend.min(chunk.ystart);
+                    operations.extend(std::iter::repeat_n(Del, chunk.ystart - y_index));
+                    if contig_idx != chunk.start_contig_idx || x_index != chunk.xstart {
+                        operations.push(Xjump(chunk.start_contig_idx, chunk.xstart));
+                    }
+                    operations.extend_from_slice(&chunk.operations);
+                }
+            }
+
+            merged = Alignment {
+                yend: chunk.yend,
+                xend: chunk.xend,
+                xlen: chunk.xlen,
+                end_contig_idx: chunk.end_contig_idx,
+                operations,
+                ..merged
+            };
+        }
+
+        merged.length = merged
+            .operations
+            .iter()
+            .filter(|op| matches!(op, Match | Subst | Ins | Del))
+            .count();
+        merged.score = merged.rescore(target_seqs, y, scoring);
+        Some(merged)
+    }
+
+    /// Removes segments that follow a jump with fewer than `min_segment_length` aligned (matched
+    /// or mismatched) bases, since such short segments between jumps are almost always noise.
+    ///
+    /// The query bases of a removed segment are deleted, and the alignment jumps to where the
+    /// segment ended in the contig, so that the start and end of the alignment are unchanged.
+    /// Consecutive jumps are then merged, and jumps to where the alignment already is removed.
+    /// The segment before the first jump after an aligned base is never removed.  The alignment
+    /// is re-scored with the given scoring (see [`Self::rescore`]), and must have no leading or
+    /// trailing clips.
+    pub fn remove_short_segments<F: MatchFunc>(
+        &self,
+        min_segment_length: usize,
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+        scoring: &Scoring<F>,
+    ) -> Alignment {
+        // Split the operations into segments, each after the first starting with a jump
+        let mut segments: Vec<&[AlignmentOperation]> = Vec::new();
+        let mut start = 0;
+        for (index, op) in self.operations.iter().enumerate() {
+            if index > start && matches!(op, Xjump(_, _)) {
+                segments.push(&self.operations[start..index]);
+                start = index;
+            }
+        }
+        segments.push(&self.operations[start..]);
+
+        let mut removed = false;
+        let mut operations: Vec<AlignmentOperation> = Vec::with_capacity(self.operations.len());
+        let mut seen_aligned = false;
+        for segment in segments {
+            let num_aligned = segment
+                .iter()
+                .filter(|op| matches!(op, Match | Subst))
+                .count();
+            match segment.first() {
+                Some(&Xjump(contig_idx, to_x_index))
+                    if seen_aligned && num_aligned < min_segment_length =>
+                {
+                    // Delete the query bases, then jump to where the segment ended
+                    let x_end = segment[1..]
+                        .iter()
+                        .fold(to_x_index as i32, |x, op| x + op.length_on_x(x as usize));
+                    let num_deleted = segment.iter().map(AlignmentOperation::length_on_y).sum();
+                    // Deletions do not move in the contig, so delete before any preceding jumps,
+                    // which are then replaced by the jump to where the segment ended
+                    while let Some(Xjump(_, _)) = operations.last() {
+                        operations.pop();
+                    }
+                    operations.extend(std::iter::repeat_n(Del, num_deleted));
+                    operations.push(Xjump(contig_idx, x_end as usize));
+                    removed |= num_deleted > 0 || segment.len() > 1;
+                }
+                _ => operations.extend_from_slice(segment),
+            }
+            seen_aligned |= num_aligned > 0;
+        }
+        if !removed {
+            return self.clone();
+        }
+
+        // Merge consecutive jumps, and remove jumps to where the alignment already is
+        let merged = merge_jumps(operations, (self.start_contig_idx, self.xstart));
+
+        let mut alignment = Alignment {
+            operations: merged,
+            ..self.clone()
+        };
+        alignment.length = alignment
+            .operations
+            .iter()
+            .filter(|op| matches!(op, Match | Subst | Ins | Del))
+            .count();
+        alignment.score = alignment.rescore(target_seqs, y, scoring);
+        alignment
+    }
+
+    /// Returns the canonical form of the alignment, such that alignments along equivalent paths
+    /// have the same operations:
+    /// - consecutive clips of the same kind, and consecutive `Yjump`s, are merged, and empty
+    ///   ones removed
+    /// - consecutive jumps are merged, and jumps to where the alignment already is (e.g. `0J`)
+    ///   removed
+    /// - insertions and deletions are shifted left past matches and mismatches as far as the
+    ///   bases of the shifted columns are unchanged
+    ///
+    /// The score, start, and end of the alignment are unchanged, but the jump intervals are
+    /// cleared (see [`Self::find_jump_intervals`]).
+    ///
+    /// Arguments:
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `y` - The query sequence that was aligned.
+    pub fn normalize(&self, target_seqs: &[TargetSeq], y: &[u8]) -> Alignment {
+        // Merge consecutive clips and Yjumps, and remove empty ones
+        let mut operations: Vec<AlignmentOperation> = Vec::with_capacity(self.operations.len());
+        for op in &self.operations {
+            match (operations.last_mut(), *op) {
+                (_, Xclip(0) | Yclip(0) | Yjump(0)) => (),
+                (Some(Xclip(len)), Xclip(other)) => *len += other,
+                (Some(Yclip(len)), Yclip(other)) => *len += other,
+                (Some(Yjump(len)), Yjump(other)) => *len += other,
+                _ => operations.push(*op),
+            }
+        }
+
+        // Merge consecutive jumps, and remove jumps to where the alignment already is, starting
+        // before any leading clip
+        let leading_xclip = match operations.iter().find(|op| !matches!(op, Yclip(_))) {
+            Some(Xclip(len)) => *len,
+            _ => 0,
+        };
+        let start = (
+            self.start_contig_idx,
+            self.xstart.saturating_sub(leading_xclip),
+        );
+        let mut alignment = Alignment {
+            operations: merge_jumps(operations, start),
+            // the jumps are no longer at the same operation indexes
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
+            ..self.clone()
+        };
+
+        // Shift each run of insertions or deletions left, one column at a time
+        let mut steps: Vec<AlignmentStep> = alignment.iter_steps().collect();
+        let mut end = 0;
+        while end < steps.len() {
+            let gap = steps[end].op;
+            if !matches!(gap, Ins | Del) {
+                end += 1;
+                continue;
+            }
+            let mut start = end;
+            while end < steps.len() && steps[end].op == gap {
+                end += 1;
+            }
+            while start > 0 && matches!(steps[start - 1].op, Match | Subst) {
+                let before = steps[start - 1];
+                let last = steps[end - 1];
+                let is_unchanged = if gap == Ins {
+                    let x = contig_bases_for(target_seqs, before.contig_idx);
+                    x[before.x_pos].eq_ignore_ascii_case(&x[last.x_pos])
+                } else {
+                    y[before.y_pos].eq_ignore_ascii_case(&y[last.y_pos])
+                };
+                if !is_unchanged {
+                    break;
+                }
+                // The gap now starts where the column before it did, and the column follows it
+                let (dx, dy) = if gap == Ins { (1, 0) } else { (0, 1) };
+                for (offset, index) in (start - 1..end - 1).enumerate() {
+                    steps[index] = AlignmentStep {
+                        op: gap,
+                        x_pos: before.x_pos + offset * dx,
+                        y_pos: before.y_pos + offset * dy,
+                        contig_idx: before.contig_idx,
+                    };
+                }
+                let len = end - start;
+                steps[end - 1] = AlignmentStep {
+                    op: before.op,
+                    x_pos: before.x_pos + len * dx,
+                    y_pos: before.y_pos + len * dy,
+                    contig_idx: before.contig_idx,
+                };
+                start -= 1;
+                end -= 1;
+                // Join a preceding run of the same gap
+                while start > 0 && steps[start - 1].op == gap {
+                    start -= 1;
+                }
+            }
+        }
+        alignment.operations = steps.iter().map(|step| step.op).collect();
+        alignment
+    }
+
+    /// Returns true if the two alignments are equal once normalized (see [`Self::normalize`]),
+    /// i.e. they have the same score and follow equivalent paths.
+    ///
+    /// Arguments:
+    /// - `other` - The alignment to compare to, of the same query to the same targets.
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `y` - The query sequence that was aligned.
+    pub fn is_equivalent(&self, other: &Alignment, target_seqs: &[TargetSeq], y: &[u8]) -> bool {
+        self.normalize(target_seqs, y) == other.normalize(target_seqs, y)
+    }
+
+    /// Returns the number of other placements of the gaps and jumps of the alignment with the
+    /// same score, namely the number of columns by which each run of insertions or deletions may
+    /// be shifted through a repeat, and each jump through a micro-homology, with the bases of the
+    /// shifted columns unchanged.  Zero if the alignment is the only one along its path (other
+    /// than clipping).
+    ///
+    /// Arguments:
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `y` - The query sequence that was aligned.
+    pub fn count_co_optimal(&self, target_seqs: &[TargetSeq], y: &[u8]) -> usize {
+        let steps: Vec<AlignmentStep> = self.iter_steps().collect();
+        let mut count = 0;
+        let mut end = 0;
+        while end < steps.len() {
+            let op = steps[end].op;
+            let start = end;
+            end += 1;
+            match op {
+                Ins | Del => {
+                    while end < steps.len() && steps[end].op == op {
+                        end += 1;
+                    }
+                    let (left, right) = gap_shifts(&steps, start..end, target_seqs, y);
+                    count += left + right;
+                }
+                Xjump(_, _) => {
+                    let (left, right) = jump_shifts(&steps, start, target_seqs);
+                    count += left + right;
+                }
+                _ => (),
+            }
+        }
+        count
+    }
+
+    /// Returns the range over which each jump of the alignment may be placed with the same score
+    /// (see [`JumpInterval`]), in the order of the jumps.
+    ///
+    /// Arguments:
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    pub fn find_jump_intervals(&self, target_seqs: &[TargetSeq]) -> Vec<JumpInterval> {
+        let steps: Vec<AlignmentStep> = self.iter_steps().collect();
+        steps
+            .iter()
+            .enumerate()
+            .filter(|(_, step)| matches!(step.op, Xjump(_, _)))
+            .map(|(index, step)| {
+                let (left, right) = jump_shifts(&steps, index, target_seqs);
+                JumpInterval {
+                    op_index: index,
+                    y_pos: step.y_pos,
+                    left,
+                    right,
+                }
+            })
+            .collect()
+    }
+
+    /// True if the alignment has other placements of its gaps or jumps with the same score, once
+    /// counted (see [`Self::ambiguity`]).
+    pub fn is_ambiguous(&self) -> bool {
+        self.ambiguity > 0
+    }
+}
+
+/// Returns the number of columns by which the run of insertions or deletions at the given steps
+/// may be shifted left and right, through matches and mismatches whose bases are unchanged.
+fn gap_shifts(
+    steps: &[AlignmentStep],
+    run: Range<usize>,
+    target_seqs: &[TargetSeq],
+    y: &[u8],
+) -> (usize, usize) {
+    let first = steps[run.start];
+    let len = run.len();
+    // The bases consumed by the gaps, which are in x for insertions and in y for deletions
+    let (bases, pos) = if first.op == Ins {
+        (contig_bases_for(target_seqs, first.contig_idx), first.x_pos)
+    } else {
+        (y, first.y_pos)
+    };
+    let is_column = |index: usize| matches!(steps[index].op, Match | Subst);
+    let same = |a: usize, b: usize| bases[a].eq_ignore_ascii_case(&bases[b]);
+    let left = (1..=run.start)
+        .take_while(|&k| is_column(run.start - k) && same(pos - k, pos + len - k))
+        .count();
+    let right = (run.end..steps.len())
+        .take_while(|&index| {
+            let k = index - run.end;
+            is_column(index) && same(pos + k, pos + len + k)
+        })
+        .count();
+    (left, right)
+}
+
+/// Returns the number of columns by which the jump at the given step may be moved left and right,
+/// such that the columns moved across the jump are aligned to identical bases of the contigs on
+/// either side of it (a micro-homology).
+fn jump_shifts(steps: &[AlignmentStep], index: usize, target_seqs: &[TargetSeq]) -> (usize, usize) {
+    let step = steps[index];
+    let Xjump(to_contig_idx, to_x) = step.op else {
+        panic!("Expected a jump: {:?}", step.op);
+    };
+    let from = contig_bases_for(target_seqs, step.contig_idx);
+    let to = contig_bases_for(target_seqs, to_contig_idx);
+    let is_column = |index: usize| matches!(steps[index].op, Match | Subst);
+    let left = (1..=index.min(to_x).min(step.x_pos))
+        .take_while(|&k| {
+            is_column(index - k) && from[step.x_pos - k].eq_ignore_ascii_case(&to[to_x - k])
+        })
+        .count();
+    let right = (index + 1..steps.len())
+        .take_while(|&next| {
+            let k = next - index - 1;
+            is_column(next)
+                && step.x_pos + k < from.len()
+                && from[step.x_pos + k].eq_ignore_ascii_case(&to[to_x + k])
+        })
+        .count();
+    (left, right)
+}
+
+/// Merges consecutive jumps into the last of them, and removes jumps to where the alignment
+/// already is, following the operations from the given contig index and position in x.
+fn merge_jumps(
+    operations: Vec<AlignmentOperation>,
+    start: (usize, usize),
+) -> Vec<AlignmentOperation> {
+    let mut merged: Vec<AlignmentOperation> = Vec::with_capacity(operations.len());
+    let mut position = start;
+    let mut before_jump = position;
+    for op in operations {
+        match op {
+            Xjump(contig_idx, x_index) => {
+                if let Some(Xjump(_, _)) = merged.last() {
+                    merged.pop();
+                    position = before_jump;
+                }
+                before_jump = position;
+                if (contig_idx, x_index) != position {
+                    merged.push(op);
+                    position = (contig_idx, x_index);
+                }
+            }
+            _ => {
+                position.1 = (position.1 as i32 + op.length_on_x(position.1)) as usize;
+                merged.push(op);
+            }
+        }
+    }
+    merged
 }
 
 impl fmt::Display for Alignment {
@@ -381,17 +1521,262 @@ impl fmt::Display for Alignment {
     }
 }
 
+/// A segment of an alignment between jumps, with the columns on the forward strand of the target
+/// to which the segment is aligned.
+#[derive(Debug, Clone)]
+pub(crate) struct AlignedSegment {
+    /// The index of the contig to which the segment is aligned.
+    pub(crate) contig_idx: usize,
+    /// The 0-based start of the segment in y (the query), as aligned.
+    pub(crate) y_start: usize,
+    /// The 0-based exclusive end of the segment in y (the query), as aligned.
+    pub(crate) y_end: usize,
+    /// True if the segment is aligned to the forward strand of the target.
+    is_forward: bool,
+    /// The operation, contig base, and query base of each column, reversed and complemented
+    /// for segments aligned to the reverse strand.  Bases not consumed by the operation are `0`.
+    columns: Vec<(AlignmentOperation, u8, u8)>,
+}
+
+impl AlignedSegment {
+    /// Returns the standard SAM CIGAR of the segment, with the query bases outside of the
+    /// segment represented per the clip policy.  A `Del` consumes only query bases so is written
+    /// as an `I`, and an `Ins` consumes only contig bases so is written as a `D`.
+    fn sam_cigar(&self, ylen: usize, clip_policy: ClipPolicy) -> String {
+        let (leading_clip, trailing_clip) = if self.is_forward {
+            (self.y_start, ylen - self.y_end)
+        } else {
+            (ylen - self.y_end, self.y_start)
+        };
+        let clip_op = match clip_policy {
+            ClipPolicy::Soft => Some('S'),
+            ClipPolicy::Hard => Some('H'),
+            ClipPolicy::Trimmed => None,
+        };
+        let mut cigar = String::new();
+        if let Some(clip_op) = clip_op.filter(|_| leading_clip > 0) {
+            cigar.push_str(&format!("{leading_clip}{clip_op}"));
+        }
+        let kinds = self.columns.iter().map(|(op, _, _)| match op {
+            Match => '=',
+            Subst => 'X',
+            Ins => 'D',
+            _ => 'I',
+        });
+        for (len, kind) in kinds.dedup_with_count() {
+            cigar.push_str(&format!("{len}{kind}"));
+        }
+        if let Some(clip_op) = clip_op.filter(|_| trailing_clip > 0) {
+            cigar.push_str(&format!("{trailing_clip}{clip_op}"));
+        }
+        cigar
+    }
+
+    /// Returns the minimap2-style `cs` difference string of the segment: `:<len>` (short form)
+    /// or `=<bases>` (long form) for a run of identical bases, `*<ref><query>` for a
+    /// substitution, `+<bases>` for bases only in the query, and `-<bases>` for bases only in the
+    /// target.  Columns are compared ignoring case, and bases are written in lowercase, except
+    /// for the identical bases of the long form which are written in uppercase.
+    pub(crate) fn cs(&self, style: CsStyle) -> String {
+        let mut cs = String::new();
+        let mut identical = Vec::new();
+        let mut last_op = None;
+        for &(op, x_base, y_base) in &self.columns {
+            if matches!(op, Match | Subst) && x_base.eq_ignore_ascii_case(&y_base) {
+                identical.push(x_base.to_ascii_uppercase());
+                last_op = Some(Match);
+                continue;
+            }
+            Self::push_identical(&mut cs, &mut identical, style);
+            match op {
+                Ins => {
+                    if last_op != Some(Ins) {
+                        cs.push('-');
+                    }
+                    cs.push(x_base.to_ascii_lowercase() as char);
+                }
+                Del => {
+                    if last_op != Some(Del) {
+                        cs.push('+');
+                    }
+                    cs.push(y_base.to_ascii_lowercase() as char);
+                }
+                _ => {
+                    cs.push('*');
+                    cs.push(x_base.to_ascii_lowercase() as char);
+                    cs.push(y_base.to_ascii_lowercase() as char);
+                }
+            }
+            last_op = Some(op);
+        }
+        Self::push_identical(&mut cs, &mut identical, style);
+        cs
+    }
+
+    /// Writes the run of identical bases, if any, to the `cs` string, then clears the run.
+    fn push_identical(cs: &mut String, identical: &mut Vec<u8>, style: CsStyle) {
+        if !identical.is_empty() {
+            match style {
+                CsStyle::Short => cs.push_str(&format!(":{}", identical.len())),
+                CsStyle::Long => {
+                    cs.push('=');
+                    cs.push_str(&String::from_utf8_lossy(identical));
+                }
+            }
+            identical.clear();
+        }
+    }
+}
+
+/// Summary statistics of an [`Alignment`], as returned by [`Alignment::stats`].
+///
+/// Insertions and deletions follow the naming of [`AlignmentOperation::Ins`] and
+/// [`AlignmentOperation::Del`], and are counted in bases.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct AlignmentStats {
+    /// The number of aligned bases that are identical in the contig and query.
+    pub matches: usize,
+    /// The number of aligned bases that differ in the contig and query.
+    pub mismatches: usize,
+    /// The number of inserted bases (consuming only the contig).
+    pub insertions: usize,
+    /// The number of deleted bases (consuming only the query).
+    pub deletions: usize,
+    /// The number of jumps, including jumps between contigs and strands.
+    pub jumps: usize,
+    /// The number of jumps between the forward and reverse strands.
+    pub strand_switches: usize,
+    /// The fraction of the query bases that are aligned (not clipped nor skipped).
+    pub aligned_query_fraction: f64,
+    /// The fraction of the alignment columns (excluding clipping and jumps) that are matches.
+    pub identity: f64,
+}
+
+/// A single step along the path of an [`Alignment`], as returned by [`Alignment::iter_steps`].
+///
+/// The positions are those at which the operation starts, such that the operation consumes the
+/// bases `x_pos..x_pos + op.length_on_x(x_pos)` of the contig and `y_pos..y_pos + op.length_on_y()`
+/// of the query.  In particular, leading clips start before `xstart` and `ystart`, trailing clips
+/// start at `xend` and `yend`, and a jump starts at the position it jumps from.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct AlignmentStep {
+    /// The alignment operation.
+    pub op: AlignmentOperation,
+    /// The 0-based position in x (the contig) at which the operation starts.
+    pub x_pos: usize,
+    /// The 0-based position in y (the query) at which the operation starts.
+    pub y_pos: usize,
+    /// The index of the contig in which `x_pos` lies.
+    pub contig_idx: usize,
+}
+
+/// A lazy iterator over the [`AlignmentStep`]s of an [`Alignment`].
+#[derive(Debug, Clone)]
+pub struct AlignmentSteps<'a> {
+    operations: std::slice::Iter<'a, AlignmentOperation>,
+    x_pos: usize,
+    y_pos: usize,
+    contig_idx: usize,
+}
+
+impl<'a> AlignmentSteps<'a> {
+    fn new(alignment: &'a Alignment) -> Self {
+        // The start positions do not include the leading clips, so back them out
+        let (mut x_pos, mut y_pos) = (alignment.xstart, alignment.ystart);
+        for op in &alignment.operations {
+            match op {
+                Xclip(len) => x_pos = x_pos.saturating_sub(*len),
+                Yclip(len) => y_pos = y_pos.saturating_sub(*len),
+                _ => break,
+            }
+        }
+        Self {
+            operations: alignment.operations.iter(),
+            x_pos,
+            y_pos,
+            contig_idx: alignment.start_contig_idx,
+        }
+    }
+}
+
+impl Iterator for AlignmentSteps<'_> {
+    type Item = AlignmentStep;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let op = *self.operations.next()?;
+        let step = AlignmentStep {
+            op,
+            x_pos: self.x_pos,
+            y_pos: self.y_pos,
+            contig_idx: self.contig_idx,
+        };
+        self.x_pos = (self.x_pos as i32 + op.length_on_x(self.x_pos)) as usize;
+        self.y_pos += op.length_on_y();
+        if let Xjump(new_contig_idx, _) = op {
+            self.contig_idx = new_contig_idx;
+        }
+        Some(step)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.operations.size_hint()
+    }
+}
+
+impl ExactSizeIterator for AlignmentSteps<'_> {}
+
 #[cfg(test)]
 pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use itertools::Itertools;
     use rstest::rstest;
 
     use crate::align::aligners::constants::{
         AlignmentMode,
         AlignmentMode::Local,
-        AlignmentOperation::{Del, Ins, Match, Subst, Xjump, Yclip, Yjump},
+        AlignmentOperation,
+        AlignmentOperation::{Del, Ins, Match, Subst, Xclip, Xjump, Yclip, Yjump},
+        MIN_SCORE,
     };
+    use crate::align::aligners::multi_contig_aligner::MultiContigAligner;
+    use crate::align::position::{ContigIdx, ContigPos, QueryPos};
+    use crate::align::scoring::Scoring;
+    use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+    use super::{
+        contig_bases_for, Alignment, AlignmentLength, AlignmentStep, CigarFormat, ClipPolicy,
+        CsStyle, JumpInterval, ScoreComponent, ScoreContribution, SegmentInfo,
+    };
+
+    fn scoring_global(
+        mismatch_score: i32,
+        gap_open: i32,
+        gap_extend: i32,
+        jump_score: i32,
+    ) -> Scoring<MatchParams> {
+        let match_fn = MatchParams::new(1, mismatch_score);
+        Scoring::with_jump_score(gap_open, gap_extend, jump_score, match_fn)
+            .set_xclip(MIN_SCORE)
+            .set_yclip(MIN_SCORE)
+    }
 
-    use super::Alignment;
+    /// Aligns the query to both strands of the targets, with contig indexes following the
+    /// convention of `Builder::build_aligners`.
+    pub(crate) fn align_double_strand(
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+        scoring: Scoring<MatchParams>,
+    ) -> Alignment {
+        let mut aligner = MultiContigAligner::new();
+        for target_seq in target_seqs {
+            aligner.add_contig(&target_seq.name, true, &target_seq.fwd, false, scoring);
+        }
+        for target_seq in target_seqs {
+            aligner.add_contig(&target_seq.name, false, &target_seq.revcomp, false, scoring);
+        }
+        aligner.custom(y)
+    }
 
     fn empty_alignment() -> Alignment {
         Alignment {
@@ -407,6 +1792,10 @@ pub mod tests {
             operations: Vec::new(),
             mode: AlignmentMode::Global,
             length: 0,
+            ambiguity: 0,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
         }
     }
 
@@ -424,6 +1813,10 @@ pub mod tests {
             operations: vec![Match; 100],
             mode: AlignmentMode::Local,
             length: 100,
+            ambiguity: 0,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
         }
     }
 
@@ -441,6 +1834,10 @@ pub mod tests {
             operations: [Match, Match, Xjump(1, 2), Match, Match].to_vec(),
             mode: AlignmentMode::Local,
             length: 4,
+            ambiguity: 0,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
         }
     }
 
@@ -468,6 +1865,10 @@ pub mod tests {
             .to_vec(),
             mode: AlignmentMode::Local,
             length: 6,
+            ambiguity: 0,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
         }
     }
 
@@ -485,6 +1886,10 @@ pub mod tests {
             operations: [Match, Match, Xjump(0, 0), Match, Match].to_vec(),
             mode: AlignmentMode::Local,
             length: 4,
+            ambiguity: 0,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
         }
     }
 
@@ -525,6 +1930,10 @@ pub mod tests {
             .to_vec(),
             mode: AlignmentMode::Local,
             length: 17,
+            ambiguity: 0,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
         }
     }
 
@@ -612,6 +2021,10 @@ pub mod tests {
             .to_vec(),
             mode: AlignmentMode::Local,
             length: 10,
+            ambiguity: 0,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
         }
     }
 
@@ -642,6 +2055,10 @@ pub mod tests {
             .to_vec(),
             mode: AlignmentMode::Local,
             length: 10,
+            ambiguity: 0,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
         }
     }
 
@@ -674,6 +2091,10 @@ pub mod tests {
             .to_vec(),
             mode,
             length: 10,
+            ambiguity: 0,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
         }
     }
 
@@ -706,4 +2127,803 @@ pub mod tests {
         assert_eq!(alignment.cigar(), cigar, "cigar {alignment}");
         assert_eq!(alignment.length, length, "length {alignment}");
     }
+
+    #[rstest]
+    #[case("ACGTAACC", "ACGTAACC")] // identical
+    #[case("AACCGGTT", "AACCGTTT")] // mismatch
+    #[case("AACCGTT", "AACCGGTT")] // deletion
+    #[case("AACCGGGTT", "AACCGTT")] // insertion
+    #[case("AAGGCCTT", "AACCGGTT")] // jumps
+    fn test_rescore_matches_aligner_score(#[case] x: &str, #[case] y: &str) {
+        let target_seqs = [TargetSeq::new("chr1", &x.as_bytes().to_vec(), false)];
+        let y = y.as_bytes();
+        let scoring = scoring_global(-1, -3, -1, -2);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(
+            alignment.rescore(&target_seqs, y, &scoring),
+            alignment.score,
+            "{alignment}"
+        );
+        let breakdown = alignment.rescore_breakdown(&target_seqs, y, &scoring);
+        assert_eq!(
+            breakdown
+                .iter()
+                .map(|contribution| contribution.score)
+                .sum::<i32>(),
+            alignment.score,
+            "{alignment}"
+        );
+    }
+
+    #[rstest]
+    fn test_rescore_with_different_scoring() {
+        // x fwd: AACCTTGG, y: AACCGGTT aligns as 4= to the forward, then a jump to the reverse
+        // strand (CCAAGGTT) for 4=
+        let target_seqs = [TargetSeq::new("chr1", &b"AACCTTGG".to_vec(), false)];
+        let y = b"AACCGGTT";
+        let scoring = scoring_global(-100, -100, -100, -1);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "4=1C0J4=");
+        assert_eq!(alignment.rescore(&target_seqs, y, &scoring), 8 - 1);
+
+        // Only the opposite-strand jump score is used
+        let rescoring = scoring_global(-100, -100, -100, -1).set_jump_scores(-10, -3, -20);
+        assert_eq!(alignment.rescore(&target_seqs, y, &rescoring), 8 - 3);
+
+        // Matches are scored with the new match function
+        let rescoring = Scoring::with_jump_score(-100, -100, -1, MatchParams::new(2, -1));
+        assert_eq!(alignment.rescore(&target_seqs, y, &rescoring), 16 - 1);
+    }
+
+    #[rstest]
+    fn test_rescore_breakdown() {
+        // x fwd: AACCTTGG, y: AACCGGTT aligns as 4= to the forward, then a jump to the reverse
+        // strand (CCAAGGTT) for 4=
+        let target_seqs = [TargetSeq::new("chr1", &b"AACCTTGG".to_vec(), false)];
+        let y = b"AACCGGTT";
+        let scoring = scoring_global(-100, -100, -100, -1).set_jump_scores(-10, -3, -20);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "4=1C0J4=");
+        let breakdown = alignment.rescore_breakdown(&target_seqs, y, &scoring);
+        assert_eq!(
+            breakdown,
+            vec![
+                ScoreContribution {
+                    op_index: 0,
+                    len: 4,
+                    component: ScoreComponent::Aligned,
+                    score: 4,
+                },
+                ScoreContribution {
+                    op_index: 4,
+                    len: 1,
+                    component: ScoreComponent::Jump,
+                    score: -3,
+                },
+                ScoreContribution {
+                    op_index: 5,
+                    len: 4,
+                    component: ScoreComponent::Aligned,
+                    score: 4,
+                },
+            ]
+        );
+        assert_eq!(breakdown.iter().join(","), "4M:4,1J:-3,4M:4");
+
+        // A run of gap columns is a single contribution, with the gap open and extension
+        let target_seqs = [TargetSeq::new("chr1", &b"AACCGGTTAACC".to_vec(), false)];
+        let y = b"AACCGGAACC";
+        let scoring = scoring_global(-100, -3, -1, -100);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        let breakdown = alignment.rescore_breakdown(&target_seqs, y, &scoring);
+        assert_eq!(breakdown.iter().join(","), "6M:6,2I:-5,4M:4");
+    }
+
+    #[rstest]
+    fn test_rescore_inter_contig_jump() {
+        let target_seqs = [
+            TargetSeq::new("chr1", &b"AAAAA".to_vec(), false),
+            TargetSeq::new("chr2", &b"CCCCC".to_vec(), false),
+        ];
+        let y = b"AAAAACCCCC";
+        let scoring = scoring_global(-100, -100, -100, -1);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "5=1C5j5=");
+        assert_eq!(
+            alignment.rescore(&target_seqs, y, &scoring),
+            alignment.score
+        );
+        let rescoring = scoring.set_jump_scores(-1, -1, -7);
+        assert_eq!(alignment.rescore(&target_seqs, y, &rescoring), 10 - 7);
+    }
+
+    #[rstest]
+    fn test_typed_positions() {
+        let target_seqs = [
+            TargetSeq::new("chr1", &b"AAAAA".to_vec(), false),
+            TargetSeq::new("chr2", &b"CCCCC".to_vec(), false),
+        ];
+        let alignment = align_double_strand(
+            &target_seqs,
+            b"AAAAACCCCC",
+            scoring_global(-100, -100, -100, -1),
+        );
+        assert_eq!(
+            (alignment.start_contig(), alignment.end_contig()),
+            (ContigIdx::new(0), ContigIdx::new(1))
+        );
+        assert_eq!(
+            (alignment.contig_start(), alignment.contig_end()),
+            (ContigPos::new(0), ContigPos::new(5))
+        );
+        assert_eq!(alignment.query_span(), QueryPos::new(0)..QueryPos::new(10));
+    }
+
+    #[rstest]
+    fn test_rescore_jumps_by_direction() {
+        // x: AAGGCCTT, y: AACCGGTT aligns with a jump forward, back, then forward
+        let target_seqs = [TargetSeq::new("chr1", &b"AAGGCCTT".to_vec(), false)];
+        let y = b"AACCGGTT";
+        let scoring = scoring_global(-100, -100, -100, -1)
+            .set_jump_scores(-1, -100, -100)
+            .set_jump_score_directions(Some(-2), Some(-3));
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "2=2J2=4j2=2J2=");
+        assert_eq!(alignment.score, 8 - 2 - 3 - 2);
+        assert_eq!(
+            alignment.rescore(&target_seqs, y, &scoring),
+            alignment.score
+        );
+        let rescoring = scoring.set_jump_score_directions(None, Some(-5));
+        assert_eq!(
+            alignment.rescore(&target_seqs, y, &rescoring),
+            8 - 1 - 5 - 1
+        );
+    }
+
+    #[rstest]
+    fn test_rescore_homopolymer_gaps() {
+        // y is missing one base of the homopolymer in x, and has an extra base in the homopolymer
+        let target_seqs = [TargetSeq::new(
+            "chr1",
+            &b"ACGTACGTAAAAAACGTACCCCCGTACGT".to_vec(),
+            false,
+        )];
+        let y = b"ACGTACGTAAAAACGTACCCCCCGTACGT";
+        let scoring = scoring_global(-4, -5, -1, -100).set_homopolymer_gap_open(Some(-1), 3);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "8=1I9=1D11=");
+        assert_eq!(alignment.score, 28 - 2 - 2);
+        assert_eq!(
+            alignment.rescore(&target_seqs, y, &scoring),
+            alignment.score
+        );
+        let rescoring = scoring.set_homopolymer_gap_open(None, 3);
+        assert_eq!(alignment.rescore(&target_seqs, y, &rescoring), 28 - 6 - 6);
+    }
+
+    fn scoring_local() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    /// Aligns each chunk of the query separately, returning the offset and alignment of each.
+    fn align_chunks(
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+        chunks: &[(usize, usize)],
+    ) -> Vec<(usize, Alignment)> {
+        chunks
+            .iter()
+            .map(|&(start, end)| {
+                let mut aln = align_double_strand(target_seqs, &y[start..end], scoring_local());
+                aln.operations
+                    .retain(|op| !matches!(op, AlignmentOperation::Xclip(_) | Yclip(_)));
+                (start, aln)
+            })
+            .collect()
+    }
+
+    #[rstest]
+    fn test_merge_chunks_agreeing_overlap() {
+        let x = b"TTGACCGATAGCTAGCTAGGCTAGCATCGACTAGCGGATCGATCGGCTAACGATTA".to_vec();
+        let target_seqs = [TargetSeq::new("chr1", &x, false)];
+        let y = &x[5..50];
+        let chunks = align_chunks(&target_seqs, y, &[(0, 25), (15, 45)]);
+        let merged = Alignment::merge_chunks(&chunks, &target_seqs, y, &scoring_local()).unwrap();
+        assert_eq!(merged.cigar(), "45=");
+        assert_eq!((merged.xstart, merged.xend), (5, 50));
+        assert_eq!((merged.ystart, merged.yend), (0, 45));
+        assert_eq!(merged.score, 45);
+        assert_eq!(merged.length, 45);
+    }
+
+    #[rstest]
+    fn test_merge_chunks_with_jump_between_chunks() {
+        let x1 = b"TTGACCGATAGCTAGCTAGGCTAGCATCG".to_vec();
+        let x2 = b"ACTAGCGGATCGATCGGCTAACGATTAGC".to_vec();
+        let target_seqs = [
+            TargetSeq::new("chr1", &x1, false),
+            TargetSeq::new("chr2", &x2, false),
+        ];
+        // the first 20bp of chr1, then the last 20bp of chr2
+        let y = [&x1[..20], &x2[9..]].concat();
+        let chunks = align_chunks(&target_seqs, &y, &[(0, 20), (20, 40)]);
+        let merged = Alignment::merge_chunks(&chunks, &target_seqs, &y, &scoring_local()).unwrap();
+        assert_eq!(merged.cigar(), "20=1C11j20=");
+        assert_eq!((merged.start_contig_idx, merged.end_contig_idx), (0, 1));
+        assert_eq!((merged.ystart, merged.yend), (0, 40));
+        assert_eq!(merged.score, 40 - 10);
+        merged.validate();
+    }
+
+    #[rstest]
+    fn test_merge_chunks_with_uncovered_query() {
+        let x = b"TTGACCGATAGCTAGCTAGGCTAGCATCGACTAGCGGATCGATCGGCTAACGATTA".to_vec();
+        let target_seqs = [TargetSeq::new("chr1", &x, false)];
+        let y = [&x[..20], b"NNN", &x[20..40]].concat();
+        let chunks = align_chunks(&target_seqs, &y, &[(0, 20), (23, 43)]);
+        let merged = Alignment::merge_chunks(&chunks, &target_seqs, &y, &scoring_local()).unwrap();
+        assert_eq!(merged.cigar(), "20=3D20=");
+        assert_eq!(merged.score, 40 - 5 - 3);
+        assert_eq!(merged.length, 43);
+    }
+
+    #[rstest]
+    fn test_merge_chunks_empty() {
+        let target_seqs = [TargetSeq::new("chr1", &b"ACGT".to_vec(), false)];
+        assert!(Alignment::merge_chunks(&[], &target_seqs, b"ACGT", &scoring_local()).is_none());
+    }
+
+    #[rstest]
+    #[case(0, "2=2J2=4j2=2J2=", 8 - 1 - 1 - 1)]
+    #[case(2, "2=2J2=4j2=2J2=", 8 - 1 - 1 - 1)]
+    #[case(3, "2=6D6J", 2 - 5 - 6 - 1)]
+    fn test_remove_short_segments(
+        #[case] min_segment_length: usize,
+        #[case] cigar: &str,
+        #[case] score: i32,
+    ) {
+        let target_seqs = [TargetSeq::new("chr1", &b"AAGGCCTT".to_vec(), false)];
+        let y = b"AACCGGTT";
+        let scoring = Scoring::with_jump_score(-5, -1, -1, MatchParams::new(1, -100))
+            .set_jump_scores(-1, -100, -100);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "2=2J2=4j2=2J2=");
+        let alignment =
+            alignment.remove_short_segments(min_segment_length, &target_seqs, y, &scoring);
+        assert_eq!(alignment.cigar(), cigar);
+        assert_eq!(alignment.score, score);
+        alignment.validate();
+    }
+
+    #[rstest]
+    fn test_remove_short_segments_between_contigs() {
+        // chr1, then 3bp of chr2, then chr1 again
+        let x1 = b"TTGACCGATAGCTAGCTAGGCTAGCATCG".to_vec();
+        let x2 = b"ACTAGCGGATCGATCGGCTAACGATTAGC".to_vec();
+        let target_seqs = [
+            TargetSeq::new("chr1", &x1, false),
+            TargetSeq::new("chr2", &x2, false),
+        ];
+        let y = [&x1[..12], &x2[10..13], &x1[12..24]].concat();
+        let operations = [
+            vec![Match; 12],
+            vec![Xjump(1, 10)],
+            vec![Match; 3],
+            vec![Xjump(0, 12)],
+            vec![Match; 12],
+        ]
+        .concat();
+        let scoring = scoring_local().set_jump_score(-1);
+        let alignment = Alignment {
+            score: 27 - 2,
+            xend: 24,
+            yend: 27,
+            ylen: 27,
+            xlen: x1.len(),
+            length: 27,
+            operations,
+            ..Alignment::default()
+        };
+        alignment.validate();
+        assert_eq!(alignment.cigar(), "12=1C2j3=1c1j12=");
+        let alignment = alignment.remove_short_segments(4, &target_seqs, &y, &scoring);
+        assert_eq!(alignment.cigar(), "12=3D12=");
+        assert_eq!(alignment.score, 24 - 5 - 3);
+        assert_eq!(
+            (alignment.start_contig_idx, alignment.end_contig_idx),
+            (0, 0)
+        );
+    }
+
+    #[rstest]
+    fn test_iter_steps_with_jump() {
+        let steps = single_jump_alignment().iter_steps().collect::<Vec<_>>();
+        let step = |op, x_pos, y_pos, contig_idx| AlignmentStep {
+            op,
+            x_pos,
+            y_pos,
+            contig_idx,
+        };
+        assert_eq!(
+            steps,
+            [
+                step(Match, 10, 11, 0),
+                step(Match, 11, 12, 0),
+                step(Xjump(1, 2), 12, 13, 0),
+                step(Match, 2, 13, 1),
+                step(Match, 3, 14, 1),
+            ]
+        );
+        assert!(empty_alignment().iter_steps().next().is_none());
+    }
+
+    #[rstest]
+    fn test_find_segments() {
+        let contig_of = |contig_idx: usize| (format!("chr{contig_idx}"), contig_idx == 0);
+        let segment =
+            |contig_idx: usize, contig: (usize, usize), query: (usize, usize)| SegmentInfo {
+                contig_idx,
+                contig_name: format!("chr{contig_idx}"),
+                is_forward: contig_idx == 0,
+                contig_start: contig.0,
+                contig_end: contig.1,
+                query_start: query.0,
+                query_end: query.1,
+            };
+        assert_eq!(
+            single_jump_alignment().find_segments(contig_of),
+            [segment(0, (10, 12), (11, 13)), segment(1, (2, 4), (13, 15))]
+        );
+        assert!(empty_alignment().find_segments(contig_of).is_empty());
+
+        // a deletion and an insertion within a segment, and clipping around it
+        let alignment = Alignment {
+            xstart: 2,
+            xend: 6,
+            ystart: 1,
+            yend: 4,
+            operations: vec![Yclip(1), Xclip(2), Match, Del, Ins, Ins, Match, Yclip(3)],
+            ..Alignment::default()
+        };
+        assert_eq!(
+            alignment.find_segments(contig_of),
+            [segment(0, (2, 6), (1, 4))]
+        );
+    }
+
+    #[rstest]
+    fn test_iter_steps_from_aligner() {
+        let target_seqs = [TargetSeq::new(
+            "chr1",
+            &b"TTTTTGCTAAAGACAATTACATAACATACACGTCAGCTTTTTTT".to_vec(),
+            false,
+        )];
+        // a deletion, an insertion, and a mismatch, with clipping on both x and y
+        let y = b"GGGGCTAAAGCAATTACATAAACATACACTTCAGCGGG";
+        let scoring = Scoring::with_jump_score(-2, -1, -100, MatchParams::new(1, -1))
+            .set_xclip(0)
+            .set_yclip(0);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "3B5A7=1I9=1D9=1X5=3B7A");
+
+        let steps = alignment.iter_steps().collect::<Vec<_>>();
+        assert_eq!(steps.len(), alignment.operations.len());
+        assert_eq!((steps[0].x_pos, steps[0].y_pos), (0, 0));
+        let first_aligned = steps.iter().find(|s| !s.op.is_special()).unwrap();
+        assert_eq!(
+            (first_aligned.x_pos, first_aligned.y_pos),
+            (alignment.xstart, alignment.ystart)
+        );
+        for step in &steps {
+            let x = contig_bases_for(&target_seqs, step.contig_idx);
+            match step.op {
+                Match => assert_eq!(x[step.x_pos], y[step.y_pos], "{step:?}"),
+                Subst => assert_ne!(x[step.x_pos], y[step.y_pos], "{step:?}"),
+                Xclip(len) if step.x_pos > alignment.xstart => {
+                    assert_eq!(step.x_pos, alignment.xend);
+                    assert_eq!(step.x_pos + len, alignment.xlen);
+                }
+                Yclip(len) if step.y_pos > alignment.ystart => {
+                    assert_eq!(step.y_pos, alignment.yend);
+                    assert_eq!(step.y_pos + len, alignment.ylen);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(0, None)] // clipped
+    #[case(3, Some((0, 5)))]
+    #[case(9, Some((0, 11)))]
+    #[case(10, Some((0, 13)))] // after the insertion
+    #[case(19, None)] // deleted
+    #[case(20, Some((0, 22)))]
+    #[case(34, Some((0, 36)))]
+    #[case(35, None)] // clipped
+    #[case(100, None)] // past the end of the query
+    fn test_project_query_to_contig(
+        #[case] y_pos: usize,
+        #[case] expected: Option<(usize, usize)>,
+    ) {
+        let target_seqs = [TargetSeq::new(
+            "chr1",
+            &b"TTTTTGCTAAAGACAATTACATAACATACACGTCAGCTTTTTTT".to_vec(),
+            false,
+        )];
+        let y = b"GGGGCTAAAGCAATTACATAAACATACACTTCAGCGGG";
+        let scoring = Scoring::with_jump_score(-2, -1, -100, MatchParams::new(1, -1))
+            .set_xclip(0)
+            .set_yclip(0);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "3B5A7=1I9=1D9=1X5=3B7A");
+        assert_eq!(alignment.project_query_to_contig(y_pos), expected);
+        if let Some((contig_idx, x_pos)) = expected {
+            assert_eq!(
+                alignment.project_contig_to_query(contig_idx, x_pos),
+                Some(y_pos)
+            );
+        }
+    }
+
+    #[rstest]
+    #[case(0, 11, Some(0))]
+    #[case(0, 12, Some(1))]
+    #[case(0, 13, None)] // jumped over
+    #[case(1, 2, Some(2))]
+    #[case(1, 3, Some(3))]
+    #[case(1, 4, None)]
+    #[case(2, 2, None)] // not aligned to this contig
+    fn test_project_contig_to_query(
+        #[case] contig_idx: usize,
+        #[case] x_pos: usize,
+        #[case] expected: Option<usize>,
+    ) {
+        let mut alignment = single_jump_alignment();
+        alignment.xstart = 11;
+        alignment.ystart = 0;
+        alignment.yend = 4;
+        alignment.ylen = 4;
+        alignment.xlen = 14;
+        assert_eq!(
+            alignment.project_contig_to_query(contig_idx, x_pos),
+            expected
+        );
+        if let Some(y_pos) = expected {
+            assert_eq!(
+                alignment.project_query_to_contig(y_pos),
+                Some((contig_idx, x_pos))
+            );
+        }
+    }
+
+    #[rstest]
+    fn test_stats() {
+        let target_seqs = [TargetSeq::new(
+            "chr1",
+            &b"TTTTTGCTAAAGACAATTACATAACATACACGTCAGCTTTTTTT".to_vec(),
+            false,
+        )];
+        let y = b"GGGGCTAAAGCAATTACATAAACATACACTTCAGCGGG";
+        let scoring = Scoring::with_jump_score(-2, -1, -100, MatchParams::new(1, -1))
+            .set_xclip(0)
+            .set_yclip(0);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "3B5A7=1I9=1D9=1X5=3B7A");
+        let stats = alignment.stats(&target_seqs, y);
+        assert_eq!(stats.matches, 30);
+        assert_eq!(stats.mismatches, 1);
+        assert_eq!(stats.insertions, 1);
+        assert_eq!(stats.deletions, 1);
+        assert_eq!(stats.jumps, 0);
+        assert_eq!(stats.strand_switches, 0);
+        assert_eq!(stats.aligned_query_fraction, 32.0 / 38.0);
+        assert_eq!(stats.identity, 30.0 / 33.0);
+    }
+
+    #[rstest]
+    fn test_stats_with_jumps() {
+        let target_seqs = [
+            TargetSeq::new("chr1", &b"AACCTTGG".to_vec(), false),
+            TargetSeq::new("chr2", &b"GGGGG".to_vec(), false),
+        ];
+        // 4= to the forward strand of chr1, then a jump to the reverse strand (CCAAGGTT) for 4=,
+        // then a jump to chr2 for 5=
+        let y = b"AACCGGTTGGGGG";
+        let scoring = scoring_global(-100, -100, -100, -1);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "4=2C0J4=1c8j5=");
+        let stats = alignment.stats(&target_seqs, y);
+        assert_eq!(stats.matches, 13);
+        assert_eq!(stats.mismatches, 0);
+        assert_eq!(stats.jumps, 2);
+        assert_eq!(stats.strand_switches, 2);
+        assert_eq!(stats.aligned_query_fraction, 1.0);
+        assert_eq!(stats.identity, 1.0);
+
+        assert_eq!(
+            Alignment::default().stats(&target_seqs, y),
+            Default::default()
+        );
+    }
+
+    #[rstest]
+    fn test_lengths() {
+        let alignment = Alignment {
+            operations: vec![
+                Yclip(2),
+                Match,
+                Ins,
+                Ins,
+                Subst,
+                Del,
+                Xjump(1, 3),
+                Match,
+                Xclip(4),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(alignment.column_count(), 6);
+        assert_eq!(alignment.query_aligned_length(), 4);
+        assert_eq!(alignment.contig_aligned_length(), 5);
+        assert_eq!(alignment.length_of(AlignmentLength::Columns), 6);
+        assert_eq!(alignment.length_of(AlignmentLength::Query), 4);
+        assert_eq!(alignment.length_of(AlignmentLength::Contig), 5);
+        assert_eq!(
+            "Target".parse::<AlignmentLength>().unwrap(),
+            AlignmentLength::Contig
+        );
+    }
+
+    #[rstest]
+    fn test_cigar_with_format_with_jumps() {
+        let target_seqs = [
+            TargetSeq::new("chr1", &b"AACCTTGG".to_vec(), false),
+            TargetSeq::new("chr2", &b"GGGGG".to_vec(), false),
+        ];
+        // 4= to the forward strand of chr1, then a jump to the reverse strand (CCAAGGTT) for 4=,
+        // then a jump to chr2 for 5=
+        let y = b"AACCGGTTGGGGG";
+        let scoring = scoring_global(-100, -100, -100, -1);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(
+            alignment.cigar_with_format(CigarFormat::Extended, &target_seqs, y),
+            ["4=2C0J4=1c8j5="]
+        );
+        assert_eq!(
+            alignment.cigar_with_format(CigarFormat::SamCompat, &target_seqs, y),
+            ["4=9S", "5S4=4S", "8S5="]
+        );
+        assert_eq!(
+            alignment.cigar_with_format(CigarFormat::Cs, &target_seqs, y),
+            [":4", ":4", ":5"]
+        );
+        assert_eq!(
+            alignment.sam_cigars(&target_seqs, y, ClipPolicy::Hard),
+            ["4=9H", "5H4=4H", "8H5="]
+        );
+        assert_eq!(
+            alignment.sam_cigars(&target_seqs, y, ClipPolicy::Trimmed),
+            ["4=", "4=", "5="]
+        );
+        assert!(Alignment::default()
+            .cigar_with_format(CigarFormat::SamCompat, &target_seqs, y)
+            .is_empty());
+    }
+
+    #[rstest]
+    fn test_cigar_with_format_reverse_strand() {
+        const CHR1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+        let target_seqs = [TargetSeq::new("chr1", &CHR1.as_bytes().to_vec(), false)];
+        // On the forward strand: 11=, a 2bp insertion, 9=, a mismatch, 6=, a 2bp deletion, 11=
+        let chr1 = CHR1.as_bytes();
+        let fwd = [
+            &chr1[..11],
+            b"GG",
+            &chr1[11..20],
+            b"G",
+            &chr1[21..27],
+            &chr1[29..],
+        ]
+        .concat();
+        let y = reverse_complement(fwd);
+        let scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0);
+        let alignment = align_double_strand(&target_seqs, &y, scoring);
+        assert_eq!(alignment.start_contig_idx, 1);
+        assert_eq!(
+            alignment.cigar_with_format(CigarFormat::SamCompat, &target_seqs, &y),
+            ["11=2I9=1X6=2D11="]
+        );
+        assert_eq!(
+            alignment.cigar_with_format(CigarFormat::Cs, &target_seqs, &y),
+            [":11+gg:9*ag:6-tc:11"]
+        );
+        assert_eq!(
+            alignment.cs(&target_seqs, &y, CsStyle::Long),
+            ["=GCTAAAGACAA+gg=TTACATAAC*ag=TACACG-tc=AGCACGAAACT"]
+        );
+        assert_eq!(
+            "SAM-compat".parse::<CigarFormat>().unwrap(),
+            CigarFormat::SamCompat
+        );
+    }
+
+    #[rstest]
+    fn test_normalize_merges_operations() {
+        let target_seqs = [TargetSeq::new("chr1", &b"AACCGGTTAACC".to_vec(), false)];
+        let y = b"AACCGGTT";
+        let alignment = Alignment {
+            xstart: 2,
+            operations: vec![
+                Xclip(1),
+                Xclip(1),
+                Match,
+                Match,
+                Match,
+                Match,
+                Xjump(0, 6),
+                Xjump(0, 0),
+                Xjump(0, 6),
+                Match,
+                Match,
+                Match,
+                Match,
+                Yjump(0),
+                Xclip(2),
+            ],
+            ..Default::default()
+        };
+        let normalized = alignment.normalize(&target_seqs, y);
+        assert_eq!(normalized.cigar(), "2A8=2A");
+        assert_eq!(normalized.xstart, 2);
+        assert!(alignment.is_equivalent(&normalized, &target_seqs, y));
+        assert_eq!(normalized.normalize(&target_seqs, y), normalized);
+    }
+
+    #[rstest]
+    #[case::ins("AACCCCGG", "AACCCGG", vec![Match; 5], vec![Ins], "2=1I5=")]
+    #[case::del("AACCCGG", "AACCCCGG", vec![Match; 5], vec![Del], "2=1D5=")]
+    #[case::two_ins("AACACAGG", "AACAGG", vec![Match; 4], vec![Ins, Ins], "1=2I5=")]
+    fn test_normalize_left_shifts_gaps(
+        #[case] x: &str,
+        #[case] y: &str,
+        #[case] before: Vec<AlignmentOperation>,
+        #[case] gap: Vec<AlignmentOperation>,
+        #[case] expected: &str,
+    ) {
+        let target_seqs = [TargetSeq::new("chr1", &x.as_bytes().to_vec(), false)];
+        let y = y.as_bytes();
+        let alignment = Alignment {
+            operations: [before, gap, vec![Match; 2]].concat(),
+            ..Default::default()
+        };
+        let normalized = alignment.normalize(&target_seqs, y);
+        assert_eq!(normalized.cigar(), expected);
+        assert!(alignment.is_equivalent(&normalized, &target_seqs, y));
+        assert_eq!(normalized.stats(&target_seqs, y).mismatches, 0);
+    }
+
+    #[rstest]
+    fn test_is_equivalent() {
+        let target_seqs = [TargetSeq::new("chr1", &b"AACCCCGG".to_vec(), false)];
+        let y = b"AACCCGG";
+        let rightmost = Alignment {
+            operations: [vec![Match; 5], vec![Ins], vec![Match; 2]].concat(),
+            ..Default::default()
+        };
+        let middle = Alignment {
+            operations: [vec![Match; 3], vec![Ins], vec![Match; 4]].concat(),
+            ..Default::default()
+        };
+        // the deleted base is not a C
+        let other = Alignment {
+            operations: [vec![Match; 6], vec![Ins], vec![Match; 1]].concat(),
+            ..Default::default()
+        };
+        assert!(rightmost.is_equivalent(&middle, &target_seqs, y));
+        assert!(!rightmost.is_equivalent(&other, &target_seqs, y));
+        let rescored = Alignment {
+            score: 1,
+            ..middle.clone()
+        };
+        assert!(!rescored.is_equivalent(&middle, &target_seqs, y));
+    }
+
+    #[rstest]
+    #[case::ins_in_repeat(5, Ins, 3)]
+    #[case::ins_before_repeat(6, Ins, 1)]
+    #[case::no_gap(8, Match, 0)]
+    fn test_count_co_optimal_gaps(
+        #[case] num_before: usize,
+        #[case] gap: AlignmentOperation,
+        #[case] expected: usize,
+    ) {
+        let target_seqs = [TargetSeq::new("chr1", &b"AACCCCGG".to_vec(), false)];
+        let y = b"AACCCGG";
+        let operations = if gap == Ins {
+            [
+                vec![Match; num_before],
+                vec![Ins],
+                vec![Match; 7 - num_before],
+            ]
+            .concat()
+        } else {
+            vec![Match; 7]
+        };
+        let mut alignment = Alignment {
+            operations,
+            ..Default::default()
+        };
+        assert_eq!(alignment.count_co_optimal(&target_seqs, y), expected);
+        assert!(!alignment.is_ambiguous());
+        alignment.ambiguity = expected;
+        assert_eq!(alignment.is_ambiguous(), expected > 0);
+    }
+
+    #[rstest]
+    fn test_count_co_optimal_jump() {
+        let target_seqs = [
+            TargetSeq::new("chr1", &b"GGGGGACGT".to_vec(), false),
+            TargetSeq::new("chr2", &b"ACGTCCCCC".to_vec(), false),
+        ];
+        let y = b"GGGGGACGTCCCCC";
+        // the jump may be placed after any of the bases of the micro-homology ACGT
+        let alignment = Alignment {
+            operations: [vec![Match; 5], vec![Xjump(1, 0)], vec![Match; 9]].concat(),
+            ..Default::default()
+        };
+        assert_eq!(alignment.count_co_optimal(&target_seqs, y), 4);
+        let alignment = Alignment {
+            operations: [vec![Match; 9], vec![Xjump(1, 4)], vec![Match; 5]].concat(),
+            ..Default::default()
+        };
+        assert_eq!(alignment.count_co_optimal(&target_seqs, y), 4);
+    }
+
+    #[rstest]
+    fn test_find_jump_intervals() {
+        let target_seqs = [
+            TargetSeq::new("chr1", &b"GGGGGACGT".to_vec(), false),
+            TargetSeq::new("chr2", &b"ACGTCCCCC".to_vec(), false),
+            TargetSeq::new("chr3", &b"TTTTTTTTT".to_vec(), false),
+        ];
+        // a micro-homology (ACGT) at the first jump, but not at the second
+        let alignment = Alignment {
+            operations: [
+                vec![Match; 7],
+                vec![Xjump(1, 2)],
+                vec![Match; 7],
+                vec![Xjump(2, 0)],
+                vec![Match; 3],
+            ]
+            .concat(),
+            ..Default::default()
+        };
+        let intervals = alignment.find_jump_intervals(&target_seqs);
+        assert_eq!(
+            intervals,
+            [
+                JumpInterval {
+                    op_index: 7,
+                    y_pos: 7,
+                    left: 2,
+                    right: 2,
+                },
+                JumpInterval {
+                    op_index: 15,
+                    y_pos: 14,
+                    left: 0,
+                    right: 0,
+                },
+            ]
+        );
+        assert_eq!(intervals[0].query_range(), 5..=9);
+        assert!(!intervals[0].is_exact());
+        assert!(intervals[1].is_exact());
+    }
 }
@@ -0,0 +1,346 @@
+//! All-vs-all alignment of queries to each contig individually, without jumps, compared with the
+//! jump-aware alignment to all the contigs, to quantify how much the chimeric model improves over
+//! per-contig alignment.
+
+use std::io::Write;
+
+use anyhow::Result;
+use bio::alignment::pairwise::MatchFunc;
+use bit_set::BitSet;
+
+use super::{
+    aligners::{
+        constants::{AlignmentOperation::Xjump, MIN_PENALTY},
+        multi_contig_aligner::MultiContigAligner,
+    },
+    alignment::{target_seq_for, Alignment},
+    scoring::Scoring,
+};
+use crate::util::target_seq::TargetSeq;
+
+/// Aligns a query to each contig (and strand) individually, with jumps disabled, such that each
+/// alignment is a conventional (affine gap) alignment to a single contig.  Circular contigs are
+/// aligned as linear contigs, since aligning across the origin requires a jump.
+pub struct PerContigAligner<F: MatchFunc> {
+    aligner: MultiContigAligner<F>,
+}
+
+impl<F: MatchFunc + Sync + Copy> PerContigAligner<F> {
+    /// Creates an aligner of the forward strand of the given targets, then their reverse strand
+    /// if `double_strand`, following the contig indexes of
+    /// [`crate::align::Builder::build_aligners`].  The jump scores of `scoring` are ignored.
+    pub fn new(target_seqs: &[TargetSeq], double_strand: bool, scoring: Scoring<F>) -> Self {
+        let scoring = scoring.set_jump_score(MIN_PENALTY);
+        let mut aligner = MultiContigAligner::new();
+        for target_seq in target_seqs {
+            aligner.add_contig(&target_seq.name, true, &target_seq.fwd, false, scoring);
+        }
+        if double_strand {
+            for target_seq in target_seqs {
+                aligner.add_contig(&target_seq.name, false, &target_seq.revcomp, false, scoring);
+            }
+        }
+        Self { aligner }
+    }
+
+    /// The number of contigs, counting each strand separately.
+    pub fn len(&self) -> usize {
+        self.aligner.len()
+    }
+
+    /// True if there are no contigs.
+    pub fn is_empty(&self) -> bool {
+        self.aligner.is_empty()
+    }
+
+    /// Returns the best alignment of the query to each contig, in the order of the contig
+    /// indexes.
+    pub fn align(&mut self, y: &[u8]) -> Vec<Alignment> {
+        (0..self.aligner.len())
+            .map(|contig_idx| {
+                let indexes = BitSet::from_iter([contig_idx]);
+                self.aligner.custom_with_subset(y, Some(&indexes))
+            })
+            .collect()
+    }
+}
+
+/// The comparison of the per-contig alignments of a query with its jump-aware alignment.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct ModelComparison {
+    /// The score of the best alignment of the query to each contig without jumps, in the order
+    /// of the contig indexes.
+    pub contig_scores: Vec<i32>,
+    /// The score of the jump-aware alignment, if the query aligned.
+    pub jump_aware_score: Option<i32>,
+    /// The number of jumps in the jump-aware alignment.
+    pub jumps: usize,
+}
+
+impl ModelComparison {
+    /// Compares the per-contig alignments of a query (as returned by [`PerContigAligner::align`])
+    /// with its (best) jump-aware alignment, if any.
+    pub fn new(per_contig: &[Alignment], jump_aware: Option<&Alignment>) -> Self {
+        Self {
+            contig_scores: per_contig.iter().map(|alignment| alignment.score).collect(),
+            jump_aware_score: jump_aware.map(|alignment| alignment.score),
+            jumps: jump_aware.map_or(0, |alignment| {
+                alignment
+                    .operations
+                    .iter()
+                    .filter(|op| matches!(op, Xjump(_, _)))
+                    .count()
+            }),
+        }
+    }
+
+    /// Returns the index and score of the contig with the best per-contig alignment, with ties
+    /// broken by the lowest contig index, or `None` if there are no contigs.
+    pub fn best_contig(&self) -> Option<(usize, i32)> {
+        self.contig_scores
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by_key(|(contig_idx, score)| (*score, std::cmp::Reverse(*contig_idx)))
+    }
+
+    /// Returns the improvement of the score of the jump-aware alignment over the best
+    /// per-contig alignment, or `None` if either is missing.
+    pub fn improvement(&self) -> Option<i32> {
+        let (_, best_score) = self.best_contig()?;
+        Some(self.jump_aware_score?.saturating_sub(best_score))
+    }
+
+    /// Writes the header of the table written by [`Self::write_row`].
+    pub fn write_header<W: Write>(writer: &mut W) -> Result<()> {
+        writeln!(
+            writer,
+            "read\tbest_contig\tbest_strand\tbest_contig_score\tjump_aware_score\tjumps\timprovement"
+        )?;
+        Ok(())
+    }
+
+    /// Writes the comparison for the query with the given name as a tab-delimited row, with
+    /// missing values written as `.`.
+    pub fn write_row<W: Write>(
+        &self,
+        writer: &mut W,
+        read_name: &str,
+        target_seqs: &[TargetSeq],
+    ) -> Result<()> {
+        let missing = || ".".to_string();
+        let (contig, strand, best_score) = match self.best_contig() {
+            Some((contig_idx, score)) => {
+                let (target_seq, is_forward) = target_seq_for(target_seqs, contig_idx);
+                let strand = if is_forward { "+" } else { "-" };
+                (
+                    target_seq.name.clone(),
+                    strand.to_string(),
+                    score.to_string(),
+                )
+            }
+            None => (missing(), missing(), missing()),
+        };
+        let jump_aware_score = self
+            .jump_aware_score
+            .map_or_else(missing, |s| s.to_string());
+        let improvement = self.improvement().map_or_else(missing, |s| s.to_string());
+        writeln!(
+            writer,
+            "{read_name}\t{contig}\t{strand}\t{best_score}\t{jump_aware_score}\t{}\t{improvement}",
+            self.jumps
+        )?;
+        Ok(())
+    }
+
+    /// Writes the score of the query with the given name to each contig as a tab-delimited row
+    /// per contig, with the read name, contig name, strand, and score.
+    pub fn write_contig_scores<W: Write>(
+        &self,
+        writer: &mut W,
+        read_name: &str,
+        target_seqs: &[TargetSeq],
+    ) -> Result<()> {
+        for (contig_idx, score) in self.contig_scores.iter().enumerate() {
+            let (target_seq, is_forward) = target_seq_for(target_seqs, contig_idx);
+            let strand = if is_forward { '+' } else { '-' };
+            writeln!(
+                writer,
+                "{read_name}\t{}\t{strand}\t{score}",
+                target_seq.name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Summarizes the improvement of the jump-aware alignments over the per-contig alignments across
+/// many queries.
+#[derive(Debug, Default, Clone)]
+pub struct ModelComparisonSummary {
+    reads: usize,
+    compared: usize,
+    improved: usize,
+    with_jumps: usize,
+    total_improvement: i64,
+}
+
+impl ModelComparisonSummary {
+    /// Adds the comparison of a single query.
+    pub fn add(&mut self, comparison: &ModelComparison) {
+        self.reads += 1;
+        if let Some(improvement) = comparison.improvement() {
+            self.compared += 1;
+            self.total_improvement += i64::from(improvement);
+            if improvement > 0 {
+                self.improved += 1;
+            }
+            if comparison.jumps > 0 {
+                self.with_jumps += 1;
+            }
+        }
+    }
+
+    /// The number of queries.
+    pub fn reads(&self) -> usize {
+        self.reads
+    }
+
+    /// The number of queries with both a per-contig and a jump-aware alignment.
+    pub fn compared(&self) -> usize {
+        self.compared
+    }
+
+    /// The number of compared queries whose jump-aware alignment scores better than their best
+    /// per-contig alignment.
+    pub fn improved(&self) -> usize {
+        self.improved
+    }
+
+    /// Writes the summary as a tab-delimited table of metric names and values.
+    pub fn write_table<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let fraction = |count: usize| {
+            if self.compared == 0 {
+                0.0
+            } else {
+                count as f64 / self.compared as f64
+            }
+        };
+        let mean_improvement = if self.compared == 0 {
+            0.0
+        } else {
+            self.total_improvement as f64 / self.compared as f64
+        };
+        writeln!(writer, "metric\tvalue")?;
+        writeln!(writer, "reads\t{}", self.reads)?;
+        writeln!(writer, "compared_reads\t{}", self.compared)?;
+        writeln!(writer, "improved_reads\t{}", self.improved)?;
+        writeln!(writer, "improved_fraction\t{:.6}", fraction(self.improved))?;
+        writeln!(writer, "reads_with_jumps\t{}", self.with_jumps)?;
+        writeln!(
+            writer,
+            "with_jumps_fraction\t{:.6}",
+            fraction(self.with_jumps)
+        )?;
+        writeln!(writer, "mean_improvement\t{mean_improvement:.6}")?;
+        Ok(())
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{
+        aligners::constants::AlignmentOperation::Xjump, alignment::tests::align_double_strand,
+        scoring::Scoring,
+    };
+    use crate::util::target_seq::TargetSeq;
+
+    use super::{ModelComparison, ModelComparisonSummary, PerContigAligner};
+
+    const CHR1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const CHR2: &str = "TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    fn target_seqs() -> Vec<TargetSeq> {
+        vec![
+            TargetSeq::new("chr1", &CHR1.as_bytes().to_vec(), false),
+            TargetSeq::new("chr2", &CHR2.as_bytes().to_vec(), false),
+        ]
+    }
+
+    #[rstest]
+    fn test_compare_chimera() {
+        let target_seqs = target_seqs();
+        let mut aligner = PerContigAligner::new(&target_seqs, true, scoring());
+        assert_eq!(aligner.len(), 4);
+
+        // chr1:0-25 then chr2:25-40
+        let y = [&CHR1.as_bytes()[..25], &CHR2.as_bytes()[25..]].concat();
+        let per_contig = aligner.align(&y);
+        for alignment in &per_contig {
+            assert!(!alignment
+                .operations
+                .iter()
+                .any(|op| matches!(op, Xjump(_, _))));
+        }
+        let jump_aware = align_double_strand(&target_seqs, &y, scoring());
+        let comparison = ModelComparison::new(&per_contig, Some(&jump_aware));
+        assert_eq!(comparison.contig_scores[..2], [25, 16]);
+        assert_eq!(comparison.best_contig(), Some((0, 25)));
+        assert_eq!(comparison.jump_aware_score, Some(30));
+        assert_eq!(comparison.jumps, 1);
+        assert_eq!(comparison.improvement(), Some(5));
+
+        let mut output = Vec::new();
+        ModelComparison::write_header(&mut output).unwrap();
+        comparison
+            .write_row(&mut output, "read1", &target_seqs)
+            .unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert!(text.ends_with("\nread1\tchr1\t+\t25\t30\t1\t5\n"));
+
+        let mut output = Vec::new();
+        comparison
+            .write_contig_scores(&mut output, "read1", &target_seqs)
+            .unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert!(text.starts_with("read1\tchr1\t+\t25\nread1\tchr2\t+\t16\nread1\tchr1\t-\t"));
+    }
+
+    #[rstest]
+    fn test_summary() {
+        let target_seqs = target_seqs();
+        let mut aligner = PerContigAligner::new(&target_seqs, false, scoring());
+        let mut summary = ModelComparisonSummary::default();
+
+        // no improvement for a query from a single contig
+        let y = CHR2.as_bytes();
+        let jump_aware = align_double_strand(&target_seqs, y, scoring());
+        let comparison = ModelComparison::new(&aligner.align(y), Some(&jump_aware));
+        assert_eq!(comparison.improvement(), Some(0));
+        summary.add(&comparison);
+        // not aligned
+        let comparison = ModelComparison::new(&aligner.align(y), None);
+        assert_eq!(comparison.improvement(), None);
+        summary.add(&comparison);
+
+        assert_eq!(
+            (summary.reads(), summary.compared(), summary.improved()),
+            (2, 1, 0)
+        );
+        let mut output = Vec::new();
+        summary.write_table(&mut output).unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert!(text.starts_with("metric\tvalue\nreads\t2\ncompared_reads\t1\nimproved_reads\t0\n"));
+        assert!(text.ends_with("mean_improvement\t0.000000\n"));
+    }
+}
@@ -0,0 +1,219 @@
+//! Bisulfite-aware matching, for aligning bisulfite-converted reads to constructs, where the
+//! unmethylated cytosines of the converted strand are read as thymines.
+//!
+//! The conversions are described on the forward strand of the contig.  A read from the converted
+//! forward (top) strand has C→T conversions.  A read from the converted reverse (bottom) strand
+//! aligns to the reverse strand of the contig, and has G→A conversions on the forward strand.  In
+//! non-directional libraries, reads from the strands complementary to the converted strands are
+//! also sequenced, so both conversions may occur on either strand.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, Error};
+use bio::alignment::pairwise::MatchFunc;
+use serde::{Deserialize, Serialize};
+
+/// The strands of the bisulfite-converted molecule sequenced by a library.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum BisulfiteLibrary {
+    /// Only the converted strands are sequenced: C→T on the forward strand of the contig, and
+    /// G→A on the reverse strand.
+    #[default]
+    Directional,
+    /// The converted strands and their complements are sequenced: C→T and G→A on either strand.
+    NonDirectional,
+}
+
+impl Display for BisulfiteLibrary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Directional => write!(f, "directional"),
+            Self::NonDirectional => write!(f, "non-directional"),
+        }
+    }
+}
+
+impl FromStr for BisulfiteLibrary {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "directional" => Ok(BisulfiteLibrary::Directional),
+            "non-directional" | "non_directional" | "nondirectional" => {
+                Ok(BisulfiteLibrary::NonDirectional)
+            }
+            _ => Err(anyhow!("Invalid bisulfite library: {}", s)),
+        }
+    }
+}
+
+/// An asymmetric match function that scores the bisulfite conversions of the contig base (x) to
+/// the query base (y) as matches, and the other differences as mismatches.  The function is
+/// specific to the strand of the contig it scores (see [`BisulfiteMatchFunc::for_strand`]), since
+/// the bases of the reverse strand are complemented before checking the conversions of the
+/// forward strand.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
+pub struct BisulfiteMatchFunc {
+    pub match_score: i32,
+    pub mismatch_score: i32,
+    /// True to score C→T on the forward strand of the contig as a match.
+    pub c_to_t: bool,
+    /// True to score G→A on the forward strand of the contig as a match.
+    pub g_to_a: bool,
+    /// True if the function scores the forward strand of the contig, false for the reverse
+    /// strand.
+    pub is_forward: bool,
+}
+
+impl BisulfiteMatchFunc {
+    /// Creates the match function for the given strand of the contig, with the conversions of the
+    /// given library.
+    ///
+    /// # Arguments
+    ///
+    /// * `match_score` - the score of identical bases and of conversions
+    /// * `mismatch_score` - the score of the other differences
+    /// * `is_forward` - true for the forward strand of the contig, false for the reverse strand
+    /// * `library` - the strands of the converted molecule sequenced by the library
+    pub fn for_strand(
+        match_score: i32,
+        mismatch_score: i32,
+        is_forward: bool,
+        library: BisulfiteLibrary,
+    ) -> Self {
+        let (c_to_t, g_to_a) = match library {
+            BisulfiteLibrary::Directional => (is_forward, !is_forward),
+            BisulfiteLibrary::NonDirectional => (true, true),
+        };
+        Self {
+            match_score,
+            mismatch_score,
+            c_to_t,
+            g_to_a,
+            is_forward,
+        }
+    }
+}
+
+impl MatchFunc for BisulfiteMatchFunc {
+    #[inline]
+    fn score(&self, a: u8, b: u8) -> i32 {
+        let (a, b) = (a.to_ascii_uppercase(), b.to_ascii_uppercase());
+        if a == b {
+            return self.match_score;
+        }
+        // the conversions of the forward strand, on the bases of the forward strand
+        let (a, b) = if self.is_forward {
+            (a, b)
+        } else {
+            (complement(a), complement(b))
+        };
+        match (a, b) {
+            (b'C', b'T') if self.c_to_t => self.match_score,
+            (b'G', b'A') if self.g_to_a => self.match_score,
+            _ => self.mismatch_score,
+        }
+    }
+}
+
+/// The complement of an upper-case base, or the base itself if not `ACGT`.
+fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' => b'A',
+        _ => base,
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchFunc;
+    use rstest::rstest;
+
+    use super::{BisulfiteLibrary, BisulfiteMatchFunc};
+    use crate::{
+        align::Builder,
+        test_util::{random_dna, Rng, SEED},
+        util::{dna::reverse_complement, target_seq::TargetSeq},
+    };
+
+    /// Converts the cytosines of the sequence to thymines, other than those of `CG` dinucleotides
+    /// (which are typically methylated).
+    fn convert(seq: &[u8]) -> Vec<u8> {
+        (0..seq.len())
+            .map(|i| match seq[i] {
+                b'C' if seq.get(i + 1) != Some(&b'G') => b'T',
+                base => base,
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case::fwd_directional(true, BisulfiteLibrary::Directional, 1, -4)]
+    #[case::rev_directional(false, BisulfiteLibrary::Directional, -4, 1)]
+    #[case::fwd_non_directional(true, BisulfiteLibrary::NonDirectional, 1, 1)]
+    #[case::rev_non_directional(false, BisulfiteLibrary::NonDirectional, 1, 1)]
+    fn test_score(
+        #[case] is_forward: bool,
+        #[case] library: BisulfiteLibrary,
+        #[case] c_to_t_score: i32,
+        #[case] g_to_a_score: i32,
+    ) {
+        let match_fn = BisulfiteMatchFunc::for_strand(1, -4, is_forward, library);
+        // the bases of the reverse strand are the complements of those of the forward strand
+        let (c_to_t, g_to_a) = if is_forward {
+            ((b'C', b'T'), (b'G', b'A'))
+        } else {
+            ((b'G', b'A'), (b'C', b'T'))
+        };
+        assert_eq!(match_fn.score(c_to_t.0, c_to_t.1), c_to_t_score);
+        assert_eq!(match_fn.score(g_to_a.0, g_to_a.1), g_to_a_score);
+        // the conversions are asymmetric
+        assert_eq!(match_fn.score(c_to_t.1, c_to_t.0), -4);
+        assert_eq!(match_fn.score(b'a', b'A'), 1);
+        assert_eq!(match_fn.score(b'A', b'C'), -4);
+    }
+
+    #[rstest]
+    fn test_bisulfite_aligner() {
+        let mut rng = Rng::new(SEED);
+        let construct = random_dna(&mut rng, 200);
+        let target_seqs = [TargetSeq::new("construct", &construct, false)];
+        let mut builder = Builder::default();
+        builder.double_strand(true);
+
+        // a read of the converted forward strand aligns to the forward strand without mismatches
+        let mut aligner =
+            builder.build_bisulfite_aligner(&target_seqs, BisulfiteLibrary::Directional);
+        let read = convert(&construct[50..150]);
+        let alignment = aligner.custom(&read);
+        assert_eq!(alignment.score, 100);
+        assert_eq!(alignment.start_contig_idx, 0);
+
+        // a read of the converted reverse strand aligns to the reverse strand without mismatches
+        let read = convert(&reverse_complement(&construct[50..150]));
+        let alignment = aligner.custom(&read);
+        assert_eq!(alignment.score, 100);
+        assert_eq!(alignment.start_contig_idx, 1);
+
+        // the complement of the converted forward strand needs a non-directional library
+        let read = reverse_complement(convert(&construct[50..150]));
+        assert!(aligner.custom(&read).score < 100);
+        let mut aligner =
+            builder.build_bisulfite_aligner(&target_seqs, BisulfiteLibrary::NonDirectional);
+        assert_eq!(aligner.custom(&read).score, 100);
+    }
+
+    #[rstest]
+    fn test_bisulfite_library_from_str() {
+        assert_eq!(
+            "Non-Directional".parse::<BisulfiteLibrary>().unwrap(),
+            BisulfiteLibrary::NonDirectional
+        );
+        assert!("pbat".parse::<BisulfiteLibrary>().is_err());
+    }
+}
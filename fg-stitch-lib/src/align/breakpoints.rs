@@ -0,0 +1,515 @@
+//! Breakpoints (structural variant junctions) given by the jumps in alignments, and their
+//! aggregation across many queries into a BEDPE report.
+
+use std::{collections::BTreeMap, io::Write};
+
+use anyhow::Result;
+
+use super::{
+    aligners::constants::AlignmentOperation::Xjump,
+    alignment::{target_seq_for, Alignment},
+};
+use crate::util::{genbank::FeatureAnnotations, target_seq::TargetSeq};
+
+/// A junction between two target positions, as given by a jump in an alignment.
+///
+/// Positions are 0-based on the forward strand of the target.  The "from" position is the last
+/// base aligned before the jump, and the "to" position the first base aligned after the jump.  The
+/// strand is that of the contig to which the query was aligned before (or after) the jump.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct Breakpoint {
+    /// The index of the target before the jump (not the contig index, which is strand specific).
+    pub from_target_idx: usize,
+    /// The position of the last base before the jump.
+    pub from_pos: usize,
+    /// True if the query was aligned to the forward strand before the jump.
+    pub from_forward: bool,
+    /// The index of the target after the jump.
+    pub to_target_idx: usize,
+    /// The position of the first base after the jump.
+    pub to_pos: usize,
+    /// True if the query was aligned to the forward strand after the jump.
+    pub to_forward: bool,
+}
+
+impl Breakpoint {
+    /// Returns the breakpoint as observed from the opposite strand of the query, where the order
+    /// of the two sides are swapped and their strands flipped.
+    pub fn flip(&self) -> Self {
+        Self {
+            from_target_idx: self.to_target_idx,
+            from_pos: self.to_pos,
+            from_forward: !self.to_forward,
+            to_target_idx: self.from_target_idx,
+            to_pos: self.from_pos,
+            to_forward: !self.from_forward,
+        }
+    }
+
+    /// Returns a canonical representation of the breakpoint, such that it is the same for the
+    /// breakpoint observed on either strand of the query.
+    pub fn canonical(&self) -> Self {
+        (*self).min(self.flip())
+    }
+}
+
+impl Alignment {
+    /// Returns the breakpoints given by the jumps in the alignment, in the order in which they
+    /// occur in the query.  A jump from the end to the start of the same circular target is not a
+    /// breakpoint.
+    ///
+    /// Arguments:
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    pub fn breakpoints(&self, target_seqs: &[TargetSeq]) -> Vec<Breakpoint> {
+        self.breakpoints_in_query(target_seqs)
+            .into_iter()
+            .map(|(breakpoint, _)| breakpoint)
+            .collect()
+    }
+
+    /// Returns the breakpoints as [`Self::breakpoints`], each with the 0-based position in the
+    /// query of the first base aligned after the jump.
+    pub fn breakpoints_in_query(&self, target_seqs: &[TargetSeq]) -> Vec<(Breakpoint, usize)> {
+        // Returns the target index, position on the forward strand, and strand of the given
+        // position on the given contig.
+        let to_forward = |contig_idx: usize, pos: usize| {
+            let (target_seq, is_forward) = target_seq_for(target_seqs, contig_idx);
+            let target_idx = contig_idx % target_seqs.len();
+            if is_forward {
+                (target_idx, pos, true)
+            } else {
+                (target_idx, target_seq.len() - 1 - pos, false)
+            }
+        };
+        self.iter_steps()
+            .filter_map(|step| match step.op {
+                Xjump(to_contig_idx, to_x) => {
+                    let (target_seq, _) = target_seq_for(target_seqs, step.contig_idx);
+                    let is_origin = target_seq.circular
+                        && to_contig_idx == step.contig_idx
+                        && step.x_pos == target_seq.len()
+                        && to_x == 0;
+                    if is_origin {
+                        return None;
+                    }
+                    let (from_target_idx, from_pos, from_forward) =
+                        to_forward(step.contig_idx, step.x_pos.saturating_sub(1));
+                    let (to_target_idx, to_pos, to_forward) = to_forward(to_contig_idx, to_x);
+                    let breakpoint = Breakpoint {
+                        from_target_idx,
+                        from_pos,
+                        from_forward,
+                        to_target_idx,
+                        to_pos,
+                        to_forward,
+                    };
+                    Some((breakpoint, step.y_pos))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Counts the number of queries supporting each breakpoint, with breakpoints observed on either
+/// strand of the query counted together (see [`Breakpoint::canonical`]).
+#[derive(Debug, Default, Clone)]
+pub struct BreakpointCounter {
+    counts: BTreeMap<Breakpoint, usize>,
+}
+
+impl BreakpointCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the breakpoints from the alignments of a single query.  A query supports a breakpoint
+    /// at most once, even if observed multiple times across its alignments.
+    pub fn add(&mut self, alignments: &[Alignment], target_seqs: &[TargetSeq]) {
+        let mut breakpoints = alignments
+            .iter()
+            .flat_map(|alignment| alignment.breakpoints(target_seqs))
+            .map(|breakpoint| breakpoint.canonical())
+            .collect::<Vec<_>>();
+        breakpoints.sort_unstable();
+        breakpoints.dedup();
+        for breakpoint in breakpoints {
+            *self.counts.entry(breakpoint).or_default() += 1;
+        }
+    }
+
+    /// Returns the number of queries supporting the given breakpoint.
+    pub fn count(&self, breakpoint: &Breakpoint) -> usize {
+        self.counts
+            .get(&breakpoint.canonical())
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Returns the breakpoints and the number of queries supporting each, in sorted order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Breakpoint, usize)> + '_ {
+        self.counts
+            .iter()
+            .map(|(breakpoint, count)| (breakpoint, *count))
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Writes the breakpoints supported by at least `min_count` queries in BEDPE format, with the
+    /// number of supporting queries in the score column.  With `annotations`, two more columns
+    /// have the names of the features containing the first and second position respectively,
+    /// comma-separated, or `.` if none.
+    pub fn write_bedpe<W: Write>(
+        &self,
+        writer: &mut W,
+        target_seqs: &[TargetSeq],
+        min_count: usize,
+        annotations: Option<&FeatureAnnotations>,
+    ) -> Result<()> {
+        self.write_bedpe_with(writer, target_seqs, min_count, annotations, |_, _| Ok(()))
+    }
+
+    /// Writes the breakpoints as [`Self::write_bedpe`], calling `write_columns` to write any more
+    /// columns at the end of the line for each breakpoint.
+    fn write_bedpe_with<W, F>(
+        &self,
+        writer: &mut W,
+        target_seqs: &[TargetSeq],
+        min_count: usize,
+        annotations: Option<&FeatureAnnotations>,
+        mut write_columns: F,
+    ) -> Result<()>
+    where
+        W: Write,
+        F: FnMut(&mut W, &Breakpoint) -> Result<()>,
+    {
+        let strand = |is_forward: bool| if is_forward { '+' } else { '-' };
+        for (breakpoint, count) in self.iter().filter(|(_, count)| *count >= min_count) {
+            let (from_name, to_name) = (
+                &target_seqs[breakpoint.from_target_idx].name,
+                &target_seqs[breakpoint.to_target_idx].name,
+            );
+            write!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t.\t{}\t{}\t{}",
+                from_name,
+                breakpoint.from_pos,
+                breakpoint.from_pos + 1,
+                to_name,
+                breakpoint.to_pos,
+                breakpoint.to_pos + 1,
+                count,
+                strand(breakpoint.from_forward),
+                strand(breakpoint.to_forward),
+            )?;
+            if let Some(annotations) = annotations {
+                let describe = |name: &str, pos: usize| {
+                    annotations
+                        .describe(name, pos)
+                        .unwrap_or_else(|| ".".to_string())
+                };
+                write!(
+                    writer,
+                    "\t{}\t{}",
+                    describe(from_name, breakpoint.from_pos),
+                    describe(to_name, breakpoint.to_pos)
+                )?;
+            }
+            write_columns(writer, breakpoint)?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the number of queries supporting each breakpoint in each of many samples aligned in the
+/// same run, for a matrix of the breakpoints across the samples.
+#[derive(Debug, Clone)]
+pub struct SampleBreakpointCounter {
+    samples: Vec<String>,
+    /// The counts across all the samples.
+    total: BreakpointCounter,
+    /// The counts of each sample.
+    counters: Vec<BreakpointCounter>,
+}
+
+impl SampleBreakpointCounter {
+    pub fn new(samples: &[String]) -> Self {
+        Self {
+            samples: samples.to_vec(),
+            total: BreakpointCounter::new(),
+            counters: vec![BreakpointCounter::new(); samples.len()],
+        }
+    }
+
+    /// Adds the breakpoints from the alignments of a single query of the sample with the given
+    /// index (see [`BreakpointCounter::add`]).
+    pub fn add(&mut self, sample_idx: usize, alignments: &[Alignment], target_seqs: &[TargetSeq]) {
+        self.total.add(alignments, target_seqs);
+        self.counters[sample_idx].add(alignments, target_seqs);
+    }
+
+    /// Returns the names of the samples.
+    pub fn samples(&self) -> &[String] {
+        &self.samples
+    }
+
+    /// Returns the counts across all the samples.
+    pub fn total(&self) -> &BreakpointCounter {
+        &self.total
+    }
+
+    /// Returns the counts of the sample with the given index.
+    pub fn counter(&self, sample_idx: usize) -> &BreakpointCounter {
+        &self.counters[sample_idx]
+    }
+
+    /// Returns the number of breakpoints across all the samples.
+    pub fn len(&self) -> usize {
+        self.total.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total.is_empty()
+    }
+
+    /// Writes the breakpoints supported by at least `min_count` queries across all the samples as
+    /// [`BreakpointCounter::write_bedpe`], with one more column per sample with the number of
+    /// supporting queries of the sample.  The first line is a header, starting with `#`, with the
+    /// names of the columns, the sample columns being named by their sample.
+    pub fn write_bedpe<W: Write>(
+        &self,
+        writer: &mut W,
+        target_seqs: &[TargetSeq],
+        min_count: usize,
+        annotations: Option<&FeatureAnnotations>,
+    ) -> Result<()> {
+        write!(
+            writer,
+            "#chrom1\tstart1\tend1\tchrom2\tstart2\tend2\tname\tscore\tstrand1\tstrand2"
+        )?;
+        if annotations.is_some() {
+            write!(writer, "\tfeatures1\tfeatures2")?;
+        }
+        for sample in &self.samples {
+            write!(writer, "\t{sample}")?;
+        }
+        writeln!(writer)?;
+        self.total.write_bedpe_with(
+            writer,
+            target_seqs,
+            min_count,
+            annotations,
+            |writer, breakpoint| {
+                for counter in &self.counters {
+                    write!(writer, "\t{}", counter.count(breakpoint))?;
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{
+        aligners::{
+            constants::AlignmentOperation::Xjump, multi_contig_aligner::MultiContigAligner,
+        },
+        alignment::tests::align_double_strand,
+        scoring::Scoring,
+    };
+    use crate::util::{
+        dna::reverse_complement,
+        genbank::{FeatureAnnotations, SeqFeature},
+        target_seq::TargetSeq,
+    };
+
+    use super::{Breakpoint, BreakpointCounter, SampleBreakpointCounter};
+
+    const CHR1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const CHR2: &str = "TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    fn target_seqs() -> Vec<TargetSeq> {
+        vec![
+            TargetSeq::new("chr1", &CHR1.as_bytes().to_vec(), false),
+            TargetSeq::new("chr2", &CHR2.as_bytes().to_vec(), false),
+        ]
+    }
+
+    fn breakpoint(
+        from_target_idx: usize,
+        from_pos: usize,
+        from_forward: bool,
+        to_target_idx: usize,
+        to_pos: usize,
+        to_forward: bool,
+    ) -> Breakpoint {
+        Breakpoint {
+            from_target_idx,
+            from_pos,
+            from_forward,
+            to_target_idx,
+            to_pos,
+            to_forward,
+        }
+    }
+
+    fn feature(name: &str, start: usize, end: usize) -> SeqFeature {
+        SeqFeature {
+            kind: "misc_feature".to_string(),
+            name: name.to_string(),
+            start,
+            end,
+            is_forward: true,
+        }
+    }
+
+    #[rstest]
+    fn test_breakpoints() {
+        let target_seqs = target_seqs();
+        // chr1:0-20 then chr2:20-40
+        let y = [&CHR1.as_bytes()[..20], &CHR2.as_bytes()[20..]].concat();
+        let alignment = align_double_strand(&target_seqs, &y, scoring());
+        assert_eq!(
+            alignment.breakpoints(&target_seqs),
+            [breakpoint(0, 19, true, 1, 20, true)]
+        );
+
+        // the same, but from the opposite strand
+        let y = reverse_complement(&y);
+        let alignment = align_double_strand(&target_seqs, &y, scoring());
+        let breakpoints = alignment.breakpoints(&target_seqs);
+        assert_eq!(breakpoints, [breakpoint(1, 20, false, 0, 19, false)]);
+        assert_eq!(
+            breakpoints[0].canonical(),
+            breakpoint(0, 19, true, 1, 20, true)
+        );
+    }
+
+    #[rstest]
+    fn test_breakpoints_strand_switch() {
+        let target_seqs = target_seqs();
+        // chr1:0-20 then the reverse strand of chr1:10-30, where the base at chr1:19 is also the
+        // complement of the base at chr1:29, and so may be placed on either side of the breakpoint
+        let y = [
+            &CHR1.as_bytes()[..20],
+            &reverse_complement(&CHR1.as_bytes()[10..30]),
+        ]
+        .concat();
+        let alignment = align_double_strand(&target_seqs, &y, scoring());
+        assert_eq!(
+            alignment.breakpoints(&target_seqs),
+            [breakpoint(0, 18, true, 0, 30, false)]
+        );
+    }
+
+    #[rstest]
+    fn test_breakpoints_circular_origin() {
+        let x = CHR1.as_bytes();
+        let y = [&x[20..], &x[..20]].concat();
+        let target_seqs = [TargetSeq::new("chr1", &x.to_vec(), true)];
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("chr1", true, x, true, scoring());
+        let alignment = aligner.custom(&y);
+        assert!(alignment.operations.contains(&Xjump(0, 0)));
+        assert!(alignment.breakpoints(&target_seqs).is_empty());
+    }
+
+    #[rstest]
+    fn test_breakpoint_counter() {
+        let target_seqs = target_seqs();
+        let y = [&CHR1.as_bytes()[..20], &CHR2.as_bytes()[20..]].concat();
+        let fwd = align_double_strand(&target_seqs, &y, scoring());
+        let rev = align_double_strand(&target_seqs, &reverse_complement(&y), scoring());
+
+        let mut counter = BreakpointCounter::new();
+        assert!(counter.is_empty());
+        counter.add(std::slice::from_ref(&fwd), &target_seqs);
+        counter.add(&[rev], &target_seqs);
+        // a query supports a breakpoint once
+        counter.add(&[fwd.clone(), fwd], &target_seqs);
+        assert_eq!(counter.len(), 1);
+        assert_eq!(counter.count(&breakpoint(0, 19, true, 1, 20, true)), 3);
+        assert_eq!(counter.count(&breakpoint(1, 20, false, 0, 19, false)), 3);
+
+        let mut bedpe: Vec<u8> = Vec::new();
+        counter
+            .write_bedpe(&mut bedpe, &target_seqs, 1, None)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(bedpe).unwrap(),
+            "chr1\t19\t20\tchr2\t20\t21\t.\t3\t+\t+\n"
+        );
+
+        // with the features containing either side
+        let mut annotations = FeatureAnnotations::new();
+        annotations.add("chr1", feature("promoter", 10, 20));
+        annotations.add("chr1", feature("enhancer", 0, 30));
+        annotations.add("chr2", feature("polyA", 0, 20));
+        let mut bedpe: Vec<u8> = Vec::new();
+        counter
+            .write_bedpe(&mut bedpe, &target_seqs, 1, Some(&annotations))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(bedpe).unwrap(),
+            "chr1\t19\t20\tchr2\t20\t21\t.\t3\t+\t+\tpromoter,enhancer\t.\n"
+        );
+
+        let mut bedpe: Vec<u8> = Vec::new();
+        counter
+            .write_bedpe(&mut bedpe, &target_seqs, 4, None)
+            .unwrap();
+        assert!(bedpe.is_empty());
+    }
+
+    #[rstest]
+    fn test_sample_breakpoint_counter() {
+        let target_seqs = target_seqs();
+        let y = [&CHR1.as_bytes()[..20], &CHR2.as_bytes()[20..]].concat();
+        let fwd = align_double_strand(&target_seqs, &y, scoring());
+        let rev = align_double_strand(&target_seqs, &reverse_complement(&y), scoring());
+        // chr2:0-20 then chr1:20-40
+        let y = [&CHR2.as_bytes()[..20], &CHR1.as_bytes()[20..]].concat();
+        let other = align_double_strand(&target_seqs, &y, scoring());
+
+        let samples = ["s1".to_string(), "s2".to_string()];
+        let mut counter = SampleBreakpointCounter::new(&samples);
+        assert!(counter.is_empty());
+        counter.add(0, std::slice::from_ref(&fwd), &target_seqs);
+        counter.add(1, &[rev], &target_seqs);
+        counter.add(1, &[other], &target_seqs);
+        assert_eq!(counter.len(), 2);
+        let breakpoint = breakpoint(0, 19, true, 1, 20, true);
+        assert_eq!(counter.total().count(&breakpoint), 2);
+        assert_eq!(counter.counter(0).count(&breakpoint), 1);
+        assert_eq!(counter.counter(1).len(), 2);
+
+        let mut bedpe: Vec<u8> = Vec::new();
+        counter
+            .write_bedpe(&mut bedpe, &target_seqs, 2, None)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(bedpe).unwrap(),
+            "#chrom1\tstart1\tend1\tchrom2\tstart2\tend2\tname\tscore\tstrand1\tstrand2\ts1\ts2\n\
+             chr1\t19\t20\tchr2\t20\t21\t.\t2\t+\t+\t1\t1\n"
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Comparison of the best alignment of each query under two alignment configurations (e.g. two
+//! scorings), reporting the queries whose best alignment changes, with the change of its score and
+//! of its jump structure, for tuning the alignment parameters.
+
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::{alignment::Alignment, poa::JumpStructure};
+use crate::util::target_seq::TargetSeq;
+
+/// The comparison of the best alignments of a query under the first and second configurations.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AlignmentComparison {
+    /// The score of the best alignment under each configuration, if the query aligned.
+    pub scores: [Option<i32>; 2],
+    /// The jump structure of the best alignment under each configuration (see
+    /// [`JumpStructure`]), if the query aligned.
+    pub structures: [Option<JumpStructure>; 2],
+    /// True if the query aligned under only one configuration, or its best alignments differ in
+    /// where they start or in their operations.  The scores alone may differ without the best
+    /// alignment changing, since the configurations may score the same alignment differently.
+    pub changed: bool,
+}
+
+impl AlignmentComparison {
+    /// Compares the best alignments of a query under the first and second configurations, if the
+    /// query aligned under each.
+    pub fn new(first: Option<&Alignment>, second: Option<&Alignment>) -> Self {
+        let changed = match (first, second) {
+            (Some(first), Some(second)) => {
+                first.start_contig_idx != second.start_contig_idx
+                    || first.xstart != second.xstart
+                    || first.ystart != second.ystart
+                    || first.operations != second.operations
+            }
+            (None, None) => false,
+            _ => true,
+        };
+        Self {
+            scores: [first, second].map(|alignment| alignment.map(|a| a.score)),
+            structures: [first, second].map(|alignment| alignment.map(JumpStructure::of)),
+            changed,
+        }
+    }
+
+    /// The score of the best alignment under the second configuration less that under the
+    /// first, or `None` unless the query aligned under both.
+    pub fn score_delta(&self) -> Option<i32> {
+        Some(self.scores[1]?.saturating_sub(self.scores[0]?))
+    }
+
+    /// True if the jump structures of the best alignments differ, including if the query
+    /// aligned under only one configuration.
+    pub fn structure_changed(&self) -> bool {
+        self.structures[0] != self.structures[1]
+    }
+
+    /// Writes the header of the table written by [`Self::write_row`].
+    pub fn write_header<W: Write>(writer: &mut W) -> Result<()> {
+        writeln!(
+            writer,
+            "read\tfirst_score\tsecond_score\tscore_delta\tfirst_structure\tsecond_structure\t\
+             structure_changed"
+        )?;
+        Ok(())
+    }
+
+    /// Writes the comparison for the query with the given name as a tab-delimited row, with each
+    /// jump structure as described by [`JumpStructure::describe`], and missing values written as
+    /// `.`.
+    pub fn write_row<W: Write>(
+        &self,
+        writer: &mut W,
+        read_name: &str,
+        target_seqs: &[TargetSeq],
+    ) -> Result<()> {
+        let missing = || ".".to_string();
+        let [first_score, second_score] = self
+            .scores
+            .map(|score| score.map_or_else(missing, |s| s.to_string()));
+        let [first_structure, second_structure] = self.structures.each_ref().map(|structure| {
+            structure
+                .as_ref()
+                .map_or_else(missing, |s| s.describe(target_seqs))
+        });
+        let score_delta = self.score_delta().map_or_else(missing, |d| d.to_string());
+        writeln!(
+            writer,
+            "{read_name}\t{first_score}\t{second_score}\t{score_delta}\t{first_structure}\t\
+             {second_structure}\t{}",
+            self.structure_changed()
+        )?;
+        Ok(())
+    }
+}
+
+/// Summarizes the changes of the best alignments between the two configurations across many
+/// queries.
+#[derive(Debug, Default, Clone)]
+pub struct AlignmentComparisonSummary {
+    reads: usize,
+    changed: usize,
+    structure_changed: usize,
+    only_first: usize,
+    only_second: usize,
+    /// The number of queries aligned under both configurations, and the sum of their score
+    /// deltas.
+    compared: usize,
+    total_score_delta: i64,
+}
+
+impl AlignmentComparisonSummary {
+    /// Adds the comparison of a single query.
+    pub fn add(&mut self, comparison: &AlignmentComparison) {
+        self.reads += 1;
+        if comparison.changed {
+            self.changed += 1;
+        }
+        if comparison.structure_changed() {
+            self.structure_changed += 1;
+        }
+        match comparison.scores {
+            [Some(_), None] => self.only_first += 1,
+            [None, Some(_)] => self.only_second += 1,
+            _ => (),
+        }
+        if let Some(delta) = comparison.score_delta() {
+            self.compared += 1;
+            self.total_score_delta += i64::from(delta);
+        }
+    }
+
+    /// The number of queries.
+    pub fn reads(&self) -> usize {
+        self.reads
+    }
+
+    /// The number of queries whose best alignment changed (see [`AlignmentComparison::changed`]).
+    pub fn changed(&self) -> usize {
+        self.changed
+    }
+
+    /// The number of queries whose best alignment changed its jump structure.
+    pub fn structure_changed(&self) -> usize {
+        self.structure_changed
+    }
+
+    /// Writes the summary as a tab-delimited table of metric names and values.
+    pub fn write_table<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let fraction = |count: usize| {
+            if self.reads == 0 {
+                0.0
+            } else {
+                count as f64 / self.reads as f64
+            }
+        };
+        let mean_score_delta = if self.compared == 0 {
+            0.0
+        } else {
+            self.total_score_delta as f64 / self.compared as f64
+        };
+        writeln!(writer, "metric\tvalue")?;
+        writeln!(writer, "reads\t{}", self.reads)?;
+        writeln!(writer, "changed_reads\t{}", self.changed)?;
+        writeln!(writer, "changed_fraction\t{:.6}", fraction(self.changed))?;
+        writeln!(
+            writer,
+            "structure_changed_reads\t{}",
+            self.structure_changed
+        )?;
+        writeln!(
+            writer,
+            "structure_changed_fraction\t{:.6}",
+            fraction(self.structure_changed)
+        )?;
+        writeln!(writer, "aligned_only_first\t{}", self.only_first)?;
+        writeln!(writer, "aligned_only_second\t{}", self.only_second)?;
+        writeln!(writer, "mean_score_delta\t{mean_score_delta:.6}")?;
+        Ok(())
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{alignment::tests::align_double_strand, scoring::Scoring};
+    use crate::util::target_seq::TargetSeq;
+
+    use super::{AlignmentComparison, AlignmentComparisonSummary};
+
+    const CHR1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const CHR2: &str = "TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    fn scoring(jump_score: i32) -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-5, -1, jump_score, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    fn target_seqs() -> Vec<TargetSeq> {
+        vec![
+            TargetSeq::new("chr1", &CHR1.as_bytes().to_vec(), false),
+            TargetSeq::new("chr2", &CHR2.as_bytes().to_vec(), false),
+        ]
+    }
+
+    #[rstest]
+    fn test_compare_jump_scores() {
+        let target_seqs = target_seqs();
+        // chr1:0-25 then chr2:25-40, which jumps only if jumps are cheap enough
+        let y = [&CHR1.as_bytes()[..25], &CHR2.as_bytes()[25..]].concat();
+        let first = align_double_strand(&target_seqs, &y, scoring(-10));
+        let second = align_double_strand(&target_seqs, &y, scoring(-100));
+
+        let comparison = AlignmentComparison::new(Some(&first), Some(&second));
+        assert_eq!(comparison.scores, [Some(30), Some(25)]);
+        assert_eq!(comparison.score_delta(), Some(-5));
+        assert!(comparison.changed);
+        assert!(comparison.structure_changed());
+        assert_eq!(comparison.structures[0].as_ref().unwrap().jumps.len(), 1);
+        assert!(comparison.structures[1].as_ref().unwrap().jumps.is_empty());
+
+        let mut output = Vec::new();
+        AlignmentComparison::write_header(&mut output).unwrap();
+        comparison
+            .write_row(&mut output, "read1", &target_seqs)
+            .unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert!(text.contains("\nread1\t30\t25\t-5\tchr1:"), "{text}");
+        assert!(text.ends_with("\tchr1+\ttrue\n"), "{text}");
+    }
+
+    #[rstest]
+    fn test_summary() {
+        let target_seqs = target_seqs();
+        let y = CHR2.as_bytes();
+        let alignment = align_double_strand(&target_seqs, y, scoring(-10));
+        let mut summary = AlignmentComparisonSummary::default();
+
+        // the same alignment under both
+        let comparison = AlignmentComparison::new(Some(&alignment), Some(&alignment));
+        assert!(!comparison.changed);
+        assert!(!comparison.structure_changed());
+        assert_eq!(comparison.score_delta(), Some(0));
+        summary.add(&comparison);
+        // aligned under only the first
+        let comparison = AlignmentComparison::new(Some(&alignment), None);
+        assert!(comparison.changed);
+        assert_eq!(comparison.score_delta(), None);
+        summary.add(&comparison);
+        // not aligned under either
+        let comparison = AlignmentComparison::new(None, None);
+        assert!(!comparison.changed);
+        summary.add(&comparison);
+
+        assert_eq!(
+            (
+                summary.reads(),
+                summary.changed(),
+                summary.structure_changed()
+            ),
+            (3, 1, 1)
+        );
+        let mut output = Vec::new();
+        summary.write_table(&mut output).unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert!(text.starts_with("metric\tvalue\nreads\t3\nchanged_reads\t1\n"));
+        assert!(text.contains("aligned_only_first\t1\naligned_only_second\t0\n"));
+        assert!(text.ends_with("mean_score_delta\t0.000000\n"));
+    }
+}
@@ -0,0 +1,387 @@
+//! The configuration of the aligner, so that parameter sets may be stored (as TOML with the
+//! `serde` feature), loaded, and recorded in outputs for reproducibility.
+
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use anyhow::{Context, Result};
+
+use bio::alignment::pairwise::MatchParams;
+
+use super::{
+    filter::AlignmentFilter, scoring::DEFAULT_HOMOPOLYMER_MIN_LENGTH, strategy::AutoStrategy,
+    AlignmentLength, AlignmentMode, Builder, ClipPolicy, CsStyle, PrimaryPickingStrategy, Scoring,
+    TracebackLayout,
+};
+
+/// The parameters of the aligner, with the same meaning and defaults as the options of the same
+/// name of [`Builder`], plus the filter applied to the alignments.
+///
+/// With the `serde` feature, a configuration may be read from, and written to, TOML.  Parameters
+/// missing from the TOML take their default value (see [`Self::from_toml`]), or the value of an
+/// existing configuration (see [`Self::merge_toml`]).
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct AlignerConfig {
+    pub mode: AlignmentMode,
+    pub match_score: i32,
+    pub mismatch_score: i32,
+    pub gap_open: i32,
+    pub gap_extend: i32,
+    pub default_jump_score: i32,
+    pub jump_score_same_contig_and_strand: Option<i32>,
+    pub jump_score_same_contig_opposite_strand: Option<i32>,
+    pub jump_score_inter_contig: Option<i32>,
+    pub jump_score_forward: Option<i32>,
+    pub jump_score_backward: Option<i32>,
+    pub splice_jump_score: Option<i32>,
+    pub min_segment_length: Option<usize>,
+    pub forbid_clip_jumps: bool,
+    pub scaffold_gap_min_len: Option<usize>,
+    pub homopolymer_gap_open: Option<i32>,
+    pub homopolymer_min_length: usize,
+    pub kmer_size: usize,
+    pub band_width: usize,
+    pub adaptive_band_width: Option<usize>,
+    pub parallel_block_rows: Option<usize>,
+    pub double_strand: bool,
+    pub circular: bool,
+    pub lazy_reverse_complement: bool,
+    pub canonical_contig_order: bool,
+    pub circular_slop: usize,
+    pub pre_align: bool,
+    pub pre_align_min_score: i32,
+    pub pre_align_subset_contigs: bool,
+    pub pre_filter: bool,
+    pub pre_filter_min_score: i32,
+    pub suboptimal: bool,
+    pub suboptimal_pct: f32,
+    pub clip_policy: ClipPolicy,
+    pub use_eq_and_x: bool,
+    pub pick_primary: PrimaryPickingStrategy,
+    pub filter_secondary: bool,
+    pub filter_secondary_pct: f32,
+    pub tie_break_length: AlignmentLength,
+    pub cs_tag: Option<CsStyle>,
+    pub max_matrix_cells: Option<usize>,
+    pub truncate_oversized_reads: bool,
+    pub read_timeout_ms: Option<u64>,
+    pub chunk_length: Option<usize>,
+    pub chunk_overlap: usize,
+    pub traceback_checkpoint_interval: Option<usize>,
+    pub traceback_layout: TracebackLayout,
+    pub max_read_memory: Option<usize>,
+    pub auto_strategy: Option<AutoStrategy>,
+    pub refine_jumps: bool,
+    pub refine_jumps_window: usize,
+    pub score_breakdown: bool,
+    /// The filter applied to the alignments of each query.  This must be the last field, as TOML
+    /// tables must follow all values.
+    pub filter: AlignmentFilter,
+}
+
+impl Default for AlignerConfig {
+    fn default() -> Self {
+        Self {
+            mode: AlignmentMode::default(),
+            match_score: 1,
+            mismatch_score: -4,
+            gap_open: -6,
+            gap_extend: -2,
+            default_jump_score: -10,
+            jump_score_same_contig_and_strand: None,
+            jump_score_same_contig_opposite_strand: None,
+            jump_score_inter_contig: None,
+            jump_score_forward: None,
+            jump_score_backward: None,
+            splice_jump_score: None,
+            min_segment_length: None,
+            forbid_clip_jumps: false,
+            scaffold_gap_min_len: None,
+            homopolymer_gap_open: None,
+            homopolymer_min_length: DEFAULT_HOMOPOLYMER_MIN_LENGTH,
+            kmer_size: 12,
+            band_width: 50,
+            adaptive_band_width: None,
+            parallel_block_rows: None,
+            double_strand: false,
+            circular: false,
+            lazy_reverse_complement: false,
+            canonical_contig_order: false,
+            circular_slop: 20,
+            pre_align: false,
+            pre_align_min_score: 100,
+            pre_align_subset_contigs: true,
+            pre_filter: false,
+            pre_filter_min_score: 100,
+            suboptimal: false,
+            suboptimal_pct: 20.0,
+            clip_policy: ClipPolicy::default(),
+            use_eq_and_x: false,
+            pick_primary: PrimaryPickingStrategy::default(),
+            filter_secondary: false,
+            filter_secondary_pct: 10.0,
+            tie_break_length: AlignmentLength::default(),
+            cs_tag: None,
+            max_matrix_cells: None,
+            truncate_oversized_reads: false,
+            read_timeout_ms: None,
+            chunk_length: None,
+            chunk_overlap: 1000,
+            traceback_checkpoint_interval: None,
+            traceback_layout: TracebackLayout::default(),
+            max_read_memory: None,
+            auto_strategy: None,
+            refine_jumps: false,
+            refine_jumps_window: 20,
+            score_breakdown: false,
+            filter: AlignmentFilter::default(),
+        }
+    }
+}
+
+impl AlignerConfig {
+    /// Creates the builder of the aligners with this configuration.
+    pub fn builder(&self) -> Builder {
+        let mut builder = Builder::default();
+        builder
+            .mode(self.mode)
+            .match_score(self.match_score)
+            .mismatch_score(self.mismatch_score)
+            .gap_open(self.gap_open)
+            .gap_extend(self.gap_extend)
+            .default_jump_score(self.default_jump_score)
+            .jump_score_same_contig_and_strand(self.jump_score_same_contig_and_strand)
+            .jump_score_same_contig_opposite_strand(self.jump_score_same_contig_opposite_strand)
+            .jump_score_inter_contig(self.jump_score_inter_contig)
+            .jump_score_forward(self.jump_score_forward)
+            .jump_score_backward(self.jump_score_backward)
+            .splice_jump_score(self.splice_jump_score)
+            .min_segment_length(self.min_segment_length)
+            .forbid_clip_jumps(self.forbid_clip_jumps)
+            .scaffold_gap_min_len(self.scaffold_gap_min_len)
+            .homopolymer_gap_open(self.homopolymer_gap_open)
+            .homopolymer_min_length(self.homopolymer_min_length)
+            .kmer_size(self.kmer_size)
+            .band_width(self.band_width)
+            .adaptive_band_width(self.adaptive_band_width)
+            .parallel_block_rows(self.parallel_block_rows)
+            .double_strand(self.double_strand)
+            .circular(self.circular)
+            .lazy_reverse_complement(self.lazy_reverse_complement)
+            .canonical_contig_order(self.canonical_contig_order)
+            .circular_slop(self.circular_slop)
+            .pre_align(self.pre_align)
+            .pre_align_min_score(self.pre_align_min_score)
+            .pre_align_subset_contigs(self.pre_align_subset_contigs)
+            .pre_filter(self.pre_filter)
+            .pre_filter_min_score(self.pre_filter_min_score)
+            .suboptimal(self.suboptimal)
+            .suboptimal_pct(self.suboptimal_pct)
+            .clip_policy(self.clip_policy)
+            .use_eq_and_x(self.use_eq_and_x)
+            .pick_primary(self.pick_primary)
+            .filter_secondary(self.filter_secondary)
+            .filter_secondary_pct(self.filter_secondary_pct)
+            .tie_break_length(self.tie_break_length)
+            .cs_tag(self.cs_tag)
+            .max_matrix_cells(self.max_matrix_cells)
+            .truncate_oversized_reads(self.truncate_oversized_reads)
+            .read_timeout_ms(self.read_timeout_ms)
+            .chunk_length(self.chunk_length)
+            .chunk_overlap(self.chunk_overlap)
+            .traceback_checkpoint_interval(self.traceback_checkpoint_interval)
+            .traceback_layout(self.traceback_layout)
+            .max_read_memory(self.max_read_memory)
+            .auto_strategy(self.auto_strategy)
+            .refine_jumps(self.refine_jumps)
+            .refine_jumps_window(self.refine_jumps_window)
+            .score_breakdown(self.score_breakdown);
+        builder
+    }
+
+    /// Sets the match, mismatch, gap (including homopolymer gap), and jump scores from the given
+    /// scoring (e.g. a preset).  The
+    /// jump score for the same contig and strand becomes the default jump score, with the other
+    /// jump scores set only if they differ from it.
+    pub fn set_scoring(&mut self, scoring: &Scoring<MatchParams>) {
+        let default_jump_score = scoring.jump_score_same_contig_and_strand;
+        let if_differs = |score: i32| (score != default_jump_score).then_some(score);
+        self.match_score = scoring.match_fn.match_score;
+        self.mismatch_score = scoring.match_fn.mismatch_score;
+        self.gap_open = scoring.gap_open;
+        self.gap_extend = scoring.gap_extend;
+        self.default_jump_score = default_jump_score;
+        self.jump_score_same_contig_and_strand = None;
+        self.jump_score_same_contig_opposite_strand =
+            if_differs(scoring.jump_score_same_contig_opposite_strand);
+        self.jump_score_inter_contig = if_differs(scoring.jump_score_inter_contig);
+        self.jump_score_forward = scoring.jump_score_forward;
+        self.jump_score_backward = scoring.jump_score_backward;
+        self.splice_jump_score = scoring.splice_jump_score;
+        self.homopolymer_gap_open = scoring.homopolymer_gap_open;
+        self.homopolymer_min_length = scoring.homopolymer_min_length;
+    }
+}
+
+#[cfg(feature = "serde")]
+impl AlignerConfig {
+    /// Writes the configuration as TOML, omitting unset optional parameters.
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).context("Could not serialize the aligner configuration")
+    }
+
+    /// Reads the configuration from TOML, with any missing parameters taking their default value.
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str(toml).context("Could not parse the aligner configuration")
+    }
+
+    /// Reads the configuration from TOML, with any missing parameters taking their value in this
+    /// configuration.
+    pub fn merge_toml(&self, toml: &str) -> Result<Self> {
+        let mut merged = toml::Value::try_from(self)?;
+        let overrides: toml::Value =
+            toml::from_str(toml).context("Could not parse the aligner configuration")?;
+        merge_tables(&mut merged, overrides);
+        merged
+            .try_into()
+            .context("Could not parse the aligner configuration")
+    }
+
+    /// Reads the configuration from the given TOML file, with any missing parameters taking their
+    /// value in this configuration.
+    pub fn merge_file(&self, path: &Path) -> Result<Self> {
+        let toml = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read config: {}", path.display()))?;
+        self.merge_toml(&toml)
+            .with_context(|| format!("Invalid config: {}", path.display()))
+    }
+}
+
+/// Overwrites the values in `base` with those in `overrides`, recursing into tables.
+#[cfg(feature = "serde")]
+fn merge_tables(base: &mut toml::Value, overrides: toml::Value) {
+    match (base, overrides) {
+        (toml::Value::Table(base), toml::Value::Table(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_tables(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::AlignerConfig;
+    #[cfg(feature = "serde")]
+    use crate::align::AlignmentMode;
+
+    #[rstest]
+    fn test_builder() {
+        let config = AlignerConfig {
+            mismatch_score: -3,
+            kmer_size: 8,
+            ..AlignerConfig::default()
+        };
+        let scoring = config.builder().build_scoring();
+        assert_eq!(scoring.match_fn.mismatch_score, -3);
+        assert_eq!(scoring.jump_score_inter_contig, -10);
+
+        // the defaults match those of the builder
+        let scoring = AlignerConfig::default().builder().build_scoring();
+        assert_eq!(scoring, crate::align::Builder::default().build_scoring());
+    }
+
+    #[rstest]
+    fn test_set_scoring() {
+        let mut config = AlignerConfig {
+            jump_score_inter_contig: Some(-20),
+            ..AlignerConfig::default()
+        };
+        let preset = crate::align::Scoring::preset_ont();
+        config.set_scoring(&preset);
+        assert_eq!(config.jump_score_inter_contig, None);
+        // the clipping scores are set by the alignment mode
+        let scoring = config.builder().build_scoring();
+        assert_eq!(scoring.match_fn, preset.match_fn);
+        assert_eq!(
+            (scoring.gap_open, scoring.gap_extend),
+            (preset.gap_open, preset.gap_extend)
+        );
+        assert_eq!(
+            scoring.jump_score_inter_contig,
+            preset.jump_score_inter_contig
+        );
+        assert_eq!(
+            scoring.jump_score_same_contig_and_strand,
+            preset.jump_score_same_contig_and_strand
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    fn test_toml_round_trip() {
+        let mut config = AlignerConfig {
+            mode: AlignmentMode::Global,
+            jump_score_inter_contig: Some(-20),
+            suboptimal_pct: 12.5,
+            ..AlignerConfig::default()
+        };
+        config.filter.min_identity = Some(0.9);
+        let toml = config.to_toml().unwrap();
+        assert!(toml.contains("mode = \"Global\"\n"));
+        assert!(toml.contains("jump_score_inter_contig = -20\n"));
+        assert!(!toml.contains("jump_score_same_contig_and_strand"));
+        assert!(toml.contains("[filter]\nmin_identity = 0.9\n"));
+        assert_eq!(AlignerConfig::from_toml(&toml).unwrap(), config);
+    }
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    fn test_from_toml_defaults() {
+        let config = AlignerConfig::from_toml("match_score = 2\n").unwrap();
+        let expected = AlignerConfig {
+            match_score: 2,
+            ..AlignerConfig::default()
+        };
+        assert_eq!(config, expected);
+        assert!(AlignerConfig::from_toml("match_scor = 2\n").is_err());
+        assert!(AlignerConfig::from_toml("match_score = \"two\"\n").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    fn test_merge_toml() {
+        let mut base = AlignerConfig {
+            mode: AlignmentMode::QueryLocal,
+            gap_open: -5,
+            ..AlignerConfig::default()
+        };
+        base.filter.min_score = Some(10);
+        let toml =
+            "gap_extend = -1\njump_score_same_contig_and_strand = -3\n\n[filter]\nmax_jumps = 2\n";
+        let merged = base.merge_toml(toml).unwrap();
+        let mut expected = AlignerConfig {
+            gap_extend: -1,
+            jump_score_same_contig_and_strand: Some(-3),
+            ..base
+        };
+        expected.filter.max_jumps = Some(2);
+        assert_eq!(merged, expected);
+        assert!(base.merge_toml("unknown = 1\n").is_err());
+    }
+}
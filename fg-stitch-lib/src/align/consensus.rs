@@ -0,0 +1,332 @@
+//! Consensus sequences of the targets built from the pileup of many alignments of reads.
+
+use std::collections::HashMap;
+
+use super::{
+    aligners::constants::AlignmentOperation::{Del, Ins, Match, Subst},
+    alignment::{target_seq_for, Alignment},
+};
+use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+/// The bases counted in the pileup, with any other base counted as an `N`.
+const BASES: [u8; 5] = *b"ACGTN";
+
+/// The maximum quality of a consensus base.
+pub const MAX_CONSENSUS_QUALITY: u8 = 93;
+
+/// Returns the index in [`BASES`] of the given base.
+fn base_index(base: u8) -> usize {
+    match base.to_ascii_uppercase() {
+        b'A' => 0,
+        b'C' => 1,
+        b'G' => 2,
+        b'T' => 3,
+        _ => 4,
+    }
+}
+
+/// Returns the phred-scaled quality of a consensus with the given support and depth, where the
+/// error probability is estimated as `(depth - support + 1) / (depth + 2)`.
+fn consensus_quality(support: usize, depth: usize) -> u8 {
+    let error = (depth - support + 1) as f64 / (depth + 2) as f64;
+    let quality = (-10.0 * error.log10()).round();
+    quality.min(f64::from(MAX_CONSENSUS_QUALITY)) as u8
+}
+
+/// The pileup of a single target, on its forward strand.
+#[derive(Debug, Clone)]
+struct Pileup {
+    /// The number of reads with each of [`BASES`] at each position.
+    bases: Vec<[usize; BASES.len()]>,
+    /// The number of reads with each position deleted.
+    deletions: Vec<usize>,
+    /// The number of reads with each inserted sequence before each position (including the
+    /// position after the last base).
+    insertions: Vec<HashMap<Vec<u8>, usize>>,
+}
+
+impl Pileup {
+    fn new(len: usize) -> Self {
+        Self {
+            bases: vec![[0; BASES.len()]; len],
+            deletions: vec![0; len],
+            insertions: vec![HashMap::new(); len + 1],
+        }
+    }
+
+    /// The number of reads with a base or deletion at the given position.
+    fn depth(&self, pos: usize) -> usize {
+        self.bases[pos].iter().sum::<usize>() + self.deletions[pos]
+    }
+}
+
+/// The consensus sequence of a single target.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Consensus {
+    /// The name of the target.
+    pub name: String,
+    /// The consensus bases, with `N` for positions with insufficient depth.
+    pub seq: Vec<u8>,
+    /// The number of reads supporting each consensus base.
+    pub depths: Vec<usize>,
+    /// The phred-scaled quality of each consensus base (not offset by 33).
+    pub quals: Vec<u8>,
+}
+
+/// Builds the consensus sequences of a set of targets from the alignments of many reads.
+///
+/// Each read contributes one vote per aligned base (or deleted base) to the forward strand of the
+/// target, with reads aligned to the reverse strand reverse complemented.  At each position, the
+/// consensus is the most common base, or no base if a deletion is more common than any base.  An
+/// insertion before a position is included if it is the most common insertion at that position and
+/// is supported by more than half the reads covering the surrounding bases.
+#[derive(Debug, Clone)]
+pub struct ConsensusBuilder {
+    target_seqs: Vec<TargetSeq>,
+    pileups: Vec<Pileup>,
+}
+
+impl ConsensusBuilder {
+    /// Creates a new builder for the given targets, with contig indexes following the convention
+    /// of [`crate::align::Builder::build_aligners`].
+    pub fn new(target_seqs: &[TargetSeq]) -> Self {
+        Self {
+            target_seqs: target_seqs.to_vec(),
+            pileups: target_seqs.iter().map(|t| Pileup::new(t.len())).collect(),
+        }
+    }
+
+    /// Adds the alignment of the given read to the pileup.
+    pub fn add(&mut self, alignment: &Alignment, y: &[u8]) {
+        // The target index, the position before which the insertion occurs on the forward strand,
+        // the strand of the contig, and the inserted bases on the strand of the contig.
+        let mut insertion: Option<(usize, usize, bool, Vec<u8>)> = None;
+        for step in alignment.iter_steps() {
+            let (target_seq, is_forward) = target_seq_for(&self.target_seqs, step.contig_idx);
+            let target_idx = step.contig_idx % self.target_seqs.len();
+            let len = target_seq.len();
+            if step.op != Del {
+                if let Some((target_idx, pos, is_forward, bases)) = insertion.take() {
+                    self.add_insertion(target_idx, pos, is_forward, bases);
+                }
+            }
+            match step.op {
+                Match | Subst => {
+                    let pileup = &mut self.pileups[target_idx];
+                    if is_forward {
+                        pileup.bases[step.x_pos][base_index(y[step.y_pos])] += 1;
+                    } else {
+                        let base = reverse_complement([y[step.y_pos]])[0];
+                        pileup.bases[len - 1 - step.x_pos][base_index(base)] += 1;
+                    }
+                }
+                Ins => {
+                    let pos = if is_forward {
+                        step.x_pos
+                    } else {
+                        len - 1 - step.x_pos
+                    };
+                    self.pileups[target_idx].deletions[pos] += 1;
+                }
+                Del => {
+                    let (_, _, _, bases) = insertion.get_or_insert_with(|| {
+                        let pos = if is_forward {
+                            step.x_pos
+                        } else {
+                            len - step.x_pos
+                        };
+                        (target_idx, pos, is_forward, Vec::new())
+                    });
+                    bases.push(y[step.y_pos]);
+                }
+                _ => (),
+            }
+        }
+        if let Some((target_idx, pos, is_forward, bases)) = insertion.take() {
+            self.add_insertion(target_idx, pos, is_forward, bases);
+        }
+    }
+
+    /// Adds an insertion to the pileup, reverse complementing the bases if they are on the
+    /// reverse strand.
+    fn add_insertion(&mut self, target_idx: usize, pos: usize, is_forward: bool, bases: Vec<u8>) {
+        let bases = if is_forward {
+            bases.to_ascii_uppercase()
+        } else {
+            reverse_complement(bases.to_ascii_uppercase())
+        };
+        *self.pileups[target_idx].insertions[pos]
+            .entry(bases)
+            .or_default() += 1;
+    }
+
+    /// Builds the consensus sequence of each target, in the order of the targets.  Positions
+    /// covered by fewer than `min_depth` reads are `N` with zero quality.
+    pub fn build(&self, min_depth: usize) -> Vec<Consensus> {
+        self.target_seqs
+            .iter()
+            .zip(self.pileups.iter())
+            .map(|(target_seq, pileup)| Self::build_one(target_seq, pileup, min_depth))
+            .collect()
+    }
+
+    fn build_one(target_seq: &TargetSeq, pileup: &Pileup, min_depth: usize) -> Consensus {
+        let mut consensus = Consensus {
+            name: target_seq.name.clone(),
+            seq: Vec::new(),
+            depths: Vec::new(),
+            quals: Vec::new(),
+        };
+        let len = target_seq.len();
+        for pos in 0..=len {
+            // The insertion before this position
+            let spanning = match pos {
+                0 if len == 0 => 0,
+                0 => pileup.depth(0),
+                _ if pos == len => pileup.depth(len - 1),
+                _ => pileup.depth(pos - 1).max(pileup.depth(pos)),
+            };
+            let best_insertion =
+                pileup.insertions[pos]
+                    .iter()
+                    .max_by(|(a_bases, a_count), (b_bases, b_count)| {
+                        a_count.cmp(b_count).then_with(|| b_bases.cmp(a_bases))
+                    });
+            if let Some((bases, &count)) = best_insertion {
+                if spanning >= min_depth && 2 * count > spanning {
+                    let qual = consensus_quality(count, spanning);
+                    consensus.seq.extend_from_slice(bases);
+                    consensus
+                        .depths
+                        .extend(std::iter::repeat_n(count, bases.len()));
+                    consensus
+                        .quals
+                        .extend(std::iter::repeat_n(qual, bases.len()));
+                }
+            }
+            if pos == len {
+                break;
+            }
+
+            // The base at this position
+            let depth = pileup.depth(pos);
+            if depth == 0 || depth < min_depth {
+                consensus.seq.push(b'N');
+                consensus.depths.push(depth);
+                consensus.quals.push(0);
+                continue;
+            }
+            let (index, &support) = pileup.bases[pos]
+                .iter()
+                .enumerate()
+                .max_by(|(a_index, a), (b_index, b)| a.cmp(b).then_with(|| b_index.cmp(a_index)))
+                .unwrap();
+            if pileup.deletions[pos] > support {
+                continue;
+            }
+            consensus.seq.push(BASES[index]);
+            consensus.depths.push(support);
+            consensus.quals.push(consensus_quality(support, depth));
+        }
+        consensus
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{alignment::tests::align_double_strand, scoring::Scoring};
+    use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+    use super::{consensus_quality, ConsensusBuilder};
+
+    const TARGET: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-2, -1, -100, MatchParams::new(1, -1))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    /// Builds the consensus of the given reads, returning the consensus sequence and depths.
+    fn consensus(reads: &[Vec<u8>], min_depth: usize) -> (String, Vec<usize>) {
+        let target_seqs = [TargetSeq::new("chr1", &TARGET.as_bytes().to_vec(), false)];
+        let mut builder = ConsensusBuilder::new(&target_seqs);
+        for read in reads {
+            let alignment = align_double_strand(&target_seqs, read, scoring());
+            builder.add(&alignment, read);
+        }
+        let consensus = builder.build(min_depth).pop().unwrap();
+        assert_eq!(consensus.name, "chr1");
+        assert_eq!(consensus.seq.len(), consensus.depths.len());
+        assert_eq!(consensus.seq.len(), consensus.quals.len());
+        (String::from_utf8(consensus.seq).unwrap(), consensus.depths)
+    }
+
+    #[rstest]
+    fn test_consensus_identical_reads() {
+        let read = TARGET.as_bytes().to_vec();
+        let (seq, depths) = consensus(&[read.clone(), read.clone(), read], 1);
+        assert_eq!(seq, TARGET);
+        assert!(depths.iter().all(|d| *d == 3));
+    }
+
+    #[rstest]
+    fn test_consensus_variants() {
+        let target = TARGET.as_bytes();
+        let snv = [&target[..20], b"G", &target[21..]].concat();
+        let deletion = [&target[..10], &target[11..]].concat();
+        let insertion = [&target[..30], b"GGG", &target[30..]].concat();
+        // every variant is supported by two of three reads
+        let reads = [
+            [&target[..10], &snv[10..]].concat(),
+            [&deletion[..29], b"GGG", &target[30..]].concat(),
+            [&target[..10], &target[11..20], b"G", &insertion[21..]].concat(),
+        ];
+        let expected = [
+            &target[..10],
+            &target[11..20],
+            b"G",
+            &target[21..30],
+            b"GGG",
+            &target[30..],
+        ]
+        .concat();
+        let (seq, _) = consensus(&reads, 1);
+        assert_eq!(seq, String::from_utf8(expected).unwrap());
+    }
+
+    #[rstest]
+    fn test_consensus_reverse_strand() {
+        let target = TARGET.as_bytes();
+        let snv = [&target[..20], b"G", &target[21..]].concat();
+        let reads = [snv.clone(), reverse_complement(&snv)];
+        let (seq, depths) = consensus(&reads, 1);
+        assert_eq!(seq, String::from_utf8(snv).unwrap());
+        assert!(depths.iter().all(|d| *d == 2));
+    }
+
+    #[rstest]
+    fn test_consensus_min_depth() {
+        let target = TARGET.as_bytes();
+        let reads = [target.to_vec(), target[10..].to_vec()];
+        let (seq, depths) = consensus(&reads, 2);
+        assert_eq!(seq, format!("{}{}", "N".repeat(10), &TARGET[10..]));
+        assert_eq!(depths[..10], [1; 10]);
+        assert_eq!(depths[10..], [2; 30]);
+    }
+
+    #[rstest]
+    #[case(0, 0, 3)]
+    #[case(1, 1, 5)]
+    #[case(3, 3, 7)]
+    #[case(2, 3, 4)]
+    #[case(1000, 1000, 30)]
+    #[case(100_000, 100_000, 50)]
+    fn test_consensus_quality(#[case] support: usize, #[case] depth: usize, #[case] qual: u8) {
+        assert_eq!(consensus_quality(support, depth), qual);
+    }
+}
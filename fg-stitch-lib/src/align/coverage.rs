@@ -0,0 +1,157 @@
+//! Per-position, per-strand coverage of the targets by the alignments of many queries, and its
+//! output as bedGraph tracks.
+
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::{
+    aligners::constants::AlignmentOperation::{Match, Subst},
+    alignment::{target_seq_for, Alignment},
+};
+use crate::util::target_seq::TargetSeq;
+
+/// Accumulates the depth of each position of each strand of each target, from the aligned bases
+/// (matches and mismatches) of alignments.  Positions are on the forward strand of the target,
+/// and the strand is that of the contig to which the bases were aligned.
+#[derive(Debug, Clone)]
+pub struct CoverageTrack {
+    /// The depths of each contig, following the contig indexes of
+    /// [`crate::align::Builder::build_aligners`], so forward strands first.
+    depths: Vec<Vec<u32>>,
+}
+
+impl CoverageTrack {
+    /// Creates an empty coverage track of both strands of the given targets.
+    pub fn new(target_seqs: &[TargetSeq]) -> Self {
+        let depths = target_seqs
+            .iter()
+            .chain(target_seqs)
+            .map(|target_seq| vec![0; target_seq.len()])
+            .collect();
+        Self { depths }
+    }
+
+    /// Adds the aligned bases of the alignment, following the alignment across the targets and
+    /// strands it jumps between.
+    pub fn add(&mut self, alignment: &Alignment, target_seqs: &[TargetSeq]) {
+        for step in alignment.iter_steps() {
+            if matches!(step.op, Match | Subst) {
+                let (target_seq, is_forward) = target_seq_for(target_seqs, step.contig_idx);
+                let pos = if is_forward {
+                    step.x_pos
+                } else {
+                    target_seq.len() - 1 - step.x_pos
+                };
+                self.depths[step.contig_idx][pos] += 1;
+            }
+        }
+    }
+
+    /// Returns the depths of the given target and strand, on the forward strand of the target.
+    pub fn depths(&self, target_idx: usize, is_forward: bool) -> &[u32] {
+        let num_targets = self.depths.len() / 2;
+        let contig_idx = if is_forward {
+            target_idx
+        } else {
+            target_idx + num_targets
+        };
+        &self.depths[contig_idx]
+    }
+
+    /// Writes the coverage of the given strand of all the targets in bedGraph format, with one
+    /// line per maximal run of positions with the same non-zero depth.
+    pub fn write_bedgraph<W: Write>(
+        &self,
+        writer: &mut W,
+        target_seqs: &[TargetSeq],
+        is_forward: bool,
+    ) -> Result<()> {
+        let strand = if is_forward { '+' } else { '-' };
+        writeln!(
+            writer,
+            "track type=bedGraph name=\"stitch coverage ({strand})\""
+        )?;
+        for (target_idx, target_seq) in target_seqs.iter().enumerate() {
+            let depths = self.depths(target_idx, is_forward);
+            let mut start = 0;
+            while start < depths.len() {
+                let depth = depths[start];
+                let end = depths[start..]
+                    .iter()
+                    .position(|d| *d != depth)
+                    .map_or(depths.len(), |len| start + len);
+                if depth > 0 {
+                    writeln!(writer, "{}\t{start}\t{end}\t{depth}", target_seq.name)?;
+                }
+                start = end;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{alignment::tests::align_double_strand, scoring::Scoring};
+    use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+    use super::CoverageTrack;
+
+    const CHR1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const CHR2: &str = "TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    fn target_seqs() -> Vec<TargetSeq> {
+        vec![
+            TargetSeq::new("chr1", &CHR1.as_bytes().to_vec(), false),
+            TargetSeq::new("chr2", &CHR2.as_bytes().to_vec(), false),
+        ]
+    }
+
+    #[rstest]
+    fn test_bedgraph() {
+        let target_seqs = target_seqs();
+        let mut track = CoverageTrack::new(&target_seqs);
+        // chr1:0-20 then chr2:20-40
+        let y = [&CHR1.as_bytes()[..20], &CHR2.as_bytes()[20..]].concat();
+        track.add(
+            &align_double_strand(&target_seqs, &y, scoring()),
+            &target_seqs,
+        );
+        // chr1:10-30, from the reverse strand
+        let y = reverse_complement(&CHR1.as_bytes()[10..30]);
+        track.add(
+            &align_double_strand(&target_seqs, &y, scoring()),
+            &target_seqs,
+        );
+
+        let mut output = Vec::new();
+        track
+            .write_bedgraph(&mut output, &target_seqs, true)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "track type=bedGraph name=\"stitch coverage (+)\"\nchr1\t0\t20\t1\nchr2\t20\t40\t1\n"
+        );
+
+        let mut output = Vec::new();
+        track
+            .write_bedgraph(&mut output, &target_seqs, false)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "track type=bedGraph name=\"stitch coverage (-)\"\nchr1\t10\t30\t1\n"
+        );
+        assert_eq!(track.depths(0, false)[9..11], [0, 1]);
+    }
+}
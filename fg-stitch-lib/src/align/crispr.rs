@@ -0,0 +1,500 @@
+//! Classification of reads from a CRISPR-edited amplicon by the outcome of the edit, based on the
+//! jump and indel structure of their alignments.
+
+use std::{fmt, io::Write, ops::Range};
+
+use anyhow::{ensure, Result};
+use bio::alignment::pairwise::MatchFunc;
+
+use super::{
+    aligners::{constants::MIN_SCORE, multi_contig_aligner::MultiContigAligner},
+    alignment::Alignment,
+    scoring::Scoring,
+    variants::{call_variants, Variant, VariantKind},
+};
+use crate::util::target_seq::TargetSeq;
+
+/// The outcome of the edit for a single read, in order of precedence when classifying.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub enum EditClass {
+    /// The read did not align.
+    Unaligned,
+    /// The read aligned to a target other than the amplicon.
+    Translocation,
+    /// The read has a jump within the amplicon that is not a large deletion, for example a
+    /// duplication or an inversion.
+    Complex,
+    /// The read has a deletion of at least the minimum large deletion length, either as a
+    /// forward jump or as a gap.
+    LargeDeletion,
+    /// The read has all the variants of the HDR template relative to the amplicon.
+    Hdr,
+    /// The read has an insertion or deletion overlapping the quantification window.
+    Nhej,
+    /// The read has no insertion or deletion overlapping the quantification window.
+    WildType,
+}
+
+impl EditClass {
+    /// All the classes, in order of precedence.
+    pub const ALL: [EditClass; 7] = [
+        Self::Unaligned,
+        Self::Translocation,
+        Self::Complex,
+        Self::LargeDeletion,
+        Self::Hdr,
+        Self::Nhej,
+        Self::WildType,
+    ];
+}
+
+impl fmt::Display for EditClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unaligned => write!(f, "unaligned"),
+            Self::Translocation => write!(f, "translocation"),
+            Self::Complex => write!(f, "complex"),
+            Self::LargeDeletion => write!(f, "large-deletion"),
+            Self::Hdr => write!(f, "hdr"),
+            Self::Nhej => write!(f, "nhej"),
+            Self::WildType => write!(f, "wild-type"),
+        }
+    }
+}
+
+/// Classifies reads aligned to an amplicon by the outcome of the edit (see [`EditClass`]).
+#[derive(Debug, Clone)]
+pub struct EditClassifier {
+    /// The index of the amplicon in the targets.
+    amplicon_idx: usize,
+    /// The variants of the HDR template relative to the amplicon.
+    expected_variants: Vec<Variant>,
+    /// The range of the amplicon in which an indel is considered an NHEJ outcome.
+    window: Range<usize>,
+    /// The minimum length of a deletion to be considered a large deletion.
+    min_large_deletion: usize,
+}
+
+impl EditClassifier {
+    /// Creates a new classifier.
+    ///
+    /// Arguments:
+    /// - `target_seqs` - The targets to which the reads are aligned.
+    /// - `amplicon_idx` - The index of the amplicon in `target_seqs`.
+    /// - `hdr_template` - The expected sequence of the amplicon after HDR, if any.  The template
+    ///   is globally aligned to the amplicon to determine the expected variants.
+    /// - `window` - The range of the amplicon in which an indel is considered an NHEJ outcome, or
+    ///   `None` for the whole amplicon.
+    /// - `min_large_deletion` - The minimum length of a deletion to be considered a large deletion.
+    /// - `scoring` - The scoring used to align the HDR template to the amplicon.
+    pub fn new<F: MatchFunc + Sync>(
+        target_seqs: &[TargetSeq],
+        amplicon_idx: usize,
+        hdr_template: Option<&[u8]>,
+        window: Option<Range<usize>>,
+        min_large_deletion: usize,
+        mut scoring: Scoring<F>,
+    ) -> Result<Self> {
+        ensure!(
+            amplicon_idx < target_seqs.len(),
+            "Amplicon index {amplicon_idx} out of range for {} targets",
+            target_seqs.len()
+        );
+        ensure!(
+            min_large_deletion > 0,
+            "The minimum large deletion must be positive"
+        );
+        let amplicon = &target_seqs[amplicon_idx];
+        let window = window.unwrap_or(0..amplicon.len());
+        ensure!(
+            window.start < window.end && window.end <= amplicon.len(),
+            "Window {window:?} out of range for the amplicon with length {}",
+            amplicon.len()
+        );
+
+        let expected_variants = match hdr_template {
+            None => Vec::new(),
+            Some(hdr_template) => {
+                scoring.xclip_prefix = MIN_SCORE;
+                scoring.xclip_suffix = MIN_SCORE;
+                scoring.yclip_prefix = MIN_SCORE;
+                scoring.yclip_suffix = MIN_SCORE;
+                let mut aligner = MultiContigAligner::new();
+                aligner.add_contig(&amplicon.name, true, &amplicon.fwd, false, scoring);
+                let alignment = aligner.custom(hdr_template);
+                // the aligner only knows about the amplicon, so the contig index is zero
+                let target_seqs = std::slice::from_ref(amplicon);
+                let variants = call_variants(&alignment, target_seqs, hdr_template)
+                    .into_iter()
+                    .map(|variant| Variant {
+                        target_idx: amplicon_idx,
+                        ..variant
+                    })
+                    .collect::<Vec<_>>();
+                ensure!(
+                    !variants.is_empty(),
+                    "The HDR template is identical to the amplicon: {}",
+                    amplicon.name
+                );
+                variants
+            }
+        };
+
+        Ok(Self {
+            amplicon_idx,
+            expected_variants,
+            window,
+            min_large_deletion,
+        })
+    }
+
+    /// Returns the variants of the HDR template relative to the amplicon.
+    pub fn expected_variants(&self) -> &[Variant] {
+        &self.expected_variants
+    }
+
+    /// Classifies a read given its best alignment, or `None` if it did not align.
+    ///
+    /// Arguments:
+    /// - `alignment` - The best alignment of the read, if any.
+    /// - `target_seqs` - The targets to which the read was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `y` - The read sequence that was aligned.
+    pub fn classify(
+        &self,
+        alignment: Option<&Alignment>,
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+    ) -> EditClass {
+        let Some(alignment) = alignment.filter(|a| a.length > 0) else {
+            return EditClass::Unaligned;
+        };
+        let on_amplicon = |contig_idx: usize| contig_idx % target_seqs.len() == self.amplicon_idx;
+
+        // Any alignment to a target other than the amplicon
+        if alignment
+            .iter_steps()
+            .any(|step| !on_amplicon(step.contig_idx))
+        {
+            return EditClass::Translocation;
+        }
+
+        // Jumps within the amplicon, where a forward jump on the same strand is a deletion
+        let mut large_deletion = false;
+        for breakpoint in alignment.breakpoints(target_seqs) {
+            let deleted = match (breakpoint.from_forward, breakpoint.to_forward) {
+                (true, true) if breakpoint.to_pos > breakpoint.from_pos => {
+                    Some(breakpoint.to_pos - breakpoint.from_pos - 1)
+                }
+                (false, false) if breakpoint.from_pos > breakpoint.to_pos => {
+                    Some(breakpoint.from_pos - breakpoint.to_pos - 1)
+                }
+                _ => None,
+            };
+            match deleted {
+                Some(len) if len >= self.min_large_deletion => large_deletion = true,
+                Some(_) => (),
+                None => return EditClass::Complex,
+            }
+        }
+
+        let variants = call_variants(alignment, target_seqs, y);
+        let is_large_deletion = |v: &Variant| {
+            v.kind == VariantKind::Deletion && v.ref_allele.len() > self.min_large_deletion
+        };
+        if large_deletion || variants.iter().any(is_large_deletion) {
+            return EditClass::LargeDeletion;
+        }
+
+        // NB: the position in the query is ignored
+        let has_variant = |expected: &Variant| {
+            variants.iter().any(|v| {
+                v.pos == expected.pos
+                    && v.ref_allele == expected.ref_allele
+                    && v.alt_allele == expected.alt_allele
+            })
+        };
+        if !self.expected_variants.is_empty() && self.expected_variants.iter().all(has_variant) {
+            return EditClass::Hdr;
+        }
+
+        let in_window = |v: &Variant| {
+            v.kind != VariantKind::Snv
+                && v.pos < self.window.end
+                && self.window.start < v.pos + v.ref_allele.len()
+        };
+        if variants.iter().any(in_window) {
+            EditClass::Nhej
+        } else {
+            EditClass::WildType
+        }
+    }
+}
+
+/// Counts the number of reads in each [`EditClass`].
+#[derive(Debug, Default, Clone)]
+pub struct EditSummary {
+    counts: [usize; EditClass::ALL.len()],
+}
+
+impl EditSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a read with the given class.
+    pub fn add(&mut self, class: EditClass) {
+        self.counts[class as usize] += 1;
+    }
+
+    /// Returns the number of reads with the given class.
+    pub fn count(&self, class: EditClass) -> usize {
+        self.counts[class as usize]
+    }
+
+    /// Returns the total number of reads.
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Writes the summary as a tab-delimited table, with the number and fraction of reads in each
+    /// class.
+    pub fn write_table<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writeln!(writer, "class\tcount\tfraction")?;
+        let total = self.total();
+        for class in EditClass::ALL {
+            let count = self.count(class);
+            let fraction = if total == 0 {
+                0.0
+            } else {
+                count as f64 / total as f64
+            };
+            writeln!(writer, "{class}\t{count}\t{fraction:.6}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the number of reads in each [`EditClass`] in each of many samples aligned in the same
+/// run, for a matrix of the outcomes across the samples.
+#[derive(Debug, Clone)]
+pub struct SampleEditSummary {
+    samples: Vec<String>,
+    summaries: Vec<EditSummary>,
+}
+
+impl SampleEditSummary {
+    pub fn new(samples: &[String]) -> Self {
+        Self {
+            samples: samples.to_vec(),
+            summaries: vec![EditSummary::new(); samples.len()],
+        }
+    }
+
+    /// Adds a read of the sample with the given index with the given class.
+    pub fn add(&mut self, sample_idx: usize, class: EditClass) {
+        self.summaries[sample_idx].add(class);
+    }
+
+    /// Returns the names of the samples.
+    pub fn samples(&self) -> &[String] {
+        &self.samples
+    }
+
+    /// Returns the summary of the sample with the given index.
+    pub fn summary(&self, sample_idx: usize) -> &EditSummary {
+        &self.summaries[sample_idx]
+    }
+
+    /// Returns the total number of reads across all the samples.
+    pub fn total(&self) -> usize {
+        self.summaries.iter().map(EditSummary::total).sum()
+    }
+
+    /// Writes the summary as a tab-delimited table with one row per class, and for each sample,
+    /// the number and fraction of reads of the sample in the class, in the `<sample>.count` and
+    /// `<sample>.fraction` columns respectively.
+    pub fn write_table<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write!(writer, "class")?;
+        for sample in &self.samples {
+            write!(writer, "\t{sample}.count\t{sample}.fraction")?;
+        }
+        writeln!(writer)?;
+        for class in EditClass::ALL {
+            write!(writer, "{class}")?;
+            for summary in &self.summaries {
+                let count = summary.count(class);
+                let fraction = if summary.total() == 0 {
+                    0.0
+                } else {
+                    count as f64 / summary.total() as f64
+                };
+                write!(writer, "\t{count}\t{fraction:.6}")?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{alignment::tests::align_double_strand, scoring::Scoring};
+    use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+    use super::{EditClass, EditClassifier, EditSummary, SampleEditSummary};
+
+    //                     0         1         2         3         4         5         6
+    //                     0123456789012345678901234567890123456789012345678901234567890123456789
+    const AMPLICON: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACTTGTTGGCCCAGTGTGAATCGCTTAAGGG";
+    const OTHER: &str = "TTAACCGGATCGATCGGCTAGCTAGGATCCGATCGAATTCGCTAGC";
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    fn target_seqs() -> Vec<TargetSeq> {
+        vec![
+            TargetSeq::new("amplicon", &AMPLICON.as_bytes().to_vec(), false),
+            TargetSeq::new("other", &OTHER.as_bytes().to_vec(), false),
+        ]
+    }
+
+    /// The amplicon with a substitution of the GAC at 32..35 with TTT.
+    fn hdr_template() -> Vec<u8> {
+        [
+            &AMPLICON.as_bytes()[..32],
+            b"TTT",
+            &AMPLICON.as_bytes()[35..],
+        ]
+        .concat()
+    }
+
+    fn classify(read: &[u8]) -> EditClass {
+        let target_seqs = target_seqs();
+        let template = hdr_template();
+        let classifier = EditClassifier::new(
+            &target_seqs,
+            0,
+            Some(&template),
+            Some(25..45),
+            20,
+            scoring(),
+        )
+        .unwrap();
+        let alignment = align_double_strand(&target_seqs, read, scoring());
+        classifier.classify(Some(&alignment), &target_seqs, read)
+    }
+
+    #[rstest]
+    fn test_expected_variants() {
+        let target_seqs = target_seqs();
+        let template = hdr_template();
+        let classifier =
+            EditClassifier::new(&target_seqs, 0, Some(&template), None, 20, scoring()).unwrap();
+        assert_eq!(classifier.expected_variants().len(), 3);
+
+        let result = EditClassifier::new(
+            &target_seqs,
+            0,
+            Some(AMPLICON.as_bytes()),
+            None,
+            20,
+            scoring(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    fn test_classify() {
+        let amplicon = AMPLICON.as_bytes();
+        assert_eq!(classify(amplicon), EditClass::WildType);
+        // an SNV is still wild-type
+        let read = [&amplicon[..10], b"T", &amplicon[11..]].concat();
+        assert_eq!(classify(&read), EditClass::WildType);
+        // a deletion outside of the window is still wild-type
+        let read = [&amplicon[..10], &amplicon[12..]].concat();
+        assert_eq!(classify(&read), EditClass::WildType);
+        // a deletion in the window
+        let read = [&amplicon[..38], &amplicon[40..]].concat();
+        assert_eq!(classify(&read), EditClass::Nhej);
+        // an insertion in the window
+        let read = [&amplicon[..38], b"GGG", &amplicon[38..]].concat();
+        assert_eq!(classify(&read), EditClass::Nhej);
+        // HDR, on either strand
+        assert_eq!(classify(&hdr_template()), EditClass::Hdr);
+        assert_eq!(
+            classify(&reverse_complement(hdr_template())),
+            EditClass::Hdr
+        );
+        // a large deletion
+        let read = [&amplicon[..20], &amplicon[45..]].concat();
+        assert_eq!(classify(&read), EditClass::LargeDeletion);
+        // a duplication
+        let read = [&amplicon[..40], &amplicon[20..]].concat();
+        assert_eq!(classify(&read), EditClass::Complex);
+        // a translocation
+        let read = [&amplicon[..35], &OTHER.as_bytes()[10..40]].concat();
+        assert_eq!(classify(&read), EditClass::Translocation);
+    }
+
+    #[rstest]
+    fn test_classify_unaligned() {
+        let target_seqs = target_seqs();
+        let classifier = EditClassifier::new(&target_seqs, 0, None, None, 20, scoring()).unwrap();
+        assert_eq!(
+            classifier.classify(None, &target_seqs, b"ACGT"),
+            EditClass::Unaligned
+        );
+    }
+
+    #[rstest]
+    fn test_edit_summary() {
+        let mut summary = EditSummary::new();
+        summary.add(EditClass::WildType);
+        summary.add(EditClass::WildType);
+        summary.add(EditClass::Hdr);
+        summary.add(EditClass::Nhej);
+        assert_eq!(summary.total(), 4);
+        assert_eq!(summary.count(EditClass::WildType), 2);
+        assert_eq!(summary.count(EditClass::Translocation), 0);
+
+        let mut table: Vec<u8> = Vec::new();
+        summary.write_table(&mut table).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        let lines = table.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "class\tcount\tfraction");
+        assert_eq!(lines[5], "hdr\t1\t0.250000");
+        assert_eq!(lines[7], "wild-type\t2\t0.500000");
+    }
+
+    #[rstest]
+    fn test_sample_edit_summary() {
+        let samples = ["s1".to_string(), "s2".to_string()];
+        let mut summary = SampleEditSummary::new(&samples);
+        summary.add(0, EditClass::WildType);
+        summary.add(0, EditClass::Hdr);
+        summary.add(1, EditClass::Hdr);
+        assert_eq!(summary.total(), 3);
+        assert_eq!(summary.summary(0).count(EditClass::Hdr), 1);
+        assert_eq!(summary.summary(1).total(), 1);
+
+        let mut table: Vec<u8> = Vec::new();
+        summary.write_table(&mut table).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        let lines = table.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), EditClass::ALL.len() + 1);
+        assert_eq!(
+            lines[0],
+            "class\ts1.count\ts1.fraction\ts2.count\ts2.fraction"
+        );
+        assert_eq!(lines[5], "hdr\t1\t0.500000\t1\t1.000000");
+        assert_eq!(lines[6], "nhej\t0\t0.000000\t0\t0.000000");
+    }
+}
@@ -0,0 +1,161 @@
+//! Reuse of alignments across reads with identical sequences.
+
+use std::collections::{HashMap, VecDeque};
+
+use super::alignment::Alignment;
+
+/// The alignments of a query, and the best score if any alignment was attempted, as returned by
+/// aligning a read with the aligners built by [`crate::align::Builder`].
+pub type AlignmentResult = (Vec<Alignment>, Option<i32>);
+
+/// A cache of the alignments of query sequences, so that reads with identical sequences are
+/// aligned once, even when they are not adjacent in the input (as is common for amplicon data).
+/// Sequences are compared case-insensitively, as they are aligned.  Holds the alignments of at
+/// most `capacity` sequences, evicting the least recently added sequence when full.
+#[derive(Debug, Default, Clone)]
+pub struct AlignmentCache {
+    capacity: usize,
+    results: HashMap<Vec<u8>, AlignmentResult>,
+    order: VecDeque<Vec<u8>>,
+    hits: usize,
+}
+
+impl AlignmentCache {
+    /// Creates a cache holding the alignments of at most `capacity` sequences, where a capacity
+    /// of zero caches nothing.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            results: HashMap::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            hits: 0,
+        }
+    }
+
+    /// The number of sequences whose alignments are cached.
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// The number of times the alignments of a sequence were found in the cache.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Returns the cached alignments of the given sequence, if any.
+    pub fn get(&mut self, seq: &[u8]) -> Option<&AlignmentResult> {
+        let result = self.results.get(&seq.to_ascii_uppercase());
+        if result.is_some() {
+            self.hits += 1;
+        }
+        result
+    }
+
+    /// Caches the alignments of the given sequence, evicting the least recently added sequence if
+    /// full.
+    pub fn insert(&mut self, seq: &[u8], result: AlignmentResult) {
+        if self.capacity == 0 {
+            return;
+        }
+        let seq = seq.to_ascii_uppercase();
+        if let Some(cached) = self.results.get_mut(&seq) {
+            *cached = result;
+            return;
+        }
+        if self.results.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.results.remove(&oldest);
+            }
+        }
+        self.order.push_back(seq.clone());
+        self.results.insert(seq, result);
+    }
+
+    /// Removes the cached alignments of the given sequence, if any, for example when the
+    /// sequence was not aligned and should be attempted again.
+    pub fn remove(&mut self, seq: &[u8]) {
+        let seq = seq.to_ascii_uppercase();
+        if self.results.remove(&seq).is_some() {
+            self.order.retain(|cached| *cached != seq);
+        }
+    }
+
+    /// Returns the alignments of the given sequence from the cache, or otherwise computes them
+    /// with `align` and caches them.
+    pub fn get_or_align<A>(&mut self, seq: &[u8], align: A) -> AlignmentResult
+    where
+        A: FnOnce() -> AlignmentResult,
+    {
+        if let Some(result) = self.get(seq) {
+            return result.clone();
+        }
+        let result = align();
+        self.insert(seq, result.clone());
+        result
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::{AlignmentCache, AlignmentResult};
+    use crate::align::Alignment;
+
+    fn result(score: i32) -> AlignmentResult {
+        let alignment = Alignment {
+            score,
+            ..Default::default()
+        };
+        (vec![alignment], Some(score))
+    }
+
+    #[rstest]
+    fn test_get_or_align() {
+        let mut cache = AlignmentCache::new(2);
+        assert_eq!(cache.get_or_align(b"ACGT", || result(1)), result(1));
+        // identical sequences are not aligned again, regardless of case
+        assert_eq!(cache.get_or_align(b"acgt", || unreachable!()), result(1));
+        assert_eq!(cache.get_or_align(b"ACGA", || result(2)), result(2));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.hits(), 1);
+    }
+
+    #[rstest]
+    fn test_evicts_oldest() {
+        let mut cache = AlignmentCache::new(2);
+        cache.insert(b"A", result(1));
+        cache.insert(b"C", result(2));
+        cache.insert(b"G", result(3));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(b"A").is_none());
+        assert_eq!(cache.get(b"C"), Some(&result(2)));
+        assert_eq!(cache.get(b"G"), Some(&result(3)));
+    }
+
+    #[rstest]
+    fn test_remove() {
+        let mut cache = AlignmentCache::new(2);
+        cache.insert(b"A", result(1));
+        cache.insert(b"C", result(2));
+        cache.remove(b"a");
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(b"A").is_none());
+        // the removed sequence is no longer the oldest to evict
+        cache.insert(b"G", result(3));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(b"C"), Some(&result(2)));
+    }
+
+    #[rstest]
+    fn test_zero_capacity() {
+        let mut cache = AlignmentCache::new(0);
+        cache.insert(b"A", result(1));
+        assert!(cache.is_empty());
+        assert_eq!(cache.get_or_align(b"A", || result(2)), result(2));
+    }
+}
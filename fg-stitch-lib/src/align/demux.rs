@@ -0,0 +1,283 @@
+//! Demultiplexing of queries by the target (or group of targets) to which most of their bases
+//! are aligned.
+
+use std::{collections::HashMap, io::Write, path::Path};
+
+use anyhow::{bail, ensure, Context, Result};
+use fgoxide::io::Io;
+
+use super::{
+    aligners::constants::AlignmentOperation::{Match, Subst},
+    alignment::Alignment,
+};
+use crate::util::target_seq::TargetSeq;
+
+/// The name of the group of queries that are not assigned to any group.
+pub const UNASSIGNED: &str = "unassigned";
+
+/// Reads the groups of targets from a headerless tab-delimited file, with the target name in the
+/// first column and the name of its group in the second column.
+pub fn read_target_groups(path: &Path) -> Result<HashMap<String, String>> {
+    let lines = Io::default()
+        .read_lines(&path)
+        .with_context(|| format!("Error reading target groups: {}", path.display()))?;
+    let mut groups = HashMap::new();
+    for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+        let Some((target, group)) = line.split_once('\t') else {
+            bail!("Expected a target and a group separated by a tab: {line}");
+        };
+        let (target, group) = (target.trim(), group.trim());
+        ensure!(
+            groups
+                .insert(target.to_string(), group.to_string())
+                .is_none(),
+            "Target found more than once in the target groups: {target}"
+        );
+    }
+    Ok(groups)
+}
+
+/// Assigns queries to the group of targets to which most of their aligned bases (matches and
+/// mismatches) are aligned, such that a chimeric query is assigned by summing its aligned bases
+/// across the jumps of its alignment.
+#[derive(Debug, Clone)]
+pub struct Demultiplexer {
+    /// The group index of each target.
+    target_groups: Vec<usize>,
+    /// The name of each group, in the order in which they first occur in the targets.
+    group_names: Vec<String>,
+    /// The fraction of the aligned bases of a query (exclusive) that must be aligned to a group.
+    min_fraction: f64,
+}
+
+impl Demultiplexer {
+    /// Creates a demultiplexer for the given targets, where each target is in the group given by
+    /// `groups` (from target name to group name), or otherwise in its own group named after it.
+    /// A query is assigned to a group with more than `min_fraction` of its aligned bases, so the
+    /// default of `0.5` requires a majority.
+    pub fn new(
+        target_seqs: &[TargetSeq],
+        groups: &HashMap<String, String>,
+        min_fraction: f64,
+    ) -> Result<Self> {
+        ensure!(
+            (0.0..1.0).contains(&min_fraction),
+            "The minimum fraction must be in [0, 1): {min_fraction}"
+        );
+        for target in groups.keys() {
+            ensure!(
+                target_seqs
+                    .iter()
+                    .any(|target_seq| &target_seq.name == target),
+                "Target in the target groups not found in the reference: {target}"
+            );
+        }
+        let mut group_names: Vec<String> = Vec::new();
+        let target_groups = target_seqs
+            .iter()
+            .map(|target_seq| {
+                let name = groups.get(&target_seq.name).unwrap_or(&target_seq.name);
+                ensure!(
+                    name != UNASSIGNED,
+                    "The group name '{UNASSIGNED}' is reserved for unassigned queries"
+                );
+                Ok(match group_names.iter().position(|group| group == name) {
+                    Some(index) => index,
+                    None => {
+                        group_names.push(name.clone());
+                        group_names.len() - 1
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            target_groups,
+            group_names,
+            min_fraction,
+        })
+    }
+
+    /// The names of the groups, in the order of their indexes.
+    pub fn group_names(&self) -> &[String] {
+        &self.group_names
+    }
+
+    /// Returns the number of aligned bases of the alignment in each group, following the
+    /// alignment across the targets it jumps between.
+    pub fn aligned_bases(&self, alignment: &Alignment) -> Vec<usize> {
+        let num_targets = self.target_groups.len();
+        let mut counts = vec![0; self.group_names.len()];
+        for step in alignment.iter_steps() {
+            if matches!(step.op, Match | Subst) {
+                counts[self.target_groups[step.contig_idx % num_targets]] += 1;
+            }
+        }
+        counts
+    }
+
+    /// Returns the index of the group to which the query with the given (best) alignment is
+    /// assigned, or `None` if the query is not aligned or no group has enough of its aligned
+    /// bases.
+    pub fn assign(&self, alignment: Option<&Alignment>) -> Option<usize> {
+        let counts = self.aligned_bases(alignment?);
+        let total: usize = counts.iter().sum();
+        let (group, count) = counts
+            .into_iter()
+            .enumerate()
+            .max_by_key(|(group, count)| (*count, std::cmp::Reverse(*group)))?;
+        (total > 0 && count as f64 > self.min_fraction * total as f64).then_some(group)
+    }
+}
+
+/// The number of queries assigned to each group by a [`Demultiplexer`].
+#[derive(Debug, Default, Clone)]
+pub struct DemuxSummary {
+    counts: Vec<usize>,
+    unassigned: usize,
+}
+
+impl DemuxSummary {
+    pub fn new(num_groups: usize) -> Self {
+        Self {
+            counts: vec![0; num_groups],
+            unassigned: 0,
+        }
+    }
+
+    /// Adds a query assigned to the given group, if any.
+    pub fn add(&mut self, group: Option<usize>) {
+        match group {
+            Some(group) => self.counts[group] += 1,
+            None => self.unassigned += 1,
+        }
+    }
+
+    /// Returns the number of queries assigned to the given group.
+    pub fn count(&self, group: usize) -> usize {
+        self.counts[group]
+    }
+
+    /// Returns the number of queries not assigned to any group.
+    pub fn unassigned(&self) -> usize {
+        self.unassigned
+    }
+
+    /// Returns the total number of queries.
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum::<usize>() + self.unassigned
+    }
+
+    /// Writes the summary as a tab-delimited table, with the number and fraction of queries in
+    /// each group, then those not assigned to any group.
+    pub fn write_table<W: Write>(&self, writer: &mut W, group_names: &[String]) -> Result<()> {
+        writeln!(writer, "group\tcount\tfraction")?;
+        let total = self.total();
+        let counts = self.counts.iter().copied().chain([self.unassigned]);
+        let names = group_names.iter().map(String::as_str).chain([UNASSIGNED]);
+        for (name, count) in names.zip(counts) {
+            let fraction = if total == 0 {
+                0.0
+            } else {
+                count as f64 / total as f64
+            };
+            writeln!(writer, "{name}\t{count}\t{fraction:.6}")?;
+        }
+        Ok(())
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{alignment::tests::align_double_strand, scoring::Scoring};
+    use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+    use super::{Demultiplexer, DemuxSummary};
+
+    const CHR1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const CHR2: &str = "TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+    const CHR3: &str = "TTAACCGGATCGATCGGCTAGCTAGGATCCGATCGAATTC";
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    fn target_seqs() -> Vec<TargetSeq> {
+        vec![
+            TargetSeq::new("chr1", &CHR1.as_bytes().to_vec(), false),
+            TargetSeq::new("chr2", &CHR2.as_bytes().to_vec(), false),
+            TargetSeq::new("chr3", &CHR3.as_bytes().to_vec(), false),
+        ]
+    }
+
+    #[rstest]
+    fn test_assign_chimera() {
+        let target_seqs = target_seqs();
+        let demux = Demultiplexer::new(&target_seqs, &HashMap::new(), 0.5).unwrap();
+        assert_eq!(demux.group_names(), ["chr1", "chr2", "chr3"]);
+
+        // chr1:0-15 then chr2:10-40, so most bases are on chr2, including from the reverse strand
+        let y = [&CHR1.as_bytes()[..15], &CHR2.as_bytes()[10..]].concat();
+        for y in [y.clone(), reverse_complement(&y)] {
+            let alignment = align_double_strand(&target_seqs, &y, scoring());
+            assert_eq!(demux.aligned_bases(&alignment), [15, 30, 0]);
+            assert_eq!(demux.assign(Some(&alignment)), Some(1));
+        }
+
+        // chr1:0-20 then chr2:20-40, so neither has a majority
+        let y = [&CHR1.as_bytes()[..20], &CHR2.as_bytes()[20..]].concat();
+        let alignment = align_double_strand(&target_seqs, &y, scoring());
+        assert_eq!(demux.assign(Some(&alignment)), None);
+        assert_eq!(demux.assign(None), None);
+    }
+
+    #[rstest]
+    fn test_assign_groups() {
+        let target_seqs = target_seqs();
+        let groups = HashMap::from([
+            ("chr1".to_string(), "sample1".to_string()),
+            ("chr2".to_string(), "sample1".to_string()),
+        ]);
+        let demux = Demultiplexer::new(&target_seqs, &groups, 0.5).unwrap();
+        assert_eq!(demux.group_names(), ["sample1", "chr3"]);
+
+        // chr1:0-20 then chr2:20-40, both in the same group
+        let y = [&CHR1.as_bytes()[..20], &CHR2.as_bytes()[20..]].concat();
+        let alignment = align_double_strand(&target_seqs, &y, scoring());
+        assert_eq!(demux.aligned_bases(&alignment), [40, 0]);
+        assert_eq!(demux.assign(Some(&alignment)), Some(0));
+    }
+
+    #[rstest]
+    fn test_new_errors() {
+        let target_seqs = target_seqs();
+        let unknown = HashMap::from([("chr4".to_string(), "sample1".to_string())]);
+        assert!(Demultiplexer::new(&target_seqs, &unknown, 0.5).is_err());
+        let reserved = HashMap::from([("chr1".to_string(), "unassigned".to_string())]);
+        assert!(Demultiplexer::new(&target_seqs, &reserved, 0.5).is_err());
+        assert!(Demultiplexer::new(&target_seqs, &HashMap::new(), 1.0).is_err());
+    }
+
+    #[rstest]
+    fn test_summary() {
+        let mut summary = DemuxSummary::new(2);
+        summary.add(Some(0));
+        summary.add(Some(0));
+        summary.add(None);
+        summary.add(Some(1));
+        let mut output = Vec::new();
+        let names = ["a".to_string(), "b".to_string()];
+        summary.write_table(&mut output, &names).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "group\tcount\tfraction\na\t2\t0.500000\nb\t1\t0.250000\nunassigned\t1\t0.250000\n"
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Human-readable, segment-by-segment descriptions of alignments, for debugging the alignment of
+//! individual queries.
+
+use std::fmt::Write;
+
+use super::{
+    aligners::constants::AlignmentOperation::{Del, Ins, Match, Subst},
+    alignment::{contig_bases_for, target_seq_for, Alignment, AlignmentStep},
+};
+use crate::util::target_seq::TargetSeq;
+
+/// The ANSI escape code for mismatches.
+const MISMATCH_COLOR: &str = "\x1b[31m";
+/// The ANSI escape code for gaps.
+const GAP_COLOR: &str = "\x1b[33m";
+/// The ANSI escape code for jumps.
+const JUMP_COLOR: &str = "\x1b[36m";
+/// The ANSI escape code to reset the color.
+const RESET_COLOR: &str = "\x1b[0m";
+
+/// A maximal run of the alignment on a single contig, between jumps.
+struct Segment {
+    contig_idx: usize,
+    x_start: usize,
+    x_end: usize,
+    y_start: usize,
+    y_end: usize,
+    steps: Vec<AlignmentStep>,
+}
+
+/// Splits the alignment into segments, excluding clipping.
+fn segments(alignment: &Alignment) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut current: Option<Segment> = None;
+    for step in alignment.iter_steps() {
+        match step.op {
+            Match | Subst | Ins | Del => {
+                let segment = current.get_or_insert_with(|| Segment {
+                    contig_idx: step.contig_idx,
+                    x_start: step.x_pos,
+                    x_end: step.x_pos,
+                    y_start: step.y_pos,
+                    y_end: step.y_pos,
+                    steps: Vec::new(),
+                });
+                segment.x_end = (step.x_pos as i32 + step.op.length_on_x(step.x_pos)) as usize;
+                segment.y_end = step.y_pos + step.op.length_on_y();
+                segment.steps.push(step);
+            }
+            _ => segments.extend(current.take()),
+        }
+    }
+    segments.extend(current.take());
+    segments
+}
+
+/// Describes alignments in a human-readable form, with a summary of the alignment, then for each
+/// segment between jumps, the query and target coordinates followed by the pairwise alignment.
+///
+/// Target coordinates are 1-based inclusive on the forward strand of the target, and query
+/// coordinates are 1-based inclusive.
+pub struct AlignmentDescriber<'a> {
+    target_seqs: &'a [TargetSeq],
+    color: bool,
+    width: usize,
+}
+
+impl<'a> AlignmentDescriber<'a> {
+    /// Creates a new describer.
+    ///
+    /// Arguments:
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `color` - True to color mismatches, gaps, and jumps with ANSI escape codes.
+    /// - `width` - The number of alignment columns per line of the pairwise alignment.
+    pub fn new(target_seqs: &'a [TargetSeq], color: bool, width: usize) -> Self {
+        assert!(width > 0, "The width must be positive");
+        Self {
+            target_seqs,
+            color,
+            width,
+        }
+    }
+
+    /// Returns the given text in the given color, if coloring.
+    fn colored(&self, text: &str, color: &str) -> String {
+        if self.color {
+            format!("{color}{text}{RESET_COLOR}")
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Returns the name, 1-based inclusive forward strand coordinates, and strand of the segment.
+    fn target_interval(&self, segment: &Segment) -> String {
+        let (target_seq, is_forward) = target_seq_for(self.target_seqs, segment.contig_idx);
+        let len = target_seq.len();
+        let (start, end, strand) = if is_forward {
+            (segment.x_start + 1, segment.x_end, '+')
+        } else {
+            (len - segment.x_end + 1, len - segment.x_start, '-')
+        };
+        format!("{}:{start}-{end} ({strand})", target_seq.name)
+    }
+
+    /// Returns the description of the alignment of the query `y`.
+    pub fn describe(&self, alignment: &Alignment, y: &[u8]) -> String {
+        let mut out = String::new();
+        if alignment.operations.is_empty() {
+            writeln!(out, "unaligned").unwrap();
+            return out;
+        }
+        let stats = alignment.stats(self.target_seqs, y);
+        let segments = segments(alignment);
+        writeln!(
+            out,
+            "score: {}, segments: {}, jumps: {}, query aligned: {:.1}%, identity: {:.1}%",
+            alignment.score,
+            segments.len(),
+            stats.jumps,
+            100.0 * stats.aligned_query_fraction,
+            100.0 * stats.identity,
+        )
+        .unwrap();
+        writeln!(out, "cigar: {}", alignment.cigar()).unwrap();
+
+        for (index, segment) in segments.iter().enumerate() {
+            if index > 0 {
+                let jump = format!(
+                    "jump: {} -> {}",
+                    self.target_interval(&segments[index - 1]),
+                    self.target_interval(segment)
+                );
+                writeln!(out, "{}", self.colored(&jump, JUMP_COLOR)).unwrap();
+            }
+            self.describe_segment(&mut out, index, segment, y);
+        }
+        out
+    }
+
+    /// Appends the description of the segment.
+    fn describe_segment(&self, out: &mut String, index: usize, segment: &Segment, y: &[u8]) {
+        let x = contig_bases_for(self.target_seqs, segment.contig_idx);
+        let (mut matches, mut mismatches, mut insertions, mut deletions) = (0, 0, 0, 0);
+        // The three lines of the pairwise alignment, as the character and color of each column
+        let mut columns: Vec<[(u8, Option<&str>); 3]> = Vec::with_capacity(segment.steps.len());
+        for step in &segment.steps {
+            let column = match step.op {
+                Match | Subst if x[step.x_pos] == y[step.y_pos] => {
+                    matches += 1;
+                    [(x[step.x_pos], None), (b'|', None), (y[step.y_pos], None)]
+                }
+                Match | Subst => {
+                    mismatches += 1;
+                    let color = Some(MISMATCH_COLOR);
+                    [(x[step.x_pos], color), (b' ', None), (y[step.y_pos], color)]
+                }
+                Ins => {
+                    insertions += 1;
+                    let color = Some(GAP_COLOR);
+                    [(x[step.x_pos], color), (b' ', None), (b'-', color)]
+                }
+                Del => {
+                    deletions += 1;
+                    let color = Some(GAP_COLOR);
+                    [(b'-', color), (b' ', None), (y[step.y_pos], color)]
+                }
+                _ => unreachable!(),
+            };
+            columns.push(column);
+        }
+
+        writeln!(
+            out,
+            "segment {}: query {}-{} -> {}, matches: {matches}, mismatches: {mismatches}, \
+             insertions: {insertions}, deletions: {deletions}",
+            index + 1,
+            segment.y_start + 1,
+            segment.y_end,
+            self.target_interval(segment),
+        )
+        .unwrap();
+
+        let labels = ["target", "", "query"];
+        for chunk in columns.chunks(self.width) {
+            for (line, label) in labels.iter().enumerate() {
+                let mut text = String::with_capacity(chunk.len());
+                for column in chunk {
+                    let (base, color) = column[line];
+                    let base = char::from(base).to_string();
+                    match color {
+                        Some(color) => text.push_str(&self.colored(&base, color)),
+                        None => text.push_str(&base),
+                    }
+                }
+                writeln!(out, "  {label:<6}  {text}").unwrap();
+            }
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{alignment::tests::align_double_strand, scoring::Scoring};
+    use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+    use super::AlignmentDescriber;
+
+    const CHR1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const CHR2: &str = "TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    fn target_seqs() -> Vec<TargetSeq> {
+        vec![
+            TargetSeq::new("chr1", &CHR1.as_bytes().to_vec(), false),
+            TargetSeq::new("chr2", &CHR2.as_bytes().to_vec(), false),
+        ]
+    }
+
+    #[rstest]
+    fn test_describe_with_gaps() {
+        let target_seqs = [TargetSeq::new(
+            "chr1",
+            &b"TTTTTGCTAAAGACAATTACATAACATACACGTCAGCTTTTTTT".to_vec(),
+            false,
+        )];
+        let y = b"GGGGCTAAAGCAATTACATAAACATACACTTCAGCGGG";
+        let scoring = Scoring::with_jump_score(-2, -1, -100, MatchParams::new(1, -1))
+            .set_xclip(0)
+            .set_yclip(0);
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "3B5A7=1I9=1D9=1X5=3B7A");
+
+        let describer = AlignmentDescriber::new(&target_seqs, false, 20);
+        let expected = [
+            "score: 23, segments: 1, jumps: 0, query aligned: 84.2%, identity: 90.9%",
+            "cigar: 3B5A7=1I9=1D9=1X5=3B7A",
+            "segment 1: query 4-35 -> chr1:6-37 (+), matches: 30, mismatches: 1, insertions: 1, \
+             deletions: 1",
+            "  target  GCTAAAGACAATTACAT-AA",
+            "          ||||||| ||||||||| ||",
+            "  query   GCTAAAG-CAATTACATAAA",
+            "  target  CATACACGTCAGC",
+            "          ||||||| |||||",
+            "  query   CATACACTTCAGC",
+            "",
+        ]
+        .join("\n");
+        assert_eq!(describer.describe(&alignment, y), expected);
+    }
+
+    #[rstest]
+    fn test_describe_with_jumps() {
+        let target_seqs = target_seqs();
+        // chr1:1-20 then the reverse strand of chr2:21-40
+        let y = [
+            &CHR1.as_bytes()[..20],
+            &reverse_complement(&CHR2.as_bytes()[20..]),
+        ]
+        .concat();
+        let scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0);
+        let alignment = align_double_strand(&target_seqs, &y, scoring);
+
+        let describer = AlignmentDescriber::new(&target_seqs, false, 80);
+        let description = describer.describe(&alignment, &y);
+        let lines = description.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "score: 30, segments: 2, jumps: 1, query aligned: 100.0%, identity: 100.0%"
+        );
+        assert!(lines[2].starts_with("segment 1: query 1-20 -> chr1:1-20 (+), matches: 20"));
+        assert_eq!(lines[6], "jump: chr1:1-20 (+) -> chr2:21-40 (-)");
+        assert!(lines[7].starts_with("segment 2: query 21-40 -> chr2:21-40 (-), matches: 20"));
+
+        // colored
+        let describer = AlignmentDescriber::new(&target_seqs, true, 80);
+        let description = describer.describe(&alignment, &y);
+        assert!(description.contains("\x1b[36mjump: chr1:1-20 (+) -> chr2:21-40 (-)\x1b[0m"));
+    }
+
+    #[rstest]
+    fn test_describe_unaligned() {
+        let target_seqs = target_seqs();
+        let describer = AlignmentDescriber::new(&target_seqs, false, 80);
+        assert_eq!(
+            describer.describe(&Default::default(), b"ACGT"),
+            "unaligned\n"
+        );
+    }
+}
@@ -0,0 +1,304 @@
+//! Pairing of the reads of the two strands of the same molecule (e.g. the template and complement
+//! reads of Oxford Nanopore duplex sequencing), and the consensus of each pair, so that each
+//! molecule is aligned once from a higher-accuracy query.
+
+use std::collections::HashSet;
+
+use bio::alignment::pairwise::MatchFunc;
+use seq_io::fastq::OwnedRecord as FastqOwnedRecord;
+
+use super::{
+    aligners::{
+        constants::{
+            AlignmentOperation::{Del, Ins, Match, Subst},
+            MIN_PENALTY,
+        },
+        multi_contig_aligner::MultiContigAligner,
+    },
+    consensus::MAX_CONSENSUS_QUALITY,
+    scoring::Scoring,
+};
+use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+/// The tag of a duplex consensus read with the name of the complement read, the consensus being
+/// named after the template read.
+pub const DUPLEX_TAG: &str = "dx";
+
+/// The k-mer size used to screen the candidate pairs of reads before aligning them.
+const SCREEN_K: usize = 15;
+
+/// The minimum fraction of the k-mers of the shorter read of a candidate pair found in the other
+/// read (reverse complemented).
+const MIN_SHARED_KMER_FRACTION: f64 = 0.1;
+
+/// The minimum fraction of each read of a pair that must be aligned to the other read.
+const MIN_OVERLAP_FRACTION: f64 = 0.8;
+
+/// The minimum quality of a consensus base.
+const MIN_CONSENSUS_QUALITY: u8 = 2;
+
+/// Pairs the reads that are the reverse complement of each other, and builds their consensus.
+///
+/// The complement read of a molecule follows its template read closely in the input, so each
+/// read is paired with the first of the following `window` unpaired reads whose reverse
+/// complement shares enough k-mers with it, and aligns to it (with free clipping of both reads)
+/// over most of both reads with at least the minimum identity.  The consensus spans the aligned
+/// region of the pair: where both reads have a base, the bases vote with their base qualities,
+/// and where only one read has a base, the base is kept if its quality is greater than that of
+/// the bases flanking the gap in the other read.
+pub struct DuplexPairer<F: MatchFunc> {
+    scoring: Scoring<F>,
+    window: usize,
+    min_identity: f64,
+}
+
+impl<F: MatchFunc + Sync + Copy> DuplexPairer<F> {
+    /// Creates the pairer of reads within `window` reads of each other whose alignment has at
+    /// least `min_identity` identity.  The match and gap scores of `scoring` are used to align
+    /// the reads, while its jump and clipping scores are ignored.
+    pub fn new(scoring: Scoring<F>, window: usize, min_identity: f64) -> Self {
+        let scoring = scoring
+            .set_jump_score(MIN_PENALTY)
+            .set_xclip(0)
+            .set_yclip(0);
+        Self {
+            scoring,
+            window,
+            min_identity,
+        }
+    }
+
+    /// Returns true if enough of the k-mers of the shorter of the two reads are in the other.
+    fn shares_kmers(template: &[u8], complement: &[u8]) -> bool {
+        let (shorter, longer) = if template.len() <= complement.len() {
+            (template, complement)
+        } else {
+            (complement, template)
+        };
+        if shorter.len() < SCREEN_K {
+            return false;
+        }
+        let kmers = longer.windows(SCREEN_K).collect::<HashSet<_>>();
+        let shared = shorter
+            .windows(SCREEN_K)
+            .filter(|kmer| kmers.contains(kmer))
+            .count();
+        shared as f64 >= MIN_SHARED_KMER_FRACTION * (shorter.len() - SCREEN_K + 1) as f64
+    }
+
+    /// Returns the duplex consensus of the template and complement reads, if they are the two
+    /// strands of the same molecule.  The consensus is on the strand of the template, and has the
+    /// header of the template with the name of the complement in the comment (see
+    /// [`DUPLEX_TAG`]).
+    pub fn consensus(
+        &self,
+        template: &FastqOwnedRecord,
+        complement: &FastqOwnedRecord,
+    ) -> Option<FastqOwnedRecord> {
+        let x = template.seq.to_ascii_uppercase();
+        let y = reverse_complement(complement.seq.to_ascii_uppercase());
+        let x_qual = &template.qual;
+        let y_qual = complement.qual.iter().rev().copied().collect::<Vec<_>>();
+        if !Self::shares_kmers(&x, &y) {
+            return None;
+        }
+
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("template", true, &x, false, self.scoring);
+        let alignment = aligner.custom(&y);
+        let overlaps = |aligned: usize, len: usize| {
+            aligned as f64 >= MIN_OVERLAP_FRACTION * len as f64 && aligned > 0
+        };
+        if !overlaps(alignment.xend - alignment.xstart, x.len())
+            || !overlaps(alignment.yend - alignment.ystart, y.len())
+        {
+            return None;
+        }
+        let target_seqs = [TargetSeq::new("template", &x, false)];
+        if alignment.stats(&target_seqs, &y).identity < self.min_identity {
+            return None;
+        }
+
+        let phred = |qual: u8| qual.saturating_sub(b'!');
+        // The quality of a gap is that of the lower of the bases flanking it
+        let gap_quality = |quals: &[u8], pos: usize| {
+            let before = pos.checked_sub(1).and_then(|pos| quals.get(pos));
+            before
+                .into_iter()
+                .chain(quals.get(pos))
+                .map(|qual| phred(*qual))
+                .min()
+                .unwrap_or(0)
+        };
+        let mut seq = Vec::with_capacity(x.len());
+        let mut qual = Vec::with_capacity(x.len());
+        let mut push = |base: u8, quality: u8| {
+            seq.push(base);
+            qual.push(quality.clamp(MIN_CONSENSUS_QUALITY, MAX_CONSENSUS_QUALITY) + b'!');
+        };
+        for step in alignment.iter_steps() {
+            match step.op {
+                Match | Subst => {
+                    let (x_base, y_base) = (x[step.x_pos], y[step.y_pos]);
+                    let (x_q, y_q) = (phred(x_qual[step.x_pos]), phred(y_qual[step.y_pos]));
+                    if x_base == y_base {
+                        push(x_base, x_q.saturating_add(y_q));
+                    } else if y_q > x_q {
+                        push(y_base, y_q - x_q);
+                    } else {
+                        push(x_base, x_q - y_q);
+                    }
+                }
+                // A base of the template only
+                Ins => {
+                    let (x_q, gap_q) =
+                        (phred(x_qual[step.x_pos]), gap_quality(&y_qual, step.y_pos));
+                    if x_q > gap_q {
+                        push(x[step.x_pos], x_q - gap_q);
+                    }
+                }
+                // A base of the complement only
+                Del => {
+                    let (y_q, gap_q) = (phred(y_qual[step.y_pos]), gap_quality(x_qual, step.x_pos));
+                    if y_q > gap_q {
+                        push(y[step.y_pos], y_q - gap_q);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let name = complement
+            .head
+            .split(u8::is_ascii_whitespace)
+            .next()
+            .unwrap_or_default();
+        let mut head = template.head.clone();
+        head.extend_from_slice(format!("\t{DUPLEX_TAG}:Z:").as_bytes());
+        head.extend_from_slice(name);
+        Some(FastqOwnedRecord { head, seq, qual })
+    }
+
+    /// Pairs the reads, returning the consensus of each pair in place of its template read, and
+    /// the unpaired reads unchanged, in the order of the reads.
+    pub fn pair_reads(&self, records: Vec<FastqOwnedRecord>) -> Vec<FastqOwnedRecord> {
+        let mut paired = vec![false; records.len()];
+        let mut consensuses: Vec<Option<FastqOwnedRecord>> = vec![None; records.len()];
+        for i in 0..records.len() {
+            if paired[i] {
+                continue;
+            }
+            let end = (i + 1 + self.window).min(records.len());
+            for j in i + 1..end {
+                if paired[j] {
+                    continue;
+                }
+                if let Some(consensus) = self.consensus(&records[i], &records[j]) {
+                    paired[i] = true;
+                    paired[j] = true;
+                    consensuses[i] = Some(consensus);
+                    break;
+                }
+            }
+        }
+        records
+            .into_iter()
+            .zip(paired)
+            .zip(consensuses)
+            .filter_map(|((record, paired), consensus)| match consensus {
+                Some(consensus) => Some(consensus),
+                None => (!paired).then_some(record),
+            })
+            .collect()
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+    use seq_io::fastq::OwnedRecord as FastqOwnedRecord;
+
+    use crate::align::{scoring::Scoring, umi::comment_tag};
+    use crate::util::dna::reverse_complement;
+
+    use super::{DuplexPairer, DUPLEX_TAG};
+
+    const MOLECULE: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT\
+                            TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+    const OTHER: &str = "AATGTACTTCGTTCAGTTACGTATTGCTGGCATCCAGTTAGCCATGCTTTACCGAGTTACAA";
+
+    fn pairer() -> DuplexPairer<MatchParams> {
+        let scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4));
+        DuplexPairer::new(scoring, 2, 0.8)
+    }
+
+    fn read(head: &str, seq: &[u8], qual: &[u8]) -> FastqOwnedRecord {
+        FastqOwnedRecord {
+            head: head.as_bytes().to_vec(),
+            seq: seq.to_vec(),
+            qual: qual.to_vec(),
+        }
+    }
+
+    /// The template read, with a low-quality error at position 10, and the complement read, with
+    /// a low-quality error at position 50 and a low-quality extra base after position 29 (in the
+    /// coordinates of the template).
+    fn reads() -> (FastqOwnedRecord, FastqOwnedRecord) {
+        let mut seq = MOLECULE.as_bytes().to_vec();
+        let mut qual = vec![b'I'; seq.len()];
+        seq[10] = b'G';
+        qual[10] = b'+';
+        let template = read("t1 RG:Z:A", &seq, &qual);
+
+        let mut seq = MOLECULE.as_bytes().to_vec();
+        let mut qual = vec![b'I'; seq.len()];
+        seq[50] = b'C';
+        qual[50] = b'+';
+        seq.insert(30, b'T');
+        qual.insert(30, b'+');
+        let qual = qual.into_iter().rev().collect::<Vec<_>>();
+        let complement = read("c1", &reverse_complement(&seq), &qual);
+        (template, complement)
+    }
+
+    #[rstest]
+    fn test_consensus() {
+        let pairer = pairer();
+        let (template, complement) = reads();
+        let consensus = pairer.consensus(&template, &complement).unwrap();
+        assert_eq!(consensus.seq, MOLECULE.as_bytes());
+        assert_eq!(consensus.head, b"t1 RG:Z:A\tdx:Z:c1");
+        assert_eq!(
+            comment_tag(&consensus.head, DUPLEX_TAG),
+            Some(b"c1".as_slice())
+        );
+        // Q40 + Q40, Q40 - Q10 at either error
+        assert_eq!(consensus.qual[0], b'q');
+        assert_eq!(consensus.qual[10], b'?');
+        assert_eq!(consensus.qual[50], b'?');
+
+        // not the reverse complement of each other
+        let other = read("o1", OTHER.as_bytes(), &[b'I'; OTHER.len()]);
+        assert!(pairer.consensus(&template, &other).is_none());
+        // the same strand
+        assert!(pairer.consensus(&template, &template).is_none());
+    }
+
+    #[rstest]
+    fn test_pair_reads() {
+        let pairer = pairer();
+        let (template, complement) = reads();
+        let other = read("o1", OTHER.as_bytes(), &[b'I'; OTHER.len()]);
+        let records = vec![template.clone(), other.clone(), complement.clone()];
+        let paired = pairer.pair_reads(records);
+        assert_eq!(paired.len(), 2);
+        assert_eq!(paired[0].seq, MOLECULE.as_bytes());
+        assert_eq!(paired[1], other);
+
+        // the complement is outside the window
+        let records = vec![template, other.clone(), other.clone(), complement];
+        assert_eq!(pairer.pair_reads(records.clone()), records);
+    }
+}
@@ -0,0 +1,385 @@
+//! Fast filters used to reject query/contig pairs before running the full jump-aware alignment,
+//! and filters used to reject alignments after.
+
+use bio::alignment::pairwise::MatchFunc;
+
+use super::{alignment::Alignment, scoring::Scoring};
+use crate::util::target_seq::TargetSeq;
+
+/// The number of bits in a block of the bit-parallel edit distance computation.
+const WORD_SIZE: usize = 64;
+
+/// Computes the edit distance of a query versus the best matching sub-sequence of a contig using
+/// the bit-parallel algorithm of Myers (1999), with the blocking of Hyyrö (2003) to support
+/// queries longer than a machine word.
+///
+/// The edit distance is used to compute an upper bound on the score of the best local alignment
+/// (without jumps) of the query to the contig (see [`EditDistanceFilter::max_score`]), and so
+/// quickly reject contigs that cannot reach a minimum score.
+///
+/// Bases are compared for exact equality, and so the query and contig should have the same case.
+#[derive(Debug, Clone)]
+pub struct EditDistanceFilter {
+    /// The length of the query.
+    len: usize,
+    /// Maps each byte to the index of its match bit-vector in `peq`.
+    codes: [usize; 256],
+    /// The match bit-vectors for each distinct query base, with one word per block.  The last
+    /// entry is for bases not found in the query.
+    peq: Vec<Vec<u64>>,
+}
+
+impl EditDistanceFilter {
+    /// Builds the filter for the given query.
+    pub fn new(query: &[u8]) -> Self {
+        let num_blocks = query.len().div_ceil(WORD_SIZE);
+        let mut codes = [usize::MAX; 256];
+        let mut peq: Vec<Vec<u64>> = Vec::new();
+        for (i, base) in query.iter().enumerate() {
+            let code = &mut codes[*base as usize];
+            if *code == usize::MAX {
+                *code = peq.len();
+                peq.push(vec![0; num_blocks]);
+            }
+            peq[*code][i / WORD_SIZE] |= 1 << (i % WORD_SIZE);
+        }
+        // bases not in the query
+        let absent = peq.len();
+        peq.push(vec![0; num_blocks]);
+        for code in &mut codes {
+            if *code == usize::MAX {
+                *code = absent;
+            }
+        }
+        Self {
+            len: query.len(),
+            codes,
+            peq,
+        }
+    }
+
+    /// Advances a single block by one contig base, returning the horizontal delta at the bottom of
+    /// the block (at row `out_bit`).
+    #[inline]
+    fn advance_block(pv: &mut u64, mv: &mut u64, eq: u64, hin: i32, out_bit: u64) -> i32 {
+        let eq = if hin < 0 { eq | 1 } else { eq };
+        let xv = eq | *mv;
+        let xh = ((eq & *pv).wrapping_add(*pv) ^ *pv) | eq;
+        let mut ph = *mv | !(xh | *pv);
+        let mut mh = *pv & xh;
+        let hout = if ph & out_bit != 0 {
+            1
+        } else if mh & out_bit != 0 {
+            -1
+        } else {
+            0
+        };
+        ph <<= 1;
+        mh <<= 1;
+        match hin.cmp(&0) {
+            std::cmp::Ordering::Less => mh |= 1,
+            std::cmp::Ordering::Greater => ph |= 1,
+            std::cmp::Ordering::Equal => (),
+        }
+        *pv = mh | !(xv | ph);
+        *mv = ph & xv;
+        hout
+    }
+
+    /// Returns the minimum edit distance of the full query versus any sub-sequence of the contig.
+    /// If the contig is circular, sub-sequences that span the origin are also considered.
+    pub fn min_edit_distance(&self, contig: &[u8], circular: bool) -> usize {
+        if self.len == 0 {
+            return 0;
+        }
+        let num_blocks = self.peq[0].len();
+        let last_bit = 1u64 << ((self.len - 1) % WORD_SIZE);
+        let mut pv = vec![u64::MAX; num_blocks];
+        let mut mv = vec![0u64; num_blocks];
+        // the score of the last row of the query
+        let mut score = self.len;
+        let mut best = score;
+
+        // sub-sequences spanning the origin need at most the query length plus the edit distance
+        // from the start of the contig
+        let wrap_len = if circular {
+            contig.len().min(2 * self.len)
+        } else {
+            0
+        };
+        for base in contig.iter().chain(contig[..wrap_len].iter()) {
+            let peq = &self.peq[self.codes[*base as usize]];
+            // any sub-sequence of the contig may be used, so the top row is always zero
+            let mut hin = 0;
+            for block in 0..num_blocks {
+                let out_bit = if block + 1 == num_blocks {
+                    last_bit
+                } else {
+                    1 << (WORD_SIZE - 1)
+                };
+                hin = Self::advance_block(&mut pv[block], &mut mv[block], peq[block], hin, out_bit);
+            }
+            score = (score as i64 + i64::from(hin)) as usize;
+            best = best.min(score);
+            if best == 0 {
+                break;
+            }
+        }
+        best
+    }
+
+    /// Returns an upper bound on the score of the best local alignment (without jumps) of the
+    /// query versus the contig.
+    ///
+    /// Each edit in the best edit alignment must lose at least `min(a, a - b, -e)` relative to the
+    /// maximum score `a * n`, where `a` is the match score, `b` is the mismatch score, `e` is the
+    /// gap extend score, and `n` is the query length.
+    pub fn max_score<F: MatchFunc>(
+        &self,
+        contig: &[u8],
+        circular: bool,
+        scoring: &Scoring<F>,
+    ) -> i32 {
+        let match_score = scoring.match_fn.score(b'A', b'A');
+        let mismatch_score = scoring.match_fn.score(b'A', b'C');
+        let max_score = match_score * self.len as i32;
+        let min_loss = match_score
+            .min(match_score - mismatch_score)
+            .min(-scoring.gap_extend);
+        if min_loss <= 0 {
+            return max_score;
+        }
+        max_score - min_loss * self.min_edit_distance(contig, circular) as i32
+    }
+
+    /// Returns true if the best local alignment (without jumps) of the query versus the contig
+    /// could have at least the given score.
+    pub fn passes<F: MatchFunc>(
+        &self,
+        contig: &[u8],
+        circular: bool,
+        scoring: &Scoring<F>,
+        min_score: i32,
+    ) -> bool {
+        self.max_score(contig, circular, scoring) >= min_score
+    }
+}
+
+/// Rejects alignments that do not meet minimum quality criteria.  Each criterion is optional, and
+/// an alignment passes if it meets all the given criteria.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct AlignmentFilter {
+    /// The minimum alignment score.
+    pub min_score: Option<i32>,
+    /// The minimum identity, as a fraction (see [`crate::align::AlignmentStats::identity`]).
+    pub min_identity: Option<f64>,
+    /// The minimum number of query bases aligned (not clipped nor skipped).
+    pub min_aligned_length: Option<usize>,
+    /// The maximum number of jumps, including jumps between contigs and strands.
+    pub max_jumps: Option<usize>,
+}
+
+impl AlignmentFilter {
+    /// Returns true if none of the criteria are given, so that all alignments pass.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns true if the alignment meets all the criteria.
+    ///
+    /// Arguments:
+    /// - `alignment` - The alignment to filter.
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `y` - The query sequence that was aligned.
+    pub fn passes(&self, alignment: &Alignment, target_seqs: &[TargetSeq], y: &[u8]) -> bool {
+        if self
+            .min_score
+            .is_some_and(|min_score| alignment.score < min_score)
+        {
+            return false;
+        }
+        if self.min_identity.is_none()
+            && self.min_aligned_length.is_none()
+            && self.max_jumps.is_none()
+        {
+            return true;
+        }
+        let stats = alignment.stats(target_seqs, y);
+        let aligned_length = stats.matches + stats.mismatches + stats.deletions;
+        self.min_identity
+            .is_none_or(|min_identity| stats.identity >= min_identity)
+            && self
+                .min_aligned_length
+                .is_none_or(|min_aligned_length| aligned_length >= min_aligned_length)
+            && self
+                .max_jumps
+                .is_none_or(|max_jumps| stats.jumps <= max_jumps)
+    }
+
+    /// Retains only the alignments that meet all the criteria.
+    pub fn retain(&self, alignments: &mut Vec<Alignment>, target_seqs: &[TargetSeq], y: &[u8]) {
+        if !self.is_empty() {
+            alignments.retain(|alignment| self.passes(alignment, target_seqs, y));
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{
+        aligners::single_contig_aligner::SingleContigAligner,
+        alignment::tests::align_double_strand, scoring::Scoring,
+    };
+    use crate::util::target_seq::TargetSeq;
+
+    use super::{AlignmentFilter, EditDistanceFilter};
+
+    /// The edit distance of the full query versus any sub-sequence of the contig using the
+    /// standard dynamic programming.
+    fn min_edit_distance_dp(query: &[u8], contig: &[u8]) -> usize {
+        let mut prev: Vec<usize> = (0..=query.len()).collect();
+        let mut best = prev[query.len()];
+        for c in contig {
+            let mut cur = vec![0; query.len() + 1];
+            for i in 1..=query.len() {
+                let diag = prev[i - 1] + usize::from(query[i - 1] != *c);
+                cur[i] = diag.min(prev[i] + 1).min(cur[i - 1] + 1);
+            }
+            best = best.min(cur[query.len()]);
+            prev = cur;
+        }
+        best
+    }
+
+    #[rstest]
+    #[case("ACGT", "TTACGTTT", 0)]
+    #[case("ACGT", "TTACCTTT", 1)]
+    #[case("ACGT", "TTACTTT", 1)]
+    #[case("ACGT", "GGGGGGG", 3)]
+    #[case("ACGTN", "ACGTA", 1)]
+    #[case("ACGT", "", 4)]
+    #[case("", "ACGT", 0)]
+    fn test_min_edit_distance(#[case] query: &str, #[case] contig: &str, #[case] expected: usize) {
+        let filter = EditDistanceFilter::new(query.as_bytes());
+        assert_eq!(filter.min_edit_distance(contig.as_bytes(), false), expected);
+        assert_eq!(
+            min_edit_distance_dp(query.as_bytes(), contig.as_bytes()),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_min_edit_distance_multiple_blocks() {
+        // queries spanning one, two, and three blocks
+        let contig = "ACGGTTACAGTCCATGACTAGCATCAG".repeat(10);
+        for query in [
+            "GGTTACAGTCTCATGACTAGC".repeat(3),
+            "CATCAGACGGTTACAGTCCATGAC".repeat(4),
+            format!("{}{}", &contig[17..95], &contig[130..200]),
+            "TTTTGGGGCCCCAAAA".repeat(9),
+        ] {
+            let filter = EditDistanceFilter::new(query.as_bytes());
+            assert_eq!(
+                filter.min_edit_distance(contig.as_bytes(), false),
+                min_edit_distance_dp(query.as_bytes(), contig.as_bytes()),
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_min_edit_distance_circular() {
+        let contig = b"GGGGTTTTTTAAAAAAAAAACCCC";
+        let query = b"ACCCCGGGGT";
+        let filter = EditDistanceFilter::new(query);
+        assert_eq!(filter.min_edit_distance(contig, false), 5);
+        assert_eq!(filter.min_edit_distance(contig, true), 0);
+    }
+
+    #[rstest]
+    #[case("ACGTACGTTTGACCA", "CCCACGTACGTTTGACCAGG")]
+    #[case("ACGTACGTTTGACCA", "CCCACGTACTTTGACCAGG")]
+    #[case("ACGTACGTTTGACCA", "CCCACGTACGTTTGGGGGGGACCAGG")]
+    #[case("ACGTACGTTTGACCA", "TTTTTTTTTTTT")]
+    #[case("TTTTTTTTTTAAGGCAACCCGGG", "GGGTTGCCTTAAAAAAAAAA")]
+    fn test_max_score_is_upper_bound(#[case] query: &str, #[case] contig: &str) {
+        let filter = EditDistanceFilter::new(query.as_bytes());
+        for (mismatch_score, gap_open, gap_extend) in [(-1, -5, -1), (-4, -6, -2), (-2, 0, -1)] {
+            let scoring = Scoring::with_jump_score(
+                gap_open,
+                gap_extend,
+                -100_000,
+                MatchParams::new(1, mismatch_score),
+            );
+            let mut aligner = SingleContigAligner::with_scoring(scoring);
+            let alignment = aligner.local(contig.as_bytes(), query.as_bytes());
+            let max_score = filter.max_score(contig.as_bytes(), false, &scoring);
+            assert!(
+                alignment.score <= max_score,
+                "{} > {max_score}",
+                alignment.score
+            );
+            assert!(filter.passes(contig.as_bytes(), false, &scoring, alignment.score));
+        }
+    }
+
+    #[rstest]
+    #[case(AlignmentFilter::default(), true)]
+    #[case(AlignmentFilter { min_score: Some(23), ..Default::default() }, true)]
+    #[case(AlignmentFilter { min_score: Some(24), ..Default::default() }, false)]
+    #[case(AlignmentFilter { min_identity: Some(0.9), ..Default::default() }, true)]
+    #[case(AlignmentFilter { min_identity: Some(0.95), ..Default::default() }, false)]
+    #[case(AlignmentFilter { min_aligned_length: Some(32), ..Default::default() }, true)]
+    #[case(AlignmentFilter { min_aligned_length: Some(33), ..Default::default() }, false)]
+    #[case(AlignmentFilter { max_jumps: Some(0), ..Default::default() }, true)]
+    #[case(AlignmentFilter { min_score: Some(23), min_identity: Some(0.95), ..Default::default() }, false)]
+    fn test_alignment_filter(#[case] filter: AlignmentFilter, #[case] passes: bool) {
+        let target_seqs = [TargetSeq::new(
+            "chr1",
+            &b"TTTTTGCTAAAGACAATTACATAACATACACGTCAGCTTTTTTT".to_vec(),
+            false,
+        )];
+        let y = b"GGGGCTAAAGCAATTACATAAACATACACTTCAGCGGG";
+        let scoring = Scoring::with_jump_score(-2, -1, -100, MatchParams::new(1, -1))
+            .set_xclip(0)
+            .set_yclip(0);
+        // 30 matches, 1 mismatch, 1 insertion, and 1 deletion, with 32 query bases aligned
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "3B5A7=1I9=1D9=1X5=3B7A");
+        assert_eq!(alignment.score, 23);
+        assert_eq!(filter.passes(&alignment, &target_seqs, y), passes);
+
+        let mut alignments = vec![alignment];
+        filter.retain(&mut alignments, &target_seqs, y);
+        assert_eq!(alignments.len(), usize::from(passes));
+    }
+
+    #[rstest]
+    fn test_alignment_filter_max_jumps() {
+        let target_seqs = [
+            TargetSeq::new("chr1", &b"AACCTTGG".to_vec(), false),
+            TargetSeq::new("chr2", &b"GGGGG".to_vec(), false),
+        ];
+        let y = b"AACCGGTTGGGGG";
+        let scoring = Scoring::with_jump_score(-100, -100, -1, MatchParams::new(1, -1));
+        let alignment = align_double_strand(&target_seqs, y, scoring);
+        assert_eq!(alignment.cigar(), "4=2C0J4=1c8j5=");
+        for (max_jumps, passes) in [(1, false), (2, true)] {
+            let filter = AlignmentFilter {
+                max_jumps: Some(max_jumps),
+                ..Default::default()
+            };
+            assert_eq!(filter.passes(&alignment, &target_seqs, y), passes);
+        }
+    }
+}
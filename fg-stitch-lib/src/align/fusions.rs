@@ -0,0 +1,225 @@
+//! Candidate gene fusions given by the jumps between different targets (e.g. the transcripts of
+//! two genes) in the alignments of RNA reads, aggregated across reads.
+
+use std::{collections::BTreeMap, io::Write};
+
+use anyhow::Result;
+
+use super::{alignment::Alignment, breakpoints::Breakpoint};
+use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+/// The reads supporting a candidate fusion, with the junction sequence of the first read.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FusionSupport {
+    /// The number of reads with a jump at the fusion.
+    pub reads: usize,
+    /// The read bases on either side of the junction, separated by `|`, in the orientation of
+    /// the fusion.
+    pub junction: String,
+}
+
+/// Counts the reads supporting each candidate fusion, namely each breakpoint between two
+/// different targets.
+///
+/// A fusion is oriented so that the targets are on their forward strand if possible: a breakpoint
+/// between the reverse strands of both targets (from a read of the opposite strand of the fused
+/// transcript) is flipped, so that with the targets given in their sense orientation, the target
+/// before the junction is the 5' partner and the one after it the 3' partner.  A breakpoint
+/// between opposite strands is counted in its canonical form (see [`Breakpoint::canonical`]).
+#[derive(Debug, Default, Clone)]
+pub struct FusionCounter {
+    /// The number of read bases on either side of the junction to report.
+    flank: usize,
+    fusions: BTreeMap<Breakpoint, FusionSupport>,
+}
+
+impl FusionCounter {
+    /// Creates a counter reporting `flank` read bases on either side of each junction.
+    pub fn new(flank: usize) -> Self {
+        Self {
+            flank,
+            fusions: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the given breakpoint oriented as a fusion, and whether it was flipped.
+    fn orient(breakpoint: &Breakpoint) -> (Breakpoint, bool) {
+        let flipped = match (breakpoint.from_forward, breakpoint.to_forward) {
+            (true, true) => false,
+            (false, false) => true,
+            _ => breakpoint.canonical() != *breakpoint,
+        };
+        if flipped {
+            (breakpoint.flip(), true)
+        } else {
+            (*breakpoint, false)
+        }
+    }
+
+    /// Adds the fusions from the alignments of a single read with bases `y`.  A read supports a
+    /// fusion at most once, even if observed multiple times across its alignments.
+    pub fn add(&mut self, y: &[u8], alignments: &[Alignment], target_seqs: &[TargetSeq]) {
+        let mut fusions: Vec<(Breakpoint, String)> = Vec::new();
+        let breakpoints = alignments
+            .iter()
+            .flat_map(|alignment| alignment.breakpoints_in_query(target_seqs))
+            .filter(|(breakpoint, _)| breakpoint.from_target_idx != breakpoint.to_target_idx);
+        for (breakpoint, y_pos) in breakpoints {
+            let (fusion, flipped) = Self::orient(&breakpoint);
+            if fusions.iter().any(|(other, _)| *other == fusion) {
+                continue;
+            }
+            let before = &y[y_pos.saturating_sub(self.flank)..y_pos];
+            let after = &y[y_pos..(y_pos + self.flank).min(y.len())];
+            let (before, after) = if flipped {
+                (reverse_complement(after), reverse_complement(before))
+            } else {
+                (before.to_vec(), after.to_vec())
+            };
+            let junction = format!(
+                "{}|{}",
+                String::from_utf8_lossy(&before),
+                String::from_utf8_lossy(&after)
+            );
+            fusions.push((fusion, junction));
+        }
+        for (fusion, junction) in fusions {
+            let support = self
+                .fusions
+                .entry(fusion)
+                .or_insert_with(|| FusionSupport { reads: 0, junction });
+            support.reads += 1;
+        }
+    }
+
+    /// Returns the reads supporting the fusion at the given breakpoint, if any, in either
+    /// orientation.
+    pub fn get(&self, breakpoint: &Breakpoint) -> Option<&FusionSupport> {
+        self.fusions.get(&Self::orient(breakpoint).0)
+    }
+
+    /// Returns the fusions and their supporting reads, sorted by breakpoint.
+    pub fn iter(&self) -> impl Iterator<Item = (&Breakpoint, &FusionSupport)> + '_ {
+        self.fusions.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fusions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fusions.is_empty()
+    }
+
+    /// Writes the fusions supported by at least `min_reads` reads as a tab-delimited table with
+    /// a header, sorted by decreasing number of supporting reads, then by breakpoint.  Each line
+    /// has the 5' target, its last position before the junction (1-based) and strand, the 3'
+    /// target, its first position after the junction (1-based) and strand, the number of
+    /// supporting reads, and the junction sequence.
+    pub fn write_tsv<W: Write>(
+        &self,
+        writer: &mut W,
+        target_seqs: &[TargetSeq],
+        min_reads: usize,
+    ) -> Result<()> {
+        let strand = |is_forward: bool| if is_forward { '+' } else { '-' };
+        let mut fusions = self
+            .iter()
+            .filter(|(_, support)| support.reads >= min_reads)
+            .collect::<Vec<_>>();
+        fusions.sort_by_key(|(fusion, support)| (std::cmp::Reverse(support.reads), **fusion));
+        writeln!(
+            writer,
+            "five_prime\tfive_prime_pos\tfive_prime_strand\tthree_prime\tthree_prime_pos\t\
+             three_prime_strand\treads\tjunction"
+        )?;
+        for (fusion, support) in fusions {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                target_seqs[fusion.from_target_idx].name,
+                fusion.from_pos + 1,
+                strand(fusion.from_forward),
+                target_seqs[fusion.to_target_idx].name,
+                fusion.to_pos + 1,
+                strand(fusion.to_forward),
+                support.reads,
+                support.junction,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{
+        alignment::tests::align_double_strand, breakpoints::Breakpoint, scoring::Scoring,
+    };
+    use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+    use super::FusionCounter;
+
+    const GENE1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const GENE2: &str = "TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    #[rstest]
+    fn test_fusion_counter() {
+        let target_seqs = vec![
+            TargetSeq::new("gene1", &GENE1.as_bytes().to_vec(), false),
+            TargetSeq::new("gene2", &GENE2.as_bytes().to_vec(), false),
+        ];
+        // gene1:0-20 then gene2:20-40, from either strand of the fused transcript
+        let fwd_y = [&GENE1.as_bytes()[..20], &GENE2.as_bytes()[20..]].concat();
+        let fwd = align_double_strand(&target_seqs, &fwd_y, scoring());
+        let rev_y = reverse_complement(&fwd_y);
+        let rev = align_double_strand(&target_seqs, &rev_y, scoring());
+        // a read within gene1 only
+        let other_y = GENE1.as_bytes()[5..35].to_vec();
+        let other = align_double_strand(&target_seqs, &other_y, scoring());
+
+        let mut counter = FusionCounter::new(5);
+        assert!(counter.is_empty());
+        counter.add(&fwd_y, std::slice::from_ref(&fwd), &target_seqs);
+        counter.add(&rev_y, &[rev], &target_seqs);
+        // a read supports a fusion once
+        counter.add(&fwd_y, &[fwd.clone(), fwd], &target_seqs);
+        counter.add(&other_y, &[other], &target_seqs);
+        assert_eq!(counter.len(), 1);
+
+        let fusion = Breakpoint {
+            from_target_idx: 0,
+            from_pos: 19,
+            from_forward: true,
+            to_target_idx: 1,
+            to_pos: 20,
+            to_forward: true,
+        };
+        let support = counter.get(&fusion).unwrap();
+        assert_eq!(support.reads, 3);
+        assert_eq!(support.junction, "ATAAC|CTTAA");
+        assert_eq!(counter.get(&fusion.flip()), Some(support));
+
+        let mut tsv: Vec<u8> = Vec::new();
+        counter.write_tsv(&mut tsv, &target_seqs, 1).unwrap();
+        let tsv = String::from_utf8(tsv).unwrap();
+        let lines = tsv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("five_prime\t"));
+        assert_eq!(lines[1], "gene1\t20\t+\tgene2\t21\t+\t3\tATAAC|CTTAA");
+
+        let mut tsv: Vec<u8> = Vec::new();
+        counter.write_tsv(&mut tsv, &target_seqs, 4).unwrap();
+        assert_eq!(String::from_utf8(tsv).unwrap().lines().count(), 1);
+    }
+}
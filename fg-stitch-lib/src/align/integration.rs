@@ -0,0 +1,302 @@
+//! Vector integration sites (e.g. of AAV or lentiviral vectors) given by the jumps between vector
+//! and host targets in alignments, and their aggregation across reads into an integration-site
+//! report with the relative abundance of each site, as an estimate of the clonality of the
+//! integrations.
+
+use std::{collections::BTreeMap, io::Write};
+
+use anyhow::Result;
+
+use super::{alignment::Alignment, breakpoints::Breakpoint};
+use crate::util::target_seq::TargetSeq;
+
+/// A junction between a host and a vector target, as given by a jump in an alignment.
+///
+/// A junction is oriented so that the vector is on its forward strand, so `vector_first` is true
+/// when the end of the vector joins the host (a vector-host junction), and false when the host
+/// joins the start of the vector (a host-vector junction), while the strand of the host gives the
+/// orientation of the vector in the host.  The host position is the host base adjacent to the
+/// junction, 0-based on the forward strand of the host.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct IntegrationSite {
+    /// The index of the host target.
+    pub host_target_idx: usize,
+    /// The position of the host base adjacent to the junction.
+    pub host_pos: usize,
+    /// True if the read is aligned to the forward strand of the host when the vector is aligned
+    /// to its forward strand.
+    pub host_forward: bool,
+    /// True if the vector is before the host in the junction.
+    pub vector_first: bool,
+}
+
+/// The reads supporting an integration site, with the vector side of the junction of the first
+/// read.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SiteSupport {
+    /// The number of reads with a junction at the site.
+    pub reads: usize,
+    /// The index of the vector target.
+    pub vector_target_idx: usize,
+    /// The position of the vector base adjacent to the junction, 0-based on the forward strand.
+    pub vector_pos: usize,
+}
+
+/// Counts the reads supporting each integration site, namely each junction between a vector and
+/// a host target, with junctions observed on either strand of the read counted together.
+#[derive(Debug, Default, Clone)]
+pub struct IntegrationSiteCounter {
+    /// True for each target that is a vector, and false for each host target.
+    is_vector: Vec<bool>,
+    sites: BTreeMap<IntegrationSite, SiteSupport>,
+}
+
+impl IntegrationSiteCounter {
+    /// Creates a counter with `is_vector` true for each target (by index) that is a vector, the
+    /// other targets being the host.
+    pub fn new(is_vector: Vec<bool>) -> Self {
+        Self {
+            is_vector,
+            sites: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the integration site and the vector side of the junction given by the breakpoint,
+    /// if between a vector and a host target.
+    fn site_of(&self, breakpoint: &Breakpoint) -> Option<(IntegrationSite, SiteSupport)> {
+        let from_vector = self.is_vector[breakpoint.from_target_idx];
+        if from_vector == self.is_vector[breakpoint.to_target_idx] {
+            return None;
+        }
+        // Orient the junction so that the vector is on its forward strand
+        let vector_forward = if from_vector {
+            breakpoint.from_forward
+        } else {
+            breakpoint.to_forward
+        };
+        let (breakpoint, vector_first) = if vector_forward {
+            (*breakpoint, from_vector)
+        } else {
+            (breakpoint.flip(), !from_vector)
+        };
+        let (host, vector) = if vector_first {
+            (
+                (
+                    breakpoint.to_target_idx,
+                    breakpoint.to_pos,
+                    breakpoint.to_forward,
+                ),
+                (breakpoint.from_target_idx, breakpoint.from_pos),
+            )
+        } else {
+            (
+                (
+                    breakpoint.from_target_idx,
+                    breakpoint.from_pos,
+                    breakpoint.from_forward,
+                ),
+                (breakpoint.to_target_idx, breakpoint.to_pos),
+            )
+        };
+        let site = IntegrationSite {
+            host_target_idx: host.0,
+            host_pos: host.1,
+            host_forward: host.2,
+            vector_first,
+        };
+        let support = SiteSupport {
+            reads: 0,
+            vector_target_idx: vector.0,
+            vector_pos: vector.1,
+        };
+        Some((site, support))
+    }
+
+    /// Adds the integration sites from the alignments of a single read.  A read supports a site
+    /// at most once, even if observed multiple times across its alignments.
+    pub fn add(&mut self, alignments: &[Alignment], target_seqs: &[TargetSeq]) {
+        let mut sites: Vec<(IntegrationSite, SiteSupport)> = Vec::new();
+        let breakpoints = alignments
+            .iter()
+            .flat_map(|alignment| alignment.breakpoints(target_seqs));
+        for (site, support) in breakpoints.filter_map(|b| self.site_of(&b)) {
+            if !sites.iter().any(|(other, _)| *other == site) {
+                sites.push((site, support));
+            }
+        }
+        for (site, support) in sites {
+            self.sites.entry(site).or_insert(support).reads += 1;
+        }
+    }
+
+    /// Returns the reads supporting the integration site given by the breakpoint, if any.
+    pub fn get(&self, breakpoint: &Breakpoint) -> Option<&SiteSupport> {
+        let (site, _) = self.site_of(breakpoint)?;
+        self.sites.get(&site)
+    }
+
+    /// Returns the integration sites and their supporting reads, sorted by site.
+    pub fn iter(&self) -> impl Iterator<Item = (&IntegrationSite, &SiteSupport)> + '_ {
+        self.sites.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+
+    /// The total number of reads supporting the sites, counting a read once per site.
+    pub fn total_reads(&self) -> usize {
+        self.sites.values().map(|support| support.reads).sum()
+    }
+
+    /// The clonality of the integrations, from 0 when every site is equally abundant (polyclonal)
+    /// to 1 when all the reads support a single site (monoclonal), as one minus the Shannon
+    /// entropy of the relative abundance of the sites normalized by its maximum.  Zero without
+    /// sites.
+    pub fn clonality(&self) -> f64 {
+        let total = self.total_reads() as f64;
+        match self.sites.len() {
+            0 => 0.0,
+            1 => 1.0,
+            num_sites => {
+                let entropy: f64 = self
+                    .sites
+                    .values()
+                    .map(|support| support.reads as f64 / total)
+                    .map(|abundance| -abundance * abundance.ln())
+                    .sum();
+                1.0 - entropy / (num_sites as f64).ln()
+            }
+        }
+    }
+
+    /// Writes the integration sites supported by at least `min_reads` reads as a tab-delimited
+    /// table with a header, sorted by decreasing number of supporting reads, then by site.  Each
+    /// line has the host target, the host position adjacent to the junction (1-based) and strand,
+    /// the junction (`host-vector` or `vector-host`), the vector target and position adjacent to
+    /// the junction (1-based), the number of supporting reads, and the relative abundance of the
+    /// site among all the sites.
+    pub fn write_tsv<W: Write>(
+        &self,
+        writer: &mut W,
+        target_seqs: &[TargetSeq],
+        min_reads: usize,
+    ) -> Result<()> {
+        let strand = |is_forward: bool| if is_forward { '+' } else { '-' };
+        let total = self.total_reads().max(1) as f64;
+        let mut sites = self
+            .iter()
+            .filter(|(_, support)| support.reads >= min_reads)
+            .collect::<Vec<_>>();
+        sites.sort_by_key(|(site, support)| (std::cmp::Reverse(support.reads), **site));
+        writeln!(
+            writer,
+            "host\thost_pos\thost_strand\tjunction\tvector\tvector_pos\treads\tabundance"
+        )?;
+        for (site, support) in sites {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.4}",
+                target_seqs[site.host_target_idx].name,
+                site.host_pos + 1,
+                strand(site.host_forward),
+                if site.vector_first {
+                    "vector-host"
+                } else {
+                    "host-vector"
+                },
+                target_seqs[support.vector_target_idx].name,
+                support.vector_pos + 1,
+                support.reads,
+                support.reads as f64 / total,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{alignment::tests::align_double_strand, scoring::Scoring};
+    use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+    use super::{IntegrationSite, IntegrationSiteCounter};
+
+    const HOST: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const VECTOR: &str = "TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    #[rstest]
+    fn test_integration_site_counter() {
+        let target_seqs = vec![
+            TargetSeq::new("host", &HOST.as_bytes().to_vec(), false),
+            TargetSeq::new("vector", &VECTOR.as_bytes().to_vec(), false),
+        ];
+        let (host, vector) = (HOST.as_bytes(), VECTOR.as_bytes());
+        // host:0-20 then vector:20-40, from either strand of the read
+        let host_vector = [&host[..20], &vector[20..]].concat();
+        // vector:0-20 then host:20-40
+        let vector_host = [&vector[..20], &host[20..]].concat();
+
+        let mut counter = IntegrationSiteCounter::new(vec![false, true]);
+        assert!(counter.is_empty());
+        assert_eq!(counter.clonality(), 0.0);
+        for y in [
+            host_vector.clone(),
+            reverse_complement(&host_vector),
+            vector_host,
+        ] {
+            let alignment = align_double_strand(&target_seqs, &y, scoring());
+            counter.add(&[alignment], &target_seqs);
+        }
+        assert_eq!(counter.len(), 2);
+        assert_eq!(counter.total_reads(), 3);
+        let sites = counter.iter().collect::<Vec<_>>();
+        assert_eq!(
+            *sites[0].0,
+            IntegrationSite {
+                host_target_idx: 0,
+                host_pos: 19,
+                host_forward: true,
+                vector_first: false,
+            }
+        );
+        assert_eq!(sites[0].1.reads, 2);
+        assert_eq!(sites[0].1.vector_pos, 20);
+        assert_eq!(
+            *sites[1].0,
+            IntegrationSite {
+                host_target_idx: 0,
+                host_pos: 20,
+                host_forward: true,
+                vector_first: true,
+            }
+        );
+        assert_eq!(sites[1].1.reads, 1);
+        assert_eq!(sites[1].1.vector_pos, 19);
+        // abundances of 2/3 and 1/3
+        assert!((counter.clonality() - 0.0817).abs() < 1e-4);
+
+        let mut tsv: Vec<u8> = Vec::new();
+        counter.write_tsv(&mut tsv, &target_seqs, 1).unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "host\thost_pos\thost_strand\tjunction\tvector\tvector_pos\treads\tabundance\n\
+             host\t20\t+\thost-vector\tvector\t21\t2\t0.6667\n\
+             host\t21\t+\tvector-host\tvector\t20\t1\t0.3333\n"
+        );
+    }
+}
@@ -1,32 +1,53 @@
 use super::alignment::Alignment;
-use crate::util::io::{is_fastq_path, is_gzip_path};
+use super::read_group::ReadGroup;
+use super::subsample::Subsample;
+#[cfg(feature = "threads")]
+use super::umi::{group_by_umi, UmiSource};
+use crate::util::{
+    dna::reverse_complement,
+    io::{is_bam_path, is_fastq_path, is_gzip_path},
+};
+#[cfg(feature = "threads")]
+use anyhow::ensure;
 use anyhow::{Context, Result};
 use flate2::bufread::MultiGzDecoder;
+#[cfg(feature = "threads")]
 use flume::{bounded, Receiver, Sender};
+use noodles::{
+    bam::Reader as BamReader,
+    sam::{alignment::Record as SamRecord, record::cigar::op::Kind},
+};
 use seq_io::fastq::{OwnedRecord as FastqOwnedRecord, Reader as FastqReader};
+#[cfg(feature = "threads")]
+use std::{collections::HashMap, thread::JoinHandle};
 use std::{
     fs::File,
     io::{BufReader, Read},
     path::PathBuf,
-    thread::JoinHandle,
 };
 
 /// 128 KB default buffer size, same as pigz.
 pub const GZ_BUFSIZE: usize = 64 * (1 << 10) * 2;
 
 /// The number of FASTQ records to includ per chunk, scaled but the # of CPUS.
+#[cfg(feature = "threads")]
 pub const RECORDS_PER_CHUNK_PER_THREAD: usize = 10;
 
 /// The number of chunks allowed in a channel
+#[cfg(feature = "threads")]
 pub const READER_CHANNEL_NUM_CHUNKS: usize = 100;
 
 /// A message that is sent from a [`FastqThreadReader`] to the aligner threadpool to align a chunk
 /// of FASTQ records.
+#[cfg(feature = "threads")]
 #[derive(Debug)]
 pub struct InputMessage {
     /// The FASTQ records to align
     pub records: Vec<FastqOwnedRecord>,
 
+    /// The 0-based ordinal of the chunk in the input, in the order in which the chunks are sent
+    pub ordinal: u64,
+
     /// Where the records will be sent after alignment
     pub oneshot: Sender<OutputMessage>,
 }
@@ -34,11 +55,12 @@ pub struct InputMessage {
 /// The output result of a single pairwise alignment as a triple:
 /// 1. the FASTQ record that was aligned
 /// 2. None if no alignment was found, otherwise some tuple of pairwise alignment and the target
-///   strand to which the alignment was made.
+///    strand to which the alignment was made.
 /// 3. The alignment score, if aligned.
 pub type OutputResult = (FastqOwnedRecord, Vec<Alignment>, Option<i32>);
 
 /// The container for a chunk of pairwise alignments, one per input FASTQ record.
+#[cfg(feature = "threads")]
 pub struct OutputMessage {
     pub results: Vec<OutputResult>,
 }
@@ -83,7 +105,171 @@ impl<I: Iterator<Item = FastqOwnedRecord>> Iterator for FastqGroupingIterator<I>
     }
 }
 
+/// Returns true if the BAM record should be aligned, namely if it is a primary record that is
+/// either unmapped, or when `min_soft_clip` is given, has at least that many soft-clipped bases.
+/// When `min_soft_clip` is not given, all primary records are aligned.
+fn is_bam_record_to_align(record: &SamRecord, min_soft_clip: Option<usize>) -> bool {
+    let flags = record.flags();
+    if flags.is_secondary() || flags.is_supplementary() {
+        return false;
+    }
+    match min_soft_clip {
+        None => true,
+        Some(_) if flags.is_unmapped() => true,
+        Some(min_soft_clip) => {
+            let soft_clipped: usize = record
+                .cigar()
+                .iter()
+                .filter(|op| op.kind() == Kind::SoftClip)
+                .map(|op| op.len())
+                .sum();
+            soft_clipped >= min_soft_clip
+        }
+    }
+}
+
+/// Converts the BAM record to a FASTQ record, with the bases and qualities in the orientation in
+/// which they were sequenced.  Missing qualities are given the minimum quality.
+fn bam_record_to_fastq(record: &SamRecord) -> FastqOwnedRecord {
+    let head = record
+        .read_name()
+        .map(|name| AsRef::<[u8]>::as_ref(name).to_vec())
+        .unwrap_or_default();
+    let mut seq: Vec<u8> = record
+        .sequence()
+        .as_ref()
+        .iter()
+        .map(|b| u8::from(*b))
+        .collect();
+    let mut qual: Vec<u8> = if record.quality_scores().is_empty() {
+        vec![b'!'; seq.len()]
+    } else {
+        record
+            .quality_scores()
+            .as_ref()
+            .iter()
+            .map(|q| u8::from(*q) + b'!')
+            .collect()
+    };
+    if record.flags().is_reverse_complemented() {
+        seq = reverse_complement(&seq);
+        qual.reverse();
+    }
+    FastqOwnedRecord { head, seq, qual }
+}
+
+/// Returns an iterator over the records in the BAM to align (see [`is_bam_record_to_align`]),
+/// as FASTQ records.
+pub fn bam_records<R: Read>(
+    reader: R,
+    min_soft_clip: Option<usize>,
+) -> Result<impl Iterator<Item = FastqOwnedRecord>> {
+    let mut reader = BamReader::new(reader);
+    let header = reader
+        .read_header()
+        .context("Error reading the BAM header")?;
+    let mut record = SamRecord::default();
+    Ok(std::iter::from_fn(move || loop {
+        let size = reader
+            .read_record(&header, &mut record)
+            .expect("Error reading");
+        if size == 0 {
+            return None;
+        }
+        if is_bam_record_to_align(&record, min_soft_clip) {
+            return Some(bam_record_to_fastq(&record));
+        }
+    }))
+}
+
+/// Opens the FASTQ (or BAM) of reads, returning an iterator over the records.
+///
+/// The file may be `-` to read a FASTQ from standard input.  A FASTQ is decompressed if it has a
+/// GZIP extension, or if `decompress` is true and it has no FASTQ extension.  If the file has a
+/// BAM extension, the reads are read from the BAM (see [`bam_records`]).
+pub fn read_records(
+    file: &PathBuf,
+    decompress: bool,
+    min_soft_clip: Option<usize>,
+) -> Result<Box<dyn Iterator<Item = FastqOwnedRecord>>> {
+    // Open the file or standad input
+    let raw_handle = if file.as_os_str() == "-" {
+        Box::new(std::io::stdin()) as Box<dyn Read>
+    } else {
+        let handle =
+            File::open(file).with_context(|| format!("Error opening input: {}", file.display()))?;
+        Box::new(handle) as Box<dyn Read>
+    };
+    // Wrap it in a buffer
+    let buf_handle = BufReader::with_capacity(GZ_BUFSIZE, raw_handle);
+
+    // Read from a BAM, otherwise open a FASTQ reader
+    if is_bam_path(file) {
+        return Ok(Box::new(bam_records(buf_handle, min_soft_clip)?));
+    }
+    // Maybe wrap it in a decompressor
+    let maybe_decoder_handle = {
+        let is_gzip = is_gzip_path(file) || (!is_fastq_path(file) && decompress);
+        if is_gzip {
+            Box::new(MultiGzDecoder::new(buf_handle)) as Box<dyn Read>
+        } else {
+            Box::new(buf_handle) as Box<dyn Read>
+        }
+    };
+    Ok(Box::new(
+        FastqReader::with_capacity(maybe_decoder_handle, GZ_BUFSIZE)
+            .into_records()
+            .map(|r| r.expect("Error reading")),
+    ))
+}
+
+/// An input FASTQ (or BAM) of reads, with the read group of its reads, if any.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ReadInput {
+    pub path: PathBuf,
+    pub read_group: Option<ReadGroup>,
+}
+
+impl ReadInput {
+    pub fn new(path: PathBuf, read_group: Option<ReadGroup>) -> Self {
+        Self { path, read_group }
+    }
+
+    /// Returns the given read to be aligned, tagged with the read group of the input, if any
+    /// (see [`ReadGroup::tag`]).
+    fn tag(&self, mut record: FastqOwnedRecord) -> FastqOwnedRecord {
+        if let Some(read_group) = &self.read_group {
+            read_group.tag(&mut record);
+        }
+        record
+    }
+}
+
+/// Opens each input of reads as with [`read_records`], returning an iterator over the records of
+/// each input in turn, tagged with the read group of their input, if any.  The records of each
+/// input are subsampled with `subsample` (see [`Subsample::apply`]), separately from those of the
+/// other inputs.
+pub fn read_inputs(
+    inputs: &[ReadInput],
+    decompress: bool,
+    min_soft_clip: Option<usize>,
+    subsample: Subsample,
+) -> Result<Box<dyn Iterator<Item = FastqOwnedRecord>>> {
+    let mut iters = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let records = read_records(&input.path, decompress, min_soft_clip)?;
+        let input = input.clone();
+        iters.push(
+            subsample
+                .apply(records)
+                .map(move |record| input.tag(record)),
+        );
+    }
+    Ok(Box::new(iters.into_iter().flatten()))
+}
+
 /// A FASTQ reader that runs in its own thread and chunks reads to send to a pool of aligners.
+#[cfg(feature = "threads")]
 pub struct FastqThreadReader {
     /// The [`JoinHandle`] for the thread that is reading.
     pub handle: JoinHandle<Result<()>>,
@@ -93,17 +279,20 @@ pub struct FastqThreadReader {
     pub to_output_rx: Receiver<Receiver<OutputMessage>>,
 }
 
+#[cfg(feature = "threads")]
 impl FastqThreadReader {
     /// Writes the chunk of records to the alignment channel, as well as a receiver to the output
     /// channel.
     fn write_records_to_txs(
         records: &[FastqOwnedRecord],
+        ordinal: u64,
         to_align_tx: &Sender<InputMessage>,
         to_output_tx: &Sender<Receiver<OutputMessage>>,
     ) {
         let (records_tx, records_rx) = flume::unbounded(); // oneshot channel
         let input_msg = InputMessage {
             records: records.to_vec(),
+            ordinal,
             oneshot: records_tx,
         };
         to_align_tx.send(input_msg).expect("Error sending message");
@@ -113,7 +302,64 @@ impl FastqThreadReader {
     }
 
     /// Creates a new `FastqThreadReader` in a new thread.
-    pub fn new(file: PathBuf, decompress: bool, threads: usize) -> Self {
+    ///
+    /// The reads are read as with [`read_inputs`].  The chunks with the ordinals in
+    /// `completed_chunks`, already aligned by an interrupted run (see
+    /// [`super::shard::Checkpoint`]), are not sent to be aligned, but must have the same number
+    /// of reads as given.
+    pub fn new(
+        inputs: Vec<ReadInput>,
+        decompress: bool,
+        threads: usize,
+        min_soft_clip: Option<usize>,
+        subsample: Subsample,
+        completed_chunks: HashMap<u64, usize>,
+    ) -> Self {
+        Self::spawn(threads, completed_chunks, move || {
+            read_inputs(&inputs, decompress, min_soft_clip, subsample)
+        })
+    }
+
+    /// Creates a new `FastqThreadReader` in a new thread that reads all the reads, groups them by
+    /// UMI, and sends the consensus read of each UMI family to be aligned, in the order in which
+    /// each UMI first occurs.  All the reads are held in memory.
+    ///
+    /// The reads of each input are read as with [`read_records`], and grouped as with
+    /// [`group_by_umi`], separately from those of the other inputs, and the consensus reads are
+    /// tagged with the read group of their input, if any.  The UMI families of each input, rather
+    /// than its reads, are subsampled with `subsample`, so that no family is split.  The
+    /// `completed_chunks` are skipped as with [`Self::new`].
+    pub fn with_umi_consensus(
+        inputs: Vec<ReadInput>,
+        decompress: bool,
+        threads: usize,
+        min_soft_clip: Option<usize>,
+        umi_source: UmiSource,
+        subsample: Subsample,
+        completed_chunks: HashMap<u64, usize>,
+    ) -> Self {
+        Self::spawn(threads, completed_chunks, move || {
+            let mut consensus_reads = Vec::new();
+            for input in &inputs {
+                let records = read_records(&input.path, decompress, min_soft_clip)?;
+                let families = group_by_umi(records, &umi_source)?;
+                consensus_reads.extend(
+                    subsample
+                        .apply(families.into_iter())
+                        .map(|family| input.tag(family.consensus())),
+                );
+            }
+            Ok(consensus_reads.into_iter())
+        })
+    }
+
+    /// Creates a new `FastqThreadReader` that sends the reads returned by `records`, which is
+    /// called in the new thread, other than those of the `completed_chunks`.
+    fn spawn<R, I>(threads: usize, completed_chunks: HashMap<u64, usize>, records: R) -> Self
+    where
+        R: FnOnce() -> Result<I> + Send + 'static,
+        I: Iterator<Item = FastqOwnedRecord>,
+    {
         // Channel to send chunks of records to align
         let (to_align_tx, to_align_rx): (Sender<InputMessage>, Receiver<InputMessage>) =
             bounded(READER_CHANNEL_NUM_CHUNKS * threads);
@@ -126,44 +372,39 @@ impl FastqThreadReader {
         ) = bounded(READER_CHANNEL_NUM_CHUNKS * threads);
 
         let handle = std::thread::spawn(move || {
-            // Open the file or standad input
-            let raw_handle = if file.as_os_str() == "-" {
-                Box::new(std::io::stdin()) as Box<dyn Read>
-            } else {
-                let handle = File::open(&file)
-                    .with_context(|| format!("Error opening input: {}", file.display()))
-                    .unwrap();
-                Box::new(handle) as Box<dyn Read>
-            };
-            // Wrap it in a buffer
-            let buf_handle = BufReader::with_capacity(GZ_BUFSIZE, raw_handle);
-            // Maybe wrap it in a decompressor
-            let maybe_decoder_handle = {
-                let is_gzip = is_gzip_path(&file) || (!is_fastq_path(&file) && decompress);
-                if is_gzip {
-                    Box::new(MultiGzDecoder::new(buf_handle)) as Box<dyn Read>
-                } else {
-                    Box::new(buf_handle) as Box<dyn Read>
+            let fastq_iter = records()?;
+
+            // Sends the chunk to be aligned, unless already aligned
+            let send = |records: &[FastqOwnedRecord], ordinal: u64| -> Result<()> {
+                match completed_chunks.get(&ordinal) {
+                    None => {
+                        Self::write_records_to_txs(records, ordinal, &to_align_tx, &to_output_tx)
+                    }
+                    Some(&num_reads) => ensure!(
+                        num_reads == records.len(),
+                        "Chunk {ordinal} has {} reads but {num_reads} when checkpointed, so the \
+                         inputs differ from those of the interrupted run",
+                        records.len()
+                    ),
                 }
+                Ok(())
             };
 
-            // Open a FASTQ reader, then group reads that have the same read sequence, then chunk
-            // chunk the reads to send over the output channel, keeping reads with the same read
-            // sequence grouped together.
-            let fastq_iter = FastqReader::with_capacity(maybe_decoder_handle, GZ_BUFSIZE)
-                .into_records()
-                .map(|r| r.expect("Error reading"));
+            // Group reads that have the same read sequence, then chunk the reads to send over the
+            // output channel, keeping reads with the same read sequence grouped together.
             let fastq_grouping_iter = FastqGroupingIterator::new(fastq_iter);
             let mut records = Vec::new();
+            let mut ordinal = 0;
             for chunk in fastq_grouping_iter {
                 records.extend(chunk);
                 if records.len() >= RECORDS_PER_CHUNK_PER_THREAD {
-                    Self::write_records_to_txs(&records, &to_align_tx, &to_output_tx);
+                    send(&records, ordinal)?;
                     records.clear();
+                    ordinal += 1;
                 }
             }
             if !records.is_empty() {
-                Self::write_records_to_txs(&records, &to_align_tx, &to_output_tx);
+                send(&records, ordinal)?;
             }
 
             Ok(())
@@ -175,3 +416,144 @@ impl FastqThreadReader {
         }
     }
 }
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use std::num::NonZeroUsize;
+
+    use noodles::{
+        bam::Writer as BamWriter,
+        core::Position,
+        sam::{
+            alignment::Record as SamRecord,
+            header::record::value::{map::ReferenceSequence, Map},
+            record::{Flags, QualityScores, ReadName, Sequence},
+            Header as SamHeader,
+        },
+    };
+    use rstest::rstest;
+
+    use super::{bam_records, read_inputs, ReadInput};
+    use crate::align::{read_group::ReadGroup, subsample::Subsample};
+
+    fn record(name: &str, flags: Flags, cigar: &str, seq: &str, qual: &str) -> SamRecord {
+        let mut builder = SamRecord::builder()
+            .set_read_name(name.parse::<ReadName>().unwrap())
+            .set_flags(flags)
+            .set_sequence(seq.parse::<Sequence>().unwrap())
+            .set_quality_scores(qual.parse::<QualityScores>().unwrap());
+        if !flags.is_unmapped() {
+            builder = builder
+                .set_reference_sequence_id(0)
+                .set_alignment_start(Position::MIN)
+                .set_cigar(cigar.parse().unwrap());
+        }
+        builder.build()
+    }
+
+    /// Writes the records to a BAM, then reads back the names, bases, and qualities of the records
+    /// to align.
+    fn read_bam(
+        records: &[SamRecord],
+        min_soft_clip: Option<usize>,
+    ) -> Vec<(String, String, String)> {
+        let header = SamHeader::builder()
+            .add_reference_sequence(
+                "chr1".parse().unwrap(),
+                Map::<ReferenceSequence>::new(NonZeroUsize::try_from(100).unwrap()),
+            )
+            .build();
+        let mut writer = BamWriter::new(Vec::new());
+        writer.write_header(&header).unwrap();
+        for record in records {
+            writer.write_record(&header, record).unwrap();
+        }
+        writer.try_finish().unwrap();
+        let data = writer.get_ref().get_ref().clone();
+        bam_records(data.as_slice(), min_soft_clip)
+            .unwrap()
+            .map(|r| {
+                (
+                    String::from_utf8(r.head).unwrap(),
+                    String::from_utf8(r.seq).unwrap(),
+                    String::from_utf8(r.qual).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[rstest]
+    fn test_bam_records() {
+        let records = [
+            record("mapped", Flags::empty(), "4M", "ACGG", "ABCD"),
+            record("reverse", Flags::REVERSE_COMPLEMENTED, "4M", "ACGG", "ABCD"),
+            record("unmapped", Flags::UNMAPPED, "", "TTTA", "IIII"),
+            record("clipped", Flags::empty(), "2S2M", "ACGG", "ABCD"),
+            record("secondary", Flags::SECONDARY, "4M", "ACGG", "ABCD"),
+            record("supplementary", Flags::SUPPLEMENTARY, "4M", "ACGG", "ABCD"),
+        ];
+        let to_tuple = |name: &str, seq: &str, qual: &str| {
+            (name.to_string(), seq.to_string(), qual.to_string())
+        };
+
+        // all primary records, in the orientation in which they were sequenced
+        assert_eq!(
+            read_bam(&records, None),
+            [
+                to_tuple("mapped", "ACGG", "ABCD"),
+                to_tuple("reverse", "CCGT", "DCBA"),
+                to_tuple("unmapped", "TTTA", "IIII"),
+                to_tuple("clipped", "ACGG", "ABCD"),
+            ]
+        );
+
+        // only unmapped or soft-clipped records
+        assert_eq!(
+            read_bam(&records, Some(2)),
+            [
+                to_tuple("unmapped", "TTTA", "IIII"),
+                to_tuple("clipped", "ACGG", "ABCD"),
+            ]
+        );
+        assert_eq!(
+            read_bam(&records, Some(3)),
+            [to_tuple("unmapped", "TTTA", "IIII")]
+        );
+    }
+
+    #[rstest]
+    fn test_read_inputs() {
+        let dir = std::env::temp_dir().join(format!("stitch-read-inputs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (first, second) = (dir.join("first.fq"), dir.join("second.fq"));
+        std::fs::write(&first, "@r1\nACGT\n+\nIIII\n@r2\nTTTT\n+\nIIII\n").unwrap();
+        std::fs::write(&second, "@r3 comment\nGGGG\n+\nIIII\n").unwrap();
+
+        let inputs = [
+            ReadInput::new(first.clone(), Some(ReadGroup::new("rg1", None))),
+            ReadInput::new(second.clone(), None),
+        ];
+        let heads: Vec<Vec<u8>> = read_inputs(&inputs, false, None, Subsample::default())
+            .unwrap()
+            .map(|record| record.head)
+            .collect();
+        assert_eq!(
+            heads,
+            [
+                b"r1\tRG:Z:rg1".to_vec(),
+                b"r2\tRG:Z:rg1".to_vec(),
+                b"r3 comment".to_vec()
+            ]
+        );
+
+        // at most one read of each input
+        let subsample = Subsample::new(Some(1), None, 1).unwrap();
+        let heads: Vec<Vec<u8>> = read_inputs(&inputs, false, None, subsample)
+            .unwrap()
+            .map(|record| record.head)
+            .collect();
+        assert_eq!(heads, [b"r1\tRG:Z:rg1".to_vec(), b"r3 comment".to_vec()]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,371 @@
+//! The positions of the contigs that alignments may jump from or to, namely the boundaries of
+//! annotated features (such as the exons of a transcript, or the ITRs of a vector) read from a
+//! BED file, so that jumps are placed at exon junctions or ITR boundaries rather than anywhere in
+//! a repeat or micro-homology.  Jumps from or to the free sites, such as known recombination or
+//! splice points, are scored without the jump score.
+
+use std::{collections::HashMap, ops::Range, path::Path};
+
+use anyhow::{anyhow, ensure, Context, Result};
+
+use crate::align::aligners::constants::MIN_SCORE;
+
+/// The feature boundaries of the contigs, from or to which jumps are either restricted, or
+/// preferred by scoring the jumps elsewhere with an extra penalty.
+///
+/// Position `p` of a contig is the boundary between its bases `p - 1` and `p` (0-based), so a
+/// feature `[start, end)` has the boundaries `start` and `end`.  A jump from a contig after
+/// aligning its base `p - 1` is from position `p`, and a jump to a contig aligning its base `p`
+/// first is to position `p`, so a jump from the end of one exon to the start of another is from
+/// and to feature boundaries.  Jumps from and to the contigs without features are not affected.
+///
+/// Jumps from or to the free sites of the contigs, at positions as for the feature boundaries,
+/// are penalty-free, namely scored without the jump score, whether or not the contig has features
+/// (see [`Self::add_free_site`]).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct JumpSites {
+    /// The sorted feature boundaries of each contig, on its forward strand.
+    boundaries: HashMap<String, Vec<usize>>,
+    /// The sorted free sites of each contig, on its forward strand.
+    free_sites: HashMap<String, Vec<usize>>,
+    /// The score added to a jump for each of its ends that is not at a feature boundary, or `None`
+    /// if such jumps are forbidden.
+    off_site_score: Option<i32>,
+}
+
+impl JumpSites {
+    /// Creates the jump sites with no features, where the jumps from or to the positions that are
+    /// not feature boundaries are scored with `off_site_score` (which must not be positive), or
+    /// forbidden if `None`.
+    pub fn new(off_site_score: Option<i32>) -> Self {
+        assert!(
+            off_site_score.is_none_or(|score| score <= 0),
+            "The off-site jump score can't be positive"
+        );
+        Self {
+            boundaries: HashMap::new(),
+            free_sites: HashMap::new(),
+            off_site_score,
+        }
+    }
+
+    /// Adds the feature `[start, end)` (0-based) of the given contig.
+    pub fn add_feature(&mut self, contig: &str, start: usize, end: usize) {
+        let boundaries = self.boundaries.entry(contig.to_string()).or_default();
+        for position in [start, end] {
+            insert_sorted(boundaries, position);
+        }
+    }
+
+    /// Adds a free site at the given position of the given contig, on its forward strand, so that
+    /// the jumps from or to it are scored without the jump score (see
+    /// [`crate::align::SingleContigAligner::set_free_jump_sites`]).  A jump from or to a free
+    /// site is never forbidden nor scored as off-site.
+    pub fn add_free_site(&mut self, contig: &str, position: usize) {
+        insert_sorted(
+            self.free_sites.entry(contig.to_string()).or_default(),
+            position,
+        );
+    }
+
+    /// Parses the features in BED format: one feature per line, with the contig name, then the
+    /// 0-based start and end of the feature, tab-delimited.  Any other columns, and the comment,
+    /// `track`, and `browser` lines, are ignored.
+    pub fn from_bed(bed: &str, off_site_score: Option<i32>) -> Result<Self> {
+        let mut sites = Self::new(off_site_score);
+        parse_bed(bed, |contig, start, end| {
+            sites.add_feature(contig, start, end)
+        })?;
+        Ok(sites)
+    }
+
+    /// Reads the features from a BED file (see [`Self::from_bed`]).
+    pub fn from_path(path: &Path, off_site_score: Option<i32>) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read the BED: {}", path.display()))?;
+        Self::from_bed(&contents, off_site_score)
+            .with_context(|| format!("Invalid BED: {}", path.display()))
+    }
+
+    /// Adds the boundaries of the features in BED format (see [`Self::from_bed`]) as free sites
+    /// (see [`Self::add_free_site`]).
+    pub fn add_free_sites_from_bed(&mut self, bed: &str) -> Result<()> {
+        parse_bed(bed, |contig, start, end| {
+            self.add_free_site(contig, start);
+            self.add_free_site(contig, end);
+        })
+    }
+
+    /// Adds the boundaries of the features in a BED file as free sites (see
+    /// [`Self::add_free_sites_from_bed`]).
+    pub fn add_free_sites_from_path(&mut self, path: &Path) -> Result<()> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read the BED: {}", path.display()))?;
+        self.add_free_sites_from_bed(&contents)
+            .with_context(|| format!("Invalid BED: {}", path.display()))
+    }
+
+    /// The feature boundaries of the given contig, on its forward strand, sorted.
+    pub fn boundaries(&self, contig: &str) -> &[usize] {
+        self.boundaries.get(contig).map_or(&[], Vec::as_slice)
+    }
+
+    /// The free sites of the given contig, on its forward strand, sorted.
+    pub fn free_sites_of(&self, contig: &str) -> &[usize] {
+        self.free_sites.get(contig).map_or(&[], Vec::as_slice)
+    }
+
+    /// The score added to a jump for each of its ends that is not at a feature boundary, or `None`
+    /// if such jumps are forbidden.
+    pub fn off_site_score(&self) -> Option<i32> {
+        self.off_site_score
+    }
+
+    /// The score added to a jump from or to each position `0..=region.len()` of the given region
+    /// of the contig and strand, with the region in the coordinates of the strand, or
+    /// [`MIN_SCORE`] where forbidden.  Empty if the contig has no features, as jumps may then be
+    /// from or to any position (see [`crate::align::SingleContigAligner::set_jump_site_scores`]).
+    /// The free sites are scored as feature boundaries.
+    pub fn site_scores(
+        &self,
+        contig: &str,
+        is_forward: bool,
+        contig_len: usize,
+        region: Range<usize>,
+    ) -> Vec<i32> {
+        let boundaries = self.boundaries(contig);
+        if boundaries.is_empty() {
+            return Vec::new();
+        }
+        let mut scores = vec![self.off_site_score.unwrap_or(MIN_SCORE); region.len() + 1];
+        let free_sites = self.free_sites_of(contig);
+        for &boundary in boundaries.iter().chain(free_sites) {
+            if let Some(index) = region_index(boundary, is_forward, contig_len, &region) {
+                scores[index] = 0;
+            }
+        }
+        scores
+    }
+
+    /// Whether jumps from or to each position `0..=region.len()` of the given region of the contig
+    /// and strand are free, with the region in the coordinates of the strand.  Empty if the contig
+    /// has no free sites (see [`crate::align::SingleContigAligner::set_free_jump_sites`]).
+    pub fn free_sites(
+        &self,
+        contig: &str,
+        is_forward: bool,
+        contig_len: usize,
+        region: Range<usize>,
+    ) -> Vec<bool> {
+        let free_sites = self.free_sites_of(contig);
+        if free_sites.is_empty() {
+            return Vec::new();
+        }
+        let mut free = vec![false; region.len() + 1];
+        for &site in free_sites {
+            if let Some(index) = region_index(site, is_forward, contig_len, &region) {
+                free[index] = true;
+            }
+        }
+        free
+    }
+}
+
+/// Inserts the position into the sorted positions, unless already there.
+fn insert_sorted(positions: &mut Vec<usize>, position: usize) {
+    if let Err(index) = positions.binary_search(&position) {
+        positions.insert(index, position);
+    }
+}
+
+/// The index in the given region of the contig and strand of the given position of the contig on
+/// its forward strand, or `None` if the position is outside the region (or the contig).
+fn region_index(
+    position: usize,
+    is_forward: bool,
+    contig_len: usize,
+    region: &Range<usize>,
+) -> Option<usize> {
+    let position = if is_forward {
+        position
+    } else {
+        contig_len.checked_sub(position)?
+    };
+    (position <= region.end)
+        .then_some(position)?
+        .checked_sub(region.start)
+}
+
+/// Parses the features in BED format (see [`JumpSites::from_bed`]), calling `add` with the contig
+/// name, start, and end of each feature.
+fn parse_bed(bed: &str, mut add: impl FnMut(&str, usize, usize)) -> Result<()> {
+    let lines = bed.lines().enumerate().filter(|(_, line)| {
+        !line.trim().is_empty()
+            && !line.starts_with('#')
+            && !line.starts_with("track")
+            && !line.starts_with("browser")
+    });
+    for (index, line) in lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        ensure!(
+            fields.len() >= 3,
+            "Expected at least 3 columns on line {} of the BED: {line}",
+            index + 1
+        );
+        let parse = |field: &str| {
+            field.trim().parse::<usize>().with_context(|| {
+                format!("Invalid position on line {} of the BED: {field}", index + 1)
+            })
+        };
+        let (start, end) = (parse(fields[1])?, parse(fields[2])?);
+        if start > end {
+            return Err(anyhow!(
+                "The feature starts after its end on line {} of the BED: {start} > {end}",
+                index + 1
+            ));
+        }
+        add(fields[0], start, end);
+    }
+    Ok(())
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{aligners::constants::MIN_SCORE, MultiContigAligner, Scoring};
+
+    use super::JumpSites;
+
+    #[rstest]
+    fn test_from_bed() {
+        let bed = "track name=exons\n# comment\nchr1\t10\t20\texon1\nchr1\t30\t40\nchr2\t5\t5\n";
+        let sites = JumpSites::from_bed(bed, None).unwrap();
+        assert_eq!(sites.boundaries("chr1"), &[10, 20, 30, 40]);
+        assert_eq!(sites.boundaries("chr2"), &[5]);
+        assert!(sites.boundaries("chr3").is_empty());
+
+        assert!(JumpSites::from_bed("chr1\t10\n", None).is_err());
+        assert!(JumpSites::from_bed("chr1\t20\t10\n", None).is_err());
+        assert!(JumpSites::from_bed("chr1\tten\t20\n", None).is_err());
+    }
+
+    #[rstest]
+    fn test_site_scores() {
+        let mut sites = JumpSites::new(Some(-3));
+        sites.add_feature("chr1", 2, 5);
+        assert_eq!(
+            sites.site_scores("chr1", true, 8, 0..8),
+            vec![-3, -3, 0, -3, -3, 0, -3, -3, -3]
+        );
+        // on the reverse strand, the boundaries are at 8 - 5 and 8 - 2
+        assert_eq!(
+            sites.site_scores("chr1", false, 8, 0..8),
+            vec![-3, -3, -3, 0, -3, -3, 0, -3, -3]
+        );
+        // in the coordinates of the region
+        assert_eq!(sites.site_scores("chr1", true, 8, 4..6), vec![-3, 0, -3]);
+        assert!(sites.site_scores("chr2", true, 8, 0..8).is_empty());
+
+        let mut sites = JumpSites {
+            off_site_score: None,
+            ..sites
+        };
+        assert_eq!(
+            sites.site_scores("chr1", true, 8, 4..6),
+            vec![MIN_SCORE, 0, MIN_SCORE]
+        );
+
+        // jumps from or to the free sites are not forbidden
+        sites.add_free_site("chr1", 6);
+        assert_eq!(
+            sites.site_scores("chr1", true, 8, 4..6),
+            vec![MIN_SCORE, 0, 0]
+        );
+    }
+
+    #[rstest]
+    fn test_free_sites() {
+        let mut sites = JumpSites::new(None);
+        assert!(sites.free_sites("chr1", true, 8, 0..8).is_empty());
+        sites
+            .add_free_sites_from_bed("chr1\t2\t5\nchr1\t9\t9\n")
+            .unwrap();
+        assert_eq!(sites.free_sites_of("chr1"), &[2, 5, 9]);
+        // no features, so jumps may be from or to any position
+        assert!(sites.site_scores("chr1", true, 8, 0..8).is_empty());
+
+        let (t, f) = (true, false);
+        assert_eq!(
+            sites.free_sites("chr1", true, 8, 0..8),
+            vec![f, f, t, f, f, t, f, f, f]
+        );
+        // on the reverse strand, the free sites are at 8 - 5 and 8 - 2, and past the contig
+        assert_eq!(
+            sites.free_sites("chr1", false, 8, 0..8),
+            vec![f, f, f, t, f, f, t, f, f]
+        );
+        // in the coordinates of the region
+        assert_eq!(sites.free_sites("chr1", true, 8, 4..6), vec![f, t, f]);
+        assert!(sites.free_sites("chr2", true, 8, 0..8).is_empty());
+
+        assert!(sites.add_free_sites_from_bed("chr1\t20\t10\n").is_err());
+    }
+
+    #[rstest]
+    fn test_align_with_jump_sites() {
+        // The query joins the two exons of the contig, where the last base of the first exon (C)
+        // is also the last base of the intron, so the jump may be placed either side of it.
+        // Without jump sites, the jump is before the C, and so misses the exon junction.
+        let exon1 = "ACGTTGCAAGGCTTACGATC";
+        let intron = "GGTCCGTAGGCATCGATGAC";
+        let exon2 = "ATGCCTAGGTCATCGGACTT";
+        let contig = format!("{exon1}{intron}{exon2}");
+        let query = format!("{exon1}{exon2}");
+
+        let mut aligner: MultiContigAligner<MatchParams> = MultiContigAligner::new();
+        let scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -1))
+            .set_xclip(0)
+            .set_yclip(0);
+        aligner.add_contig("chr1", true, contig.as_bytes(), false, scoring);
+        let alignment = aligner.custom(query.as_bytes());
+        assert_eq!(alignment.cigar(), "19=20J21=");
+        assert_eq!(alignment.score, 40 - 10);
+
+        let mut sites = JumpSites::new(None);
+        sites.add_feature("chr1", 0, 20);
+        sites.add_feature("chr1", 40, 60);
+        aligner.set_jump_sites(Some(Arc::new(sites)));
+        let alignment = aligner.custom(query.as_bytes());
+        assert_eq!(alignment.cigar(), "20=20J20=");
+        assert_eq!(alignment.score, 40 - 10);
+    }
+
+    #[rstest]
+    #[case(20)] // a jump from the free site
+    #[case(40)] // a jump to the free site
+    fn test_align_with_free_jump_sites(#[case] free_site: usize) {
+        // As in test_align_with_jump_sites, but the jump at the exon junction is free
+        let exon1 = "ACGTTGCAAGGCTTACGATC";
+        let intron = "GGTCCGTAGGCATCGATGAC";
+        let exon2 = "ATGCCTAGGTCATCGGACTT";
+        let contig = format!("{exon1}{intron}{exon2}");
+        let query = format!("{exon1}{exon2}");
+
+        let mut aligner: MultiContigAligner<MatchParams> = MultiContigAligner::new();
+        let scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -1))
+            .set_xclip(0)
+            .set_yclip(0);
+        aligner.add_contig("chr1", true, contig.as_bytes(), false, scoring);
+        let mut sites = JumpSites::new(None);
+        sites.add_free_site("chr1", free_site);
+        aligner.set_jump_sites(Some(Arc::new(sites)));
+        let alignment = aligner.custom(query.as_bytes());
+        assert_eq!(alignment.cigar(), "20=20J20=");
+        assert_eq!(alignment.score, 40);
+    }
+}
@@ -0,0 +1,396 @@
+//! Timing of the stages of aligning each query, for finding where the time goes without an
+//! external profiler.
+//!
+//! The [`MultiContigAligner`](super::MultiContigAligner) adds the time spent in each [`Stage`] to
+//! its [`StageTimings`] when recording (see
+//! [`MultiContigAligner::set_record_timings`](super::MultiContigAligner::set_record_timings)),
+//! which are then written per read by a [`MetricsWriter`].  With the `tracing` feature, the stages
+//! are also `tracing` spans, for structured logs of each alignment.
+
+use std::{
+    fmt::Display,
+    io::Write,
+    ops::AddAssign,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Error, Result};
+
+/// Enters a `tracing` span at the given level (e.g. `DEBUG`), with the given name and fields,
+/// until the end of the enclosing block, when built with the `tracing` feature.
+macro_rules! stage_span {
+    ($level:ident, $($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::span!(tracing::Level::$level, $($arg)*).entered();
+    };
+}
+pub(crate) use stage_span;
+
+/// A stage of aligning a query.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Stage {
+    /// Acquiring and initializing the matrices of the contigs.
+    MatrixInit,
+    /// Filling the columns of the matrices, other than computing the best jumps.
+    ColumnFill,
+    /// Computing the best jump into each contig for each column.
+    JumpInfo,
+    /// Tracing back the alignments from the matrices.
+    Traceback,
+}
+
+impl Stage {
+    /// All the stages, in the order in which they first occur.
+    pub const ALL: [Stage; 4] = [
+        Self::MatrixInit,
+        Self::ColumnFill,
+        Self::JumpInfo,
+        Self::Traceback,
+    ];
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MatrixInit => write!(f, "matrix_init"),
+            Self::ColumnFill => write!(f, "column_fill"),
+            Self::JumpInfo => write!(f, "jump_info"),
+            Self::Traceback => write!(f, "traceback"),
+        }
+    }
+}
+
+/// The time spent in each [`Stage`], serialized in seconds.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub struct StageTimings {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "as_secs"))]
+    pub matrix_init: Duration,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "as_secs"))]
+    pub column_fill: Duration,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "as_secs"))]
+    pub jump_info: Duration,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "as_secs"))]
+    pub traceback: Duration,
+}
+
+impl StageTimings {
+    /// Returns the time spent in the given stage.
+    pub fn get(&self, stage: Stage) -> Duration {
+        match stage {
+            Stage::MatrixInit => self.matrix_init,
+            Stage::ColumnFill => self.column_fill,
+            Stage::JumpInfo => self.jump_info,
+            Stage::Traceback => self.traceback,
+        }
+    }
+
+    /// Adds the given time to the given stage.
+    pub fn add(&mut self, stage: Stage, duration: Duration) {
+        match stage {
+            Stage::MatrixInit => self.matrix_init += duration,
+            Stage::ColumnFill => self.column_fill += duration,
+            Stage::JumpInfo => self.jump_info += duration,
+            Stage::Traceback => self.traceback += duration,
+        }
+    }
+
+    /// Returns the time spent in all the stages.
+    pub fn total(&self) -> Duration {
+        Stage::ALL.iter().map(|stage| self.get(*stage)).sum()
+    }
+}
+
+impl AddAssign for StageTimings {
+    fn add_assign(&mut self, other: Self) {
+        for stage in Stage::ALL {
+            self.add(stage, other.get(stage));
+        }
+    }
+}
+
+/// Adds the time since `started` to the given stage, if recording the timings, returning the
+/// current time from which to time the next stage.
+pub(crate) fn lap(
+    timings: &mut Option<StageTimings>,
+    stage: Stage,
+    started: Option<Instant>,
+) -> Option<Instant> {
+    let (timings, started) = (timings.as_mut()?, started?);
+    let now = Instant::now();
+    timings.add(stage, now - started);
+    Some(now)
+}
+
+#[cfg(feature = "serde")]
+fn as_secs<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// The time spent aligning a read.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ReadMetrics {
+    pub read_name: String,
+    pub read_length: usize,
+    /// The time spent in each stage of the jump-aware alignment of the read.
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub timings: StageTimings,
+    /// The time spent aligning the read, including the stages before the jump-aware alignment
+    /// (e.g. pre-aligning) and after (e.g. re-aligning around the origin).
+    #[cfg_attr(feature = "serde", serde(serialize_with = "as_secs"))]
+    pub total: Duration,
+}
+
+impl ReadMetrics {
+    /// Returns the metrics as a single line of JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String> {
+        use anyhow::Context;
+        serde_json::to_string(self).context("Could not serialize the read metrics")
+    }
+
+    /// Writes the names of the columns written by [`Self::write_tsv`].
+    pub fn write_tsv_header<W: Write>(writer: &mut W) -> Result<()> {
+        write!(writer, "read_name\tread_length")?;
+        for stage in Stage::ALL {
+            write!(writer, "\t{stage}")?;
+        }
+        writeln!(writer, "\ttotal")?;
+        Ok(())
+    }
+
+    /// Writes the metrics as a tab-delimited line, with the times in seconds.
+    pub fn write_tsv<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write!(writer, "{}\t{}", self.read_name, self.read_length)?;
+        for stage in Stage::ALL {
+            write!(writer, "\t{:.6}", self.timings.get(stage).as_secs_f64())?;
+        }
+        writeln!(writer, "\t{:.6}", self.total.as_secs_f64())?;
+        Ok(())
+    }
+}
+
+/// The format in which the metrics of each read are written.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum MetricsFormat {
+    /// One JSON object per line (see [`ReadMetrics::to_json`]).
+    Json,
+    /// Tab-delimited, with a header line (see [`ReadMetrics::write_tsv`]).
+    Tsv,
+}
+
+impl Display for MetricsFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Json => write!(f, "json"),
+            Self::Tsv => write!(f, "tsv"),
+        }
+    }
+}
+
+impl FromStr for MetricsFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(MetricsFormat::Json),
+            "tsv" => Ok(MetricsFormat::Tsv),
+            _ => Err(anyhow!("Invalid metrics format: {}", s)),
+        }
+    }
+}
+
+/// The time spent aligning all the reads, and the slowest read.
+#[derive(Debug, Default, Clone)]
+pub struct MetricsSummary {
+    pub reads: usize,
+    pub timings: StageTimings,
+    pub total: Duration,
+    /// The name of the slowest read to align, and the time spent aligning it.
+    pub slowest: Option<(String, Duration)>,
+}
+
+impl MetricsSummary {
+    pub fn add(&mut self, metrics: &ReadMetrics) {
+        self.reads += 1;
+        self.timings += metrics.timings;
+        self.total += metrics.total;
+        if self
+            .slowest
+            .as_ref()
+            .is_none_or(|(_, total)| metrics.total > *total)
+        {
+            self.slowest = Some((metrics.read_name.clone(), metrics.total));
+        }
+    }
+
+    /// Returns the fraction of the time spent aligning the reads spent in the given stage.
+    pub fn fraction(&self, stage: Stage) -> f64 {
+        if self.total.is_zero() {
+            0.0
+        } else {
+            self.timings.get(stage).as_secs_f64() / self.total.as_secs_f64()
+        }
+    }
+}
+
+/// Writes the metrics of each read in the given format, and sums them.
+pub struct MetricsWriter {
+    writer: Box<dyn Write + Send>,
+    format: MetricsFormat,
+    summary: MetricsSummary,
+}
+
+impl MetricsWriter {
+    pub fn new(mut writer: Box<dyn Write + Send>, format: MetricsFormat) -> Result<Self> {
+        if format == MetricsFormat::Tsv {
+            ReadMetrics::write_tsv_header(&mut writer)?;
+        }
+        Ok(Self {
+            writer,
+            format,
+            summary: MetricsSummary::default(),
+        })
+    }
+
+    /// Writes the metrics of a read.
+    pub fn write(&mut self, metrics: &ReadMetrics) -> Result<()> {
+        match self.format {
+            #[cfg(feature = "serde")]
+            MetricsFormat::Json => writeln!(self.writer, "{}", metrics.to_json()?)?,
+            #[cfg(not(feature = "serde"))]
+            MetricsFormat::Json => anyhow::bail!("JSON metrics require the serde feature"),
+            MetricsFormat::Tsv => metrics.write_tsv(&mut self.writer)?,
+        }
+        self.summary.add(metrics);
+        Ok(())
+    }
+
+    /// Flushes the output, returning the sum of the metrics of all the reads.
+    pub fn finish(mut self) -> Result<MetricsSummary> {
+        self.writer.flush()?;
+        Ok(self.summary)
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use rstest::rstest;
+
+    use super::{
+        lap, MetricsFormat, MetricsSummary, MetricsWriter, ReadMetrics, Stage, StageTimings,
+    };
+
+    fn metrics(read_name: &str, millis: u64) -> ReadMetrics {
+        let mut timings = StageTimings::default();
+        timings.add(Stage::ColumnFill, Duration::from_millis(millis));
+        timings.add(Stage::Traceback, Duration::from_millis(1));
+        ReadMetrics {
+            read_name: read_name.to_string(),
+            read_length: 100,
+            timings,
+            total: Duration::from_millis(millis + 2),
+        }
+    }
+
+    /// A writer whose output may be read after it is moved into a [`MetricsWriter`].
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[rstest]
+    fn test_stage_timings() {
+        let mut timings = StageTimings::default();
+        timings.add(Stage::JumpInfo, Duration::from_millis(2));
+        timings.add(Stage::JumpInfo, Duration::from_millis(3));
+        timings += metrics("r1", 10).timings;
+        assert_eq!(timings.get(Stage::JumpInfo), Duration::from_millis(5));
+        assert_eq!(timings.total(), Duration::from_millis(16));
+    }
+
+    #[rstest]
+    fn test_lap() {
+        // not recording
+        let mut timings = None;
+        assert_eq!(lap(&mut timings, Stage::Traceback, None), None);
+
+        let mut timings = Some(StageTimings::default());
+        let started = Some(std::time::Instant::now());
+        std::thread::sleep(Duration::from_millis(1));
+        let next = lap(&mut timings, Stage::Traceback, started);
+        assert!(next > started);
+        let timings = timings.unwrap();
+        assert!(timings.traceback >= Duration::from_millis(1));
+        assert_eq!(timings.total(), timings.traceback);
+    }
+
+    #[rstest]
+    fn test_metrics_summary() {
+        let mut summary = MetricsSummary::default();
+        assert_eq!(summary.fraction(Stage::ColumnFill), 0.0);
+        summary.add(&metrics("r1", 10));
+        summary.add(&metrics("r2", 28));
+        summary.add(&metrics("r3", 2));
+        assert_eq!(summary.reads, 3);
+        assert_eq!(summary.total, Duration::from_millis(46));
+        assert!((summary.fraction(Stage::ColumnFill) - 40.0 / 46.0).abs() < 1e-9);
+        assert_eq!(
+            summary.slowest,
+            Some(("r2".to_string(), Duration::from_millis(30)))
+        );
+    }
+
+    #[rstest]
+    fn test_write_tsv() {
+        let buffer = SharedBuffer::default();
+        let mut writer = MetricsWriter::new(Box::new(buffer.clone()), MetricsFormat::Tsv).unwrap();
+        writer.write(&metrics("r1", 10)).unwrap();
+        assert_eq!(writer.finish().unwrap().reads, 1);
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap(),
+            "read_name\tread_length\tmatrix_init\tcolumn_fill\tjump_info\ttraceback\ttotal\n\
+             r1\t100\t0.000000\t0.010000\t0.000000\t0.001000\t0.012000\n"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    fn test_write_json() {
+        let buffer = SharedBuffer::default();
+        let mut writer = MetricsWriter::new(Box::new(buffer.clone()), MetricsFormat::Json).unwrap();
+        writer.write(&metrics("r1", 10)).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap(),
+            "{\"read_name\":\"r1\",\"read_length\":100,\"matrix_init\":0.0,\"column_fill\":0.01,\
+             \"jump_info\":0.0,\"traceback\":0.001,\"total\":0.012}\n"
+        );
+    }
+
+    #[rstest]
+    fn test_metrics_format_from_str() {
+        assert_eq!(
+            "JSON".parse::<MetricsFormat>().unwrap(),
+            MetricsFormat::Json
+        );
+        assert_eq!("tsv".parse::<MetricsFormat>().unwrap(), MetricsFormat::Tsv);
+        assert!("csv".parse::<MetricsFormat>().is_err());
+    }
+}
@@ -1,16 +1,227 @@
+pub mod adapters;
 mod aligners;
 mod alignment;
+pub mod all_vs_all;
+pub mod bisulfite;
+pub mod breakpoints;
+pub mod compare;
+pub mod config;
+pub mod consensus;
+pub mod coverage;
+pub mod crispr;
+pub mod dedup;
+pub mod demux;
+pub mod describe;
+pub mod duplex;
+pub mod filter;
+pub mod fusions;
+pub mod integration;
 pub mod io;
+pub mod jump_sites;
+pub mod metrics;
+pub mod overlap;
+pub mod poa;
+mod position;
+pub mod primers;
+mod profile;
+pub mod qc;
+pub mod read_group;
+pub mod read_support;
+mod refine;
 mod scoring;
+pub mod self_align;
+pub mod shard;
+pub mod splice;
+pub mod strategy;
 mod sub_alignment;
+pub mod subsample;
+mod substitution_matrix;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 mod traceback;
+pub mod umi;
+pub mod variants;
+pub mod verify;
+#[cfg(feature = "viz")]
+pub mod viz;
 
-pub use aligners::{AlignmentMode, Builder};
+pub use aligners::{
+    constants::{AlignerBackend, AlignmentOperation},
+    multi_contig_aligner::{
+        ContigInfo, MultiContigAligner, ProgressCallback, StreamingAligner, Verdict,
+    },
+    shared_contigs::SharedContigs,
+    single_contig_aligner::SingleContigAligner,
+    AlignmentMode, Builder,
+};
+pub use alignment::{
+    Alignment, AlignmentStats, AlignmentStep, AlignmentSteps, JumpInterval, ScoreComponent,
+    ScoreContribution, SegmentInfo,
+};
+pub use position::{ContigIdx, ContigPos, QueryPos};
+pub use profile::ScoreProfile;
+pub use scoring::{ContigScoring, GlobalScoring, Scoring, ScoringPreset};
+pub use substitution_matrix::{MatrixMatchFunc, BLOSUM62, EDNAFULL, MAX_MATRIX_SYMBOLS};
+#[cfg(feature = "serde")]
+pub use traceback::dump::{ContigTraceback, TracebackDump, TRACEBACK_DUMP_VERSION};
+pub use traceback::{EndTieBreak, TracebackLayout};
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) use {aligners::constants::MIN_SCORE, alignment::contig_bases_for};
 
 use anyhow::{anyhow, Error};
 use std::{fmt::Display, str::FromStr};
 
+/// The definitions of the length of an alignment (see [`Alignment::length_of`]), as expected by
+/// different downstream tools.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum AlignmentLength {
+    /// The number of alignment columns (see [`Alignment::column_count`]).
+    #[default]
+    Columns,
+    /// The number of aligned query bases (see [`Alignment::query_aligned_length`]).
+    Query,
+    /// The number of aligned contig bases (see [`Alignment::contig_aligned_length`]).
+    Contig,
+}
+
+impl Display for AlignmentLength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Columns => write!(f, "columns"),
+            Self::Query => write!(f, "query"),
+            Self::Contig => write!(f, "contig"),
+        }
+    }
+}
+
+impl FromStr for AlignmentLength {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "columns" | "column" => Ok(AlignmentLength::Columns),
+            "query" => Ok(AlignmentLength::Query),
+            "contig" | "target" => Ok(AlignmentLength::Contig),
+            _ => Err(anyhow!("Invalid alignment length: {}", s)),
+        }
+    }
+}
+
+/// The formats in which the operations of an alignment are written (see
+/// [`Alignment::cigar_with_format`]).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum CigarFormat {
+    /// A single CIGAR across the whole alignment, with the jumps between and within contigs
+    /// encoded with the `J`/`j` and `C`/`c` operators, and the clips with `A` and `B`.
+    #[default]
+    Extended,
+    /// One standard SAM CIGAR per segment of the alignment between jumps, on the forward strand
+    /// of the target, with the query bases outside of the segment soft-clipped.  The first
+    /// segment is typically used for the record and the others for its `SA` tag.
+    SamCompat,
+    /// One minimap2-style `cs` difference string per segment of the alignment between jumps, on
+    /// the forward strand of the target, in the short form (see [`CsStyle::Short`]).
+    Cs,
+}
+
+impl Display for CigarFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Extended => write!(f, "extended"),
+            Self::SamCompat => write!(f, "sam-compat"),
+            Self::Cs => write!(f, "cs"),
+        }
+    }
+}
+
+impl FromStr for CigarFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "extended" => Ok(CigarFormat::Extended),
+            "sam-compat" | "sam_compat" | "samcompat" | "sam" => Ok(CigarFormat::SamCompat),
+            "cs" => Ok(CigarFormat::Cs),
+            _ => Err(anyhow!("Invalid CIGAR format: {}", s)),
+        }
+    }
+}
+
+/// The forms of the minimap2-style `cs` difference string (see [`Alignment::cs`]).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum CsStyle {
+    /// Runs of identical bases are written as their length (e.g. `:10`).
+    #[default]
+    Short,
+    /// Runs of identical bases are written as their bases (e.g. `=ACGTACGTAC`).
+    Long,
+}
+
+impl Display for CsStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Short => write!(f, "short"),
+            Self::Long => write!(f, "long"),
+        }
+    }
+}
+
+impl FromStr for CsStyle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "short" => Ok(CsStyle::Short),
+            "long" => Ok(CsStyle::Long),
+            _ => Err(anyhow!("Invalid cs style: {}", s)),
+        }
+    }
+}
+
+/// How the query bases outside of the aligned part of a record are represented (see
+/// [`Alignment::sam_cigars`]), as downstream tools differ on what they accept for supplementary
+/// records.  Primary records are always soft-clipped, since they must hold the whole query.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ClipPolicy {
+    /// Soft-clipped (`S`), with the whole query in the record.
+    Soft,
+    /// Hard-clipped (`H`), with only the aligned bases in the record.
+    #[default]
+    Hard,
+    /// Omitted from the record, with only the aligned bases in the record and no clip operators,
+    /// so the position of the aligned bases in the query is given only by the `qs` and `qe` tags.
+    Trimmed,
+}
+
+impl Display for ClipPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Soft => write!(f, "soft"),
+            Self::Hard => write!(f, "hard"),
+            Self::Trimmed => write!(f, "trimmed"),
+        }
+    }
+}
+
+impl FromStr for ClipPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "soft" => Ok(ClipPolicy::Soft),
+            "hard" => Ok(ClipPolicy::Hard),
+            "trimmed" | "trim" => Ok(ClipPolicy::Trimmed),
+            _ => Err(anyhow!("Invalid clip policy: {}", s)),
+        }
+    }
+}
+
 /// The various strategies to pick the primary alignment amongst multiple sub-alignments.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
 pub enum PrimaryPickingStrategy {
     #[default]
@@ -40,3 +251,32 @@ impl FromStr for PrimaryPickingStrategy {
         }
     }
 }
+
+/// The tag of the records of an alignment with the contribution of each run of its operations
+/// to its score (see [`Builder::score_breakdown`] and [`ScoreContribution`]), separated by commas.
+pub const SCORE_BREAKDOWN_TAG: &str = "sb";
+
+/// The tag of an unmapped record whose read was not aligned, with the reason (see
+/// [`SkipReason`]).
+pub const SKIP_TAG: &str = "sk";
+
+/// The reasons a read is not aligned by the guards of the aligners (see
+/// [`Builder::max_matrix_cells`], [`Builder::max_read_memory`], and [`Builder::read_timeout_ms`]).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SkipReason {
+    /// The matrices to align the read would have more cells, or use more memory, than the
+    /// maximum.
+    MatrixSize,
+    /// The read was not aligned within the timeout.
+    Timeout,
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MatrixSize => write!(f, "matrix-size"),
+            Self::Timeout => write!(f, "timeout"),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Overlap (dovetail) alignment of two contigs, where a suffix of the first contig is aligned to a
+//! prefix of the second, for stitching fragments of a construct (or assembly) into one sequence.
+
+use bio::alignment::pairwise::MatchFunc;
+
+use super::{
+    aligners::{
+        constants::{AlignmentOperation::Match, MIN_PENALTY, MIN_SCORE},
+        single_contig_aligner::SingleContigAligner,
+    },
+    alignment::Alignment,
+    scoring::Scoring,
+};
+
+/// The overlap of a suffix of the first contig with a prefix of the second contig.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Overlap {
+    /// The alignment of the first contig (x) to the second contig (y), which clips the prefix of
+    /// the first contig and the suffix of the second contig.
+    pub alignment: Alignment,
+    /// The 0-based start of the overlap in the first contig, which continues to its end.
+    pub first_start: usize,
+    /// The 0-based exclusive end of the overlap in the second contig, which starts at its start.
+    pub second_end: usize,
+    /// The number of alignment columns of the overlap (see [`Alignment::column_count`]).
+    pub length: usize,
+    /// The fraction of the alignment columns of the overlap that are matches.
+    pub identity: f64,
+}
+
+impl Overlap {
+    /// Builds the overlap from the alignment of the first contig to the second, or `None` if the
+    /// alignment has no columns.
+    fn from_alignment(alignment: Alignment) -> Option<Self> {
+        let length = alignment.column_count();
+        if length == 0 {
+            return None;
+        }
+        let matches = alignment
+            .operations
+            .iter()
+            .filter(|op| matches!(op, Match))
+            .count();
+        Some(Self {
+            first_start: alignment.xstart,
+            second_end: alignment.yend,
+            length,
+            identity: matches as f64 / length as f64,
+            alignment,
+        })
+    }
+
+    /// Returns the sequence stitching the two contigs through the overlap: the first contig up to
+    /// the overlap, the overlap as the second contig has it, then the rest of the second contig.
+    pub fn stitch(&self, first: &[u8], second: &[u8]) -> Vec<u8> {
+        [&first[..self.first_start], second].concat()
+    }
+}
+
+/// Aligns a suffix of one contig to a prefix of another, without jumps, so that each alignment is
+/// a conventional (affine gap) dovetail alignment.
+pub struct OverlapAligner<F: MatchFunc> {
+    aligner: SingleContigAligner<F>,
+}
+
+impl<F: MatchFunc + Sync + Copy> OverlapAligner<F> {
+    /// Creates an aligner with the match, mismatch, and gap scores of `scoring`.  The jump scores
+    /// and clipping penalties of `scoring` are ignored.
+    pub fn new(scoring: Scoring<F>) -> Self {
+        let scoring = scoring
+            .set_jump_score(MIN_PENALTY)
+            .set_xclip_prefix(0)
+            .set_xclip_suffix(MIN_SCORE)
+            .set_yclip_prefix(MIN_SCORE)
+            .set_yclip_suffix(0);
+        Self {
+            aligner: SingleContigAligner::with_scoring(scoring),
+        }
+    }
+
+    /// Returns the best scoring overlap of a suffix of `first` with a prefix of `second`, or
+    /// `None` if no overlap scores above an empty overlap.  Swap the contigs for the overlap of a
+    /// suffix of `second` with a prefix of `first`.
+    pub fn align(&mut self, first: &[u8], second: &[u8]) -> Option<Overlap> {
+        let alignment = self.aligner.custom(first, second);
+        if alignment.score <= 0 {
+            return None;
+        }
+        Overlap::from_alignment(alignment)
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use super::OverlapAligner;
+    use crate::{
+        align::Scoring,
+        test_util::{random_dna, Rng, SEED},
+    };
+
+    fn aligner() -> OverlapAligner<MatchParams> {
+        OverlapAligner::new(Scoring::with_jump_score(
+            -6,
+            -2,
+            -10,
+            MatchParams::new(1, -4),
+        ))
+    }
+
+    #[rstest]
+    fn test_overlap() {
+        let mut rng = Rng::new(SEED);
+        let first = random_dna(&mut rng, 200);
+        let second = [&first[150..], &random_dna(&mut rng, 100)[..]].concat();
+
+        let overlap = aligner().align(&first, &second).unwrap();
+        assert_eq!(overlap.first_start, 150);
+        assert_eq!(overlap.second_end, 50);
+        assert_eq!(overlap.length, 50);
+        assert_eq!(overlap.identity, 1.0);
+        assert_eq!(overlap.alignment.cigar(), "150A50=100B");
+        let stitched = overlap.stitch(&first, &second);
+        assert_eq!(stitched, [&first[..], &second[50..]].concat());
+
+        // the other order overlaps by chance at most
+        let overlap = aligner().align(&second, &first);
+        assert!(overlap.is_none_or(|overlap| overlap.length < 10));
+    }
+
+    #[rstest]
+    fn test_overlap_with_differences() {
+        let mut rng = Rng::new(SEED);
+        let first = random_dna(&mut rng, 200);
+        let mut second = [&first[120..], &random_dna(&mut rng, 100)[..]].concat();
+        second[40] = if second[40] == b'A' { b'C' } else { b'A' };
+        second.remove(60);
+
+        let overlap = aligner().align(&first, &second).unwrap();
+        assert_eq!(overlap.first_start, 120);
+        assert_eq!(overlap.second_end, 79);
+        assert_eq!(overlap.length, 80);
+        assert_eq!(overlap.identity, 78.0 / 80.0);
+    }
+}
@@ -0,0 +1,417 @@
+//! Jump-aware multiple sequence alignment of reads, where the reads with the same jump structure
+//! are collapsed into a partial-order alignment (POA) graph seeded by their alignments, from which
+//! the consensus and the alleles of the reads are read with their per-base support, for example to
+//! report the alleles of an edited locus.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
+
+use anyhow::Result;
+
+use super::{
+    aligners::constants::AlignmentOperation::{Del, Match, Subst, Xjump},
+    alignment::{target_seq_for, Alignment},
+};
+use crate::util::target_seq::TargetSeq;
+
+/// A jump in an alignment, between contig positions.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct Jump {
+    /// The index of the contig before the jump.
+    pub from_contig_idx: usize,
+    /// The position on the contig after the last base aligned before the jump.
+    pub from_pos: usize,
+    /// The index of the contig after the jump.
+    pub to_contig_idx: usize,
+    /// The position on the contig of the first base aligned after the jump.
+    pub to_pos: usize,
+}
+
+/// The jump structure of an alignment, namely the contig on which it starts and its jumps (in
+/// contig coordinates, strand specific), including any jumps across the origin of circular contigs.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct JumpStructure {
+    pub start_contig_idx: usize,
+    pub jumps: Vec<Jump>,
+}
+
+impl JumpStructure {
+    /// Returns the jump structure of the alignment.
+    pub fn of(alignment: &Alignment) -> Self {
+        let jumps = alignment
+            .iter_steps()
+            .filter_map(|step| match step.op {
+                Xjump(to_contig_idx, to_pos) => Some(Jump {
+                    from_contig_idx: step.contig_idx,
+                    from_pos: step.x_pos,
+                    to_contig_idx,
+                    to_pos,
+                }),
+                _ => None,
+            })
+            .collect();
+        Self {
+            start_contig_idx: alignment.start_contig_idx,
+            jumps,
+        }
+    }
+
+    /// Describes the structure, as the target and strand on which the alignment starts (e.g.
+    /// `chr1+`) if it has no jumps, and otherwise as its jumps separated by commas, each as the
+    /// target, 1-based position on the forward strand, and strand of the last base before the
+    /// jump, then those of the first base after the jump (e.g. `chr1:100:+>chr2:500:-`).
+    pub fn describe(&self, target_seqs: &[TargetSeq]) -> String {
+        let strand = |is_forward: bool| if is_forward { '+' } else { '-' };
+        if self.jumps.is_empty() {
+            let (target_seq, is_forward) = target_seq_for(target_seqs, self.start_contig_idx);
+            return format!("{}{}", target_seq.name, strand(is_forward));
+        }
+        // The 1-based position on the forward strand of the given base on the contig
+        let describe = |contig_idx: usize, pos: usize| {
+            let (target_seq, is_forward) = target_seq_for(target_seqs, contig_idx);
+            let pos = if is_forward {
+                pos + 1
+            } else {
+                target_seq.len() - pos
+            };
+            format!("{}:{}:{}", target_seq.name, pos, strand(is_forward))
+        };
+        self.jumps
+            .iter()
+            .map(|jump| {
+                format!(
+                    "{}>{}",
+                    describe(jump.from_contig_idx, jump.from_pos - 1),
+                    describe(jump.to_contig_idx, jump.to_pos)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// A node of a POA graph, as the segment of the alignments (between jumps), the position on the
+/// contig of the segment, the offset of an inserted base before that position (or `usize::MAX`
+/// for a base aligned to the position), and the base.  The order of the nodes is a topological
+/// order of the graph.
+type NodeKey = (usize, usize, usize, u8);
+
+/// A node of a POA graph, with the number of reads through it and through each of its in-edges.
+#[derive(Debug, Default, Clone)]
+struct Node {
+    support: usize,
+    predecessors: HashMap<NodeKey, usize>,
+}
+
+/// A path through a POA graph.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PoaPath {
+    /// The bases along the path.
+    pub seq: Vec<u8>,
+    /// The number of reads through each base of the path.
+    pub support: Vec<usize>,
+    /// The number of reads following exactly the path.
+    pub reads: usize,
+}
+
+/// The partial-order alignment graph of reads with the same jump structure.
+///
+/// Rather than aligning each read to the graph, the reads are threaded through the graph using
+/// their alignments to the contigs: a read base aligned to a contig position is a node of the
+/// column of that position (with one node per base), a read base inserted before a contig position
+/// is a node of the insertion at that position (by its offset in the insertion), and deleted
+/// contig positions are skipped.  Since the reads share their jumps, the segments between jumps
+/// line up across reads.
+#[derive(Debug, Default, Clone)]
+pub struct PoaGraph {
+    nodes: BTreeMap<NodeKey, Node>,
+    paths: HashMap<Vec<NodeKey>, usize>,
+    reads: usize,
+}
+
+impl PoaGraph {
+    /// Adds the read bases to the graph along their alignment.
+    pub fn add(&mut self, alignment: &Alignment, y: &[u8]) {
+        let mut path: Vec<NodeKey> = Vec::new();
+        let mut segment = 0;
+        // The offset of the next base of an insertion
+        let mut offset = 0;
+        for step in alignment.iter_steps() {
+            if step.op != Del {
+                offset = 0;
+            }
+            let key = match step.op {
+                Match | Subst => (segment, step.x_pos, usize::MAX),
+                Del => {
+                    offset += 1;
+                    (segment, step.x_pos, offset - 1)
+                }
+                Xjump(_, _) => {
+                    segment += 1;
+                    continue;
+                }
+                _ => continue,
+            };
+            let key = (key.0, key.1, key.2, y[step.y_pos].to_ascii_uppercase());
+            let node = self.nodes.entry(key).or_default();
+            node.support += 1;
+            if let Some(previous) = path.last() {
+                *node.predecessors.entry(*previous).or_default() += 1;
+            }
+            path.push(key);
+        }
+        *self.paths.entry(path).or_default() += 1;
+        self.reads += 1;
+    }
+
+    /// The number of reads in the graph.
+    pub fn reads(&self) -> usize {
+        self.reads
+    }
+
+    /// Returns the path of the given nodes.
+    fn path(&self, keys: &[NodeKey]) -> PoaPath {
+        PoaPath {
+            seq: keys.iter().map(|key| key.3).collect(),
+            support: keys.iter().map(|key| self.nodes[key].support).collect(),
+            reads: self.paths.get(keys).copied().unwrap_or(0),
+        }
+    }
+
+    /// Returns the consensus path through the graph, where each node follows the predecessor
+    /// with the most reads through their edge (then with the highest score), the score of a node
+    /// being that of its predecessor plus the number of reads through their edge, and the path
+    /// ends at the node with the highest score.
+    pub fn consensus(&self) -> PoaPath {
+        let mut scores: HashMap<NodeKey, (usize, Option<NodeKey>)> = HashMap::new();
+        for (key, node) in &self.nodes {
+            let best = node.predecessors.iter().max_by_key(|(previous, reads)| {
+                (**reads, scores[*previous].0, Reverse(**previous))
+            });
+            let score = match best {
+                Some((previous, reads)) => (scores[previous].0 + reads, Some(*previous)),
+                None => (0, None),
+            };
+            scores.insert(*key, score);
+        }
+        let mut keys = Vec::new();
+        let mut current = self
+            .nodes
+            .keys()
+            .max_by_key(|key| (scores[*key].0, Reverse(**key)))
+            .copied();
+        while let Some(key) = current {
+            keys.push(key);
+            current = scores[&key].1;
+        }
+        keys.reverse();
+        self.path(&keys)
+    }
+
+    /// Returns the alleles of the reads, namely their distinct paths through the graph, sorted by
+    /// decreasing number of reads, then by sequence.
+    pub fn alleles(&self) -> Vec<PoaPath> {
+        let mut alleles = self
+            .paths
+            .keys()
+            .map(|keys| self.path(keys))
+            .collect::<Vec<_>>();
+        alleles.sort_by(|a, b| b.reads.cmp(&a.reads).then_with(|| a.seq.cmp(&b.seq)));
+        alleles
+    }
+}
+
+/// Groups the reads by the jump structure of their alignments, with one POA graph per structure.
+#[derive(Debug, Default, Clone)]
+pub struct JumpAwareMsa {
+    graphs: BTreeMap<JumpStructure, PoaGraph>,
+}
+
+impl JumpAwareMsa {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the read bases to the graph of the jump structure of their alignment.
+    pub fn add(&mut self, alignment: &Alignment, y: &[u8]) {
+        let structure = JumpStructure::of(alignment);
+        self.graphs.entry(structure).or_default().add(alignment, y);
+    }
+
+    /// Returns the jump structures and their graphs, sorted by structure.
+    pub fn iter(&self) -> impl Iterator<Item = (&JumpStructure, &PoaGraph)> + '_ {
+        self.graphs.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.graphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.graphs.is_empty()
+    }
+
+    /// Writes the consensus and alleles of each jump structure with at least `min_reads` reads as
+    /// a tab-delimited table with a header, with the structures sorted by decreasing number of
+    /// reads.  Each line has the jump structure (see [`JumpStructure::describe`]), its number of
+    /// reads, the allele (`consensus` for the consensus, or the 1-based rank of the allele), the
+    /// number of reads of the allele and their fraction of the reads of the structure, the
+    /// sequence of the allele, and the number of reads supporting each of its bases (separated by
+    /// commas).  Alleles with fewer than `min_reads` reads are not written.
+    pub fn write_tsv<W: Write>(
+        &self,
+        writer: &mut W,
+        target_seqs: &[TargetSeq],
+        min_reads: usize,
+    ) -> Result<()> {
+        let mut graphs = self
+            .iter()
+            .filter(|(_, graph)| graph.reads() >= min_reads)
+            .collect::<Vec<_>>();
+        graphs.sort_by_key(|(structure, graph)| (Reverse(graph.reads()), *structure));
+        writeln!(
+            writer,
+            "structure\tstructure_reads\tallele\treads\tfraction\tsequence\tsupport"
+        )?;
+        for (structure, graph) in graphs {
+            let structure = structure.describe(target_seqs);
+            let alleles = graph
+                .alleles()
+                .into_iter()
+                .enumerate()
+                .filter(|(_, allele)| allele.reads >= min_reads)
+                .map(|(index, allele)| ((index + 1).to_string(), allele));
+            for (allele, path) in
+                std::iter::once(("consensus".to_string(), graph.consensus())).chain(alleles)
+            {
+                let support = path
+                    .support
+                    .iter()
+                    .map(usize::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}\t{:.4}\t{}\t{}",
+                    structure,
+                    graph.reads(),
+                    allele,
+                    path.reads,
+                    path.reads as f64 / graph.reads() as f64,
+                    String::from_utf8_lossy(&path.seq),
+                    support,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{alignment::tests::align_double_strand, scoring::Scoring};
+    use crate::util::target_seq::TargetSeq;
+
+    use super::{Jump, JumpAwareMsa, JumpStructure};
+
+    const AMPLICON: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT\
+                            TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    #[rstest]
+    fn test_jump_aware_msa() {
+        let target_seqs = vec![TargetSeq::new(
+            "amplicon",
+            &AMPLICON.as_bytes().to_vec(),
+            false,
+        )];
+        let amplicon = AMPLICON.as_bytes();
+        // a substitution at position 10
+        let mut substitution = amplicon.to_vec();
+        substitution[10] = b'G';
+        // a deletion of positions 33-53, as a jump
+        let deletion = [&amplicon[..33], &amplicon[53..]].concat();
+
+        let mut msa = JumpAwareMsa::new();
+        assert!(msa.is_empty());
+        for y in [
+            amplicon.to_vec(),
+            amplicon.to_vec(),
+            amplicon.to_vec(),
+            substitution.clone(),
+            deletion.clone(),
+            deletion.clone(),
+        ] {
+            let alignment = align_double_strand(&target_seqs, &y, scoring());
+            msa.add(&alignment, &y);
+        }
+        assert_eq!(msa.len(), 2);
+        let graphs = msa.iter().collect::<Vec<_>>();
+
+        // no jumps
+        let (structure, graph) = graphs[0];
+        assert_eq!(structure.describe(&target_seqs), "amplicon+");
+        assert_eq!(graph.reads(), 4);
+        let consensus = graph.consensus();
+        assert_eq!(consensus.seq, amplicon);
+        assert_eq!(consensus.reads, 3);
+        let mut support = vec![4; amplicon.len()];
+        support[10] = 3;
+        assert_eq!(consensus.support, support);
+        let alleles = graph.alleles();
+        assert_eq!(alleles.len(), 2);
+        assert_eq!(alleles[0], consensus);
+        assert_eq!(alleles[1].seq, substitution);
+        assert_eq!(alleles[1].reads, 1);
+        assert_eq!(alleles[1].support[10], 1);
+
+        // the deletion
+        let (structure, graph) = graphs[1];
+        assert_eq!(
+            *structure,
+            JumpStructure {
+                start_contig_idx: 0,
+                jumps: vec![Jump {
+                    from_contig_idx: 0,
+                    from_pos: 33,
+                    to_contig_idx: 0,
+                    to_pos: 53,
+                }],
+            }
+        );
+        assert_eq!(
+            structure.describe(&target_seqs),
+            "amplicon:33:+>amplicon:54:+"
+        );
+        let consensus = graph.consensus();
+        assert_eq!(consensus.seq, deletion);
+        assert_eq!(consensus.reads, 2);
+        assert_eq!(consensus.support, vec![2; deletion.len()]);
+
+        let mut tsv: Vec<u8> = Vec::new();
+        msa.write_tsv(&mut tsv, &target_seqs, 2).unwrap();
+        let tsv = String::from_utf8(tsv).unwrap();
+        let lines = tsv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[0],
+            "structure\tstructure_reads\tallele\treads\tfraction\tsequence\tsupport"
+        );
+        assert!(lines[1].starts_with(&format!("amplicon+\t4\tconsensus\t3\t0.7500\t{AMPLICON}\t")));
+        assert!(lines[2].starts_with(&format!("amplicon+\t4\t1\t3\t0.7500\t{AMPLICON}\t")));
+        assert!(lines[3].starts_with("amplicon:33:+>amplicon:54:+\t2\tconsensus\t2\t1.0000\t"));
+        assert!(lines[4].ends_with(&vec!["2"; deletion.len()].join(",")));
+    }
+}
@@ -0,0 +1,115 @@
+//! Positions in the query and the contigs, and indexes of the contigs, as distinct types, so that
+//! one is not silently used for another, nor truncated when narrowed to the `u32` values stored
+//! in the traceback.
+
+use std::fmt::Display;
+
+use anyhow::{anyhow, Error, Result};
+
+/// Defines a newtype over a `usize`, with checked conversions to and from `u32`.
+macro_rules! position_type {
+    ($(#[$meta:meta])* $name:ident, $what:literal) => {
+        $(#[$meta])*
+        #[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(usize);
+
+        impl $name {
+            /// Creates the value from a `usize`.
+            pub const fn new(value: usize) -> Self {
+                Self(value)
+            }
+
+            /// Returns the value as a `usize`.
+            pub const fn get(self) -> usize {
+                self.0
+            }
+
+            /// Returns the value as a `u32`, or an error if it does not fit.
+            pub fn to_u32(self) -> Result<u32> {
+                u32::try_from(self.0).map_err(|_| {
+                    anyhow!(concat!("The ", $what, " {} does not fit in 32 bits"), self.0)
+                })
+            }
+        }
+
+        impl From<usize> for $name {
+            fn from(value: usize) -> Self {
+                Self(value)
+            }
+        }
+
+        impl From<u32> for $name {
+            fn from(value: u32) -> Self {
+                Self(value as usize)
+            }
+        }
+
+        impl From<$name> for usize {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl TryFrom<$name> for u32 {
+            type Error = Error;
+
+            fn try_from(value: $name) -> Result<Self> {
+                value.to_u32()
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+position_type!(
+    /// A 0-based offset in the query (y).
+    QueryPos,
+    "query position"
+);
+
+position_type!(
+    /// A 0-based offset in a contig (x), on the strand of the contig.
+    ContigPos,
+    "contig position"
+);
+
+position_type!(
+    /// The index of a contig (a strand of a target), following the convention of
+    /// [`crate::align::Builder::build_aligners`]: the forward strands of the targets, then their
+    /// reverse strands.
+    ContigIdx,
+    "contig index"
+);
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::{ContigIdx, ContigPos, QueryPos};
+
+    #[rstest]
+    fn test_conversions() {
+        let pos = ContigPos::from(42_u32);
+        assert_eq!(pos.get(), 42);
+        assert_eq!(usize::from(pos), 42);
+        assert_eq!(u32::try_from(pos).unwrap(), 42);
+        assert_eq!(ContigIdx::new(3).to_string(), "3");
+        assert!(QueryPos::new(7) < QueryPos::new(8));
+    }
+
+    #[rstest]
+    fn test_checked_narrowing() {
+        let pos = ContigPos::new(u32::MAX as usize + 1);
+        assert_eq!(
+            pos.to_u32().unwrap_err().to_string(),
+            "The contig position 4294967296 does not fit in 32 bits"
+        );
+        assert!(u32::try_from(ContigIdx::new(u32::MAX as usize)).is_ok());
+    }
+}
@@ -0,0 +1,330 @@
+//! Trimming (or masking) of the primers at the ends of amplicon reads before alignment, since the
+//! primer-derived bases match the reference by construction, and so otherwise bias the placement
+//! of jumps near the ends of the amplicons.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, ensure, Error, Result};
+use seq_io::fastq::OwnedRecord as FastqOwnedRecord;
+
+use crate::util::target_seq::TargetSeq;
+
+/// The tag of the records of a read with its trimmed (or masked) primers, as a comma-separated
+/// list of `<primer>:<start>-<end>`, with the 0-based half-open range of the primer in the read
+/// before trimming.
+pub const PRIMER_TAG: &str = "pt";
+
+/// What to do with the primer bases found in a read.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum PrimerMode {
+    /// Remove the primer bases, and any bases outside them.
+    #[default]
+    Trim,
+    /// Replace the primer bases with `N`, so that they align without score, keeping the read
+    /// length and coordinates.
+    Mask,
+}
+
+impl Display for PrimerMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Trim => write!(f, "trim"),
+            Self::Mask => write!(f, "mask"),
+        }
+    }
+}
+
+impl FromStr for PrimerMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trim" => Ok(PrimerMode::Trim),
+            "mask" => Ok(PrimerMode::Mask),
+            _ => Err(anyhow!("Invalid primer mode: {}", s)),
+        }
+    }
+}
+
+/// A primer found in a read.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PrimerHit {
+    /// The index of the primer.
+    pub primer_idx: usize,
+    /// The 0-based half-open range of the primer in the read.
+    pub start: usize,
+    pub end: usize,
+    /// The number of mismatches to the primer.
+    pub mismatches: usize,
+}
+
+/// Finds the primers at the ends of reads, and trims or masks them.
+///
+/// A read of an amplicon starts with one of its primers and ends with the reverse complement of
+/// the other, in either orientation, so any primer is searched for within the first
+/// `search_window` bases of the read, and the reverse complement of any primer within the last
+/// `search_window` bases, as an ungapped match with at most `max_mismatches` mismatches.  The best
+/// hit at each end is that with the fewest mismatches, then the longest primer, then the closest
+/// to the end of the read.
+#[derive(Debug, Clone)]
+pub struct PrimerTrimmer {
+    primers: Vec<TargetSeq>,
+    mode: PrimerMode,
+    max_mismatches: usize,
+    search_window: usize,
+}
+
+impl PrimerTrimmer {
+    /// Creates the trimmer of the given primers, which must not be empty.
+    pub fn new(
+        primers: Vec<TargetSeq>,
+        mode: PrimerMode,
+        max_mismatches: usize,
+        search_window: usize,
+    ) -> Result<Self> {
+        ensure!(!primers.is_empty(), "No primers given");
+        let primers = primers
+            .into_iter()
+            .map(|primer| {
+                let seq = primer.fwd.to_ascii_uppercase();
+                TargetSeq::new(&primer.name, &seq, false)
+            })
+            .collect::<Vec<_>>();
+        for primer in &primers {
+            ensure!(
+                primer.len() > max_mismatches,
+                "Primer {} must be longer than the maximum number of mismatches",
+                primer.name
+            );
+        }
+        Ok(Self {
+            primers,
+            mode,
+            max_mismatches,
+            search_window,
+        })
+    }
+
+    pub fn primers(&self) -> &[TargetSeq] {
+        &self.primers
+    }
+
+    /// Returns the number of mismatches of `bases` to `primer` if at most the maximum.
+    fn mismatches(&self, bases: &[u8], primer: &[u8]) -> Option<usize> {
+        let mut mismatches = 0;
+        for (base, expected) in bases.iter().zip(primer) {
+            if !base.eq_ignore_ascii_case(expected) {
+                mismatches += 1;
+                if mismatches > self.max_mismatches {
+                    return None;
+                }
+            }
+        }
+        Some(mismatches)
+    }
+
+    /// Returns the best hit of a primer (or of its reverse complement, if `at_start` is false)
+    /// within the search window at the start (or end) of the read bases.
+    fn find(&self, seq: &[u8], at_start: bool) -> Option<PrimerHit> {
+        let mut best: Option<PrimerHit> = None;
+        for (primer_idx, primer) in self.primers.iter().enumerate() {
+            let bases = if at_start {
+                &primer.fwd
+            } else {
+                &primer.revcomp
+            };
+            if bases.len() > seq.len() {
+                continue;
+            }
+            let window = self.search_window.clamp(bases.len(), seq.len());
+            let starts = if at_start {
+                0..=window - bases.len()
+            } else {
+                seq.len() - window..=seq.len() - bases.len()
+            };
+            for start in starts {
+                let Some(mismatches) = self.mismatches(&seq[start..start + bases.len()], bases)
+                else {
+                    continue;
+                };
+                let hit = PrimerHit {
+                    primer_idx,
+                    start,
+                    end: start + bases.len(),
+                    mismatches,
+                };
+                // Fewer mismatches, then a longer primer, then closer to the end of the read
+                let distance = |hit: &PrimerHit| {
+                    if at_start {
+                        hit.start
+                    } else {
+                        seq.len() - hit.end
+                    }
+                };
+                let key = |hit: &PrimerHit| {
+                    (
+                        hit.mismatches,
+                        std::cmp::Reverse(hit.end - hit.start),
+                        distance(hit),
+                    )
+                };
+                if best.as_ref().is_none_or(|best| key(&hit) < key(best)) {
+                    best = Some(hit);
+                }
+            }
+        }
+        best
+    }
+
+    /// Returns the primers found at the start and end of the read bases, where the hit at the end
+    /// must not overlap that at the start.
+    pub fn find_primers(&self, seq: &[u8]) -> (Option<PrimerHit>, Option<PrimerHit>) {
+        let start = self.find(seq, true);
+        let offset = start.as_ref().map_or(0, |hit| hit.end);
+        let end = self.find(&seq[offset..], false).map(|hit| PrimerHit {
+            start: hit.start + offset,
+            end: hit.end + offset,
+            ..hit
+        });
+        (start, end)
+    }
+
+    /// Trims (or masks) the primers found at the ends of the read, and adds the primers found to
+    /// the comment of its header (see [`PRIMER_TAG`]).  Returns the primers found.
+    pub fn trim(&self, record: &mut FastqOwnedRecord) -> Vec<PrimerHit> {
+        let (start, end) = self.find_primers(&record.seq);
+        let hits = start.iter().chain(&end).cloned().collect::<Vec<_>>();
+        if hits.is_empty() {
+            return hits;
+        }
+        match self.mode {
+            PrimerMode::Trim => {
+                let first = start.as_ref().map_or(0, |hit| hit.end);
+                let last = end.as_ref().map_or(record.seq.len(), |hit| hit.start);
+                record.seq = record.seq[first..last].to_vec();
+                record.qual = record.qual[first..last].to_vec();
+            }
+            PrimerMode::Mask => {
+                for hit in &hits {
+                    record.seq[hit.start..hit.end].fill(b'N');
+                }
+            }
+        }
+        let value = hits
+            .iter()
+            .map(|hit| {
+                let name = &self.primers[hit.primer_idx].name;
+                format!("{name}:{}-{}", hit.start, hit.end)
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        record
+            .head
+            .extend(format!("\t{PRIMER_TAG}:Z:{value}").bytes());
+        hits
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+    use seq_io::fastq::OwnedRecord as FastqOwnedRecord;
+
+    use crate::align::umi::comment_tag;
+    use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+    use super::{PrimerMode, PrimerTrimmer, PRIMER_TAG};
+
+    const FWD: &str = "ACGTTGCAAC";
+    const REV: &str = "GGATCCTTAG";
+    const INSERT: &str = "TTTTTCCCCCAAAAAGGGGG";
+
+    fn trimmer(mode: PrimerMode) -> PrimerTrimmer {
+        let primers = vec![
+            TargetSeq::new("fwd", &FWD.as_bytes().to_vec(), false),
+            TargetSeq::new("rev", &REV.as_bytes().to_vec(), false),
+        ];
+        PrimerTrimmer::new(primers, mode, 1, 15).unwrap()
+    }
+
+    fn record(seq: &[u8]) -> FastqOwnedRecord {
+        FastqOwnedRecord {
+            head: b"read1".to_vec(),
+            seq: seq.to_vec(),
+            qual: vec![b'I'; seq.len()],
+        }
+    }
+
+    /// The amplicon read with two extra bases before the forward primer and after the reverse
+    /// primer.
+    fn amplicon() -> Vec<u8> {
+        [
+            b"TT".as_slice(),
+            FWD.as_bytes(),
+            INSERT.as_bytes(),
+            &reverse_complement(REV.as_bytes()),
+            b"CC",
+        ]
+        .concat()
+    }
+
+    #[rstest]
+    fn test_trim() {
+        let trimmer = trimmer(PrimerMode::Trim);
+        let mut read = record(&amplicon());
+        let hits = trimmer.trim(&mut read);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(read.seq, INSERT.as_bytes());
+        assert_eq!(read.qual.len(), INSERT.len());
+        assert_eq!(
+            comment_tag(&read.head, PRIMER_TAG),
+            Some(b"fwd:2-12,rev:32-42".as_slice())
+        );
+
+        // the opposite strand of the amplicon, with a mismatch in the reverse primer
+        let mut seq = reverse_complement(amplicon());
+        seq[4] = b'C';
+        let mut read = record(&seq);
+        trimmer.trim(&mut read);
+        assert_eq!(read.seq, reverse_complement(INSERT.as_bytes()));
+        assert_eq!(
+            comment_tag(&read.head, PRIMER_TAG),
+            Some(b"rev:2-12,fwd:32-42".as_slice())
+        );
+
+        // no primers
+        let mut read = record(INSERT.as_bytes());
+        assert!(trimmer.trim(&mut read).is_empty());
+        assert_eq!(read.seq, INSERT.as_bytes());
+        assert_eq!(read.head, b"read1");
+    }
+
+    #[rstest]
+    fn test_mask() {
+        let trimmer = trimmer(PrimerMode::Mask);
+        let mut read = record(&amplicon());
+        trimmer.trim(&mut read);
+        let expected = [
+            b"TT".as_slice(),
+            &[b'N'; 10],
+            INSERT.as_bytes(),
+            &[b'N'; 10],
+            b"CC",
+        ]
+        .concat();
+        assert_eq!(read.seq, expected);
+        assert_eq!(
+            comment_tag(&read.head, PRIMER_TAG),
+            Some(b"fwd:2-12,rev:32-42".as_slice())
+        );
+    }
+
+    #[rstest]
+    fn test_primer_mode() {
+        assert_eq!("Mask".parse::<PrimerMode>().unwrap(), PrimerMode::Mask);
+        assert_eq!(PrimerMode::Trim.to_string(), "trim");
+        assert!("clip".parse::<PrimerMode>().is_err());
+    }
+}
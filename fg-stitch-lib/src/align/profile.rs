@@ -0,0 +1,212 @@
+use crate::{align::aligners::constants::MIN_PENALTY, util::dna::reverse_complement};
+use anyhow::{ensure, Result};
+use std::ops::Index;
+
+/// The bases scored at each position of a [`ScoreProfile`], in order.
+const PROFILE_BASES: [u8; 4] = [b'A', b'C', b'G', b'T'];
+
+/// Returns the index of the given base in [`PROFILE_BASES`], or `None` if not a (case-insensitive)
+/// A, C, G, or T.
+fn base_index(base: u8) -> Option<usize> {
+    match base.to_ascii_uppercase() {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        _ => None,
+    }
+}
+
+/// A position-specific scoring matrix for a contig, giving the score for aligning each of A, C, G,
+/// and T to each position of the contig, in place of the match function of the scoring.  This
+/// allows aligning to a contig that is variable at some positions, for example a vector region
+/// with known variants, without penalizing the variants.
+///
+/// Each position also has a consensus base, namely the best scoring base, which is reported as the
+/// base of the contig at that position: a query base equal to the consensus base is a match, and
+/// any other query base is a substitution, regardless of their scores.  Query bases other than A,
+/// C, G, or T are given the lowest score at the position.
+#[derive(Default, Debug, PartialEq, Eq, Clone)]
+pub struct ScoreProfile {
+    scores: Vec<[i32; 4]>,
+    consensus: Vec<u8>,
+}
+
+impl ScoreProfile {
+    /// Creates a new profile from the scores for A, C, G, and T (in that order) at each position.
+    /// The consensus base at each position is the best scoring base, preferring the first on ties.
+    pub fn new(scores: Vec<[i32; 4]>) -> Self {
+        assert!(
+            scores.iter().flatten().all(|score| *score >= MIN_PENALTY),
+            "Profile scores can't be less than {MIN_PENALTY}"
+        );
+        let consensus = scores
+            .iter()
+            .map(|position| {
+                let best = position.iter().copied().max().unwrap();
+                PROFILE_BASES[position.iter().position(|s| *s == best).unwrap()]
+            })
+            .collect();
+        Self { scores, consensus }
+    }
+
+    /// Creates a new profile from the given equal-length sequences, for example the rows of a
+    /// multiple alignment of the variants of a contig.  The score for a base at a position is the
+    /// mismatch score plus the difference between the match and mismatch scores scaled by the
+    /// fraction (rounded) of sequences with that base at that position, so a base observed in
+    /// every sequence gets the match score, and a base never observed gets the mismatch score.
+    /// Characters other than A, C, G, or T (e.g. gaps or `N`s) do not count towards any base, and
+    /// the consensus base at a position where no sequence has such a base is `N`.
+    pub fn from_sequences(seqs: &[&[u8]], match_score: i32, mismatch_score: i32) -> Result<Self> {
+        ensure!(
+            !seqs.is_empty(),
+            "No sequences given to build a score profile"
+        );
+        ensure!(
+            mismatch_score >= MIN_PENALTY,
+            "The mismatch score ({mismatch_score}) can't be less than {MIN_PENALTY}"
+        );
+        ensure!(
+            mismatch_score <= match_score,
+            "The mismatch score ({mismatch_score}) must be at most the match score ({match_score})"
+        );
+        let len = seqs[0].len();
+        ensure!(
+            seqs.iter().all(|seq| seq.len() == len),
+            "The sequences to build a score profile must all have the same length"
+        );
+        let mut scores = Vec::with_capacity(len);
+        let mut consensus = Vec::with_capacity(len);
+        for i in 0..len {
+            let mut counts = [0i32; 4];
+            for seq in seqs {
+                if let Some(b) = base_index(seq[i]) {
+                    counts[b] += 1;
+                }
+            }
+            let total: i32 = counts.iter().sum();
+            let position = counts.map(|count| {
+                if total == 0 {
+                    mismatch_score
+                } else {
+                    mismatch_score + ((match_score - mismatch_score) * count + total / 2) / total
+                }
+            });
+            let best = counts.iter().copied().max().unwrap();
+            consensus.push(if total == 0 {
+                b'N'
+            } else {
+                PROFILE_BASES[counts.iter().position(|c| *c == best).unwrap()]
+            });
+            scores.push(position);
+        }
+        Ok(Self { scores, consensus })
+    }
+
+    /// The number of positions in the profile.
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// The consensus base at each position.
+    pub fn consensus(&self) -> &[u8] {
+        &self.consensus
+    }
+
+    /// The score for aligning the given query base to the given (zero-based) position.
+    pub fn score(&self, pos: usize, base: u8) -> i32 {
+        let position = &self.scores[pos];
+        match base_index(base) {
+            Some(b) => position[b],
+            None => position.iter().copied().min().unwrap(),
+        }
+    }
+
+    /// The highest score for aligning any base to any position, or zero if the profile is empty.
+    pub fn max_score(&self) -> i32 {
+        self.scores.iter().flatten().copied().max().unwrap_or(0)
+    }
+
+    /// Returns the profile for the reverse complement of the contig.
+    pub fn reverse_complement(&self) -> Self {
+        let scores = self
+            .scores
+            .iter()
+            .rev()
+            .map(|position| [position[3], position[2], position[1], position[0]])
+            .collect();
+        let consensus = reverse_complement(&self.consensus);
+        Self { scores, consensus }
+    }
+}
+
+impl Index<usize> for ScoreProfile {
+    type Output = u8;
+
+    /// The consensus base at the given position.
+    fn index(&self, index: usize) -> &u8 {
+        &self.consensus[index]
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::ScoreProfile;
+    use rstest::rstest;
+
+    #[rstest]
+    fn test_new() {
+        let profile = ScoreProfile::new(vec![[1, -4, -4, -4], [-4, 1, 1, -4], [-4, -4, -4, 0]]);
+        assert_eq!(profile.len(), 3);
+        assert_eq!(profile.consensus(), b"ACT");
+        assert_eq!(profile[1], b'C');
+        assert_eq!(profile.score(1, b'G'), 1);
+        assert_eq!(profile.score(1, b't'), -4);
+        assert_eq!(profile.score(2, b'N'), -4);
+    }
+
+    #[rstest]
+    fn test_from_sequences() {
+        let seqs: [&[u8]; 4] = [b"ACGT-N", b"ACGA-N", b"aCTA-N", b"ACTAAN"];
+        let profile = ScoreProfile::from_sequences(&seqs, 2, -4).unwrap();
+        assert_eq!(profile.consensus(), b"ACGAAN");
+        assert_eq!(profile.score(0, b'A'), 2);
+        assert_eq!(profile.score(0, b'C'), -4);
+        // half of the sequences have a G, and half a T
+        assert_eq!(profile.score(2, b'G'), -1);
+        assert_eq!(profile.score(2, b'T'), -1);
+        // 3/4 of the sequences have an A
+        assert_eq!(profile.score(3, b'A'), 1);
+        assert_eq!(profile.score(3, b'T'), -2);
+        // gaps are not counted
+        assert_eq!(profile.score(4, b'A'), 2);
+        assert_eq!(profile.score(5, b'A'), -4);
+    }
+
+    #[rstest]
+    #[case(&[], "No sequences")]
+    #[case(&[b"ACGT".as_slice(), b"ACG"], "same length")]
+    fn test_from_sequences_errors(#[case] seqs: &[&[u8]], #[case] message: &str) {
+        let result = ScoreProfile::from_sequences(seqs, 1, -4);
+        assert!(result.unwrap_err().to_string().contains(message));
+    }
+
+    #[rstest]
+    fn test_reverse_complement() {
+        let profile = ScoreProfile::new(vec![[1, -4, -4, -4], [-4, -4, 1, 0]]);
+        let revcomp = profile.reverse_complement();
+        assert_eq!(revcomp.consensus(), b"CT");
+        assert_eq!(revcomp.score(0, b'C'), 1);
+        assert_eq!(revcomp.score(0, b'A'), 0);
+        assert_eq!(revcomp.score(1, b'T'), 1);
+        assert_eq!(
+            revcomp,
+            ScoreProfile::new(vec![[0, 1, -4, -4], [-4, -4, -4, 1]])
+        );
+        assert_eq!(revcomp.reverse_complement(), profile);
+    }
+}
@@ -0,0 +1,338 @@
+//! Run-level quality control summaries of the alignments of many queries, such as the alignment
+//! rate, the distribution of identities and jumps, and the coverage of each target.
+
+use std::{collections::BTreeMap, io::Write};
+
+#[cfg(feature = "serde")]
+use anyhow::Context;
+use anyhow::Result;
+
+use super::{
+    aligners::constants::AlignmentOperation::{Match, Subst},
+    alignment::{target_seq_for, Alignment},
+};
+use crate::util::{run_info::RunInfo, target_seq::TargetSeq};
+
+/// The number of bins of the identity histogram, where bin `i` holds identities in
+/// `[i / 100, (i + 1) / 100)`, and the last bin holds identities of one.
+pub const IDENTITY_BINS: usize = 101;
+
+/// The coverage of a single target by the best alignments of the queries.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct TargetCoverage {
+    /// The name of the target.
+    pub name: String,
+    /// The length of the target.
+    pub length: usize,
+    /// The number of queries with at least one base aligned to the target.
+    pub reads: usize,
+    /// The number of query bases aligned (matches and mismatches) to the target.
+    pub aligned_bases: usize,
+    /// The mean depth across the target.
+    pub mean_depth: f64,
+    /// The fraction of the target covered by at least one aligned base.
+    pub breadth: f64,
+}
+
+/// A run-level quality control summary of the best alignment of each query, as built by
+/// [`QcCollector::report`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct QcReport {
+    /// The number of queries.
+    pub reads: usize,
+    /// The number of queries with at least one alignment.
+    pub aligned_reads: usize,
+    /// The fraction of queries with at least one alignment.
+    pub alignment_rate: f64,
+    /// The mean identity of the aligned queries (see [`crate::align::AlignmentStats::identity`]).
+    pub mean_identity: f64,
+    /// The number of aligned queries in each identity bin (see [`IDENTITY_BINS`]).
+    pub identity_histogram: Vec<usize>,
+    /// The number of aligned queries with each number of jumps.
+    pub jump_histogram: BTreeMap<usize, usize>,
+    /// The number of aligned queries with at least one jump between strands.
+    pub strand_switch_reads: usize,
+    /// The fraction of aligned queries with at least one jump between strands.
+    pub strand_switch_rate: f64,
+    /// The coverage of each target, in the order of the targets.
+    pub targets: Vec<TargetCoverage>,
+    /// The manifest of the run that produced the alignments, if known.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub run_info: Option<RunInfo>,
+}
+
+impl QcReport {
+    /// Returns the report as pretty-printed JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Could not serialize the QC report")
+    }
+
+    /// Writes the report as human-readable text.
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writeln!(writer, "reads\t{}", self.reads)?;
+        writeln!(writer, "aligned_reads\t{}", self.aligned_reads)?;
+        writeln!(writer, "alignment_rate\t{:.6}", self.alignment_rate)?;
+        writeln!(writer, "mean_identity\t{:.6}", self.mean_identity)?;
+        writeln!(writer, "strand_switch_reads\t{}", self.strand_switch_reads)?;
+        writeln!(writer, "strand_switch_rate\t{:.6}", self.strand_switch_rate)?;
+
+        writeln!(writer, "\n# identity histogram (empty bins omitted)")?;
+        writeln!(writer, "identity\treads")?;
+        for (bin, count) in self.identity_histogram.iter().enumerate() {
+            if *count > 0 {
+                writeln!(writer, "{:.2}\t{count}", bin as f64 / 100.0)?;
+            }
+        }
+
+        writeln!(writer, "\n# jump histogram")?;
+        writeln!(writer, "jumps\treads")?;
+        for (jumps, count) in &self.jump_histogram {
+            writeln!(writer, "{jumps}\t{count}")?;
+        }
+
+        writeln!(writer, "\n# target coverage")?;
+        writeln!(
+            writer,
+            "target\tlength\treads\taligned_bases\tmean_depth\tbreadth"
+        )?;
+        for target in &self.targets {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{:.6}\t{:.6}",
+                target.name,
+                target.length,
+                target.reads,
+                target.aligned_bases,
+                target.mean_depth,
+                target.breadth
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the quality control summary of the best alignment of each query, one query at a
+/// time, holding the per-base depth of each target.
+#[derive(Debug, Clone)]
+pub struct QcCollector {
+    reads: usize,
+    aligned_reads: usize,
+    identity_sum: f64,
+    identity_histogram: Vec<usize>,
+    jump_histogram: BTreeMap<usize, usize>,
+    strand_switch_reads: usize,
+    target_reads: Vec<usize>,
+    depths: Vec<Vec<u32>>,
+}
+
+impl QcCollector {
+    /// Creates a collector for the alignments to the given targets.
+    pub fn new(target_seqs: &[TargetSeq]) -> Self {
+        Self {
+            reads: 0,
+            aligned_reads: 0,
+            identity_sum: 0.0,
+            identity_histogram: vec![0; IDENTITY_BINS],
+            jump_histogram: BTreeMap::new(),
+            strand_switch_reads: 0,
+            target_reads: vec![0; target_seqs.len()],
+            depths: target_seqs
+                .iter()
+                .map(|target_seq| vec![0; target_seq.len()])
+                .collect(),
+        }
+    }
+
+    /// Adds a query with the given (best) alignment, if aligned.
+    ///
+    /// Arguments:
+    /// - `alignment` - The best alignment of the query, if any.
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `y` - The query sequence that was aligned.
+    pub fn add(&mut self, alignment: Option<&Alignment>, target_seqs: &[TargetSeq], y: &[u8]) {
+        self.reads += 1;
+        let Some(alignment) = alignment else {
+            return;
+        };
+        self.aligned_reads += 1;
+
+        let stats = alignment.stats(target_seqs, y);
+        self.identity_sum += stats.identity;
+        let bin = ((stats.identity * 100.0) as usize).min(IDENTITY_BINS - 1);
+        self.identity_histogram[bin] += 1;
+        *self.jump_histogram.entry(stats.jumps).or_default() += 1;
+        if stats.strand_switches > 0 {
+            self.strand_switch_reads += 1;
+        }
+
+        // The depth is on the forward strand of each target
+        let mut is_on_target = vec![false; target_seqs.len()];
+        for step in alignment.iter_steps() {
+            if matches!(step.op, Match | Subst) {
+                let (target_seq, is_forward) = target_seq_for(target_seqs, step.contig_idx);
+                let target_idx = step.contig_idx % target_seqs.len();
+                let pos = if is_forward {
+                    step.x_pos
+                } else {
+                    target_seq.len() - 1 - step.x_pos
+                };
+                self.depths[target_idx][pos] += 1;
+                is_on_target[target_idx] = true;
+            }
+        }
+        for (reads, is_on_target) in self.target_reads.iter_mut().zip(is_on_target) {
+            *reads += usize::from(is_on_target);
+        }
+    }
+
+    /// Builds the report from the queries added so far.
+    pub fn report(&self, target_seqs: &[TargetSeq]) -> QcReport {
+        let fraction = |count: usize, total: usize| {
+            if total == 0 {
+                0.0
+            } else {
+                count as f64 / total as f64
+            }
+        };
+        let targets = target_seqs
+            .iter()
+            .zip(&self.depths)
+            .zip(&self.target_reads)
+            .map(|((target_seq, depths), reads)| {
+                let aligned_bases: usize = depths.iter().map(|depth| *depth as usize).sum();
+                let covered = depths.iter().filter(|depth| **depth > 0).count();
+                TargetCoverage {
+                    name: target_seq.name.clone(),
+                    length: depths.len(),
+                    reads: *reads,
+                    aligned_bases,
+                    mean_depth: fraction(aligned_bases, depths.len()),
+                    breadth: fraction(covered, depths.len()),
+                }
+            })
+            .collect();
+        QcReport {
+            reads: self.reads,
+            aligned_reads: self.aligned_reads,
+            alignment_rate: fraction(self.aligned_reads, self.reads),
+            mean_identity: if self.aligned_reads == 0 {
+                0.0
+            } else {
+                self.identity_sum / self.aligned_reads as f64
+            },
+            identity_histogram: self.identity_histogram.clone(),
+            jump_histogram: self.jump_histogram.clone(),
+            strand_switch_reads: self.strand_switch_reads,
+            strand_switch_rate: fraction(self.strand_switch_reads, self.aligned_reads),
+            targets,
+            run_info: None,
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{alignment::tests::align_double_strand, scoring::Scoring};
+    use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+    use super::QcCollector;
+
+    const CHR1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const CHR2: &str = "TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    fn target_seqs() -> Vec<TargetSeq> {
+        vec![
+            TargetSeq::new("chr1", &CHR1.as_bytes().to_vec(), false),
+            TargetSeq::new("chr2", &CHR2.as_bytes().to_vec(), false),
+        ]
+    }
+
+    #[rstest]
+    fn test_report() {
+        let target_seqs = target_seqs();
+        let mut collector = QcCollector::new(&target_seqs);
+
+        // chr1:0-20 then chr2:20-40, from the reverse strand
+        let chimera = reverse_complement([&CHR1.as_bytes()[..20], &CHR2.as_bytes()[20..]].concat());
+        let alignment = align_double_strand(&target_seqs, &chimera, scoring());
+        collector.add(Some(&alignment), &target_seqs, &chimera);
+        // all of chr1
+        let y = CHR1.as_bytes();
+        let alignment = align_double_strand(&target_seqs, y, scoring());
+        collector.add(Some(&alignment), &target_seqs, y);
+        // not aligned
+        collector.add(None, &target_seqs, b"");
+
+        let report = collector.report(&target_seqs);
+        assert_eq!(report.reads, 3);
+        assert_eq!(report.aligned_reads, 2);
+        assert!((report.alignment_rate - 2.0 / 3.0).abs() < 1e-9);
+        assert!((report.mean_identity - 1.0).abs() < 1e-9);
+        assert_eq!(report.identity_histogram[100], 2);
+        assert_eq!(
+            report.jump_histogram.into_iter().collect::<Vec<_>>(),
+            [(0, 1), (1, 1)]
+        );
+        assert_eq!(report.strand_switch_reads, 0);
+
+        let (chr1, chr2) = (&report.targets[0], &report.targets[1]);
+        assert_eq!((chr1.reads, chr1.aligned_bases), (2, 60));
+        assert!((chr1.mean_depth - 1.5).abs() < 1e-9);
+        assert!((chr1.breadth - 1.0).abs() < 1e-9);
+        assert_eq!((chr2.reads, chr2.aligned_bases), (1, 20));
+        assert!((chr2.breadth - 0.5).abs() < 1e-9);
+    }
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    fn test_to_json() {
+        use crate::{align::config::AlignerConfig, util::run_info::RunInfo};
+
+        let target_seqs = target_seqs();
+        let mut collector = QcCollector::new(&target_seqs);
+        collector.add(None, &target_seqs, b"");
+        let mut report = collector.report(&target_seqs);
+        let json = report.to_json().unwrap();
+        assert!(json.contains("\"reads\": 1,"));
+        assert!(json.contains("\"name\": \"chr2\","));
+        assert!(!json.contains("\"run_info\""));
+
+        report.run_info = Some(RunInfo::new(
+            "stitch align",
+            AlignerConfig::default(),
+            &target_seqs,
+        ));
+        let json = report.to_json().unwrap();
+        assert!(json.contains("\"command_line\": \"stitch align\","));
+        assert!(json.contains("\"md5\": "));
+    }
+
+    #[rstest]
+    fn test_write_text() {
+        let target_seqs = target_seqs();
+        let collector = QcCollector::new(&target_seqs);
+        let mut output = Vec::new();
+        collector
+            .report(&target_seqs)
+            .write_text(&mut output)
+            .unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert!(text.starts_with("reads\t0\naligned_reads\t0\n"));
+        assert!(text
+            .ends_with("chr1\t40\t0\t0\t0.000000\t0.000000\nchr2\t40\t0\t0\t0.000000\t0.000000\n"));
+    }
+}
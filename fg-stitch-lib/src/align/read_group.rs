@@ -0,0 +1,238 @@
+//! Read groups and samples of the reads, so that the reads of many inputs (e.g. one per sample)
+//! aligned in a single run may be told apart in the outputs.
+
+use std::collections::HashMap;
+
+use anyhow::{ensure, Result};
+use seq_io::fastq::OwnedRecord as FastqOwnedRecord;
+
+use super::umi::comment_tag;
+
+/// The SAM tag holding the ID of the read group of a record.
+pub const READ_GROUP_TAG: &str = "RG";
+
+/// A read group, as for the `@RG` header lines of a SAM file.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ReadGroup {
+    /// The ID of the read group, unique amongst the read groups of a run.
+    pub id: String,
+    /// The sample sequenced, if known.
+    pub sample: Option<String>,
+}
+
+impl ReadGroup {
+    pub fn new(id: &str, sample: Option<&str>) -> Self {
+        Self {
+            id: id.to_string(),
+            sample: sample.map(str::to_string),
+        }
+    }
+
+    /// Returns the read group of each of the given number of inputs, given the read group IDs and
+    /// the samples, or none if neither is given.  A single ID (or sample) applies to all the
+    /// inputs, otherwise there must be one per input.  Without IDs, the ID of each read group is
+    /// its sample.
+    pub fn for_inputs(num_inputs: usize, ids: &[String], samples: &[String]) -> Result<Vec<Self>> {
+        let per_input = |values: &[String], name: &str| -> Result<Vec<String>> {
+            match values.len() {
+                0 => Ok(Vec::new()),
+                1 => Ok(vec![values[0].clone(); num_inputs]),
+                len => {
+                    ensure!(
+                        len == num_inputs,
+                        "Expected one {name} or one per input ({num_inputs}), found {len}"
+                    );
+                    Ok(values.to_vec())
+                }
+            }
+        };
+        let ids = per_input(ids, "read group")?;
+        let samples = per_input(samples, "sample")?;
+        let read_groups = match (ids.is_empty(), samples.is_empty()) {
+            (true, true) => Vec::new(),
+            (false, true) => ids.iter().map(|id| Self::new(id, None)).collect(),
+            (true, false) => samples.iter().map(|s| Self::new(s, Some(s))).collect(),
+            (false, false) => ids
+                .iter()
+                .zip(&samples)
+                .map(|(id, sample)| Self::new(id, Some(sample)))
+                .collect(),
+        };
+        for (index, read_group) in read_groups.iter().enumerate() {
+            let other = read_groups[..index]
+                .iter()
+                .find(|other| other.id == read_group.id);
+            if let Some(other) = other {
+                ensure!(
+                    other == read_group,
+                    "Read group {} is given with different samples",
+                    read_group.id
+                );
+            }
+        }
+        Ok(read_groups)
+    }
+
+    /// Returns the distinct read groups, in the order in which they first occur.
+    pub fn distinct(read_groups: &[Self]) -> Vec<Self> {
+        let mut distinct: Vec<Self> = Vec::new();
+        for read_group in read_groups {
+            if !distinct.contains(read_group) {
+                distinct.push(read_group.clone());
+            }
+        }
+        distinct
+    }
+
+    /// Adds the ID of the read group to the comment of the header of the read (see
+    /// [`READ_GROUP_TAG`]), from which it is copied to the records of the read.
+    pub fn tag(&self, record: &mut FastqOwnedRecord) {
+        record
+            .head
+            .extend(format!("\t{READ_GROUP_TAG}:Z:{}", self.id).bytes());
+    }
+
+    /// Returns the ID of the read group of a read, from the comment of its header (see
+    /// [`Self::tag`]), if any.
+    pub fn id_of(head: &[u8]) -> Option<&[u8]> {
+        comment_tag(head, READ_GROUP_TAG)
+    }
+}
+
+/// The samples of the read groups of a run, for reporting the reads of each sample separately
+/// (e.g. one column per sample).  A read group without a sample is its own sample, named by its
+/// ID, and read groups with the same sample are reported together.
+#[derive(Debug, Default, Clone)]
+pub struct SampleIndex {
+    /// The names of the samples, in the order in which they first occur.
+    samples: Vec<String>,
+    /// The index of the sample of each read group, by read group ID.
+    by_read_group: HashMap<String, usize>,
+}
+
+impl SampleIndex {
+    pub fn new(read_groups: &[ReadGroup]) -> Self {
+        let mut index = Self::default();
+        for read_group in read_groups {
+            let sample = read_group.sample.as_ref().unwrap_or(&read_group.id);
+            let sample_idx = match index.samples.iter().position(|s| s == sample) {
+                Some(sample_idx) => sample_idx,
+                None => {
+                    index.samples.push(sample.clone());
+                    index.samples.len() - 1
+                }
+            };
+            index
+                .by_read_group
+                .insert(read_group.id.clone(), sample_idx);
+        }
+        index
+    }
+
+    /// Returns the names of the samples.
+    pub fn samples(&self) -> &[String] {
+        &self.samples
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Returns the index of the sample of a read, from the read group in the comment of its
+    /// header (see [`ReadGroup::tag`]), if any.
+    pub fn index_of(&self, head: &[u8]) -> Option<usize> {
+        let id = std::str::from_utf8(ReadGroup::id_of(head)?).ok()?;
+        self.by_read_group.get(id).copied()
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+    use seq_io::fastq::OwnedRecord as FastqOwnedRecord;
+
+    use super::{ReadGroup, SampleIndex};
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[rstest]
+    fn test_for_inputs() {
+        assert_eq!(ReadGroup::for_inputs(2, &[], &[]).unwrap(), []);
+        assert_eq!(
+            ReadGroup::for_inputs(2, &strings(&["rg1"]), &[]).unwrap(),
+            [ReadGroup::new("rg1", None), ReadGroup::new("rg1", None)]
+        );
+        assert_eq!(
+            ReadGroup::for_inputs(2, &strings(&["rg1", "rg2"]), &strings(&["s1"])).unwrap(),
+            [
+                ReadGroup::new("rg1", Some("s1")),
+                ReadGroup::new("rg2", Some("s1"))
+            ]
+        );
+        assert_eq!(
+            ReadGroup::for_inputs(2, &[], &strings(&["s1", "s2"])).unwrap(),
+            [
+                ReadGroup::new("s1", Some("s1")),
+                ReadGroup::new("s2", Some("s2"))
+            ]
+        );
+        // neither one nor one per input
+        let error = ReadGroup::for_inputs(3, &strings(&["rg1", "rg2"]), &[]).unwrap_err();
+        assert!(error.to_string().contains("one per input (3), found 2"));
+        // the same read group with different samples
+        let error =
+            ReadGroup::for_inputs(2, &strings(&["rg1"]), &strings(&["s1", "s2"])).unwrap_err();
+        assert!(error.to_string().contains("Read group rg1"));
+    }
+
+    #[rstest]
+    fn test_distinct() {
+        let read_groups = [
+            ReadGroup::new("rg1", None),
+            ReadGroup::new("rg2", None),
+            ReadGroup::new("rg1", None),
+        ];
+        assert_eq!(
+            ReadGroup::distinct(&read_groups),
+            [ReadGroup::new("rg1", None), ReadGroup::new("rg2", None)]
+        );
+    }
+
+    #[rstest]
+    fn test_tag() {
+        let mut record = FastqOwnedRecord {
+            head: b"r1 comment".to_vec(),
+            seq: b"ACGT".to_vec(),
+            qual: b"IIII".to_vec(),
+        };
+        assert_eq!(ReadGroup::id_of(&record.head), None);
+        ReadGroup::new("rg1", Some("s1")).tag(&mut record);
+        assert_eq!(record.head, b"r1 comment\tRG:Z:rg1");
+        assert_eq!(ReadGroup::id_of(&record.head), Some(&b"rg1"[..]));
+    }
+
+    #[rstest]
+    fn test_sample_index() {
+        let read_groups = [
+            ReadGroup::new("rg1", Some("s1")),
+            ReadGroup::new("rg2", None),
+            ReadGroup::new("rg3", Some("s1")),
+        ];
+        let index = SampleIndex::new(&read_groups);
+        assert_eq!(index.samples(), ["s1", "rg2"]);
+        assert_eq!(index.index_of(b"r1\tRG:Z:rg1"), Some(0));
+        assert_eq!(index.index_of(b"r1\tRG:Z:rg2"), Some(1));
+        assert_eq!(index.index_of(b"r1 comment\tRG:Z:rg3"), Some(0));
+        assert_eq!(index.index_of(b"r1\tRG:Z:rg4"), None);
+        assert_eq!(index.index_of(b"r1"), None);
+        assert!(SampleIndex::new(&[]).is_empty());
+    }
+}
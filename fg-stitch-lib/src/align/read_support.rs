@@ -0,0 +1,188 @@
+//! Reference-free support of chimeric junctions by other reads, by aligning a read to a set of
+//! other reads as contigs, for validating that a putative fusion is supported by multiple
+//! independent reads.
+//!
+//! A read supports a junction of the query when the query aligns to the read across the junction
+//! without a jump, such that the read has the same junction.  The putative junctions of the query
+//! are the jumps in its alignment to the reads.
+
+use bio::alignment::pairwise::MatchFunc;
+use bit_set::BitSet;
+
+use super::{
+    aligners::{constants::AlignmentOperation::Xjump, multi_contig_aligner::MultiContigAligner},
+    alignment::Alignment,
+    all_vs_all::PerContigAligner,
+    scoring::Scoring,
+};
+use crate::util::target_seq::TargetSeq;
+
+/// Aligns a query (read) to a set of reads as contigs, on both strands of the reads.  Reads are
+/// indexed by their order in the set, and should have unique names.
+pub struct ReadSetAligner<F: MatchFunc> {
+    num_reads: usize,
+    jump_aware: MultiContigAligner<F>,
+    per_read: PerContigAligner<F>,
+}
+
+impl<F: MatchFunc + Sync + Copy> ReadSetAligner<F> {
+    /// Creates an aligner to the given reads, typically with the clipping of the local mode, as
+    /// the reads overlap the query only in part.
+    pub fn new(reads: &[TargetSeq], scoring: Scoring<F>) -> Self {
+        let mut jump_aware = MultiContigAligner::new();
+        for read in reads {
+            jump_aware.add_contig(&read.name, true, &read.fwd, false, scoring);
+        }
+        for read in reads {
+            jump_aware.add_contig(&read.name, false, &read.revcomp, false, scoring);
+        }
+        Self {
+            num_reads: reads.len(),
+            jump_aware,
+            per_read: PerContigAligner::new(reads, true, scoring),
+        }
+    }
+
+    /// The number of reads in the set.
+    pub fn len(&self) -> usize {
+        self.num_reads
+    }
+
+    /// True if there are no reads in the set.
+    pub fn is_empty(&self) -> bool {
+        self.num_reads == 0
+    }
+
+    /// Returns the jump-aware alignment of the query to the reads, other than the read with the
+    /// index `exclude` (for example the query itself), or `None` if there are no other reads.
+    pub fn align(&mut self, query: &[u8], exclude: Option<usize>) -> Option<Alignment> {
+        let n = self.num_reads;
+        let indexes = (0..2 * n)
+            .filter(|contig_idx| Some(contig_idx % n) != exclude)
+            .collect::<BitSet<u32>>();
+        if indexes.is_empty() {
+            return None;
+        }
+        Some(self.jump_aware.custom_with_subset(query, Some(&indexes)))
+    }
+
+    /// Returns the indexes of the reads, other than the read with the index `exclude`, that
+    /// support the junction of the query before the query base at `query_pos`: the query aligns
+    /// to the read (on either strand, without jumps) across the junction, with at least
+    /// `min_flank` query bases aligned on each side of it.
+    pub fn supporting_reads(
+        &mut self,
+        query: &[u8],
+        query_pos: usize,
+        min_flank: usize,
+        exclude: Option<usize>,
+    ) -> Vec<usize> {
+        let n = self.num_reads;
+        let mut reads = self
+            .per_read
+            .align(query)
+            .iter()
+            .enumerate()
+            .filter(|(contig_idx, alignment)| {
+                Some(contig_idx % n) != exclude
+                    && alignment.score > 0
+                    && alignment.ystart + min_flank <= query_pos
+                    && query_pos + min_flank <= alignment.yend
+            })
+            .map(|(contig_idx, _)| contig_idx % n)
+            .collect::<Vec<_>>();
+        reads.sort_unstable();
+        reads.dedup();
+        reads
+    }
+
+    /// Returns the putative junctions of the query, as the query positions of the jumps in its
+    /// alignment to the reads other than the read with the index `exclude` (see [`Self::align`]),
+    /// in increasing order.
+    pub fn junctions(&mut self, query: &[u8], exclude: Option<usize>) -> Vec<usize> {
+        let Some(alignment) = self.align(query, exclude) else {
+            return Vec::new();
+        };
+        alignment
+            .iter_steps()
+            .filter(|step| matches!(step.op, Xjump(_, _)))
+            .map(|step| step.y_pos)
+            .collect()
+    }
+
+    /// Returns the putative junctions of the query (see [`Self::junctions`]) supported by none of
+    /// the reads other than the read with the index `exclude` (see [`Self::supporting_reads`]).
+    pub fn unsupported_junctions(
+        &mut self,
+        query: &[u8],
+        min_flank: usize,
+        exclude: Option<usize>,
+    ) -> Vec<usize> {
+        let junctions = self.junctions(query, exclude);
+        junctions
+            .into_iter()
+            .filter(|&pos| {
+                self.supporting_reads(query, pos, min_flank, exclude)
+                    .is_empty()
+            })
+            .collect()
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use super::ReadSetAligner;
+    use crate::{
+        align::Scoring,
+        test_util::{random_dna, Rng, SEED},
+        util::{dna::reverse_complement, target_seq::TargetSeq},
+    };
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-6, -2, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    #[rstest]
+    fn test_read_support() {
+        let mut rng = Rng::new(SEED);
+        let gene_a = random_dna(&mut rng, 300);
+        let gene_b = random_dna(&mut rng, 300);
+        // the query and a second read have the fusion of gene A to gene B, the other reads do not
+        let query = [&gene_a[100..200], &gene_b[100..200]].concat();
+        let fusion = [&gene_a[130..200], &gene_b[100..170]].concat();
+        let reads = [
+            TargetSeq::new("query", &query, false),
+            TargetSeq::new("fusion", &reverse_complement(&fusion), false),
+            TargetSeq::new("gene-a", &gene_a[50..250].to_vec(), false),
+            TargetSeq::new("gene-b", &gene_b[50..250].to_vec(), false),
+        ];
+        let mut aligner = ReadSetAligner::new(&reads, scoring());
+        assert_eq!(aligner.len(), 4);
+
+        // the fusion read supports the junction, on the opposite strand, but not a junction near
+        // its end
+        assert_eq!(aligner.supporting_reads(&query, 100, 20, Some(0)), [1]);
+        assert_eq!(
+            aligner.supporting_reads(&query, 100, 80, Some(0)),
+            [] as [usize; 0]
+        );
+        // while the reads of either gene support a position within the gene
+        assert_eq!(aligner.supporting_reads(&query, 50, 20, Some(0)), [1, 2]);
+        assert_eq!(aligner.supporting_reads(&query, 150, 20, Some(0)), [1, 3]);
+
+        // the junction is supported by the fusion read, but by neither gene
+        assert_eq!(aligner.junctions(&query, Some(0)), [100]);
+        assert_eq!(
+            aligner.unsupported_junctions(&query, 20, Some(0)),
+            [] as [usize; 0]
+        );
+        let mut aligner = ReadSetAligner::new(&[&reads[..1], &reads[2..]].concat(), scoring());
+        assert_eq!(aligner.unsupported_junctions(&query, 20, Some(0)), [100]);
+    }
+}
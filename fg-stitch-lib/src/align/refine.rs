@@ -0,0 +1,353 @@
+//! Refinement of the placement of the jumps of an alignment, by re-aligning a window of the query
+//! around each jump to the contigs on either side of it.  The window is aligned without a band,
+//! with affine gaps, and split at the query base where the two sides score best together, so that
+//! the breakpoint is placed at the best base even where the full alignment was banded, chunked, or
+//! scored gaps next to homopolymers differently.
+
+use bio::alignment::pairwise::MatchFunc;
+
+use super::{
+    aligners::constants::{
+        AlignmentOperation,
+        AlignmentOperation::{Del, Ins, Match, Subst, Xjump},
+        MIN_SCORE,
+    },
+    alignment::{contig_bases_for, Alignment, AlignmentStep},
+    scoring::Scoring,
+};
+use crate::util::target_seq::TargetSeq;
+
+/// The index of the layer of the window matrices for columns with both bases.
+const MATCH_LAYER: usize = 0;
+/// The index of the layer of the window matrices for bases only in the contig (`Ins`).
+const INS_LAYER: usize = 1;
+/// The index of the layer of the window matrices for bases only in the query (`Del`).
+const DEL_LAYER: usize = 2;
+
+/// Adds the delta to the score, unless the score is unreachable.
+fn add(score: i32, delta: i32) -> i32 {
+    if score == MIN_SCORE {
+        MIN_SCORE
+    } else {
+        score + delta
+    }
+}
+
+/// The matrices of the affine gap alignment of a query (rows) to a contig (columns), anchored at
+/// the start of both and free to end anywhere in the contig.
+struct WindowMatrices {
+    cols: usize,
+    /// The best score of each cell for each layer.
+    scores: [Vec<i32>; 3],
+}
+
+impl WindowMatrices {
+    /// Fills the matrices of the given query and contig.
+    fn fill<F: MatchFunc>(scoring: &Scoring<F>, query: &[u8], contig: &[u8]) -> Self {
+        let (rows, cols) = (query.len() + 1, contig.len() + 1);
+        let mut scores = [
+            vec![MIN_SCORE; rows * cols],
+            vec![MIN_SCORE; rows * cols],
+            vec![MIN_SCORE; rows * cols],
+        ];
+        let open = scoring.gap_open + scoring.gap_extend;
+        scores[MATCH_LAYER][0] = 0;
+        for i in 0..rows {
+            for j in 0..cols {
+                let cell = i * cols + j;
+                if i > 0 && j > 0 {
+                    let diagonal = cell - cols - 1;
+                    let best = scores.iter().map(|layer| layer[diagonal]).max().unwrap();
+                    let delta = scoring.match_fn.score(contig[j - 1], query[i - 1]);
+                    scores[MATCH_LAYER][cell] = add(best, delta);
+                }
+                if j > 0 {
+                    let left = cell - 1;
+                    scores[INS_LAYER][cell] = add(scores[MATCH_LAYER][left], open)
+                        .max(add(scores[INS_LAYER][left], scoring.gap_extend))
+                        .max(add(scores[DEL_LAYER][left], open));
+                }
+                if i > 0 {
+                    let up = cell - cols;
+                    scores[DEL_LAYER][cell] = add(scores[MATCH_LAYER][up], open)
+                        .max(add(scores[INS_LAYER][up], open))
+                        .max(add(scores[DEL_LAYER][up], scoring.gap_extend));
+                }
+            }
+        }
+        Self { cols, scores }
+    }
+
+    /// The best score of the query prefix of the given length, ending anywhere in the contig, with
+    /// the length of the contig prefix and the layer at which it ends (the first when tied).
+    fn best_in_row(&self, i: usize) -> (i32, usize, usize) {
+        let mut best = (MIN_SCORE, 0, MATCH_LAYER);
+        for j in 0..self.cols {
+            for (layer, scores) in self.scores.iter().enumerate() {
+                if scores[i * self.cols + j] > best.0 {
+                    best = (scores[i * self.cols + j], j, layer);
+                }
+            }
+        }
+        best
+    }
+
+    /// Traces back the operations from the given cell and layer to the start of the query and
+    /// contig, returned in order from the start.
+    fn traceback<F: MatchFunc>(
+        &self,
+        scoring: &Scoring<F>,
+        query: &[u8],
+        contig: &[u8],
+        (mut i, mut j, mut layer): (usize, usize, usize),
+    ) -> Vec<AlignmentOperation> {
+        let open = scoring.gap_open + scoring.gap_extend;
+        let mut operations = Vec::with_capacity(i + j);
+        while i > 0 || j > 0 {
+            let cell = i * self.cols + j;
+            let score = self.scores[layer][cell];
+            // The operation of the layer, the previous cell, and the delta from each layer of the
+            // previous cell
+            let (op, prev_cell, deltas) = match layer {
+                MATCH_LAYER => {
+                    let delta = scoring.match_fn.score(contig[j - 1], query[i - 1]);
+                    let op = if contig[j - 1].eq_ignore_ascii_case(&query[i - 1]) {
+                        Match
+                    } else {
+                        Subst
+                    };
+                    (op, cell - self.cols - 1, [delta; 3])
+                }
+                INS_LAYER => (Ins, cell - 1, [open, scoring.gap_extend, open]),
+                _ => (Del, cell - self.cols, [open, open, scoring.gap_extend]),
+            };
+            operations.push(op);
+            layer = (0..3)
+                .find(|&prev| {
+                    let prev_score = self.scores[prev][prev_cell];
+                    prev_score != MIN_SCORE && prev_score + deltas[prev] == score
+                })
+                .expect("No previous cell in the traceback of the window");
+            i -= op.length_on_y();
+            j -= op.length_on_x(j) as usize;
+        }
+        operations.reverse();
+        operations
+    }
+}
+
+impl Alignment {
+    /// Returns the alignment with each jump placed at the best query base within a window of the
+    /// given number of columns on either side of it.  The columns of the window are re-aligned to
+    /// the contigs before and after the jump, with the start and end of the window fixed, and kept
+    /// only if the re-scored alignment scores higher.  The window stops at other jumps and at
+    /// clips.
+    ///
+    /// Arguments:
+    /// - `window` - The number of columns on either side of each jump to re-align.
+    /// - `target_seqs` - The targets to which the query was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    /// - `y` - The query sequence that was aligned.
+    /// - `scoring` - The scoring with which to re-align the windows and re-score the alignment.
+    pub fn refine_jumps<F: MatchFunc>(
+        &self,
+        window: usize,
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+        scoring: &Scoring<F>,
+    ) -> Alignment {
+        let mut refined = self.clone();
+        let mut index = 0;
+        loop {
+            let steps: Vec<AlignmentStep> = refined.iter_steps().collect();
+            let Some(jump_index) =
+                (index..steps.len()).find(|&t| matches!(steps[t].op, Xjump(_, _)))
+            else {
+                break;
+            };
+            index = jump_index + 1;
+            if let Some((candidate, new_index)) = refine_jump(
+                &refined,
+                &steps,
+                jump_index,
+                window,
+                target_seqs,
+                y,
+                scoring,
+            ) {
+                if candidate.score > refined.score {
+                    refined = candidate;
+                    index = new_index;
+                }
+            }
+        }
+        refined
+    }
+}
+
+/// Re-aligns the window around the jump at the given step (see [`Alignment::refine_jumps`]),
+/// returning the re-aligned and re-scored alignment with the index of the operation at which the
+/// window ends, or `None` if the jump is across the origin of a circular contig.
+fn refine_jump<F: MatchFunc>(
+    alignment: &Alignment,
+    steps: &[AlignmentStep],
+    jump_index: usize,
+    window: usize,
+    target_seqs: &[TargetSeq],
+    y: &[u8],
+    scoring: &Scoring<F>,
+) -> Option<(Alignment, usize)> {
+    let jump = steps[jump_index];
+    let Xjump(to_contig_idx, to_x) = jump.op else {
+        panic!("Expected a jump: {:?}", jump.op);
+    };
+    let from = contig_bases_for(target_seqs, jump.contig_idx);
+    let to = contig_bases_for(target_seqs, to_contig_idx);
+    if to_contig_idx == jump.contig_idx && jump.x_pos == from.len() && to_x == 0 {
+        return None;
+    }
+
+    // The columns of the window, up to the other jumps and the clips
+    let is_column = |step: &&AlignmentStep| matches!(step.op, Match | Subst | Ins | Del);
+    let num_before = steps[..jump_index]
+        .iter()
+        .rev()
+        .take(window)
+        .take_while(is_column)
+        .count();
+    let after = &steps[jump_index + 1..];
+    let num_after = after.iter().take(window).take_while(is_column).count();
+    let start = jump_index - num_before;
+    let end = jump_index + 1 + num_after;
+    let (x_start, y_start) = match num_before {
+        0 => (jump.x_pos, jump.y_pos),
+        _ => (steps[start].x_pos, steps[start].y_pos),
+    };
+    let x_end = to_x
+        + after[..num_after]
+            .iter()
+            .map(|step| step.op.length_on_x(step.x_pos) as usize)
+            .sum::<usize>();
+    let y_end = jump.y_pos
+        + after[..num_after]
+            .iter()
+            .map(|step| step.op.length_on_y())
+            .sum::<usize>();
+
+    // Align the window from its start in the contig before the jump, and backwards from its end
+    // in the contig after the jump, with the jump free to move by up to the window
+    let query = &y[y_start..y_end];
+    let before_contig = &from[x_start..(jump.x_pos + window).min(from.len())];
+    let after_start = to_x.saturating_sub(window);
+    let after_contig = &to[after_start..x_end];
+    let reversed_query: Vec<u8> = query.iter().rev().copied().collect();
+    let reversed_after: Vec<u8> = after_contig.iter().rev().copied().collect();
+    let forward = WindowMatrices::fill(scoring, query, before_contig);
+    let backward = WindowMatrices::fill(scoring, &reversed_query, &reversed_after);
+
+    // Split the window at the query base where the sides score best, preferring the current one
+    let total = |split: usize| {
+        let (before, _, _) = forward.best_in_row(split);
+        let (after, _, _) = backward.best_in_row(query.len() - split);
+        add(before, after).max(MIN_SCORE)
+    };
+    let mut split = jump.y_pos - y_start;
+    let mut best = total(split);
+    for other in 0..=query.len() {
+        if total(other) > best {
+            best = total(other);
+            split = other;
+        }
+    }
+    let (_, before_len, before_layer) = forward.best_in_row(split);
+    let (_, after_len, after_layer) = backward.best_in_row(query.len() - split);
+    let mut operations = alignment.operations[..start].to_vec();
+    operations.extend(forward.traceback(
+        scoring,
+        query,
+        before_contig,
+        (split, before_len, before_layer),
+    ));
+    let new_to_x = after_start + after_contig.len() - after_len;
+    if to_contig_idx != jump.contig_idx || x_start + before_len != new_to_x {
+        operations.push(Xjump(to_contig_idx, new_to_x));
+    }
+    let mut after_operations = backward.traceback(
+        scoring,
+        &reversed_query,
+        &reversed_after,
+        (query.len() - split, after_len, after_layer),
+    );
+    after_operations.reverse();
+    operations.extend(after_operations);
+    let new_end = operations.len();
+    operations.extend_from_slice(&alignment.operations[end..]);
+
+    let mut refined = Alignment {
+        operations,
+        ..alignment.clone()
+    };
+    refined.length = refined
+        .operations
+        .iter()
+        .filter(|op| matches!(op, Match | Subst | Ins | Del))
+        .count();
+    refined.score = refined.rescore(target_seqs, y, scoring);
+    Some((refined, new_end))
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{
+        aligners::constants::AlignmentOperation::{Match, Subst, Xjump},
+        alignment::Alignment,
+        scoring::Scoring,
+    };
+    use crate::util::target_seq::TargetSeq;
+
+    #[rstest]
+    fn test_refine_jumps() {
+        let target_seqs = [
+            TargetSeq::new("chr1", &b"AAAAACCCCCGGGGG".to_vec(), false),
+            TargetSeq::new("chr2", &b"TTTTTGGGGGACGTA".to_vec(), false),
+        ];
+        let scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4));
+        // the query has the first ten bases of chr1 then the last five of chr2, but the jump is
+        // placed two bases early, with two mismatches
+        let y = b"AAAAACCCCCACGTA";
+        let alignment = Alignment {
+            xend: 15,
+            yend: 15,
+            xlen: 15,
+            ylen: 15,
+            end_contig_idx: 1,
+            operations: [
+                vec![Match; 8],
+                vec![Xjump(1, 8)],
+                vec![Subst; 2],
+                vec![Match; 5],
+            ]
+            .concat(),
+            length: 15,
+            ..Default::default()
+        };
+        let alignment = Alignment {
+            score: alignment.rescore(&target_seqs, y, &scoring),
+            ..alignment
+        };
+        let refined = alignment.refine_jumps(5, &target_seqs, y, &scoring);
+        assert_eq!(refined.cigar(), "10=1C0J5=");
+        assert_eq!(refined.score, 15 - 10);
+        assert_eq!(refined.length, 15);
+        refined.validate();
+
+        // within a window of one column, the jump moves by one base
+        let refined = alignment.refine_jumps(1, &target_seqs, y, &scoring);
+        assert_eq!(refined.cigar(), "9=1C0J1X5=");
+        assert_eq!(refined.score, 14 - 4 - 10);
+    }
+}
@@ -1,19 +1,46 @@
-use crate::align::aligners::constants::MIN_SCORE;
-use bio::alignment::pairwise::MatchFunc;
-use serde::Serialize;
+use crate::align::{aligners::constants::MIN_SCORE, splice};
+use anyhow::{anyhow, ensure, Error, Result};
+use bio::alignment::pairwise::{MatchFunc, MatchParams};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
 
 /// Details of scoring are encapsulated in this structure.
 ///
 /// An [affine gap score model](https://en.wikipedia.org/wiki/Gap_penalty#Affine)
 /// is used so that the gap score for a length `k` is:
 /// `GapScore(k) = gap_open + gap_extend * k
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize)]
+///
+/// The score for a jump within the same contig and strand may depend on its direction: a jump
+/// forward (skipping ahead in the contig) is deletion-like, while a jump backward (re-aligning
+/// bases already aligned) implies a tandem duplication.  When `jump_score_forward` or
+/// `jump_score_backward` is `None`, jumps in that direction are scored with
+/// `jump_score_same_contig_and_strand`.
+///
+/// Since the errors of long reads (e.g. ONT or PacBio) are enriched for insertions and
+/// deletions in homopolymers, a gap may be opened with the `homopolymer_gap_open` score instead
+/// of `gap_open` next to (or inside) homopolymer runs of at least `homopolymer_min_length` bases
+/// in the contig.
+///
+/// To align RNA to a genome, a jump forward within the same contig and strand over an intron,
+/// from a splice donor to a splice acceptor (see [`crate::align::splice`]), may be scored with
+/// the (usually cheaper) `splice_jump_score` instead of the forward jump score.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
 pub struct Scoring<F: MatchFunc> {
     pub gap_open: i32,
     pub gap_extend: i32,
     pub jump_score_same_contig_and_strand: i32,
     pub jump_score_same_contig_opposite_strand: i32,
     pub jump_score_inter_contig: i32,
+    #[serde(default)]
+    pub jump_score_forward: Option<i32>,
+    #[serde(default)]
+    pub jump_score_backward: Option<i32>,
+    #[serde(default)]
+    pub splice_jump_score: Option<i32>,
+    #[serde(default)]
+    pub homopolymer_gap_open: Option<i32>,
+    #[serde(default = "default_homopolymer_min_length")]
+    pub homopolymer_min_length: usize,
     pub match_fn: F,
     pub match_scores: Option<(i32, i32)>,
     pub xclip_prefix: i32,
@@ -32,7 +59,7 @@ impl<F: MatchFunc> Scoring<F> {
     /// * `gap_extend` - the score for extending a gap (should not be positive)
     /// * `jump_score` - the score for jumping in the query (should not be positive)
     /// * `match_fn` - function that returns the score for substitutions
-    ///    (see also [`bio::alignment::pairwise::Scoring`](struct.Scoring.html))
+    ///   (see also [`bio::alignment::pairwise::Scoring`](struct.Scoring.html))
     pub fn with_jump_score(gap_open: i32, gap_extend: i32, jump_score: i32, match_fn: F) -> Self {
         assert!(gap_open <= 0, "gap_open can't be positive");
         assert!(gap_extend <= 0, "gap_extend can't be positive");
@@ -54,7 +81,7 @@ impl<F: MatchFunc> Scoring<F> {
     /// * `jump_score_same_contig_opposite_strand` - the score for jumping to the same contig and opposite strand in the query (should not be positive)
     /// * `jump_score_inter_contig` - the score for jumping to a different contig in the query (should not be positive)
     /// * `match_fn` - function that returns the score for substitutions
-    ///    (see also [`bio::alignment::pairwise::Scoring`](struct.Scoring.html))
+    ///   (see also [`bio::alignment::pairwise::Scoring`](struct.Scoring.html))
     pub fn with_jump_scores(
         gap_open: i32,
         gap_extend: i32,
@@ -82,6 +109,11 @@ impl<F: MatchFunc> Scoring<F> {
             jump_score_same_contig_and_strand,
             jump_score_same_contig_opposite_strand,
             jump_score_inter_contig,
+            jump_score_forward: None,
+            jump_score_backward: None,
+            splice_jump_score: None,
+            homopolymer_gap_open: None,
+            homopolymer_min_length: DEFAULT_HOMOPOLYMER_MIN_LENGTH,
             match_fn,
             match_scores: None,
             xclip_prefix: MIN_SCORE,
@@ -91,7 +123,31 @@ impl<F: MatchFunc> Scoring<F> {
         }
     }
 
-    /// Sets the jump scores to the given value
+    /// Returns the scoring with the given function for the scores of substitutions in place of
+    /// this scoring's function, for example a [`crate::align::MatrixMatchFunc`] with a
+    /// non-uniform substitution model.
+    pub fn set_match_fn<G: MatchFunc>(self, match_fn: G) -> Scoring<G> {
+        Scoring {
+            gap_open: self.gap_open,
+            gap_extend: self.gap_extend,
+            jump_score_same_contig_and_strand: self.jump_score_same_contig_and_strand,
+            jump_score_same_contig_opposite_strand: self.jump_score_same_contig_opposite_strand,
+            jump_score_inter_contig: self.jump_score_inter_contig,
+            jump_score_forward: self.jump_score_forward,
+            jump_score_backward: self.jump_score_backward,
+            splice_jump_score: self.splice_jump_score,
+            homopolymer_gap_open: self.homopolymer_gap_open,
+            homopolymer_min_length: self.homopolymer_min_length,
+            match_fn,
+            match_scores: None,
+            xclip_prefix: self.xclip_prefix,
+            xclip_suffix: self.xclip_suffix,
+            yclip_prefix: self.yclip_prefix,
+            yclip_suffix: self.yclip_suffix,
+        }
+    }
+
+    /// Sets the jump scores to the given value, in either direction
     ///
     /// # Arguments
     ///
@@ -101,10 +157,12 @@ impl<F: MatchFunc> Scoring<F> {
         self.jump_score_same_contig_and_strand = jump_score;
         self.jump_score_same_contig_opposite_strand = jump_score;
         self.jump_score_inter_contig = jump_score;
+        self.jump_score_forward = None;
+        self.jump_score_backward = None;
         self
     }
 
-    /// Sets the jump scores to the given values
+    /// Sets the jump scores to the given values, in either direction
     #[allow(dead_code)]
     pub fn set_jump_scores(
         mut self,
@@ -115,9 +173,119 @@ impl<F: MatchFunc> Scoring<F> {
         self.jump_score_same_contig_and_strand = jump_score_same_contig_and_strand;
         self.jump_score_same_contig_opposite_strand = jump_score_same_contig_opposite_strand;
         self.jump_score_inter_contig = jump_score_inter_contig;
+        self.jump_score_forward = None;
+        self.jump_score_backward = None;
+        self
+    }
+
+    /// Sets the scores for jumps forward and backward within the same contig and strand, with
+    /// `None` to use `jump_score_same_contig_and_strand`.
+    ///
+    /// # Arguments
+    ///
+    /// * `forward` - the score for jumping ahead in the contig (should not be positive)
+    /// * `backward` - the score for jumping back in the contig (should not be positive)
+    pub fn set_jump_score_directions(
+        mut self,
+        forward: Option<i32>,
+        backward: Option<i32>,
+    ) -> Self {
+        assert!(
+            forward.is_none_or(|score| score <= 0),
+            "jump_score_forward can't be positive"
+        );
+        assert!(
+            backward.is_none_or(|score| score <= 0),
+            "jump_score_backward can't be positive"
+        );
+        self.jump_score_forward = forward;
+        self.jump_score_backward = backward;
+        self
+    }
+
+    /// Sets the score for a jump forward within the same contig and strand over an intron, from a
+    /// splice donor to a splice acceptor, with `None` to score such jumps as any other forward
+    /// jump.
+    ///
+    /// # Arguments
+    ///
+    /// * `splice_jump_score` - the score for jumping over an intron (should not be positive)
+    pub fn set_splice_jump_score(mut self, splice_jump_score: Option<i32>) -> Self {
+        assert!(
+            splice_jump_score.is_none_or(|score| score <= 0),
+            "splice_jump_score can't be positive"
+        );
+        self.splice_jump_score = splice_jump_score;
+        self
+    }
+
+    /// Sets the score for opening a gap next to (or inside) homopolymer runs in the contig of at
+    /// least the given length, with `None` to use `gap_open` everywhere.
+    ///
+    /// # Arguments
+    ///
+    /// * `gap_open` - the score for opening a gap in a homopolymer (should not be positive)
+    /// * `min_length` - the minimum length of a homopolymer run
+    pub fn set_homopolymer_gap_open(mut self, gap_open: Option<i32>, min_length: usize) -> Self {
+        assert!(
+            gap_open.is_none_or(|score| score <= 0),
+            "homopolymer_gap_open can't be positive"
+        );
+        self.homopolymer_gap_open = gap_open;
+        self.homopolymer_min_length = min_length;
         self
     }
 
+    /// The score for opening a gap next to a homopolymer run of the given length in the contig.
+    pub fn gap_open_for_run_length(&self, run_length: usize) -> i32 {
+        match self.homopolymer_gap_open {
+            Some(gap_open) if run_length >= self.homopolymer_min_length => gap_open,
+            _ => self.gap_open,
+        }
+    }
+
+    /// The score for a jump forward (skipping ahead) within the same contig and strand.
+    pub fn forward_jump_score(&self) -> i32 {
+        self.jump_score_forward
+            .unwrap_or(self.jump_score_same_contig_and_strand)
+    }
+
+    /// The score for a jump backward (duplication-like) within the same contig and strand.
+    pub fn backward_jump_score(&self) -> i32 {
+        self.jump_score_backward
+            .unwrap_or(self.jump_score_same_contig_and_strand)
+    }
+
+    /// True if jumps within the same contig and strand are scored differently forward and
+    /// backward, or forward over an intron.
+    pub fn has_directional_jump_scores(&self) -> bool {
+        self.forward_jump_score() != self.backward_jump_score() || self.splice_jump_score.is_some()
+    }
+
+    /// The score for a jump within the same contig and strand, from after the base at 0-based
+    /// index `from - 1` to before the base at index `to`.  A jump with `to < from` is backward.
+    pub fn same_strand_jump_score(&self, from: usize, to: usize) -> i32 {
+        if to < from {
+            self.backward_jump_score()
+        } else {
+            self.forward_jump_score()
+        }
+    }
+
+    /// The score for a jump within the same contig and strand of `x`, as
+    /// [`Self::same_strand_jump_score`], but with the splice jump score for a jump forward over
+    /// an intron with splice motifs (see [`splice::is_spliced_jump`]) if higher.  Annotated
+    /// introns are not known here, so are scored as any other forward jump.
+    pub fn same_strand_jump_score_in(&self, x: &[u8], from: usize, to: usize) -> i32 {
+        let jump_score = self.same_strand_jump_score(from, to);
+        match self.splice_jump_score {
+            Some(splice_jump_score) if splice::is_spliced_jump(x, from, to) => {
+                jump_score.max(splice_jump_score)
+            }
+            _ => jump_score,
+        }
+    }
+
     /// Sets the prefix and suffix clipping penalties for x to the input value
     ///
     /// # Arguments
@@ -247,3 +415,501 @@ impl<F: MatchFunc> Scoring<F> {
         self
     }
 }
+
+impl<F: MatchFunc + Clone> Scoring<F> {
+    /// Creates a scoring from its per-contig and global parts.
+    pub fn from_parts(contig: ContigScoring<F>, global: GlobalScoring) -> Self {
+        Self {
+            gap_open: contig.gap_open,
+            gap_extend: contig.gap_extend,
+            jump_score_same_contig_and_strand: global.jump_score_same_contig_and_strand,
+            jump_score_same_contig_opposite_strand: global.jump_score_same_contig_opposite_strand,
+            jump_score_inter_contig: global.jump_score_inter_contig,
+            jump_score_forward: global.jump_score_forward,
+            jump_score_backward: global.jump_score_backward,
+            splice_jump_score: global.splice_jump_score,
+            homopolymer_gap_open: contig.homopolymer_gap_open,
+            homopolymer_min_length: contig.homopolymer_min_length,
+            match_fn: contig.match_fn,
+            match_scores: contig.match_scores,
+            xclip_prefix: global.xclip_prefix,
+            xclip_suffix: global.xclip_suffix,
+            yclip_prefix: global.yclip_prefix,
+            yclip_suffix: global.yclip_suffix,
+        }
+    }
+
+    /// Returns the part of the scoring that may differ between contigs.
+    pub fn contig_scoring(&self) -> ContigScoring<F> {
+        ContigScoring {
+            gap_open: self.gap_open,
+            gap_extend: self.gap_extend,
+            homopolymer_gap_open: self.homopolymer_gap_open,
+            homopolymer_min_length: self.homopolymer_min_length,
+            match_fn: self.match_fn.clone(),
+            match_scores: self.match_scores,
+        }
+    }
+}
+
+impl<F: MatchFunc> Scoring<F> {
+    /// Returns the part of the scoring that must be the same for all contigs.
+    pub fn global_scoring(&self) -> GlobalScoring {
+        GlobalScoring {
+            jump_score_same_contig_and_strand: self.jump_score_same_contig_and_strand,
+            jump_score_same_contig_opposite_strand: self.jump_score_same_contig_opposite_strand,
+            jump_score_inter_contig: self.jump_score_inter_contig,
+            jump_score_forward: self.jump_score_forward,
+            jump_score_backward: self.jump_score_backward,
+            splice_jump_score: self.splice_jump_score,
+            xclip_prefix: self.xclip_prefix,
+            xclip_suffix: self.xclip_suffix,
+            yclip_prefix: self.yclip_prefix,
+            yclip_suffix: self.yclip_suffix,
+        }
+    }
+
+    /// Checks that the scoring is not pathological, returning an error describing the first
+    /// problem found otherwise, since the aligner would silently return nonsensical alignments:
+    /// - a match must score positively, otherwise the empty alignment is optimal;
+    /// - gap and clipping scores must not be positive, otherwise gaps would be opened without
+    ///   bound, and clipping everything would be optimal;
+    /// - a jump must cost at least as much as a match scores, otherwise jumping to align each
+    ///   single matching base would improve the score, and alignments would jump at every base.
+    pub fn validate(&self) -> Result<()> {
+        let match_score = b"ACGT"
+            .iter()
+            .map(|base| self.match_fn.score(*base, *base))
+            .max()
+            .unwrap_or_default();
+        ensure!(
+            match_score > 0,
+            "The match score must be positive, found {match_score}: the empty alignment would be \
+             optimal"
+        );
+        let gap_scores = [
+            ("gap_open", Some(self.gap_open)),
+            ("gap_extend", Some(self.gap_extend)),
+            ("homopolymer_gap_open", self.homopolymer_gap_open),
+        ];
+        for (name, score) in gap_scores {
+            if let Some(score) = score {
+                ensure!(
+                    score <= 0,
+                    "The {name} score must not be positive, found {score}: gaps would be \
+                     opened without bound"
+                );
+            }
+        }
+        let clip_scores = [
+            ("xclip_prefix", self.xclip_prefix),
+            ("xclip_suffix", self.xclip_suffix),
+            ("yclip_prefix", self.yclip_prefix),
+            ("yclip_suffix", self.yclip_suffix),
+        ];
+        for (name, score) in clip_scores {
+            ensure!(
+                score <= 0,
+                "The {name} score must not be positive, found {score}: clipping everything \
+                 would be optimal"
+            );
+        }
+        let jump_scores = [
+            (
+                "jump_score_same_contig_and_strand",
+                Some(self.jump_score_same_contig_and_strand),
+            ),
+            (
+                "jump_score_same_contig_opposite_strand",
+                Some(self.jump_score_same_contig_opposite_strand),
+            ),
+            (
+                "jump_score_inter_contig",
+                Some(self.jump_score_inter_contig),
+            ),
+            ("jump_score_forward", self.jump_score_forward),
+            ("jump_score_backward", self.jump_score_backward),
+            ("splice_jump_score", self.splice_jump_score),
+        ];
+        for (name, score) in jump_scores {
+            if let Some(score) = score {
+                ensure!(
+                    score + match_score <= 0,
+                    "The {name} score must be at most minus the match score ({}), found \
+                     {score}: alignments would jump to align each matching base",
+                    -match_score
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The part of a [`Scoring`] that may differ between the contigs of a
+/// [`crate::align::MultiContigAligner`], namely the match function and the gap scores, which
+/// only score the alignment within a contig.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub struct ContigScoring<F: MatchFunc> {
+    pub gap_open: i32,
+    pub gap_extend: i32,
+    #[serde(default)]
+    pub homopolymer_gap_open: Option<i32>,
+    #[serde(default = "default_homopolymer_min_length")]
+    pub homopolymer_min_length: usize,
+    pub match_fn: F,
+    pub match_scores: Option<(i32, i32)>,
+}
+
+impl<F: MatchFunc> ContigScoring<F> {
+    /// Creates a new per-contig scoring with the given gap open and extend scores, and the score
+    /// function, without a separate homopolymer gap open score.
+    ///
+    /// # Arguments
+    ///
+    /// * `gap_open` - the score for opening a gap (should not be positive)
+    /// * `gap_extend` - the score for extending a gap (should not be positive)
+    /// * `match_fn` - function that returns the score for substitutions
+    pub fn new(gap_open: i32, gap_extend: i32, match_fn: F) -> Self {
+        assert!(gap_open <= 0, "gap_open can't be positive");
+        assert!(gap_extend <= 0, "gap_extend can't be positive");
+        Self {
+            gap_open,
+            gap_extend,
+            homopolymer_gap_open: None,
+            homopolymer_min_length: DEFAULT_HOMOPOLYMER_MIN_LENGTH,
+            match_fn,
+            match_scores: None,
+        }
+    }
+}
+
+/// The part of a [`Scoring`] that must be the same for all the contigs of a
+/// [`crate::align::MultiContigAligner`], namely the jump scores and the clipping penalties,
+/// since the aligner compares the scores of jumps and clips across contigs.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub struct GlobalScoring {
+    pub jump_score_same_contig_and_strand: i32,
+    pub jump_score_same_contig_opposite_strand: i32,
+    pub jump_score_inter_contig: i32,
+    #[serde(default)]
+    pub jump_score_forward: Option<i32>,
+    #[serde(default)]
+    pub jump_score_backward: Option<i32>,
+    #[serde(default)]
+    pub splice_jump_score: Option<i32>,
+    pub xclip_prefix: i32,
+    pub xclip_suffix: i32,
+    pub yclip_prefix: i32,
+    pub yclip_suffix: i32,
+}
+
+impl GlobalScoring {
+    /// Creates a new global scoring with the same score for all jumps, and the clip penalties
+    /// set to [`MIN_SCORE`] (i.e. no clipping).
+    ///
+    /// # Arguments
+    ///
+    /// * `jump_score` - the score for jumping in the query (should not be positive)
+    pub fn with_jump_score(jump_score: i32) -> Self {
+        assert!(jump_score <= 0, "jump_score can't be positive");
+        Self {
+            jump_score_same_contig_and_strand: jump_score,
+            jump_score_same_contig_opposite_strand: jump_score,
+            jump_score_inter_contig: jump_score,
+            jump_score_forward: None,
+            jump_score_backward: None,
+            splice_jump_score: None,
+            xclip_prefix: MIN_SCORE,
+            xclip_suffix: MIN_SCORE,
+            yclip_prefix: MIN_SCORE,
+            yclip_suffix: MIN_SCORE,
+        }
+    }
+
+    /// Returns an error naming each score that differs from the other global scoring, if any.
+    pub fn ensure_compatible(&self, other: &GlobalScoring) -> Result<()> {
+        let fields: [(&str, Option<i32>, Option<i32>); 10] = [
+            (
+                "jump_score_same_contig_and_strand",
+                Some(self.jump_score_same_contig_and_strand),
+                Some(other.jump_score_same_contig_and_strand),
+            ),
+            (
+                "jump_score_same_contig_opposite_strand",
+                Some(self.jump_score_same_contig_opposite_strand),
+                Some(other.jump_score_same_contig_opposite_strand),
+            ),
+            (
+                "jump_score_inter_contig",
+                Some(self.jump_score_inter_contig),
+                Some(other.jump_score_inter_contig),
+            ),
+            (
+                "jump_score_forward",
+                self.jump_score_forward,
+                other.jump_score_forward,
+            ),
+            (
+                "jump_score_backward",
+                self.jump_score_backward,
+                other.jump_score_backward,
+            ),
+            (
+                "splice_jump_score",
+                self.splice_jump_score,
+                other.splice_jump_score,
+            ),
+            (
+                "xclip_prefix",
+                Some(self.xclip_prefix),
+                Some(other.xclip_prefix),
+            ),
+            (
+                "xclip_suffix",
+                Some(self.xclip_suffix),
+                Some(other.xclip_suffix),
+            ),
+            (
+                "yclip_prefix",
+                Some(self.yclip_prefix),
+                Some(other.yclip_prefix),
+            ),
+            (
+                "yclip_suffix",
+                Some(self.yclip_suffix),
+                Some(other.yclip_suffix),
+            ),
+        ];
+        let format = |score: Option<i32>| score.map_or("none".to_string(), |s| s.to_string());
+        let differences = fields
+            .iter()
+            .filter(|(_, left, right)| left != right)
+            .map(|(name, left, right)| format!("{name} ({} vs {})", format(*left), format(*right)))
+            .collect::<Vec<_>>();
+        ensure!(
+            differences.is_empty(),
+            "Incompatible global scoring: {}",
+            differences.join(", ")
+        );
+        Ok(())
+    }
+}
+
+/// The default minimum length of a homopolymer run in which gaps are opened with the
+/// homopolymer gap open score.
+pub const DEFAULT_HOMOPOLYMER_MIN_LENGTH: usize = 3;
+
+fn default_homopolymer_min_length() -> usize {
+    DEFAULT_HOMOPOLYMER_MIN_LENGTH
+}
+
+/// Named scoring presets tuned for common sequencing platforms and assays, as starting points
+/// for new users.  The scores are similar to those of other aligners for the same platform, with
+/// the jump score scaled to the match score.
+impl Scoring<MatchParams> {
+    /// Scoring for short, accurate reads whose errors are mostly substitutions (e.g. Illumina).
+    pub fn preset_illumina() -> Self {
+        Self::with_jump_score(-6, -1, -10, MatchParams::new(1, -4))
+    }
+
+    /// Scoring for long, noisy reads whose errors are mostly small insertions and deletions (e.g.
+    /// Oxford Nanopore).  Gaps are cheap, while jumps are expensive, to avoid spurious jumps to
+    /// explain clusters of errors.
+    pub fn preset_ont() -> Self {
+        Self::with_jump_score(-4, -2, -30, MatchParams::new(2, -4))
+    }
+
+    /// Scoring for long, accurate reads (e.g. PacBio HiFi).
+    pub fn preset_pacbio_hifi() -> Self {
+        Self::with_jump_score(-6, -2, -15, MatchParams::new(1, -4))
+    }
+
+    /// Scoring for accurate reads of short amplicons (e.g. of CRISPR edit sites), where small
+    /// insertions and deletions are expected, and larger rearrangements are explained by jumps.
+    pub fn preset_amplicon() -> Self {
+        Self::with_jump_score(-5, -1, -15, MatchParams::new(1, -3))
+    }
+
+    /// Scoring for RNA (e.g. cDNA) aligned to a genome, where the jumps forward over introns are
+    /// expected.  Jumps are expensive, except from a splice donor to a splice acceptor (see
+    /// [`crate::align::splice`]), so that the exons of a transcript are joined by jumps at their
+    /// splice sites, while the other jumps show fusions or other rearrangements.
+    pub fn preset_spliced() -> Self {
+        Self::with_jump_score(-6, -1, -30, MatchParams::new(1, -4)).set_splice_jump_score(Some(-8))
+    }
+}
+
+/// The named scoring presets (see [`Scoring::preset_illumina`] and friends).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ScoringPreset {
+    Illumina,
+    Ont,
+    PacbioHifi,
+    Amplicon,
+    Spliced,
+}
+
+impl ScoringPreset {
+    /// Returns the scoring of this preset.
+    pub fn scoring(&self) -> Scoring<MatchParams> {
+        match self {
+            Self::Illumina => Scoring::preset_illumina(),
+            Self::Ont => Scoring::preset_ont(),
+            Self::PacbioHifi => Scoring::preset_pacbio_hifi(),
+            Self::Amplicon => Scoring::preset_amplicon(),
+            Self::Spliced => Scoring::preset_spliced(),
+        }
+    }
+}
+
+impl Display for ScoringPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Illumina => write!(f, "illumina"),
+            Self::Ont => write!(f, "ont"),
+            Self::PacbioHifi => write!(f, "pacbio-hifi"),
+            Self::Amplicon => write!(f, "amplicon"),
+            Self::Spliced => write!(f, "spliced"),
+        }
+    }
+}
+
+impl FromStr for ScoringPreset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "illumina" => Ok(Self::Illumina),
+            "ont" | "nanopore" => Ok(Self::Ont),
+            "pacbio-hifi" | "pacbio_hifi" | "hifi" => Ok(Self::PacbioHifi),
+            "amplicon" => Ok(Self::Amplicon),
+            "spliced" | "splice" | "rna" => Ok(Self::Spliced),
+            _ => Err(anyhow!("Invalid scoring preset: {}", s)),
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use bio::alignment::pairwise::MatchParams;
+
+    use super::{ContigScoring, GlobalScoring, Scoring, ScoringPreset};
+
+    #[rstest]
+    #[case(ScoringPreset::Illumina)]
+    #[case(ScoringPreset::Ont)]
+    #[case(ScoringPreset::PacbioHifi)]
+    #[case(ScoringPreset::Amplicon)]
+    #[case(ScoringPreset::Spliced)]
+    fn test_scoring_preset(#[case] preset: ScoringPreset) {
+        assert_eq!(preset.to_string().parse::<ScoringPreset>().unwrap(), preset);
+        let scoring = preset.scoring();
+        assert!(scoring.match_fn.match_score > 0);
+        assert!(scoring.match_fn.mismatch_score < 0);
+        // a single mismatch is cheaper than a gap, and a gap cheaper than a jump
+        let gap = scoring.gap_open + scoring.gap_extend;
+        assert!(scoring.match_fn.mismatch_score >= gap);
+        assert!(gap > scoring.jump_score_same_contig_and_strand);
+        // a jump over an intron is cheaper than any other jump
+        if let Some(splice_jump_score) = scoring.splice_jump_score {
+            assert!(splice_jump_score > scoring.forward_jump_score());
+        }
+    }
+
+    #[rstest]
+    fn test_scoring_preset_from_str() {
+        assert_eq!(
+            "HiFi".parse::<ScoringPreset>().unwrap(),
+            ScoringPreset::PacbioHifi
+        );
+        assert!("sanger".parse::<ScoringPreset>().is_err());
+    }
+
+    #[rstest]
+    fn test_scoring_parts() {
+        let scoring = Scoring::with_jump_scores(-5, -1, -10, -12, -14, MatchParams::new(1, -4))
+            .set_jump_score_directions(Some(-8), None)
+            .set_xclip(0)
+            .set_yclip_prefix(-3);
+        let (contig, global) = (scoring.contig_scoring(), scoring.global_scoring());
+        assert_eq!(contig, ContigScoring::new(-5, -1, MatchParams::new(1, -4)));
+        assert_eq!(global.jump_score_forward, Some(-8));
+        assert_eq!(global.yclip_suffix, super::MIN_SCORE);
+        assert_eq!(Scoring::from_parts(contig, global), scoring);
+    }
+
+    #[rstest]
+    fn test_validate() {
+        let scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4));
+        assert!(scoring.validate().is_ok());
+        for preset in [
+            ScoringPreset::Illumina,
+            ScoringPreset::Ont,
+            ScoringPreset::PacbioHifi,
+            ScoringPreset::Amplicon,
+            ScoringPreset::Spliced,
+        ] {
+            assert!(preset.scoring().validate().is_ok());
+        }
+
+        let mut bad = scoring;
+        bad.match_fn = MatchParams::new(0, -4);
+        let message = bad.validate().unwrap_err().to_string();
+        assert_eq!(
+            message,
+            "The match score must be positive, found 0: the empty alignment would be optimal"
+        );
+
+        let mut bad = scoring;
+        bad.gap_extend = 1;
+        let message = bad.validate().unwrap_err().to_string();
+        assert!(message.starts_with("The gap_extend score must not be positive, found 1"));
+
+        let mut bad = scoring;
+        bad.yclip_suffix = 2;
+        let message = bad.validate().unwrap_err().to_string();
+        assert!(message.starts_with("The yclip_suffix score must not be positive, found 2"));
+
+        // a jump as cheap as a match is fine, but not any cheaper
+        assert!(scoring.set_jump_score(-1).validate().is_ok());
+        let bad = scoring.set_jump_score_directions(None, Some(0));
+        let message = bad.validate().unwrap_err().to_string();
+        assert_eq!(
+            message,
+            "The jump_score_backward score must be at most minus the match score (-1), found 0: \
+             alignments would jump to align each matching base"
+        );
+    }
+
+    #[rstest]
+    fn test_ensure_compatible() {
+        let global = GlobalScoring::with_jump_score(-10);
+        assert!(global.ensure_compatible(&global).is_ok());
+        let mut other = global;
+        other.jump_score_inter_contig = -20;
+        other.jump_score_backward = Some(-5);
+        let message = global.ensure_compatible(&other).unwrap_err().to_string();
+        assert_eq!(
+            message,
+            "Incompatible global scoring: jump_score_inter_contig (-10 vs -20), \
+             jump_score_backward (none vs -5)"
+        );
+    }
+
+    #[rstest]
+    fn test_same_strand_jump_score_in() {
+        let scoring = Scoring::with_jump_score(-5, -1, -30, MatchParams::new(1, -4));
+        // the intron GTACAG of x[4..10]
+        let x = b"AAAAGTACAGCCCC";
+        assert_eq!(scoring.same_strand_jump_score_in(x, 4, 10), -30);
+        let scoring = scoring.set_splice_jump_score(Some(-8));
+        assert!(scoring.has_directional_jump_scores());
+        assert_eq!(scoring.same_strand_jump_score_in(x, 4, 10), -8);
+        assert_eq!(scoring.same_strand_jump_score_in(x, 3, 10), -30);
+        assert_eq!(scoring.same_strand_jump_score_in(x, 10, 4), -30);
+    }
+}
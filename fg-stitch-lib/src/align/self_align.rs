@@ -0,0 +1,308 @@
+//! Alignment of a sequence to itself and to its reverse complement, with the trivial alignment of
+//! each base to itself masked, to find the internal duplications and inverted repeats of the
+//! sequence, for example to check a construct for repeats that may recombine or form hairpins.
+
+use std::ops::Range;
+
+use bio::alignment::pairwise::MatchFunc;
+
+use super::{
+    aligners::{
+        constants::AlignmentOperation::{Del, Ins, Match, Subst, Xclip, Xjump, Yclip, Yjump},
+        multi_contig_aligner::MultiContigAligner,
+    },
+    alignment::{Alignment, AlignmentStep},
+    scoring::Scoring,
+};
+use crate::util::dna::reverse_complement;
+
+/// The name of the contig that is the sequence aligned to itself.
+const SELF_CONTIG: &str = "self";
+
+/// The kinds of repeats within a sequence.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub enum RepeatKind {
+    /// Both copies are on the same strand, as for an internal (e.g. tandem) duplication.
+    Duplication,
+    /// The second copy is the reverse complement of the first, as for a hairpin.
+    InvertedRepeat,
+}
+
+impl std::fmt::Display for RepeatKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Duplication => write!(f, "duplication"),
+            Self::InvertedRepeat => write!(f, "inverted_repeat"),
+        }
+    }
+}
+
+/// Two copies of a repeat within a sequence, as the ranges `[start, end)` (0-based) on the forward
+/// strand of the sequence.  The copies may overlap, for example for a tandem repeat, or be the
+/// same for a palindrome.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Repeat {
+    pub kind: RepeatKind,
+    /// The copy that starts first (or ends first, if both start at the same position).
+    pub first: Range<usize>,
+    pub second: Range<usize>,
+    /// The number of alignment columns between the two copies.
+    pub length: usize,
+    /// The fraction of the alignment columns between the two copies that are matches.
+    pub identity: f64,
+}
+
+/// A segment of the alignment of a sequence to itself, between jumps.
+struct Segment {
+    is_forward: bool,
+    /// The range of the segment on the strand of the contig aligned to.
+    contig: Range<usize>,
+    query: Range<usize>,
+    matches: usize,
+    columns: usize,
+}
+
+impl Segment {
+    fn new(step: &AlignmentStep) -> Self {
+        Self {
+            // The forward strand is added first (see `SelfAligner::find_repeats`)
+            is_forward: step.contig_idx == 0,
+            contig: step.x_pos..step.x_pos,
+            query: step.y_pos..step.y_pos,
+            matches: 0,
+            columns: 0,
+        }
+    }
+
+    fn add(&mut self, step: &AlignmentStep) {
+        self.contig.end = step.x_pos + step.op.length_on_x(step.x_pos) as usize;
+        self.query.end = step.y_pos + step.op.length_on_y();
+        self.columns += 1;
+        if matches!(step.op, Match) {
+            self.matches += 1;
+        }
+    }
+
+    /// The repeat of a sequence of the given length between the query and contig ranges of the
+    /// segment.
+    fn to_repeat(&self, len: usize) -> Repeat {
+        let (kind, contig) = if self.is_forward {
+            (RepeatKind::Duplication, self.contig.clone())
+        } else {
+            let contig = len - self.contig.end..len - self.contig.start;
+            (RepeatKind::InvertedRepeat, contig)
+        };
+        let (first, second) = if (self.query.start, self.query.end) <= (contig.start, contig.end) {
+            (self.query.clone(), contig)
+        } else {
+            (contig, self.query.clone())
+        };
+        Repeat {
+            kind,
+            first,
+            second,
+            length: self.columns,
+            identity: self.matches as f64 / self.columns as f64,
+        }
+    }
+}
+
+/// Finds the repeats within a sequence by aligning it, as the query, to itself as a contig, on
+/// both strands, locally and with jumps.  Aligning each base to itself is masked on the forward
+/// strand (see [`MultiContigAligner::set_mask_diagonal`]), so that the alignment instead follows
+/// the repeats, with each segment of the alignment between jumps aligning one copy of a repeat to
+/// another.  The repeats are those of the best alignment ending on each strand, so a sequence with
+/// many repeats may have more than are reported.
+pub struct SelfAligner<F: MatchFunc> {
+    scoring: Scoring<F>,
+    min_len: usize,
+}
+
+impl<F: MatchFunc + Sync + Copy> SelfAligner<F> {
+    /// Creates an aligner with the match, mismatch, gap, and jump scores of `scoring`, reporting
+    /// the repeats whose copies have at least `min_len` bases.  The clipping penalties of
+    /// `scoring` are ignored, since the alignment is local.
+    pub fn new(scoring: Scoring<F>, min_len: usize) -> Self {
+        let scoring = scoring
+            .set_xclip_prefix(0)
+            .set_xclip_suffix(0)
+            .set_yclip_prefix(0)
+            .set_yclip_suffix(0);
+        Self { scoring, min_len }
+    }
+
+    /// Returns the repeats of at least the minimum length within the sequence, sorted by the
+    /// first copy then the second copy, or none if the sequence is empty.  A repeat whose copies
+    /// are within those of another repeat of the same kind is left out.
+    pub fn find_repeats(&self, seq: &[u8]) -> Vec<Repeat> {
+        if seq.is_empty() {
+            return Vec::new();
+        }
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig(SELF_CONTIG, true, seq, false, self.scoring);
+        aligner.add_contig(
+            SELF_CONTIG,
+            false,
+            &reverse_complement(seq),
+            false,
+            self.scoring,
+        );
+        aligner.set_mask_diagonal(0, true);
+        aligner.custom(seq);
+
+        let mut repeats: Vec<Repeat> = Vec::new();
+        for alignment in aligner.traceback_all(seq.len(), None) {
+            if alignment.score <= 0 {
+                continue;
+            }
+            for repeat in self.repeats_of(&alignment, seq.len()) {
+                // Each repeat may be found from either copy
+                if !repeats.contains(&repeat) {
+                    repeats.push(repeat);
+                }
+            }
+        }
+        // The best alignment ending on one strand may jump to it after aligning only part of a
+        // repeat on the other strand, whose full copies are found from the alignment ending there
+        let within = |outer: &Range<usize>, inner: &Range<usize>| {
+            outer.start <= inner.start && inner.end <= outer.end
+        };
+        let mut repeats = repeats
+            .iter()
+            .filter(|repeat| {
+                !repeats.iter().any(|other| {
+                    other != *repeat
+                        && other.kind == repeat.kind
+                        && within(&other.first, &repeat.first)
+                        && within(&other.second, &repeat.second)
+                })
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        repeats.sort_by_key(|repeat| {
+            (
+                repeat.first.start,
+                repeat.first.end,
+                repeat.second.start,
+                repeat.second.end,
+                repeat.kind,
+            )
+        });
+        repeats
+    }
+
+    /// The repeats given by the segments of the alignment of a sequence of the given length to
+    /// itself, whose copies have at least the minimum length.
+    fn repeats_of(&self, alignment: &Alignment, len: usize) -> Vec<Repeat> {
+        let mut segments = Vec::new();
+        let mut current: Option<Segment> = None;
+        for step in alignment.iter_steps() {
+            match step.op {
+                Match | Subst | Ins | Del => {
+                    current
+                        .get_or_insert_with(|| Segment::new(&step))
+                        .add(&step);
+                }
+                Xjump(_, _) | Yjump(_) => segments.extend(current.take()),
+                Xclip(_) | Yclip(_) => (),
+            }
+        }
+        segments.extend(current);
+        segments
+            .iter()
+            .filter(|segment| {
+                segment.contig.len() >= self.min_len && segment.query.len() >= self.min_len
+            })
+            .map(|segment| segment.to_repeat(len))
+            .collect()
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use super::{RepeatKind, SelfAligner};
+    use crate::{
+        align::Scoring,
+        test_util::{random_dna, Rng, SEED},
+        util::dna::reverse_complement,
+    };
+
+    fn aligner() -> SelfAligner<MatchParams> {
+        SelfAligner::new(
+            Scoring::with_jump_score(-6, -2, -10, MatchParams::new(1, -4)),
+            20,
+        )
+    }
+
+    /// True if the range is within a few bases of the expected range, as the copies of a repeat
+    /// may be extended by bases flanking both copies that match by chance
+    fn near(range: &std::ops::Range<usize>, expected: std::ops::Range<usize>) -> bool {
+        range.start <= expected.start
+            && expected.start - range.start <= 3
+            && range.end >= expected.end
+            && range.end - expected.end <= 3
+    }
+
+    #[rstest]
+    fn test_find_duplication() {
+        let mut rng = Rng::new(SEED);
+        let repeat = random_dna(&mut rng, 40);
+        let seq = [
+            random_dna(&mut rng, 50),
+            repeat.clone(),
+            random_dna(&mut rng, 60),
+            repeat,
+            random_dna(&mut rng, 50),
+        ]
+        .concat();
+
+        let repeats = aligner().find_repeats(&seq);
+        let duplication = repeats
+            .iter()
+            .find(|r| r.kind == RepeatKind::Duplication)
+            .unwrap();
+        assert!(near(&duplication.first, 50..90), "{duplication:?}");
+        assert!(near(&duplication.second, 150..190), "{duplication:?}");
+        assert!(duplication.length >= 40);
+        assert!(duplication.identity > 0.9);
+    }
+
+    #[rstest]
+    fn test_find_inverted_repeat() {
+        let mut rng = Rng::new(SEED);
+        let repeat = random_dna(&mut rng, 40);
+        let seq = [
+            random_dna(&mut rng, 50),
+            repeat.clone(),
+            random_dna(&mut rng, 60),
+            reverse_complement(&repeat),
+            random_dna(&mut rng, 50),
+        ]
+        .concat();
+
+        let repeats = aligner().find_repeats(&seq);
+        let inverted = repeats
+            .iter()
+            .find(|r| r.kind == RepeatKind::InvertedRepeat)
+            .unwrap();
+        assert!(near(&inverted.first, 50..90), "{inverted:?}");
+        assert!(near(&inverted.second, 150..190), "{inverted:?}");
+        assert!(inverted.identity > 0.9);
+        // the forward strand is not aligned to itself
+        assert!(repeats
+            .iter()
+            .all(|r| r.kind != RepeatKind::Duplication || r.first != r.second));
+    }
+
+    #[rstest]
+    fn test_find_no_repeats() {
+        let mut rng = Rng::new(SEED);
+        let seq = random_dna(&mut rng, 300);
+        assert_eq!(aligner().find_repeats(&seq), Vec::new());
+        assert_eq!(aligner().find_repeats(&[]), Vec::new());
+    }
+}
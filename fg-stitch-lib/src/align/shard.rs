@@ -0,0 +1,464 @@
+//! Sharded output of parallel runs.  Each aligner thread writes the encoded records of the chunks
+//! of reads it aligns to its own shard, so that the threads do not wait on a single output, and
+//! the shards are merged into the final output at the end of the run.
+//!
+//! The encoded chunks are copied verbatim when merging, so must be self-contained, for example
+//! complete BGZF blocks of BAM records, which may be concatenated after the BAM header.
+//!
+//! Each shard has a checkpoint file, to which the location and number of reads of each chunk are
+//! appended once the chunk is written to the shard, so that an interrupted run may be resumed
+//! from the chunks written by the run (see [`Checkpoint`]) without aligning their reads again.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, ensure, Context, Error, Result};
+
+/// The order in which the alignments of the reads are output.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum OutputOrder {
+    /// The order of the reads in the input.
+    #[default]
+    Input,
+    /// The order in which the chunks of reads finish aligning, which does not hold back the
+    /// output of later chunks while an earlier chunk has a slow read.
+    Completion,
+}
+
+impl Display for OutputOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Input => write!(f, "input"),
+            Self::Completion => write!(f, "completion"),
+        }
+    }
+}
+
+impl FromStr for OutputOrder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "input" => Ok(OutputOrder::Input),
+            "completion" => Ok(OutputOrder::Completion),
+            _ => Err(anyhow!("Invalid output order: {}", s)),
+        }
+    }
+}
+
+/// The location of an encoded chunk in a shard.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct ChunkExtent {
+    /// The ordinal of the chunk in the input.
+    ordinal: u64,
+    /// The byte offset of the chunk in the shard.
+    offset: u64,
+    /// The number of bytes of the chunk.
+    len: u64,
+    /// The number of reads in the chunk.
+    num_reads: usize,
+}
+
+impl ChunkExtent {
+    /// Parses the extent from a line of a checkpoint file (see [`ShardWriter::write_chunk`]).
+    fn parse(line: &str) -> Result<Self> {
+        let fields = line.split('\t').collect::<Vec<_>>();
+        ensure!(fields.len() == 4, "Invalid checkpoint: {line}");
+        Ok(Self {
+            ordinal: fields[0].parse()?,
+            offset: fields[1].parse()?,
+            len: fields[2].parse()?,
+            num_reads: fields[3].parse()?,
+        })
+    }
+}
+
+/// Returns the path of the shard with the given index in the given directory.
+fn shard_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("shard.{index}"))
+}
+
+/// Returns the path of the checkpoint of the shard with the given path.
+fn checkpoint_path(shard_path: &Path) -> PathBuf {
+    let mut path = shard_path.as_os_str().to_owned();
+    path.push(".checkpoint");
+    PathBuf::from(path)
+}
+
+/// Writes encoded chunks to a shard file, keeping the location of each chunk in the shard, and
+/// appending it to the checkpoint of the shard.
+#[derive(Debug)]
+pub struct ShardWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    checkpoint: File,
+    offset: u64,
+    extents: Vec<ChunkExtent>,
+}
+
+impl ShardWriter {
+    /// Creates the shard named `shard.<index>` in the given directory, and its checkpoint named
+    /// `shard.<index>.checkpoint`.
+    pub fn create(dir: &Path, index: usize) -> Result<Self> {
+        let path = shard_path(dir, index);
+        let file = File::create(&path)
+            .with_context(|| format!("Error creating shard: {}", path.display()))?;
+        let checkpoint_path = checkpoint_path(&path);
+        let checkpoint = File::create(&checkpoint_path)
+            .with_context(|| format!("Error creating checkpoint: {}", checkpoint_path.display()))?;
+        Ok(Self {
+            path,
+            writer: BufWriter::new(file),
+            checkpoint,
+            offset: 0,
+            extents: Vec::new(),
+        })
+    }
+
+    /// Writes the encoded chunk with the given ordinal in the input, of the given number of reads.
+    /// The chunks of a shard must be written in increasing order of their ordinals.
+    ///
+    /// Once the chunk is flushed to the shard, its ordinal, offset and length in the shard, and
+    /// number of reads are appended to the checkpoint as a tab-delimited line.
+    pub fn write_chunk(&mut self, ordinal: u64, num_reads: usize, bytes: &[u8]) -> Result<()> {
+        if let Some(last) = self.extents.last() {
+            assert!(
+                last.ordinal < ordinal,
+                "Chunks written out of order to shard {}: {} after {}",
+                self.path.display(),
+                ordinal,
+                last.ordinal
+            );
+        }
+        self.writer
+            .write_all(bytes)
+            .and_then(|_| self.writer.flush())
+            .with_context(|| format!("Error writing shard: {}", self.path.display()))?;
+        let extent = ChunkExtent {
+            ordinal,
+            offset: self.offset,
+            len: bytes.len() as u64,
+            num_reads,
+        };
+        let line = format!(
+            "{}\t{}\t{}\t{}\n",
+            extent.ordinal, extent.offset, extent.len, extent.num_reads
+        );
+        self.checkpoint
+            .write_all(line.as_bytes())
+            .with_context(|| format!("Error writing checkpoint of: {}", self.path.display()))?;
+        self.extents.push(extent);
+        self.offset += extent.len;
+        Ok(())
+    }
+
+    /// Flushes the shard, returning it to be merged.
+    pub fn finish(mut self) -> Result<Shard> {
+        self.writer
+            .flush()
+            .with_context(|| format!("Error writing shard: {}", self.path.display()))?;
+        Ok(Shard {
+            path: self.path,
+            extents: self.extents,
+        })
+    }
+}
+
+/// A shard written by a [`ShardWriter`], to be merged with [`merge_shards`].
+#[derive(Debug, Clone)]
+pub struct Shard {
+    path: PathBuf,
+    extents: Vec<ChunkExtent>,
+}
+
+impl Shard {
+    /// The number of chunks in the shard.
+    pub fn num_chunks(&self) -> usize {
+        self.extents.len()
+    }
+
+    /// Reads the shard with the given path from its checkpoint, truncating the shard after the
+    /// last chunk in the checkpoint, in case the run was interrupted while writing a chunk.  A
+    /// partly written line at the end of the checkpoint is ignored.
+    fn from_checkpoint(path: PathBuf) -> Result<Self> {
+        let checkpoint_path = checkpoint_path(&path);
+        let checkpoint = std::fs::read_to_string(&checkpoint_path)
+            .with_context(|| format!("Error reading checkpoint: {}", checkpoint_path.display()))?;
+        let complete = &checkpoint[..checkpoint.rfind('\n').map_or(0, |i| i + 1)];
+        let extents = complete
+            .lines()
+            .map(ChunkExtent::parse)
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Error reading checkpoint: {}", checkpoint_path.display()))?;
+        let len = extents.last().map_or(0, |e| e.offset + e.len);
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Error opening shard: {}", path.display()))?;
+        ensure!(
+            file.metadata()?.len() >= len,
+            "Shard shorter than its checkpoint: {}",
+            path.display()
+        );
+        file.set_len(len)?;
+        Ok(Self { path, extents })
+    }
+
+    /// Removes the shard and its checkpoint.
+    fn remove(&self) -> Result<()> {
+        std::fs::remove_file(&self.path)
+            .with_context(|| format!("Error removing shard: {}", self.path.display()))?;
+        let checkpoint_path = checkpoint_path(&self.path);
+        if checkpoint_path.exists() {
+            std::fs::remove_file(&checkpoint_path).with_context(|| {
+                format!("Error removing checkpoint: {}", checkpoint_path.display())
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// The chunks written to the shards in a directory by an interrupted run, from the checkpoints of
+/// the shards, so that the run may be resumed by aligning only the other chunks, with the shards
+/// of the resumed run merged with these.
+///
+/// The chunks of the resumed run must have the same ordinals as those of the interrupted run, so
+/// the inputs and the chunking of the reads must be the same.
+#[derive(Debug, Default, Clone)]
+pub struct Checkpoint {
+    shards: Vec<Shard>,
+    /// The number of reads of each chunk, by ordinal.
+    num_reads: HashMap<u64, usize>,
+    /// The index of the first shard after those of the interrupted run.
+    next_index: usize,
+}
+
+impl Checkpoint {
+    /// Reads the checkpoints of the shards (`shard.<index>`) in the given directory, if any.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut checkpoint = Self::default();
+        if !dir.exists() {
+            return Ok(checkpoint);
+        }
+        let mut indexes = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let index = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("shard."))
+                .and_then(|index| index.parse::<usize>().ok());
+            if let Some(index) = index {
+                indexes.push(index);
+            }
+        }
+        indexes.sort_unstable();
+        for index in indexes {
+            let path = shard_path(dir, index);
+            let shard = if checkpoint_path(&path).exists() {
+                Shard::from_checkpoint(path)?
+            } else {
+                // NB: a shard without a checkpoint has no complete chunks
+                Shard {
+                    path,
+                    extents: Vec::new(),
+                }
+            };
+            for extent in &shard.extents {
+                let previous = checkpoint
+                    .num_reads
+                    .insert(extent.ordinal, extent.num_reads);
+                ensure!(
+                    previous.is_none(),
+                    "Chunk {} is in many shards in: {}",
+                    extent.ordinal,
+                    dir.display()
+                );
+            }
+            checkpoint.next_index = index + 1;
+            checkpoint.shards.push(shard);
+        }
+        Ok(checkpoint)
+    }
+
+    /// The number of chunks written by the interrupted run.
+    pub fn num_chunks(&self) -> usize {
+        self.num_reads.len()
+    }
+
+    /// The number of reads in the chunks written by the interrupted run.
+    pub fn num_reads(&self) -> usize {
+        self.num_reads.values().sum()
+    }
+
+    /// Returns the number of reads of each chunk written by the interrupted run, by ordinal.
+    pub fn chunks(&self) -> &HashMap<u64, usize> {
+        &self.num_reads
+    }
+
+    /// The index of the first shard of the resumed run, after those of the interrupted run.
+    pub fn next_index(&self) -> usize {
+        self.next_index
+    }
+
+    /// Returns the shards of the interrupted run, to be merged with those of the resumed run.
+    pub fn into_shards(self) -> Vec<Shard> {
+        self.shards
+    }
+
+    /// Removes the shards of the interrupted run and their checkpoints, to start the run afresh.
+    pub fn remove(self) -> Result<()> {
+        for shard in &self.shards {
+            shard.remove()?;
+        }
+        Ok(())
+    }
+}
+
+/// Copies the chunks of the shards to the writer, either in the order of their ordinals in the
+/// input, or shard by shard, then removes the shard files and their checkpoints.  Every chunk is
+/// copied once, so the order of the output is only that of the input if every chunk ordinal is in
+/// exactly one shard.  Only the chunks of a shard are copied, and not any bytes of the shard after
+/// them, such as those of a shard of an interrupted run without a checkpoint.
+pub fn merge_shards<W: Write>(
+    shards: Vec<Shard>,
+    order: OutputOrder,
+    writer: &mut W,
+) -> Result<()> {
+    let mut files = shards
+        .iter()
+        .map(|shard| {
+            File::open(&shard.path)
+                .with_context(|| format!("Error opening shard: {}", shard.path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut chunks: Vec<(ChunkExtent, usize)> = shards
+        .iter()
+        .enumerate()
+        .flat_map(|(index, shard)| shard.extents.iter().map(move |e| (*e, index)))
+        .collect();
+    if order == OutputOrder::Input {
+        chunks.sort_unstable_by_key(|(extent, _)| extent.ordinal);
+    }
+    for (extent, index) in chunks {
+        let file = &mut files[index];
+        file.seek(SeekFrom::Start(extent.offset))?;
+        let copied = io::copy(&mut file.take(extent.len), writer)?;
+        anyhow::ensure!(
+            copied == extent.len,
+            "Shard truncated: {}",
+            shards[index].path.display()
+        );
+    }
+
+    drop(files);
+    for shard in &shards {
+        shard.remove()?;
+    }
+    Ok(())
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use std::io::Write;
+
+    use super::{checkpoint_path, merge_shards, Checkpoint, OutputOrder, ShardWriter};
+
+    #[rstest]
+    #[case::input(OutputOrder::Input, "0123456")]
+    #[case::completion(OutputOrder::Completion, "0256134")]
+    fn test_merge_shards(#[case] order: OutputOrder, #[case] expected: &str) {
+        let dir =
+            std::env::temp_dir().join(format!("stitch-shards-{order}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut first = ShardWriter::create(&dir, 0).unwrap();
+        let mut second = ShardWriter::create(&dir, 1).unwrap();
+        for ordinal in [0, 2, 5, 6] {
+            first
+                .write_chunk(ordinal, 1, ordinal.to_string().as_bytes())
+                .unwrap();
+        }
+        for ordinal in [1, 3, 4] {
+            second
+                .write_chunk(ordinal, 1, ordinal.to_string().as_bytes())
+                .unwrap();
+        }
+        let shards = vec![first.finish().unwrap(), second.finish().unwrap()];
+        assert_eq!(shards[0].num_chunks(), 4);
+
+        let mut output = Vec::new();
+        merge_shards(shards, order, &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[rstest]
+    #[case::input(OutputOrder::Input, "0123")]
+    #[case::completion(OutputOrder::Completion, "0213")]
+    fn test_checkpoint(#[case] order: OutputOrder, #[case] expected: &str) {
+        let dir =
+            std::env::temp_dir().join(format!("stitch-checkpoint-{order}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(Checkpoint::load(&dir).unwrap().num_chunks(), 0);
+
+        // an interrupted run, with a chunk and a line of the checkpoint partly written
+        let mut first = ShardWriter::create(&dir, 0).unwrap();
+        let mut second = ShardWriter::create(&dir, 1).unwrap();
+        first.write_chunk(0, 10, b"0").unwrap();
+        first.write_chunk(2, 10, b"2").unwrap();
+        second.write_chunk(1, 10, b"1").unwrap();
+        first.writer.write_all(b"partial").unwrap();
+        first.writer.flush().unwrap();
+        first.checkpoint.write_all(b"3\t2").unwrap();
+        drop((first, second));
+        // a shard whose checkpoint was never created
+        std::fs::write(dir.join("shard.2"), b"partial").unwrap();
+
+        let checkpoint = Checkpoint::load(&dir).unwrap();
+        assert_eq!(checkpoint.num_chunks(), 3);
+        assert_eq!(checkpoint.num_reads(), 30);
+        assert_eq!(checkpoint.chunks().get(&2), Some(&10));
+        assert_eq!(checkpoint.chunks().get(&3), None);
+        assert_eq!(checkpoint.next_index(), 3);
+        assert_eq!(std::fs::read(dir.join("shard.0")).unwrap(), b"02");
+
+        // the resumed run
+        let mut third = ShardWriter::create(&dir, checkpoint.next_index()).unwrap();
+        third.write_chunk(3, 5, b"3").unwrap();
+        let mut shards = checkpoint.into_shards();
+        shards.push(third.finish().unwrap());
+        let mut output = Vec::new();
+        merge_shards(shards, order, &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        // starting afresh
+        let mut first = ShardWriter::create(&dir, 0).unwrap();
+        first.write_chunk(0, 10, b"0").unwrap();
+        drop(first);
+        assert!(checkpoint_path(&dir.join("shard.0")).exists());
+        Checkpoint::load(&dir).unwrap().remove().unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[rstest]
+    fn test_output_order_from_str() {
+        assert_eq!(
+            "Completion".parse::<OutputOrder>().unwrap(),
+            OutputOrder::Completion
+        );
+        assert!("sorted".parse::<OutputOrder>().is_err());
+    }
+}
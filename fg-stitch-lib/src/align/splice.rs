@@ -0,0 +1,318 @@
+//! The splice sites of the contigs, so that RNA aligned to a genome jumps over introns: a jump
+//! forward within the same contig and strand, from a splice donor (the start of an intron) to a
+//! splice acceptor (the end of an intron), may be scored with the splice jump score (see
+//! [`crate::align::Scoring::splice_jump_score`]).
+//!
+//! Splice sites are found from the canonical `GT`-`AG` motifs of the introns, or `CT`-`AC` for
+//! the introns of the genes on the opposite strand, and from the introns between the annotated
+//! exons of each transcript read from a GTF or GFF file.  Position `p` of a contig is the boundary
+//! between its bases `p - 1` and `p` (0-based), so an intron of bases `[start, end)` has its donor
+//! at position `start` and its acceptor at position `end`.
+
+use std::{collections::HashMap, ops::Range, path::Path};
+
+use anyhow::{ensure, Context, Result};
+
+/// The number of kinds of splice sites: `GT`-`AG` motifs, `CT`-`AC` motifs, and annotated.  A
+/// jump is spliced only from a donor to an acceptor of the same kind.
+pub const SPLICE_KINDS: usize = 3;
+
+/// The kind of splice sites found from the annotated introns.
+pub const ANNOTATED: usize = 2;
+
+/// The minimum length of an intron found from its motifs, so that its donor and acceptor motifs
+/// don't overlap.
+pub const MIN_INTRON_LEN: usize = 4;
+
+/// The donor and acceptor motifs of the introns of each kind found from their motifs.
+const MOTIFS: [(&[u8; 2], &[u8; 2]); ANNOTATED] = [(b"GT", b"AG"), (b"CT", b"AC")];
+
+/// The bit set at the position of a donor of the given kind of splice sites.
+pub const fn donor(kind: usize) -> u8 {
+    1 << (2 * kind)
+}
+
+/// The bit set at the position of an acceptor of the given kind of splice sites.
+pub const fn acceptor(kind: usize) -> u8 {
+    1 << (2 * kind + 1)
+}
+
+/// The splice sites of each position `0..=x.len()` of x found from the intron motifs, as the
+/// [`donor`] and [`acceptor`] bits of each kind.
+pub fn motif_splice_sites(x: &[u8]) -> Vec<u8> {
+    let mut sites = vec![0; x.len() + 1];
+    for (p, site) in sites.iter_mut().enumerate() {
+        for (kind, (donor_motif, acceptor_motif)) in MOTIFS.iter().enumerate() {
+            if x.get(p..p + 2)
+                .is_some_and(|bases| bases.eq_ignore_ascii_case(*donor_motif))
+            {
+                *site |= donor(kind);
+            }
+            if p >= 2 && x[p - 2..p].eq_ignore_ascii_case(*acceptor_motif) {
+                *site |= acceptor(kind);
+            }
+        }
+    }
+    sites
+}
+
+/// True if the jump within x from after the base `from - 1` to before the base `to` is forward
+/// over an intron of bases `[from, to)` with the donor and acceptor motifs of the same kind.
+pub fn is_spliced_jump(x: &[u8], from: usize, to: usize) -> bool {
+    if to < from + MIN_INTRON_LEN || to > x.len() {
+        return false;
+    }
+    let (donor_bases, acceptor_bases) = (&x[from..from + 2], &x[to - 2..to]);
+    MOTIFS.iter().any(|(donor_motif, acceptor_motif)| {
+        donor_bases.eq_ignore_ascii_case(*donor_motif)
+            && acceptor_bases.eq_ignore_ascii_case(*acceptor_motif)
+    })
+}
+
+/// The introns of the annotated transcripts of each contig, from which jumps over introns at the
+/// annotated splice sites are scored as spliced, even without the intron motifs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpliceAnnotation {
+    /// The sorted introns `[start, end)` (0-based) of each contig, on its forward strand.
+    introns: HashMap<String, Vec<(usize, usize)>>,
+}
+
+impl SpliceAnnotation {
+    /// Adds the intron `[start, end)` (0-based) of the given contig.
+    pub fn add_intron(&mut self, contig: &str, start: usize, end: usize) {
+        let introns = self.introns.entry(contig.to_string()).or_default();
+        if let Err(index) = introns.binary_search(&(start, end)) {
+            introns.insert(index, (start, end));
+        }
+    }
+
+    /// Parses the exons of the transcripts in GTF or GFF format, with the introns between the
+    /// consecutive exons of each transcript.  Only the `exon` features are read, with their
+    /// transcript from the `transcript_id` (GTF) or `Parent` (GFF) attribute, and the comment
+    /// lines are ignored.
+    pub fn from_gtf(gtf: &str) -> Result<Self> {
+        let mut exons: HashMap<(&str, &str), Vec<(usize, usize)>> = HashMap::new();
+        let lines = gtf
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+        for (index, line) in lines {
+            let fields: Vec<&str> = line.split('\t').collect();
+            ensure!(
+                fields.len() >= 9,
+                "Expected 9 columns on line {} of the annotation: {line}",
+                index + 1
+            );
+            if fields[2] != "exon" {
+                continue;
+            }
+            let parse = |field: &str| {
+                field.trim().parse::<usize>().with_context(|| {
+                    format!(
+                        "Invalid position on line {} of the annotation: {field}",
+                        index + 1
+                    )
+                })
+            };
+            let (start, end) = (parse(fields[3])?, parse(fields[4])?);
+            ensure!(
+                start >= 1 && start <= end,
+                "Invalid exon on line {} of the annotation: {start}-{end}",
+                index + 1
+            );
+            let transcript = transcript_id(fields[8]).with_context(|| {
+                format!(
+                    "No transcript_id or Parent attribute on line {} of the annotation",
+                    index + 1
+                )
+            })?;
+            // 1-based inclusive to 0-based half-open
+            exons
+                .entry((fields[0], transcript))
+                .or_default()
+                .push((start - 1, end));
+        }
+
+        let mut annotation = Self::default();
+        for ((contig, _), mut exons) in exons {
+            exons.sort_unstable();
+            for (prev, next) in exons.iter().zip(exons.iter().skip(1)) {
+                if prev.1 < next.0 {
+                    annotation.add_intron(contig, prev.1, next.0);
+                }
+            }
+        }
+        Ok(annotation)
+    }
+
+    /// Reads the exons of the transcripts from a GTF or GFF file (see [`Self::from_gtf`]).
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read the annotation: {}", path.display()))?;
+        Self::from_gtf(&contents).with_context(|| format!("Invalid annotation: {}", path.display()))
+    }
+
+    /// The introns `[start, end)` of the given contig, on its forward strand, sorted.
+    pub fn introns(&self, contig: &str) -> &[(usize, usize)] {
+        self.introns.get(contig).map_or(&[], Vec::as_slice)
+    }
+
+    /// The annotated splice sites of each position `0..=region.len()` of the given region of the
+    /// contig and strand, with the region in the coordinates of the strand, as the [`donor`] and
+    /// [`acceptor`] bits of the [`ANNOTATED`] kind.  Empty if the contig has no introns.  On the
+    /// reverse strand, the donor of an intron is at its end on the forward strand.
+    pub fn splice_sites(
+        &self,
+        contig: &str,
+        is_forward: bool,
+        contig_len: usize,
+        region: Range<usize>,
+    ) -> Vec<u8> {
+        let introns = self.introns(contig);
+        if introns.is_empty() {
+            return Vec::new();
+        }
+        let mut sites = vec![0; region.len() + 1];
+        for &(start, end) in introns.iter().filter(|(_, end)| *end <= contig_len) {
+            let (from, to) = if is_forward {
+                (start, end)
+            } else {
+                (contig_len - end, contig_len - start)
+            };
+            for (position, bit) in [(from, donor(ANNOTATED)), (to, acceptor(ANNOTATED))] {
+                if region.start <= position && position <= region.end {
+                    sites[position - region.start] |= bit;
+                }
+            }
+        }
+        sites
+    }
+}
+
+/// The transcript of a feature from its attributes: the `transcript_id` attribute in GTF, or the
+/// `Parent` attribute in GFF.
+fn transcript_id(attributes: &str) -> Option<&str> {
+    attributes.split(';').map(str::trim).find_map(|attribute| {
+        if let Some(value) = attribute.strip_prefix("transcript_id") {
+            Some(value.trim_start_matches('=').trim().trim_matches('"'))
+        } else {
+            attribute.strip_prefix("Parent=")
+        }
+    })
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{MultiContigAligner, Scoring};
+
+    use super::{
+        acceptor, donor, is_spliced_jump, motif_splice_sites, SpliceAnnotation, ANNOTATED,
+    };
+
+    #[rstest]
+    fn test_motif_splice_sites() {
+        let sites = motif_splice_sites(b"AGTCAGAC");
+        assert_eq!(sites.len(), 9);
+        assert_eq!(sites[1], donor(0));
+        assert_eq!(sites[2], acceptor(0));
+        assert_eq!(sites[6], acceptor(0));
+        assert_eq!(sites[8], acceptor(1));
+        assert_eq!(sites[0] | sites[3] | sites[4] | sites[5] | sites[7], 0);
+
+        assert!(is_spliced_jump(b"AGTCCAGA", 1, 7));
+        assert!(is_spliced_jump(b"actaaaac", 1, 8));
+        // too short, or the motifs of different kinds
+        assert!(!is_spliced_jump(b"AGTAGA", 1, 4));
+        assert!(!is_spliced_jump(b"AGTCCACA", 1, 7));
+        assert!(!is_spliced_jump(b"AGTCCAGA", 7, 1));
+    }
+
+    #[rstest]
+    fn test_from_gtf() {
+        let gtf = "##gff-version 2\n\
+            chr1\tsrc\ttranscript\t11\t60\t.\t+\t.\ttranscript_id \"tx1\";\n\
+            chr1\tsrc\texon\t41\t60\t.\t+\t.\tgene_id \"g1\"; transcript_id \"tx1\";\n\
+            chr1\tsrc\texon\t11\t20\t.\t+\t.\tgene_id \"g1\"; transcript_id \"tx1\";\n\
+            chr1\tsrc\texon\t31\t35\t.\t+\t.\tID=e1;Parent=tx2\n\
+            chr1\tsrc\texon\t51\t55\t.\t+\t.\tID=e2;Parent=tx2\n\
+            chr2\tsrc\texon\t1\t10\t.\t-\t.\ttranscript_id \"tx3\";\n";
+        let annotation = SpliceAnnotation::from_gtf(gtf).unwrap();
+        assert_eq!(annotation.introns("chr1"), &[(20, 40), (35, 50)]);
+        // a single exon has no introns
+        assert!(annotation.introns("chr2").is_empty());
+
+        assert!(SpliceAnnotation::from_gtf("chr1\tsrc\texon\t1\t10\n").is_err());
+        assert!(SpliceAnnotation::from_gtf("chr1\tsrc\texon\t10\t1\t.\t+\t.\tParent=a").is_err());
+        assert!(SpliceAnnotation::from_gtf("chr1\tsrc\texon\t1\t10\t.\t+\t.\tID=a").is_err());
+    }
+
+    #[rstest]
+    fn test_splice_sites() {
+        let mut annotation = SpliceAnnotation::default();
+        annotation.add_intron("chr1", 2, 5);
+        let (from, to) = (donor(ANNOTATED), acceptor(ANNOTATED));
+        assert_eq!(
+            annotation.splice_sites("chr1", true, 8, 0..8),
+            vec![0, 0, from, 0, 0, to, 0, 0, 0]
+        );
+        // on the reverse strand, the intron is from 8 - 5 to 8 - 2
+        assert_eq!(
+            annotation.splice_sites("chr1", false, 8, 0..8),
+            vec![0, 0, 0, from, 0, 0, to, 0, 0]
+        );
+        assert_eq!(
+            annotation.splice_sites("chr1", true, 8, 4..6),
+            vec![0, to, 0]
+        );
+        assert!(annotation.splice_sites("chr2", true, 8, 0..8).is_empty());
+    }
+
+    #[rstest]
+    fn test_align_spliced() {
+        // The query joins the two exons of the contig over an intron with GT-AG motifs, with the
+        // last base of the first exon (G) also the last base of the intron, so the jump may be
+        // placed either side of it.  Without the splice jump score, the jump is before the G, and
+        // so misses the splice sites.
+        let exon1 = "ACGTTGCAAGGCTTACGATG";
+        let intron = "GTCCGTAGGCATCGATGCAG";
+        let exon2 = "ATGCCTAGGTCATCGGACTT";
+        let contig = format!("{exon1}{intron}{exon2}");
+        let query = format!("{exon1}{exon2}");
+        let scoring = Scoring::with_jump_score(-5, -1, -15, MatchParams::new(1, -1))
+            .set_xclip(0)
+            .set_yclip(0);
+
+        let mut aligner: MultiContigAligner<MatchParams> = MultiContigAligner::new();
+        aligner.add_contig("chr1", true, contig.as_bytes(), false, scoring);
+        let alignment = aligner.custom(query.as_bytes());
+        assert_eq!(alignment.cigar(), "19=20J21=");
+        assert_eq!(alignment.score, 40 - 15);
+
+        let mut aligner: MultiContigAligner<MatchParams> = MultiContigAligner::new();
+        let scoring = scoring.set_splice_jump_score(Some(-8));
+        aligner.add_contig("chr1", true, contig.as_bytes(), false, scoring);
+        let alignment = aligner.custom(query.as_bytes());
+        assert_eq!(alignment.cigar(), "20=20J20=");
+        assert_eq!(alignment.score, 40 - 8);
+
+        // the same intron, annotated, without its motifs
+        let intron = "CCCCGTAGGCATCGATGCCC";
+        let contig = format!("{exon1}{intron}{exon2}");
+        let mut aligner: MultiContigAligner<MatchParams> = MultiContigAligner::new();
+        aligner.add_contig("chr1", true, contig.as_bytes(), false, scoring);
+        let alignment = aligner.custom(query.as_bytes());
+        assert_eq!(alignment.score, 40 - 15);
+        let mut annotation = SpliceAnnotation::default();
+        annotation.add_intron("chr1", 20, 40);
+        aligner.set_splice_annotation(Some(Arc::new(annotation)));
+        let alignment = aligner.custom(query.as_bytes());
+        assert_eq!(alignment.cigar(), "20=20J20=");
+        assert_eq!(alignment.score, 40 - 8);
+    }
+}
@@ -0,0 +1,207 @@
+//! Automatic selection of the strategy for aligning each query, from the length of the query, the
+//! lengths of the contigs, and the identity of the query to the contigs estimated from the k-mers
+//! they share, so that queries are aligned quickly without tuning the aligner.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, Error};
+
+use crate::util::target_seq::TargetHash;
+
+/// The strategies for aligning a query (see [`AutoStrategy`]).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum AlignStrategy {
+    /// Fill the matrices of every contig in full.
+    #[default]
+    Full,
+    /// Fill only the rows of each column near the best scoring row of the previous column (see
+    /// [`crate::align::Builder::adaptive_band_width`]).
+    Banded,
+    /// Find the contigs with a local alignment of the query, banded around the k-mers they share,
+    /// then fill the matrices of only those contigs (see [`crate::align::Builder::pre_align`]).
+    SeedAndExtend,
+    /// Align the query in overlapping chunks (see [`crate::align::Builder::chunk_length`]).
+    Chunked,
+}
+
+impl Display for AlignStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Full => write!(f, "full"),
+            Self::Banded => write!(f, "banded"),
+            Self::SeedAndExtend => write!(f, "seed-and-extend"),
+            Self::Chunked => write!(f, "chunked"),
+        }
+    }
+}
+
+impl FromStr for AlignStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "full" => Ok(AlignStrategy::Full),
+            "banded" => Ok(AlignStrategy::Banded),
+            "seed-and-extend" | "seed_and_extend" | "seed" => Ok(AlignStrategy::SeedAndExtend),
+            "chunked" => Ok(AlignStrategy::Chunked),
+            _ => Err(anyhow!("Invalid alignment strategy: {}", s)),
+        }
+    }
+}
+
+/// Picks the strategy for aligning each query (see [`AutoStrategy::choose`]).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct AutoStrategy {
+    /// The maximum number of cells of the matrices to fill in full (see
+    /// [`crate::align::MultiContigAligner::num_cells`]).
+    pub max_full_cells: usize,
+    /// The query length above which to align in chunks of this length, in the modes clipping the
+    /// contigs.
+    pub chunk_length: usize,
+    /// The minimum estimated identity of the query to band the matrices.
+    pub min_banded_identity: f64,
+    /// The number of rows on either side of the best scoring row to fill, when banding.
+    pub band_width: usize,
+    /// The minimum estimated identity of the query to seed and extend, below which too few k-mers
+    /// are shared to reliably find the contigs to align to.
+    pub min_seed_identity: f64,
+}
+
+impl Default for AutoStrategy {
+    fn default() -> Self {
+        Self {
+            max_full_cells: 10_000_000,
+            chunk_length: 50_000,
+            min_banded_identity: 0.9,
+            band_width: 100,
+            min_seed_identity: 0.75,
+        }
+    }
+}
+
+impl AutoStrategy {
+    /// Picks the strategy for aligning a query of the given length, given the number of cells of
+    /// its matrices across the contigs, the number of targets, whether the alignment mode clips the
+    /// contigs (local, target-local, or semi-global), and the estimated identity of the query to the targets (see
+    /// [`estimate_identity`]).  In order:
+    /// 1. matrices with few enough cells are filled in full;
+    /// 2. long queries are aligned in chunks, in the modes clipping the contigs;
+    /// 3. queries to more than one target are seeded and extended, if similar enough;
+    /// 4. queries similar to the targets are banded;
+    /// 5. otherwise the matrices are filled in full.
+    pub fn choose(
+        &self,
+        query_len: usize,
+        num_cells: usize,
+        num_targets: usize,
+        local: bool,
+        identity: f64,
+    ) -> AlignStrategy {
+        if num_cells <= self.max_full_cells {
+            AlignStrategy::Full
+        } else if local && query_len > self.chunk_length {
+            AlignStrategy::Chunked
+        } else if num_targets > 1 && identity >= self.min_seed_identity {
+            AlignStrategy::SeedAndExtend
+        } else if identity >= self.min_banded_identity {
+            AlignStrategy::Banded
+        } else {
+            AlignStrategy::Full
+        }
+    }
+}
+
+/// Estimates the identity of the query to the most similar target, from the fraction of the
+/// k-mers of the query found in either strand of the target.  A k-mer is found when all of its `k`
+/// bases match, so the identity is estimated as that fraction to the power of `1 / k`.  Queries
+/// shorter than `k` have an estimated identity of zero.
+pub fn estimate_identity(query: &[u8], target_hashes: &[TargetHash], k: usize) -> f64 {
+    if k == 0 || query.len() < k {
+        return 0.0;
+    }
+    let num_kmers = query.len() - k + 1;
+    let best = target_hashes
+        .iter()
+        .flat_map(|target_hash| [&target_hash.fwd_hash, &target_hash.revcomp_hash])
+        .map(|hash| {
+            query
+                .windows(k)
+                .filter(|kmer| hash.contains_key(kmer))
+                .count()
+        })
+        .max()
+        .unwrap_or(0);
+    (best as f64 / num_kmers as f64).powf(1.0 / k as f64)
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::{estimate_identity, AlignStrategy, AutoStrategy};
+    use crate::{
+        test_util::{random_dna, Rng, SEED},
+        util::target_seq::TargetSeq,
+    };
+
+    #[rstest]
+    #[case::small(1_000, 1_000, 2, true, 0.5, AlignStrategy::Full)]
+    #[case::long_local(100_000, 1 << 30, 1, true, 0.99, AlignStrategy::Chunked)]
+    #[case::long_global(100_000, 1 << 30, 1, false, 0.99, AlignStrategy::Banded)]
+    #[case::many_targets(10_000, 1 << 30, 3, true, 0.8, AlignStrategy::SeedAndExtend)]
+    #[case::one_target(10_000, 1 << 30, 1, true, 0.8, AlignStrategy::Full)]
+    #[case::dissimilar(10_000, 1 << 30, 3, true, 0.5, AlignStrategy::Full)]
+    fn test_choose(
+        #[case] query_len: usize,
+        #[case] num_cells: usize,
+        #[case] num_targets: usize,
+        #[case] local: bool,
+        #[case] identity: f64,
+        #[case] expected: AlignStrategy,
+    ) {
+        let strategy =
+            AutoStrategy::default().choose(query_len, num_cells, num_targets, local, identity);
+        assert_eq!(strategy, expected);
+    }
+
+    #[rstest]
+    fn test_estimate_identity() {
+        let mut rng = Rng::new(SEED);
+        let seq = random_dna(&mut rng, 500);
+        let target_seqs = [TargetSeq::new("chr1", &seq, false)];
+        let target_hashes = target_seqs
+            .iter()
+            .map(|target_seq| target_seq.build_target_hash(8))
+            .collect::<Vec<_>>();
+
+        // the query is in the target, on either strand
+        assert_eq!(estimate_identity(&seq[100..300], &target_hashes, 8), 1.0);
+        let revcomp = &target_seqs[0].revcomp[100..300];
+        assert_eq!(estimate_identity(revcomp, &target_hashes, 8), 1.0);
+
+        // a mismatch every 20 bases leaves some of the k-mers
+        let mut query = seq[100..300].to_vec();
+        for i in (10..query.len()).step_by(20) {
+            query[i] = if query[i] == b'A' { b'C' } else { b'A' };
+        }
+        let identity = estimate_identity(&query, &target_hashes, 8);
+        assert!(0.8 < identity && identity < 1.0, "{identity}");
+
+        // an unrelated query shares few k-mers, and a short query none
+        let unrelated = random_dna(&mut rng, 200);
+        assert!(estimate_identity(&unrelated, &target_hashes, 8) < 0.7);
+        assert_eq!(estimate_identity(&seq[..7], &target_hashes, 8), 0.0);
+    }
+
+    #[rstest]
+    fn test_align_strategy_from_str() {
+        assert_eq!(
+            "Seed-And-Extend".parse::<AlignStrategy>().unwrap(),
+            AlignStrategy::SeedAndExtend
+        );
+        assert!("fast".parse::<AlignStrategy>().is_err());
+    }
+}
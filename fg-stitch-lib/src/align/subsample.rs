@@ -0,0 +1,136 @@
+//! Subsampling of the reads to align, for quick previews of large datasets.  The reads kept are
+//! chosen by a seeded pseudo-random number generator, so are the same for the same seed.
+
+use anyhow::{ensure, Result};
+
+/// The seed of the random number generator choosing the reads to keep, unless given.
+pub const DEFAULT_SEED: u64 = 42;
+
+/// The limits on the reads to align of each input.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Subsample {
+    /// The maximum number of reads to keep, if any.
+    pub max_reads: Option<usize>,
+    /// The fraction of the reads to keep, if any.
+    pub fraction: Option<f64>,
+    /// The seed of the random number generator choosing the reads to keep.
+    pub seed: u64,
+}
+
+impl Default for Subsample {
+    fn default() -> Self {
+        Self {
+            max_reads: None,
+            fraction: None,
+            seed: DEFAULT_SEED,
+        }
+    }
+}
+
+impl Subsample {
+    /// Creates the limits, where the fraction must be in `(0, 1]`, and the maximum number of reads
+    /// positive.
+    pub fn new(max_reads: Option<usize>, fraction: Option<f64>, seed: u64) -> Result<Self> {
+        ensure!(
+            max_reads.is_none_or(|max_reads| max_reads > 0),
+            "The maximum number of reads must be positive"
+        );
+        ensure!(
+            fraction.is_none_or(|fraction| fraction > 0.0 && fraction <= 1.0),
+            "The fraction of reads to keep must be in (0, 1]"
+        );
+        Ok(Self {
+            max_reads,
+            fraction,
+            seed,
+        })
+    }
+
+    /// True if all the reads are kept.
+    pub fn is_none(&self) -> bool {
+        self.max_reads.is_none() && self.fraction.is_none()
+    }
+
+    /// Returns the items to keep, each kept with probability of the fraction, up to the maximum
+    /// number of items.  No more items are taken from `iter` once the maximum number is kept.
+    pub fn apply<I: Iterator>(&self, iter: I) -> impl Iterator<Item = I::Item> {
+        let fraction = self.fraction.unwrap_or(1.0);
+        let mut rng = SplitMix64(self.seed);
+        iter.filter(move |_| fraction >= 1.0 || rng.next_f64() < fraction)
+            .take(self.max_reads.unwrap_or(usize::MAX))
+    }
+}
+
+/// The SplitMix64 pseudo-random number generator, so the reads kept are the same for the same
+/// seed on every platform.
+#[derive(Debug, Clone)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `[0, 1)`, from the upper 53 bits of the next number.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::{SplitMix64, Subsample};
+
+    #[rstest]
+    fn test_new() {
+        assert!(Subsample::new(None, None, 1).unwrap().is_none());
+        assert!(!Subsample::new(Some(10), None, 1).unwrap().is_none());
+        assert!(Subsample::new(Some(0), None, 1).is_err());
+        assert!(Subsample::new(None, Some(0.0), 1).is_err());
+        assert!(Subsample::new(None, Some(1.5), 1).is_err());
+        assert!(Subsample::new(None, Some(1.0), 1).is_ok());
+    }
+
+    #[rstest]
+    fn test_split_mix_64() {
+        // the first outputs for the seed zero, from the reference implementation
+        let mut rng = SplitMix64(0);
+        assert_eq!(rng.next_u64(), 0xe220a8397b1dcdaf);
+        assert_eq!(rng.next_u64(), 0x6e789e6aa1b965f4);
+        let value = SplitMix64(7).next_f64();
+        assert!((0.0..1.0).contains(&value));
+    }
+
+    #[rstest]
+    fn test_apply() {
+        // all the items
+        let subsample = Subsample::default();
+        assert_eq!(subsample.apply(0..100).count(), 100);
+
+        // up to the maximum number of items
+        let subsample = Subsample::new(Some(10), None, 1).unwrap();
+        assert_eq!(
+            subsample.apply(0..100).collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>()
+        );
+
+        // about the fraction of the items, the same for the same seed
+        let subsample = Subsample::new(None, Some(0.25), 1).unwrap();
+        let kept = subsample.apply(0..10000).collect::<Vec<_>>();
+        assert!(kept.len() > 2250 && kept.len() < 2750, "{}", kept.len());
+        assert_eq!(subsample.apply(0..10000).collect::<Vec<_>>(), kept);
+        let other = Subsample::new(None, Some(0.25), 2).unwrap();
+        assert_ne!(other.apply(0..10000).collect::<Vec<_>>(), kept);
+
+        // both, where the maximum number applies to the items kept
+        let subsample = Subsample::new(Some(10), Some(0.25), 1).unwrap();
+        assert_eq!(subsample.apply(0..10000).collect::<Vec<_>>(), kept[..10]);
+    }
+}
@@ -0,0 +1,409 @@
+//! Substitution matrices for scoring the pairs of bases with a non-uniform substitution model
+//! (for example, scoring transitions above transversions), loaded from the NCBI/EMBOSS matrix
+//! format used by `EDNAFULL` and `BLOSUM62`.
+
+use std::{path::Path, str::FromStr};
+
+use anyhow::{anyhow, ensure, Context, Error, Result};
+use bio::alignment::pairwise::MatchFunc;
+use serde::{Deserialize, Serialize};
+
+/// The maximum number of symbols of a substitution matrix, for the 20 amino acids with the
+/// ambiguity codes and the stop (see [`crate::util::alphabet::AMINO_ACIDS`]).
+pub const MAX_MATRIX_SYMBOLS: usize = 24;
+
+/// The index of the bytes that are not symbols of the matrix.
+const NO_SYMBOL: u8 = u8::MAX;
+
+/// The `EDNAFULL` (`NUC.4.4`) nucleotide substitution matrix, with the IUPAC ambiguity codes.
+pub const EDNAFULL: &str = "\
+# EDNAFULL (NUC.4.4)
+    A   T   G   C   S   W   R   Y   K   M   B   V   H   D   N
+A   5  -4  -4  -4  -4   1   1  -4  -4   1  -4  -1  -1  -1  -2
+T  -4   5  -4  -4  -4   1  -4   1   1  -4  -1  -4  -1  -1  -2
+G  -4  -4   5  -4   1  -4   1  -4   1  -4  -1  -1  -4  -1  -2
+C  -4  -4  -4   5   1  -4  -4   1  -4   1  -1  -1  -1  -4  -2
+S  -4  -4   1   1  -1  -4  -2  -2  -2  -2  -1  -1  -3  -3  -1
+W   1   1  -4  -4  -4  -1  -2  -2  -2  -2  -3  -3  -1  -1  -1
+R   1  -4   1  -4  -2  -2  -1  -4  -2  -2  -3  -1  -3  -1  -1
+Y  -4   1  -4   1  -2  -2  -4  -1  -2  -2  -1  -3  -1  -3  -1
+K  -4   1   1  -4  -2  -2  -2  -2  -1  -4  -1  -3  -3  -1  -1
+M   1  -4  -4   1  -2  -2  -2  -2  -4  -1  -3  -1  -1  -3  -1
+B  -4  -1  -1  -1  -1  -3  -3  -1  -1  -3  -1  -2  -2  -2  -1
+V  -1  -4  -1  -1  -1  -3  -1  -3  -3  -1  -2  -1  -2  -2  -1
+H  -1  -1  -4  -1  -3  -1  -3  -1  -3  -1  -2  -2  -1  -2  -1
+D  -1  -1  -1  -4  -3  -1  -1  -3  -1  -3  -2  -2  -2  -1  -1
+N  -2  -2  -2  -2  -1  -1  -1  -1  -1  -1  -1  -1  -1  -1  -1
+";
+
+/// The `BLOSUM62` amino acid substitution matrix, with the ambiguity codes and the stop.
+pub const BLOSUM62: &str = "\
+# BLOSUM62
+   A  R  N  D  C  Q  E  G  H  I  L  K  M  F  P  S  T  W  Y  V  B  Z  X  *
+A  4 -1 -2 -2  0 -1 -1  0 -2 -1 -1 -1 -1 -2 -1  1  0 -3 -2  0 -2 -1  0 -4
+R -1  5  0 -2 -3  1  0 -2  0 -3 -2  2 -1 -3 -2 -1 -1 -3 -2 -3 -1  0 -1 -4
+N -2  0  6  1 -3  0  0  0  1 -3 -3  0 -2 -3 -2  1  0 -4 -2 -3  3  0 -1 -4
+D -2 -2  1  6 -3  0  2 -1 -1 -3 -4 -1 -3 -3 -1  0 -1 -4 -3 -3  4  1 -1 -4
+C  0 -3 -3 -3  9 -3 -4 -3 -3 -1 -1 -3 -1 -2 -3 -1 -1 -2 -2 -1 -3 -3 -2 -4
+Q -1  1  0  0 -3  5  2 -2  0 -3 -2  1  0 -3 -1  0 -1 -2 -1 -2  0  3 -1 -4
+E -1  0  0  2 -4  2  5 -2  0 -3 -3  1 -2 -3 -1  0 -1 -3 -2 -2  1  4 -1 -4
+G  0 -2  0 -1 -3 -2 -2  6 -2 -4 -4 -2 -3 -3 -2  0 -2 -2 -3 -3 -1 -2 -1 -4
+H -2  0  1 -1 -3  0  0 -2  8 -3 -3 -1 -2 -1 -2 -1 -2 -2  2 -3  0  0 -1 -4
+I -1 -3 -3 -3 -1 -3 -3 -4 -3  4  2 -3  1  0 -3 -2 -1 -3 -1  3 -3 -3 -1 -4
+L -1 -2 -3 -4 -1 -2 -3 -4 -3  2  4 -2  2  0 -3 -2 -1 -2 -1  1 -4 -3 -1 -4
+K -1  2  0 -1 -3  1  1 -2 -1 -3 -2  5 -1 -3 -1  0 -1 -3 -2 -2  0  1 -1 -4
+M -1 -1 -2 -3 -1  0 -2 -3 -2  1  2 -1  5  0 -2 -1 -1 -1 -1  1 -3 -1 -1 -4
+F -2 -3 -3 -3 -2 -3 -3 -3 -1  0  0 -3  0  6 -4 -2 -2  1  3 -1 -3 -3 -1 -4
+P -1 -2 -2 -1 -3 -1 -1 -2 -2 -3 -3 -1 -2 -4  7 -1 -1 -4 -3 -2 -2 -1 -2 -4
+S  1 -1  1  0 -1  0  0  0 -1 -2 -2  0 -1 -2 -1  4  1 -3 -2 -2  0  0  0 -4
+T  0 -1  0 -1 -1 -1 -1 -2 -2 -1 -1 -1 -1 -2 -1  1  5 -2 -2  0 -1 -1  0 -4
+W -3 -3 -4 -4 -2 -2 -3 -2 -2 -3 -2 -3 -1  1 -4 -3 -2 11  2 -3 -4 -3 -2 -4
+Y -2 -2 -2 -3 -2 -1 -2 -3  2 -1 -1 -2 -1  3 -3 -2 -2  2  7 -1 -3 -2 -1 -4
+V  0 -3 -3 -3 -1 -2 -2 -3 -3  3  1 -2  1 -1 -2 -2  0 -3 -1  4 -3 -2 -1 -4
+B -2 -1  3  4 -3  0  1 -1  0 -3 -4  0 -3 -3 -2  0 -1 -4 -3 -3  4  1 -1 -4
+Z -1  0  0  1 -3  3  4 -2  0 -3 -3  1 -1 -3 -1  0 -1 -3 -2 -2  1  4 -1 -4
+X  0 -1 -1 -1 -2 -1 -1 -1 -1 -1 -1 -1 -1 -1 -2  0  0 -2 -1 -1 -1 -1 -1 -4
+* -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4 -4  1
+";
+
+/// A match function scoring each pair of bases from a square substitution matrix of up to
+/// [`MAX_MATRIX_SYMBOLS`] symbols.  The symbols are case-insensitive, and pairs with a base that
+/// is not a symbol of the matrix are scored with the lowest score of the matrix.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
+#[serde(into = "MatrixDef", try_from = "MatrixDef")]
+pub struct MatrixMatchFunc {
+    /// The symbols, in the order of the rows and columns of the scores.
+    symbols: [u8; MAX_MATRIX_SYMBOLS],
+    num_symbols: usize,
+    /// The index of the symbol of each byte, or [`NO_SYMBOL`].
+    index: [u8; 256],
+    scores: [[i32; MAX_MATRIX_SYMBOLS]; MAX_MATRIX_SYMBOLS],
+    /// The score of the pairs with a byte that is not a symbol.
+    unknown_score: i32,
+}
+
+impl MatrixMatchFunc {
+    /// Creates the match function from the symbols and the rows of scores, in the order of the
+    /// symbols, where `scores[i][j]` is the score of the pair of `symbols[i]` in the contig and
+    /// `symbols[j]` in the query.
+    pub fn new(symbols: &[u8], scores: &[Vec<i32>]) -> Result<Self> {
+        ensure!(
+            !symbols.is_empty(),
+            "The substitution matrix has no symbols"
+        );
+        ensure!(
+            symbols.len() <= MAX_MATRIX_SYMBOLS,
+            "The substitution matrix has more than {MAX_MATRIX_SYMBOLS} symbols: {}",
+            symbols.len()
+        );
+        ensure!(
+            scores.len() == symbols.len(),
+            "The substitution matrix has {} rows for {} symbols",
+            scores.len(),
+            symbols.len()
+        );
+        let mut matrix = Self {
+            symbols: [0; MAX_MATRIX_SYMBOLS],
+            num_symbols: symbols.len(),
+            index: [NO_SYMBOL; 256],
+            scores: [[0; MAX_MATRIX_SYMBOLS]; MAX_MATRIX_SYMBOLS],
+            unknown_score: 0,
+        };
+        for (i, &symbol) in symbols.iter().enumerate() {
+            let upper = symbol.to_ascii_uppercase();
+            ensure!(
+                matrix.index[upper as usize] == NO_SYMBOL,
+                "Duplicate symbol in the substitution matrix: {}",
+                symbol as char
+            );
+            matrix.symbols[i] = upper;
+            matrix.index[upper as usize] = i as u8;
+            matrix.index[upper.to_ascii_lowercase() as usize] = i as u8;
+        }
+        for (i, row) in scores.iter().enumerate() {
+            ensure!(
+                row.len() == symbols.len(),
+                "The row of the substitution matrix for {} has {} scores for {} symbols",
+                symbols[i] as char,
+                row.len(),
+                symbols.len()
+            );
+            matrix.scores[i][..row.len()].copy_from_slice(row);
+        }
+        matrix.unknown_score = scores.iter().flatten().copied().min().unwrap_or(0);
+        Ok(matrix)
+    }
+
+    /// Creates the 4x4 matrix of the bases `ACGT` with a transition (`A<->G`, `C<->T`) scored
+    /// differently from a transversion.
+    ///
+    /// # Arguments
+    ///
+    /// * `match_score` - the score of identical bases
+    /// * `transition_score` - the score of a purine to purine or pyrimidine to pyrimidine
+    ///   substitution
+    /// * `transversion_score` - the score of a purine to pyrimidine substitution, or vice versa
+    pub fn transition_transversion(
+        match_score: i32,
+        transition_score: i32,
+        transversion_score: i32,
+    ) -> Self {
+        let symbols = b"ACGT";
+        let is_purine = |base: u8| base == b'A' || base == b'G';
+        let scores = symbols
+            .iter()
+            .map(|&a| {
+                symbols
+                    .iter()
+                    .map(|&b| match (a, b) {
+                        _ if a == b => match_score,
+                        _ if is_purine(a) == is_purine(b) => transition_score,
+                        _ => transversion_score,
+                    })
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        Self::new(symbols, &scores).unwrap()
+    }
+
+    /// The `EDNAFULL` nucleotide substitution matrix (see [`EDNAFULL`]).
+    pub fn ednafull() -> Self {
+        Self::from_str(EDNAFULL).unwrap()
+    }
+
+    /// The `BLOSUM62` amino acid substitution matrix (see [`BLOSUM62`]).
+    pub fn blosum62() -> Self {
+        Self::from_str(BLOSUM62).unwrap()
+    }
+
+    /// Reads the matrix from a file in the NCBI/EMBOSS matrix format (see
+    /// [`MatrixMatchFunc::from_str`]).
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).with_context(|| {
+            format!("Could not read the substitution matrix: {}", path.display())
+        })?;
+        Self::from_str(&contents)
+            .with_context(|| format!("Invalid substitution matrix: {}", path.display()))
+    }
+
+    /// The symbols of the matrix, in the order of its rows and columns.
+    pub fn symbols(&self) -> &[u8] {
+        &self.symbols[..self.num_symbols]
+    }
+
+    /// The highest score of the matrix, typically that of a match.
+    pub fn max_score(&self) -> i32 {
+        self.rows().flatten().copied().max().unwrap_or(0)
+    }
+
+    /// The lowest score of the matrix, which also scores the pairs with a base that is not a
+    /// symbol of the matrix.
+    pub fn min_score(&self) -> i32 {
+        self.unknown_score
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &[i32]> {
+        self.scores[..self.num_symbols]
+            .iter()
+            .map(|row| &row[..self.num_symbols])
+    }
+}
+
+impl MatchFunc for MatrixMatchFunc {
+    #[inline]
+    fn score(&self, a: u8, b: u8) -> i32 {
+        let i = self.index[a as usize];
+        let j = self.index[b as usize];
+        if i == NO_SYMBOL || j == NO_SYMBOL {
+            self.unknown_score
+        } else {
+            self.scores[i as usize][j as usize]
+        }
+    }
+}
+
+impl FromStr for MatrixMatchFunc {
+    type Err = Error;
+
+    /// Parses the matrix in the NCBI/EMBOSS matrix format: lines starting with `#` are comments,
+    /// the first other line has the symbols of the columns, and each following line has the
+    /// symbol of the row then its scores.  The rows may be in any order, but each symbol must
+    /// have exactly one row.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        let header = lines
+            .next()
+            .ok_or_else(|| anyhow!("The substitution matrix has no header"))?;
+        let symbols = header
+            .split_whitespace()
+            .map(parse_symbol)
+            .collect::<Result<Vec<_>>>()?;
+        let mut rows: Vec<Option<Vec<i32>>> = vec![None; symbols.len()];
+        for line in lines {
+            let mut fields = line.split_whitespace();
+            let symbol = parse_symbol(fields.next().unwrap())?;
+            let i = symbols
+                .iter()
+                .position(|s| s.eq_ignore_ascii_case(&symbol))
+                .ok_or_else(|| anyhow!("Row for an unknown symbol: {}", symbol as char))?;
+            ensure!(
+                rows[i].is_none(),
+                "Duplicate row for symbol: {}",
+                symbol as char
+            );
+            let scores = fields
+                .map(|field| {
+                    field
+                        .parse::<i32>()
+                        .with_context(|| format!("Invalid score: {field}"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            rows[i] = Some(scores);
+        }
+        let rows = rows
+            .into_iter()
+            .zip(&symbols)
+            .map(|(row, &symbol)| {
+                row.ok_or_else(|| anyhow!("No row for symbol: {}", symbol as char))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(&symbols, &rows)
+    }
+}
+
+/// Parses a symbol of the matrix, which must be a single character.
+fn parse_symbol(field: &str) -> Result<u8> {
+    match field.as_bytes() {
+        [symbol] => Ok(*symbol),
+        _ => Err(anyhow!(
+            "Invalid symbol in the substitution matrix: {}",
+            field
+        )),
+    }
+}
+
+/// The serialized form of a [`MatrixMatchFunc`], with the symbols as a string and the rows of
+/// scores.
+#[derive(Clone, Serialize, Deserialize)]
+struct MatrixDef {
+    symbols: String,
+    scores: Vec<Vec<i32>>,
+}
+
+impl From<MatrixMatchFunc> for MatrixDef {
+    fn from(matrix: MatrixMatchFunc) -> Self {
+        Self {
+            symbols: String::from_utf8_lossy(matrix.symbols()).into_owned(),
+            scores: matrix.rows().map(<[i32]>::to_vec).collect(),
+        }
+    }
+}
+
+impl TryFrom<MatrixDef> for MatrixMatchFunc {
+    type Error = Error;
+
+    fn try_from(def: MatrixDef) -> Result<Self> {
+        Self::new(def.symbols.as_bytes(), &def.scores)
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::{MatchFunc, MatchParams};
+    use rstest::rstest;
+
+    use super::MatrixMatchFunc;
+    use crate::align::{Scoring, SingleContigAligner};
+
+    #[rstest]
+    fn test_ednafull() {
+        let matrix = MatrixMatchFunc::ednafull();
+        assert_eq!(matrix.symbols(), b"ATGCSWRYKMBVHDN");
+        for &a in matrix.symbols() {
+            for &b in matrix.symbols() {
+                assert_eq!(matrix.score(a, b), matrix.score(b, a));
+            }
+        }
+        assert_eq!(matrix.score(b'A', b'A'), 5);
+        assert_eq!(matrix.score(b'a', b'C'), -4);
+        assert_eq!(matrix.score(b'A', b'R'), 1);
+        assert_eq!(matrix.score(b'N', b'g'), -2);
+        // bytes that are not symbols score the lowest score
+        assert_eq!(matrix.score(b'A', b'-'), -4);
+        assert_eq!((matrix.max_score(), matrix.min_score()), (5, -4));
+    }
+
+    #[rstest]
+    fn test_blosum62() {
+        let matrix = MatrixMatchFunc::blosum62();
+        assert_eq!(matrix.symbols(), b"ARNDCQEGHILKMFPSTWYVBZX*");
+        for &a in matrix.symbols() {
+            for &b in matrix.symbols() {
+                assert_eq!(matrix.score(a, b), bio::scores::blosum62(a, b));
+            }
+        }
+        assert_eq!(matrix.score(b'w', b'W'), 11);
+        assert_eq!((matrix.max_score(), matrix.min_score()), (11, -4));
+    }
+
+    #[rstest]
+    fn test_transition_transversion() {
+        let matrix = MatrixMatchFunc::transition_transversion(2, -1, -3);
+        assert_eq!(matrix.score(b'A', b'A'), 2);
+        assert_eq!(matrix.score(b'A', b'G'), -1);
+        assert_eq!(matrix.score(b'T', b'C'), -1);
+        assert_eq!(matrix.score(b'A', b'C'), -3);
+        assert_eq!(matrix.score(b'G', b'T'), -3);
+        assert_eq!(matrix.score(b'N', b'N'), -3);
+    }
+
+    #[rstest]
+    fn test_from_str() {
+        // the rows may be in any order, and need not be symmetric
+        let matrix = "# comment\n A C\nC -1 3\nA 1 -2\n"
+            .parse::<MatrixMatchFunc>()
+            .unwrap();
+        assert_eq!(matrix.score(b'A', b'C'), -2);
+        assert_eq!(matrix.score(b'C', b'A'), -1);
+        assert_eq!(matrix.score(b'C', b'C'), 3);
+
+        assert!("".parse::<MatrixMatchFunc>().is_err());
+        assert!("A C\nA 1 -2\n".parse::<MatrixMatchFunc>().is_err());
+        assert!("A C\nA 1\nC -1 3\n".parse::<MatrixMatchFunc>().is_err());
+        assert!("A C\nA 1 -2\nA 1 -2\nC -1 3\n"
+            .parse::<MatrixMatchFunc>()
+            .is_err());
+        assert!("A C\nA 1 x\nC -1 3\n".parse::<MatrixMatchFunc>().is_err());
+        assert!("AC G\nAC 1 -2\nG -1 3\n"
+            .parse::<MatrixMatchFunc>()
+            .is_err());
+    }
+
+    #[rstest]
+    fn test_align_with_matrix() {
+        let scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(2, -3))
+            .set_match_fn(MatrixMatchFunc::transition_transversion(2, -1, -3));
+        let mut aligner = SingleContigAligner::with_scoring(scoring);
+        let x = b"ACGTACGTACGTACGTACGT";
+        // a transition in the query scores above a transversion
+        let transition = aligner.custom(x, b"ACGTACGTATGTACGTACGT");
+        let transversion = aligner.custom(x, b"ACGTACGTAGGTACGTACGT");
+        assert_eq!(transition.score, 19 * 2 - 1);
+        assert_eq!(transversion.score, 19 * 2 - 3);
+    }
+
+    #[rstest]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let matrix = MatrixMatchFunc::transition_transversion(2, -1, -3);
+        let json = serde_json::to_string(&matrix).unwrap();
+        assert_eq!(
+            serde_json::from_str::<MatrixMatchFunc>(&json).unwrap(),
+            matrix
+        );
+    }
+}
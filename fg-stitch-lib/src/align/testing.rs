@@ -0,0 +1,342 @@
+//! A slow reference implementation of the jump-aware aligner, for comparing the optimized
+//! dynamic programming against on small random inputs (e.g. in property tests and fuzzers).
+//!
+//! The reference aligner computes the best score of aligning a query to a set of contigs by
+//! memoized recursion over every cell of every contig, following the definition of an alignment
+//! directly: prefix clips, then matches, mismatches, and affine gaps, with a jump to any position
+//! of any contig before each match or mismatch, then suffix clips.  It is quadratic in the total
+//! length of the contigs for each query base, so is only suitable for short sequences.
+
+use bio::alignment::pairwise::MatchFunc;
+
+use super::{aligners::constants::MIN_SCORE, MultiContigAligner, Scoring};
+
+/// A contig of the [`ReferenceAligner`].
+#[derive(Debug, Clone)]
+struct ReferenceContig {
+    name: String,
+    is_forward: bool,
+    seq: Vec<u8>,
+}
+
+/// The states of a cell of the alignment of a contig and a query.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum State {
+    /// The best alignment ending at the cell, with any move.
+    Any,
+    /// The best alignment ending at the cell with an insertion (consuming only the contig).
+    Ins,
+    /// The best alignment ending at the cell with a deletion (consuming only the query).
+    Del,
+}
+
+impl State {
+    const COUNT: usize = 3;
+
+    fn index(self) -> usize {
+        match self {
+            Self::Any => 0,
+            Self::Ins => 1,
+            Self::Del => 2,
+        }
+    }
+}
+
+/// A slow, obviously-correct aligner computing the score of the best jump-aware alignment of a
+/// query to a set of contigs, as [`MultiContigAligner::custom`] would with the same contigs and
+/// scoring (see [`Self::multi_contig_aligner`]).
+///
+/// Contigs are not circular, and all contigs share the same scoring, which must not score gaps
+/// next to homopolymers differently (see [`Scoring::homopolymer_gap_open`]).  As for the
+/// optimized aligner, the scores of the cells are floored at [`MIN_SCORE`].  When backward jumps
+/// are cheaper than forward jumps, the optimized aligner may score lower, as it does not jump
+/// backward from the end of a contig in a column where the suffix of the contig is clipped.
+#[derive(Debug, Clone)]
+pub struct ReferenceAligner<F: MatchFunc> {
+    contigs: Vec<ReferenceContig>,
+    scoring: Scoring<F>,
+}
+
+/// The memoized scores of the cells of each contig, for a single query.
+struct Memo {
+    /// The scores of each contig, indexed by row, then column, then state.
+    scores: Vec<Vec<Option<i64>>>,
+    /// The number of columns (the query length plus one).
+    cols: usize,
+}
+
+impl Memo {
+    fn index(&self, i: usize, j: usize, state: State) -> usize {
+        (i * self.cols + j) * State::COUNT + state.index()
+    }
+}
+
+impl<F: MatchFunc + Copy> ReferenceAligner<F> {
+    /// Creates a reference aligner without any contig, with the given scoring for all contigs.
+    pub fn new(scoring: Scoring<F>) -> Self {
+        assert!(
+            scoring.homopolymer_gap_open.is_none(),
+            "The reference aligner does not support homopolymer gap scores"
+        );
+        Self {
+            contigs: Vec::new(),
+            scoring,
+        }
+    }
+
+    /// Adds the given strand of a contig.  The two strands of a contig have the same name, and
+    /// jumps between them are scored as jumps to the opposite strand of the same contig.
+    pub fn add_contig(&mut self, name: &str, is_forward: bool, seq: &[u8]) {
+        self.contigs.push(ReferenceContig {
+            name: name.to_string(),
+            is_forward,
+            seq: seq.to_vec(),
+        });
+    }
+
+    /// The number of contigs (strands) added.
+    pub fn len(&self) -> usize {
+        self.contigs.len()
+    }
+
+    /// True if no contig has been added.
+    pub fn is_empty(&self) -> bool {
+        self.contigs.is_empty()
+    }
+
+    /// Builds the optimized aligner with the same contigs, in the same order, and scoring, whose
+    /// alignment scores should be those of [`Self::score`].
+    pub fn multi_contig_aligner(&self) -> MultiContigAligner<F>
+    where
+        F: Sync,
+    {
+        let mut aligner = MultiContigAligner::new();
+        for contig in &self.contigs {
+            aligner.add_contig(
+                &contig.name,
+                contig.is_forward,
+                &contig.seq,
+                false,
+                self.scoring,
+            );
+        }
+        aligner
+    }
+
+    /// Returns the score of the best alignment of the query to the contigs, or [`MIN_SCORE`] if
+    /// no contig has been added.
+    pub fn score(&self, y: &[u8]) -> i32 {
+        let n = y.len();
+        let mut memo = Memo {
+            scores: self
+                .contigs
+                .iter()
+                .map(|contig| vec![None; (contig.seq.len() + 1) * (n + 1) * State::COUNT])
+                .collect(),
+            cols: n + 1,
+        };
+
+        let mut best = i64::from(MIN_SCORE);
+        for (c, contig) in self.contigs.iter().enumerate() {
+            let m = contig.seq.len();
+            for i in 0..=m {
+                for j in 0..=n {
+                    let mut score = self.best(&mut memo, y, c, i, j, State::Any);
+                    if j < n {
+                        score += i64::from(self.scoring.yclip_suffix);
+                    }
+                    if i < m {
+                        // The remaining bases of the contig may be clipped, or jumped over once
+                        // the query is fully aligned
+                        let mut end_score = self.scoring.xclip_suffix;
+                        if j == n {
+                            end_score = end_score.max(self.scoring.forward_jump_score());
+                        }
+                        score += i64::from(end_score);
+                    }
+                    best = best.max(score);
+                }
+            }
+        }
+        best as i32
+    }
+
+    /// The score of the best alignment of `y[..j]` ending at row `i` of contig `c` in the given
+    /// state, floored at [`MIN_SCORE`].
+    fn best(&self, memo: &mut Memo, y: &[u8], c: usize, i: usize, j: usize, state: State) -> i64 {
+        let index = memo.index(i, j, state);
+        if let Some(score) = memo.scores[c][index] {
+            return score;
+        }
+
+        let scoring = &self.scoring;
+        let gap_open = i64::from(scoring.gap_open + scoring.gap_extend);
+        let gap_extend = i64::from(scoring.gap_extend);
+        let mut score = i64::MIN;
+        match state {
+            State::Any => {
+                // Start of the alignment, possibly after clipping the prefixes of the contig and
+                // the query
+                let mut start = 0;
+                if i > 0 {
+                    start += i64::from(scoring.xclip_prefix);
+                }
+                if j > 0 {
+                    start += i64::from(scoring.yclip_prefix);
+                }
+                score = score.max(start);
+                // Gaps
+                if i > 0 {
+                    score = score.max(self.best(memo, y, c, i, j, State::Ins));
+                }
+                if j > 0 {
+                    score = score.max(self.best(memo, y, c, i, j, State::Del));
+                }
+                // A match or mismatch, either from the previous cell or after a jump from any
+                // cell of any contig in the previous column
+                if i > 0 && j > 0 {
+                    let addend =
+                        i64::from(scoring.match_fn.score(self.contigs[c].seq[i - 1], y[j - 1]));
+                    score = score.max(self.best(memo, y, c, i - 1, j - 1, State::Any) + addend);
+                    for from_c in 0..self.contigs.len() {
+                        for k in 0..=self.contigs[from_c].seq.len() {
+                            let jump = i64::from(self.jump_score(from_c, k, c, i - 1));
+                            let from = self.best(memo, y, from_c, k, j - 1, State::Any);
+                            score = score.max(from + jump + addend);
+                        }
+                    }
+                }
+            }
+            State::Ins => {
+                if i > 0 {
+                    let open = self.best(memo, y, c, i - 1, j, State::Any) + gap_open;
+                    let extend = self.best(memo, y, c, i - 1, j, State::Ins) + gap_extend;
+                    score = open.max(extend);
+                }
+            }
+            State::Del => {
+                if j > 0 {
+                    let open = self.best(memo, y, c, i, j - 1, State::Any) + gap_open;
+                    let extend = self.best(memo, y, c, i, j - 1, State::Del) + gap_extend;
+                    score = open.max(extend);
+                }
+            }
+        }
+
+        let score = score.max(i64::from(MIN_SCORE));
+        memo.scores[c][index] = Some(score);
+        score
+    }
+
+    /// The score of jumping from after the base at index `from - 1` of contig `from_c` to before
+    /// the base at index `to` of contig `to_c`.
+    fn jump_score(&self, from_c: usize, from: usize, to_c: usize, to: usize) -> i32 {
+        let (source, target) = (&self.contigs[from_c], &self.contigs[to_c]);
+        if from_c == to_c {
+            self.scoring.same_strand_jump_score(from, to)
+        } else if source.name == target.name && source.is_forward != target.is_forward {
+            self.scoring.jump_score_same_contig_opposite_strand
+        } else {
+            self.scoring.jump_score_inter_contig
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{aligners::constants::MIN_SCORE, Scoring};
+    use crate::test_util::{random_dna, Rng, SEED};
+    use crate::util::dna::reverse_complement;
+
+    use super::ReferenceAligner;
+
+    fn scoring(clip: i32, jump_score: i32) -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-3, -1, jump_score, MatchParams::new(1, -2))
+            .set_xclip(clip)
+            .set_yclip(clip)
+    }
+
+    #[rstest]
+    #[case::identical(scoring(0, -5), "ACGTACGT", "ACGTACGT", 8)]
+    #[case::local(scoring(0, -5), "ACGTACGT", "TTACGTAC", 6)]
+    #[case::global_gap(scoring(MIN_SCORE, -50), "ACGTACGT", "ACGTCGT", 7 - 4)]
+    #[case::jump(scoring(MIN_SCORE, -2), "AAAACCCCGGGGTTTT", "AAAAGGGG", 8 - 2 - 2)]
+    fn test_score(
+        #[case] scoring: Scoring<MatchParams>,
+        #[case] x: &str,
+        #[case] y: &str,
+        #[case] expected: i32,
+    ) {
+        let mut reference = ReferenceAligner::new(scoring);
+        reference.add_contig("chr1", true, x.as_bytes());
+        assert_eq!(reference.score(y.as_bytes()), expected);
+        let mut aligner = reference.multi_contig_aligner();
+        assert_eq!(aligner.custom(y.as_bytes()).score, expected);
+    }
+
+    #[rstest]
+    fn test_no_contigs() {
+        let reference = ReferenceAligner::new(scoring(0, -5));
+        assert!(reference.is_empty());
+        assert_eq!(reference.score(b"ACGT"), MIN_SCORE);
+    }
+
+    /// Jumping over the end of a contig after jumping into it from another contig stays within
+    /// the contig during traceback.
+    #[rstest]
+    fn test_jump_over_contig_end_after_inter_contig_jump() {
+        let mut scoring = scoring(-3, -1);
+        scoring.jump_score_same_contig_opposite_strand = -4;
+        scoring.jump_score_backward = Some(-6);
+        let mut reference = ReferenceAligner::new(scoring);
+        reference.add_contig("chr1", true, b"AC");
+        reference.add_contig("chr1", false, b"GT");
+        reference.add_contig("chr2", true, b"AACAAGTAA");
+        reference.add_contig("chr2", false, b"TTACTTGTT");
+        let alignment = reference.multi_contig_aligner().custom(b"AGCTCG");
+        assert_eq!(alignment.score, reference.score(b"AGCTCG"));
+    }
+
+    #[rstest]
+    fn test_matches_multi_contig_aligner() {
+        let mut rng = Rng::new(SEED);
+        for _ in 0..500 {
+            let clip = [0, -1, -3, MIN_SCORE][rng.below(4)];
+            let jump_score = -(rng.below(8) as i32) - 1;
+            let mut scoring = scoring(clip, jump_score);
+            scoring.jump_score_inter_contig = -(rng.below(8) as i32) - 1;
+            scoring.jump_score_same_contig_opposite_strand = -(rng.below(8) as i32) - 1;
+            // NB: backward jumps are no cheaper than forward jumps, otherwise the optimized
+            // aligner may miss a backward jump from the end of a contig whose suffix is clipped
+            if rng.below(2) == 0 {
+                scoring.jump_score_backward = Some(jump_score - rng.below(4) as i32);
+            }
+
+            let mut reference = ReferenceAligner::new(scoring);
+            let double_strand = rng.below(2) == 0;
+            for name in ["chr1", "chr2"].iter().take(rng.below(2) + 1) {
+                let len = rng.below(10) + 1;
+                let seq = random_dna(&mut rng, len);
+                reference.add_contig(name, true, &seq);
+                if double_strand {
+                    reference.add_contig(name, false, &reverse_complement(&seq));
+                }
+            }
+            let len = rng.below(12) + 1;
+            let y = random_dna(&mut rng, len);
+
+            let expected = reference.score(&y);
+            let alignment = reference.multi_contig_aligner().custom(&y);
+            assert_eq!(
+                alignment.score,
+                expected,
+                "query: {}, scoring: {scoring:?}, alignment: {alignment}",
+                String::from_utf8_lossy(&y)
+            );
+        }
+    }
+}
@@ -0,0 +1,363 @@
+//! Dumps of the traceback matrices of the alignment of a query, with the metadata of the contigs
+//! needed to interpret them, to a compact binary file, so that alignments may be traced back
+//! later (e.g. choosing differently between equally good alignment ends) without re-running the
+//! dynamic programming.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    ops::Range,
+    path::Path,
+};
+
+use anyhow::{ensure, Context, Result};
+use bio::alignment::pairwise::MatchFunc;
+use bit_set::BitSet;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    alignment_ends, best_alignment_end_with, traceback_all_from, traceback_from, EndTieBreak,
+    Traceback, TracebackMatrices,
+};
+use crate::align::{
+    aligners::{
+        constants::MIN_SCORE, multi_contig_aligner::region_to_contig_coordinates,
+        single_contig_aligner::SingleContigAligner,
+    },
+    alignment::Alignment,
+    position::ContigIdx,
+};
+
+/// The bytes at the start of a traceback dump.
+const MAGIC: &[u8; 4] = b"STBD";
+
+/// The version of the format of traceback dumps, incremented when the format changes.
+pub const TRACEBACK_DUMP_VERSION: u32 = 1;
+
+/// The traceback matrices of the alignment of a query to a contig, with the metadata of the
+/// contig.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContigTraceback {
+    /// The index of the contig.
+    pub contig_idx: usize,
+    /// The name of the contig.
+    pub name: String,
+    /// True if the contig is the forward strand.
+    pub is_forward: bool,
+    /// True if the contig is circular.
+    pub circular: bool,
+    /// The aligned region of the contig.
+    pub region: Range<usize>,
+    /// The length of the full contig.
+    pub contig_len: usize,
+    traceback: Traceback,
+    /// The best score of an alignment ending in each row of the last column.
+    last_column: Vec<i32>,
+    /// The number of contig bases suffix clipped before each column.
+    x_suffix_clip_lens: Vec<usize>,
+    /// The number of query bases before the suffix clip of the query in each row.
+    y_suffix_clip_starts: Vec<usize>,
+}
+
+impl ContigTraceback {
+    /// Copies the traceback matrices of the alignment of a query of length `n` by the given
+    /// aligner to the given region of a contig.
+    pub(crate) fn new<F: MatchFunc>(
+        aligner: &SingleContigAligner<F>,
+        n: usize,
+        name: &str,
+        is_forward: bool,
+        region: Range<usize>,
+        contig_len: usize,
+    ) -> Self {
+        let rows = aligner.traceback.rows;
+        Self {
+            contig_idx: aligner.contig_idx.get(),
+            name: name.to_string(),
+            is_forward,
+            circular: aligner.circular,
+            region,
+            contig_len,
+            traceback: aligner.traceback.clone(),
+            last_column: aligner.S[n % 2].iter().take(rows).copied().collect(),
+            x_suffix_clip_lens: aligner.Lx.iter().take(n + 1).copied().collect(),
+            y_suffix_clip_starts: aligner.Ly.iter().take(rows).copied().collect(),
+        }
+    }
+}
+
+impl TracebackMatrices for ContigTraceback {
+    fn contig_idx(&self) -> ContigIdx {
+        ContigIdx::new(self.contig_idx)
+    }
+
+    fn traceback(&self) -> &Traceback {
+        &self.traceback
+    }
+
+    fn last_column_score(&self, i: usize, _n: usize) -> i32 {
+        self.last_column.get(i).copied().unwrap_or(MIN_SCORE)
+    }
+
+    fn x_suffix_clip_len(&self, j: usize) -> usize {
+        self.x_suffix_clip_lens[j]
+    }
+
+    fn y_suffix_clip_start(&self, i: usize) -> usize {
+        self.y_suffix_clip_starts[i]
+    }
+}
+
+/// The traceback matrices of the alignment of a query to each contig, in the order in which the
+/// contigs were evaluated (see [`crate::align::MultiContigAligner::dump_traceback`]).
+///
+/// A dump is written as a few magic bytes and the version of the format, followed by the
+/// zlib-compressed `bincode` serialization of the dump, and may only be read by a build with the
+/// same traceback cells (i.e. with or without `low_mem`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TracebackDump {
+    low_mem: bool,
+    query_len: usize,
+    contigs: Vec<ContigTraceback>,
+}
+
+impl TracebackDump {
+    pub(crate) fn new(query_len: usize, contigs: Vec<ContigTraceback>) -> Self {
+        Self {
+            low_mem: cfg!(low_mem),
+            query_len,
+            contigs,
+        }
+    }
+
+    /// The length of the query aligned.
+    pub fn query_len(&self) -> usize {
+        self.query_len
+    }
+
+    /// The tracebacks of the contigs, in the order in which they were evaluated.
+    pub fn contigs(&self) -> &[ContigTraceback] {
+        &self.contigs
+    }
+
+    /// Writes the dump.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&TRACEBACK_DUMP_VERSION.to_le_bytes())?;
+        let mut encoder = ZlibEncoder::new(writer, Compression::default());
+        bincode::serialize_into(&mut encoder, self)
+            .context("Could not serialize the traceback dump")?;
+        encoder.finish()?.flush()?;
+        Ok(())
+    }
+
+    /// Reads a dump written by [`Self::write`].
+    pub fn read<R: Read>(mut reader: R) -> Result<Self> {
+        let mut magic = [0u8; MAGIC.len()];
+        reader
+            .read_exact(&mut magic)
+            .context("Could not read the traceback dump")?;
+        ensure!(&magic == MAGIC, "Not a traceback dump");
+        let mut version = [0u8; 4];
+        reader
+            .read_exact(&mut version)
+            .context("Could not read the traceback dump")?;
+        let version = u32::from_le_bytes(version);
+        ensure!(
+            version == TRACEBACK_DUMP_VERSION,
+            "Unsupported traceback dump version: {version} (expected {TRACEBACK_DUMP_VERSION})"
+        );
+        let dump: Self = bincode::deserialize_from(ZlibDecoder::new(reader))
+            .context("Could not parse the traceback dump")?;
+        ensure!(
+            dump.low_mem == cfg!(low_mem),
+            "The traceback dump was written by a build with{} low_mem",
+            if dump.low_mem { "" } else { "out" }
+        );
+        Ok(dump)
+    }
+
+    /// Writes the dump to the given file.
+    pub fn to_path(&self, path: &Path) -> Result<()> {
+        let file =
+            File::create(path).with_context(|| format!("Could not create: {}", path.display()))?;
+        self.write(BufWriter::new(file))
+    }
+
+    /// Reads the dump from the given file.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Could not open: {}", path.display()))?;
+        Self::read(BufReader::new(file))
+            .with_context(|| format!("Invalid traceback dump: {}", path.display()))
+    }
+
+    /// Converts the alignment from the coordinates of the aligned region of each contig to the
+    /// coordinates of the full contig, and finds its segments (see [`Alignment::find_segments`]).
+    fn finish_alignment(&self, alignment: Alignment) -> Alignment {
+        let contig_of = |contig_idx: usize| {
+            self.contigs
+                .iter()
+                .find(|c| c.contig_idx == contig_idx)
+                .unwrap()
+        };
+        let mut alignment = if self
+            .contigs
+            .iter()
+            .all(|c| c.region.start == 0 && c.region.len() == c.contig_len)
+        {
+            alignment
+        } else {
+            region_to_contig_coordinates(alignment, |contig_idx| {
+                let contig = contig_of(contig_idx);
+                (contig.region.start, contig.region.len(), contig.contig_len)
+            })
+        };
+        alignment.segments = alignment.find_segments(|contig_idx| {
+            let contig = contig_of(contig_idx);
+            (contig.name.clone(), contig.is_forward)
+        });
+        alignment
+    }
+
+    /// Traces back the best alignment, choosing between alignment ends with equal scores with the
+    /// given tie-break, or returns `None` if there are no contigs.
+    pub fn traceback(&self, tie_break: EndTieBreak) -> Option<Alignment> {
+        let contigs = self.contigs.iter().collect_vec();
+        if contigs.is_empty() {
+            return None;
+        }
+        let contig_idx = best_alignment_end_with(&contigs, self.query_len, tie_break);
+        traceback_from(&contigs, self.query_len, contig_idx)
+            .map(|alignment| self.finish_alignment(alignment))
+    }
+
+    /// Traces back the best alignment ending in the contig with the given index, or returns
+    /// `None` if the contig (or a contig it jumps from) is not in the dump.
+    pub fn traceback_from(&self, contig_index: usize) -> Option<Alignment> {
+        let contigs = self.contigs.iter().collect_vec();
+        if contigs.is_empty() {
+            return None;
+        }
+        traceback_from(&contigs, self.query_len, ContigIdx::new(contig_index))
+            .map(|alignment| self.finish_alignment(alignment))
+    }
+
+    /// Traces back the best alignment ending in each of the given contigs (or all contigs), as
+    /// [`crate::align::MultiContigAligner::traceback_all`], choosing between alignment ends with
+    /// equal scores with the given tie-break.
+    pub fn traceback_all(
+        &self,
+        contig_indexes: Option<&BitSet<u32>>,
+        tie_break: EndTieBreak,
+    ) -> Vec<Alignment> {
+        let contigs = self.contigs.iter().collect_vec();
+        if contigs.is_empty() {
+            return Vec::new();
+        }
+        let contig_indexes_to_consider = match contig_indexes {
+            Some(indexes) => indexes.clone(),
+            None => self
+                .contigs
+                .iter()
+                .map(|contig| contig.contig_idx)
+                .collect::<BitSet<u32>>(),
+        };
+        let ends = alignment_ends(&contigs, self.query_len);
+        traceback_all_from(
+            &ends,
+            &contig_indexes_to_consider,
+            tie_break,
+            |contig_idx| traceback_from(&contigs, self.query_len, contig_idx),
+        )
+        .into_iter()
+        .map(|alignment| self.finish_alignment(alignment))
+        .collect_vec()
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use super::{TracebackDump, MAGIC};
+    use crate::align::{traceback::EndTieBreak, MultiContigAligner, Scoring};
+
+    const X1: &[u8] = b"GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const X2: &[u8] = b"TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-3, -1, -2, MatchParams::new(1, -1))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    fn round_trip(dump: &TracebackDump) -> TracebackDump {
+        let mut bytes = Vec::new();
+        dump.write(&mut bytes).unwrap();
+        assert!(bytes.starts_with(MAGIC));
+        TracebackDump::read(bytes.as_slice()).unwrap()
+    }
+
+    #[rstest]
+    fn test_dump_traceback() {
+        let y = [&X1[10..20], &X2[25..35], &X1[5..15]].concat();
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig_region("chr1", true, X1, 3..22, false, scoring());
+        aligner.add_contig_region("chr2", true, X2, 20..38, false, scoring());
+        let expected = aligner.custom(&y);
+
+        let dump = round_trip(&aligner.dump_traceback(y.len(), None).unwrap());
+        assert_eq!(dump.query_len(), y.len());
+        assert_eq!(dump.contigs().len(), 2);
+        assert_eq!(dump.contigs()[1].name, "chr2");
+        assert_eq!(dump.contigs()[1].region, 20..38);
+        assert_eq!(dump.traceback(EndTieBreak::Longest), Some(expected.clone()));
+        assert_eq!(dump.traceback_from(0), Some(expected));
+        assert_eq!(
+            dump.traceback_all(None, EndTieBreak::Longest),
+            aligner.traceback_all(y.len(), None)
+        );
+
+        // not a dump, or another version
+        assert!(TracebackDump::read(b"STBX\x01\0\0\0".as_slice()).is_err());
+        let mut bytes = Vec::new();
+        dump.write(&mut bytes).unwrap();
+        bytes[MAGIC.len()] += 1;
+        assert!(TracebackDump::read(bytes.as_slice()).is_err());
+    }
+
+    #[rstest]
+    fn test_end_tie_break() {
+        // two identical contigs, so both alignment ends have the same score and length
+        let y = &X1[5..35];
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("chr1", true, X1, false, scoring());
+        aligner.add_contig("chr2", true, X1, false, scoring());
+        let expected = aligner.custom(y);
+        assert_eq!(expected.end_contig_idx, 0);
+
+        let dump = round_trip(&aligner.dump_traceback(y.len(), None).unwrap());
+        for (tie_break, contig_idx) in [
+            (EndTieBreak::Longest, 0),
+            (EndTieBreak::Shortest, 0),
+            (EndTieBreak::First, 0),
+            (EndTieBreak::Last, 1),
+        ] {
+            let alignment = dump.traceback(tie_break).unwrap();
+            assert_eq!(alignment.start_contig_idx, contig_idx);
+            assert_eq!(alignment.end_contig_idx, contig_idx);
+            assert_eq!(alignment.score, expected.score);
+        }
+        let contigs = dump
+            .traceback_all(None, EndTieBreak::Last)
+            .iter()
+            .map(|alignment| alignment.end_contig_idx)
+            .collect::<Vec<_>>();
+        assert_eq!(contigs, vec![1, 0]);
+    }
+}
@@ -8,16 +8,19 @@ use super::{
         single_contig_aligner::SingleContigAligner,
     },
     alignment::Alignment,
+    position::{ContigIdx, ContigPos},
 };
+use anyhow::{anyhow, Error};
 use bio::alignment::pairwise::MatchFunc;
 use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
 
 #[derive(Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct SValue {
     pub tb: u16,
     pub len: u32,
-    pub idx: u32,
-    pub from: u32,
+    pub idx: ContigIdx,
+    pub from: ContigPos,
 }
 
 pub trait TracebackCell: Clone {
@@ -27,7 +30,7 @@ pub trait TracebackCell: Clone {
     fn set_i(&mut self, tb: u16, len: u32);
     fn set_d(&mut self, tb: u16, len: u32);
     fn set_s(&mut self, tb: u16, len: u32);
-    fn set_s_all(&mut self, tb: u16, len: u32, idx: u32, from: u32);
+    fn set_s_all(&mut self, tb: u16, len: u32, idx: ContigIdx, from: ContigPos);
     /// Set all matrices to the same value.
     fn set_all(&mut self, tb: u16, len: u32) {
         self.set_i(tb, len);
@@ -43,6 +46,17 @@ pub trait TracebackCell: Clone {
     fn get_s_len(self) -> u32;
 }
 
+/// Narrows the contig index and position of an S value to the `u32`s stored in a traceback cell,
+/// panicking if either does not fit in the cell.
+#[inline(always)]
+fn narrow_s_value<C: TracebackCell>(idx: ContigIdx, from: ContigPos) -> (u32, u32) {
+    let idx = idx.to_u32().unwrap();
+    let from = from.to_u32().unwrap();
+    assert!(idx <= C::max_num_contigs());
+    assert!(from <= C::max_target_len());
+    (idx, from)
+}
+
 // Traceback moves
 pub const TB_START: u16 = 0b0000;
 pub const TB_INS: u16 = 0b0001; // 1
@@ -56,7 +70,11 @@ pub const TB_YCLIP_SUFFIX: u16 = 0b1000; // suffix clip of y (8)
 pub const TB_XJUMP: u16 = 0b1001; // jump (9)
 pub const TB_MAX: u16 = 0b1001; // Useful in checking that the TB value we got is a valid one
 
+#[cfg(feature = "serde")]
+pub mod dump;
+#[cfg_attr(low_mem, allow(dead_code))]
 pub mod packed_length_cell;
+#[cfg_attr(not(low_mem), allow(dead_code))]
 pub mod simple_cell;
 
 cfg_if::cfg_if! {
@@ -71,12 +89,78 @@ pub fn default() -> Cell {
     Cell::default()
 }
 
+/// The order in which the cells of the traceback are stored (see [`Traceback::set_layout`]).
+#[derive(
+    Default, Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Copy, Clone, Serialize, Deserialize,
+)]
+pub enum TracebackLayout {
+    /// The cells of each row (contig base) are stored together.
+    #[default]
+    RowMajor,
+    /// The cells of each column (query base) are stored together, in the order in which they are
+    /// filled.
+    ColumnMajor,
+}
+
+impl Display for TracebackLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RowMajor => write!(f, "row-major"),
+            Self::ColumnMajor => write!(f, "column-major"),
+        }
+    }
+}
+
+impl FromStr for TracebackLayout {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "row-major" | "row" => Ok(TracebackLayout::RowMajor),
+            "column-major" | "column" => Ok(TracebackLayout::ColumnMajor),
+            _ => Err(anyhow!("Invalid traceback layout: {}", s)),
+        }
+    }
+}
+
 /// Internal traceback.
+///
+/// In the row-major layout (see [`TracebackLayout`]), the cells are stored by row, with `stride`
+/// cells per row, of which the first `cols` are in use, so that columns may be added without
+/// moving every cell (see [`Traceback::extend_cols`]).  In the column-major layout, the cells are
+/// stored by column, with `rows` cells per column, so that columns are added at the end.
+///
+/// When checkpointing (see [`Traceback::set_checkpoint_interval`]), only a window of the columns
+/// is stored, of `stride` columns, along with the first and last columns.
 #[derive(Default, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
 pub struct Traceback {
     rows: usize,
     cols: usize,
+    stride: usize,
     matrix: Vec<Cell>,
+    /// The order in which the cells of `matrix` are stored.
+    layout: TracebackLayout,
+    /// The order in which the cells are stored from the next [`Traceback::init`].
+    next_layout: TracebackLayout,
+    /// The number of columns between checkpoints, when checkpointing.
+    checkpoint_interval: Option<usize>,
+    /// The first column of the window, when checkpointing.
+    window_start: usize,
+    /// The first column, when checkpointing.
+    first: Vec<Cell>,
+    /// The last column, when checkpointing.
+    last: Vec<Cell>,
+    /// The cell from which each row is suffix clipped in y, when checkpointing (see
+    /// [`Traceback::y_suffix_clip_cell`]).
+    y_suffix_clip_cells: Vec<Cell>,
+}
+
+/// Returns the cell to which every cell of the traceback is initialized.
+fn start_cell() -> Cell {
+    let mut start = crate::align::traceback::default();
+    start.set_all(TB_START, 0);
+    start.set_s_all(TB_START, 0, ContigIdx::new(0), ContigPos::new(0));
+    start
 }
 
 impl Traceback {
@@ -86,109 +170,394 @@ impl Traceback {
         Traceback {
             rows,
             cols,
+            stride: cols,
             matrix: Vec::with_capacity(rows * cols),
+            ..Default::default()
         }
     }
 
+    /// Sets the number of columns between checkpoints, or `None` to store every column, taking
+    /// effect from the next [`Self::init`].  When checkpointing, only the first and last columns
+    /// and a window of `interval + 1` columns are stored, starting at a checkpoint column (a
+    /// multiple of `interval`), so the memory of the traceback is that of `interval + 3` columns.
+    /// The other columns must be recomputed from the checkpoint before them to be read (see
+    /// [`Self::is_resident`] and [`Self::set_window_start`]).
+    pub fn set_checkpoint_interval(&mut self, interval: Option<usize>) {
+        assert!(
+            interval.is_none_or(|interval| interval > 0),
+            "The checkpoint interval must be positive"
+        );
+        self.checkpoint_interval = interval;
+    }
+
+    /// Sets the order in which the cells are stored, taking effect from the next [`Self::init`].
+    /// The alignment matrices are filled column by column, so storing the cells by column keeps
+    /// the cells of a column together in memory, which may be faster for long contigs, while
+    /// storing them by row may be faster for long queries.
+    pub fn set_layout(&mut self, layout: TracebackLayout) {
+        self.next_layout = layout;
+    }
+
     pub fn init(&mut self, m: usize, n: usize) {
         self.matrix.clear();
-        let mut start = crate::align::traceback::default();
-        start.set_all(TB_START, 0);
-        start.set_s_all(TB_START, 0, 0, 0);
-        // set every cell to start
-        self.resize(m, n, start);
+        self.layout = self.next_layout;
+        match self.checkpoint_interval {
+            None => {
+                self.first.clear();
+                self.last.clear();
+                self.y_suffix_clip_cells.clear();
+                // set every cell to start
+                self.resize(m, n, start_cell());
+            }
+            Some(interval) => {
+                self.rows = m + 1;
+                self.cols = n + 1;
+                self.stride = interval + 1;
+                self.window_start = 0;
+                self.matrix.resize(self.rows * self.stride, start_cell());
+                for column in [
+                    &mut self.first,
+                    &mut self.last,
+                    &mut self.y_suffix_clip_cells,
+                ] {
+                    column.clear();
+                    column.resize(self.rows, start_cell());
+                }
+            }
+        }
+    }
+
+    /// Adds columns so that the traceback has `n + 1` columns, keeping the existing cells and
+    /// initializing the new cells as in [`Self::init`].  The storage of each row at least doubles
+    /// when it is full, so that adding columns one at a time takes amortized constant time per
+    /// cell.
+    pub fn extend_cols(&mut self, n: usize) {
+        assert!(
+            self.checkpoint_interval.is_none(),
+            "Cannot add columns to a checkpointed traceback"
+        );
+        let cols = n + 1;
+        assert!(
+            cols >= self.cols,
+            "Cannot remove columns from the traceback"
+        );
+        if self.layout == TracebackLayout::ColumnMajor {
+            // NB: the vector doubles its capacity when full
+            self.matrix.resize(self.rows * cols, start_cell());
+        } else if cols > self.stride {
+            let stride = cols.max(2 * self.stride);
+            let mut matrix = vec![start_cell(); self.rows * stride];
+            for i in 0..self.rows {
+                let row = &self.matrix[i * self.stride..i * self.stride + self.cols];
+                matrix[i * stride..i * stride + self.cols].copy_from_slice(row);
+            }
+            self.matrix = matrix;
+            self.stride = stride;
+        }
+        self.cols = cols;
+    }
+
+    /// True if the cells of column `j` are stored, which is always the case unless checkpointing
+    /// (see [`Self::set_checkpoint_interval`]).
+    pub fn is_resident(&self, j: usize) -> bool {
+        match self.checkpoint_interval {
+            None => true,
+            Some(interval) => {
+                j == 0
+                    || j + 1 == self.cols
+                    || (self.window_start <= j && j <= self.window_start + interval)
+            }
+        }
+    }
+
+    /// Moves the window of stored columns to start at the given checkpoint column, when
+    /// checkpointing (see [`Self::set_checkpoint_interval`]).  If `keep` is true, the window
+    /// follows on from the current window, whose last column becomes the first column of the
+    /// window, otherwise the cells of the window are left to be recomputed.
+    pub fn set_window_start(&mut self, j: usize, keep: bool) {
+        let interval = self
+            .checkpoint_interval
+            .expect("The traceback is not checkpointed");
+        debug_assert!(j.is_multiple_of(interval));
+        if keep {
+            debug_assert!(j == self.window_start + interval);
+            match self.layout {
+                TracebackLayout::RowMajor => {
+                    for i in 0..self.rows {
+                        self.matrix[i * self.stride] = self.matrix[i * self.stride + interval];
+                    }
+                }
+                TracebackLayout::ColumnMajor => {
+                    let start = interval * self.rows;
+                    self.matrix.copy_within(start..start + self.rows, 0);
+                }
+            }
+        }
+        self.window_start = j;
     }
 
+    /// The offset in `matrix` of the cell at `(i, j)`, unless stored in the first or last column
+    /// when checkpointing.
     #[inline(always)]
-    pub fn set(&mut self, i: usize, j: usize, v: Cell) {
+    fn offset(&self, i: usize, j: usize) -> usize {
         debug_assert!(i < self.rows);
         debug_assert!(j < self.cols);
-        self.matrix[i * self.cols + j] = v;
+        debug_assert!(self.is_resident(j), "Column {j} is not stored");
+        match self.layout {
+            TracebackLayout::RowMajor => i * self.stride + j - self.window_start,
+            TracebackLayout::ColumnMajor => (j - self.window_start) * self.rows + i,
+        }
+    }
+
+    #[inline(always)]
+    pub fn set(&mut self, i: usize, j: usize, v: Cell) {
+        *self.get_mut(i, j) = v;
     }
 
     #[inline(always)]
     pub fn get(&self, i: usize, j: usize) -> &Cell {
-        debug_assert!(i < self.rows);
-        debug_assert!(j < self.cols);
-        &self.matrix[i * self.cols + j]
+        if self.checkpoint_interval.is_some() {
+            if j == 0 {
+                return &self.first[i];
+            } else if j + 1 == self.cols {
+                return &self.last[i];
+            }
+        }
+        &self.matrix[self.offset(i, j)]
     }
 
+    #[inline(always)]
     pub fn get_mut(&mut self, i: usize, j: usize) -> &mut Cell {
-        debug_assert!(i < self.rows);
-        debug_assert!(j < self.cols);
-        &mut self.matrix[i * self.cols + j]
+        if self.checkpoint_interval.is_some() {
+            if j == 0 {
+                return &mut self.first[i];
+            } else if j + 1 == self.cols {
+                return &mut self.last[i];
+            }
+        }
+        let offset = self.offset(i, j);
+        &mut self.matrix[offset]
+    }
+
+    /// Keeps the cell from which row `i` is suffix clipped in y, when checkpointing, since its
+    /// column may no longer be stored when the suffix clip is applied (see
+    /// [`Self::y_suffix_clip_cell`]).
+    #[inline(always)]
+    pub fn keep_y_suffix_clip_cell(&mut self, i: usize, cell: Cell) {
+        if self.checkpoint_interval.is_some() {
+            self.y_suffix_clip_cells[i] = cell;
+        }
+    }
+
+    /// The cell at `(i, j)`, from which row `i` is suffix clipped in y, whether or not column `j`
+    /// is stored (see [`Self::keep_y_suffix_clip_cell`]).
+    pub fn y_suffix_clip_cell(&self, i: usize, j: usize) -> &Cell {
+        if self.is_resident(j) {
+            self.get(i, j)
+        } else {
+            &self.y_suffix_clip_cells[i]
+        }
+    }
+
+    /// The number of cells for which the traceback has storage.
+    pub fn capacity(&self) -> usize {
+        self.matrix.capacity()
+    }
+
+    /// Moves the storage of the cells out of the traceback, leaving the traceback empty until the
+    /// next [`Self::init`], so that the storage may be reused by another traceback.
+    pub fn take_cells(&mut self) -> Vec<Cell> {
+        std::mem::take(&mut self.matrix)
+    }
+
+    /// Replaces the storage of the cells with the given storage, to be filled by the next
+    /// [`Self::init`].
+    pub fn set_cells(&mut self, mut cells: Vec<Cell>) {
+        cells.clear();
+        self.matrix = cells;
     }
 
     pub fn resize(&mut self, m: usize, n: usize, v: Cell) {
         self.rows = m + 1;
         self.cols = n + 1;
+        self.stride = self.cols;
+        // Every column is stored, so the window starts at the first column, whichever column the
+        // window of a previous checkpointed traceback started at
+        self.window_start = 0;
         self.matrix.resize(self.rows * self.cols, v);
     }
 }
 
-pub fn traceback<F: MatchFunc>(aligners: &[&SingleContigAligner<F>], n: usize) -> Alignment {
-    let mut aligner_offset = 0;
+/// The matrices of the alignment of a query to a contig read when tracing back an alignment, namely
+/// the traceback, the scores of the last column, and the lengths of the suffix clips.
+pub trait TracebackMatrices {
+    /// The index of the contig.
+    fn contig_idx(&self) -> ContigIdx;
+    /// The traceback of the alignment to the contig.
+    fn traceback(&self) -> &Traceback;
+    /// The best score of an alignment ending in row `i` of the last column, column `n`.
+    fn last_column_score(&self, i: usize, n: usize) -> i32;
+    /// The number of contig bases suffix clipped before column `j`.
+    fn x_suffix_clip_len(&self, j: usize) -> usize;
+    /// The number of query bases before the suffix clip of the query in row `i`.
+    fn y_suffix_clip_start(&self, i: usize) -> usize;
+}
+
+impl<F: MatchFunc> TracebackMatrices for SingleContigAligner<F> {
+    fn contig_idx(&self) -> ContigIdx {
+        self.contig_idx
+    }
+
+    fn traceback(&self) -> &Traceback {
+        &self.traceback
+    }
+
+    fn last_column_score(&self, i: usize, n: usize) -> i32 {
+        self.S[n % 2][i]
+    }
+
+    fn x_suffix_clip_len(&self, j: usize) -> usize {
+        self.Lx[j]
+    }
+
+    fn y_suffix_clip_start(&self, i: usize) -> usize {
+        self.Ly[i]
+    }
+}
+
+/// How to choose between alignment ends with the same score (see [`alignment_ends`]).
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum EndTieBreak {
+    /// The end of the longest alignment, then the first.
+    #[default]
+    Longest,
+    /// The end of the shortest alignment, then the first.
+    Shortest,
+    /// The first end, in the order in which the contigs are evaluated.
+    First,
+    /// The last end, in the order in which the contigs are evaluated.
+    Last,
+}
+
+/// The score and length of the best alignment ending in the last row and column of the matrices
+/// of a contig (see [`alignment_ends`]).
+#[derive(Copy, Clone, Debug)]
+pub struct AlignmentEnd {
+    pub contig_idx: ContigIdx,
+    pub score: i32,
+    pub len: u32,
+}
+
+/// Returns the end of the best alignment in each of the given aligners, in the same order, where
+/// an aligner without a traceback has no alignment better than the minimum score.
+pub fn alignment_ends<T: TracebackMatrices>(aligners: &[&T], n: usize) -> Vec<AlignmentEnd> {
+    aligners
+        .iter()
+        .map(|aligner| {
+            if aligner.traceback().matrix.is_empty() {
+                return AlignmentEnd {
+                    contig_idx: aligner.contig_idx(),
+                    score: MIN_SCORE,
+                    len: 0,
+                };
+            }
+            let m: usize = aligner.traceback().rows - 1;
+            AlignmentEnd {
+                contig_idx: aligner.contig_idx(),
+                score: aligner.last_column_score(m, n),
+                len: aligner.traceback().get(m, n).get_s_len(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the contig index of the best of the alignment ends for which `include` is true,
+/// choosing between ends with equal scores with the given tie-break.  Returns the contig index of
+/// the first end for which `include` is true (or the first end) if none is better than the
+/// minimum score.
+fn best_end(
+    ends: &[AlignmentEnd],
+    include: impl Fn(ContigIdx) -> bool,
+    tie_break: EndTieBreak,
+) -> ContigIdx {
+    let mut best = ends
+        .iter()
+        .find(|end| include(end.contig_idx))
+        .unwrap_or(&ends[0])
+        .contig_idx;
     let mut score = MIN_SCORE;
     let mut alignment_length = 0;
-    for (cur_aligner_offset, cur_aligner) in aligners.iter().enumerate() {
-        let m: usize = cur_aligner.traceback.rows - 1;
-        let cur_score = cur_aligner.S[n % 2][m];
-        let cur_len = cur_aligner.traceback.get(m, n).get_s_len();
-        // NB: If the scores equal, pick the one with the longer alignment length
-        let update = match cur_score.cmp(&score) {
+    for end in ends.iter().filter(|end| include(end.contig_idx)) {
+        // NB: If the scores equal, pick by the tie-break (by default, the longer alignment)
+        let update = match end.score.cmp(&score) {
             std::cmp::Ordering::Less => false,
             std::cmp::Ordering::Greater => true,
-            std::cmp::Ordering::Equal => cur_len > alignment_length,
+            std::cmp::Ordering::Equal => match tie_break {
+                EndTieBreak::Longest => end.len > alignment_length,
+                EndTieBreak::Shortest => end.len < alignment_length,
+                EndTieBreak::First => false,
+                EndTieBreak::Last => true,
+            },
         };
         if update {
-            aligner_offset = cur_aligner_offset;
-            score = cur_score;
-            alignment_length = cur_len;
+            best = end.contig_idx;
+            score = end.score;
+            alignment_length = end.len;
         }
     }
-    traceback_from(aligners, n, aligners[aligner_offset].contig_idx).unwrap()
+    best
+}
+
+/// Returns the contig index of the best alignment end (see [`alignment_ends`]).
+pub fn best_alignment_end<T: TracebackMatrices>(aligners: &[&T], n: usize) -> ContigIdx {
+    best_alignment_end_with(aligners, n, EndTieBreak::default())
 }
 
-pub fn traceback_all<F: MatchFunc>(
-    aligners: &[&SingleContigAligner<F>],
+/// Returns the contig index of the best alignment end (see [`alignment_ends`]), choosing between
+/// ends with equal scores with the given tie-break.
+pub fn best_alignment_end_with<T: TracebackMatrices>(
+    aligners: &[&T],
     n: usize,
+    tie_break: EndTieBreak,
+) -> ContigIdx {
+    best_end(&alignment_ends(aligners, n), |_| true, tie_break)
+}
+
+pub fn traceback<T: TracebackMatrices>(aligners: &[&T], n: usize) -> Alignment {
+    traceback_from(aligners, n, best_alignment_end(aligners, n)).unwrap()
+}
+
+/// Returns the best alignment ending in each contig to consider, from the best to the worst, until
+/// every contig to consider is in an alignment, given the alignment ends (see [`alignment_ends`])
+/// and a function to trace back an alignment from the end in a given contig.  Ends with equal
+/// scores are chosen between with the given tie-break.
+pub fn traceback_all_from(
+    ends: &[AlignmentEnd],
     contig_indexes_to_consider: &BitSet<u32>,
+    tie_break: EndTieBreak,
+    mut traceback_from: impl FnMut(ContigIdx) -> Option<Alignment>,
 ) -> Vec<Alignment> {
     let mut alignments = Vec::new();
+    if ends.is_empty() {
+        return alignments;
+    }
     let mut contig_indexes_seen: BitSet<u32> =
         BitSet::with_capacity(contig_indexes_to_consider.len());
 
     while contig_indexes_seen.len() < contig_indexes_to_consider.len() {
         // Get the highest scoring alignment that _ends_ in a contig we haven't seen
-        let mut aligner_offset = 0;
-        let mut score = MIN_SCORE;
-        let mut alignment_length = 0;
-        for (cur_aligner_offset, cur_aligner) in aligners.iter().enumerate() {
-            if !contig_indexes_to_consider.contains(cur_aligner.contig_idx as usize) {
-                continue;
-            }
-            if contig_indexes_seen.contains(cur_aligner.contig_idx as usize) {
-                continue;
-            }
-            let m: usize = cur_aligner.traceback.rows - 1;
-            let cur_score = cur_aligner.S[n % 2][m];
-            let cur_len = cur_aligner.traceback.get(m, n).get_s_len();
-            // NB: If the scores equal, pick the one with the longer alignment length
-            let update = match cur_score.cmp(&score) {
-                std::cmp::Ordering::Less => false,
-                std::cmp::Ordering::Greater => true,
-                std::cmp::Ordering::Equal => cur_len > alignment_length,
-            };
-            if update {
-                aligner_offset = cur_aligner_offset;
-                score = cur_score;
-                alignment_length = cur_len;
-            }
-        }
+        let contig_idx = best_end(
+            ends,
+            |contig_idx| {
+                contig_indexes_to_consider.contains(contig_idx.get())
+                    && !contig_indexes_seen.contains(contig_idx.get())
+            },
+            tie_break,
+        );
         // Add the contigs from this alignment to the ones already seen
-        match traceback_from(aligners, n, aligners[aligner_offset].contig_idx) {
+        match traceback_from(contig_idx) {
             None => {
-                let contig_index = aligners[aligner_offset].contig_idx as usize;
+                let contig_index = contig_idx.get();
                 if contig_indexes_to_consider.contains(contig_index) {
                     contig_indexes_seen.insert(contig_index);
                 }
@@ -216,158 +585,240 @@ pub fn traceback_all<F: MatchFunc>(
     alignments
 }
 
-pub fn traceback_from<F: MatchFunc>(
-    aligners: &[&SingleContigAligner<F>],
-    n: usize,
-    contig_index: u32,
-) -> Option<Alignment> {
-    let mut j = n;
-    let mut operations: Vec<AlignmentOperation> = Vec::with_capacity(n);
-    let mut xstart: usize = 0usize;
-    let mut ystart: usize = 0usize;
-    let mut yend = n;
-
+/// Returns the index of each aligner with a traceback, by contig index.
+fn aligner_indexes<T: TracebackMatrices>(aligners: &[&T]) -> IndexMap<usize> {
     assert!(!aligners.is_empty());
-
-    let max_contig_idx = aligners.iter().map(|a| a.contig_idx).max().unwrap();
-    let mut contig_idx_to_aligner_idx = IndexMap::new(max_contig_idx as usize);
+    let max_contig_idx = aligners.iter().map(|a| a.contig_idx()).max().unwrap();
+    let mut contig_idx_to_aligner_idx = IndexMap::new(max_contig_idx.get());
     for (aligner_index, aligner) in aligners.iter().enumerate() {
-        if !aligner.traceback.matrix.is_empty() {
-            contig_idx_to_aligner_idx.put_u32(aligner.contig_idx, aligner_index);
+        if !aligner.traceback().matrix.is_empty() {
+            contig_idx_to_aligner_idx.put(aligner.contig_idx().get(), aligner_index);
         }
     }
-    if !contig_idx_to_aligner_idx.contains_u32(contig_index) {
-        return None;
-    }
-    let mut cur_aligner = aligners[contig_idx_to_aligner_idx.get_u32(contig_index).unwrap()];
-    let score = cur_aligner.S[n % 2][cur_aligner.traceback.rows - 1];
-    let alignment_length = cur_aligner
-        .traceback
-        .get(cur_aligner.traceback.rows - 1, n)
-        .get_s_len();
-
-    let contig_idx = cur_aligner.contig_idx;
-    let xlen = cur_aligner.traceback.rows - 1;
-    let mut cur_contig_idx = contig_idx;
-    let mut i = cur_aligner.traceback.rows - 1;
-    let mut xend = cur_aligner.traceback.rows - 1;
-    let mut last_layer = cur_aligner.traceback.get(i, j).get_s().tb;
-    loop {
-        cur_aligner = match contig_idx_to_aligner_idx.get_u32(cur_contig_idx) {
-            None => return None,
-            Some(idx) => aligners[idx],
-        };
-        let next_layer: u16;
-        match last_layer {
-            TB_START => break,
-            TB_INS => {
-                operations.push(AlignmentOperation::Ins);
-                next_layer = cur_aligner.traceback.get(i, j).get_i().0;
-                i -= 1;
-            }
-            TB_DEL => {
-                operations.push(AlignmentOperation::Del);
-                next_layer = cur_aligner.traceback.get(i, j).get_d().0;
-                j -= 1;
+    contig_idx_to_aligner_idx
+}
+
+/// The result of resuming a traceback (see [`TracebackCursor::resume`]).
+pub enum TracebackStep {
+    /// The traceback is done, with the alignment, or `None` if it reached a contig without a
+    /// traceback.
+    Done(Option<Alignment>),
+    /// The traceback stopped at the given column, which is not stored.
+    NeedsColumn(usize),
+}
+
+/// A traceback in progress, from the end of the best alignment in a given contig (see
+/// [`traceback_from`]).  The traceback stops when it reaches a column of the matrices that is not
+/// stored (see [`Traceback::is_resident`]), and may be resumed once the column is recomputed.
+/// Within a contig the columns are visited in decreasing order, so a window of columns need only
+/// be recomputed once.
+pub struct TracebackCursor {
+    i: usize,
+    j: usize,
+    /// The layer of the current move, or `None` if it is that of the S value at `(i, j)`.
+    layer: Option<u16>,
+    operations: Vec<AlignmentOperation>,
+    xstart: usize,
+    ystart: usize,
+    yend: usize,
+    xend: usize,
+    xlen: usize,
+    n: usize,
+    score: i32,
+    alignment_length: u32,
+    contig_idx: ContigIdx,
+    cur_contig_idx: ContigIdx,
+}
+
+impl TracebackCursor {
+    /// Starts the traceback from the end of the best alignment in the contig with the given
+    /// index, or returns `None` if none of the aligners has a traceback for that contig.
+    pub fn new<T: TracebackMatrices>(
+        aligners: &[&T],
+        n: usize,
+        contig_index: ContigIdx,
+    ) -> Option<Self> {
+        let contig_idx_to_aligner_idx = aligner_indexes(aligners);
+        if !contig_idx_to_aligner_idx.contains(contig_index.get()) {
+            return None;
+        }
+        let cur_aligner = aligners[contig_idx_to_aligner_idx.get(contig_index.get()).unwrap()];
+        if cur_aligner.traceback().matrix.is_empty() {
+            return None;
+        }
+        let m = cur_aligner.traceback().rows - 1;
+        Some(Self {
+            i: m,
+            j: n,
+            layer: None,
+            operations: Vec::with_capacity(n),
+            xstart: 0,
+            ystart: 0,
+            yend: n,
+            xend: m,
+            xlen: m,
+            n,
+            score: cur_aligner.last_column_score(m, n),
+            alignment_length: cur_aligner.traceback().get(m, n).get_s_len(),
+            contig_idx: cur_aligner.contig_idx(),
+            cur_contig_idx: cur_aligner.contig_idx(),
+        })
+    }
+
+    /// Continues the traceback until it is done, or reaches a column that is not stored.
+    pub fn resume<T: TracebackMatrices>(&mut self, aligners: &[&T]) -> TracebackStep {
+        let contig_idx_to_aligner_idx = aligner_indexes(aligners);
+        loop {
+            let cur_aligner = match contig_idx_to_aligner_idx.get(self.cur_contig_idx.get()) {
+                None => return TracebackStep::Done(None),
+                Some(idx) => aligners[idx],
+            };
+            let (i, j) = (self.i, self.j);
+            if !cur_aligner.traceback().is_resident(j) {
+                return TracebackStep::NeedsColumn(j);
             }
-            TB_MATCH | TB_SUBST => {
-                if last_layer == TB_MATCH {
-                    operations.push(AlignmentOperation::Match);
-                } else {
-                    operations.push(AlignmentOperation::Subst);
+            let last_layer = self
+                .layer
+                .unwrap_or_else(|| cur_aligner.traceback().get(i, j).get_s().tb);
+            let next_layer: Option<u16>;
+            match last_layer {
+                TB_START => break,
+                TB_INS => {
+                    self.operations.push(AlignmentOperation::Ins);
+                    next_layer = Some(cur_aligner.traceback().get(i, j).get_i().0);
+                    self.i -= 1;
                 }
-                let s_value: SValue = cur_aligner.traceback.get(i, j).get_s();
-                let s_from = s_value.from as usize;
-                if s_value.idx != cur_contig_idx || s_from != i - 1 {
-                    operations.push(AlignmentOperation::Xjump(cur_contig_idx as usize, i - 1));
-                    cur_contig_idx = s_value.idx;
-                    cur_aligner = match contig_idx_to_aligner_idx.get_u32(cur_contig_idx) {
-                        None => return None,
-                        Some(idx) => aligners[idx],
-                    };
+                TB_DEL => {
+                    self.operations.push(AlignmentOperation::Del);
+                    next_layer = Some(cur_aligner.traceback().get(i, j).get_d().0);
+                    self.j -= 1;
                 }
-                i = s_from;
-                j -= 1;
-                next_layer = cur_aligner.traceback.get(s_from, j).get_s().tb;
-            }
-            TB_XCLIP_PREFIX => {
-                next_layer = cur_aligner.traceback.get(0, j).get_s().tb;
-                // only add Xclip if there are only clip moves left, since we may have jumped!
-                if next_layer == TB_START || next_layer == TB_YCLIP_PREFIX {
-                    operations.push(AlignmentOperation::Xclip(i));
-                    xstart = i;
+                TB_MATCH | TB_SUBST => {
+                    if last_layer == TB_MATCH {
+                        self.operations.push(AlignmentOperation::Match);
+                    } else {
+                        self.operations.push(AlignmentOperation::Subst);
+                    }
+                    let s_value: SValue = cur_aligner.traceback().get(i, j).get_s();
+                    let s_from = s_value.from.get();
+                    if s_value.idx != self.cur_contig_idx || s_from != i - 1 {
+                        self.operations
+                            .push(AlignmentOperation::Xjump(self.cur_contig_idx.get(), i - 1));
+                        self.cur_contig_idx = s_value.idx;
+                    }
+                    self.i = s_from;
+                    self.j -= 1;
+                    next_layer = None;
                 }
-                i = 0;
-            }
-            TB_XCLIP_SUFFIX => {
-                if operations.is_empty()
-                    || matches!(operations.first().unwrap(), AlignmentOperation::Yclip(_))
-                {
-                    operations.push(AlignmentOperation::Xclip(cur_aligner.Lx[j]));
-                    xend = i - cur_aligner.Lx[j];
+                TB_XCLIP_PREFIX => {
+                    let layer = cur_aligner.traceback().get(0, j).get_s().tb;
+                    // only add Xclip if there are only clip moves left, since we may have jumped!
+                    if layer == TB_START || layer == TB_YCLIP_PREFIX {
+                        self.operations.push(AlignmentOperation::Xclip(i));
+                        self.xstart = i;
+                    }
+                    self.i = 0;
+                    next_layer = Some(layer);
                 }
-                i -= cur_aligner.Lx[j];
-                next_layer = cur_aligner.traceback.get(i, j).get_s().tb;
-            }
-            TB_YCLIP_PREFIX => {
-                operations.push(AlignmentOperation::Yclip(j));
-                ystart = j;
-                j = 0;
-                next_layer = cur_aligner.traceback.get(i, 0).get_s().tb;
-            }
-            TB_YCLIP_SUFFIX => {
-                operations.push(AlignmentOperation::Yclip(cur_aligner.Ly[i]));
-                let s_from = cur_aligner.traceback.get(i, j).get_s().from as usize;
-                j -= cur_aligner.Ly[i];
-                if s_from != i {
-                    operations.push(AlignmentOperation::Xjump(cur_contig_idx as usize, i));
-                    i = s_from;
+                TB_XCLIP_SUFFIX => {
+                    if self.operations.is_empty()
+                        || matches!(
+                            self.operations.first().unwrap(),
+                            AlignmentOperation::Yclip(_)
+                        )
+                    {
+                        self.operations
+                            .push(AlignmentOperation::Xclip(cur_aligner.x_suffix_clip_len(j)));
+                        self.xend = i - cur_aligner.x_suffix_clip_len(j);
+                    }
+                    self.i -= cur_aligner.x_suffix_clip_len(j);
+                    next_layer = None;
                 }
-                yend = j;
-                next_layer = cur_aligner.traceback.get(i, j).get_s().tb;
-            }
-            TB_XJUMP => {
-                let s_value = cur_aligner.traceback.get(i, j).get_s();
-                operations.push(AlignmentOperation::Xjump(cur_contig_idx as usize, i));
-                cur_contig_idx = s_value.idx;
-                cur_aligner = match contig_idx_to_aligner_idx.get_u32(cur_contig_idx) {
-                    None => return None,
-                    Some(idx) => aligners[idx],
-                };
-                i = s_value.from as usize;
-                next_layer = cur_aligner.traceback.get(i, j).get_s().tb;
+                TB_YCLIP_PREFIX => {
+                    self.operations.push(AlignmentOperation::Yclip(j));
+                    self.ystart = j;
+                    self.j = 0;
+                    next_layer = None;
+                }
+                TB_YCLIP_SUFFIX => {
+                    self.operations.push(AlignmentOperation::Yclip(
+                        j - cur_aligner.y_suffix_clip_start(i),
+                    ));
+                    let s_from = cur_aligner.traceback().get(i, j).get_s().from.get();
+                    self.j = cur_aligner.y_suffix_clip_start(i);
+                    if s_from != i {
+                        self.operations
+                            .push(AlignmentOperation::Xjump(self.cur_contig_idx.get(), i));
+                        self.i = s_from;
+                    }
+                    self.yend = self.j;
+                    next_layer = None;
+                }
+                TB_XJUMP => {
+                    let s_value = cur_aligner.traceback().get(i, j).get_s();
+                    self.operations
+                        .push(AlignmentOperation::Xjump(self.cur_contig_idx.get(), i));
+                    self.cur_contig_idx = s_value.idx;
+                    self.i = s_value.from.get();
+                    next_layer = None;
+                }
+                _ => panic!("Dint expect this!"),
             }
-            _ => panic!("Dint expect this!"),
+            self.layer = next_layer;
         }
-        last_layer = next_layer;
+        TracebackStep::Done(Some(self.alignment()))
     }
 
-    operations.reverse();
-    {
-        use AlignmentOperation::{Xclip, Xjump, Yclip};
-        if operations
-            .iter()
-            .all(|op| matches!(op, Xclip(_) | Yclip(_) | Xjump(_, _)))
+    /// The alignment traced back, once done.
+    fn alignment(&mut self) -> Alignment {
+        let mut operations = std::mem::take(&mut self.operations);
+        operations.reverse();
+        let (mut xstart, mut xend, mut ystart, mut yend) =
+            (self.xstart, self.xend, self.ystart, self.yend);
         {
-            xstart = 0;
-            xend = 0;
-            ystart = 0;
-            yend = 0;
+            use AlignmentOperation::{Xclip, Xjump, Yclip};
+            if operations
+                .iter()
+                .all(|op| matches!(op, Xclip(_) | Yclip(_) | Xjump(_, _)))
+            {
+                xstart = 0;
+                xend = 0;
+                ystart = 0;
+                yend = 0;
+            }
         }
+        Alignment {
+            score: self.score,
+            ystart,
+            xstart,
+            yend,
+            xend,
+            xlen: self.xlen,
+            ylen: self.n,
+            start_contig_idx: self.cur_contig_idx.get(),
+            end_contig_idx: self.contig_idx.get(),
+            operations,
+            mode: AlignmentMode::Custom,
+            length: self.alignment_length as usize,
+            ambiguity: 0,
+            jump_intervals: Vec::new(),
+            score_breakdown: Vec::new(),
+            segments: Vec::new(),
+        }
+    }
+}
+
+/// Traces back the best alignment ending in the contig with the given index, or returns `None` if
+/// it reaches a contig without a traceback.  Every column of the traceback must be stored (see
+/// [`TracebackCursor`] otherwise).
+pub fn traceback_from<T: TracebackMatrices>(
+    aligners: &[&T],
+    n: usize,
+    contig_index: ContigIdx,
+) -> Option<Alignment> {
+    let mut cursor = TracebackCursor::new(aligners, n, contig_index)?;
+    match cursor.resume(aligners) {
+        TracebackStep::Done(alignment) => alignment,
+        TracebackStep::NeedsColumn(j) => panic!("Column {j} of the traceback is not stored"),
     }
-    let alignment = Alignment {
-        score,
-        ystart,
-        xstart,
-        yend,
-        xend,
-        xlen,
-        ylen: n,
-        start_contig_idx: cur_contig_idx as usize,
-        end_contig_idx: contig_idx as usize,
-        operations,
-        mode: AlignmentMode::Custom,
-        length: alignment_length as usize,
-    };
-    Some(alignment)
 }
@@ -2,7 +2,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::align::traceback::TB_MAX;
 
-use super::{SValue, TracebackCell};
+use super::{narrow_s_value, SValue, TracebackCell};
+use crate::align::position::{ContigIdx, ContigPos};
 
 /// Packed representation of one cell of a Smith-Waterman traceback matrix. Stores the
 /// alignment length for each matrix value, and so can be used to break ties to prefer longer
@@ -15,9 +16,11 @@ use super::{SValue, TracebackCell};
 /// - bits 0-3 are reserved for *_POS
 /// - bits 4-31 are reserved for alignment length (27 bits each)
 /// - bit 31 for each of s/i/d is reserved for the upper 3-bits of the jump contig index (i.e. which contig did we jump from)
+///
 /// The `aux` field is packed as follows:
 /// - bits 0-4 are for the lower 5-bits of the jump contig index (i.e. which contig did we jump from)
 /// - bits 5-31 are for the "from" jump index in the contig (i.e. where in the given contig did we jump from)
+///
 /// Also contains s_from (u32), so we have a totoal of u32 * 4 = 128 bits
 #[derive(
     Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize,
@@ -135,9 +138,8 @@ impl TracebackCell for PackedLengthCell {
     }
 
     #[inline(always)]
-    fn set_s_all(&mut self, tb: u16, len: u32, idx: u32, from: u32) {
-        assert!(idx <= Self::max_num_contigs());
-        assert!(from <= Self::max_target_len());
+    fn set_s_all(&mut self, tb: u16, len: u32, idx: ContigIdx, from: ContigPos) {
+        let (idx, from) = narrow_s_value::<Self>(idx, from);
         // Traceback corresponding to matrix S
         self.s = self.set_tb(self.s, tb);
         self.s = self.set_len(self.s, len);
@@ -175,8 +177,8 @@ impl TracebackCell for PackedLengthCell {
         SValue {
             tb: self.get_tb(self.s),
             len: self.get_s_len(),
-            idx: self.get_idx(),
-            from: self.get_from(),
+            idx: ContigIdx::from(self.get_idx()),
+            from: ContigPos::from(self.get_from()),
         }
     }
 }
@@ -186,6 +188,8 @@ impl TracebackCell for PackedLengthCell {
 pub mod tests {
     use rstest::rstest;
 
+    use crate::align::position::{ContigIdx, ContigPos};
+
     use crate::align::traceback::{
         packed_length_cell::PackedLengthCell, SValue, TracebackCell, TB_MAX,
     };
@@ -222,14 +226,14 @@ pub mod tests {
     fn test_set_and_get_s() {
         let mut cell = PackedLengthCell::default();
         for tb in 0..=TB_MAX {
-            cell.set_s_all(0, 0, 0, 0);
+            cell.set_s_all(0, 0, ContigIdx::new(0), ContigPos::new(0));
             assert_eq!(
                 cell.get_s(),
                 SValue {
                     tb: 0,
                     len: 0,
-                    idx: 0,
-                    from: 0
+                    idx: ContigIdx::new(0),
+                    from: ContigPos::new(0)
                 }
             );
             assert_eq!(cell.get_s_len(), 0);
@@ -239,19 +243,19 @@ pub mod tests {
                 SValue {
                     tb,
                     len: 13,
-                    idx: 0,
-                    from: 0
+                    idx: ContigIdx::new(0),
+                    from: ContigPos::new(0)
                 }
             );
             assert_eq!(cell.get_s_len(), 13);
-            cell.set_s_all(tb, 14, 8, 22);
+            cell.set_s_all(tb, 14, ContigIdx::new(8), ContigPos::new(22));
             assert_eq!(
                 cell.get_s(),
                 SValue {
                     tb,
                     len: 14,
-                    idx: 8,
-                    from: 22
+                    idx: ContigIdx::new(8),
+                    from: ContigPos::new(22)
                 }
             );
             assert_eq!(cell.get_s_len(), 14);
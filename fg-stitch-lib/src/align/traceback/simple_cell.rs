@@ -1,6 +1,7 @@
 use crate::align::traceback::TB_MAX;
 
-use super::{SValue, TracebackCell};
+use super::{narrow_s_value, SValue, TracebackCell};
+use crate::align::position::{ContigIdx, ContigPos};
 use serde::{Deserialize, Serialize};
 
 /// Packed representation of one cell of a Smith-Waterman traceback matrix.  Does not store
@@ -15,6 +16,7 @@ use serde::{Deserialize, Serialize};
 /// - bits 4-7 are for deletion traceback (D_POS)
 /// - bits 8-11 are for S matrix traceback (S_POS)
 /// - bit 12-15 are for the upper 4-bits of the jump contig index (i.e. which contig did we jump from)
+///
 /// The `aux` field is packed as follows:
 /// - bits 0-3 are for the lower 4-bits of the jump contig index (i.e. which contig did we jump from)
 /// - bits 4-31 are for the "from" jump index in the contig (i.e. where in the given contig did we jump from)
@@ -84,9 +86,8 @@ impl TracebackCell for SimpleCell {
 
     /// Sets the S traceback with contig index and from information.
     #[inline(always)]
-    fn set_s_all(&mut self, tb: u16, len: u32, idx: u32, from: u32) {
-        assert!(idx <= Self::max_num_contigs());
-        assert!(from <= Self::max_target_len());
+    fn set_s_all(&mut self, tb: u16, len: u32, idx: ContigIdx, from: ContigPos) {
+        let (idx, from) = narrow_s_value::<Self>(idx, from);
         // Traceback corresponding to matrix S
         self.set_s(tb, len);
         // contig index (upper 4 bits, then lower 4bits)
@@ -114,8 +115,8 @@ impl TracebackCell for SimpleCell {
         SValue {
             tb: self.get_tb(TB_S_POS),
             len: 0, // alignment length is never stored, so always zero
-            idx,
-            from,
+            idx: ContigIdx::from(idx),
+            from: ContigPos::from(from),
         }
     }
 
@@ -143,6 +144,8 @@ impl TracebackCell for SimpleCell {
 pub mod tests {
     use rstest::rstest;
 
+    use crate::align::position::{ContigIdx, ContigPos};
+
     use crate::align::traceback::{simple_cell::SimpleCell, SValue, TracebackCell, TB_MAX};
 
     #[rstest]
@@ -175,14 +178,14 @@ pub mod tests {
     fn test_set_and_get_s() {
         let mut cell = SimpleCell::default();
         for tb in 0..=TB_MAX {
-            cell.set_s_all(0, 0, 0, 0);
+            cell.set_s_all(0, 0, ContigIdx::new(0), ContigPos::new(0));
             assert_eq!(
                 cell.get_s(),
                 SValue {
                     tb: 0,
                     len: 0,
-                    idx: 0,
-                    from: 0
+                    idx: ContigIdx::new(0),
+                    from: ContigPos::new(0)
                 }
             );
             assert_eq!(cell.get_s_len(), 0);
@@ -192,19 +195,19 @@ pub mod tests {
                 SValue {
                     tb,
                     len: 0,
-                    idx: 0,
-                    from: 0
+                    idx: ContigIdx::new(0),
+                    from: ContigPos::new(0)
                 }
             );
             assert_eq!(cell.get_s_len(), 0);
-            cell.set_s_all(tb, 14, 8, 22);
+            cell.set_s_all(tb, 14, ContigIdx::new(8), ContigPos::new(22));
             assert_eq!(
                 cell.get_s(),
                 SValue {
                     tb,
                     len: 0,
-                    idx: 8,
-                    from: 22
+                    idx: ContigIdx::new(8),
+                    from: ContigPos::new(22)
                 }
             );
             assert_eq!(cell.get_s_len(), 0);
@@ -0,0 +1,283 @@
+//! Grouping of reads by their unique molecular identifier (UMI), and the consensus of the reads
+//! of each UMI, so that each molecule is aligned once.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use anyhow::{bail, ensure, Error, Result};
+use seq_io::fastq::{OwnedRecord as FastqOwnedRecord, Record};
+
+/// The bases voted on in a consensus, in order of preference on ties.
+const BASES: [u8; 4] = *b"ACGT";
+
+/// The SAM tag for the UMI of a consensus read.
+pub const UMI_TAG: &str = "RX";
+
+/// The SAM tag for the number of reads in the family of a consensus read.
+pub const FAMILY_SIZE_TAG: &str = "cD";
+
+/// The minimum quality of a consensus base.
+const MIN_CONSENSUS_QUALITY: u8 = 2;
+
+/// The maximum quality of a consensus base, so that the encoded quality (`]`) is valid in a SAM
+/// record.
+const MAX_CONSENSUS_QUALITY: u8 = 60;
+
+/// Where the UMI of a read is found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UmiSource {
+    /// The last colon-delimited field of the read name, as in Illumina read names (e.g.
+    /// `M00123:1:000000000-A1B2C:1:1101:15589:1331:ACGTACGT`).
+    ReadName,
+    /// The SAM-style tag with the given name in the comment of the read header (e.g.
+    /// `RX:Z:ACGTACGT`).
+    Tag(String),
+}
+
+impl Display for UmiSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReadName => write!(f, "name"),
+            Self::Tag(tag) => write!(f, "{tag}"),
+        }
+    }
+}
+
+impl FromStr for UmiSource {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(Self::ReadName),
+            tag if tag.len() == 2 && tag.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                Ok(Self::Tag(tag.to_string()))
+            }
+            _ => bail!("Invalid UMI source, must be 'name' or a two-character tag: {s}"),
+        }
+    }
+}
+
+impl UmiSource {
+    /// Returns the UMI of the read with the given header, if any.  A UMI in the read name must
+    /// consist of bases, with `+` or `-` separating the UMIs of dual-indexed reads.
+    pub fn umi<'a>(&self, head: &'a [u8]) -> Option<&'a [u8]> {
+        match self {
+            Self::ReadName => {
+                let name = head.split(u8::is_ascii_whitespace).next()?;
+                let umi = name.rsplit(|b| *b == b':').next()?;
+                let is_umi = name.len() > umi.len()
+                    && !umi.is_empty()
+                    && umi
+                        .iter()
+                        .all(|b| b.is_ascii_alphabetic() || *b == b'+' || *b == b'-');
+                is_umi.then_some(umi)
+            }
+            Self::Tag(tag) => comment_tag(head, tag),
+        }
+    }
+}
+
+/// Returns the value of the SAM-style tag (e.g. `RX:Z:ACGT`) with the given name in the comment
+/// of the read header (after the read name), if any.
+pub fn comment_tag<'a>(head: &'a [u8], tag: &str) -> Option<&'a [u8]> {
+    head.split(u8::is_ascii_whitespace)
+        .skip(1)
+        .find(|field| field.len() >= 5 && field.starts_with(tag.as_bytes()) && field[2] == b':')
+        .and_then(|field| (field[4] == b':').then(|| &field[5..]))
+}
+
+/// The reads with the same UMI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UmiFamily {
+    pub umi: Vec<u8>,
+    pub reads: Vec<FastqOwnedRecord>,
+}
+
+impl UmiFamily {
+    /// The number of reads in the family.
+    pub fn len(&self) -> usize {
+        self.reads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reads.is_empty()
+    }
+
+    /// Builds the consensus read of the family, from the reads with the most common length
+    /// (preferring the longest on ties), which are assumed to be from the same molecule and so
+    /// to not have indels relative to each other.  At each position, each read votes for its
+    /// base with its base quality, and the consensus is the base with the most votes (or `N` if
+    /// there are none), with quality the difference between the votes for it and for all other
+    /// bases.  The consensus is named after the first read of the family, with its UMI and the
+    /// number of reads of the family in the comment of its header (see [`UMI_TAG`] and
+    /// [`FAMILY_SIZE_TAG`]).  Panics if the family has no reads.
+    pub fn consensus(&self) -> FastqOwnedRecord {
+        let mut length_counts: HashMap<usize, usize> = HashMap::new();
+        for read in &self.reads {
+            *length_counts.entry(read.seq.len()).or_default() += 1;
+        }
+        let len = length_counts
+            .into_iter()
+            .max_by_key(|(len, count)| (*count, *len))
+            .map_or(0, |(len, _)| len);
+        let reads = self.reads.iter().filter(|read| read.seq.len() == len);
+
+        let mut votes = vec![[0u32; BASES.len()]; len];
+        for read in reads {
+            for (i, (base, qual)) in read.seq.iter().zip(&read.qual).enumerate() {
+                if let Some(b) = BASES.iter().position(|b| *b == base.to_ascii_uppercase()) {
+                    votes[i][b] += u32::from(qual.saturating_sub(b'!'));
+                }
+            }
+        }
+        let (seq, qual) = votes
+            .iter()
+            .map(|position| {
+                let total: u32 = position.iter().sum();
+                let best = position.iter().copied().max().unwrap_or(0);
+                if total == 0 {
+                    return (b'N', MIN_CONSENSUS_QUALITY + b'!');
+                }
+                let base = BASES[position.iter().position(|v| *v == best).unwrap()];
+                let quality = (2 * best).saturating_sub(total).clamp(
+                    u32::from(MIN_CONSENSUS_QUALITY),
+                    u32::from(MAX_CONSENSUS_QUALITY),
+                ) as u8;
+                (base, quality + b'!')
+            })
+            .unzip();
+
+        let name = self.reads[0]
+            .head()
+            .split(u8::is_ascii_whitespace)
+            .next()
+            .unwrap_or_default();
+        let mut head = name.to_vec();
+        head.extend_from_slice(format!(" {UMI_TAG}:Z:").as_bytes());
+        head.extend_from_slice(&self.umi);
+        head.extend_from_slice(format!(" {FAMILY_SIZE_TAG}:i:{}", self.len()).as_bytes());
+        FastqOwnedRecord { head, seq, qual }
+    }
+}
+
+/// Groups the reads by their UMI, in the order in which each UMI first occurs.  Returns an error
+/// if a read has no UMI.
+pub fn group_by_umi<I>(records: I, source: &UmiSource) -> Result<Vec<UmiFamily>>
+where
+    I: IntoIterator<Item = FastqOwnedRecord>,
+{
+    let mut families: Vec<UmiFamily> = Vec::new();
+    let mut umi_to_family: HashMap<Vec<u8>, usize> = HashMap::new();
+    for record in records {
+        let Some(umi) = source.umi(record.head()) else {
+            bail!(
+                "No UMI found in the {source} of read: {}",
+                String::from_utf8_lossy(record.head())
+            );
+        };
+        ensure!(
+            !umi.is_empty(),
+            "Empty UMI for read: {}",
+            String::from_utf8_lossy(record.head())
+        );
+        let umi = umi.to_ascii_uppercase();
+        let index = *umi_to_family.entry(umi.clone()).or_insert_with(|| {
+            families.push(UmiFamily {
+                umi,
+                reads: Vec::new(),
+            });
+            families.len() - 1
+        });
+        families[index].reads.push(record);
+    }
+    Ok(families)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+    use seq_io::fastq::OwnedRecord as FastqOwnedRecord;
+
+    use super::{comment_tag, group_by_umi, UmiFamily, UmiSource};
+
+    fn read(head: &str, seq: &str, qual: &str) -> FastqOwnedRecord {
+        FastqOwnedRecord {
+            head: head.as_bytes().to_vec(),
+            seq: seq.as_bytes().to_vec(),
+            qual: qual.as_bytes().to_vec(),
+        }
+    }
+
+    #[rstest]
+    #[case::name(UmiSource::ReadName, "r1:1:2:ACGT+TTGA extra", Some("ACGT+TTGA"))]
+    #[case::name_without_umi(UmiSource::ReadName, "r1:1:2:1331", None)]
+    #[case::name_without_fields(UmiSource::ReadName, "ACGT", None)]
+    #[case::tag(UmiSource::Tag("RX".to_string()), "r1 BC:Z:TTTT RX:Z:ACGT", Some("ACGT"))]
+    #[case::missing_tag(UmiSource::Tag("RX".to_string()), "r1 BC:Z:TTTT", None)]
+    fn test_umi(#[case] source: UmiSource, #[case] head: &str, #[case] expected: Option<&str>) {
+        assert_eq!(source.umi(head.as_bytes()), expected.map(str::as_bytes));
+    }
+
+    #[rstest]
+    fn test_umi_source_from_str() {
+        assert_eq!("name".parse::<UmiSource>().unwrap(), UmiSource::ReadName);
+        assert_eq!(
+            "RX".parse::<UmiSource>().unwrap(),
+            UmiSource::Tag("RX".to_string())
+        );
+        assert!("RXX".parse::<UmiSource>().is_err());
+    }
+
+    #[rstest]
+    fn test_comment_tag() {
+        let head = b"r1 RX:Z:ACGT cD:i:3";
+        assert_eq!(comment_tag(head, "cD"), Some(b"3".as_slice()));
+        assert_eq!(comment_tag(head, "RX"), Some(b"ACGT".as_slice()));
+        assert_eq!(comment_tag(b"RX:Z:ACGT", "RX"), None);
+    }
+
+    #[rstest]
+    fn test_group_by_umi() {
+        let reads = vec![
+            read("r1:AAAA", "ACGT", "IIII"),
+            read("r2:CCCC", "ACGT", "IIII"),
+            read("r3:aaaa", "ACGA", "IIII"),
+        ];
+        let families = group_by_umi(reads.clone(), &UmiSource::ReadName).unwrap();
+        assert_eq!(
+            families,
+            vec![
+                UmiFamily {
+                    umi: b"AAAA".to_vec(),
+                    reads: vec![reads[0].clone(), reads[2].clone()]
+                },
+                UmiFamily {
+                    umi: b"CCCC".to_vec(),
+                    reads: vec![reads[1].clone()]
+                },
+            ]
+        );
+
+        let result = group_by_umi(vec![read("r1", "ACGT", "IIII")], &UmiSource::ReadName);
+        assert!(result.unwrap_err().to_string().contains("No UMI found"));
+    }
+
+    #[rstest]
+    fn test_consensus() {
+        // Q40 = 'I', Q10 = '+', Q20 = '5'
+        let family = UmiFamily {
+            umi: b"AAAA".to_vec(),
+            reads: vec![
+                read("r1:AAAA comment", "ACGTA", "IIIII"),
+                read("r2:AAAA", "ACCTN", "II+II"),
+                read("r3:AAAA", "ACGAN", "II555"),
+                // a different length, so is not included
+                read("r4:AAAA", "TTTT", "IIII"),
+            ],
+        };
+        let consensus = family.consensus();
+        assert_eq!(consensus.head, b"r1:AAAA RX:Z:AAAA cD:i:4");
+        assert_eq!(consensus.seq, b"ACGTA");
+        // 120 and 120 (capped at 60), 40 + 20 - 10 = 50, 40 + 40 - 20 = 60, 40
+        assert_eq!(consensus.qual, b"]]S]I");
+    }
+}
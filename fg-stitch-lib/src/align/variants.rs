@@ -0,0 +1,397 @@
+//! Extraction of variants (SNVs, insertions, and deletions) of a query relative to the contigs to
+//! which it was aligned, with an optional minimal VCF writer.
+
+use std::{fmt, io::Write};
+
+use anyhow::Result;
+
+use super::{
+    aligners::constants::AlignmentOperation::{Del, Ins, Match, Subst, Xclip, Xjump, Yclip, Yjump},
+    alignment::{contig_bases_for, target_seq_for, Alignment},
+};
+use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+/// The kinds of variants of the query relative to the contig.
+///
+/// NB: the kinds are relative to the contig (reference), and so an [`Ins`] operation (consuming
+/// only the contig) is a [`VariantKind::Deletion`], while a [`Del`] operation (consuming only the
+/// query) is a [`VariantKind::Insertion`].
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum VariantKind {
+    /// A single base substitution.
+    Snv,
+    /// Bases present in the query but not the contig.
+    Insertion,
+    /// Bases present in the contig but not the query.
+    Deletion,
+}
+
+impl fmt::Display for VariantKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Snv => write!(f, "snv"),
+            Self::Insertion => write!(f, "ins"),
+            Self::Deletion => write!(f, "del"),
+        }
+    }
+}
+
+/// A variant of the query relative to the forward strand of a target, in VCF style.
+///
+/// Insertions and deletions are left-aligned on the forward strand, and include the base preceding
+/// the event (or following the event if it is at the start of the target) in both alleles.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Variant {
+    /// The index of the target (not the contig index, which is strand specific).
+    pub target_idx: usize,
+    /// The 0-based position of the first base of the reference allele on the forward strand.
+    pub pos: usize,
+    /// The bases of the target.
+    pub ref_allele: Vec<u8>,
+    /// The bases of the query.
+    pub alt_allele: Vec<u8>,
+    /// The kind of variant.
+    pub kind: VariantKind,
+    /// The 0-based position in the query at which the variant was found, before left-alignment.
+    pub query_pos: usize,
+}
+
+/// A variant prior to normalization, in the coordinates of the strand of the contig to which it
+/// was aligned, and without any padding base.
+struct RawVariant {
+    contig_idx: usize,
+    /// The range of contig bases replaced, which is empty for insertions.
+    start: usize,
+    end: usize,
+    /// The query bases replacing the contig bases, which is empty for deletions.
+    alt: Vec<u8>,
+    kind: VariantKind,
+    query_pos: usize,
+    /// The range of contig bases within which the variant may be shifted, namely within the
+    /// segment of the alignment to the contig, and not overlapping the neighboring variants.  Set
+    /// by [`bound_variants`] once the segment is complete.
+    lower: usize,
+    upper: usize,
+}
+
+/// Returns the variants of the query relative to the targets given its alignment, in the order in
+/// which they occur in the query.
+///
+/// Variants on the reverse strand of a target are reported on its forward strand.  Insertions and
+/// deletions are left-aligned on the forward strand, but are not shifted past neighboring variants
+/// or outside the segment of the alignment (between jumps) in which they were found.  Adjacent
+/// substitutions are reported as separate SNVs.
+///
+/// Arguments:
+/// - `alignment` - The alignment of the query.
+/// - `target_seqs` - The targets to which the query was aligned, with contig indexes following the
+///   convention of [`crate::align::Builder::build_aligners`].
+/// - `y` - The query sequence that was aligned.
+pub fn call_variants(alignment: &Alignment, target_seqs: &[TargetSeq], y: &[u8]) -> Vec<Variant> {
+    let mut raw_variants: Vec<RawVariant> = Vec::new();
+    // The index of the first variant in the current segment, and the start of the segment
+    let mut segment_first_variant = 0;
+    let mut segment_start = alignment.xstart;
+    let mut last_op = None;
+    for step in alignment.iter_steps() {
+        let x = contig_bases_for(target_seqs, step.contig_idx);
+        match step.op {
+            Subst if x[step.x_pos] != y[step.y_pos] => raw_variants.push(RawVariant {
+                contig_idx: step.contig_idx,
+                start: step.x_pos,
+                end: step.x_pos + 1,
+                alt: vec![y[step.y_pos]],
+                kind: VariantKind::Snv,
+                query_pos: step.y_pos,
+                lower: 0,
+                upper: 0,
+            }),
+            Ins if last_op == Some(Ins) => raw_variants.last_mut().unwrap().end += 1,
+            Ins => raw_variants.push(RawVariant {
+                contig_idx: step.contig_idx,
+                start: step.x_pos,
+                end: step.x_pos + 1,
+                alt: Vec::new(),
+                kind: VariantKind::Deletion,
+                query_pos: step.y_pos,
+                lower: 0,
+                upper: 0,
+            }),
+            Del if last_op == Some(Del) => raw_variants.last_mut().unwrap().alt.push(y[step.y_pos]),
+            Del => raw_variants.push(RawVariant {
+                contig_idx: step.contig_idx,
+                start: step.x_pos,
+                end: step.x_pos,
+                alt: vec![y[step.y_pos]],
+                kind: VariantKind::Insertion,
+                query_pos: step.y_pos,
+                lower: 0,
+                upper: 0,
+            }),
+            Xjump(_, to_x) => {
+                let segment = &mut raw_variants[segment_first_variant..];
+                bound_variants(segment, segment_start, step.x_pos);
+                segment_first_variant = raw_variants.len();
+                segment_start = to_x;
+            }
+            Match | Subst | Xclip(_) | Yclip(_) | Yjump(_) => (),
+        }
+        last_op = Some(step.op);
+    }
+    let segment = &mut raw_variants[segment_first_variant..];
+    bound_variants(segment, segment_start, alignment.xend);
+
+    raw_variants
+        .into_iter()
+        .map(|raw_variant| normalize(raw_variant, target_seqs))
+        .collect()
+}
+
+/// Bounds the range within which each variant in a segment of the alignment may be shifted such
+/// that it does not overlap its neighbors nor extend past the end of the segment.
+fn bound_variants(raw_variants: &mut [RawVariant], segment_start: usize, segment_end: usize) {
+    let mut upper = segment_end;
+    for raw_variant in raw_variants.iter_mut().rev() {
+        raw_variant.upper = upper;
+        upper = raw_variant.start;
+    }
+    let mut lower = segment_start;
+    for raw_variant in raw_variants.iter_mut() {
+        raw_variant.lower = lower;
+        lower = raw_variant.end;
+    }
+}
+
+/// Converts the variant to the forward strand of its target, left-aligns it, and pads indels.
+fn normalize(raw_variant: RawVariant, target_seqs: &[TargetSeq]) -> Variant {
+    let (target_seq, is_forward) = target_seq_for(target_seqs, raw_variant.contig_idx);
+    let x = &target_seq.fwd;
+    let RawVariant {
+        mut start,
+        mut end,
+        mut alt,
+        kind,
+        lower,
+        ..
+    } = raw_variant;
+    let lower = if is_forward {
+        lower
+    } else {
+        let len = x.len();
+        (start, end) = (len - end, len - start);
+        alt = reverse_complement(&alt);
+        len - raw_variant.upper
+    };
+
+    match kind {
+        VariantKind::Snv => (),
+        VariantKind::Deletion => {
+            while start > lower && x[start - 1] == x[end - 1] {
+                start -= 1;
+                end -= 1;
+            }
+        }
+        VariantKind::Insertion => {
+            while start > lower && x[start - 1] == alt[alt.len() - 1] {
+                alt.rotate_right(1);
+                start -= 1;
+                end -= 1;
+            }
+        }
+    }
+
+    let (pos, ref_allele, alt_allele) = match kind {
+        VariantKind::Snv => (start, x[start..end].to_vec(), alt),
+        _ if start > 0 => {
+            let pad = x[start - 1];
+            let alt_allele = [&[pad], alt.as_slice()].concat();
+            (start - 1, x[start - 1..end].to_vec(), alt_allele)
+        }
+        _ => {
+            let pad = x[end];
+            let alt_allele = [alt.as_slice(), &[pad]].concat();
+            (start, x[start..=end].to_vec(), alt_allele)
+        }
+    };
+    Variant {
+        target_idx: raw_variant.contig_idx % target_seqs.len(),
+        pos,
+        ref_allele,
+        alt_allele,
+        kind,
+        query_pos: raw_variant.query_pos,
+    }
+}
+
+/// Writes variants in a minimal VCF format, with one record per variant per query.  The query name
+/// and position are stored in the INFO field, and the QUAL and FILTER fields are left empty.
+pub struct VcfWriter<W: Write> {
+    writer: W,
+    target_names: Vec<String>,
+}
+
+impl<W: Write> VcfWriter<W> {
+    /// Creates a new writer, writing the VCF header with a contig line for each target.
+    pub fn new(mut writer: W, target_seqs: &[TargetSeq]) -> Result<Self> {
+        writeln!(writer, "##fileformat=VCFv4.2")?;
+        writeln!(writer, "##source=stitch")?;
+        for target_seq in target_seqs {
+            writeln!(
+                writer,
+                "##contig=<ID={},length={}>",
+                target_seq.name,
+                target_seq.len()
+            )?;
+        }
+        writeln!(
+            writer,
+            "##INFO=<ID=TYPE,Number=1,Type=String,Description=\"The type of variant (snv, ins, or del)\">"
+        )?;
+        writeln!(
+            writer,
+            "##INFO=<ID=QNAME,Number=1,Type=String,Description=\"The name of the query with the variant\">"
+        )?;
+        writeln!(
+            writer,
+            "##INFO=<ID=QPOS,Number=1,Type=Integer,Description=\"The 1-based position of the variant in the query\">"
+        )?;
+        writeln!(writer, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO")?;
+        Ok(Self {
+            writer,
+            target_names: target_seqs.iter().map(|t| t.name.clone()).collect(),
+        })
+    }
+
+    /// Writes a record for the given variant of the query with the given name.
+    pub fn write(&mut self, query_name: &str, variant: &Variant) -> Result<()> {
+        writeln!(
+            self.writer,
+            "{}\t{}\t.\t{}\t{}\t.\t.\tTYPE={};QNAME={};QPOS={}",
+            self.target_names[variant.target_idx],
+            variant.pos + 1,
+            String::from_utf8_lossy(&variant.ref_allele),
+            String::from_utf8_lossy(&variant.alt_allele),
+            variant.kind,
+            query_name,
+            variant.query_pos + 1
+        )?;
+        Ok(())
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{alignment::tests::align_double_strand, scoring::Scoring};
+    use crate::util::{dna::reverse_complement, target_seq::TargetSeq};
+
+    use super::{call_variants, Variant, VariantKind, VcfWriter};
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-2, -1, -100, MatchParams::new(1, -1))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    fn variant(pos: usize, ref_allele: &str, alt_allele: &str, kind: VariantKind) -> Variant {
+        Variant {
+            target_idx: 0,
+            pos,
+            ref_allele: ref_allele.as_bytes().to_vec(),
+            alt_allele: alt_allele.as_bytes().to_vec(),
+            kind,
+            query_pos: 0,
+        }
+    }
+
+    /// Calls variants of the query versus the target, ignoring the query positions.
+    fn call(target: &str, query: &[u8]) -> Vec<Variant> {
+        let target_seqs = [TargetSeq::new("chr1", &target.as_bytes().to_vec(), false)];
+        let alignment = align_double_strand(&target_seqs, query, scoring());
+        call_variants(&alignment, &target_seqs, query)
+            .into_iter()
+            .map(|v| Variant { query_pos: 0, ..v })
+            .collect()
+    }
+
+    const TARGET: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+
+    #[rstest]
+    #[case("GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT", vec![])]
+    #[case(
+        "GCTAAAGACAATTACATTACATACACGTCAGCACGAAACT",
+        vec![variant(17, "A", "T", VariantKind::Snv)]
+    )]
+    // deletion of one A in the AAA at 3..6 is left-aligned to after the T at 2
+    #[case(
+        "GCTAAGACAATTACATAACATACACGTCAGCACGAAACT",
+        vec![variant(2, "TA", "T", VariantKind::Deletion)]
+    )]
+    // insertion of an extra CA after the AACA at 17..21 is left-aligned to an AC after the A at 17
+    #[case(
+        "GCTAAAGACAATTACATAACACATACACGTCAGCACGAAACT",
+        vec![variant(17, "A", "AAC", VariantKind::Insertion)]
+    )]
+    fn test_call_variants(#[case] query: &str, #[case] expected: Vec<Variant>) {
+        assert_eq!(call(TARGET, query.as_bytes()), expected);
+    }
+
+    #[rstest]
+    fn test_call_variants_reverse_strand() {
+        // The same variants as above, but the query is on the reverse strand
+        for query in [
+            "GCTAAAGACAATTACATTACATACACGTCAGCACGAAACT",
+            "GCTAAGACAATTACATAACATACACGTCAGCACGAAACT",
+            "GCTAAAGACAATTACATAACACATACACGTCAGCACGAAACT",
+        ] {
+            let revcomp = reverse_complement(query.as_bytes());
+            assert_eq!(call(TARGET, &revcomp), call(TARGET, query.as_bytes()));
+        }
+    }
+
+    #[rstest]
+    fn test_call_variants_query_pos() {
+        let target_seqs = [TargetSeq::new("chr1", &TARGET.as_bytes().to_vec(), false)];
+        let query = b"GCTAAAGACAATTACATTACATACACGTCAGCACGAAACT";
+        let alignment = align_double_strand(&target_seqs, query, scoring());
+        let variants = call_variants(&alignment, &target_seqs, query);
+        assert_eq!(variants.len(), 1);
+        assert_eq!(variants[0].query_pos, 17);
+    }
+
+    #[rstest]
+    fn test_vcf_writer() {
+        let target_seqs = [TargetSeq::new("chr1", &TARGET.as_bytes().to_vec(), false)];
+        let mut bytes: Vec<u8> = Vec::new();
+        {
+            let mut writer = VcfWriter::new(&mut bytes, &target_seqs).unwrap();
+            let variant = Variant {
+                query_pos: 17,
+                ..variant(17, "A", "T", VariantKind::Snv)
+            };
+            writer.write("read1", &variant).unwrap();
+            writer.flush().unwrap();
+        }
+        let vcf = String::from_utf8(bytes).unwrap();
+        let lines = vcf.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "##fileformat=VCFv4.2");
+        assert_eq!(lines[2], "##contig=<ID=chr1,length=40>");
+        assert_eq!(
+            lines[lines.len() - 2],
+            "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO"
+        );
+        assert_eq!(
+            lines[lines.len() - 1],
+            "chr1\t18\t.\tA\tT\t.\t.\tTYPE=snv;QNAME=read1;QPOS=18"
+        );
+    }
+}
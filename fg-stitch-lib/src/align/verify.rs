@@ -0,0 +1,600 @@
+//! Verification of a plasmid (or other construct) against its expected map, from the alignments
+//! of long reads: the coverage of each expected feature, the unexpected junctions (e.g. from
+//! recombination), and the large deletions, summarized as a pass/fail report.
+
+use std::{collections::BTreeMap, io::Write, path::Path};
+
+use anyhow::{ensure, Context, Result};
+
+use super::{
+    aligners::constants::AlignmentOperation::{Ins, Match, Subst},
+    alignment::{target_seq_for, Alignment},
+    breakpoints::Breakpoint,
+};
+use crate::util::{genbank::FeatureAnnotations, target_seq::TargetSeq};
+
+/// A feature of the expected plasmid map, as the range `[start, end)` (0-based) on the forward
+/// strand of the plasmid.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PlasmidFeature {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl PlasmidFeature {
+    /// Parses the features of the given contig in BED format: one feature per line, with the
+    /// contig name, the 0-based start and end of the feature, and optionally its name,
+    /// tab-delimited.  Unnamed features are named by their 1-based range.  The features of the
+    /// other contigs, any other columns, and the comment, `track`, and `browser` lines are
+    /// ignored.
+    pub fn from_bed(bed: &str, contig: &str) -> Result<Vec<Self>> {
+        let mut features = Vec::new();
+        let lines = bed.lines().enumerate().filter(|(_, line)| {
+            !line.trim().is_empty()
+                && !line.starts_with('#')
+                && !line.starts_with("track")
+                && !line.starts_with("browser")
+        });
+        for (index, line) in lines {
+            let fields: Vec<&str> = line.split('\t').collect();
+            ensure!(
+                fields.len() >= 3,
+                "Expected at least 3 columns on line {} of the BED: {line}",
+                index + 1
+            );
+            if fields[0] != contig {
+                continue;
+            }
+            let parse = |field: &str| {
+                field.trim().parse::<usize>().with_context(|| {
+                    format!("Invalid position on line {} of the BED: {field}", index + 1)
+                })
+            };
+            let (start, end) = (parse(fields[1])?, parse(fields[2])?);
+            ensure!(
+                start < end,
+                "The feature does not end after its start on line {} of the BED: {start} >= {end}",
+                index + 1
+            );
+            let name = match fields.get(3).map(|name| name.trim()) {
+                Some(name) if !name.is_empty() && name != "." => name.to_string(),
+                _ => format!("{contig}:{}-{end}", start + 1),
+            };
+            features.push(Self { name, start, end });
+        }
+        Ok(features)
+    }
+
+    /// Reads the features of the given contig from a BED file (see [`Self::from_bed`]).
+    pub fn from_path(path: &Path, contig: &str) -> Result<Vec<Self>> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read the BED: {}", path.display()))?;
+        Self::from_bed(&contents, contig)
+            .with_context(|| format!("Invalid BED: {}", path.display()))
+    }
+
+    /// Returns the features of the given contig from the feature table of a GenBank or EMBL file,
+    /// other than the `source` features spanning whole records.
+    pub fn from_annotations(annotations: &FeatureAnnotations, contig: &str) -> Vec<Self> {
+        annotations
+            .features(contig)
+            .iter()
+            .filter(|feature| feature.kind != "source")
+            .map(|feature| Self {
+                name: feature.name.clone(),
+                start: feature.start,
+                end: feature.end,
+            })
+            .collect()
+    }
+
+    /// True if the feature contains the given position.
+    fn contains(&self, pos: usize) -> bool {
+        self.start <= pos && pos < self.end
+    }
+}
+
+/// The outcome of a check of the report.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub enum Status {
+    Pass,
+    /// An unexpected junction or deletion supported by too few reads to fail the plasmid, for
+    /// example from a minor sub-population or an artifact.
+    Warn,
+    Fail,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pass => write!(f, "PASS"),
+            Self::Warn => write!(f, "WARN"),
+            Self::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// A deletion of the plasmid, as the 0-based position of its first deleted base on the forward
+/// strand and its length.  A deletion of a circular plasmid may span its origin.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct Deletion {
+    pub start: usize,
+    pub len: usize,
+}
+
+/// Accumulates the evidence from the reads aligned to a plasmid, and reports it against the
+/// expected features.
+///
+/// Each read contributes its best alignment: the aligned bases of the plasmid (on either strand)
+/// add to its coverage, the deletions (as gaps or as forward jumps on the same strand) of at
+/// least the minimum length are counted, and any other jump (a backward jump such as a
+/// duplication, a jump to the opposite strand such as an inversion, or a jump to another target)
+/// is counted as an unexpected junction in its canonical form (see [`Breakpoint::canonical`]).
+/// For a circular plasmid, a jump across the origin skipping at most half the plasmid is a
+/// deletion.
+#[derive(Debug, Clone)]
+pub struct PlasmidVerifier {
+    /// The index of the plasmid in the targets.
+    plasmid_idx: usize,
+    /// The expected features of the plasmid.
+    features: Vec<PlasmidFeature>,
+    /// The minimum length of a deletion to be reported.
+    min_deletion: usize,
+    /// The depth of each position of the plasmid, on its forward strand.
+    depths: Vec<u32>,
+    /// The number of reads, and the number with an alignment.
+    reads: usize,
+    aligned_reads: usize,
+    junctions: BTreeMap<Breakpoint, usize>,
+    deletions: BTreeMap<Deletion, usize>,
+}
+
+impl PlasmidVerifier {
+    /// Creates a verifier of the plasmid with index `plasmid_idx` in `target_seqs` with the
+    /// given expected features, reporting deletions of at least `min_deletion` bases.
+    pub fn new(
+        target_seqs: &[TargetSeq],
+        plasmid_idx: usize,
+        features: Vec<PlasmidFeature>,
+        min_deletion: usize,
+    ) -> Result<Self> {
+        ensure!(
+            plasmid_idx < target_seqs.len(),
+            "Plasmid index {plasmid_idx} out of range for {} targets",
+            target_seqs.len()
+        );
+        ensure!(min_deletion > 0, "The minimum deletion must be positive");
+        let plasmid = &target_seqs[plasmid_idx];
+        for feature in &features {
+            ensure!(
+                feature.end <= plasmid.len(),
+                "Feature {} ends at {} past the end of the plasmid with length {}",
+                feature.name,
+                feature.end,
+                plasmid.len()
+            );
+        }
+        Ok(Self {
+            plasmid_idx,
+            features,
+            min_deletion,
+            depths: vec![0; plasmid.len()],
+            reads: 0,
+            aligned_reads: 0,
+            junctions: BTreeMap::new(),
+            deletions: BTreeMap::new(),
+        })
+    }
+
+    /// Returns the deletion given by a jump within the plasmid, if any, and otherwise `None` for
+    /// an unexpected junction.  The deletion may be shorter than the minimum length.
+    fn deletion_of(&self, breakpoint: &Breakpoint, target_seqs: &[TargetSeq]) -> Option<Deletion> {
+        if breakpoint.from_target_idx != self.plasmid_idx
+            || breakpoint.to_target_idx != self.plasmid_idx
+            || breakpoint.from_forward != breakpoint.to_forward
+        {
+            return None;
+        }
+        let breakpoint = if breakpoint.from_forward {
+            *breakpoint
+        } else {
+            breakpoint.flip()
+        };
+        let plasmid = &target_seqs[self.plasmid_idx];
+        if breakpoint.to_pos > breakpoint.from_pos {
+            let len = breakpoint.to_pos - breakpoint.from_pos - 1;
+            Some(Deletion {
+                start: breakpoint.from_pos + 1,
+                len,
+            })
+        } else if plasmid.circular {
+            let len = breakpoint.to_pos + plasmid.len() - breakpoint.from_pos - 1;
+            (len <= plasmid.len() / 2).then_some(Deletion {
+                start: (breakpoint.from_pos + 1) % plasmid.len(),
+                len,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Adds a read given its best alignment, or `None` if it did not align.
+    ///
+    /// Arguments:
+    /// - `alignment` - The best alignment of the read, if any.
+    /// - `target_seqs` - The targets to which the read was aligned, with contig indexes
+    ///   following the convention of [`crate::align::Builder::build_aligners`].
+    pub fn add(&mut self, alignment: Option<&Alignment>, target_seqs: &[TargetSeq]) {
+        self.reads += 1;
+        let Some(alignment) = alignment.filter(|a| a.length > 0) else {
+            return;
+        };
+        self.aligned_reads += 1;
+
+        let plasmid_len = self.depths.len();
+        let mut deletions: Vec<Deletion> = Vec::new();
+        let mut gap: Option<(usize, usize)> = None;
+        for step in alignment.iter_steps() {
+            let on_plasmid = step.contig_idx % target_seqs.len() == self.plasmid_idx;
+            let (_, is_forward) = target_seq_for(target_seqs, step.contig_idx);
+            let forward_pos = |pos: usize| {
+                if is_forward {
+                    pos
+                } else {
+                    plasmid_len - 1 - pos
+                }
+            };
+            // Close the run of deleted bases when leaving it
+            if step.op != Ins {
+                if let Some((start, len)) = gap.take() {
+                    deletions.push(Deletion { start, len });
+                }
+            }
+            if !on_plasmid {
+                continue;
+            }
+            match step.op {
+                Match | Subst => self.depths[forward_pos(step.x_pos)] += 1,
+                // NB: an insertion in the contig is a deletion in the read
+                Ins => {
+                    gap = match gap {
+                        Some((start, len)) if is_forward => Some((start, len + 1)),
+                        Some((_, len)) => Some((forward_pos(step.x_pos), len + 1)),
+                        None => Some((forward_pos(step.x_pos), 1)),
+                    }
+                }
+                _ => (),
+            }
+        }
+        if let Some((start, len)) = gap {
+            deletions.push(Deletion { start, len });
+        }
+
+        let mut junctions: Vec<Breakpoint> = Vec::new();
+        for breakpoint in alignment.breakpoints(target_seqs) {
+            match self.deletion_of(&breakpoint, target_seqs) {
+                Some(deletion) => deletions.push(deletion),
+                None => junctions.push(breakpoint.canonical()),
+            }
+        }
+        deletions.retain(|deletion| deletion.len >= self.min_deletion);
+        deletions.sort_unstable();
+        deletions.dedup();
+        for deletion in deletions {
+            *self.deletions.entry(deletion).or_default() += 1;
+        }
+        junctions.sort_unstable();
+        junctions.dedup();
+        for junction in junctions {
+            *self.junctions.entry(junction).or_default() += 1;
+        }
+    }
+
+    /// The number of reads, and the number of aligned reads.
+    pub fn reads(&self) -> (usize, usize) {
+        (self.reads, self.aligned_reads)
+    }
+
+    /// Returns the depth of each position of the plasmid, on its forward strand.
+    pub fn depths(&self) -> &[u32] {
+        &self.depths
+    }
+
+    /// Returns the unexpected junctions and the number of reads supporting each, in sorted order.
+    pub fn junctions(&self) -> impl Iterator<Item = (&Breakpoint, usize)> + '_ {
+        self.junctions
+            .iter()
+            .map(|(junction, count)| (junction, *count))
+    }
+
+    /// Returns the deletions and the number of reads supporting each, in sorted order.
+    pub fn deletions(&self) -> impl Iterator<Item = (&Deletion, usize)> + '_ {
+        self.deletions
+            .iter()
+            .map(|(deletion, count)| (deletion, *count))
+    }
+
+    /// Returns the minimum depth of the feature, and the fraction of its bases with at least
+    /// `min_depth` depth.
+    pub fn feature_coverage(&self, feature: &PlasmidFeature, min_depth: u32) -> (u32, f64) {
+        let depths = &self.depths[feature.start..feature.end];
+        let covered = depths.iter().filter(|depth| **depth >= min_depth).count();
+        let min = depths.iter().copied().min().unwrap_or(0);
+        (min, covered as f64 / depths.len().max(1) as f64)
+    }
+
+    /// The fraction of the aligned reads supporting an event with the given number of reads.
+    fn fraction_of_aligned(&self, reads: usize) -> f64 {
+        reads as f64 / self.aligned_reads.max(1) as f64
+    }
+
+    /// The names of the features containing the given position, comma-separated, or `-` if none.
+    fn features_at(&self, pos: usize) -> String {
+        let names = self
+            .features
+            .iter()
+            .filter(|feature| feature.contains(pos))
+            .map(|feature| feature.name.as_str())
+            .collect::<Vec<_>>();
+        if names.is_empty() {
+            "-".to_string()
+        } else {
+            names.join(",")
+        }
+    }
+
+    /// Returns the overall status of the plasmid: failed if it has no aligned reads, or a
+    /// feature without every base at `min_depth` depth, or a junction or deletion supported by at
+    /// least `min_event_fraction` of the aligned reads.
+    pub fn status(&self, min_depth: u32, min_event_fraction: f64) -> Status {
+        let feature_failed = self
+            .features
+            .iter()
+            .any(|feature| self.feature_coverage(feature, min_depth).1 < 1.0);
+        let event_failed = self
+            .junctions
+            .values()
+            .chain(self.deletions.values())
+            .any(|reads| self.fraction_of_aligned(*reads) >= min_event_fraction);
+        if self.aligned_reads == 0 || feature_failed || event_failed {
+            Status::Fail
+        } else {
+            Status::Pass
+        }
+    }
+
+    /// Writes the report as a tab-delimited table with a header.  Each line has the kind of
+    /// check, its name, its location, the number of reads, a fraction, and its status:
+    /// - `feature`: for each expected feature, its name and 1-based range, its minimum depth, and
+    ///   the fraction of its bases with at least `min_depth` depth.  It fails unless the fraction
+    ///   is one.
+    /// - `deletion`: for each deletion, the features containing its first deleted base, the
+    ///   1-based range of the deleted bases (where the end precedes the start for a deletion
+    ///   spanning the origin), the number of supporting reads, and their fraction of the aligned
+    ///   reads.  It fails if the fraction is at least `min_event_fraction`, and otherwise warns.
+    /// - `junction`: for each unexpected junction, the features containing its two sides, the
+    ///   1-based position and strand of its two sides, and the read support as for deletions.
+    /// - `summary`: the plasmid name and 1-based range, the number and fraction of aligned reads,
+    ///   and the overall status (see [`Self::status`]).
+    ///
+    /// Deletions and junctions are sorted by decreasing number of supporting reads.
+    pub fn write_report<W: Write>(
+        &self,
+        writer: &mut W,
+        target_seqs: &[TargetSeq],
+        min_depth: u32,
+        min_event_fraction: f64,
+    ) -> Result<()> {
+        let plasmid = &target_seqs[self.plasmid_idx].name;
+        let strand = |is_forward: bool| if is_forward { '+' } else { '-' };
+        let event_status = |reads: usize| {
+            if self.fraction_of_aligned(reads) >= min_event_fraction {
+                Status::Fail
+            } else {
+                Status::Warn
+            }
+        };
+        writeln!(writer, "kind\tname\tlocation\treads\tfraction\tstatus")?;
+
+        for feature in &self.features {
+            let (min, fraction) = self.feature_coverage(feature, min_depth);
+            let status = if fraction < 1.0 {
+                Status::Fail
+            } else {
+                Status::Pass
+            };
+            writeln!(
+                writer,
+                "feature\t{}\t{plasmid}:{}-{}\t{min}\t{fraction:.4}\t{status}",
+                feature.name,
+                feature.start + 1,
+                feature.end
+            )?;
+        }
+
+        let mut deletions = self.deletions().collect::<Vec<_>>();
+        deletions.sort_by_key(|(deletion, reads)| (std::cmp::Reverse(*reads), **deletion));
+        for (deletion, reads) in deletions {
+            let end = (deletion.start + deletion.len - 1) % self.depths.len();
+            writeln!(
+                writer,
+                "deletion\t{}\t{plasmid}:{}-{}\t{reads}\t{:.4}\t{}",
+                self.features_at(deletion.start),
+                deletion.start + 1,
+                end + 1,
+                self.fraction_of_aligned(reads),
+                event_status(reads)
+            )?;
+        }
+
+        let mut junctions = self.junctions().collect::<Vec<_>>();
+        junctions.sort_by_key(|(junction, reads)| (std::cmp::Reverse(*reads), **junction));
+        for (junction, reads) in junctions {
+            let features_at = |target_idx: usize, pos: usize| {
+                if target_idx == self.plasmid_idx {
+                    self.features_at(pos)
+                } else {
+                    "-".to_string()
+                }
+            };
+            writeln!(
+                writer,
+                "junction\t{}>{}\t{}:{}{}>{}:{}{}\t{reads}\t{:.4}\t{}",
+                features_at(junction.from_target_idx, junction.from_pos),
+                features_at(junction.to_target_idx, junction.to_pos),
+                target_seqs[junction.from_target_idx].name,
+                junction.from_pos + 1,
+                strand(junction.from_forward),
+                target_seqs[junction.to_target_idx].name,
+                junction.to_pos + 1,
+                strand(junction.to_forward),
+                self.fraction_of_aligned(reads),
+                event_status(reads)
+            )?;
+        }
+
+        writeln!(
+            writer,
+            "summary\t{plasmid}\t{plasmid}:1-{}\t{}\t{:.4}\t{}",
+            self.depths.len(),
+            self.aligned_reads,
+            self.aligned_reads as f64 / self.reads.max(1) as f64,
+            self.status(min_depth, min_event_fraction)
+        )?;
+        Ok(())
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::{alignment::tests::align_double_strand, scoring::Scoring};
+    use crate::util::{
+        dna::reverse_complement,
+        genbank::{FeatureAnnotations, SeqFeature},
+        target_seq::TargetSeq,
+    };
+
+    use super::{Deletion, PlasmidFeature, PlasmidVerifier, Status};
+
+    //                    0         1         2         3         4         5         6
+    //                    01234567890123456789012345678901234567890123456789012345678901234567
+    const PLASMID: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACTTGTTGGCCCAGTGTGAATCGCTTAAGGG";
+
+    fn scoring() -> Scoring<MatchParams> {
+        Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0)
+    }
+
+    #[rstest]
+    fn test_from_bed() {
+        let bed = "track name=map\nplasmid\t0\t20\tori\nother\t0\t10\tgene\nplasmid\t30\t40\n";
+        let features = PlasmidFeature::from_bed(bed, "plasmid").unwrap();
+        assert_eq!(
+            features,
+            vec![
+                PlasmidFeature {
+                    name: "ori".to_string(),
+                    start: 0,
+                    end: 20
+                },
+                PlasmidFeature {
+                    name: "plasmid:31-40".to_string(),
+                    start: 30,
+                    end: 40
+                },
+            ]
+        );
+        assert!(PlasmidFeature::from_bed("plasmid\t20\t20\n", "plasmid").is_err());
+        assert!(PlasmidFeature::from_bed("plasmid\t20\n", "plasmid").is_err());
+    }
+
+    #[rstest]
+    fn test_from_annotations() {
+        let mut annotations = FeatureAnnotations::new();
+        for (kind, name, start, end) in [("source", "source", 0, 68), ("promoter", "CMV", 5, 25)] {
+            let feature = SeqFeature {
+                kind: kind.to_string(),
+                name: name.to_string(),
+                start,
+                end,
+                is_forward: true,
+            };
+            annotations.add("plasmid", feature);
+        }
+        assert_eq!(
+            PlasmidFeature::from_annotations(&annotations, "plasmid"),
+            vec![PlasmidFeature {
+                name: "CMV".to_string(),
+                start: 5,
+                end: 25
+            }]
+        );
+        assert!(PlasmidFeature::from_annotations(&annotations, "other").is_empty());
+    }
+
+    #[rstest]
+    fn test_plasmid_verifier() {
+        let target_seqs = vec![TargetSeq::new(
+            "plasmid",
+            &PLASMID.as_bytes().to_vec(),
+            false,
+        )];
+        let feature = |name: &str, start: usize, end: usize| PlasmidFeature {
+            name: name.to_string(),
+            start,
+            end,
+        };
+        let features = vec![
+            feature("left", 0, 20),
+            feature("middle", 20, 40),
+            feature("right", 44, 64),
+        ];
+        let mut verifier = PlasmidVerifier::new(&target_seqs, 0, features, 10).unwrap();
+
+        let plasmid = PLASMID.as_bytes();
+        // the expected plasmid, a deletion of 22-42, and an inversion of 34-68
+        let reads = [
+            plasmid.to_vec(),
+            [&plasmid[..22], &plasmid[42..]].concat(),
+            [&plasmid[..34], &reverse_complement(&plasmid[34..])].concat(),
+        ];
+        for y in &reads {
+            let alignment = align_double_strand(&target_seqs, y, scoring());
+            verifier.add(Some(&alignment), &target_seqs);
+        }
+        verifier.add(None, &target_seqs);
+        assert_eq!(verifier.reads(), (4, 3));
+        assert_eq!(
+            verifier.deletions().collect::<Vec<_>>(),
+            vec![(&Deletion { start: 22, len: 20 }, 1)]
+        );
+        assert_eq!(verifier.junctions().count(), 1);
+
+        let middle = feature("middle", 20, 40);
+        assert_eq!(verifier.feature_coverage(&middle, 3), (2, 0.1));
+        assert_eq!(verifier.status(2, 0.5), Status::Pass);
+        assert_eq!(verifier.status(3, 0.5), Status::Fail);
+        assert_eq!(verifier.status(2, 0.3), Status::Fail);
+
+        let mut report: Vec<u8> = Vec::new();
+        verifier
+            .write_report(&mut report, &target_seqs, 3, 0.5)
+            .unwrap();
+        let report = String::from_utf8(report).unwrap();
+        let lines = report.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], "kind\tname\tlocation\treads\tfraction\tstatus");
+        assert_eq!(lines[1], "feature\tleft\tplasmid:1-20\t3\t1.0000\tPASS");
+        assert_eq!(lines[2], "feature\tmiddle\tplasmid:21-40\t2\t0.1000\tFAIL");
+        assert_eq!(lines[3], "feature\tright\tplasmid:45-64\t3\t1.0000\tPASS");
+        assert_eq!(lines[4], "deletion\tmiddle\tplasmid:23-42\t1\t0.3333\tWARN");
+        assert!(lines[5].starts_with("junction\t") && lines[5].ends_with("\t1\t0.3333\tWARN"));
+        assert_eq!(lines[6], "summary\tplasmid\tplasmid:1-68\t3\t0.7500\tFAIL");
+    }
+}
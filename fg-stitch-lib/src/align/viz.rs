@@ -0,0 +1,418 @@
+//! Visualizations of the alignment of a query against the targets, for understanding why a jump
+//! was or wasn't chosen: the k-mer dot-plot of the query versus each target, and the score
+//! surface of the dynamic programming, exported as TSV or grayscale PNG.
+
+use std::io::Write;
+
+use anyhow::Result;
+use bio::alignment::sparse::hash_kmers;
+use flate2::{write::ZlibEncoder, Compression};
+
+use super::aligners::constants::MIN_SCORE;
+use crate::util::target_seq::TargetSeq;
+
+/// The score surface of the alignment of a query to a single contig, namely the best score of an
+/// alignment ending at each contig and query position.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ScoreSurface {
+    /// The index of the contig.
+    pub contig_idx: usize,
+    /// The name of the contig.
+    pub name: String,
+    /// True if the contig is the forward strand.
+    pub is_forward: bool,
+    /// The offset of the aligned region in the contig.
+    pub offset: usize,
+    /// The number of rows, one more than the length of the aligned region of the contig.
+    pub rows: usize,
+    /// The number of columns, one more than the length of the query.
+    pub cols: usize,
+    /// The scores, stored by column.
+    pub scores: Vec<i32>,
+}
+
+impl ScoreSurface {
+    /// Creates a new score surface with all cells unreachable.
+    pub(crate) fn new(
+        contig_idx: usize,
+        name: &str,
+        is_forward: bool,
+        offset: usize,
+        rows: usize,
+        cols: usize,
+    ) -> Self {
+        Self {
+            contig_idx,
+            name: name.to_string(),
+            is_forward,
+            offset,
+            rows,
+            cols,
+            scores: vec![MIN_SCORE; rows * cols],
+        }
+    }
+
+    /// Sets the scores for the given column.
+    pub(crate) fn set_column(&mut self, j: usize, scores: &[i32]) {
+        let start = j * self.rows;
+        self.scores[start..start + self.rows].copy_from_slice(&scores[..self.rows]);
+    }
+
+    /// Returns the score of the best alignment ending after `i` contig bases and `j` query bases.
+    pub fn get(&self, i: usize, j: usize) -> i32 {
+        self.scores[j * self.rows + i]
+    }
+
+    /// Returns true if an alignment may end at the given cell.
+    fn is_reachable(score: i32) -> bool {
+        score > MIN_SCORE / 2
+    }
+
+    /// Writes the reachable cells as TSV, with the contig position (in the coordinates of the
+    /// strand of the contig), query position, and score of each cell.
+    pub fn write_tsv<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writeln!(writer, "contig\tstrand\tcontig_pos\tquery_pos\tscore")?;
+        let strand = if self.is_forward { '+' } else { '-' };
+        for j in 0..self.cols {
+            for i in 0..self.rows {
+                let score = self.get(i, j);
+                if Self::is_reachable(score) {
+                    let pos = self.offset + i;
+                    writeln!(writer, "{}\t{strand}\t{pos}\t{j}\t{score}", self.name)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the score surface as a grayscale PNG, with the query along the x-axis and the contig
+    /// along the y-axis, and darker pixels for higher scores.  The image is downsampled, taking
+    /// the best score, such that neither dimension exceeds `max_size` pixels.
+    pub fn write_png<W: Write>(&self, writer: &mut W, max_size: usize) -> Result<()> {
+        let scale = downsampling_scale(self.cols, self.rows, max_size);
+        let (width, height) = (self.cols.div_ceil(scale), self.rows.div_ceil(scale));
+        let mut best = vec![MIN_SCORE; width * height];
+        for j in 0..self.cols {
+            for i in 0..self.rows {
+                let pixel = &mut best[(i / scale) * width + j / scale];
+                *pixel = (*pixel).max(self.get(i, j));
+            }
+        }
+        let reachable = best.iter().copied().filter(|s| Self::is_reachable(*s));
+        let (min, max) = reachable.fold((i32::MAX, i32::MIN), |(min, max), s| {
+            (min.min(s), max.max(s))
+        });
+        let range = f64::from(max.saturating_sub(min).max(1));
+        let pixels = best
+            .iter()
+            .map(|score| {
+                if Self::is_reachable(*score) {
+                    let fraction = f64::from(score - min) / range;
+                    (255.0 * (1.0 - fraction)).round() as u8
+                } else {
+                    u8::MAX
+                }
+            })
+            .collect::<Vec<_>>();
+        write_grayscale_png(writer, width, height, &pixels)
+    }
+}
+
+/// A k-mer shared by the query and a target.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub struct DotPlotPoint {
+    /// The 0-based position of the k-mer in the query.
+    pub query_pos: usize,
+    /// The 0-based position of the k-mer on the forward strand of the target.
+    pub target_pos: usize,
+    /// True if the k-mer is shared with the forward strand of the target, false for the reverse
+    /// strand.
+    pub is_forward: bool,
+}
+
+/// The k-mers shared by the query and a single target.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DotPlot {
+    /// The name of the target.
+    pub name: String,
+    /// The length of the target.
+    pub target_len: usize,
+    /// The length of the query.
+    pub query_len: usize,
+    /// The shared k-mers, sorted by query then target position.
+    pub points: Vec<DotPlotPoint>,
+}
+
+impl DotPlot {
+    /// Builds the dot-plot of the query versus each target, using k-mers of length `k`.  If
+    /// `double_strand` is true, k-mers shared with the reverse strand of the target are included.
+    pub fn build(
+        target_seqs: &[TargetSeq],
+        y: &[u8],
+        k: usize,
+        double_strand: bool,
+    ) -> Vec<DotPlot> {
+        target_seqs
+            .iter()
+            .map(|target_seq| {
+                let mut points = Vec::new();
+                if y.len() >= k {
+                    let fwd_hash = hash_kmers(&target_seq.fwd, k);
+                    let revcomp_hash = hash_kmers(&target_seq.revcomp, k);
+                    for (query_pos, kmer) in y.windows(k).enumerate() {
+                        for target_pos in fwd_hash.get(kmer).into_iter().flatten() {
+                            points.push(DotPlotPoint {
+                                query_pos,
+                                target_pos: *target_pos as usize,
+                                is_forward: true,
+                            });
+                        }
+                        if !double_strand {
+                            continue;
+                        }
+                        for revcomp_pos in revcomp_hash.get(kmer).into_iter().flatten() {
+                            points.push(DotPlotPoint {
+                                query_pos,
+                                target_pos: target_seq.len() - k - *revcomp_pos as usize,
+                                is_forward: false,
+                            });
+                        }
+                    }
+                }
+                points.sort_unstable();
+                DotPlot {
+                    name: target_seq.name.clone(),
+                    target_len: target_seq.len(),
+                    query_len: y.len(),
+                    points,
+                }
+            })
+            .collect()
+    }
+
+    /// Writes the shared k-mers as TSV, with the target name, strand, query position, and target
+    /// position of each k-mer.
+    pub fn write_tsv<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writeln!(writer, "target\tstrand\tquery_pos\ttarget_pos")?;
+        for point in &self.points {
+            let strand = if point.is_forward { '+' } else { '-' };
+            writeln!(
+                writer,
+                "{}\t{strand}\t{}\t{}",
+                self.name, point.query_pos, point.target_pos
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the dot-plot as a grayscale PNG, with the query along the x-axis and the target
+    /// along the y-axis, with shared k-mers in black (forward strand) and gray (reverse strand).
+    /// The image is downsampled such that neither dimension exceeds `max_size` pixels.
+    pub fn write_png<W: Write>(&self, writer: &mut W, max_size: usize) -> Result<()> {
+        let scale = downsampling_scale(self.query_len, self.target_len, max_size);
+        let width = self.query_len.div_ceil(scale).max(1);
+        let height = self.target_len.div_ceil(scale).max(1);
+        let mut pixels = vec![u8::MAX; width * height];
+        for point in &self.points {
+            let pixel = &mut pixels[(point.target_pos / scale) * width + point.query_pos / scale];
+            let value = if point.is_forward { 0 } else { 128 };
+            *pixel = (*pixel).min(value);
+        }
+        write_grayscale_png(writer, width, height, &pixels)
+    }
+}
+
+/// Returns the factor by which to downsample an image such that neither dimension exceeds the
+/// maximum size.
+fn downsampling_scale(width: usize, height: usize, max_size: usize) -> usize {
+    assert!(max_size > 0, "The maximum image size must be positive");
+    width.max(height).div_ceil(max_size).max(1)
+}
+
+/// Returns the CRC-32 of the bytes, as used by PNG.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Writes a single PNG chunk.
+fn write_png_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<()> {
+    writer.write_all(&u32::try_from(data.len())?.to_be_bytes())?;
+    let mut bytes = kind.to_vec();
+    bytes.extend_from_slice(data);
+    writer.write_all(&bytes)?;
+    writer.write_all(&crc32(&bytes).to_be_bytes())?;
+    Ok(())
+}
+
+/// Writes an 8-bit grayscale PNG with the given pixels, stored by row.
+fn write_grayscale_png<W: Write>(
+    writer: &mut W,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+) -> Result<()> {
+    assert_eq!(pixels.len(), width * height);
+    writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&u32::try_from(width)?.to_be_bytes());
+    header.extend_from_slice(&u32::try_from(height)?.to_be_bytes());
+    // bit depth 8, grayscale, default compression, filtering, and no interlacing
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+    write_png_chunk(writer, b"IHDR", &header)?;
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in pixels.chunks(width) {
+        // no filtering of the row
+        encoder.write_all(&[0])?;
+        encoder.write_all(row)?;
+    }
+    write_png_chunk(writer, b"IDAT", &encoder.finish()?)?;
+    write_png_chunk(writer, b"IEND", &[])?;
+    Ok(())
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use std::io::Read;
+
+    use bio::alignment::pairwise::MatchParams;
+    use flate2::read::ZlibDecoder;
+    use rstest::rstest;
+
+    use crate::{
+        align::{aligners::multi_contig_aligner::MultiContigAligner, scoring::Scoring},
+        util::{dna::reverse_complement, target_seq::TargetSeq},
+    };
+
+    use super::{crc32, write_grayscale_png, DotPlot, DotPlotPoint};
+
+    const CHR1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+
+    #[rstest]
+    fn test_crc32() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[rstest]
+    fn test_write_grayscale_png() {
+        let mut png = Vec::new();
+        write_grayscale_png(&mut png, 3, 2, &[0, 1, 2, 3, 4, 5]).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // IHDR
+        assert_eq!(&png[8..16], b"\x00\x00\x00\x0dIHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 3, 0, 0, 0, 2]);
+        // IDAT
+        let idat_len = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let mut rows = Vec::new();
+        ZlibDecoder::new(&png[41..41 + idat_len])
+            .read_to_end(&mut rows)
+            .unwrap();
+        assert_eq!(rows, [0, 0, 1, 2, 0, 3, 4, 5]);
+        // IEND
+        assert_eq!(
+            &png[png.len() - 12..],
+            b"\x00\x00\x00\x00IEND\xae\x42\x60\x82"
+        );
+    }
+
+    #[rstest]
+    fn test_dot_plot() {
+        let target_seqs = [TargetSeq::new("chr1", &CHR1.as_bytes().to_vec(), false)];
+        // the forward strand of chr1:0-10, then the reverse strand of chr1:20-30
+        let y = [
+            &CHR1.as_bytes()[..10],
+            &reverse_complement(&CHR1.as_bytes()[20..30]),
+        ]
+        .concat();
+        let k = 8;
+
+        let dot_plots = DotPlot::build(&target_seqs, &y, k, false);
+        assert_eq!(dot_plots.len(), 1);
+        let expected_fwd = (0..3).map(|pos| DotPlotPoint {
+            query_pos: pos,
+            target_pos: pos,
+            is_forward: true,
+        });
+        assert_eq!(
+            dot_plots[0].points,
+            expected_fwd.clone().collect::<Vec<_>>()
+        );
+
+        let dot_plots = DotPlot::build(&target_seqs, &y, k, true);
+        let expected_rev = (0..3).map(|pos| DotPlotPoint {
+            query_pos: 10 + pos,
+            target_pos: 22 - pos,
+            is_forward: false,
+        });
+        assert_eq!(
+            dot_plots[0].points,
+            expected_fwd.chain(expected_rev).collect::<Vec<_>>()
+        );
+
+        let mut tsv = Vec::new();
+        dot_plots[0].write_tsv(&mut tsv).unwrap();
+        let tsv = String::from_utf8(tsv).unwrap();
+        let lines = tsv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "target\tstrand\tquery_pos\ttarget_pos");
+        assert_eq!(lines[1], "chr1\t+\t0\t0");
+        assert_eq!(lines[6], "chr1\t-\t12\t20");
+
+        let mut png = Vec::new();
+        dot_plots[0].write_png(&mut png, 10).unwrap();
+        // downsampled by 4 to 5x10 pixels
+        assert_eq!(&png[16..24], &[0, 0, 0, 5, 0, 0, 0, 10]);
+    }
+
+    #[rstest]
+    fn test_score_surface() {
+        let x = CHR1.as_bytes();
+        let y = &x[10..20];
+        let scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_yclip(0);
+        let mut aligner = MultiContigAligner::new();
+        aligner.add_contig("chr1", true, x, false, scoring);
+        assert!(aligner.score_surfaces().is_empty());
+
+        aligner.set_record_score_surfaces(true);
+        let alignment = aligner.custom(y);
+        let surfaces = aligner.score_surfaces();
+        assert_eq!(surfaces.len(), 1);
+        let surface = &surfaces[0];
+        assert_eq!((surface.rows, surface.cols), (x.len() + 1, y.len() + 1));
+        // the score increases along the diagonal of the alignment
+        for j in 0..=y.len() {
+            assert_eq!(surface.get(10 + j, j), j as i32);
+        }
+        assert_eq!(alignment.score, 10);
+
+        let mut tsv = Vec::new();
+        surface.write_tsv(&mut tsv).unwrap();
+        let tsv = String::from_utf8(tsv).unwrap();
+        assert!(tsv.starts_with("contig\tstrand\tcontig_pos\tquery_pos\tscore\n"));
+        assert!(tsv.contains("\nchr1\t+\t20\t10\t10\n"));
+
+        let mut png = Vec::new();
+        surface.write_png(&mut png, 100).unwrap();
+        assert_eq!(&png[16..24], &[0, 0, 0, 11, 0, 0, 0, 41]);
+
+        aligner.set_record_score_surfaces(false);
+        aligner.custom(y);
+        assert!(aligner.score_surfaces().is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+//! Standardized synthetic datasets for benchmarking the aligner, so that the numbers of the
+//! benchmarks in `benches/` can be reproduced, and compared to those of other aligners, by
+//! crates embedding the aligner (with the `test-util` feature).
+//!
+//! Each dataset is generated from a seed with the [`Rng`] of [`crate::test_util`], so the same
+//! seed gives the same targets and queries on every platform:
+//!
+//! ```
+//! use fg_stitch_lib::bench_util::{Dataset, DatasetKind};
+//!
+//! let dataset = Dataset::new(DatasetKind::SingleContig, 42);
+//! let mut aligner = dataset.aligner();
+//! for query in &dataset.queries {
+//!     let alignment = aligner.custom(query);
+//!     assert!(alignment.score > 0);
+//! }
+//! ```
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use bio::alignment::pairwise::MatchParams;
+
+use crate::align::{MultiContigAligner, Scoring};
+use crate::test_util::{multi_contig_aligner, random_dna, Rng};
+use crate::util::target_seq::TargetSeq;
+
+/// The adaptive band width of the [`DatasetKind::Banded`] dataset.
+pub const BENCH_BAND_WIDTH: usize = 64;
+
+/// The kinds of datasets.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DatasetKind {
+    /// Short reads with few errors, aligned to both strands of a single contig.
+    SingleContig,
+    /// Short chimeric reads, each jumping once between two of many short contigs.
+    ManyContigs,
+    /// A long read with many errors, aligned to the forward strand of a single long contig.
+    LongRead,
+    /// The long read of [`DatasetKind::LongRead`], aligned within an adaptive band (see
+    /// [`MultiContigAligner::set_adaptive_band_width`]).
+    Banded,
+}
+
+impl DatasetKind {
+    /// All the kinds of datasets.
+    pub const ALL: [DatasetKind; 4] = [
+        DatasetKind::SingleContig,
+        DatasetKind::ManyContigs,
+        DatasetKind::LongRead,
+        DatasetKind::Banded,
+    ];
+}
+
+impl Display for DatasetKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DatasetKind::SingleContig => "single-contig",
+            DatasetKind::ManyContigs => "many-contigs",
+            DatasetKind::LongRead => "long-read",
+            DatasetKind::Banded => "banded",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for DatasetKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "single-contig" => Ok(DatasetKind::SingleContig),
+            "many-contigs" => Ok(DatasetKind::ManyContigs),
+            "long-read" => Ok(DatasetKind::LongRead),
+            "banded" => Ok(DatasetKind::Banded),
+            _ => bail!("Unknown dataset: {s}"),
+        }
+    }
+}
+
+/// The targets and queries of a benchmark, with the scoring and options of the aligner.
+#[derive(Clone, Debug)]
+pub struct Dataset {
+    pub kind: DatasetKind,
+    pub target_seqs: Vec<TargetSeq>,
+    pub queries: Vec<Vec<u8>>,
+    pub scoring: Scoring<MatchParams>,
+    /// True to align to both strands of the targets.
+    pub double_strand: bool,
+    pub adaptive_band_width: Option<usize>,
+}
+
+impl Dataset {
+    /// Generates the dataset of the given kind from the given seed.
+    pub fn new(kind: DatasetKind, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let scoring = Scoring::with_jump_score(-6, -2, -10, MatchParams::new(1, -4));
+        let (target_seqs, queries, double_strand) = match kind {
+            DatasetKind::SingleContig => {
+                let target_seqs = random_targets(&mut rng, 1, 2_000);
+                let queries = (0..20)
+                    .map(|_| simulate_read(&mut rng, &target_seqs, 500, 0.02, 1, true))
+                    .collect();
+                (target_seqs, queries, true)
+            }
+            DatasetKind::ManyContigs => {
+                let target_seqs = random_targets(&mut rng, 100, 500);
+                let queries = (0..20)
+                    .map(|_| simulate_read(&mut rng, &target_seqs, 300, 0.02, 2, true))
+                    .collect();
+                (target_seqs, queries, true)
+            }
+            DatasetKind::LongRead | DatasetKind::Banded => {
+                let target_seqs = random_targets(&mut rng, 1, 10_000);
+                let queries = vec![simulate_read(&mut rng, &target_seqs, 5_000, 0.1, 1, false)];
+                (target_seqs, queries, false)
+            }
+        };
+        let adaptive_band_width = (kind == DatasetKind::Banded).then_some(BENCH_BAND_WIDTH);
+        Self {
+            kind,
+            target_seqs,
+            queries,
+            scoring,
+            double_strand,
+            adaptive_band_width,
+        }
+    }
+
+    /// Builds the aligner of the targets, with the scoring and options of the dataset.
+    pub fn aligner(&self) -> MultiContigAligner<MatchParams> {
+        let mut aligner = multi_contig_aligner(&self.target_seqs, self.scoring, self.double_strand);
+        aligner.set_adaptive_band_width(self.adaptive_band_width);
+        aligner
+    }
+
+    /// The number of cells of the alignment matrices filled to align all the queries without a
+    /// band, to report the throughput of a benchmark in cells per second.
+    pub fn num_cells(&self) -> u64 {
+        let strands = if self.double_strand { 2 } else { 1 };
+        let target_len: usize = self.target_seqs.iter().map(|t| t.fwd.len()).sum();
+        let query_len: usize = self.queries.iter().map(Vec::len).sum();
+        (strands * target_len * query_len) as u64
+    }
+}
+
+/// Returns `count` linear targets, named `chr1`, `chr2`, etc., each of random DNA of length `len`.
+fn random_targets(rng: &mut Rng, count: usize, len: usize) -> Vec<TargetSeq> {
+    (1..=count)
+        .map(|i| TargetSeq::new(&format!("chr{i}"), &random_dna(rng, len), false))
+        .collect()
+}
+
+/// Returns a read of about `len` bases from `num_segments` segments of random targets, each from a
+/// random position of the target (and strand, if `double_strand` is true), with substitutions,
+/// insertions, and deletions in equal proportions at the given rate of errors per base.
+pub fn simulate_read(
+    rng: &mut Rng,
+    target_seqs: &[TargetSeq],
+    len: usize,
+    error_rate: f64,
+    num_segments: usize,
+    double_strand: bool,
+) -> Vec<u8> {
+    // The error rate, in errors per million bases
+    let errors_per_million = (error_rate * 1e6) as usize;
+    let mut read = Vec::with_capacity(len + len / 10);
+    for _ in 0..num_segments {
+        let target_seq = &target_seqs[rng.below(target_seqs.len())];
+        let bases = if double_strand && rng.below(2) == 1 {
+            &target_seq.revcomp
+        } else {
+            &target_seq.fwd
+        };
+        let segment_len = (len / num_segments).min(bases.len());
+        let start = rng.below(bases.len() - segment_len + 1);
+        for &base in &bases[start..start + segment_len] {
+            if rng.below(1_000_000) >= errors_per_million {
+                read.push(base);
+                continue;
+            }
+            match rng.below(3) {
+                0 => {
+                    let index = b"ACGT".iter().position(|b| *b == base).unwrap_or(0);
+                    read.push(b"ACGT"[(index + 1 + rng.below(3)) % 4]);
+                }
+                1 => read.extend([base, b"ACGT"[rng.below(4)]]),
+                _ => (),
+            }
+        }
+    }
+    read
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::{Dataset, DatasetKind, BENCH_BAND_WIDTH};
+
+    #[rstest]
+    fn test_datasets() {
+        for kind in DatasetKind::ALL {
+            assert_eq!(kind.to_string().parse::<DatasetKind>().unwrap(), kind);
+            let dataset = Dataset::new(kind, 42);
+            // reproducible from the seed
+            let again = Dataset::new(kind, 42);
+            assert_eq!(dataset.queries, again.queries);
+            assert_eq!(
+                dataset
+                    .target_seqs
+                    .iter()
+                    .map(|t| &t.fwd)
+                    .collect::<Vec<_>>(),
+                again.target_seqs.iter().map(|t| &t.fwd).collect::<Vec<_>>()
+            );
+            assert!(dataset.num_cells() > 0);
+        }
+        assert_eq!(
+            Dataset::new(DatasetKind::Banded, 42).adaptive_band_width,
+            Some(BENCH_BAND_WIDTH)
+        );
+        assert!("unknown".parse::<DatasetKind>().is_err());
+
+        let dataset = Dataset::new(DatasetKind::SingleContig, 42);
+        let mut aligner = dataset.aligner();
+        for query in &dataset.queries {
+            let alignment = aligner.custom(query);
+            assert!(alignment.score > 0);
+        }
+    }
+}
@@ -14,4 +14,8 @@
 )]
 
 pub mod align;
+#[cfg(any(test, feature = "test-util"))]
+pub mod bench_util;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 pub mod util;
@@ -0,0 +1,270 @@
+//! Generators of random inputs and checkers of the invariants of alignments, for property tests of
+//! the aligner, including by crates embedding the aligner (with the `test-util` feature).
+//!
+//! The generators are driven by a small seeded [`Rng`], so a failing case is reproduced by its
+//! seed, and produce short sequences to keep the [`ReferenceAligner`] fast.  The checkers return
+//! an error describing the first violated invariant, for example:
+//!
+//! ```
+//! use fg_stitch_lib::test_util::{
+//!     align, check_invariants, random_dna, random_scoring, random_target_seqs, Rng,
+//! };
+//!
+//! let mut rng = Rng::new(42);
+//! for _ in 0..10 {
+//!     let target_seqs = random_target_seqs(&mut rng, 2, 10);
+//!     let scoring = random_scoring(&mut rng);
+//!     let len = rng.below(12) + 1;
+//!     let y = random_dna(&mut rng, len);
+//!     let alignment = align(&target_seqs, &y, scoring, true);
+//!     check_invariants(&alignment, &target_seqs, &y, &scoring, true).unwrap();
+//! }
+//! ```
+
+use anyhow::{bail, ensure, Result};
+use bio::alignment::pairwise::MatchParams;
+
+use crate::align::{
+    contig_bases_for, testing::ReferenceAligner, Alignment, AlignmentOperation, MultiContigAligner,
+    Scoring, MIN_SCORE,
+};
+use crate::util::target_seq::TargetSeq;
+
+/// The clipping scores chosen by [`random_scoring`], from free clipping to no clipping at all.
+const CLIP_SCORES: [i32; 4] = [0, -1, -3, MIN_SCORE];
+
+/// The seed of the random inputs of the tests, so that they are the same on every run.
+pub const SEED: u64 = 42;
+
+/// A small xorshift pseudo-random number generator, so the random inputs are the same for the
+/// same seed on every platform.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Creates a generator from the given seed.  A seed of zero is replaced by a fixed non-zero
+    /// seed, as the generator would otherwise only return zeros.
+    pub fn new(seed: u64) -> Self {
+        Self(if seed == 0 { 0x5EED } else { seed })
+    }
+
+    /// Returns a number in `[0, bound)`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+/// Returns random DNA (upper-case `ACGT`) of the given length.
+pub fn random_dna(rng: &mut Rng, len: usize) -> Vec<u8> {
+    (0..len).map(|_| b"ACGT"[rng.below(4)]).collect()
+}
+
+/// Returns a random scoring, with random match and mismatch scores, affine gap scores, clipping
+/// scores (from free clipping to no clipping), and jump scores.  Backward jumps, when scored
+/// separately, are never cheaper than forward jumps (see [`ReferenceAligner`]).
+pub fn random_scoring(rng: &mut Rng) -> Scoring<MatchParams> {
+    let match_params = MatchParams::new(rng.below(3) as i32 + 1, -(rng.below(4) as i32) - 1);
+    let gap_open = -(rng.below(5) as i32);
+    let gap_extend = -(rng.below(3) as i32) - 1;
+    let jump_score = -(rng.below(8) as i32) - 1;
+    let mut scoring = Scoring::with_jump_score(gap_open, gap_extend, jump_score, match_params)
+        .set_xclip(CLIP_SCORES[rng.below(CLIP_SCORES.len())])
+        .set_yclip(CLIP_SCORES[rng.below(CLIP_SCORES.len())]);
+    scoring.jump_score_same_contig_opposite_strand = -(rng.below(8) as i32) - 1;
+    scoring.jump_score_inter_contig = -(rng.below(8) as i32) - 1;
+    if rng.below(2) == 0 {
+        scoring.jump_score_backward = Some(jump_score - rng.below(4) as i32);
+    }
+    scoring
+}
+
+/// Returns between one and `max_targets` random linear targets, named `chr1`, `chr2`, etc., each
+/// of random DNA with a length between one and `max_len`.
+pub fn random_target_seqs(rng: &mut Rng, max_targets: usize, max_len: usize) -> Vec<TargetSeq> {
+    let num_targets = rng.below(max_targets) + 1;
+    (1..=num_targets)
+        .map(|i| {
+            let len = rng.below(max_len) + 1;
+            TargetSeq::new(&format!("chr{i}"), &random_dna(rng, len), false)
+        })
+        .collect()
+}
+
+/// Builds the aligner of the forward strands of the targets, then their reverse strands if
+/// `double_strand` is true, following the contig indexes of
+/// [`crate::align::Builder::build_aligners`].
+pub fn multi_contig_aligner(
+    target_seqs: &[TargetSeq],
+    scoring: Scoring<MatchParams>,
+    double_strand: bool,
+) -> MultiContigAligner<MatchParams> {
+    let mut aligner = MultiContigAligner::new();
+    for target_seq in target_seqs {
+        aligner.add_contig(&target_seq.name, true, &target_seq.fwd, false, scoring);
+    }
+    if double_strand {
+        for target_seq in target_seqs {
+            aligner.add_contig(&target_seq.name, false, &target_seq.revcomp, false, scoring);
+        }
+    }
+    aligner
+}
+
+/// Returns the best alignment of the query to the targets (see [`multi_contig_aligner`]).
+pub fn align(
+    target_seqs: &[TargetSeq],
+    y: &[u8],
+    scoring: Scoring<MatchParams>,
+    double_strand: bool,
+) -> Alignment {
+    multi_contig_aligner(target_seqs, scoring, double_strand).custom(y)
+}
+
+/// Checks that the score of the alignment is the score of its operations, re-scored against the
+/// targets and the query (see [`Alignment::rescore`]).
+pub fn check_score_consistency(
+    alignment: &Alignment,
+    target_seqs: &[TargetSeq],
+    y: &[u8],
+    scoring: &Scoring<MatchParams>,
+) -> Result<()> {
+    let rescored = alignment.rescore(target_seqs, y, scoring);
+    ensure!(
+        rescored == alignment.score,
+        "The alignment score {} differs from the score of its operations {rescored}: {alignment}",
+        alignment.score
+    );
+    Ok(())
+}
+
+/// Checks that the operations of the alignment consume the query exactly, and that the bases of
+/// the query and the contigs are those aligned by matches and mismatches.
+pub fn check_operations(alignment: &Alignment, target_seqs: &[TargetSeq], y: &[u8]) -> Result<()> {
+    ensure!(
+        alignment.ylen == y.len(),
+        "The alignment query length {} differs from the query length {}: {alignment}",
+        alignment.ylen,
+        y.len()
+    );
+    let mut steps = alignment.iter_steps().peekable();
+    let Some(first) = steps.peek() else {
+        return Ok(());
+    };
+    ensure!(
+        first.y_pos == 0,
+        "The alignment operations start at query offset {}: {alignment}",
+        first.y_pos
+    );
+    let mut y_end = 0;
+    for step in steps {
+        if matches!(
+            step.op,
+            AlignmentOperation::Match | AlignmentOperation::Subst
+        ) {
+            let is_match =
+                contig_bases_for(target_seqs, step.contig_idx)[step.x_pos] == y[step.y_pos];
+            match step.op {
+                AlignmentOperation::Match if !is_match => {
+                    bail!(
+                        "A match at query offset {} is a mismatch: {alignment}",
+                        step.y_pos
+                    )
+                }
+                AlignmentOperation::Subst if is_match => {
+                    bail!(
+                        "A mismatch at query offset {} is a match: {alignment}",
+                        step.y_pos
+                    )
+                }
+                _ => (),
+            }
+        }
+        y_end = step.y_pos + step.op.length_on_y();
+    }
+    ensure!(
+        y_end == y.len(),
+        "The alignment operations end at query offset {y_end} of {}: {alignment}",
+        y.len()
+    );
+    Ok(())
+}
+
+/// Checks that the alignment score is the best score of the [`ReferenceAligner`], with the
+/// contigs of [`multi_contig_aligner`].
+pub fn check_reference_score(
+    alignment: &Alignment,
+    target_seqs: &[TargetSeq],
+    y: &[u8],
+    scoring: Scoring<MatchParams>,
+    double_strand: bool,
+) -> Result<()> {
+    let mut reference = ReferenceAligner::new(scoring);
+    for target_seq in target_seqs {
+        reference.add_contig(&target_seq.name, true, &target_seq.fwd);
+    }
+    if double_strand {
+        for target_seq in target_seqs {
+            reference.add_contig(&target_seq.name, false, &target_seq.revcomp);
+        }
+    }
+    let expected = reference.score(y);
+    ensure!(
+        alignment.score == expected,
+        "The alignment score {} differs from the reference score {expected}: {alignment}",
+        alignment.score
+    );
+    Ok(())
+}
+
+/// Checks all the invariants of the best alignment of the query to the targets: those of
+/// [`check_operations`], [`check_score_consistency`], and [`check_reference_score`].
+pub fn check_invariants(
+    alignment: &Alignment,
+    target_seqs: &[TargetSeq],
+    y: &[u8],
+    scoring: &Scoring<MatchParams>,
+    double_strand: bool,
+) -> Result<()> {
+    check_operations(alignment, target_seqs, y)?;
+    check_score_consistency(alignment, target_seqs, y, scoring)?;
+    check_reference_score(alignment, target_seqs, y, *scoring, double_strand)
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::{
+        align, check_invariants, random_dna, random_scoring, random_target_seqs, Rng, SEED,
+    };
+
+    #[rstest]
+    fn test_rng_is_seeded() {
+        let mut first = Rng::new(7);
+        let mut second = Rng::new(7);
+        let first: Vec<usize> = (0..10).map(|_| first.below(100)).collect();
+        let second: Vec<usize> = (0..10).map(|_| second.below(100)).collect();
+        assert_eq!(first, second);
+        assert!(first.iter().all(|value| *value < 100));
+        assert_ne!(Rng::new(0).below(1000), Rng::new(0).below(1000) + 1000);
+    }
+
+    #[rstest]
+    #[case::single_strand(false)]
+    #[case::double_strand(true)]
+    fn test_check_invariants(#[case] double_strand: bool) {
+        let mut rng = Rng::new(SEED);
+        for _ in 0..300 {
+            let target_seqs = random_target_seqs(&mut rng, 2, 10);
+            let scoring = random_scoring(&mut rng);
+            let len = rng.below(12) + 1;
+            let y = random_dna(&mut rng, len);
+            let alignment = align(&target_seqs, &y, scoring, double_strand);
+            check_invariants(&alignment, &target_seqs, &y, &scoring, double_strand).unwrap();
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! The alphabets of the sequences to align.  The aligner itself only compares symbols with a match
+//! function, so it aligns any alphabet, but the strands of the contigs (and so the jumps between
+//! strands) only exist for the alphabets with a complement, such as DNA.
+
+use crate::util::dna::{reverse_complement, IUPAC_BASES};
+
+/// The 20 amino acids, with the ambiguity codes `B` (`D` or `N`), `Z` (`E` or `Q`), `X` (any),
+/// and the stop `*`.
+pub const AMINO_ACIDS: [u8; 24] = *b"ARNDCQEGHILKMFPSTWYVBZX*";
+
+/// An alphabet of sequences, which determines whether the contigs have a reverse strand.
+pub trait Alphabet {
+    /// The name of the alphabet, for messages.
+    const NAME: &'static str;
+
+    /// True if the sequences have a reverse strand, such that contigs are aligned on both strands
+    /// and queries may jump between strands.
+    const HAS_STRANDS: bool;
+
+    /// True if the (case-insensitive) symbol belongs to the alphabet.
+    fn is_valid(symbol: u8) -> bool;
+
+    /// The reverse strand of the sequence, or `None` if the alphabet has no strands.
+    fn reverse_strand(seq: &[u8]) -> Option<Vec<u8>>;
+
+    /// The first symbol of the sequence that does not belong to the alphabet, if any.
+    fn first_invalid(seq: &[u8]) -> Option<u8> {
+        seq.iter().copied().find(|&symbol| !Self::is_valid(symbol))
+    }
+}
+
+/// The IUPAC DNA bases, with the reverse complement as the reverse strand.
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Dna;
+
+impl Alphabet for Dna {
+    const NAME: &'static str = "DNA";
+    const HAS_STRANDS: bool = true;
+
+    fn is_valid(symbol: u8) -> bool {
+        IUPAC_BASES.contains(&symbol.to_ascii_uppercase())
+    }
+
+    fn reverse_strand(seq: &[u8]) -> Option<Vec<u8>> {
+        Some(reverse_complement(seq))
+    }
+}
+
+/// The amino acids (see [`AMINO_ACIDS`]), which have no reverse strand.
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Protein;
+
+impl Alphabet for Protein {
+    const NAME: &'static str = "protein";
+    const HAS_STRANDS: bool = false;
+
+    fn is_valid(symbol: u8) -> bool {
+        AMINO_ACIDS.contains(&symbol.to_ascii_uppercase())
+    }
+
+    fn reverse_strand(_seq: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::{Alphabet, Dna, Protein};
+
+    #[rstest]
+    fn test_dna() {
+        const { assert!(Dna::HAS_STRANDS) };
+        assert_eq!(Dna::reverse_strand(b"AACG"), Some(b"CGTT".to_vec()));
+        assert_eq!(Dna::first_invalid(b"acgtN"), None);
+        assert_eq!(Dna::first_invalid(b"ACGEQ"), Some(b'E'));
+    }
+
+    #[rstest]
+    fn test_protein() {
+        const { assert!(!Protein::HAS_STRANDS) };
+        assert_eq!(Protein::reverse_strand(b"MKV*"), None);
+        assert_eq!(Protein::first_invalid(b"mkvEQX*"), None);
+        assert_eq!(Protein::first_invalid(b"MKVO"), Some(b'O'));
+    }
+}
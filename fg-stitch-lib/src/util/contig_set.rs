@@ -0,0 +1,486 @@
+//! A set of contigs preprocessed for alignment, namely their sequences on both strands, their MD5
+//! checksums, the k-mer indexes used for banded pre-alignment, and the scoring they were built
+//! with, that may be persisted to an index file so that repeated runs against the same reference
+//! skip reading the FASTA and building the k-mer indexes.
+
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use bio::alignment::{
+    pairwise::MatchParams,
+    sparse::{hash_kmers, HashMapFx},
+};
+use fgoxide::io::Io;
+
+use super::{
+    md5::sequence_md5,
+    target_seq::{self, TargetHash, TargetSeq},
+};
+use crate::align::Scoring;
+
+/// The first bytes of a contig index file.
+const MAGIC: &[u8; 8] = b"STITCHCI";
+
+/// The version of the contig index file format, incremented on any change to the format.
+const VERSION: u32 = 3;
+
+/// The k-mer index of one strand of a contig: each distinct k-mer, identified by the position of
+/// its first occurrence, with the positions of all its occurrences.
+type KmerIndex = Vec<(u32, Vec<u32>)>;
+
+/// The contigs (targets) to align to, with their k-mer indexes and the scoring they were built
+/// with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContigSet {
+    target_seqs: Vec<TargetSeq>,
+    /// The MD5 checksum of each contig (see [`sequence_md5`]).
+    md5s: Vec<String>,
+    kmer_size: usize,
+    /// The k-mer indexes of the forward then reverse strand of each contig.
+    kmer_indexes: Vec<[KmerIndex; 2]>,
+    scoring: Scoring<MatchParams>,
+}
+
+impl ContigSet {
+    /// Creates the set of the given contigs, computing their MD5 checksums and building their
+    /// k-mer indexes with the given k-mer size.
+    pub fn new(
+        target_seqs: Vec<TargetSeq>,
+        kmer_size: usize,
+        scoring: Scoring<MatchParams>,
+    ) -> Self {
+        let kmer_indexes = target_seqs
+            .iter()
+            .map(|target_seq| {
+                [
+                    kmer_index(&target_seq.fwd, kmer_size),
+                    kmer_index(&target_seq.revcomp, kmer_size),
+                ]
+            })
+            .collect();
+        let md5s = target_seqs
+            .iter()
+            .map(|target_seq| sequence_md5(&target_seq.fwd))
+            .collect();
+        Self {
+            target_seqs,
+            md5s,
+            kmer_size,
+            kmer_indexes,
+            scoring,
+        }
+    }
+
+    /// Reads all the contigs from the FASTA (see [`target_seq::from_fasta`]), builds the set of
+    /// contigs, and writes it to the index file at `path`.
+    pub fn build(
+        fasta: &PathBuf,
+        circular: bool,
+        kmer_size: usize,
+        scoring: Scoring<MatchParams>,
+        path: &Path,
+    ) -> Result<Self> {
+        let target_seqs = target_seq::from_fasta(fasta, circular)?;
+        let contig_set = Self::new(target_seqs, kmer_size, scoring);
+        contig_set.write(path)?;
+        Ok(contig_set)
+    }
+
+    /// Reads the set of contigs from the index file at `path` (see [`Self::write`]).
+    pub fn load(path: &Path) -> Result<Self> {
+        let reader = Io::default()
+            .new_reader(&path)
+            .with_context(|| format!("Error opening contig index: {}", path.display()))?;
+        Self::read(reader)
+            .with_context(|| format!("Error reading contig index: {}", path.display()))
+    }
+
+    /// Writes the set of contigs to the index file at `path`, which is GZIP compressed if the
+    /// path ends with `.gz`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut writer = Io::default()
+            .new_writer(&path)
+            .with_context(|| format!("Error creating contig index: {}", path.display()))?;
+        self.write_to(&mut writer)
+            .and_then(|()| writer.flush().map_err(Into::into))
+            .with_context(|| format!("Error writing contig index: {}", path.display()))
+    }
+
+    /// The contigs, in the order in which they were added.
+    pub fn target_seqs(&self) -> &[TargetSeq] {
+        &self.target_seqs
+    }
+
+    /// Returns the contigs, in the order in which they were added.
+    pub fn into_target_seqs(self) -> Vec<TargetSeq> {
+        self.target_seqs
+    }
+
+    /// The MD5 checksum of each contig, in the order in which they were added, as for the `M5`
+    /// tag of the SAM `@SQ` header lines (see [`sequence_md5`]).
+    pub fn md5s(&self) -> &[String] {
+        &self.md5s
+    }
+
+    /// The k-mer size of the k-mer indexes.
+    pub fn kmer_size(&self) -> usize {
+        self.kmer_size
+    }
+
+    /// The scoring the set of contigs was built with, as the default scoring for aligning to it.
+    pub fn scoring(&self) -> &Scoring<MatchParams> {
+        &self.scoring
+    }
+
+    /// Returns the k-mer hashes of the contigs used for banded pre-alignment with the given
+    /// k-mer size, from the k-mer indexes if built with that size, and otherwise hashed anew.
+    pub fn target_hashes(&self, kmer_size: usize) -> Vec<TargetHash<'_>> {
+        if kmer_size != self.kmer_size {
+            return self
+                .target_seqs
+                .iter()
+                .map(|target_seq| target_seq.build_target_hash(kmer_size))
+                .collect();
+        }
+        self.target_seqs
+            .iter()
+            .zip(&self.kmer_indexes)
+            .map(|(target_seq, [fwd, revcomp])| TargetHash {
+                name: target_seq.name.clone(),
+                fwd_hash: kmer_hash(&target_seq.fwd, fwd, kmer_size),
+                revcomp_hash: kmer_hash(&target_seq.revcomp, revcomp, kmer_size),
+            })
+            .collect()
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        write_u32(writer, VERSION)?;
+        write_u64(writer, self.kmer_size as u64)?;
+        write_scoring(writer, &self.scoring)?;
+        write_u64(writer, self.target_seqs.len() as u64)?;
+        for ((target_seq, md5), kmer_indexes) in self
+            .target_seqs
+            .iter()
+            .zip(&self.md5s)
+            .zip(&self.kmer_indexes)
+        {
+            write_bytes(writer, target_seq.name.as_bytes())?;
+            writer.write_all(&[u8::from(target_seq.circular)])?;
+            write_bytes(writer, &target_seq.fwd)?;
+            write_bytes(writer, &target_seq.revcomp)?;
+            write_bytes(writer, md5.as_bytes())?;
+            for kmer_index in kmer_indexes {
+                write_u64(writer, kmer_index.len() as u64)?;
+                for (first, positions) in kmer_index {
+                    write_u32(writer, *first)?;
+                    write_u32s(writer, positions)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(mut reader: R) -> Result<Self> {
+        let reader = &mut reader;
+        let mut magic = [0u8; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        ensure!(&magic == MAGIC, "Not a contig index");
+        let version = read_u32(reader)?;
+        ensure!(
+            version == VERSION,
+            "Unsupported contig index version {version}, expected {VERSION}"
+        );
+        let kmer_size = read_u64(reader)? as usize;
+        let scoring = read_scoring(reader)?;
+        let num_targets = read_u64(reader)? as usize;
+        let mut target_seqs = Vec::new();
+        let mut md5s = Vec::new();
+        let mut kmer_indexes = Vec::new();
+        for _ in 0..num_targets {
+            let name = String::from_utf8(read_bytes(reader)?).context("Invalid contig name")?;
+            let circular = read_u8(reader)? != 0;
+            let fwd = read_bytes(reader)?;
+            let revcomp = read_bytes(reader)?;
+            ensure!(
+                fwd.len() == revcomp.len(),
+                "The strands of contig {name} differ in length"
+            );
+            let md5 = String::from_utf8(read_bytes(reader)?)
+                .ok()
+                .filter(|md5| md5.len() == 32 && md5.bytes().all(|b| b.is_ascii_hexdigit()))
+                .with_context(|| format!("Invalid MD5 checksum of contig {name}"))?;
+            let mut strands: [KmerIndex; 2] = Default::default();
+            for kmer_index in &mut strands {
+                let num_kmers = read_u64(reader)? as usize;
+                for _ in 0..num_kmers {
+                    let first = read_u32(reader)?;
+                    ensure!(
+                        first as usize + kmer_size <= fwd.len(),
+                        "K-mer out of bounds of contig {name}"
+                    );
+                    kmer_index.push((first, read_u32s(reader)?));
+                }
+            }
+            target_seqs.push(TargetSeq {
+                name,
+                fwd,
+                revcomp,
+                circular,
+            });
+            md5s.push(md5);
+            kmer_indexes.push(strands);
+        }
+        if reader.read(&mut [0u8])? > 0 {
+            bail!("Unexpected data at the end of the contig index");
+        }
+        Ok(Self {
+            target_seqs,
+            md5s,
+            kmer_size,
+            kmer_indexes,
+            scoring,
+        })
+    }
+}
+
+/// Builds the k-mer index of the given sequence (see [`KmerIndex`]).
+fn kmer_index(seq: &[u8], kmer_size: usize) -> KmerIndex {
+    let mut index = hash_kmers(seq, kmer_size)
+        .into_values()
+        .map(|positions| (positions[0], positions))
+        .collect::<Vec<_>>();
+    index.sort_unstable_by_key(|(first, _)| *first);
+    index
+}
+
+/// Builds the k-mer hash of the given sequence from its k-mer index (see [`KmerIndex`]).
+fn kmer_hash<'a>(
+    seq: &'a [u8],
+    index: &KmerIndex,
+    kmer_size: usize,
+) -> HashMapFx<&'a [u8], Vec<u32>> {
+    let mut hash = HashMapFx::default();
+    hash.reserve(index.len());
+    for (first, positions) in index {
+        let first = *first as usize;
+        hash.insert(&seq[first..first + kmer_size], positions.clone());
+    }
+    hash
+}
+
+fn write_scoring<W: Write>(writer: &mut W, scoring: &Scoring<MatchParams>) -> Result<()> {
+    for score in [
+        scoring.match_fn.match_score,
+        scoring.match_fn.mismatch_score,
+        scoring.gap_open,
+        scoring.gap_extend,
+        scoring.jump_score_same_contig_and_strand,
+        scoring.jump_score_same_contig_opposite_strand,
+        scoring.jump_score_inter_contig,
+        scoring.xclip_prefix,
+        scoring.xclip_suffix,
+        scoring.yclip_prefix,
+        scoring.yclip_suffix,
+    ] {
+        write_i32(writer, score)?;
+    }
+    for score in [
+        scoring.jump_score_forward,
+        scoring.jump_score_backward,
+        scoring.splice_jump_score,
+        scoring.homopolymer_gap_open,
+    ] {
+        write_option_i32(writer, score)?;
+    }
+    write_u64(writer, scoring.homopolymer_min_length as u64)?;
+    match scoring.match_scores {
+        Some((match_score, mismatch_score)) => {
+            writer.write_all(&[1])?;
+            write_i32(writer, match_score)?;
+            write_i32(writer, mismatch_score)?;
+        }
+        None => writer.write_all(&[0])?,
+    }
+    Ok(())
+}
+
+fn read_scoring<R: Read>(reader: &mut R) -> Result<Scoring<MatchParams>> {
+    let mut scores = [0; 11];
+    for score in &mut scores {
+        *score = read_i32(reader)?;
+    }
+    let jump_score_forward = read_option_i32(reader)?;
+    let jump_score_backward = read_option_i32(reader)?;
+    let splice_jump_score = read_option_i32(reader)?;
+    let homopolymer_gap_open = read_option_i32(reader)?;
+    let homopolymer_min_length = read_u64(reader)? as usize;
+    let match_scores = if read_u8(reader)? != 0 {
+        Some((read_i32(reader)?, read_i32(reader)?))
+    } else {
+        None
+    };
+    Ok(Scoring {
+        gap_open: scores[2],
+        gap_extend: scores[3],
+        jump_score_same_contig_and_strand: scores[4],
+        jump_score_same_contig_opposite_strand: scores[5],
+        jump_score_inter_contig: scores[6],
+        jump_score_forward,
+        jump_score_backward,
+        splice_jump_score,
+        homopolymer_gap_open,
+        homopolymer_min_length,
+        match_fn: MatchParams::new(scores[0], scores[1]),
+        match_scores,
+        xclip_prefix: scores[7],
+        xclip_suffix: scores[8],
+        yclip_prefix: scores[9],
+        yclip_suffix: scores[10],
+    })
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> Result<()> {
+    writer.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> Result<()> {
+    writer.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_i32<W: Write>(writer: &mut W, value: i32) -> Result<()> {
+    writer.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_option_i32<W: Write>(writer: &mut W, value: Option<i32>) -> Result<()> {
+    writer.write_all(&[u8::from(value.is_some())])?;
+    write_i32(writer, value.unwrap_or(0))
+}
+
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+    write_u64(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+fn write_u32s<W: Write>(writer: &mut W, values: &[u32]) -> Result<()> {
+    write_u64(writer, values.len() as u64)?;
+    for value in values {
+        write_u32(writer, *value)?;
+    }
+    Ok(())
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_i32<R: Read>(reader: &mut R) -> Result<i32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+fn read_option_i32<R: Read>(reader: &mut R) -> Result<Option<i32>> {
+    let is_some = read_u8(reader)? != 0;
+    let value = read_i32(reader)?;
+    Ok(is_some.then_some(value))
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> Result<Vec<u8>> {
+    let len = read_u64(reader)? as usize;
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    ensure!(bytes.len() == len, "Truncated contig index");
+    Ok(bytes)
+}
+
+fn read_u32s<R: Read>(reader: &mut R) -> Result<Vec<u32>> {
+    let len = read_u64(reader)? as usize;
+    (0..len).map(|_| read_u32(reader)).collect()
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use bio::alignment::pairwise::MatchParams;
+    use rstest::rstest;
+
+    use crate::align::Scoring;
+    use crate::util::{md5::md5_hex, target_seq::TargetSeq};
+
+    use super::ContigSet;
+
+    fn contig_set() -> ContigSet {
+        let target_seqs = vec![
+            TargetSeq::new("chr1", &b"GCTAAAGACAATTACATAACATACACGTCAGC".to_vec(), false),
+            TargetSeq::new("chr2", &b"TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAA".to_vec(), true),
+        ];
+        let scoring = Scoring::with_jump_score(-5, -1, -10, MatchParams::new(1, -4))
+            .set_xclip(0)
+            .set_homopolymer_gap_open(Some(-3), 4);
+        ContigSet::new(target_seqs, 4, scoring)
+    }
+
+    #[rstest]
+    fn test_write_and_load() {
+        let contig_set = contig_set();
+        let dir = std::env::temp_dir().join(format!("stitch-contig-set-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["index.bin", "index.bin.gz"] {
+            let path = dir.join(name);
+            contig_set.write(&path).unwrap();
+            assert_eq!(ContigSet::load(&path).unwrap(), contig_set);
+        }
+        std::fs::write(dir.join("bad.bin"), b"STITCHCI\x04\x00\x00\x00").unwrap();
+        let error = ContigSet::load(&dir.join("bad.bin")).unwrap_err();
+        assert!(format!("{error:#}").contains("Unsupported contig index version 4"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[rstest]
+    fn test_md5s() {
+        let contig_set = contig_set();
+        assert_eq!(
+            contig_set.md5s(),
+            [
+                md5_hex(b"GCTAAAGACAATTACATAACATACACGTCAGC"),
+                md5_hex(b"TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAA"),
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_target_hashes() {
+        let contig_set = contig_set();
+        for kmer_size in [4, 5] {
+            let hashes = contig_set.target_hashes(kmer_size);
+            for (hash, target_seq) in hashes.iter().zip(contig_set.target_seqs()) {
+                let expected = target_seq.build_target_hash(kmer_size);
+                assert_eq!(hash.name, expected.name);
+                assert_eq!(hash.fwd_hash, expected.fwd_hash);
+                assert_eq!(hash.revcomp_hash, expected.revcomp_hash);
+            }
+        }
+    }
+}
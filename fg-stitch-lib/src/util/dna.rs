@@ -1,5 +1,5 @@
 use lazy_static::lazy_static;
-use std::borrow::Borrow;
+use std::{borrow::Borrow, ops::Index, ops::Range};
 
 /// Valid IUPAC DNA bases
 pub const IUPAC_BASES: [u8; 15] = *b"AGCTYRWSKMDVHBN";
@@ -22,8 +22,29 @@ lazy_static! {
     };
 }
 
+/// The bases that can be stored in two bits, in order of their encoding.  The complement of a base
+/// with code `c` has code `3 - c`.
+const PACKED_BASES: [u8; 4] = *b"ACGT";
+
+/// The value in [`PACKED_CODES`] for bases that cannot be stored in two bits.
+const NO_PACKED_CODE: u8 = u8::MAX;
+
+/// The number of bases stored in a single word of a [`PackedSeq`].
+const BASES_PER_WORD: usize = 32;
+
+lazy_static! {
+    /// An array-based look up of the two-bit encoding of each base
+    static ref PACKED_CODES: [u8; 256] = {
+        let mut codes = [NO_PACKED_CODE; 256];
+        for (code, &base) in PACKED_BASES.iter().enumerate() {
+            codes[base as usize] = code as u8;
+        }
+        codes
+    };
+}
+
 /// Complements a given DNA IUPAC base.
-fn complement(a: u8) -> u8 {
+pub(crate) fn complement(a: u8) -> u8 {
     COMPLEMENT[a as usize]
 }
 
@@ -39,3 +60,372 @@ where
         .map(|a| complement(*a.borrow()))
         .collect()
 }
+
+/// Returns the length of the homopolymer run (ignoring case) containing each base, capped at
+/// `u8::MAX`.
+pub fn homopolymer_run_lengths(seq: &[u8]) -> Vec<u8> {
+    let mut lengths = Vec::with_capacity(seq.len());
+    let mut start = 0;
+    while start < seq.len() {
+        let end = start
+            + seq[start..]
+                .iter()
+                .take_while(|base| base.eq_ignore_ascii_case(&seq[start]))
+                .count();
+        let length = (end - start).min(u8::MAX as usize) as u8;
+        lengths.extend(std::iter::repeat_n(length, end - start));
+        start = end;
+    }
+    lengths
+}
+
+/// Returns, for each position `0..=seq.len()` of the sequence, the length of the run of at least
+/// `min_len` `N`s (ignoring case) ending just before it, or zero if there is none, such that a run
+/// of Ns `[start, end)` has its length at position `end`.
+pub fn n_run_lengths(seq: &[u8], min_len: usize) -> Vec<u32> {
+    let mut lengths = vec![0; seq.len() + 1];
+    let mut run = 0;
+    for (p, base) in seq.iter().enumerate() {
+        if base.eq_ignore_ascii_case(&b'N') {
+            run += 1;
+        } else {
+            run = 0;
+        }
+        let ends_run = !seq
+            .get(p + 1)
+            .is_some_and(|next| next.eq_ignore_ascii_case(&b'N'));
+        if run > 0 && run >= min_len.max(1) && ends_run {
+            lengths[p + 1] = run as u32;
+        }
+    }
+    lengths
+}
+
+/// Returns the length of the homopolymer run (ignoring case) containing the base at the given
+/// index, capped at `u8::MAX` as in [`homopolymer_run_lengths`].
+pub fn homopolymer_run_length_at(seq: &[u8], index: usize) -> u8 {
+    let base = seq[index];
+    let before = seq[..index]
+        .iter()
+        .rev()
+        .take_while(|b| b.eq_ignore_ascii_case(&base))
+        .count();
+    let after = seq[index..]
+        .iter()
+        .take_while(|b| b.eq_ignore_ascii_case(&base))
+        .count();
+    (before + after).min(u8::MAX as usize) as u8
+}
+
+/// A DNA sequence storing the bases `A`, `C`, `G`, and `T` in two bits each.
+///
+/// Any other base (e.g. `N`, other IUPAC codes, or lower case bases) is stored separately, such
+/// that the original sequence is always returned.  Sequences with few such bases therefore use
+/// about a quarter of the memory of the unpacked sequence.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackedSeq {
+    /// The number of bases in the sequence.
+    len: usize,
+    /// The two-bit encoded bases, with the first base in the lowest bits of the first word.
+    words: Vec<u64>,
+    /// One bit per base, set if the base could not be two-bit encoded.
+    unpacked: Vec<u64>,
+    /// The position and value of each base that could not be two-bit encoded, sorted by position.
+    exceptions: Vec<(usize, u8)>,
+}
+
+impl PackedSeq {
+    /// Packs the given sequence.
+    pub fn new(seq: &[u8]) -> Self {
+        let mut words = vec![0u64; seq.len().div_ceil(BASES_PER_WORD)];
+        let mut unpacked = vec![0u64; seq.len().div_ceil(64)];
+        let mut exceptions = Vec::new();
+        for (i, &base) in seq.iter().enumerate() {
+            let code = PACKED_CODES[base as usize];
+            if code == NO_PACKED_CODE {
+                unpacked[i / 64] |= 1 << (i % 64);
+                exceptions.push((i, base));
+            } else {
+                words[i / BASES_PER_WORD] |= u64::from(code) << (2 * (i % BASES_PER_WORD));
+            }
+        }
+        Self {
+            len: seq.len(),
+            words,
+            unpacked,
+            exceptions,
+        }
+    }
+
+    /// The number of bases in the sequence.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// True if the sequence has no bases.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The two-bit code of the base at the given position.  The code is zero for bases that
+    /// could not be two-bit encoded.
+    #[inline]
+    fn code(&self, index: usize) -> usize {
+        ((self.words[index / BASES_PER_WORD] >> (2 * (index % BASES_PER_WORD))) & 3) as usize
+    }
+
+    #[inline]
+    fn is_unpacked(&self, index: usize) -> bool {
+        (self.unpacked[index / 64] >> (index % 64)) & 1 == 1
+    }
+
+    /// Returns a reference to the base at the given position.
+    #[inline]
+    fn base_ref(&self, index: usize) -> &u8 {
+        assert!(
+            index < self.len,
+            "index out of bounds: the len is {} but the index is {index}",
+            self.len
+        );
+        if self.is_unpacked(index) {
+            let i = self
+                .exceptions
+                .binary_search_by_key(&index, |(pos, _)| *pos)
+                .unwrap();
+            &self.exceptions[i].1
+        } else {
+            &PACKED_BASES[self.code(index)]
+        }
+    }
+
+    /// Returns the base at the given position.
+    #[inline]
+    pub fn get(&self, index: usize) -> u8 {
+        *self.base_ref(index)
+    }
+
+    /// Returns an iterator over the bases in the sequence.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = u8> + '_ {
+        (0..self.len).map(|i| self.get(i))
+    }
+
+    /// Returns the (unpacked) bases in the given range.
+    pub fn slice(&self, range: Range<usize>) -> Vec<u8> {
+        assert!(
+            range.start <= range.end && range.end <= self.len,
+            "range {range:?} out of bounds for sequence of length {}",
+            self.len
+        );
+        range.map(|i| self.get(i)).collect()
+    }
+
+    /// Returns the (unpacked) sequence.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.slice(0..self.len)
+    }
+
+    /// Returns the reverse complement of the sequence, operating on the packed bases a word at a
+    /// time.
+    pub fn reverse_complement(&self) -> Self {
+        let num_words = self.words.len();
+        // reverse each word two bits at a time and complement, such that the bases are in
+        // reverse order if the sequence were padded to a multiple of the word size
+        let reversed = self
+            .words
+            .iter()
+            .rev()
+            .map(|word| {
+                let bits = word.reverse_bits();
+                let pairs =
+                    ((bits >> 1) & 0x5555_5555_5555_5555) | ((bits & 0x5555_5555_5555_5555) << 1);
+                !pairs
+            })
+            .collect::<Vec<u64>>();
+        // remove the padding at the start of the reversed sequence
+        let pad = num_words * BASES_PER_WORD - self.len;
+        let mut words = vec![0u64; num_words];
+        for k in 0..num_words {
+            words[k] = if pad == 0 {
+                reversed[k]
+            } else {
+                let next = reversed.get(k + 1).map_or(0, |w| w << (64 - 2 * pad));
+                (reversed[k] >> (2 * pad)) | next
+            };
+        }
+        // clear the unused bits in the last word
+        let used = self.len % BASES_PER_WORD;
+        if used > 0 {
+            words[num_words - 1] &= (1u64 << (2 * used)) - 1;
+        }
+
+        let mut unpacked = vec![0u64; self.unpacked.len()];
+        let exceptions = self
+            .exceptions
+            .iter()
+            .rev()
+            .map(|&(pos, base)| {
+                let pos = self.len - 1 - pos;
+                unpacked[pos / 64] |= 1 << (pos % 64);
+                words[pos / BASES_PER_WORD] &= !(3 << (2 * (pos % BASES_PER_WORD)));
+                (pos, complement(base))
+            })
+            .collect();
+
+        Self {
+            len: self.len,
+            words,
+            unpacked,
+            exceptions,
+        }
+    }
+
+    /// Returns a view of the reverse complement of the sequence, which computes each base on
+    /// access rather than storing the reverse complement.
+    pub fn reverse_complement_view(&self) -> ReverseComplementView<'_> {
+        ReverseComplementView { seq: self }
+    }
+
+    /// Returns a reference to the complement of the base at the given position.
+    #[inline]
+    fn complement_ref(&self, index: usize) -> &u8 {
+        if self.is_unpacked(index) {
+            &COMPLEMENT[self.get(index) as usize]
+        } else {
+            &PACKED_BASES[3 - self.code(index)]
+        }
+    }
+}
+
+/// A view of the reverse complement of a [`PackedSeq`].  See
+/// [`PackedSeq::reverse_complement_view`].
+#[derive(Debug, Copy, Clone)]
+pub struct ReverseComplementView<'a> {
+    seq: &'a PackedSeq,
+}
+
+impl ReverseComplementView<'_> {
+    /// The number of bases in the sequence.
+    pub fn len(&self) -> usize {
+        self.seq.len()
+    }
+
+    /// True if the sequence has no bases.
+    pub fn is_empty(&self) -> bool {
+        self.seq.is_empty()
+    }
+}
+
+impl Index<usize> for ReverseComplementView<'_> {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        assert!(
+            index < self.seq.len(),
+            "index out of bounds: the len is {} but the index is {index}",
+            self.seq.len()
+        );
+        self.seq.complement_ref(self.seq.len() - 1 - index)
+    }
+}
+
+impl Index<usize> for PackedSeq {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.base_ref(index)
+    }
+}
+
+impl From<&[u8]> for PackedSeq {
+    fn from(seq: &[u8]) -> Self {
+        Self::new(seq)
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::{
+        homopolymer_run_length_at, homopolymer_run_lengths, n_run_lengths, reverse_complement,
+        PackedSeq,
+    };
+
+    #[rstest]
+    #[case("", &[])]
+    #[case("A", &[1])]
+    #[case("ACGT", &[1, 1, 1, 1])]
+    #[case("AAcGGGt", &[2, 2, 1, 3, 3, 3, 1])]
+    #[case("aAAN", &[3, 3, 3, 1])]
+    fn test_homopolymer_run_lengths(#[case] seq: &str, #[case] expected: &[u8]) {
+        let lengths = homopolymer_run_lengths(seq.as_bytes());
+        assert_eq!(lengths, expected);
+        for (index, length) in lengths.iter().enumerate() {
+            assert_eq!(homopolymer_run_length_at(seq.as_bytes(), index), *length);
+        }
+    }
+
+    #[rstest]
+    #[case("ACGT", 1, &[0, 0, 0, 0, 0])]
+    #[case("ANNc", 1, &[0, 0, 0, 2, 0])]
+    #[case("ANNc", 3, &[0, 0, 0, 0, 0])]
+    #[case("nNNAN", 2, &[0, 0, 0, 3, 0, 0])]
+    #[case("NANN", 0, &[0, 1, 0, 0, 2])]
+    fn test_n_run_lengths(#[case] seq: &str, #[case] min_len: usize, #[case] expected: &[u32]) {
+        assert_eq!(n_run_lengths(seq.as_bytes(), min_len), expected);
+    }
+
+    #[rstest]
+    fn test_homopolymer_run_lengths_capped() {
+        let seq = "A".repeat(300);
+        assert!(homopolymer_run_lengths(seq.as_bytes())
+            .iter()
+            .all(|length| *length == u8::MAX));
+        assert_eq!(homopolymer_run_length_at(seq.as_bytes(), 150), u8::MAX);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("A")]
+    #[case("ACGTTGCA")]
+    #[case("ACGTNacgtRYN")]
+    #[case("NNNN")]
+    #[case(&"ACGGT".repeat(6))] // 30 bases
+    #[case(&"ACGT".repeat(8))] // 32 bases
+    #[case(&"ACGTA".repeat(13))] // 65 bases
+    #[case(&format!("{}N{}", "ACGTTC".repeat(11), "GATTACA".repeat(10)))]
+    fn test_packed_seq(#[case] seq: &str) {
+        let bases = seq.as_bytes();
+        let packed = PackedSeq::new(bases);
+        assert_eq!(packed.len(), bases.len());
+        assert_eq!(packed.is_empty(), bases.is_empty());
+        assert_eq!(packed.to_vec(), bases);
+        for (i, base) in bases.iter().enumerate() {
+            assert_eq!(packed[i], *base);
+            assert_eq!(packed.get(i), *base);
+        }
+        assert_eq!(packed.iter().collect::<Vec<u8>>(), bases);
+        if bases.len() > 2 {
+            assert_eq!(packed.slice(1..bases.len() - 1), bases[1..bases.len() - 1]);
+        }
+
+        let revcomp = packed.reverse_complement();
+        assert_eq!(revcomp.to_vec(), reverse_complement(bases));
+        assert_eq!(revcomp, PackedSeq::new(&reverse_complement(bases)));
+        assert_eq!(revcomp.reverse_complement(), packed);
+
+        let view = packed.reverse_complement_view();
+        assert_eq!(view.len(), bases.len());
+        for (i, base) in reverse_complement(bases).iter().enumerate() {
+            assert_eq!(view[i], *base);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn test_packed_seq_out_of_bounds() {
+        let packed = PackedSeq::new(b"ACGT");
+        let _ = packed[4];
+    }
+}
@@ -0,0 +1,491 @@
+//! Reading annotated references from GenBank or EMBL flat files, namely the sequence of each
+//! record along with its feature table, so that jumps and variants can be described in terms of
+//! the named features of the reference (e.g. a junction inside the CMV promoter).
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, Read},
+    path::Path,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use fgoxide::io::Io;
+
+use crate::util::target_seq::TargetSeq;
+
+/// The buffer size for reading flat files
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// The qualifiers naming a feature, in order of precedence.
+const NAME_QUALIFIERS: [&str; 6] = [
+    "label",
+    "gene",
+    "product",
+    "standard_name",
+    "locus_tag",
+    "note",
+];
+
+/// A feature from the feature table of a record, spanning `[start, end)` (0-based) on the forward
+/// strand of the record.  A feature with multiple parts (e.g. `join(...)`) spans from the start of
+/// its first part to the end of its last part.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SeqFeature {
+    /// The feature key, e.g. `promoter` or `CDS`.
+    pub kind: String,
+    /// The name of the feature, from its first naming qualifier (e.g. `/label` or `/gene`),
+    /// otherwise its key.
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    /// False if the feature is on the reverse strand (`complement(...)`).
+    pub is_forward: bool,
+}
+
+impl SeqFeature {
+    /// True if the feature contains the given position.
+    pub fn contains(&self, pos: usize) -> bool {
+        self.start <= pos && pos < self.end
+    }
+}
+
+/// A record of a GenBank or EMBL file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AnnotatedRecord {
+    pub name: String,
+    /// The sequence, upper case.
+    pub seq: Vec<u8>,
+    /// True if the molecule is declared circular.
+    pub circular: bool,
+    pub features: Vec<SeqFeature>,
+}
+
+/// The features of the references, by reference name.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct FeatureAnnotations {
+    features: HashMap<String, Vec<SeqFeature>>,
+}
+
+impl FeatureAnnotations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the feature of the given reference.
+    pub fn add(&mut self, contig: &str, feature: SeqFeature) {
+        self.features
+            .entry(contig.to_string())
+            .or_default()
+            .push(feature);
+    }
+
+    /// The features of the given reference, in the order they were added.
+    pub fn features(&self, contig: &str) -> &[SeqFeature] {
+        self.features.get(contig).map_or(&[], Vec::as_slice)
+    }
+
+    /// The features of the given reference containing the given 0-based position.  The `source`
+    /// features, which span whole records, are ignored.
+    pub fn features_at<'a>(
+        &'a self,
+        contig: &str,
+        pos: usize,
+    ) -> impl Iterator<Item = &'a SeqFeature> + 'a {
+        self.features(contig)
+            .iter()
+            .filter(move |feature| feature.kind != "source" && feature.contains(pos))
+    }
+
+    /// Describes the given 0-based position of the reference by the names of the features
+    /// containing it, comma-separated, or `None` if none.
+    pub fn describe(&self, contig: &str, pos: usize) -> Option<String> {
+        let names = self
+            .features_at(contig, pos)
+            .map(|feature| feature.name.as_str())
+            .collect::<Vec<_>>();
+        (!names.is_empty()).then(|| names.join(","))
+    }
+
+    /// The number of references with features.
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+}
+
+/// True if the contents are a GenBank or EMBL flat file, rather than (for example) a FASTA, from
+/// the first non-empty line.
+pub fn is_flat_file(contents: &str) -> bool {
+    contents
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .is_some_and(|line| line.starts_with("LOCUS") || line.starts_with("ID   "))
+}
+
+/// Parses the location of a feature into its 0-based span and strand, or `None` for a location
+/// referring to another record (e.g. `J00194.1:100..202`).  Partial ends (`<` and `>`) and
+/// between-base sites (`^`) are accepted.
+fn parse_location(location: &str) -> Result<Option<(usize, usize, bool)>> {
+    if location.contains(':') {
+        return Ok(None);
+    }
+    let positions = location
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|field| !field.is_empty())
+        .map(|field| field.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid feature location: {location}"))?;
+    let (Some(start), Some(end)) = (positions.iter().min(), positions.iter().max()) else {
+        bail!("Invalid feature location: {location}");
+    };
+    ensure!(*start > 0, "Invalid feature location: {location}");
+    Ok(Some((start - 1, *end, !location.contains("complement"))))
+}
+
+/// Builds the feature from its key, location, and qualifiers (without the leading `/`).
+fn build_feature(
+    kind: &str,
+    location: &str,
+    qualifiers: &[String],
+    seq_len: Option<usize>,
+) -> Result<Option<SeqFeature>> {
+    let Some((start, end, is_forward)) = parse_location(location)? else {
+        return Ok(None);
+    };
+    if let Some(seq_len) = seq_len {
+        ensure!(
+            end <= seq_len,
+            "Feature {kind} at {location} past the end of the record with length {seq_len}"
+        );
+    }
+    let qualifier = |key: &str| {
+        qualifiers.iter().find_map(|qualifier| {
+            let (name, value) = qualifier.split_once('=')?;
+            (name == key).then(|| value.trim_matches('"').to_string())
+        })
+    };
+    let name = NAME_QUALIFIERS
+        .iter()
+        .find_map(|key| qualifier(key))
+        .unwrap_or_else(|| kind.to_string());
+    Ok(Some(SeqFeature {
+        kind: kind.to_string(),
+        name,
+        start,
+        end,
+        is_forward,
+    }))
+}
+
+/// Parses the records of a GenBank or EMBL flat file.
+///
+/// Only the name (from the `LOCUS` or `ID` line), topology, feature table, and sequence of each
+/// record are read.  Features referring to other records are ignored.
+pub fn parse(contents: &str) -> Result<Vec<AnnotatedRecord>> {
+    /// The section of the record being read.
+    #[derive(PartialEq)]
+    enum Section {
+        Header,
+        Features,
+        Sequence,
+    }
+
+    let mut records = Vec::new();
+    let mut record: Option<AnnotatedRecord> = None;
+    let mut section = Section::Header;
+    // The declared length of the record, and the feature being read: its key, location, and
+    // qualifiers
+    let mut seq_len: Option<usize> = None;
+    let mut feature: Option<(String, String, Vec<String>)> = None;
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with("XX") {
+            continue;
+        }
+        // Normalize the EMBL feature table lines to the GenBank layout
+        let line = match line.strip_prefix("FT") {
+            Some(rest) => format!("  {rest}"),
+            None => line.to_string(),
+        };
+        let is_embl_id = line.starts_with("ID   ");
+        if line.starts_with("LOCUS") || is_embl_id {
+            ensure!(
+                record.is_none(),
+                "Missing record terminator (//) before line {}",
+                index + 1
+            );
+            let fields = line
+                .split(|c: char| c.is_whitespace() || c == ';')
+                .filter(|field| !field.is_empty())
+                .collect::<Vec<_>>();
+            let name = fields
+                .get(1)
+                .with_context(|| format!("Missing record name on line {}", index + 1))?;
+            seq_len = fields
+                .iter()
+                .position(|field| field.trim_end_matches('.').eq_ignore_ascii_case("bp"))
+                .and_then(|i| fields[i - 1].parse::<usize>().ok());
+            record = Some(AnnotatedRecord {
+                name: name.to_string(),
+                seq: Vec::new(),
+                circular: fields.contains(&"circular"),
+                features: Vec::new(),
+            });
+            section = Section::Header;
+            continue;
+        }
+        let Some(current) = record.as_mut() else {
+            bail!("Expected a LOCUS or ID line on line {}", index + 1);
+        };
+
+        if line.starts_with("//") {
+            if let Some((kind, location, qualifiers)) = feature.take() {
+                if let Some(f) = build_feature(&kind, &location, &qualifiers, seq_len)? {
+                    current.features.push(f);
+                }
+            }
+            if let Some(seq_len) = seq_len {
+                ensure!(
+                    current.seq.len() == seq_len,
+                    "Record {} has {} bases, expected {seq_len}",
+                    current.name,
+                    current.seq.len()
+                );
+            }
+            records.push(record.take().unwrap());
+            continue;
+        }
+
+        if line.starts_with("FEATURES") || line.starts_with("FH") {
+            section = Section::Features;
+            continue;
+        }
+        if line.starts_with("ORIGIN") || line.starts_with("SQ") {
+            section = Section::Sequence;
+            continue;
+        }
+        match section {
+            Section::Header => (),
+            Section::Sequence => current.seq.extend(
+                line.bytes()
+                    .filter(u8::is_ascii_alphabetic)
+                    .map(|b| b.to_ascii_uppercase()),
+            ),
+            // A new top-level header ends the feature table (e.g. CONTIG or BASE COUNT)
+            Section::Features if !line.starts_with(' ') => section = Section::Header,
+            Section::Features => {
+                let key = line.get(5..21).unwrap_or_default().trim();
+                let value = line.get(21..).unwrap_or_default().trim();
+                if !key.is_empty() {
+                    if let Some((kind, location, qualifiers)) = feature.take() {
+                        if let Some(f) = build_feature(&kind, &location, &qualifiers, seq_len)? {
+                            current.features.push(f);
+                        }
+                    }
+                    feature = Some((key.to_string(), value.to_string(), Vec::new()));
+                } else if let Some((_, location, qualifiers)) = feature.as_mut() {
+                    match (value.strip_prefix('/'), qualifiers.last_mut()) {
+                        (Some(qualifier), _) => qualifiers.push(qualifier.to_string()),
+                        (None, Some(qualifier)) => {
+                            qualifier.push(' ');
+                            qualifier.push_str(value);
+                        }
+                        (None, None) => location.push_str(value),
+                    }
+                }
+            }
+        }
+    }
+    ensure!(
+        record.is_none(),
+        "Missing record terminator (//) at the end"
+    );
+    ensure!(!records.is_empty(), "Found no records");
+    Ok(records)
+}
+
+/// Reads the records of a GenBank or EMBL flat file, which may be GZIP compressed.
+pub fn read_records(path: &Path) -> Result<Vec<AnnotatedRecord>> {
+    let fg_io: Io = Io::new(5, BUFFER_SIZE);
+    let mut contents = String::new();
+    fg_io
+        .new_reader(&path)?
+        .read_to_string(&mut contents)
+        .with_context(|| format!("Could not read: {}", path.display()))?;
+    parse(&contents).with_context(|| format!("Invalid GenBank or EMBL file: {}", path.display()))
+}
+
+/// Returns true if the file (which may be GZIP compressed) is a GenBank or EMBL flat file, from
+/// its first non-empty line.
+pub fn is_flat_file_path(path: &Path) -> Result<bool> {
+    let fg_io: Io = Io::new(5, BUFFER_SIZE);
+    let reader = fg_io.new_reader(&path)?;
+    for line in reader.lines() {
+        let line = line.with_context(|| format!("Could not read: {}", path.display()))?;
+        if !line.trim().is_empty() {
+            return Ok(is_flat_file(&line));
+        }
+    }
+    Ok(false)
+}
+
+/// Converts the records to references, where a reference is circular if its record is declared
+/// circular or `circular` is true, and their features to annotations.  If `names` is not empty,
+/// only the records with the given names are returned, in the given order.
+pub fn to_target_seqs(
+    records: &[AnnotatedRecord],
+    names: &[String],
+    circular: bool,
+) -> Result<(Vec<TargetSeq>, FeatureAnnotations)> {
+    let records = if names.is_empty() {
+        records.iter().collect::<Vec<_>>()
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                records
+                    .iter()
+                    .find(|record| &record.name == name)
+                    .with_context(|| format!("Record not found: {name}"))
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+    let mut annotations = FeatureAnnotations::new();
+    for record in &records {
+        for feature in &record.features {
+            annotations.add(&record.name, feature.clone());
+        }
+    }
+    let target_seqs = records
+        .iter()
+        .map(|record| TargetSeq::new(&record.name, &record.seq, record.circular || circular))
+        .collect();
+    Ok((target_seqs, annotations))
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::{is_flat_file, parse, to_target_seqs, SeqFeature};
+
+    const GENBANK: &str = "\
+LOCUS       pTEST                     30 bp    DNA     circular SYN 01-JAN-2024
+DEFINITION  A test plasmid.
+FEATURES             Location/Qualifiers
+     source          1..30
+                     /organism=\"synthetic DNA construct\"
+     promoter        3..12
+                     /label=\"CMV promoter\"
+                     /note=\"a long note
+                     continued\"
+     CDS             complement(join(15..20,
+                     25..28))
+                     /gene=\"neo\"
+     misc_feature    J00194.1:100..202
+ORIGIN
+        1 acgtacgtac gtacgtacgt acgtacgtac
+//
+";
+
+    const EMBL: &str = "\
+ID   pEMBL; SV 1; linear; DNA; STD; SYN; 12 BP.
+XX
+FH   Key             Location/Qualifiers
+FT   rep_origin      <2..>5
+FT   terminator      8
+SQ   Sequence 12 BP;
+     ttttaaaacc cc                                                        12
+//
+";
+
+    #[rstest]
+    fn test_parse_genbank() {
+        assert!(is_flat_file(GENBANK));
+        let records = parse(GENBANK).unwrap();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.name, "pTEST");
+        assert_eq!(record.seq, b"ACGTACGTACGTACGTACGTACGTACGTAC");
+        assert!(record.circular);
+        assert_eq!(record.features.len(), 3);
+        assert_eq!(
+            record.features[1],
+            SeqFeature {
+                kind: "promoter".to_string(),
+                name: "CMV promoter".to_string(),
+                start: 2,
+                end: 12,
+                is_forward: true,
+            }
+        );
+        assert_eq!(
+            record.features[2],
+            SeqFeature {
+                kind: "CDS".to_string(),
+                name: "neo".to_string(),
+                start: 14,
+                end: 28,
+                is_forward: false,
+            }
+        );
+    }
+
+    #[rstest]
+    fn test_parse_embl() {
+        assert!(is_flat_file(EMBL));
+        assert!(!is_flat_file(">chr1\nACGT\n"));
+        let records = parse(EMBL).unwrap();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.name, "pEMBL");
+        assert_eq!(record.seq, b"TTTTAAAACCCC");
+        assert!(!record.circular);
+        let features = record
+            .features
+            .iter()
+            .map(|f| (f.name.as_str(), f.start, f.end))
+            .collect::<Vec<_>>();
+        assert_eq!(features, vec![("rep_origin", 1, 5), ("terminator", 7, 8)]);
+    }
+
+    #[rstest]
+    fn test_parse_invalid() {
+        // the sequence is shorter than declared
+        assert!(parse(&GENBANK.replace("gtacgtac\n//", "gtac\n//")).is_err());
+        // a feature past the end of the sequence
+        assert!(parse(&GENBANK.replace("3..12", "3..31")).is_err());
+        // no record terminator
+        assert!(parse(&GENBANK.replace("//\n", "")).is_err());
+        assert!(parse(">chr1\nACGT\n").is_err());
+    }
+
+    #[rstest]
+    fn test_to_target_seqs() {
+        let records = [parse(GENBANK).unwrap(), parse(EMBL).unwrap()].concat();
+        let (target_seqs, annotations) = to_target_seqs(&records, &[], false).unwrap();
+        assert_eq!(target_seqs.len(), 2);
+        assert!(target_seqs[0].circular);
+        assert!(!target_seqs[1].circular);
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(
+            annotations.describe("pTEST", 5),
+            Some("CMV promoter".to_string())
+        );
+        // the source feature is ignored, and within a join the whole span is used
+        assert_eq!(annotations.describe("pTEST", 0), None);
+        assert_eq!(annotations.describe("pTEST", 22), Some("neo".to_string()));
+
+        let names = vec!["pEMBL".to_string()];
+        let (target_seqs, annotations) = to_target_seqs(&records, &names, true).unwrap();
+        assert_eq!(target_seqs.len(), 1);
+        assert!(target_seqs[0].circular);
+        assert!(annotations.features("pTEST").is_empty());
+        assert!(to_target_seqs(&records, &["pNONE".to_string()], false).is_err());
+    }
+}
@@ -12,7 +12,7 @@
 use std::path::Path;
 
 /// Returns true if the path ends with a recognized GZIP file extension
-fn is_path_with_extension<P: AsRef<Path>>(p: &P, extensions: [&str; 2]) -> bool {
+fn is_path_with_extension<P: AsRef<Path>>(p: &P, extensions: &[&str]) -> bool {
     if let Some(ext) = p.as_ref().extension() {
         match ext.to_str() {
             Some(x) => extensions.contains(&x),
@@ -28,7 +28,7 @@ const GZIP_EXTENSIONS: [&str; 2] = ["gz", "bgz"];
 
 /// Returns true if the path ends with a recognized GZIP file extension
 pub fn is_gzip_path<P: AsRef<Path>>(p: &P) -> bool {
-    is_path_with_extension(p, GZIP_EXTENSIONS)
+    is_path_with_extension(p, &GZIP_EXTENSIONS)
 }
 
 /// The set of file extensions to treat as FASTQ
@@ -36,5 +36,13 @@ const FASTQ_EXTENSIONS: [&str; 2] = ["fastq", "fq"];
 
 /// Returns true if the path ends with a recognized FASTQ file extension
 pub fn is_fastq_path<P: AsRef<Path>>(p: &P) -> bool {
-    is_path_with_extension(p, FASTQ_EXTENSIONS)
+    is_path_with_extension(p, &FASTQ_EXTENSIONS)
+}
+
+/// The set of file extensions to treat as BAM
+const BAM_EXTENSIONS: [&str; 1] = ["bam"];
+
+/// Returns true if the path ends with a recognized BAM file extension
+pub fn is_bam_path<P: AsRef<Path>>(p: &P) -> bool {
+    is_path_with_extension(p, &BAM_EXTENSIONS)
 }
@@ -0,0 +1,191 @@
+//! The MD5 message digest (RFC 1321), for the checksums of the references recorded in the outputs
+//! (e.g. as the `M5` tag of the SAM `@SQ` header lines), so that downstream tools may check that
+//! they use the same references.
+
+/// The number of bytes in each block of the message.
+const BLOCK_LEN: usize = 64;
+
+/// The left rotation of each of the 64 operations.
+const SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15,
+    21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+/// The constant added by each of the 64 operations, the integer part of `2^32 * |sin(i + 1)|`.
+const SINES: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+/// The incremental computation of the MD5 digest of a message given in parts.
+#[derive(Debug, Clone)]
+pub struct Md5 {
+    state: [u32; 4],
+    /// The bytes of the message after its last full block.
+    buffer: [u8; BLOCK_LEN],
+    buffer_len: usize,
+    /// The number of bytes of the message.
+    len: u64,
+}
+
+impl Default for Md5 {
+    fn default() -> Self {
+        Self {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            buffer: [0; BLOCK_LEN],
+            buffer_len: 0,
+            len: 0,
+        }
+    }
+}
+
+impl Md5 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the bytes to the message.
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        self.len = self.len.wrapping_add(bytes.len() as u64);
+        if self.buffer_len > 0 {
+            let len = (BLOCK_LEN - self.buffer_len).min(bytes.len());
+            self.buffer[self.buffer_len..self.buffer_len + len].copy_from_slice(&bytes[..len]);
+            self.buffer_len += len;
+            bytes = &bytes[len..];
+            if self.buffer_len < BLOCK_LEN {
+                return;
+            }
+            let block = self.buffer;
+            self.process(&block);
+            self.buffer_len = 0;
+        }
+        let mut blocks = bytes.chunks_exact(BLOCK_LEN);
+        for block in &mut blocks {
+            self.process(block.try_into().unwrap());
+        }
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffer_len = rest.len();
+    }
+
+    /// Returns the digest of the message.
+    pub fn finalize(mut self) -> [u8; 16] {
+        // Pad the message with a one bit, then zeros up to the length of the message in bits
+        let bit_len = self.len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.buffer_len != BLOCK_LEN - 8 {
+            self.update(&[0]);
+        }
+        self.update(&bit_len.to_le_bytes());
+        let mut digest = [0; 16];
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+
+    /// Returns the digest of the message as 32 lower case hexadecimal digits.
+    pub fn finalize_hex(self) -> String {
+        self.finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    fn process(&mut self, block: &[u8; BLOCK_LEN]) {
+        let mut words = [0u32; 16];
+        for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+        let [mut a, mut b, mut c, mut d] = self.state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f
+                .wrapping_add(a)
+                .wrapping_add(SINES[i])
+                .wrapping_add(words[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[i]));
+        }
+        for (state, word) in self.state.iter_mut().zip([a, b, c, d]) {
+            *state = state.wrapping_add(word);
+        }
+    }
+}
+
+/// Returns the MD5 digest of the bytes as 32 lower case hexadecimal digits.
+pub fn md5_hex(bytes: &[u8]) -> String {
+    let mut md5 = Md5::new();
+    md5.update(bytes);
+    md5.finalize_hex()
+}
+
+/// Returns the MD5 checksum of a reference sequence as for the `M5` tag of the SAM `@SQ` header
+/// lines, namely of the sequence in upper case without any characters outside `!` to `~` (e.g.
+/// whitespace).
+pub fn sequence_md5(seq: &[u8]) -> String {
+    let mut md5 = Md5::new();
+    let mut bases = Vec::with_capacity(seq.len().min(1 << 16));
+    for chunk in seq.chunks(1 << 16) {
+        bases.clear();
+        bases.extend(
+            chunk
+                .iter()
+                .filter(|base| (b'!'..=b'~').contains(*base))
+                .map(u8::to_ascii_uppercase),
+        );
+        md5.update(&bases);
+    }
+    md5.finalize_hex()
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::{md5_hex, sequence_md5, Md5};
+
+    #[rstest]
+    #[case(b"", "d41d8cd98f00b204e9800998ecf8427e")]
+    #[case(b"a", "0cc175b9c0f1b6a831c399e269772661")]
+    #[case(b"abc", "900150983cd24fb0d6963f7d28e17f72")]
+    #[case(b"message digest", "f96b697d7cb7938d525a2f31aaf161d0")]
+    #[case(
+        b"The quick brown fox jumps over the lazy dog",
+        "9e107d9d372bb6826bd81d3542a419d6"
+    )]
+    #[case(
+        b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+        "57edf4a22be3c955ac49da2e2107b67a"
+    )]
+    fn test_md5(#[case] bytes: &[u8], #[case] expected: &str) {
+        assert_eq!(md5_hex(bytes), expected);
+        // the same when given in parts
+        let mut md5 = Md5::new();
+        for part in bytes.chunks(7) {
+            md5.update(part);
+        }
+        assert_eq!(md5.finalize_hex(), expected);
+    }
+
+    #[rstest]
+    fn test_sequence_md5() {
+        assert_eq!(sequence_md5(b"ac gT\nN"), md5_hex(b"ACGTN"));
+        assert_eq!(sequence_md5(b""), md5_hex(b""));
+    }
+}
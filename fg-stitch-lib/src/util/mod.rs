@@ -1,5 +1,10 @@
-pub(crate) mod dna;
+pub mod alphabet;
+pub mod contig_set;
+pub mod dna;
+pub mod genbank;
 pub(crate) mod index_map;
 pub(crate) mod io;
+pub mod md5;
+pub mod run_info;
 pub mod target_seq;
 pub mod version;
@@ -0,0 +1,174 @@
+//! The manifest of a run of the aligner (the version, command line, parameters, and the checksums
+//! of the references), recorded in its outputs so that the results may be traced to, and
+//! reproduced from, the run that produced them.
+
+#[cfg(feature = "serde")]
+use anyhow::Result;
+
+use super::{md5::sequence_md5, target_seq::TargetSeq, version::built_info};
+use crate::align::{config::AlignerConfig, read_group::ReadGroup};
+
+/// A reference aligned to in a run.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ReferenceInfo {
+    pub name: String,
+    pub length: usize,
+    pub circular: bool,
+    /// The MD5 checksum of the reference (see [`sequence_md5`]).
+    pub md5: String,
+}
+
+impl ReferenceInfo {
+    pub fn new(target_seq: &TargetSeq) -> Self {
+        Self::with_md5(target_seq, sequence_md5(&target_seq.fwd))
+    }
+
+    /// Creates the reference with its already computed MD5 checksum (e.g. from
+    /// [`crate::util::contig_set::ContigSet::md5s`]).
+    pub fn with_md5(target_seq: &TargetSeq, md5: String) -> Self {
+        Self {
+            name: target_seq.name.clone(),
+            length: target_seq.len(),
+            circular: target_seq.circular,
+            md5,
+        }
+    }
+}
+
+/// The manifest of a run of the aligner.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct RunInfo {
+    /// The version of the aligner (see [`built_info::VERSION`]).
+    pub version: String,
+    /// The command line of the run.
+    pub command_line: String,
+    /// The full set of alignment parameters of the run.
+    pub parameters: AlignerConfig,
+    /// The references aligned to, in order.
+    pub references: Vec<ReferenceInfo>,
+    /// The read groups of the reads aligned, if any.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub read_groups: Vec<ReadGroup>,
+}
+
+impl RunInfo {
+    /// Creates the manifest of a run with the given command line and parameters, aligning to the
+    /// given references.
+    pub fn new(command_line: &str, parameters: AlignerConfig, target_seqs: &[TargetSeq]) -> Self {
+        Self {
+            version: built_info::VERSION.clone(),
+            command_line: command_line.to_string(),
+            parameters,
+            references: target_seqs.iter().map(ReferenceInfo::new).collect(),
+            read_groups: Vec::new(),
+        }
+    }
+
+    /// Creates the manifest as with [`Self::new`], with the already computed MD5 checksum of each
+    /// reference.
+    pub fn with_md5s(
+        command_line: &str,
+        parameters: AlignerConfig,
+        target_seqs: &[TargetSeq],
+        md5s: &[String],
+    ) -> Self {
+        Self {
+            references: target_seqs
+                .iter()
+                .zip(md5s)
+                .map(|(target_seq, md5)| ReferenceInfo::with_md5(target_seq, md5.clone()))
+                .collect(),
+            ..Self::new(command_line, parameters, &[])
+        }
+    }
+
+    /// Returns the manifest as lines for the `@CO` lines of a SAM header, each prefixed with
+    /// `stitch <field>: `.  The parameters are given as one line of TOML per `stitch config: `
+    /// line, so that the prefix may be stripped to recreate the configuration file.
+    #[cfg(feature = "serde")]
+    pub fn header_comments(&self) -> Result<Vec<String>> {
+        let mut comments = vec![
+            format!("stitch version: {}", self.version),
+            format!("stitch command: {}", self.command_line),
+        ];
+        for line in self.parameters.to_toml()?.lines().filter(|l| !l.is_empty()) {
+            comments.push(format!("stitch config: {line}"));
+        }
+        for reference in &self.references {
+            comments.push(format!(
+                "stitch reference: {} length={} circular={} md5={}",
+                reference.name, reference.length, reference.circular, reference.md5
+            ));
+        }
+        Ok(comments)
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use rstest::rstest;
+
+    use super::RunInfo;
+    use crate::{
+        align::config::AlignerConfig,
+        util::{md5::md5_hex, target_seq::TargetSeq},
+    };
+
+    #[rstest]
+    fn test_run_info() {
+        let target_seqs = vec![
+            TargetSeq::new("chr1", &b"acgt".to_vec(), false),
+            TargetSeq::new("plasmid", &b"GGCC".to_vec(), true),
+        ];
+        let run_info = RunInfo::new(
+            "stitch align -f r.fq -r t.fa",
+            AlignerConfig::default(),
+            &target_seqs,
+        );
+        assert_eq!(run_info.command_line, "stitch align -f r.fq -r t.fa");
+        assert_eq!(run_info.references.len(), 2);
+        assert_eq!(run_info.references[0].md5, md5_hex(b"ACGT"));
+        assert_eq!(run_info.references[1].length, 4);
+        assert!(run_info.references[1].circular);
+
+        // the same with the checksums given
+        let md5s = [md5_hex(b"ACGT"), md5_hex(b"GGCC")];
+        let with_md5s = RunInfo::with_md5s(
+            "stitch align -f r.fq -r t.fa",
+            AlignerConfig::default(),
+            &target_seqs,
+            &md5s,
+        );
+        assert_eq!(with_md5s, run_info);
+    }
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    fn test_header_comments() {
+        let target_seqs = vec![TargetSeq::new("chr1", &b"ACGT".to_vec(), false)];
+        let run_info = RunInfo::new("stitch align", AlignerConfig::default(), &target_seqs);
+        let comments = run_info.header_comments().unwrap();
+        assert_eq!(comments[1], "stitch command: stitch align");
+        assert!(comments[0].starts_with("stitch version: "));
+
+        // the configuration may be recreated from its comments
+        let toml = comments
+            .iter()
+            .filter_map(|c| c.strip_prefix("stitch config: "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            AlignerConfig::from_toml(&toml).unwrap(),
+            AlignerConfig::default()
+        );
+
+        let expected = format!(
+            "stitch reference: chr1 length=4 circular=false md5={}",
+            md5_hex(b"ACGT")
+        );
+        assert_eq!(comments.last().unwrap(), &expected);
+    }
+}
@@ -1,11 +1,18 @@
-use crate::util::dna::reverse_complement;
+use crate::util::{alphabet::Alphabet, dna::reverse_complement};
 use anyhow::{ensure, Context, Result};
 use bio::alignment::sparse::{hash_kmers, HashMapFx};
 use fgoxide::io::Io;
 use itertools::{self, Itertools};
+use noodles::{
+    core::Region,
+    fasta::{indexed_reader::Builder as IndexedFastaReaderBuilder, IndexedReader},
+};
 use seq_io::fasta::{Reader as FastaReader, Record as FastaRecord};
-use std::path::PathBuf;
-use std::{collections::HashMap, io::BufRead};
+use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    io::{BufRead, Seek},
+};
 
 /// The buffer size for reading FASTAs
 const BUFFER_SIZE: usize = 1024 * 1024;
@@ -38,6 +45,17 @@ impl TargetSeq {
         }
     }
 
+    /// Creates a new `TargetSeq` for the contig with the given name and sequence of the given
+    /// alphabet.  The reverse strand is empty if the alphabet has no strands (e.g. protein).
+    pub fn with_alphabet<A: Alphabet>(name: &str, seq: &[u8], circular: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            fwd: seq.to_vec(),
+            revcomp: A::reverse_strand(seq).unwrap_or_default(),
+            circular,
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.fwd.len()
     }
@@ -47,7 +65,7 @@ impl TargetSeq {
     }
 
     /// Creates a new `TargetHash` with the given k-mer size.
-    pub fn build_target_hash(&self, k: usize) -> TargetHash {
+    pub fn build_target_hash(&self, k: usize) -> TargetHash<'_> {
         TargetHash {
             name: self.name.clone(),
             fwd_hash: hash_kmers(&self.fwd, k),
@@ -66,11 +84,13 @@ fn header_to_name(header: &[u8]) -> Result<String> {
         .context("empty read name")
 }
 
-pub fn from_fasta(file: &PathBuf, circular: bool) -> Result<Vec<TargetSeq>> {
+/// Reads which contigs have a circular topology from the sequence dictionary of the FASTA, if the
+/// sequence dictionary exists.
+fn read_circular_contigs(file: &Path) -> Result<HashMap<String, bool>> {
     let fg_io: Io = Io::new(5, BUFFER_SIZE);
 
     // Check if the .dict file exist, and if so, check if which contigs have a circular topology.
-    let dict = file.as_path().with_extension(".dict");
+    let dict = file.with_extension(".dict");
     let circular_contigs = if dict.exists() {
         let mut circular_contigs = HashMap::new();
         for line in fg_io.read_lines(&dict)? {
@@ -81,12 +101,12 @@ pub fn from_fasta(file: &PathBuf, circular: bool) -> Result<Vec<TargetSeq>> {
             let contig_is_circular = fields
                 .iter()
                 .filter(|field| field.starts_with("TP"))
-                .filter_map(|field| field.split_terminator(':').last())
+                .filter_map(|field| field.split_terminator(':').next_back())
                 .any(|field| field == "circular");
             let contig_name = fields
                 .iter()
                 .filter(|field| field.starts_with("SN"))
-                .find_map(|field| field.split_terminator(':').last())
+                .find_map(|field| field.split_terminator(':').next_back())
                 .unwrap();
             circular_contigs.insert(contig_name.to_owned(), contig_is_circular);
         }
@@ -94,6 +114,13 @@ pub fn from_fasta(file: &PathBuf, circular: bool) -> Result<Vec<TargetSeq>> {
     } else {
         HashMap::new()
     };
+    Ok(circular_contigs)
+}
+
+/// Reads all the contigs from the FASTA, which may be GZIP or BGZF compressed.
+pub fn from_fasta(file: &PathBuf, circular: bool) -> Result<Vec<TargetSeq>> {
+    let fg_io: Io = Io::new(5, BUFFER_SIZE);
+    let circular_contigs = read_circular_contigs(file)?;
 
     let source: FastaReader<Box<dyn BufRead + Send>> =
         FastaReader::with_capacity(fg_io.new_reader(file)?, BUFFER_SIZE);
@@ -121,3 +148,84 @@ pub fn from_fasta(file: &PathBuf, circular: bool) -> Result<Vec<TargetSeq>> {
         })
         .collect()
 }
+
+/// Reads only the given contigs, in the given order, from a FASTA indexed with `samtools faidx`,
+/// without reading the rest of the FASTA.  The FASTA may be BGZF compressed (with the extension
+/// `.gz` or `.bgz`), in which case the `.gzi` index is also required.
+pub fn from_indexed_fasta(
+    file: &PathBuf,
+    names: &[String],
+    circular: bool,
+) -> Result<Vec<TargetSeq>> {
+    let circular_contigs = read_circular_contigs(file)?;
+    let mut reader = IndexedFastaReaderBuilder::default()
+        .build_from_path(file)
+        .with_context(|| format!("Error opening indexed FASTA: {}", file.display()))?;
+    read_indexed_contigs(&mut reader, names, &circular_contigs, circular)
+}
+
+/// Reads the given contigs from the indexed FASTA reader.
+fn read_indexed_contigs<R: BufRead + Seek>(
+    reader: &mut IndexedReader<R>,
+    names: &[String],
+    circular_contigs: &HashMap<String, bool>,
+    circular: bool,
+) -> Result<Vec<TargetSeq>> {
+    ensure!(!names.is_empty(), "No contigs given to read from the FASTA");
+    names
+        .iter()
+        .map(|name| {
+            let record = reader
+                .query(&Region::new(name.as_str(), ..))
+                .with_context(|| format!("Error reading contig from the FASTA: {name}"))?;
+            let sequence = record
+                .sequence()
+                .as_ref()
+                .iter()
+                .map(u8::to_ascii_uppercase)
+                .collect_vec();
+            let contig_is_circular = circular_contigs
+                .get(name)
+                .map_or(circular, |circular| *circular);
+            Ok(TargetSeq::new(name, &sequence, contig_is_circular))
+        })
+        .collect()
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use std::{collections::HashMap, io::Cursor};
+
+    use noodles::fasta::{fai, IndexedReader};
+    use rstest::rstest;
+
+    use super::read_indexed_contigs;
+
+    #[rstest]
+    fn test_read_indexed_contigs() {
+        // two contigs with 4 bases per line
+        let fasta = b">chr1\nACGT\nAC\n>chr2 desc\nttttGG\n";
+        let index = vec![
+            fai::Record::new("chr1", 6, 6, 4, 5),
+            fai::Record::new("chr2", 6, 25, 6, 7),
+        ];
+        let mut reader = IndexedReader::new(Cursor::new(fasta.as_slice()), index);
+        let circular_contigs = HashMap::from([("chr2".to_string(), true)]);
+
+        let names = vec!["chr2".to_string(), "chr1".to_string()];
+        let target_seqs =
+            read_indexed_contigs(&mut reader, &names, &circular_contigs, false).unwrap();
+        assert_eq!(target_seqs.len(), 2);
+        assert_eq!(target_seqs[0].name, "chr2");
+        assert_eq!(target_seqs[0].fwd, b"TTTTGG");
+        assert_eq!(target_seqs[0].revcomp, b"CCAAAA");
+        assert!(target_seqs[0].circular);
+        assert_eq!(target_seqs[1].name, "chr1");
+        assert_eq!(target_seqs[1].fwd, b"ACGTAC");
+        assert!(!target_seqs[1].circular);
+
+        let names = vec!["chr3".to_string()];
+        assert!(read_indexed_contigs(&mut reader, &names, &circular_contigs, false).is_err());
+    }
+}
@@ -1,5 +1,7 @@
+#[cfg(feature = "threads")]
 use lazy_static::lazy_static;
 
+#[cfg(feature = "threads")]
 lazy_static! {
     /// Return the number of cpus as a String
     pub static ref NUM_CPU: String = num_cpus::get().to_string();
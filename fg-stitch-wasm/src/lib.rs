@@ -0,0 +1,333 @@
+//! WebAssembly bindings for the Stitch jump-aware aligner, so that web applications (e.g. plasmid
+//! editors) can align sequences client-side.
+//!
+//! This crate is not a member of the workspace, and is built with
+//! [`wasm-pack`](https://rustwasm.github.io/wasm-pack/):
+//!
+//! ```console
+//! wasm-pack build fg-stitch-wasm --target web
+//! wasm-pack test fg-stitch-wasm --node
+//! ```
+//!
+//! The aligner is then used from JavaScript as:
+//!
+//! ```js
+//! import init, { Aligner, AlignerOptions } from "./pkg/fg_stitch_wasm.js";
+//! await init();
+//! const aligner = new Aligner(new AlignerOptions());
+//! aligner.addContig("pUC19", plasmid, true, true);
+//! aligner.addContig("pUC19", plasmid, false, true);
+//! const alignment = aligner.align(read);
+//! console.log(alignment.score, alignment.cigar, alignment.startContig, alignment.contigStart);
+//! ```
+//!
+//! Invalid arguments are thrown as JavaScript errors.  The core library is built without its
+//! `threads` feature, as threads are not available in `wasm32-unknown-unknown`.
+
+use bio::alignment::pairwise::MatchParams;
+use stitch::{
+    align::{AlignmentMode, Builder, MultiContigAligner, Scoring},
+    util::dna::reverse_complement,
+};
+use wasm_bindgen::prelude::*;
+
+/// The alignment mode, namely which of the query and contigs may be clipped.
+#[wasm_bindgen]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Mode {
+    /// Aligns a sub-sequence of the query versus a sub-sequence of the contigs.
+    Local = 0,
+    /// Aligns a sub-sequence of the query versus the full contigs.
+    QueryLocal = 1,
+    /// Aligns the full query versus a sub-sequence of the contigs.
+    TargetLocal = 2,
+    /// Aligns the full query versus the full contigs.
+    Global = 3,
+}
+
+/// The options for the aligner, with the same meaning and defaults as `stitch align`.
+#[wasm_bindgen(js_name = AlignerOptions)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct AlignerOptions {
+    /// The alignment mode.
+    pub mode: Mode,
+    /// The score for a matching base (should not be negative).
+    #[wasm_bindgen(js_name = matchScore)]
+    pub match_score: i32,
+    /// The score for a mismatching base (should not be positive).
+    #[wasm_bindgen(js_name = mismatchScore)]
+    pub mismatch_score: i32,
+    /// The score for opening a gap (should not be positive).
+    #[wasm_bindgen(js_name = gapOpen)]
+    pub gap_open: i32,
+    /// The score for extending a gap (should not be positive).
+    #[wasm_bindgen(js_name = gapExtend)]
+    pub gap_extend: i32,
+    /// The score for jumping to the same contig and strand (should not be positive).
+    #[wasm_bindgen(js_name = jumpScoreSameContigAndStrand)]
+    pub jump_score_same_contig_and_strand: i32,
+    /// The score for jumping to the same contig and opposite strand (should not be positive).
+    #[wasm_bindgen(js_name = jumpScoreSameContigOppositeStrand)]
+    pub jump_score_same_contig_opposite_strand: i32,
+    /// The score for jumping to a different contig (should not be positive).
+    #[wasm_bindgen(js_name = jumpScoreInterContig)]
+    pub jump_score_inter_contig: i32,
+}
+
+impl Default for AlignerOptions {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Local,
+            match_score: 1,
+            mismatch_score: -4,
+            gap_open: -6,
+            gap_extend: -2,
+            jump_score_same_contig_and_strand: -10,
+            jump_score_same_contig_opposite_strand: -10,
+            jump_score_inter_contig: -10,
+        }
+    }
+}
+
+#[wasm_bindgen(js_class = AlignerOptions)]
+impl AlignerOptions {
+    /// Creates the default options.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AlignerOptions {
+    /// Returns an error if any score has the wrong sign.  The scores are validated here rather
+    /// than by the core library, since panics abort in WASM.
+    fn validate(&self) -> Result<(), String> {
+        let non_positive = [
+            ("mismatchScore", self.mismatch_score),
+            ("gapOpen", self.gap_open),
+            ("gapExtend", self.gap_extend),
+            (
+                "jumpScoreSameContigAndStrand",
+                self.jump_score_same_contig_and_strand,
+            ),
+            (
+                "jumpScoreSameContigOppositeStrand",
+                self.jump_score_same_contig_opposite_strand,
+            ),
+            ("jumpScoreInterContig", self.jump_score_inter_contig),
+        ];
+        if self.match_score < 0 {
+            return Err("matchScore can't be negative".to_string());
+        }
+        match non_positive.iter().find(|(_, score)| *score > 0) {
+            Some((name, _)) => Err(format!("{name} can't be positive")),
+            None => Ok(()),
+        }
+    }
+
+    /// Builds the scoring of the jump-aware aligner.
+    fn scoring(&self) -> Scoring<MatchParams> {
+        let mode = match self.mode {
+            Mode::Local => AlignmentMode::Local,
+            Mode::QueryLocal => AlignmentMode::QueryLocal,
+            Mode::TargetLocal => AlignmentMode::TargetLocal,
+            Mode::Global => AlignmentMode::Global,
+        };
+        Builder::default()
+            .mode(mode)
+            .match_score(self.match_score)
+            .mismatch_score(self.mismatch_score)
+            .gap_open(self.gap_open)
+            .gap_extend(self.gap_extend)
+            .jump_score_same_contig_and_strand(Some(self.jump_score_same_contig_and_strand))
+            .jump_score_same_contig_opposite_strand(Some(
+                self.jump_score_same_contig_opposite_strand,
+            ))
+            .jump_score_inter_contig(Some(self.jump_score_inter_contig))
+            .build_scoring()
+    }
+}
+
+/// The alignment of a query.
+///
+/// Contig coordinates are 0-based on the strand of the contig, with the start on the first
+/// aligned contig and the end on the last aligned contig (these differ if the alignment jumps
+/// between contigs).  If the query did not align, the CIGAR is empty.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Alignment {
+    /// The alignment score.
+    pub score: i32,
+    /// The 0-based start of the alignment in the query.
+    #[wasm_bindgen(js_name = queryStart)]
+    pub query_start: usize,
+    /// The 0-based exclusive end of the alignment in the query.
+    #[wasm_bindgen(js_name = queryEnd)]
+    pub query_end: usize,
+    /// The name of the first aligned contig.
+    #[wasm_bindgen(js_name = startContig)]
+    pub start_contig: String,
+    /// True if the first aligned contig is the forward strand.
+    #[wasm_bindgen(js_name = startIsForward)]
+    pub start_is_forward: bool,
+    /// The 0-based start of the alignment in the first aligned contig.
+    #[wasm_bindgen(js_name = contigStart)]
+    pub contig_start: usize,
+    /// The name of the last aligned contig.
+    #[wasm_bindgen(js_name = endContig)]
+    pub end_contig: String,
+    /// True if the last aligned contig is the forward strand.
+    #[wasm_bindgen(js_name = endIsForward)]
+    pub end_is_forward: bool,
+    /// The 0-based exclusive end of the alignment in the last aligned contig.
+    #[wasm_bindgen(js_name = contigEnd)]
+    pub contig_end: usize,
+    /// The CIGAR of the alignment, including clipping and jumps.
+    pub cigar: String,
+}
+
+/// A jump-aware aligner of queries to one or more contigs.
+#[wasm_bindgen]
+pub struct Aligner {
+    aligner: MultiContigAligner<MatchParams>,
+    scoring: Scoring<MatchParams>,
+    /// The name and strand of each contig, by contig index.
+    contigs: Vec<(String, bool)>,
+}
+
+#[wasm_bindgen]
+impl Aligner {
+    /// Creates a new aligner with the given options, or the default options if none are given.
+    #[wasm_bindgen(constructor)]
+    pub fn new(options: Option<AlignerOptions>) -> Result<Aligner, JsError> {
+        let options = options.unwrap_or_default();
+        options.validate().map_err(|e| JsError::new(&e))?;
+        Ok(Self {
+            aligner: MultiContigAligner::new(),
+            scoring: options.scoring(),
+            contigs: Vec::new(),
+        })
+    }
+
+    /// Adds a strand of a contig to the aligner, returning the index of the contig.
+    ///
+    /// The sequence is always given for the forward strand of the contig; if `isForward` is
+    /// false, the aligner aligns to its reverse complement.  To align to both strands, add the
+    /// contig twice.
+    #[wasm_bindgen(js_name = addContig)]
+    pub fn add_contig(
+        &mut self,
+        name: &str,
+        seq: &str,
+        is_forward: bool,
+        circular: bool,
+    ) -> Result<usize, JsError> {
+        if self
+            .aligner
+            .contig_index_for_strand(is_forward, name)
+            .is_some()
+        {
+            let strand = if is_forward { "forward" } else { "reverse" };
+            return Err(JsError::new(&format!(
+                "the {strand} strand of contig {name} was already added"
+            )));
+        }
+        let seq = seq.as_bytes().to_ascii_uppercase();
+        let seq = if is_forward {
+            seq
+        } else {
+            reverse_complement(&seq)
+        };
+        self.aligner
+            .add_contig(name, is_forward, &seq, circular, self.scoring);
+        self.contigs.push((name.to_string(), is_forward));
+        Ok(self.contigs.len() - 1)
+    }
+
+    /// Aligns the query to the contigs added to the aligner, returning the best alignment.
+    pub fn align(&mut self, query: &str) -> Result<Alignment, JsError> {
+        if self.aligner.is_empty() {
+            return Err(JsError::new("no contigs were added to the aligner"));
+        }
+        let query = query.as_bytes().to_ascii_uppercase();
+        let alignment = self.aligner.custom(&query);
+        let (start_contig, start_is_forward) = self.contigs[alignment.start_contig_idx].clone();
+        let (end_contig, end_is_forward) = self.contigs[alignment.end_contig_idx].clone();
+        Ok(Alignment {
+            score: alignment.score,
+            query_start: alignment.ystart,
+            query_end: alignment.yend,
+            start_contig,
+            start_is_forward,
+            contig_start: alignment.xstart,
+            end_contig,
+            end_is_forward,
+            contig_end: alignment.xend,
+            cigar: alignment.cigar(),
+        })
+    }
+}
+
+// Tests
+#[cfg(test)]
+pub mod tests {
+    use stitch::util::dna::reverse_complement;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{Aligner, AlignerOptions, Mode};
+
+    const CHR1: &str = "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACT";
+    const CHR2: &str = "TGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGT";
+
+    #[wasm_bindgen_test]
+    fn test_align() {
+        let mut aligner = Aligner::new(None).unwrap();
+        for (name, seq) in [("chr1", CHR1), ("chr2", CHR2)] {
+            aligner.add_contig(name, seq, true, false).unwrap();
+            aligner.add_contig(name, seq, false, false).unwrap();
+        }
+
+        // chr1:0-20 then the reverse strand of chr2:20-40
+        let query = [
+            &CHR1.as_bytes()[..20],
+            &reverse_complement(&CHR2.as_bytes()[20..]),
+        ]
+        .concat();
+        let alignment = aligner
+            .align(&String::from_utf8(query).unwrap().to_lowercase())
+            .unwrap();
+        assert_eq!(alignment.score, 30);
+        assert_eq!((alignment.query_start, alignment.query_end), (0, 40));
+        assert_eq!(alignment.start_contig, "chr1");
+        assert!(alignment.start_is_forward);
+        assert_eq!(alignment.contig_start, 0);
+        assert_eq!(alignment.end_contig, "chr2");
+        assert!(!alignment.end_is_forward);
+        assert_eq!(alignment.contig_end, 20);
+        assert_eq!(alignment.cigar, "20=3C20j20=20A");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_options() {
+        let mut options = AlignerOptions::new();
+        assert!(options.validate().is_ok());
+        options.gap_open = 1;
+        assert_eq!(
+            options.validate(),
+            Err("gapOpen can't be positive".to_string())
+        );
+        options.gap_open = -6;
+        options.match_score = -1;
+        assert_eq!(
+            options.validate(),
+            Err("matchScore can't be negative".to_string())
+        );
+        options.match_score = 1;
+
+        options.mode = Mode::Global;
+        let mut aligner = Aligner::new(Some(options)).unwrap();
+        aligner.add_contig("chr1", CHR1, true, false).unwrap();
+        let alignment = aligner.align(&CHR1[10..30]).unwrap();
+        assert_eq!(alignment.cigar, "10J20=10J");
+    }
+}